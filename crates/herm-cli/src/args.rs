@@ -0,0 +1,415 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use herm::storage::CleanupPolicy;
+
+/// Broker the commands bootstrap from unless `--bootstrap-server` is given.
+pub const DEFAULT_BOOTSTRAP: &str = "127.0.0.1:9092";
+
+pub const USAGE: &str = "\
+Usage: herm-cli [--bootstrap-server <addr>] <command>
+
+Commands:
+  produce <topic> [--partition <n>] [--key-separator <sep>]
+      Sends each line of stdin as a record
+  consume <topic> [--partition <n>] [--group <group>] [--from-beginning]
+          [--max-messages <n>] [--print-key]
+      Prints records as they arrive, from the end unless told otherwise
+  topics create <topic> [--partitions <n>]
+  topics list
+  topics delete <topic>
+  topics estimate-cleanup <topic> [--policy delete|compact]
+                          [--retention-ms <n>] [--retention-bytes <n>]
+      Prints what a cleanup would reclaim from each partition without
+      cleaning up, with the topic's config or the given overrides; a
+      negative retention means no limit
+  offsets <topic> [--time earliest|latest|<ms>]
+      Prints topic:partition:offset for each partition
+  mirror <topic>[,<topic>] --target-server <addr> [--prefix <prefix>]
+         [--checkpoint <file>]
+      Copies topics from the bootstrap cluster into the target, resuming
+      from the checkpoint file if given
+  dump-log <file> [--print-values]
+      Prints the batches of a segment, or the entries of an index, without
+      a broker";
+
+#[derive(Error, Debug, PartialEq)]
+pub enum UsageError {
+    #[error("Missing command")]
+    MissingCommand,
+    #[error("Unknown command {0}")]
+    UnknownCommand(String),
+    #[error("Missing {0}")]
+    Missing(&'static str),
+    #[error("Unknown option {0}")]
+    UnknownOption(String),
+    #[error("Option {0} needs a value")]
+    MissingValue(String),
+    #[error("Invalid value {value} for {option}")]
+    InvalidValue { option: String, value: String },
+    #[error("Unexpected argument {0}")]
+    Unexpected(String),
+}
+
+/// Which offset of each partition `offsets` looks up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OffsetTime {
+    Earliest,
+    Latest,
+    /// Milliseconds since the unix epoch.
+    Timestamp(u64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Produce {
+        topic: String,
+        /// Picked per record by key hash when `None`.
+        partition: Option<u32>,
+        /// Splits each line into a key and a value at its first occurrence.
+        key_separator: Option<String>,
+    },
+    Consume {
+        topic: String,
+        /// Every partition of the topic when `None`.
+        partition: Option<u32>,
+        group: Option<String>,
+        from_beginning: bool,
+        max_messages: Option<usize>,
+        print_key: bool,
+    },
+    CreateTopic {
+        topic: String,
+        partitions: u32,
+    },
+    ListTopics,
+    DeleteTopic {
+        topic: String,
+    },
+    /// Overrides left `None` are as the topic has them.
+    EstimateCleanup {
+        topic: String,
+        policy: Option<CleanupPolicy>,
+        retention_ms: Option<i64>,
+        retention_bytes: Option<i64>,
+    },
+    Offsets {
+        topic: String,
+        time: OffsetTime,
+    },
+    Mirror {
+        topics: Vec<String>,
+        target: Vec<String>,
+        /// Prepended to topic names in the target.
+        prefix: String,
+        checkpoint: Option<PathBuf>,
+    },
+    /// Reads a segment or index file directly, so needs no broker.
+    DumpLog {
+        path: PathBuf,
+        print_values: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Args {
+    pub bootstrap: Vec<String>,
+    pub command: Command,
+}
+
+impl Args {
+    /// Parses the arguments after the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, UsageError> {
+        let mut args = Parser::new(args);
+        let bootstrap = match args.option("--bootstrap-server")? {
+            Some(servers) => servers.split(',').map(str::to_string).collect(),
+            None => vec![DEFAULT_BOOTSTRAP.to_string()],
+        };
+
+        let command = match args
+            .positional()
+            .ok_or(UsageError::MissingCommand)?
+            .as_str()
+        {
+            // Options go first so their values aren't taken for the topic
+            "produce" => Command::Produce {
+                partition: args.parsed("--partition")?,
+                key_separator: args.option("--key-separator")?,
+                topic: args.required("topic")?,
+            },
+            "consume" => Command::Consume {
+                partition: args.parsed("--partition")?,
+                group: args.option("--group")?,
+                from_beginning: args.flag("--from-beginning"),
+                max_messages: args.parsed("--max-messages")?,
+                print_key: args.flag("--print-key"),
+                topic: args.required("topic")?,
+            },
+            "topics" => match args.required("topics command")?.as_str() {
+                "create" => Command::CreateTopic {
+                    partitions: args.parsed("--partitions")?.unwrap_or(1),
+                    topic: args.required("topic")?,
+                },
+                "list" => Command::ListTopics,
+                "delete" => Command::DeleteTopic {
+                    topic: args.required("topic")?,
+                },
+                "estimate-cleanup" => Command::EstimateCleanup {
+                    policy: match args.option("--policy")?.as_deref() {
+                        None => None,
+                        Some("delete") => Some(CleanupPolicy::Delete),
+                        Some("compact") => Some(CleanupPolicy::Compact),
+                        Some(policy) => {
+                            return Err(UsageError::InvalidValue {
+                                option: "--policy".to_string(),
+                                value: policy.to_string(),
+                            })
+                        }
+                    },
+                    retention_ms: args.parsed("--retention-ms")?,
+                    retention_bytes: args.parsed("--retention-bytes")?,
+                    topic: args.required("topic")?,
+                },
+                other => return Err(UsageError::UnknownCommand(format!("topics {other}"))),
+            },
+            "offsets" => Command::Offsets {
+                time: match args.option("--time")?.as_deref() {
+                    None | Some("latest") => OffsetTime::Latest,
+                    Some("earliest") => OffsetTime::Earliest,
+                    Some(time) => OffsetTime::Timestamp(time.parse().map_err(|_| {
+                        UsageError::InvalidValue {
+                            option: "--time".to_string(),
+                            value: time.to_string(),
+                        }
+                    })?),
+                },
+                topic: args.required("topic")?,
+            },
+            "mirror" => Command::Mirror {
+                target: args
+                    .option("--target-server")?
+                    .ok_or(UsageError::Missing("--target-server"))?
+                    .split(',')
+                    .map(str::to_string)
+                    .collect(),
+                prefix: args.option("--prefix")?.unwrap_or_default(),
+                checkpoint: args.option("--checkpoint")?.map(PathBuf::from),
+                topics: args
+                    .required("topic")?
+                    .split(',')
+                    .map(str::to_string)
+                    .collect(),
+            },
+            "dump-log" => Command::DumpLog {
+                print_values: args.flag("--print-values"),
+                path: args.required("file")?.into(),
+            },
+            other => return Err(UsageError::UnknownCommand(other.to_string())),
+        };
+        args.finish()?;
+
+        Ok(Args { bootstrap, command })
+    }
+}
+
+/// Hands out positional arguments in order while options and flags are
+/// taken from anywhere.
+struct Parser {
+    args: Vec<Option<String>>,
+}
+
+impl Parser {
+    fn new(args: impl IntoIterator<Item = String>) -> Self {
+        Parser {
+            args: args.into_iter().map(Some).collect(),
+        }
+    }
+
+    fn flag(&mut self, name: &str) -> bool {
+        match self
+            .args
+            .iter()
+            .position(|arg| arg.as_deref() == Some(name))
+        {
+            Some(i) => {
+                self.args[i] = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn option(&mut self, name: &str) -> Result<Option<String>, UsageError> {
+        let Some(i) = self
+            .args
+            .iter()
+            .position(|arg| arg.as_deref() == Some(name))
+        else {
+            return Ok(None);
+        };
+        self.args[i] = None;
+        match self.args.get_mut(i + 1).and_then(Option::take) {
+            Some(value) => Ok(Some(value)),
+            None => Err(UsageError::MissingValue(name.to_string())),
+        }
+    }
+
+    fn parsed<T: std::str::FromStr>(&mut self, name: &str) -> Result<Option<T>, UsageError> {
+        self.option(name)?
+            .map(|value| {
+                value.parse().map_err(|_| UsageError::InvalidValue {
+                    option: name.to_string(),
+                    value,
+                })
+            })
+            .transpose()
+    }
+
+    /// The next argument that isn't an option.
+    fn positional(&mut self) -> Option<String> {
+        self.args
+            .iter_mut()
+            .find(|arg| matches!(arg, Some(arg) if !arg.starts_with("--")))
+            .and_then(Option::take)
+    }
+
+    fn required(&mut self, name: &'static str) -> Result<String, UsageError> {
+        self.positional().ok_or(UsageError::Missing(name))
+    }
+
+    /// Fails on the first argument left over.
+    fn finish(self) -> Result<(), UsageError> {
+        match self.args.into_iter().flatten().next() {
+            Some(arg) if arg.starts_with("--") => Err(UsageError::UnknownOption(arg)),
+            Some(arg) => Err(UsageError::Unexpected(arg)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Args, UsageError> {
+        Args::parse(args.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn test_parse() {
+        let args = parse("consume events --from-beginning --bootstrap-server a:1,b:2").unwrap();
+        assert_eq!(args.bootstrap, vec!["a:1", "b:2"]);
+        assert_eq!(
+            args.command,
+            Command::Consume {
+                topic: "events".to_string(),
+                partition: None,
+                group: None,
+                from_beginning: true,
+                max_messages: None,
+                print_key: false,
+            }
+        );
+
+        let args = parse("produce --partition 2 events").unwrap();
+        assert_eq!(args.bootstrap, vec![DEFAULT_BOOTSTRAP]);
+        assert_eq!(
+            args.command,
+            Command::Produce {
+                topic: "events".to_string(),
+                partition: Some(2),
+                key_separator: None,
+            }
+        );
+
+        assert_eq!(
+            parse("topics create events --partitions 3")
+                .unwrap()
+                .command,
+            Command::CreateTopic {
+                topic: "events".to_string(),
+                partitions: 3,
+            }
+        );
+        assert_eq!(parse("topics list").unwrap().command, Command::ListTopics);
+        assert_eq!(
+            parse("topics estimate-cleanup events --policy compact --retention-bytes -1")
+                .unwrap()
+                .command,
+            Command::EstimateCleanup {
+                topic: "events".to_string(),
+                policy: Some(CleanupPolicy::Compact),
+                retention_ms: None,
+                retention_bytes: Some(-1),
+            }
+        );
+        assert_eq!(
+            parse("offsets events --time earliest").unwrap().command,
+            Command::Offsets {
+                topic: "events".to_string(),
+                time: OffsetTime::Earliest,
+            }
+        );
+        assert_eq!(
+            parse("dump-log --print-values data/events-0/00000000000000000000.log")
+                .unwrap()
+                .command,
+            Command::DumpLog {
+                path: "data/events-0/00000000000000000000.log".into(),
+                print_values: true,
+            }
+        );
+        assert_eq!(
+            parse("mirror events,orders --target-server dr:9092 --prefix dc1.")
+                .unwrap()
+                .command,
+            Command::Mirror {
+                topics: vec!["events".to_string(), "orders".to_string()],
+                target: vec!["dr:9092".to_string()],
+                prefix: "dc1.".to_string(),
+                checkpoint: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        assert_eq!(parse(""), Err(UsageError::MissingCommand));
+        assert_eq!(
+            parse("topics drop events"),
+            Err(UsageError::UnknownCommand("topics drop".to_string()))
+        );
+        assert_eq!(parse("produce"), Err(UsageError::Missing("topic")));
+        assert_eq!(
+            parse("mirror events"),
+            Err(UsageError::Missing("--target-server"))
+        );
+        assert_eq!(
+            parse("consume events --partition"),
+            Err(UsageError::MissingValue("--partition".to_string()))
+        );
+        assert_eq!(
+            parse("offsets events --time yesterday"),
+            Err(UsageError::InvalidValue {
+                option: "--time".to_string(),
+                value: "yesterday".to_string(),
+            })
+        );
+        assert_eq!(
+            parse("topics estimate-cleanup events --policy shred"),
+            Err(UsageError::InvalidValue {
+                option: "--policy".to_string(),
+                value: "shred".to_string(),
+            })
+        );
+        assert_eq!(
+            parse("topics list --verbose"),
+            Err(UsageError::UnknownOption("--verbose".to_string()))
+        );
+        assert_eq!(
+            parse("topics list extra"),
+            Err(UsageError::Unexpected("extra".to_string()))
+        );
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use bytes::Bytes;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use herm::client::{
+    Client, Consumer, ConsumerConfig, KeyHashPartitioner, Producer, ProducerConfig,
+};
+use herm::protocol::TopicPartition;
+use herm::record::Record;
+use herm::request::{CreateTopic, DeleteTopic, EstimateCleanup, ListOffsets, Metadata};
+use herm::response::PartitionCleanupEstimate;
+use herm::storage::{dump_file, Dump};
+
+use crate::args::OffsetTime;
+
+/// Sends each line of `input` to `topic`, split into a key and a value at
+/// the first `key_separator` if one is given. Returns how many were sent.
+pub async fn produce(
+    client: Arc<Client>,
+    topic: &str,
+    partition: Option<u32>,
+    key_separator: Option<&str>,
+    input: impl AsyncBufRead + Unpin,
+) -> anyhow::Result<usize> {
+    let producer = Producer::new(client, ProducerConfig::default())
+        .with_partitioner(KeyHashPartitioner::new());
+    let mut deliveries = vec![];
+    let mut lines = input.lines();
+    while let Some(line) = lines.next_line().await? {
+        let (key, value) = match key_separator.and_then(|sep| line.split_once(sep)) {
+            Some((key, value)) => (Some(key.to_string()), value.to_string()),
+            None => (None, line),
+        };
+        let record = Record::new(key.map(Bytes::from), Some(Bytes::from(value)));
+        let delivery = match partition {
+            Some(partition) => producer.send(topic, partition, record)?,
+            None => producer.send_to_topic(topic, record).await?,
+        };
+        deliveries.push(delivery);
+    }
+
+    producer.flush().await;
+    let sent = deliveries.len();
+    for delivery in deliveries {
+        delivery.await?;
+    }
+    Ok(sent)
+}
+
+/// How [`consume`] reads and prints records.
+#[derive(Debug, Clone, Default)]
+pub struct ConsumeOptions {
+    /// Every partition of the topic when `None`.
+    pub partition: Option<u32>,
+    /// Starts from the group's committed offsets and commits as it goes.
+    pub group: Option<String>,
+    pub from_beginning: bool,
+    /// Runs until stopped when `None`.
+    pub max_messages: Option<usize>,
+    pub print_key: bool,
+}
+
+/// Writes the value of each record of `topic` on a line of `out`, the
+/// key first and a tab if asked. Starts from the end of each partition
+/// unless reading from the beginning or as a group. Returns how many were
+/// printed.
+pub async fn consume(
+    client: Arc<Client>,
+    topic: &str,
+    options: ConsumeOptions,
+    out: &mut impl Write,
+) -> anyhow::Result<usize> {
+    let config = ConsumerConfig {
+        group: options.group.clone(),
+        ..Default::default()
+    };
+    let mut consumer = Consumer::new(client, config);
+    match options.partition {
+        Some(partition) => {
+            consumer
+                .assign(vec![TopicPartition::new(topic, partition)])
+                .await?
+        }
+        None => consumer.subscribe(topic).await?,
+    }
+    if options.from_beginning {
+        consumer.seek_to_beginning().await?;
+    } else if options.group.is_none() {
+        consumer.seek_to_end().await?;
+    }
+
+    let mut printed = 0;
+    while options.max_messages.is_none_or(|max| printed < max) {
+        let records = consumer.poll().await?;
+        let left = options.max_messages.map_or(usize::MAX, |max| max - printed);
+        for consumed in records.iter().take(left) {
+            let record = &consumed.record;
+            if options.print_key {
+                out.write_all(record.key.as_deref().unwrap_or_default())?;
+                out.write_all(b"\t")?;
+            }
+            out.write_all(record.value.as_deref().unwrap_or_default())?;
+            out.write_all(b"\n")?;
+            printed += 1;
+        }
+        out.flush()?;
+    }
+    if options.group.is_some() {
+        consumer.commit().await?;
+    }
+    Ok(printed)
+}
+
+pub async fn create_topic(client: &Client, topic: &str, partitions: u32) -> anyhow::Result<()> {
+    let response = client
+        .create_topic(CreateTopic::new(topic.to_string(), partitions)?)
+        .await?;
+    if !response.error.is_ok() {
+        bail!("Failed to create {topic}: {}", response.error);
+    }
+    Ok(())
+}
+
+/// Names of the topics with a live leader, sorted.
+pub async fn list_topics(client: &Client) -> anyhow::Result<Vec<String>> {
+    let response = client.refresh_metadata().await?;
+    if !response.error.is_ok() {
+        bail!("Failed to list topics: {}", response.error);
+    }
+    let topics: BTreeSet<_> = response
+        .topics
+        .into_iter()
+        .map(|metadata| metadata.topic)
+        .collect();
+    Ok(topics.into_iter().collect())
+}
+
+/// Deletes the topic from every broker leading one of its partitions.
+pub async fn delete_topic(client: &Client, topic: &str) -> anyhow::Result<()> {
+    let request = DeleteTopic::new(topic.to_string())?;
+    let response = client
+        .metadata(Metadata::new(vec![topic.to_string()])?)
+        .await?;
+    let leaders: BTreeSet<_> = response
+        .topics
+        .iter()
+        .filter(|metadata| metadata.error.is_ok())
+        .flat_map(|metadata| &metadata.partitions)
+        .map(|partition| partition.leader_id)
+        .collect();
+    if leaders.is_empty() {
+        bail!("Unknown topic {topic}");
+    }
+
+    for broker_id in leaders {
+        let response = client
+            .delete_topic(broker_id, request.clone())
+            .await
+            .with_context(|| format!("Failed to reach broker {broker_id}"))?;
+        if !response.error.is_ok() {
+            bail!(
+                "Failed to delete {topic} on broker {broker_id}: {}",
+                response.error
+            );
+        }
+    }
+    Ok(())
+}
+
+/// What a cleanup would reclaim from each partition of the topic, asking
+/// the leader of each. Sorted by partition.
+pub async fn estimate_cleanup(
+    client: &Client,
+    request: EstimateCleanup,
+) -> anyhow::Result<Vec<PartitionCleanupEstimate>> {
+    let topic = request.topic().to_string();
+    let response = client.metadata(Metadata::new(vec![topic.clone()])?).await?;
+    let leaders: BTreeMap<_, _> = response
+        .topics
+        .iter()
+        .filter(|metadata| metadata.error.is_ok())
+        .flat_map(|metadata| &metadata.partitions)
+        .map(|partition| (partition.partition, partition.leader_id))
+        .collect();
+    if leaders.is_empty() {
+        bail!("Unknown topic {topic}");
+    }
+
+    let mut estimates = vec![];
+    for broker_id in leaders.values().collect::<BTreeSet<_>>() {
+        let response = client
+            .estimate_cleanup(*broker_id, request.clone())
+            .await
+            .with_context(|| format!("Failed to reach broker {broker_id}"))?;
+        if !response.error.is_ok() {
+            bail!(
+                "Failed to estimate the cleanup of {topic} on broker {broker_id}: {}",
+                response.error
+            );
+        }
+        // Followers hold partitions too, only count the leader's copy
+        estimates.extend(
+            response
+                .partitions
+                .into_iter()
+                .filter(|estimate| leaders.get(&estimate.partition) == Some(broker_id)),
+        );
+    }
+    estimates.sort_by_key(|estimate| estimate.partition);
+    Ok(estimates)
+}
+
+/// The offset of each partition of `topic` at `time`, leaving out those
+/// with no record as recent.
+pub async fn offsets(
+    client: &Client,
+    topic: &str,
+    time: OffsetTime,
+) -> anyhow::Result<Vec<(TopicPartition, u64)>> {
+    let Some(partitions) = client.partition_count(topic).await? else {
+        bail!("Unknown topic {topic}");
+    };
+
+    let mut offsets = vec![];
+    for partition in 0..partitions {
+        let name = topic.to_string();
+        let request = match time {
+            OffsetTime::Earliest => ListOffsets::earliest(name, partition)?,
+            OffsetTime::Latest => ListOffsets::latest(name, partition)?,
+            OffsetTime::Timestamp(timestamp) => ListOffsets::new(name, partition, timestamp)?,
+        };
+        let response = client.list_offsets(request).await?;
+        if !response.error.is_ok() {
+            bail!(
+                "Failed to list offsets of partition {partition}: {}",
+                response.error
+            );
+        }
+        if let Some(offset) = response.found() {
+            offsets.push((TopicPartition::new(topic, partition), offset));
+        }
+    }
+    Ok(offsets)
+}
+
+/// Writes what [`dump_file`] finds in `path` to `out`: a line per batch
+/// of a segment followed by a line per record, or a line per index entry.
+pub fn dump_log(path: &Path, print_values: bool, out: &mut impl Write) -> anyhow::Result<()> {
+    let dump = dump_file(path).with_context(|| format!("Failed to dump {}", path.display()))?;
+    let torn_bytes = match dump {
+        Dump::Segment(segment) => {
+            writeln!(out, "Segment with base offset {}", segment.base_offset)?;
+            for entry in segment.entries {
+                write!(
+                    out,
+                    "position: {} size: {} crc_valid: {}",
+                    entry.position, entry.size, entry.crc_valid
+                )?;
+                let batch = match entry.batch {
+                    Ok(batch) => batch,
+                    Err(err) => {
+                        writeln!(out, " error: {err}")?;
+                        continue;
+                    }
+                };
+                writeln!(
+                    out,
+                    " base_offset: {} last_offset: {} count: {} first_timestamp: {} max_timestamp: {}",
+                    batch.base_offset,
+                    batch.last_offset(),
+                    batch.records.len(),
+                    batch.first_timestamp(),
+                    batch.max_timestamp()
+                )?;
+                for record in &batch.records {
+                    write!(
+                        out,
+                        "| offset: {} timestamp: {} key: {}",
+                        record.offset,
+                        record.timestamp,
+                        display_bytes(record.key.as_deref())
+                    )?;
+                    if print_values {
+                        write!(out, " value: {}", display_bytes(record.value.as_deref()))?;
+                    }
+                    writeln!(out)?;
+                }
+            }
+            segment.torn_bytes
+        }
+        Dump::OffsetIndex(index) => {
+            writeln!(out, "Offset index with base offset {}", index.base_offset)?;
+            for (offset, position) in index.entries {
+                writeln!(out, "offset: {offset} position: {position}")?;
+            }
+            index.torn_bytes
+        }
+        Dump::TimeIndex(index) => {
+            writeln!(out, "Time index with base offset {}", index.base_offset)?;
+            for (timestamp, position) in index.entries {
+                writeln!(out, "timestamp: {timestamp} position: {position}")?;
+            }
+            index.torn_bytes
+        }
+    };
+    if torn_bytes > 0 {
+        writeln!(out, "Torn bytes at the end: {torn_bytes}")?;
+    }
+    Ok(())
+}
+
+fn display_bytes(bytes: Option<&[u8]>) -> std::borrow::Cow<'_, str> {
+    match bytes {
+        Some(bytes) => String::from_utf8_lossy(bytes),
+        None => "null".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use herm::record::RecordBatch;
+    use herm::storage::{Log, LogConfig};
+    use herm_test::EmbeddedBroker;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_produce_and_consume() {
+        let broker = EmbeddedBroker::start_in_memory().await.unwrap();
+        let client = Arc::new(broker.client());
+        create_topic(&client, "events", 2).await.unwrap();
+        assert_eq!(list_topics(&client).await.unwrap(), vec!["events"]);
+
+        let input: &[u8] = b"a=1\nb=2\nc=3\n";
+        let sent = produce(client.clone(), "events", Some(1), Some("="), input)
+            .await
+            .unwrap();
+        assert_eq!(sent, 3);
+        assert_eq!(
+            offsets(&client, "events", OffsetTime::Latest)
+                .await
+                .unwrap(),
+            vec![
+                (TopicPartition::new("events", 0), 0),
+                (TopicPartition::new("events", 1), 3)
+            ]
+        );
+
+        let mut out = vec![];
+        let options = ConsumeOptions {
+            from_beginning: true,
+            max_messages: Some(2),
+            print_key: true,
+            ..Default::default()
+        };
+        let consumed = consume(client.clone(), "events", options, &mut out)
+            .await
+            .unwrap();
+        assert_eq!(consumed, 2);
+        assert_eq!(String::from_utf8(out).unwrap(), "a\t1\nb\t2\n");
+    }
+
+    #[tokio::test]
+    async fn test_topic_admin() {
+        let broker = EmbeddedBroker::start_in_memory().await.unwrap();
+        let client = broker.client();
+        create_topic(&client, "events", 1).await.unwrap();
+        assert!(create_topic(&client, "events", 1).await.is_err());
+
+        delete_topic(&client, "events").await.unwrap();
+        assert!(broker.handler().logs().partitions().is_empty());
+        assert!(delete_topic(&client, "events").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_estimate_cleanup() {
+        let broker = EmbeddedBroker::start_in_memory().await.unwrap();
+        let client = Arc::new(broker.client());
+        create_topic(&client, "events", 2).await.unwrap();
+        produce(client.clone(), "events", Some(1), None, &b"a\nb\n"[..])
+            .await
+            .unwrap();
+
+        let request = EstimateCleanup::new("events".to_string()).unwrap();
+        let estimates = estimate_cleanup(&client, request.clone()).await.unwrap();
+        assert_eq!(
+            estimates
+                .iter()
+                .map(|estimate| (estimate.partition, estimate.reclaimable_bytes))
+                .collect::<Vec<_>>(),
+            vec![(0, 0), (1, 0)]
+        );
+        assert!(estimates[1].size > 0);
+
+        let missing = EstimateCleanup::new("missing".to_string()).unwrap();
+        assert!(estimate_cleanup(&client, missing).await.is_err());
+    }
+
+    #[test]
+    fn test_dump_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        let record = |key: &'static str, value: &'static str| {
+            Record::new(Some(Bytes::from(key)), Some(Bytes::from(value))).with_timestamp(1000)
+        };
+        log.append(RecordBatch::new(vec![record("a", "1"), record("b", "2")]))
+            .unwrap();
+        log.flush().unwrap();
+
+        let mut out = vec![];
+        let segment = dir.path().join("00000000000000000000.log");
+        dump_log(&segment, true, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines[0], "Segment with base offset 0");
+        assert!(lines[1].starts_with("position: 0 "));
+        assert!(lines[1].contains("crc_valid: true base_offset: 0 last_offset: 1 count: 2"));
+        assert_eq!(lines[2], "| offset: 0 timestamp: 1000 key: a value: 1");
+        assert_eq!(lines[3], "| offset: 1 timestamp: 1000 key: b value: 2");
+
+        assert!(dump_log(&dir.path().join("missing.log"), false, &mut vec![]).is_err());
+    }
+}
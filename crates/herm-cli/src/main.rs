@@ -0,0 +1,151 @@
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use tokio::io::BufReader;
+
+use herm::client::Client;
+use herm::mirror::{CheckpointStore, ClusterTarget, Mirror, MirrorConfig};
+use herm::request::EstimateCleanup;
+
+use args::{Args, Command, USAGE};
+use commands::ConsumeOptions;
+
+mod args;
+mod commands;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match run(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(args: Args) -> anyhow::Result<()> {
+    if let Command::DumpLog { path, print_values } = &args.command {
+        return commands::dump_log(path, *print_values, &mut std::io::stdout().lock());
+    }
+
+    let client = Arc::new(Client::new(args.bootstrap).with_client_id("herm-cli"));
+    match args.command {
+        Command::Produce {
+            topic,
+            partition,
+            key_separator,
+        } => {
+            let stdin = BufReader::new(tokio::io::stdin());
+            let sent =
+                commands::produce(client, &topic, partition, key_separator.as_deref(), stdin)
+                    .await?;
+            eprintln!("Sent {sent} records");
+        }
+        Command::Consume {
+            topic,
+            partition,
+            group,
+            from_beginning,
+            max_messages,
+            print_key,
+        } => {
+            let options = ConsumeOptions {
+                partition,
+                group,
+                from_beginning,
+                max_messages,
+                print_key,
+            };
+            let mut stdout = std::io::stdout().lock();
+            let consumed = tokio::select! {
+                consumed = commands::consume(client, &topic, options, &mut stdout) => consumed?,
+                _ = tokio::signal::ctrl_c() => return Ok(()),
+            };
+            eprintln!("Consumed {consumed} records");
+        }
+        Command::CreateTopic { topic, partitions } => {
+            commands::create_topic(&client, &topic, partitions).await?;
+            println!("Created {topic} with {partitions} partitions");
+        }
+        Command::ListTopics => {
+            for topic in commands::list_topics(&client).await? {
+                println!("{topic}");
+            }
+        }
+        Command::DeleteTopic { topic } => {
+            commands::delete_topic(&client, &topic).await?;
+            println!("Deleted {topic}");
+        }
+        Command::EstimateCleanup {
+            topic,
+            policy,
+            retention_ms,
+            retention_bytes,
+        } => {
+            let mut request = EstimateCleanup::new(topic.clone())?;
+            if let Some(policy) = policy {
+                request = request.with_cleanup_policy(policy);
+            }
+            if let Some(retention_ms) = retention_ms {
+                request = request.with_retention_ms(retention_ms);
+            }
+            if let Some(retention_bytes) = retention_bytes {
+                request = request.with_retention_bytes(retention_bytes);
+            }
+            let estimates = commands::estimate_cleanup(&client, request).await?;
+            for estimate in &estimates {
+                println!(
+                    "{topic}:{} size: {} reclaimable_bytes: {} segments: {} records: {}",
+                    estimate.partition,
+                    estimate.size,
+                    estimate.reclaimable_bytes,
+                    estimate.segments,
+                    estimate.records_removed
+                );
+            }
+            let reclaimable: u64 = estimates
+                .iter()
+                .map(|estimate| estimate.reclaimable_bytes)
+                .sum();
+            eprintln!("Would reclaim {reclaimable} bytes");
+        }
+        Command::Offsets { topic, time } => {
+            for (partition, offset) in commands::offsets(&client, &topic, time).await? {
+                println!("{}:{}:{}", partition.topic, partition.partition, offset);
+            }
+        }
+        Command::Mirror {
+            topics,
+            target,
+            prefix,
+            checkpoint,
+        } => {
+            let target = Arc::new(Client::new(target).with_client_id("herm-cli"));
+            let checkpoints = match checkpoint {
+                Some(path) => CheckpointStore::open(path)?,
+                None => CheckpointStore::in_memory(),
+            };
+            let config = MirrorConfig {
+                topics,
+                target_prefix: prefix,
+                ..Default::default()
+            };
+            let mirror =
+                Mirror::start(client, ClusterTarget::new(target), config, checkpoints).await?;
+            tokio::select! {
+                result = mirror.run() => result?,
+                _ = tokio::signal::ctrl_c() => {}
+            }
+        }
+        Command::DumpLog { .. } => unreachable!("dumped without a client"),
+    }
+    Ok(())
+}
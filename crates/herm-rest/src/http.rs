@@ -0,0 +1,229 @@
+use serde::Serialize;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Longest request line and headers read before giving up on a request.
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+/// Largest request body accepted.
+const MAX_REQUEST_BODY: usize = 16 * 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum HttpError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Connection closed mid-request")]
+    Closed,
+    #[error("Request head is over {MAX_REQUEST_HEAD} bytes")]
+    HeadTooLarge,
+    #[error("Request body is over {MAX_REQUEST_BODY} bytes")]
+    BodyTooLarge,
+    #[error("Malformed request")]
+    Malformed,
+}
+
+/// Just the parts of an HTTP/1.1 request the gateway routes on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    /// Without the query string.
+    pub path: String,
+    /// Pairs of the query string in order, not percent-decoded as topic
+    /// names and numbers never need it.
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Reads a request off `stream`, with a body if it has a
+    /// `Content-Length`. Returns `None` if the stream ends before one
+    /// starts.
+    pub async fn read(stream: &mut (impl AsyncRead + Unpin)) -> Result<Option<Self>, HttpError> {
+        let mut buf = Vec::new();
+        let mut chunk = [0; 1024];
+        let head_end = loop {
+            if let Some(i) = buf.windows(4).position(|window| window == b"\r\n\r\n") {
+                break i;
+            }
+            if buf.len() > MAX_REQUEST_HEAD {
+                return Err(HttpError::HeadTooLarge);
+            }
+            let read = stream.read(&mut chunk).await?;
+            if read == 0 {
+                return match buf.is_empty() {
+                    true => Ok(None),
+                    false => Err(HttpError::Closed),
+                };
+            }
+            buf.extend_from_slice(&chunk[..read]);
+        };
+
+        let head = std::str::from_utf8(&buf[..head_end]).map_err(|_| HttpError::Malformed)?;
+        let mut lines = head.split("\r\n");
+        let mut request_line = lines.next().unwrap_or_default().split(' ');
+        let (Some(method), Some(target)) = (request_line.next(), request_line.next()) else {
+            return Err(HttpError::Malformed);
+        };
+        let headers = lines
+            .map(|line| {
+                let (name, value) = line.split_once(':').ok_or(HttpError::Malformed)?;
+                Ok((name.trim().to_string(), value.trim().to_string()))
+            })
+            .collect::<Result<Vec<_>, HttpError>>()?;
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let query = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (name.to_string(), value.to_string())
+            })
+            .collect();
+        let request = Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            query,
+            headers,
+            body: vec![],
+        };
+        let content_length: usize = match request.header("content-length") {
+            Some(length) => length.parse().map_err(|_| HttpError::Malformed)?,
+            None => 0,
+        };
+        if content_length > MAX_REQUEST_BODY {
+            return Err(HttpError::BodyTooLarge);
+        }
+
+        let mut body = buf.split_off(head_end + 4);
+        if body.len() < content_length {
+            let start = body.len();
+            body.resize(content_length, 0);
+            stream
+                .read_exact(&mut body[start..])
+                .await
+                .map_err(|_| HttpError::Closed)?;
+        }
+        body.truncate(content_length);
+        Ok(Some(Request { body, ..request }))
+    }
+
+    /// The value of the first `name` pair of the query string.
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The value of the first `name` header, matched ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A JSON response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: serde_json::Value,
+}
+
+impl Response {
+    pub fn ok(body: impl Serialize) -> Self {
+        Response {
+            status: 200,
+            body: serde_json::to_value(body).expect("replies serialize to json"),
+        }
+    }
+
+    /// A `{"error": message}` body.
+    pub fn error(status: u16, message: impl ToString) -> Self {
+        Response {
+            status,
+            body: serde_json::json!({ "error": message.to_string() }),
+        }
+    }
+
+    /// Writes the response, saying the connection closes after it.
+    pub async fn write(&self, stream: &mut (impl AsyncWrite + Unpin)) -> std::io::Result<()> {
+        let body = self.body.to_string();
+        let head = format!(
+            "HTTP/1.1 {} {}\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\
+             \r\n",
+            self.status,
+            reason(self.status),
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(body.as_bytes()).await?;
+        stream.flush().await
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        416 => "Range Not Satisfiable",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read() {
+        let mut input: &[u8] =
+            b"POST /topics/events/partitions/0?a=1&b HTTP/1.1\r\nContent-Length: 4\r\n\r\nbodyextra";
+        let request = Request::read(&mut input).await.unwrap().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/topics/events/partitions/0");
+        assert_eq!(request.query("a"), Some("1"));
+        assert_eq!(request.query("b"), Some(""));
+        assert_eq!(request.query("c"), None);
+        assert_eq!(request.header("CONTENT-LENGTH"), Some("4"));
+        assert_eq!(request.body, b"body");
+
+        let mut input: &[u8] = b"";
+        assert!(Request::read(&mut input).await.unwrap().is_none());
+        let mut input: &[u8] = b"GET / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort";
+        assert!(matches!(
+            Request::read(&mut input).await,
+            Err(HttpError::Closed)
+        ));
+        let mut input: &[u8] = b"GET / HTTP/1.1\r\nbroken\r\n\r\n";
+        assert!(matches!(
+            Request::read(&mut input).await,
+            Err(HttpError::Malformed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_write() {
+        let mut out = vec![];
+        Response::error(404, "Unknown topic")
+            .write(&mut out)
+            .await
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let (head, body) = out.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(head.contains(&format!("Content-Length: {}", body.len())));
+        assert_eq!(body, r#"{"error":"Unknown topic"}"#);
+    }
+}
@@ -0,0 +1,9 @@
+mod http;
+mod routes;
+mod server;
+mod ws;
+
+pub use http::{HttpError, Request, Response};
+pub use routes::{handle, FetchReply, ProduceBody, ProduceReply, RecordIn, RecordOut, RestError};
+pub use server::RestServer;
+pub use ws::{accept_key, Frame, Opcode, WsError};
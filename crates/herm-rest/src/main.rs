@@ -0,0 +1,59 @@
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use herm::client::Client;
+use herm_rest::RestServer;
+
+const USAGE: &str = "\
+Usage: herm-rest [--listen <addr>] [--bootstrap-server <addr>[,<addr>]]
+
+Serves, translating to the native protocol:
+  POST /topics/{topic}/partitions/{partition}
+      {\"records\": [{\"key\": .., \"value\": .., \"timestamp\": ..}]}
+  GET  /topics/{topic}/partitions/{partition}/records?offset=<n>[&max_bytes=<n>]";
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:8082";
+const DEFAULT_BOOTSTRAP: &str = "127.0.0.1:9092";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut listen = DEFAULT_LISTEN.to_string();
+    let mut bootstrap = DEFAULT_BOOTSTRAP.to_string();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let target = match arg.as_str() {
+            "--listen" => &mut listen,
+            "--bootstrap-server" => &mut bootstrap,
+            _ => {
+                eprintln!("Unexpected argument {arg}\n\n{USAGE}");
+                return ExitCode::from(2);
+            }
+        };
+        match args.next() {
+            Some(value) => *target = value,
+            None => {
+                eprintln!("Option {arg} needs a value\n\n{USAGE}");
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    match run(&listen, bootstrap.split(',').map(str::to_string).collect()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(listen: &str, bootstrap: Vec<String>) -> anyhow::Result<()> {
+    let client = Arc::new(Client::new(bootstrap).with_client_id("herm-rest"));
+    let server = RestServer::bind(listen, client).await?;
+    eprintln!("Listening on {}", server.local_addr()?);
+    tokio::select! {
+        result = server.run() => result?,
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    Ok(())
+}
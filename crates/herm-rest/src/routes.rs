@@ -0,0 +1,357 @@
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use herm::client::{Client, ClientError};
+use herm::protocol::ErrorCode;
+use herm::record::{Record, RecordBatch};
+use herm::request::{Fetch, Produce};
+
+use crate::http::{Request, Response};
+
+/// Bytes a fetch asks for unless `max_bytes` is given.
+const DEFAULT_FETCH_MAX_BYTES: u32 = 1024 * 1024;
+
+/// Body of `POST /topics/{topic}/partitions/{partition}`, appended as a
+/// single batch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProduceBody {
+    pub records: Vec<RecordIn>,
+}
+
+/// Keys and values are taken as UTF-8 text.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RecordIn {
+    #[serde(default)]
+    pub key: Option<String>,
+    /// A tombstone when `None`.
+    #[serde(default)]
+    pub value: Option<String>,
+    /// Milliseconds since the unix epoch, the time of the request if not
+    /// given.
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+}
+
+impl From<RecordIn> for Record {
+    fn from(record: RecordIn) -> Self {
+        let built = Record::new(record.key.map(Bytes::from), record.value.map(Bytes::from));
+        match record.timestamp {
+            Some(timestamp) => built.with_timestamp(timestamp),
+            None => built,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProduceReply {
+    /// Offset given to the first record.
+    pub base_offset: u64,
+}
+
+/// Answer to `GET /topics/{topic}/partitions/{partition}/records`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FetchReply {
+    pub records: Vec<RecordOut>,
+    /// Offset to ask for next, after the last record returned.
+    pub next_offset: u64,
+    pub high_watermark: u64,
+}
+
+/// Keys and values that aren't UTF-8 come out lossily converted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordOut {
+    pub offset: u64,
+    pub timestamp: u64,
+    pub key: Option<String>,
+    pub value: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum RestError {
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("No route for {method} {path}")]
+    NotFound { method: String, path: String },
+    #[error("{0} isn't allowed here")]
+    MethodNotAllowed(String),
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    #[error("Broker answered {0}")]
+    Broker(ErrorCode),
+}
+
+impl RestError {
+    pub fn status(&self) -> u16 {
+        match self {
+            RestError::BadRequest(_) => 400,
+            RestError::NotFound { .. } => 404,
+            RestError::MethodNotAllowed(_) => 405,
+            RestError::Client(ClientError::NoLeader(_)) => 404,
+            RestError::Client(_) => 502,
+            RestError::Broker(error) => match error {
+                ErrorCode::UnknownTopicOrPartition => 404,
+                ErrorCode::TopicAuthorizationFailed => 403,
+                ErrorCode::OffsetOutOfRange => 416,
+                ErrorCode::InvalidTopic | ErrorCode::InvalidRequest | ErrorCode::CorruptMessage => {
+                    400
+                }
+                ErrorCode::NotLeaderOrFollower | ErrorCode::RequestTimedOut => 503,
+                _ => 502,
+            },
+        }
+    }
+}
+
+impl From<RestError> for Response {
+    fn from(err: RestError) -> Self {
+        Response::error(err.status(), err)
+    }
+}
+
+/// Answers `request` by making the produce or fetch it maps to through
+/// `client`.
+pub async fn handle(client: &Client, request: Request) -> Response {
+    match route(client, &request).await {
+        Ok(response) => response,
+        Err(err) => err.into(),
+    }
+}
+
+async fn route(client: &Client, request: &Request) -> Result<Response, RestError> {
+    let segments: Vec<_> = request.path.trim_matches('/').split('/').collect();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("POST", ["topics", topic, "partitions", partition]) => {
+            let body: ProduceBody = serde_json::from_slice(&request.body)
+                .map_err(|err| RestError::BadRequest(format!("Invalid body: {err}")))?;
+            let records = body.records.into_iter().map(Record::from).collect();
+            let reply = produce(client, topic, parse(partition, "partition")?, records).await?;
+            Ok(Response::ok(reply))
+        }
+        ("GET", ["topics", topic, "partitions", partition, "records"]) => {
+            let offset = request
+                .query("offset")
+                .ok_or_else(|| RestError::BadRequest("Missing offset".to_string()))?;
+            let max_bytes = match request.query("max_bytes") {
+                Some(max_bytes) => parse(max_bytes, "max_bytes")?,
+                None => DEFAULT_FETCH_MAX_BYTES,
+            };
+            let reply = fetch(
+                client,
+                topic,
+                parse(partition, "partition")?,
+                parse(offset, "offset")?,
+                max_bytes,
+            )
+            .await?;
+            Ok(Response::ok(reply))
+        }
+        (_, ["topics", _, "partitions", _]) | (_, ["topics", _, "partitions", _, "records"]) => {
+            Err(RestError::MethodNotAllowed(request.method.clone()))
+        }
+        _ => Err(RestError::NotFound {
+            method: request.method.clone(),
+            path: request.path.clone(),
+        }),
+    }
+}
+
+pub(crate) fn parse<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, RestError> {
+    value
+        .parse()
+        .map_err(|_| RestError::BadRequest(format!("Invalid {name} {value}")))
+}
+
+pub(crate) async fn produce(
+    client: &Client,
+    topic: &str,
+    partition: u32,
+    records: Vec<Record>,
+) -> Result<ProduceReply, RestError> {
+    if records.is_empty() {
+        return Err(RestError::BadRequest("No records".to_string()));
+    }
+    let request = Produce::new(topic.to_string(), partition, RecordBatch::new(records))
+        .map_err(|err| RestError::BadRequest(err.to_string()))?;
+
+    // Sent with the default acks, which always get a response
+    let Some(response) = client.produce(request).await? else {
+        return Err(RestError::Client(ClientError::Disconnected));
+    };
+    if !response.error.is_ok() {
+        return Err(RestError::Broker(response.error));
+    }
+    Ok(ProduceReply {
+        base_offset: response.base_offset,
+    })
+}
+
+async fn fetch(
+    client: &Client,
+    topic: &str,
+    partition: u32,
+    offset: u64,
+    max_bytes: u32,
+) -> Result<FetchReply, RestError> {
+    let request = Fetch::new(topic.to_string(), partition, offset, max_bytes)
+        .map_err(|err| RestError::BadRequest(err.to_string()))?;
+    let response = client.fetch(request).await?;
+    if !response.error.is_ok() {
+        return Err(RestError::Broker(response.error));
+    }
+
+    let text =
+        |bytes: Option<Bytes>| bytes.map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+    // The first batch may start before the offset asked for
+    let records: Vec<_> = response
+        .batches
+        .into_iter()
+        .flat_map(|batch| batch.records)
+        .filter(|record| record.offset >= offset)
+        .map(|record| RecordOut {
+            offset: record.offset,
+            timestamp: record.timestamp,
+            key: text(record.key),
+            value: text(record.value),
+        })
+        .collect();
+    Ok(FetchReply {
+        next_offset: records.last().map_or(offset, |record| record.offset + 1),
+        records,
+        high_watermark: response.high_watermark,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use herm_test::EmbeddedBroker;
+    use serde_json::json;
+
+    use super::*;
+
+    fn request(method: &str, target: &str, body: serde_json::Value) -> Request {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (name, value) = pair.split_once('=').unwrap();
+                    (name.to_string(), value.to_string())
+                })
+                .collect(),
+            headers: vec![],
+            body: if body.is_null() {
+                vec![]
+            } else {
+                body.to_string().into_bytes()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_produce_and_fetch() {
+        let broker = EmbeddedBroker::start_in_memory().await.unwrap();
+        broker.create_topic("events", 1).unwrap();
+        let client = broker.client();
+
+        let body = json!({"records": [
+            {"key": "a", "value": "1", "timestamp": 1000},
+            {"value": "2"},
+            {"key": "c"},
+        ]});
+        let response = handle(
+            &client,
+            request("POST", "/topics/events/partitions/0", body),
+        )
+        .await;
+        assert_eq!(response, Response::ok(json!({"base_offset": 0})));
+
+        let response = handle(
+            &client,
+            request(
+                "GET",
+                "/topics/events/partitions/0/records?offset=1",
+                json!(null),
+            ),
+        )
+        .await;
+        assert_eq!(response.status, 200);
+        let reply: FetchReply = serde_json::from_value(response.body).unwrap();
+        assert_eq!(reply.high_watermark, 3);
+        assert_eq!(reply.next_offset, 3);
+        assert_eq!(reply.records.len(), 2);
+        assert_eq!(reply.records[0].key, None);
+        assert_eq!(reply.records[0].value.as_deref(), Some("2"));
+        assert_eq!(reply.records[1].offset, 2);
+        assert_eq!(reply.records[1].key.as_deref(), Some("c"));
+        assert_eq!(reply.records[1].value, None);
+
+        let response = handle(
+            &client,
+            request(
+                "GET",
+                "/topics/events/partitions/0/records?offset=3",
+                json!(null),
+            ),
+        )
+        .await;
+        let reply: FetchReply = serde_json::from_value(response.body).unwrap();
+        assert!(reply.records.is_empty());
+        assert_eq!(reply.next_offset, 3);
+    }
+
+    #[tokio::test]
+    async fn test_bad_requests() {
+        let broker = EmbeddedBroker::start_in_memory().await.unwrap();
+        broker.create_topic("events", 1).unwrap();
+        let client = broker.client();
+
+        for (method, target, body, status) in [
+            (
+                "POST",
+                "/topics/events/partitions/0",
+                json!({"records": []}),
+                400,
+            ),
+            (
+                "POST",
+                "/topics/events/partitions/0",
+                json!({"values": []}),
+                400,
+            ),
+            (
+                "POST",
+                "/topics/events/partitions/x",
+                json!({"records": [{}]}),
+                400,
+            ),
+            (
+                "GET",
+                "/topics/events/partitions/0/records",
+                json!(null),
+                400,
+            ),
+            (
+                "GET",
+                "/topics/events/partitions/0/records?offset=-1",
+                json!(null),
+                400,
+            ),
+            (
+                "GET",
+                "/topics/events/partitions/0/records?offset=9",
+                json!(null),
+                416,
+            ),
+            ("DELETE", "/topics/events/partitions/0", json!(null), 405),
+            ("GET", "/topics", json!(null), 404),
+        ] {
+            let response = handle(&client, request(method, target, body)).await;
+            assert_eq!(response.status, status, "{method} {target}: {response:?}");
+            assert!(response.body["error"].is_string());
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use herm::client::Client;
+
+use crate::http::{HttpError, Request, Response};
+use crate::routes::handle;
+use crate::ws;
+
+/// Serves [`handle`] over HTTP/1.1, every response closing its connection,
+/// and partition streams to requests upgrading to WebSocket.
+#[derive(Debug)]
+pub struct RestServer {
+    listener: TcpListener,
+    client: Arc<Client>,
+}
+
+impl RestServer {
+    pub async fn bind(addr: impl ToSocketAddrs, client: Arc<Client>) -> io::Result<Self> {
+        Ok(RestServer {
+            listener: TcpListener::bind(addr).await?,
+            client,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Runs the accept loop. Only returns if accepting fails.
+    pub async fn run(self) -> io::Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            let client = self.client.clone();
+            tokio::spawn(async move {
+                let _ = serve(stream, client).await;
+            });
+        }
+    }
+}
+
+async fn serve(mut stream: TcpStream, client: Arc<Client>) -> io::Result<()> {
+    let response = match Request::read(&mut stream).await {
+        Ok(Some(request)) if ws::is_upgrade(&request) => {
+            return ws::serve(stream, client, request).await
+        }
+        Ok(Some(request)) => handle(&client, request).await,
+        Ok(None) | Err(HttpError::Closed) => return Ok(()),
+        Err(HttpError::Io(err)) => return Err(err),
+        Err(err @ HttpError::BodyTooLarge) => Response::error(413, err),
+        Err(err) => Response::error(400, err),
+    };
+    response.write(&mut stream).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use herm_test::EmbeddedBroker;
+
+    use super::*;
+
+    async fn send(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_server() {
+        let broker = EmbeddedBroker::start_in_memory().await.unwrap();
+        broker.create_topic("events", 1).unwrap();
+        let server = RestServer::bind("127.0.0.1:0", Arc::new(broker.client()))
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let body = r#"{"records":[{"key":"a","value":"1"}]}"#;
+        let response = send(
+            addr,
+            &format!(
+                "POST /topics/events/partitions/0 HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            ),
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with(r#"{"base_offset":0}"#));
+
+        let response = send(
+            addr,
+            "GET /topics/events/partitions/0/records?offset=0 HTTP/1.1\r\n\r\n",
+        )
+        .await;
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let reply: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(reply["records"][0]["value"], "1");
+
+        let response = send(addr, "GET / HTTP/1.1\r\nno colon\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+}
@@ -0,0 +1,555 @@
+use std::sync::Arc;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+
+use herm::client::Client;
+use herm::record::Record;
+use herm::request::{Fetch, ListOffsets};
+
+use crate::http::{Request, Response};
+use crate::routes::{parse, produce, ProduceBody, RestError};
+
+/// Appended to a client's key before hashing it into the accept key.
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest frame payload accepted from a client.
+const MAX_FRAME_PAYLOAD: usize = 1024 * 1024;
+
+/// Bytes each fetch of a stream asks for.
+const STREAM_FETCH_MAX_BYTES: u32 = 1024 * 1024;
+
+/// How long each fetch of a stream waits at the broker for new records.
+const STREAM_FETCH_WAIT_MS: u32 = 500;
+
+/// Frames queued for the socket before the stream stops fetching.
+const OUTBOUND_FRAMES: usize = 16;
+
+/// Close code for a stream ended by a broker or cluster error.
+const CLOSE_INTERNAL_ERROR: u16 = 1011;
+
+#[derive(Error, Debug)]
+pub enum WsError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Frame payload is over {MAX_FRAME_PAYLOAD} bytes")]
+    FrameTooLarge,
+    #[error("Fragmented frames aren't supported")]
+    Fragmented,
+    #[error("Client frames must be masked")]
+    Unmasked,
+    #[error("Unknown opcode {0}")]
+    UnknownOpcode(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    fn code(self) -> u8 {
+        match self {
+            Opcode::Text => 0x1,
+            Opcode::Binary => 0x2,
+            Opcode::Close => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xa,
+        }
+    }
+
+    fn from_code(code: u8) -> Result<Self, WsError> {
+        match code {
+            0x0 => Err(WsError::Fragmented),
+            0x1 => Ok(Opcode::Text),
+            0x2 => Ok(Opcode::Binary),
+            0x8 => Ok(Opcode::Close),
+            0x9 => Ok(Opcode::Ping),
+            0xa => Ok(Opcode::Pong),
+            code => Err(WsError::UnknownOpcode(code)),
+        }
+    }
+}
+
+/// A whole, unfragmented WebSocket frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub opcode: Opcode,
+    pub payload: Bytes,
+}
+
+impl Frame {
+    pub fn new(opcode: Opcode, payload: impl Into<Bytes>) -> Self {
+        Frame {
+            opcode,
+            payload: payload.into(),
+        }
+    }
+
+    /// A close frame carrying `code` and `reason`.
+    pub fn close(code: u16, reason: &str) -> Self {
+        let mut payload = code.to_be_bytes().to_vec();
+        // Control frame payloads are capped at 125 bytes
+        let reason = &reason.as_bytes()[..reason.len().min(123)];
+        payload.extend_from_slice(reason);
+        Frame::new(Opcode::Close, payload)
+    }
+
+    /// Reads a frame sent by a client, which must be masked. Returns
+    /// `None` if the stream ends between frames.
+    pub async fn read(stream: &mut (impl AsyncRead + Unpin)) -> Result<Option<Self>, WsError> {
+        let mut head = [0; 2];
+        match stream.read_exact(&mut head).await {
+            Ok(_) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+        if head[0] & 0x80 == 0 {
+            return Err(WsError::Fragmented);
+        }
+        let opcode = Opcode::from_code(head[0] & 0x0f)?;
+        if head[1] & 0x80 == 0 {
+            return Err(WsError::Unmasked);
+        }
+
+        let len = match head[1] & 0x7f {
+            126 => stream.read_u16().await? as u64,
+            127 => stream.read_u64().await?,
+            len => len as u64,
+        };
+        if len > MAX_FRAME_PAYLOAD as u64 {
+            return Err(WsError::FrameTooLarge);
+        }
+        let mut mask = [0; 4];
+        stream.read_exact(&mut mask).await?;
+        let mut payload = vec![0; len as usize];
+        stream.read_exact(&mut payload).await?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        Ok(Some(Frame::new(opcode, payload)))
+    }
+
+    /// Writes the frame unmasked, as servers send them.
+    pub async fn write(&self, stream: &mut (impl AsyncWrite + Unpin)) -> std::io::Result<()> {
+        let mut head = vec![0x80 | self.opcode.code()];
+        match self.payload.len() {
+            len @ 0..=125 => head.push(len as u8),
+            len @ 126..=0xffff => {
+                head.push(126);
+                head.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                head.push(127);
+                head.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        stream.write_all(&head).await?;
+        stream.write_all(&self.payload).await?;
+        stream.flush().await
+    }
+}
+
+/// Whether `request` asks to switch to the WebSocket protocol.
+pub fn is_upgrade(request: &Request) -> bool {
+    request
+        .header("upgrade")
+        .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+}
+
+/// The `Sec-WebSocket-Accept` answering a client's `Sec-WebSocket-Key`.
+pub fn accept_key(key: &str) -> String {
+    STANDARD.encode(sha1(format!("{key}{HANDSHAKE_GUID}").as_bytes()))
+}
+
+/// Upgrades a request for `/topics/{topic}/partitions/{partition}/stream`
+/// and streams the partition until either side closes.
+///
+/// From `offset` in the query string on, or from the end of the partition
+/// without one, each batch fetched is sent as a binary frame in the
+/// [`RecordBatch`](herm::record::RecordBatch) encoding. The client produces
+/// to the partition with binary frames, each a record value, or text frames
+/// holding a [`ProduceBody`], and is answered with a text frame holding a
+/// [`ProduceReply`](crate::ProduceReply) or an error.
+pub async fn serve(
+    mut stream: TcpStream,
+    client: Arc<Client>,
+    request: Request,
+) -> std::io::Result<()> {
+    let (topic, partition, offset) = match target(&client, &request).await {
+        Ok(target) => target,
+        Err(err) => {
+            Response::from(err).write(&mut stream).await?;
+            return stream.shutdown().await;
+        }
+    };
+    let Some(key) = request.header("sec-websocket-key") else {
+        Response::error(400, "Missing Sec-WebSocket-Key")
+            .write(&mut stream)
+            .await?;
+        return stream.shutdown().await;
+    };
+
+    let handshake = format!(
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\
+         \r\n",
+        accept_key(key)
+    );
+    stream.write_all(handshake.as_bytes()).await?;
+
+    let (mut reader, mut writer) = stream.into_split();
+    let (frames, mut outbound) = mpsc::channel(OUTBOUND_FRAMES);
+    let tail = tokio::spawn(tail(
+        client.clone(),
+        topic.clone(),
+        partition,
+        offset,
+        frames.clone(),
+    ));
+
+    let write = async {
+        while let Some(frame) = outbound.recv().await {
+            let closing = frame.opcode == Opcode::Close;
+            frame.write(&mut writer).await?;
+            if closing {
+                break;
+            }
+        }
+        writer.shutdown().await
+    };
+    let read = async {
+        let close = loop {
+            let frame = match Frame::read(&mut reader).await {
+                Ok(Some(frame)) => frame,
+                Ok(None) => return,
+                Err(err) => break Frame::close(1002, &err.to_string()),
+            };
+            let reply = match frame.opcode {
+                Opcode::Close => break Frame::new(Opcode::Close, frame.payload),
+                Opcode::Ping => Frame::new(Opcode::Pong, frame.payload),
+                Opcode::Pong => continue,
+                Opcode::Binary => {
+                    let records = vec![Record::new(None, Some(frame.payload))];
+                    produce_reply(&client, &topic, partition, Ok(records)).await
+                }
+                Opcode::Text => {
+                    let records = serde_json::from_slice::<ProduceBody>(&frame.payload)
+                        .map(|body| body.records.into_iter().map(Record::from).collect())
+                        .map_err(|err| RestError::BadRequest(format!("Invalid body: {err}")));
+                    produce_reply(&client, &topic, partition, records).await
+                }
+            };
+            if frames.send(reply).await.is_err() {
+                return;
+            }
+        };
+        let _ = frames.send(close).await;
+        // Keep the writer going until it sends the close frame
+        std::future::pending::<()>().await
+    };
+
+    let result = tokio::select! {
+        result = write => result,
+        _ = read => Ok(()),
+    };
+    tail.abort();
+    result
+}
+
+/// The partition and starting offset a stream request names.
+async fn target(client: &Client, request: &Request) -> Result<(String, u32, u64), RestError> {
+    let segments: Vec<_> = request.path.trim_matches('/').split('/').collect();
+    let ["topics", topic, "partitions", partition, "stream"] = segments.as_slice() else {
+        return Err(RestError::NotFound {
+            method: request.method.clone(),
+            path: request.path.clone(),
+        });
+    };
+    let partition = parse(partition, "partition")?;
+    let offset = match request.query("offset") {
+        Some(offset) => parse(offset, "offset")?,
+        None => {
+            let request = ListOffsets::latest(topic.to_string(), partition)
+                .map_err(|err| RestError::BadRequest(err.to_string()))?;
+            let response = client.list_offsets(request).await?;
+            if !response.error.is_ok() {
+                return Err(RestError::Broker(response.error));
+            }
+            response.found().unwrap_or_default()
+        }
+    };
+    Ok((topic.to_string(), partition, offset))
+}
+
+async fn produce_reply(
+    client: &Client,
+    topic: &str,
+    partition: u32,
+    records: Result<Vec<Record>, RestError>,
+) -> Frame {
+    let response = match records {
+        Ok(records) => match produce(client, topic, partition, records).await {
+            Ok(reply) => Response::ok(reply),
+            Err(err) => err.into(),
+        },
+        Err(err) => err.into(),
+    };
+    Frame::new(Opcode::Text, response.body.to_string())
+}
+
+/// Sends each batch of the partition from `offset` on as a binary frame,
+/// until the socket goes away or a fetch fails, which closes it.
+async fn tail(
+    client: Arc<Client>,
+    topic: String,
+    partition: u32,
+    mut offset: u64,
+    frames: mpsc::Sender<Frame>,
+) {
+    let close = loop {
+        let fetch = match Fetch::new(topic.clone(), partition, offset, STREAM_FETCH_MAX_BYTES) {
+            Ok(fetch) => fetch.wait_for(1, STREAM_FETCH_WAIT_MS),
+            Err(err) => break Frame::close(CLOSE_INTERNAL_ERROR, &err.to_string()),
+        };
+        let response = match client.fetch(fetch).await {
+            Ok(response) if response.error.is_ok() => response,
+            Ok(response) => {
+                let reason = RestError::Broker(response.error).to_string();
+                break Frame::close(CLOSE_INTERNAL_ERROR, &reason);
+            }
+            Err(err) => break Frame::close(CLOSE_INTERNAL_ERROR, &err.to_string()),
+        };
+
+        for mut batch in response.batches {
+            // The first batch may start before the offset asked for
+            batch.records.retain(|record| record.offset >= offset);
+            if batch.records.is_empty() {
+                continue;
+            }
+            offset = batch.next_offset();
+            if frames
+                .send(Frame::new(Opcode::Binary, batch.to_bytes()))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    };
+    let _ = frames.send(close).await;
+}
+
+/// SHA-1 of `data`, only used for the handshake where the protocol asks
+/// for it.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut words = [0u32; 80];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            words[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in state.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (chunk, word) in digest.chunks_exact_mut(4).zip(state) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use herm::record::RecordBatch;
+    use herm_test::EmbeddedBroker;
+
+    use super::*;
+    use crate::RestServer;
+
+    #[test]
+    fn test_accept_key() {
+        let hex: String = sha1(b"abc").iter().map(|b| format!("{b:02x}")).collect();
+        assert_eq!(hex, "a9993e364706816aba3e25717850c26c9cd0d89d");
+        // The example of RFC 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    /// Encodes a frame as a client would, masked.
+    fn client_frame(opcode: Opcode, payload: &[u8]) -> Vec<u8> {
+        let mask = [1, 2, 3, 4];
+        let mut frame = vec![0x80 | opcode.code()];
+        match payload.len() {
+            len @ 0..=125 => frame.push(0x80 | len as u8),
+            len => {
+                frame.push(0x80 | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    #[tokio::test]
+    async fn test_frames() {
+        let payload = vec![7; 300];
+        let encoded = client_frame(Opcode::Binary, &payload);
+        let frame = Frame::read(&mut &encoded[..]).await.unwrap().unwrap();
+        assert_eq!(frame, Frame::new(Opcode::Binary, payload.clone()));
+        assert!(Frame::read(&mut &[][..]).await.unwrap().is_none());
+
+        let mut out = vec![];
+        frame.write(&mut out).await.unwrap();
+        assert_eq!(&out[..4], &[0x82, 126, 1, 44]);
+        assert_eq!(&out[4..], &payload[..]);
+
+        let unmasked = [0x81, 0];
+        assert!(matches!(
+            Frame::read(&mut &unmasked[..]).await,
+            Err(WsError::Unmasked)
+        ));
+        let fragment = [0x01, 0x80, 0, 0, 0, 0];
+        assert!(matches!(
+            Frame::read(&mut &fragment[..]).await,
+            Err(WsError::Fragmented)
+        ));
+    }
+
+    /// Reads a frame sent by the server, unmasked.
+    async fn server_frame(stream: &mut TcpStream) -> Frame {
+        let opcode = stream.read_u8().await.unwrap() & 0x0f;
+        let len = match stream.read_u8().await.unwrap() {
+            126 => stream.read_u16().await.unwrap() as usize,
+            len => len as usize,
+        };
+        let mut payload = vec![0; len];
+        stream.read_exact(&mut payload).await.unwrap();
+        Frame::new(Opcode::from_code(opcode).unwrap(), payload)
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let broker = EmbeddedBroker::start_in_memory().await.unwrap();
+        broker.create_topic("events", 1).unwrap();
+        let server = RestServer::bind("127.0.0.1:0", Arc::new(broker.client()))
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(
+                b"GET /topics/events/partitions/0/stream?offset=0 HTTP/1.1\r\n\
+                  Upgrade: websocket\r\n\
+                  Connection: Upgrade\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                  Sec-WebSocket-Version: 13\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut head = vec![];
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await.unwrap());
+        }
+        let head = String::from_utf8(head).unwrap();
+        assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        stream
+            .write_all(&client_frame(Opcode::Binary, b"hello"))
+            .await
+            .unwrap();
+        let body = br#"{"records":[{"key":"k","value":"world"}]}"#;
+        stream
+            .write_all(&client_frame(Opcode::Text, body))
+            .await
+            .unwrap();
+
+        // Acks and streamed batches interleave
+        let mut acks = vec![];
+        let mut records = vec![];
+        while acks.len() < 2 || records.len() < 2 {
+            let frame = server_frame(&mut stream).await;
+            match frame.opcode {
+                Opcode::Text => {
+                    let reply: serde_json::Value = serde_json::from_slice(&frame.payload).unwrap();
+                    acks.push(reply["base_offset"].as_u64().unwrap());
+                }
+                Opcode::Binary => {
+                    let batch = RecordBatch::from_bytes(frame.payload).unwrap();
+                    records.extend(batch.records);
+                }
+                opcode => panic!("unexpected {opcode:?} frame"),
+            }
+        }
+        assert_eq!(acks, vec![0, 1]);
+        assert_eq!(records[0].value.as_deref(), Some(&b"hello"[..]));
+        assert_eq!(records[1].offset, 1);
+        assert_eq!(records[1].key.as_deref(), Some(&b"k"[..]));
+
+        stream
+            .write_all(&client_frame(Opcode::Ping, b"hi"))
+            .await
+            .unwrap();
+        assert_eq!(
+            server_frame(&mut stream).await,
+            Frame::new(Opcode::Pong, &b"hi"[..])
+        );
+        stream
+            .write_all(&client_frame(Opcode::Close, &1000u16.to_be_bytes()))
+            .await
+            .unwrap();
+        assert_eq!(server_frame(&mut stream).await.opcode, Opcode::Close);
+        assert_eq!(stream.read(&mut [0; 1]).await.unwrap(), 0);
+    }
+}
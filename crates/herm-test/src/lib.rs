@@ -0,0 +1,168 @@
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use tempfile::TempDir;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use herm::broker::{LogHandler, Server};
+use herm::client::Client;
+use herm::protocol::TopicPartition;
+use herm::storage::{LogConfig, LogDirError, LogDirs, Placement};
+
+/// Where an [`EmbeddedBroker`] keeps its partitions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Storage {
+    /// In a temp dir, removed when the broker is dropped.
+    #[default]
+    Disk,
+    /// In memory, for tests that don't need real files.
+    Memory,
+}
+
+/// A broker running in the test process on an ephemeral port of
+/// `127.0.0.1`, so integration tests can talk to a real one without any
+/// setup. Stops when dropped, taking its data with it.
+///
+/// Topics aren't created on first use, see [`EmbeddedBroker::create_topic`].
+#[derive(Debug)]
+pub struct EmbeddedBroker {
+    address: String,
+    handler: LogHandler,
+    shutdown: Option<oneshot::Sender<()>>,
+    server: JoinHandle<io::Result<()>>,
+    dir: Option<TempDir>,
+}
+
+impl EmbeddedBroker {
+    /// Starts a broker keeping its partitions on disk.
+    pub async fn start() -> io::Result<Self> {
+        Self::start_with(Storage::Disk).await
+    }
+
+    pub async fn start_in_memory() -> io::Result<Self> {
+        Self::start_with(Storage::Memory).await
+    }
+
+    pub async fn start_with(storage: Storage) -> io::Result<Self> {
+        let (logs, dir) = match storage {
+            Storage::Disk => {
+                let dir = tempfile::tempdir()?;
+                let logs = LogDirs::open(
+                    [dir.path().to_path_buf()],
+                    LogConfig::default(),
+                    Placement::default(),
+                );
+                (logs, Some(dir))
+            }
+            Storage::Memory => (LogDirs::in_memory(LogConfig::default()), None),
+        };
+
+        let handler = LogHandler::new(Arc::new(logs));
+        let server = Server::bind("127.0.0.1:0", handler.clone()).await?;
+        let address = server.local_addr()?.to_string();
+        handler.replicas().set_advertised_listener(address.clone());
+        let (shutdown, stopped) = oneshot::channel();
+        let server = tokio::spawn(server.run_until(async {
+            let _ = stopped.await;
+        }));
+        Ok(EmbeddedBroker {
+            address,
+            handler,
+            shutdown: Some(shutdown),
+            server,
+            dir,
+        })
+    }
+
+    /// Address clients bootstrap from.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// A client bootstrapping from this broker.
+    pub fn client(&self) -> Client {
+        Client::new(vec![self.address.clone()])
+    }
+
+    /// Creates `partitions` partitions of `topic`, all led by this broker.
+    pub fn create_topic(&self, topic: &str, partitions: u32) -> Result<(), LogDirError> {
+        for partition in 0..partitions {
+            self.handler
+                .logs()
+                .create(&TopicPartition::new(topic, partition))?;
+        }
+        Ok(())
+    }
+
+    /// The broker's handler, to reach its logs, groups and replicas.
+    pub fn handler(&self) -> &LogHandler {
+        &self.handler
+    }
+
+    /// Dir holding the partitions, `None` when they are kept in memory.
+    pub fn data_dir(&self) -> Option<&Path> {
+        self.dir.as_ref().map(TempDir::path)
+    }
+
+    /// Stops accepting requests and waits for those in flight to be
+    /// answered.
+    pub async fn shutdown(mut self) -> io::Result<()> {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        match (&mut self.server).await {
+            Ok(result) => result,
+            Err(err) => Err(io::Error::other(err)),
+        }
+    }
+}
+
+impl Drop for EmbeddedBroker {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use herm::record::{Record, RecordBatch};
+    use herm::request::{Fetch, Produce};
+
+    use super::*;
+
+    async fn round_trip(broker: &EmbeddedBroker) {
+        broker.create_topic("events", 2).unwrap();
+        let client = broker.client();
+        assert_eq!(client.partition_count("events").await.unwrap(), Some(2));
+
+        let batch = RecordBatch::new(vec![Record::new(None, Some("a".into()))]);
+        let produce = Produce::new("events".to_string(), 1, batch).unwrap();
+        let response = client.produce(produce).await.unwrap().unwrap();
+        assert!(response.error.is_ok());
+
+        let fetch = Fetch::new("events".to_string(), 1, 0, 1024).unwrap();
+        let response = client.fetch(fetch).await.unwrap();
+        assert_eq!(response.batches[0].records.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_disk() {
+        let broker = EmbeddedBroker::start().await.unwrap();
+        round_trip(&broker).await;
+        let dir = broker.data_dir().unwrap().to_path_buf();
+        assert!(dir.join("events-1").exists());
+
+        broker.shutdown().await.unwrap();
+        assert!(!dir.exists());
+    }
+
+    #[tokio::test]
+    async fn test_memory() {
+        let broker = EmbeddedBroker::start_in_memory().await.unwrap();
+        assert!(broker.data_dir().is_none());
+        round_trip(&broker).await;
+        broker.shutdown().await.unwrap();
+    }
+}
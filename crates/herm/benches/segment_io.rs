@@ -0,0 +1,119 @@
+//! Compares appending to and reading back segments through plain syscalls
+//! against io_uring.
+//!
+//! Run with `cargo bench --bench segment_io --features io-uring` to include
+//! the io_uring backend.
+
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use herm::record::{Record, RecordBatch};
+use herm::storage::{FlushPolicy, Log, LogConfig};
+
+const BATCHES: usize = 2_000;
+const RECORDS_PER_BATCH: usize = 10;
+const VALUE_SIZE: usize = 100;
+const FETCH_BYTES: usize = 16 * 1024;
+const ROUNDS: usize = 5;
+
+fn batch() -> RecordBatch {
+    let value = Bytes::from(vec![b'x'; VALUE_SIZE]);
+    RecordBatch::new(
+        (0..RECORDS_PER_BATCH)
+            .map(|_| Record::new(None, Some(value.clone())))
+            .collect(),
+    )
+}
+
+/// Appends every batch to a fresh log, returning the bytes written.
+fn append_all(config: &LogConfig) -> (Duration, usize) {
+    let dir = tempfile::tempdir().unwrap();
+    let mut log = Log::open(dir.path(), config.clone()).unwrap();
+    let batches: Vec<_> = (0..BATCHES).map(|_| batch()).collect();
+    let bytes = batches.iter().map(RecordBatch::size).sum();
+
+    let start = Instant::now();
+    for batch in batches {
+        log.append(batch).unwrap();
+    }
+    (start.elapsed(), bytes)
+}
+
+/// Reads the whole log front to back in fetch sized chunks.
+fn read_all(log: &Log) -> usize {
+    let mut offset = log.start_offset();
+    let mut bytes = 0;
+    while offset < log.next_offset() {
+        let batches = log.read(offset, FETCH_BYTES).unwrap();
+        bytes += batches.iter().map(RecordBatch::size).sum::<usize>();
+        offset = batches.last().unwrap().next_offset();
+    }
+    bytes
+}
+
+fn report(name: &str, op: &str, bytes: usize, best: Duration) {
+    let mib = bytes as f64 / (1024.0 * 1024.0);
+    println!(
+        "{:<8} {:<14} {:>8.1} MiB in {:>8.2?}  {:>8.1} MiB/s",
+        name,
+        op,
+        mib,
+        best,
+        mib / best.as_secs_f64()
+    );
+}
+
+fn bench(name: &str, config: LogConfig) {
+    for (op, flush_policy) in [
+        ("append", FlushPolicy::Never),
+        ("append+flush", FlushPolicy::EveryWrite),
+    ] {
+        let config = LogConfig {
+            flush_policy,
+            ..config.clone()
+        };
+        let mut best = Duration::MAX;
+        let mut bytes = 0;
+        for _ in 0..ROUNDS {
+            let (elapsed, written) = append_all(&config);
+            best = best.min(elapsed);
+            bytes = written;
+        }
+        report(name, op, bytes, best);
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut log = Log::open(dir.path(), config).unwrap();
+    for _ in 0..BATCHES {
+        log.append(batch()).unwrap();
+    }
+    // Warm the page cache so reads measure the syscall path, not the disk
+    read_all(&log);
+
+    let mut best = Duration::MAX;
+    let mut bytes = 0;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        bytes = read_all(&log);
+        best = best.min(start.elapsed());
+    }
+    report(name, "read", bytes, best);
+}
+
+fn main() {
+    let config = LogConfig {
+        segment_bytes: 4 * 1024 * 1024,
+        ..Default::default()
+    };
+
+    bench("std", config.clone());
+
+    #[cfg(feature = "io-uring")]
+    bench(
+        "io_uring",
+        LogConfig {
+            io_uring: true,
+            ..config
+        },
+    );
+}
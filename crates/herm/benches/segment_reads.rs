@@ -0,0 +1,90 @@
+//! Compares reading sealed segments with pread against memory maps.
+//!
+//! Run with `cargo bench --bench segment_reads --features mmap` to include the
+//! mmap path.
+
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use herm::record::{Record, RecordBatch};
+use herm::storage::{Log, LogConfig};
+
+const BATCHES: usize = 2_000;
+const RECORDS_PER_BATCH: usize = 50;
+const VALUE_SIZE: usize = 100;
+const FETCH_BYTES: usize = 64 * 1024;
+const ROUNDS: usize = 5;
+
+fn fill(log: &mut Log) {
+    let value = Bytes::from(vec![b'x'; VALUE_SIZE]);
+    for _ in 0..BATCHES {
+        log.append(RecordBatch::new(
+            (0..RECORDS_PER_BATCH)
+                .map(|_| Record::new(None, Some(value.clone())))
+                .collect(),
+        ))
+        .unwrap();
+    }
+    // Seal everything written, only sealed segments are mapped
+    log.roll().unwrap();
+}
+
+/// Reads the whole log front to back in fetch sized chunks.
+fn read_all(log: &Log) -> usize {
+    let mut offset = log.start_offset();
+    let mut bytes = 0;
+    while offset < log.next_offset() {
+        let batches = log.read(offset, FETCH_BYTES).unwrap();
+        bytes += batches.iter().map(RecordBatch::size).sum::<usize>();
+        offset = batches.last().unwrap().next_offset();
+    }
+    bytes
+}
+
+fn bench(name: &str, config: LogConfig) {
+    let dir = tempfile::tempdir().unwrap();
+    let mut log = Log::open(dir.path(), config.clone()).unwrap();
+    fill(&mut log);
+    drop(log);
+
+    // Reopen so sealed segments go through the configured read path
+    let log = Log::open(dir.path(), config).unwrap();
+
+    // Warm the page cache so both paths read from memory
+    read_all(&log);
+
+    let mut best = Duration::MAX;
+    let mut bytes = 0;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        bytes = read_all(&log);
+        best = best.min(start.elapsed());
+    }
+
+    let mib = bytes as f64 / (1024.0 * 1024.0);
+    println!(
+        "{:<6} {:>8.1} MiB in {:>8.2?}  {:>8.1} MiB/s",
+        name,
+        mib,
+        best,
+        mib / best.as_secs_f64()
+    );
+}
+
+fn main() {
+    let config = LogConfig {
+        segment_bytes: 4 * 1024 * 1024,
+        ..Default::default()
+    };
+
+    bench("pread", config.clone());
+
+    #[cfg(feature = "mmap")]
+    bench(
+        "mmap",
+        LogConfig {
+            mmap_reads: true,
+            ..config
+        },
+    );
+}
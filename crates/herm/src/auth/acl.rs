@@ -0,0 +1,286 @@
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use thiserror::Error;
+
+use super::{Authorizer, Operation, Resource};
+use crate::broker::Principal;
+
+#[derive(Error, Debug)]
+pub enum AclError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Line {line}: {reason}")]
+    Parse { line: usize, reason: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    Allow,
+    Deny,
+}
+
+/// Resources an ACL applies to. Topic and group names ending in `*` match
+/// every topic or group with that prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResourcePattern {
+    Topic(String),
+    TopicPrefix(String),
+    Group(String),
+    GroupPrefix(String),
+    Cluster,
+}
+
+impl ResourcePattern {
+    fn matches(&self, resource: Resource) -> bool {
+        match (self, resource) {
+            (ResourcePattern::Topic(name), Resource::Topic(topic)) => name == topic,
+            (ResourcePattern::TopicPrefix(prefix), Resource::Topic(topic)) => {
+                topic.starts_with(prefix.as_str())
+            }
+            (ResourcePattern::Group(name), Resource::Group(group)) => name == group,
+            (ResourcePattern::GroupPrefix(prefix), Resource::Group(group)) => {
+                group.starts_with(prefix.as_str())
+            }
+            (ResourcePattern::Cluster, Resource::Cluster) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A single rule, `principal` of `None` applies to everyone and `operation`
+/// of `None` to every operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Acl {
+    pub permission: Permission,
+    pub principal: Option<Principal>,
+    pub operation: Option<Operation>,
+    pub resource: ResourcePattern,
+}
+
+impl Acl {
+    fn matches(&self, principal: &Principal, operation: Operation, resource: Resource) -> bool {
+        self.principal.as_ref().is_none_or(|p| p == principal)
+            && self.operation.is_none_or(|op| op == operation)
+            && self.resource.matches(resource)
+    }
+}
+
+impl FromStr for Acl {
+    type Err = String;
+
+    /// Parses `<allow|deny> <principal> <operation> <resource>`, like
+    /// `allow User:alice write topic:orders`. `*` stands for any principal or
+    /// operation.
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [permission, principal, operation, resource] = fields[..] else {
+            return Err(format!("expected 4 fields, got {}", fields.len()));
+        };
+
+        let permission = match permission {
+            "allow" => Permission::Allow,
+            "deny" => Permission::Deny,
+            other => return Err(format!("unknown permission {:?}", other)),
+        };
+
+        let principal = match principal {
+            "*" => None,
+            "User:ANONYMOUS" => Some(Principal::Anonymous),
+            other => match other.strip_prefix("User:") {
+                Some(name) if !name.is_empty() => Some(Principal::User(name.to_string())),
+                _ => return Err(format!("invalid principal {:?}", other)),
+            },
+        };
+
+        let operation = match operation {
+            "*" => None,
+            "read" => Some(Operation::Read),
+            "write" => Some(Operation::Write),
+            "create" => Some(Operation::Create),
+            "delete" => Some(Operation::Delete),
+            "alter" => Some(Operation::Alter),
+            "describe" => Some(Operation::Describe),
+            other => return Err(format!("unknown operation {:?}", other)),
+        };
+
+        let resource = match resource {
+            "cluster" => ResourcePattern::Cluster,
+            other => match (other.strip_prefix("topic:"), other.strip_prefix("group:")) {
+                (Some(prefix), _) if prefix.ends_with('*') => {
+                    ResourcePattern::TopicPrefix(prefix.trim_end_matches('*').to_string())
+                }
+                (Some(name), _) if !name.is_empty() => ResourcePattern::Topic(name.to_string()),
+                (_, Some(prefix)) if prefix.ends_with('*') => {
+                    ResourcePattern::GroupPrefix(prefix.trim_end_matches('*').to_string())
+                }
+                (_, Some(name)) if !name.is_empty() => ResourcePattern::Group(name.to_string()),
+                _ => return Err(format!("invalid resource {:?}", other)),
+            },
+        };
+
+        Ok(Acl {
+            permission,
+            principal,
+            operation,
+            resource,
+        })
+    }
+}
+
+/// [`Authorizer`] backed by a list of ACLs, one per line of a file:
+///
+/// ```text
+/// # Producers write to orders, everyone may read it.
+/// allow User:producer write topic:orders
+/// allow * read topic:orders
+/// deny User:mallory * topic:*
+/// ```
+///
+/// A matching deny wins over any allow, and anything not allowed is denied.
+#[derive(Debug, Clone, Default)]
+pub struct AclAuthorizer {
+    acls: Vec<Acl>,
+}
+
+impl AclAuthorizer {
+    pub fn new(acls: Vec<Acl>) -> Self {
+        AclAuthorizer { acls }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, AclError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses ACLs one per line, skipping blank lines and `#` comments.
+    pub fn parse(text: &str) -> Result<Self, AclError> {
+        let mut acls = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let acl = line.parse().map_err(|reason| AclError::Parse {
+                line: index + 1,
+                reason,
+            })?;
+            acls.push(acl);
+        }
+        Ok(AclAuthorizer { acls })
+    }
+
+    pub fn acls(&self) -> &[Acl] {
+        &self.acls
+    }
+}
+
+impl Authorizer for AclAuthorizer {
+    fn authorize(&self, principal: &Principal, operation: Operation, resource: Resource) -> bool {
+        let mut allowed = false;
+        for acl in &self.acls {
+            if acl.matches(principal, operation, resource) {
+                match acl.permission {
+                    Permission::Deny => return false,
+                    Permission::Allow => allowed = true,
+                }
+            }
+        }
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(name: &str) -> Principal {
+        Principal::User(name.to_string())
+    }
+
+    #[test]
+    fn test_parse() {
+        let authorizer = AclAuthorizer::parse(
+            "# comment\n\
+             \n\
+             allow User:alice write topic:orders  # trailing\n\
+             deny * * topic:internal-*\n\
+             allow User:ANONYMOUS describe cluster\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            authorizer.acls(),
+            &[
+                Acl {
+                    permission: Permission::Allow,
+                    principal: Some(user("alice")),
+                    operation: Some(Operation::Write),
+                    resource: ResourcePattern::Topic("orders".to_string()),
+                },
+                Acl {
+                    permission: Permission::Deny,
+                    principal: None,
+                    operation: None,
+                    resource: ResourcePattern::TopicPrefix("internal-".to_string()),
+                },
+                Acl {
+                    permission: Permission::Allow,
+                    principal: Some(Principal::Anonymous),
+                    operation: Some(Operation::Describe),
+                    resource: ResourcePattern::Cluster,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (text, line) in [
+            ("allow User:alice write", 1),
+            ("\npermit * * cluster", 2),
+            ("allow alice * cluster", 1),
+            ("allow User: * cluster", 1),
+            ("allow * produce cluster", 1),
+            ("allow * * user:g", 1),
+            ("allow * * group:", 1),
+            ("allow * * topic:", 1),
+        ] {
+            match AclAuthorizer::parse(text) {
+                Err(AclError::Parse { line: actual, .. }) => assert_eq!(actual, line, "{}", text),
+                other => panic!("{:?} parsed as {:?}", text, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_authorize() {
+        let authorizer = AclAuthorizer::parse(
+            "allow User:alice write topic:orders\n\
+             allow * read topic:orders\n\
+             allow * * topic:scratch-*\n\
+             deny User:mallory * topic:*\n\
+             allow User:bob read group:billing-*\n",
+        )
+        .unwrap();
+
+        let orders = Resource::Topic("orders");
+        assert!(authorizer.authorize(&user("alice"), Operation::Write, orders));
+        assert!(!authorizer.authorize(&user("bob"), Operation::Write, orders));
+        assert!(authorizer.authorize(&user("bob"), Operation::Read, orders));
+        assert!(authorizer.authorize(&Principal::Anonymous, Operation::Read, orders));
+
+        let scratch = Resource::Topic("scratch-1");
+        assert!(authorizer.authorize(&user("bob"), Operation::Delete, scratch));
+        assert!(!authorizer.authorize(&user("mallory"), Operation::Read, orders));
+        assert!(!authorizer.authorize(&user("mallory"), Operation::Write, scratch));
+
+        let billing = Resource::Group("billing-eu");
+        assert!(authorizer.authorize(&user("bob"), Operation::Read, billing));
+        assert!(!authorizer.authorize(&user("alice"), Operation::Read, billing));
+        // Topic patterns don't cover groups of the same name
+        assert!(!authorizer.authorize(&user("bob"), Operation::Read, Resource::Group("orders")));
+
+        assert!(!authorizer.authorize(&user("alice"), Operation::Alter, Resource::Cluster));
+        assert!(!AclAuthorizer::default().authorize(&user("alice"), Operation::Read, orders));
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt::{Debug, Display};
+use std::sync::Arc;
+
+use crate::broker::Principal;
+
+/// What a request does to a resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    Read,
+    Write,
+    Create,
+    Delete,
+    Alter,
+    Describe,
+}
+
+impl Operation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::Read => "read",
+            Operation::Write => "write",
+            Operation::Create => "create",
+            Operation::Delete => "delete",
+            Operation::Alter => "alter",
+            Operation::Describe => "describe",
+        }
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What a request acts on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resource<'a> {
+    Topic(&'a str),
+    /// A consumer group, by id.
+    Group(&'a str),
+    /// The broker as a whole, for admin operations.
+    Cluster,
+}
+
+impl Display for Resource<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Resource::Topic(topic) => write!(f, "topic:{}", topic),
+            Resource::Group(group) => write!(f, "group:{}", group),
+            Resource::Cluster => f.write_str("cluster"),
+        }
+    }
+}
+
+/// Decides whether a principal may perform an operation on a resource.
+/// Consulted by [`dispatch`](crate::broker::dispatch) before a request
+/// reaches the handler, denied requests are answered with an authorization
+/// error.
+pub trait Authorizer: Debug + Send + Sync + 'static {
+    fn authorize(&self, principal: &Principal, operation: Operation, resource: Resource) -> bool;
+}
+
+impl<A: Authorizer + ?Sized> Authorizer for Arc<A> {
+    fn authorize(&self, principal: &Principal, operation: Operation, resource: Resource) -> bool {
+        (**self).authorize(principal, operation, resource)
+    }
+}
+
+/// Lets everything through. The broker runs with this unless configured
+/// with ACLs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AllowAll;
+
+impl Authorizer for AllowAll {
+    fn authorize(&self, _: &Principal, _: Operation, _: Resource) -> bool {
+        true
+    }
+}
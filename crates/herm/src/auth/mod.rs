@@ -0,0 +1,4 @@
+mod acl;
+mod authorizer;
+pub use acl::{Acl, AclAuthorizer, AclError, Permission, ResourcePattern};
+pub use authorizer::{AllowAll, Authorizer, Operation, Resource};
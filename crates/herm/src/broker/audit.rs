@@ -0,0 +1,211 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use super::RequestContext;
+use crate::protocol::{ApiKey, ErrorCode};
+use crate::record::now_ms;
+
+/// One line of the audit log.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditRecord<'a> {
+    pub timestamp_ms: u64,
+    pub principal: String,
+    pub client_id: &'a str,
+    pub correlation_id: u32,
+    pub api: &'static str,
+    /// Left out for requests not about a single partition.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub partition: Option<u32>,
+    /// Name of the error code the request was answered with, `None` on
+    /// success.
+    pub outcome: &'static str,
+    pub latency_us: u64,
+}
+
+/// Writes a JSON line for every request the server answers, denied and
+/// throttled ones included, see [`Server::with_audit_log`].
+///
+/// The file at `path` is rotated once it grows past `max_file_bytes`: it
+/// moves to `path.1`, older files shift up by one and the oldest past
+/// `max_files` is deleted.
+///
+/// [`Server::with_audit_log`]: super::Server::with_audit_log
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    max_file_bytes: u64,
+    max_files: usize,
+    file: Mutex<AuditFile>,
+}
+
+#[derive(Debug)]
+struct AuditFile {
+    file: File,
+    size: u64,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path, max_file_bytes: u64, max_files: usize) -> io::Result<Self> {
+        Ok(AuditLog {
+            path: path.to_path_buf(),
+            max_file_bytes,
+            max_files,
+            file: Mutex::new(AuditFile::open(path)?),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends a line for a `api_key` request to `topic` and `partition`,
+    /// answered with `error` after `latency`.
+    pub fn record(
+        &self,
+        context: &RequestContext,
+        api_key: ApiKey,
+        topic: Option<&str>,
+        partition: Option<u32>,
+        error: ErrorCode,
+        latency: Duration,
+    ) -> io::Result<()> {
+        let record = AuditRecord {
+            timestamp_ms: now_ms(),
+            principal: context.principal.to_string(),
+            client_id: &context.client_id,
+            correlation_id: context.correlation_id,
+            api: api_key.name(),
+            topic,
+            partition,
+            outcome: error.name(),
+            latency_us: latency.as_micros().try_into().unwrap_or(u64::MAX),
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        self.write(&line)
+    }
+
+    fn write(&self, line: &[u8]) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        if file.size > 0 && file.size + line.len() as u64 > self.max_file_bytes {
+            self.rotate()?;
+            *file = AuditFile::open(&self.path)?;
+        }
+        // One write per line, so lines aren't torn if the broker dies
+        file.file.write_all(line)?;
+        file.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shifts `path.n` to `path.n+1`, oldest first, and `path` to `path.1`.
+    fn rotate(&self) -> io::Result<()> {
+        if self.max_files == 0 {
+            return std::fs::remove_file(&self.path);
+        }
+        let _ = std::fs::remove_file(self.rotated(self.max_files));
+        for n in (1..self.max_files).rev() {
+            match std::fs::rename(self.rotated(n), self.rotated(n + 1)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        std::fs::rename(&self.path, self.rotated(1))
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        path.into()
+    }
+}
+
+impl AuditFile {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(AuditFile { file, size })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::broker::Principal;
+
+    fn context() -> RequestContext {
+        RequestContext {
+            client_id: "app".to_string(),
+            correlation_id: 7,
+            principal: Principal::User("alice".to_string()),
+            peer: None,
+        }
+    }
+
+    fn record(audit: &AuditLog, error: ErrorCode) {
+        audit
+            .record(
+                &context(),
+                ApiKey::Produce,
+                Some("events"),
+                Some(2),
+                error,
+                Duration::from_micros(1500),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let audit = AuditLog::open(&path, 1024 * 1024, 3).unwrap();
+        record(&audit, ErrorCode::None);
+        record(&audit, ErrorCode::TopicAuthorizationFailed);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["principal"], "User:alice");
+        assert_eq!(lines[0]["client_id"], "app");
+        assert_eq!(lines[0]["correlation_id"], 7);
+        assert_eq!(lines[0]["api"], "Produce");
+        assert_eq!(lines[0]["topic"], "events");
+        assert_eq!(lines[0]["partition"], 2);
+        assert_eq!(lines[0]["outcome"], "None");
+        assert_eq!(lines[0]["latency_us"], 1500);
+        assert!(lines[0]["timestamp_ms"].as_u64().unwrap() > 0);
+        assert_eq!(lines[1]["outcome"], "TopicAuthorizationFailed");
+    }
+
+    #[test]
+    fn test_rotate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let audit = AuditLog::open(&path, 1, 2).unwrap();
+        for _ in 0..4 {
+            record(&audit, ErrorCode::None);
+        }
+
+        // Every line got its own file, and the oldest was dropped
+        for path in [path.clone(), audit.rotated(1), audit.rotated(2)] {
+            assert_eq!(std::fs::read_to_string(path).unwrap().lines().count(), 1);
+        }
+        assert!(!audit.rotated(3).exists());
+
+        // Reopening picks up the size of the current file
+        let reopened = AuditLog::open(&path, 1, 2).unwrap();
+        record(&reopened, ErrorCode::None);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+    }
+}
@@ -0,0 +1,905 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+
+use serde::Deserialize;
+
+use super::{
+    DelayConfig, QuotaConfig, QuotaKey, DEFAULT_DRAIN_TIMEOUT, DEFAULT_MAX_FRAME_SIZE,
+    DEFAULT_MAX_IN_FLIGHT,
+};
+use crate::cluster::{ClusterConfig, DEFAULT_HEARTBEAT_INTERVAL, DEFAULT_SESSION_TIMEOUT};
+use crate::protocol::DecodeLimits;
+use crate::replication::{FetcherConfig, ReplicaConfig, DEFAULT_REPLICA_LAG_TIME_MAX};
+use crate::storage::{CleanupPolicy, FlushPolicy, LogConfig, Placement, TopicConfigError};
+use crate::tenant::{NamespaceConfig, NamespaceError, NamespaceQuota, Namespaces};
+use crate::transform::{TransformChain, TransformError, TransformRegistry};
+
+/// Prefix of the environment variables that override config file settings.
+/// Sections are separated by a double underscore, so `HERM_LOG__SEGMENT_BYTES`
+/// sets `segment_bytes` under `[log]`.
+pub const ENV_PREFIX: &str = "HERM_";
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Parse(#[from] toml::de::Error),
+    #[error("{0} does not name a config section")]
+    NotASection(String),
+    #[error("Invalid config: {0}")]
+    Invalid(&'static str),
+    #[error(transparent)]
+    Transform(#[from] TransformError),
+    #[error(transparent)]
+    TopicConfig(#[from] TopicConfigError),
+}
+
+/// Everything the broker is run with, read from a TOML file:
+///
+/// ```toml
+/// listen = "0.0.0.0:9092"
+/// data_dirs = ["/var/lib/herm/a", "/var/lib/herm/b"]
+///
+/// [log]
+/// segment_bytes = 268435456
+/// flush_messages = 1
+///
+/// [limits]
+/// max_in_flight = 16
+/// ```
+///
+/// Settings left out keep their defaults.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Id this broker fetches with when following another.
+    pub broker_id: u32,
+    pub listen: String,
+    /// Address other brokers reach this one at, when it differs from
+    /// `listen`. Partitions moved here are followed from it.
+    pub advertised_listen: Option<String>,
+    /// Rack this broker is in, as reported to tooling describing the
+    /// cluster.
+    pub rack: Option<String>,
+    /// Also listen on a Unix domain socket at this path when set.
+    pub listen_unix: Option<PathBuf>,
+    pub data_dirs: Vec<PathBuf>,
+    pub placement: Placement,
+    pub log: LogSettings,
+    pub limits: Limits,
+    pub quotas: QuotaSettings,
+    /// File of ACLs to check requests against, see
+    /// [`AclAuthorizer`](crate::auth::AclAuthorizer). Everything is allowed
+    /// without one.
+    pub acl_path: Option<PathBuf>,
+    /// Follow the broker at this address, replicating every partition held
+    /// here from it, when set.
+    pub replicate_from: Option<String>,
+    /// How long a follower may go without catching up before it leaves the
+    /// in-sync replicas and stops holding back the high watermark.
+    pub replica_lag_time_max_ms: u64,
+    /// The other brokers of the cluster, the same on every broker. They are
+    /// sent heartbeats, listed in metadata while alive, may replicate the
+    /// partitions led here, and take them over on shutdown.
+    pub peers: Vec<PeerSettings>,
+    pub heartbeat_interval_ms: u64,
+    /// How long a peer counts as alive after its last heartbeat.
+    pub session_timeout_ms: u64,
+    /// How long shutdown waits for followers to take over the partitions led
+    /// here, 0 to shut down without handing them off.
+    pub controlled_shutdown_timeout_ms: u64,
+    /// Create topics no broker knows of when they are produced to, fetched
+    /// from or asked for by name in metadata, rather than answering that
+    /// they are unknown.
+    pub auto_create_topics: bool,
+    /// Partitions of topics created automatically.
+    pub default_partitions: u32,
+    /// Relay produces, fetches and offset lookups for partitions followed
+    /// here to their leader, rather than refusing them, for clients that can
+    /// only reach this broker.
+    pub forward_to_leader: bool,
+    /// Configs of individual topics by name, so far their transforms, see
+    /// [`TransformRegistry`]:
+    ///
+    /// ```toml
+    /// [topics.orders]
+    /// "produce.transforms" = "redact"
+    /// "transforms.redact.type" = "redact-headers"
+    /// "transforms.redact.headers" = "ssn"
+    /// ```
+    pub topics: HashMap<String, HashMap<String, String>>,
+    /// Tenant namespaces by name, whose topics are named `tenant/topic`.
+    /// Only the principals listed may use their topics, and they are held
+    /// to the namespace quotas:
+    ///
+    /// ```toml
+    /// [namespaces.billing]
+    /// principals = ["alice"]
+    /// max_topics = 10
+    /// produce_bytes_per_sec = 1048576
+    ///
+    /// [namespaces.billing.topic_defaults]
+    /// "retention.ms" = "86400000"
+    /// ```
+    ///
+    /// Without any, topic names aren't scoped to namespaces.
+    pub namespaces: HashMap<String, NamespaceSettings>,
+    /// Write an audit line for every request when set.
+    pub audit: Option<AuditSettings>,
+    /// Serve Prometheus metrics over HTTP on this address when set.
+    #[cfg(feature = "prometheus")]
+    pub metrics_listen: Option<String>,
+    /// Serve TLS rather than plaintext when set.
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsSettings>,
+}
+
+/// Storage settings shared by every partition, see [`LogConfig`]. Like in
+/// Kafka, a retention of -1 means unlimited.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogSettings {
+    pub segment_bytes: u64,
+    pub segment_ms: u64,
+    pub retention_ms: i64,
+    pub retention_bytes: i64,
+    pub cleanup_policy: CleanupPolicy,
+    pub tombstone_retention_ms: u64,
+    /// Flush every this many messages, 1 to flush every write.
+    pub flush_messages: Option<u64>,
+    /// Flush once this long has passed since the last flush. Left to the OS
+    /// if neither this nor `flush_messages` is set.
+    pub flush_interval_ms: Option<u64>,
+    /// How often retention, compaction and interval flushes run.
+    pub cleanup_interval_ms: u64,
+    #[cfg(feature = "mmap")]
+    pub mmap_reads: bool,
+    #[cfg(feature = "io-uring")]
+    pub io_uring: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    pub max_frame_size: usize,
+    pub max_in_flight: usize,
+    /// How long shutdown waits for in-flight requests.
+    pub drain_timeout_ms: u64,
+    pub max_topic_len: usize,
+    pub max_batch_bytes: usize,
+    /// Most records a produced batch may hold.
+    pub max_records: usize,
+    /// Most records held for later delivery at once.
+    pub max_delayed_records: usize,
+    pub max_delayed_bytes: usize,
+    /// Furthest in the future records may be produced for delivery.
+    pub max_delivery_delay_ms: u64,
+}
+
+/// Per-client byte rates, see [`QuotaConfig`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuotaSettings {
+    pub produce_bytes_per_sec: Option<u64>,
+    pub fetch_bytes_per_sec: Option<u64>,
+    /// Track quotas by `client-id` or by `principal`.
+    pub key: QuotaKey,
+    pub window_ms: u64,
+}
+
+/// A tenant namespace, see [`NamespaceConfig`].
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NamespaceSettings {
+    pub principals: Vec<String>,
+    pub max_topics: Option<usize>,
+    pub max_partitions: Option<u32>,
+    pub produce_bytes_per_sec: Option<u64>,
+    pub fetch_bytes_per_sec: Option<u64>,
+    /// Topic configs, by their Kafka names, that topics created in the
+    /// namespace start with.
+    pub topic_defaults: HashMap<String, String>,
+}
+
+/// Another broker, by the id it fetches with.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PeerSettings {
+    pub id: u32,
+    pub address: String,
+}
+
+/// Where the [`AuditLog`](super::AuditLog) goes and when it rotates.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuditSettings {
+    pub path: PathBuf,
+    #[serde(default = "AuditSettings::default_max_file_bytes")]
+    pub max_file_bytes: u64,
+    /// Rotated files kept besides the current one.
+    #[serde(default = "AuditSettings::default_max_files")]
+    pub max_files: usize,
+}
+
+/// Certificate and key of a TLS listener, both PEM files.
+#[cfg(feature = "tls")]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsSettings {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    /// Require clients to present a certificate signed by one of these CAs,
+    /// and know them by its name.
+    pub client_ca_path: Option<PathBuf>,
+}
+
+impl Config {
+    /// Reads the config from `path`, or starts from the defaults without one,
+    /// then applies `HERM_` environment overrides and validates the result.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let toml = match path {
+            Some(path) => std::fs::read_to_string(path)?,
+            None => String::new(),
+        };
+        Self::parse(&toml, std::env::vars())
+    }
+
+    /// Like [`load`](Config::load), with the file contents and environment
+    /// passed in.
+    pub fn parse(
+        toml: &str,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let mut table: toml::Table = toml.parse()?;
+        for (name, value) in env {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            set(&mut table, &name, &key.to_lowercase(), env_value(&value))?;
+        }
+
+        let config: Config = table.try_into()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let log = &self.log;
+        ensure(!self.listen.is_empty(), "listen is empty")?;
+        ensure(!self.data_dirs.is_empty(), "data_dirs is empty")?;
+        ensure(log.segment_bytes > 0, "segment_bytes must be positive")?;
+        ensure(log.retention_ms >= -1, "retention_ms must be -1 or more")?;
+        ensure(
+            log.retention_bytes >= -1,
+            "retention_bytes must be -1 or more",
+        )?;
+        ensure(
+            log.flush_messages.is_none() || log.flush_interval_ms.is_none(),
+            "only one of flush_messages and flush_interval_ms can be set",
+        )?;
+        ensure(
+            log.flush_messages != Some(0),
+            "flush_messages must be positive",
+        )?;
+        ensure(
+            log.cleanup_interval_ms > 0,
+            "cleanup_interval_ms must be positive",
+        )?;
+        ensure(
+            self.limits.max_frame_size > 0,
+            "max_frame_size must be positive",
+        )?;
+        ensure(
+            self.limits.max_in_flight > 0,
+            "max_in_flight must be positive",
+        )?;
+        ensure(
+            self.limits.max_batch_bytes <= self.limits.max_frame_size,
+            "max_batch_bytes can't be over max_frame_size",
+        )?;
+        ensure(
+            self.quotas.window_ms > 0,
+            "quota window_ms must be positive",
+        )?;
+        let mut peer_ids: Vec<_> = self.peers.iter().map(|peer| peer.id).collect();
+        peer_ids.sort_unstable();
+        peer_ids.dedup();
+        ensure(
+            peer_ids.len() == self.peers.len(),
+            "peer ids must be unique",
+        )?;
+        ensure(
+            !peer_ids.contains(&self.broker_id),
+            "peers can't include broker_id",
+        )?;
+        ensure(
+            self.default_partitions > 0,
+            "default_partitions must be positive",
+        )?;
+        ensure(
+            self.heartbeat_interval_ms > 0,
+            "heartbeat_interval_ms must be positive",
+        )?;
+        ensure(
+            self.session_timeout_ms > self.heartbeat_interval_ms,
+            "session_timeout_ms must be over heartbeat_interval_ms",
+        )?;
+        self.transforms()?;
+        for settings in self.namespaces.values() {
+            self.log_config()
+                .with_topic_configs(&settings.topic_defaults)?;
+        }
+        Ok(())
+    }
+
+    /// The [`LogConfig`] every partition is opened with.
+    pub fn log_config(&self) -> LogConfig {
+        let log = &self.log;
+        LogConfig {
+            segment_bytes: log.segment_bytes,
+            segment_age: Duration::from_millis(log.segment_ms),
+            retention_age: (log.retention_ms >= 0)
+                .then(|| Duration::from_millis(log.retention_ms as u64)),
+            retention_bytes: (log.retention_bytes >= 0).then_some(log.retention_bytes as u64),
+            cleanup_policy: log.cleanup_policy,
+            tombstone_retention: Duration::from_millis(log.tombstone_retention_ms),
+            flush_policy: log.flush_policy(),
+            #[cfg(feature = "mmap")]
+            mmap_reads: log.mmap_reads,
+            #[cfg(feature = "io-uring")]
+            io_uring: log.io_uring,
+        }
+    }
+
+    pub fn replica_lag_time_max(&self) -> Duration {
+        Duration::from_millis(self.replica_lag_time_max_ms)
+    }
+
+    /// The [`FetcherConfig`] partitions are replicated with.
+    pub fn fetcher_config(&self) -> FetcherConfig {
+        FetcherConfig {
+            replica_id: self.broker_id,
+            ..Default::default()
+        }
+    }
+
+    /// The [`ReplicaConfig`] partitions are led and followed with.
+    pub fn replica_config(&self) -> ReplicaConfig {
+        ReplicaConfig {
+            replica_lag_time_max: self.replica_lag_time_max(),
+            fetcher: self.fetcher_config(),
+            peers: self
+                .peers
+                .iter()
+                .map(|peer| (peer.id, peer.address.clone()))
+                .collect(),
+        }
+    }
+
+    /// The [`ClusterConfig`] peers are tracked with.
+    pub fn cluster_config(&self) -> ClusterConfig {
+        ClusterConfig {
+            broker_id: self.broker_id,
+            rack: self.rack.clone(),
+            peers: self.replica_config().peers,
+            heartbeat_interval: Duration::from_millis(self.heartbeat_interval_ms),
+            session_timeout: Duration::from_millis(self.session_timeout_ms),
+        }
+    }
+
+    pub fn controlled_shutdown_timeout(&self) -> Duration {
+        Duration::from_millis(self.controlled_shutdown_timeout_ms)
+    }
+
+    /// The [`TransformChain`] of each topic configured with transforms.
+    pub fn transforms(&self) -> Result<HashMap<String, TransformChain>, TransformError> {
+        let registry = TransformRegistry::new();
+        let mut transforms = HashMap::new();
+        for (topic, configs) in &self.topics {
+            let chain = registry.build(configs)?;
+            if !chain.is_empty() {
+                transforms.insert(topic.clone(), chain);
+            }
+        }
+        Ok(transforms)
+    }
+
+    /// The configured [`Namespaces`], all registered.
+    pub fn namespaces(&self) -> Result<Namespaces, NamespaceError> {
+        let mut namespaces = Namespaces::new();
+        for (name, settings) in &self.namespaces {
+            namespaces.register(name, settings.namespace_config())?;
+        }
+        Ok(namespaces)
+    }
+}
+
+impl NamespaceSettings {
+    pub fn namespace_config(&self) -> NamespaceConfig {
+        NamespaceConfig {
+            quota: NamespaceQuota {
+                max_topics: self.max_topics,
+                max_partitions: self.max_partitions,
+                produce_bytes_per_sec: self.produce_bytes_per_sec,
+                fetch_bytes_per_sec: self.fetch_bytes_per_sec,
+            },
+            topic_defaults: self.topic_defaults.clone(),
+            principals: self.principals.iter().cloned().collect::<HashSet<_>>(),
+        }
+    }
+}
+
+impl QuotaSettings {
+    /// The [`QuotaConfig`] the server throttles clients with.
+    pub fn quota_config(&self) -> QuotaConfig {
+        QuotaConfig {
+            produce_rate: self.produce_bytes_per_sec,
+            fetch_rate: self.fetch_bytes_per_sec,
+            key: self.key,
+            window: Duration::from_millis(self.window_ms),
+        }
+    }
+}
+
+impl AuditSettings {
+    fn default_max_file_bytes() -> u64 {
+        100 * 1024 * 1024
+    }
+
+    fn default_max_files() -> usize {
+        10
+    }
+}
+
+impl LogSettings {
+    pub fn flush_policy(&self) -> FlushPolicy {
+        match (self.flush_messages, self.flush_interval_ms) {
+            (Some(1), _) => FlushPolicy::EveryWrite,
+            (Some(n), _) => FlushPolicy::EveryNMessages(n),
+            (None, Some(ms)) => FlushPolicy::Interval(Duration::from_millis(ms)),
+            (None, None) => FlushPolicy::Never,
+        }
+    }
+
+    pub fn cleanup_interval(&self) -> Duration {
+        Duration::from_millis(self.cleanup_interval_ms)
+    }
+}
+
+impl Limits {
+    pub fn drain_timeout(&self) -> Duration {
+        Duration::from_millis(self.drain_timeout_ms)
+    }
+
+    /// The [`DecodeLimits`] requests are read with.
+    pub fn decode_limits(&self) -> DecodeLimits {
+        DecodeLimits {
+            max_topic_len: self.max_topic_len,
+            max_batch_bytes: self.max_batch_bytes,
+            max_records: self.max_records,
+        }
+    }
+
+    /// The [`DelayConfig`] records produced for later delivery are held
+    /// with.
+    pub fn delay_config(&self) -> DelayConfig {
+        DelayConfig {
+            max_records: self.max_delayed_records,
+            max_bytes: self.max_delayed_bytes,
+            max_delay: Duration::from_millis(self.max_delivery_delay_ms),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            broker_id: 0,
+            listen: "127.0.0.1:9092".to_string(),
+            advertised_listen: None,
+            rack: None,
+            listen_unix: None,
+            data_dirs: vec![PathBuf::from("data")],
+            placement: Placement::default(),
+            log: LogSettings::default(),
+            limits: Limits::default(),
+            quotas: QuotaSettings::default(),
+            acl_path: None,
+            replicate_from: None,
+            replica_lag_time_max_ms: DEFAULT_REPLICA_LAG_TIME_MAX.as_millis() as u64,
+            peers: vec![],
+            heartbeat_interval_ms: DEFAULT_HEARTBEAT_INTERVAL.as_millis() as u64,
+            session_timeout_ms: DEFAULT_SESSION_TIMEOUT.as_millis() as u64,
+            controlled_shutdown_timeout_ms: 30_000,
+            auto_create_topics: false,
+            default_partitions: 1,
+            forward_to_leader: false,
+            topics: HashMap::new(),
+            namespaces: HashMap::new(),
+            audit: None,
+            #[cfg(feature = "prometheus")]
+            metrics_listen: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        let defaults = LogConfig::default();
+        LogSettings {
+            segment_bytes: defaults.segment_bytes,
+            segment_ms: defaults.segment_age.as_millis() as u64,
+            retention_ms: defaults
+                .retention_age
+                .map_or(-1, |age| age.as_millis() as i64),
+            retention_bytes: defaults.retention_bytes.map_or(-1, |bytes| bytes as i64),
+            cleanup_policy: defaults.cleanup_policy,
+            tombstone_retention_ms: defaults.tombstone_retention.as_millis() as u64,
+            flush_messages: None,
+            flush_interval_ms: None,
+            cleanup_interval_ms: 30_000,
+            #[cfg(feature = "mmap")]
+            mmap_reads: defaults.mmap_reads,
+            #[cfg(feature = "io-uring")]
+            io_uring: defaults.io_uring,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        let decode = DecodeLimits::default();
+        let delay = DelayConfig::default();
+        Limits {
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            drain_timeout_ms: DEFAULT_DRAIN_TIMEOUT.as_millis() as u64,
+            max_topic_len: decode.max_topic_len,
+            max_batch_bytes: decode.max_batch_bytes,
+            max_records: decode.max_records,
+            max_delayed_records: delay.max_records,
+            max_delayed_bytes: delay.max_bytes,
+            max_delivery_delay_ms: delay.max_delay.as_millis() as u64,
+        }
+    }
+}
+
+impl Default for QuotaSettings {
+    fn default() -> Self {
+        let defaults = QuotaConfig::default();
+        QuotaSettings {
+            produce_bytes_per_sec: defaults.produce_rate,
+            fetch_bytes_per_sec: defaults.fetch_rate,
+            key: defaults.key,
+            window_ms: defaults.window.as_millis() as u64,
+        }
+    }
+}
+
+fn ensure(valid: bool, reason: &'static str) -> Result<(), ConfigError> {
+    if valid {
+        Ok(())
+    } else {
+        Err(ConfigError::Invalid(reason))
+    }
+}
+
+/// Reads an environment value as a TOML value, so numbers and arrays work,
+/// falling back to a plain string.
+fn env_value(raw: &str) -> toml::Value {
+    format!("value = {}", raw)
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+/// Sets the `__` separated `key` in `table`, creating sections on the way.
+fn set(
+    table: &mut toml::Table,
+    name: &str,
+    key: &str,
+    value: toml::Value,
+) -> Result<(), ConfigError> {
+    let mut path: Vec<_> = key.split("__").collect();
+    let last = path.pop().unwrap();
+
+    let mut table = table;
+    for section in path {
+        table = table
+            .entry(section)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| ConfigError::NotASection(name.to_string()))?;
+    }
+    table.insert(last.to_string(), value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = Config::parse("", []).unwrap();
+        assert_eq!(config, Config::default());
+        assert_eq!(config.log_config(), LogConfig::default());
+        assert_eq!(config.quotas.quota_config(), QuotaConfig::default());
+    }
+
+    #[test]
+    fn test_parse() {
+        let toml = r#"
+            broker_id = 2
+            listen = "0.0.0.0:9093"
+            advertised_listen = "broker-2:9093"
+            rack = "eu-west-1a"
+            listen_unix = "/run/herm.sock"
+            data_dirs = ["a", "b"]
+            placement = "fewest-partitions"
+            acl_path = "acls.txt"
+            replicate_from = "leader:9092"
+            replica_lag_time_max_ms = 10000
+            controlled_shutdown_timeout_ms = 5000
+            heartbeat_interval_ms = 500
+            session_timeout_ms = 3000
+            auto_create_topics = true
+            default_partitions = 3
+            forward_to_leader = true
+
+            [[peers]]
+            id = 1
+            address = "broker-1:9092"
+
+            [log]
+            segment_bytes = 1024
+            retention_ms = -1
+            cleanup_policy = "compact"
+            flush_interval_ms = 500
+
+            [limits]
+            max_in_flight = 8
+            max_delivery_delay_ms = 60000
+
+            [quotas]
+            produce_bytes_per_sec = 1048576
+            key = "principal"
+        "#;
+        let config = Config::parse(toml, []).unwrap();
+        assert_eq!(config.listen, "0.0.0.0:9093");
+        assert_eq!(config.advertised_listen.as_deref(), Some("broker-2:9093"));
+        assert_eq!(config.listen_unix, Some(PathBuf::from("/run/herm.sock")));
+        assert_eq!(
+            config.data_dirs,
+            vec![PathBuf::from("a"), PathBuf::from("b")]
+        );
+        assert_eq!(config.placement, Placement::FewestPartitions);
+        assert_eq!(config.acl_path, Some(PathBuf::from("acls.txt")));
+        assert_eq!(config.replicate_from.as_deref(), Some("leader:9092"));
+        assert_eq!(config.replica_lag_time_max(), Duration::from_secs(10));
+        assert_eq!(config.fetcher_config().replica_id, 2);
+        assert_eq!(
+            config.replica_config().replica_lag_time_max,
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            config.replica_config().peers,
+            HashMap::from([(1, "broker-1:9092".to_string())])
+        );
+        assert_eq!(config.controlled_shutdown_timeout(), Duration::from_secs(5));
+        assert!(config.auto_create_topics);
+        assert_eq!(config.default_partitions, 3);
+        assert!(config.forward_to_leader);
+        let cluster = config.cluster_config();
+        assert_eq!(cluster.broker_id, 2);
+        assert_eq!(cluster.rack.as_deref(), Some("eu-west-1a"));
+        assert_eq!(cluster.peers.len(), 1);
+        assert_eq!(cluster.heartbeat_interval, Duration::from_millis(500));
+        assert_eq!(cluster.session_timeout, Duration::from_secs(3));
+        assert_eq!(config.limits.max_in_flight, 8);
+        assert_eq!(config.limits.max_frame_size, DEFAULT_MAX_FRAME_SIZE);
+        assert_eq!(config.limits.decode_limits(), DecodeLimits::default());
+        assert_eq!(
+            config.limits.delay_config(),
+            DelayConfig {
+                max_delay: Duration::from_secs(60),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            config.quotas.quota_config(),
+            QuotaConfig {
+                produce_rate: Some(1048576),
+                fetch_rate: None,
+                key: QuotaKey::Principal,
+                window: Duration::from_secs(1),
+            }
+        );
+
+        let log = config.log_config();
+        assert_eq!(log.segment_bytes, 1024);
+        assert_eq!(log.retention_age, None);
+        assert_eq!(log.cleanup_policy, CleanupPolicy::Compact);
+        assert_eq!(
+            log.flush_policy,
+            FlushPolicy::Interval(Duration::from_millis(500))
+        );
+
+        assert!(matches!(
+            Config::parse("unknown = 1", []),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn test_metrics_listen() {
+        assert_eq!(Config::parse("", []).unwrap().metrics_listen, None);
+        let config = Config::parse("", env(&[("HERM_METRICS_LISTEN", "0.0.0.0:9100")])).unwrap();
+        assert_eq!(config.metrics_listen.as_deref(), Some("0.0.0.0:9100"));
+    }
+
+    #[test]
+    fn test_audit() {
+        assert_eq!(Config::parse("", []).unwrap().audit, None);
+
+        let config = Config::parse("[audit]\npath = \"audit.log\"\nmax_files = 2", []).unwrap();
+        assert_eq!(
+            config.audit,
+            Some(AuditSettings {
+                path: PathBuf::from("audit.log"),
+                max_file_bytes: 100 * 1024 * 1024,
+                max_files: 2,
+            })
+        );
+        assert!(matches!(
+            Config::parse("[audit]\nmax_files = 2", []),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn test_tls() {
+        assert_eq!(Config::parse("", []).unwrap().tls, None);
+
+        let toml = "[tls]\ncert_path = \"cert.pem\"\nkey_path = \"key.pem\"";
+        let tls = Config::parse(toml, []).unwrap().tls.unwrap();
+        assert_eq!(tls.cert_path, PathBuf::from("cert.pem"));
+        assert_eq!(tls.key_path, PathBuf::from("key.pem"));
+        assert_eq!(tls.client_ca_path, None);
+
+        assert!(matches!(
+            Config::parse("[tls]\ncert_path = \"cert.pem\"", []),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let config = Config::parse(
+            "listen = \"0.0.0.0:9093\"\n[log]\nsegment_bytes = 1024",
+            env(&[
+                ("HERM_LISTEN", "127.0.0.1:9999"),
+                ("HERM_DATA_DIRS", r#"["x", "y"]"#),
+                ("HERM_LOG__SEGMENT_BYTES", "2048"),
+                ("HERM_LIMITS__MAX_IN_FLIGHT", "4"),
+                ("PATH", "/usr/bin"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.listen, "127.0.0.1:9999");
+        assert_eq!(
+            config.data_dirs,
+            vec![PathBuf::from("x"), PathBuf::from("y")]
+        );
+        assert_eq!(config.log.segment_bytes, 2048);
+        assert_eq!(config.limits.max_in_flight, 4);
+
+        assert!(matches!(
+            Config::parse("", env(&[("HERM_LOG__SEGMENT_BYTES", "lots")])),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            Config::parse("listen = \"a:1\"", env(&[("HERM_LISTEN__PORT", "1")])),
+            Err(ConfigError::NotASection(name)) if name == "HERM_LISTEN__PORT"
+        ));
+    }
+
+    #[test]
+    fn test_validate() {
+        let invalid = [
+            "data_dirs = []",
+            "[log]\nsegment_bytes = 0",
+            "[log]\nretention_bytes = -2",
+            "[log]\nflush_messages = 10\nflush_interval_ms = 10",
+            "[limits]\nmax_in_flight = 0",
+            "[limits]\nmax_frame_size = 1024\nmax_batch_bytes = 2048",
+            "[[peers]]\nid = 0\naddress = \"a:1\"",
+            "heartbeat_interval_ms = 0",
+            "default_partitions = 0",
+            "heartbeat_interval_ms = 1000\nsession_timeout_ms = 1000",
+            "[[peers]]\nid = 1\naddress = \"a:1\"\n[[peers]]\nid = 1\naddress = \"b:1\"",
+        ];
+        for toml in invalid {
+            assert!(
+                matches!(Config::parse(toml, []), Err(ConfigError::Invalid(_))),
+                "{}",
+                toml
+            );
+        }
+
+        let config = Config::parse("[log]\nflush_messages = 1", []).unwrap();
+        assert_eq!(config.log.flush_policy(), FlushPolicy::EveryWrite);
+
+        assert_eq!(
+            Config::parse("[topics.orders]\n\"produce.transforms\" = \"x\"", [])
+                .unwrap_err()
+                .to_string(),
+            TransformError::MissingType("x".to_string()).to_string()
+        );
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let toml = r#"
+            [namespaces.billing]
+            principals = ["alice"]
+            max_topics = 1
+
+            [namespaces.billing.topic_defaults]
+            "retention.ms" = "1000"
+        "#;
+        let config = Config::parse(toml, []).unwrap();
+        let namespaces = config.namespaces().unwrap();
+        assert_eq!(
+            namespaces.topic_configs("billing/orders", &HashMap::new()),
+            Ok(HashMap::from([(
+                "retention.ms".to_string(),
+                "1000".to_string()
+            )]))
+        );
+        assert!(namespaces.authorize("alice", "billing/orders").is_ok());
+        assert!(namespaces.authorize("bob", "billing/orders").is_err());
+        namespaces.add_topic("billing/orders", 3).unwrap();
+        assert!(namespaces.add_topic("billing/payments", 1).is_err());
+
+        let toml = r#"
+            [namespaces.billing.topic_defaults]
+            "retention.ms" = "soon"
+        "#;
+        assert!(matches!(
+            Config::parse(toml, []),
+            Err(ConfigError::TopicConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_topic_transforms() {
+        let toml = r#"
+            [topics.orders]
+            "produce.transforms" = "source"
+            "transforms.source.type" = "insert-header"
+            "transforms.source.key" = "source"
+            "transforms.source.value" = "billing"
+
+            [topics.events]
+        "#;
+        let config = Config::parse(toml, []).unwrap();
+        let transforms = config.transforms().unwrap();
+        assert_eq!(transforms.len(), 1);
+        assert!(!transforms["orders"].is_empty());
+    }
+}
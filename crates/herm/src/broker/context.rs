@@ -0,0 +1,49 @@
+use std::fmt::Display;
+use std::net::SocketAddr;
+
+use crate::protocol::RequestHeader;
+
+/// Identity of a client, established when it connected. Shown Kafka style,
+/// like `User:alice`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Principal {
+    /// Clients that didn't authenticate, like those on plaintext listeners.
+    #[default]
+    Anonymous,
+    User(String),
+}
+
+impl Display for Principal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Principal::Anonymous => f.write_str("User:ANONYMOUS"),
+            Principal::User(name) => write!(f, "User:{}", name),
+        }
+    }
+}
+
+/// Who sent a request, handed to the [`Handler`](super::Handler) with it.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RequestContext {
+    pub client_id: String,
+    pub correlation_id: u32,
+    pub principal: Principal,
+    /// Address the client connected from, `None` over unix sockets.
+    pub peer: Option<SocketAddr>,
+}
+
+impl RequestContext {
+    pub fn new(header: RequestHeader, principal: Principal) -> Self {
+        RequestContext {
+            client_id: header.client_id,
+            correlation_id: header.correlation_id,
+            principal,
+            peer: None,
+        }
+    }
+
+    pub fn with_peer(mut self, peer: Option<SocketAddr>) -> Self {
+        self.peer = peer;
+        self
+    }
+}
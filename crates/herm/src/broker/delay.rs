@@ -0,0 +1,403 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::protocol::TopicPartition;
+use crate::record::{now_ms, Record};
+
+/// How often delayed records are checked for being due, which is about how
+/// late they can be delivered.
+pub const DELIVERY_TICK: Duration = Duration::from_millis(10);
+
+/// Slots of the wheel delayed records wait in, each a tick wide.
+const WHEEL_SLOTS: usize = 512;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum DelayError {
+    #[error("Record due in {0:?}, over the most records can be delayed by")]
+    TooLate(Duration),
+    #[error("Holding {records} more records of {bytes} bytes would be over the limits")]
+    Full { records: usize, bytes: usize },
+}
+
+/// How many records [`DelayedRecords`] holds, and for how long.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DelayConfig {
+    pub max_records: usize,
+    /// Most bytes of records held, by [`Record::size`].
+    pub max_bytes: usize,
+    /// Furthest in the future records may be delivered.
+    pub max_delay: Duration,
+}
+
+impl Default for DelayConfig {
+    fn default() -> Self {
+        DelayConfig {
+            max_records: 100_000,
+            max_bytes: 64 * 1024 * 1024,
+            max_delay: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+/// A hashed timing wheel. Items sit in the slot of the tick their deadline
+/// falls in, those more than a turn of the wheel away sitting through the
+/// turns before, so inserting is constant time and advancing only looks at
+/// the slots passed.
+#[derive(Debug)]
+pub struct TimingWheel<T> {
+    tick_ms: u64,
+    slots: Vec<Vec<(u64, T)>>,
+    /// Tick up to which the wheel was advanced.
+    current: u64,
+    len: usize,
+}
+
+impl<T> TimingWheel<T> {
+    /// A wheel of `slots` slots, `tick` wide, starting at `now` in
+    /// milliseconds since the unix epoch.
+    pub fn new(tick: Duration, slots: usize, now: u64) -> Self {
+        let tick_ms = (tick.as_millis() as u64).max(1);
+        TimingWheel {
+            tick_ms,
+            slots: (0..slots.max(1)).map(|_| vec![]).collect(),
+            current: now / tick_ms,
+            len: 0,
+        }
+    }
+
+    /// Adds `item`, due at `deadline`. Items already due are handed out by
+    /// the next advance.
+    pub fn insert(&mut self, deadline: u64, item: T) {
+        let tick = (deadline / self.tick_ms).max(self.current);
+        let slot = (tick % self.slots.len() as u64) as usize;
+        self.slots[slot].push((deadline, item));
+        self.len += 1;
+    }
+
+    /// Takes the items due by `now`, by deadline and in the order they were
+    /// inserted for equal ones.
+    pub fn advance(&mut self, now: u64) -> Vec<T> {
+        let target = (now / self.tick_ms).max(self.current);
+        // A full turn looks at every slot
+        let ticks = (target - self.current + 1).min(self.slots.len() as u64);
+        let mut due = vec![];
+        for tick in target + 1 - ticks..=target {
+            let slot = (tick % self.slots.len() as u64) as usize;
+            let (ready, waiting) = std::mem::take(&mut self.slots[slot])
+                .into_iter()
+                .partition(|(deadline, _)| *deadline <= now);
+            self.slots[slot] = waiting;
+            due.extend(ready);
+        }
+        self.current = target;
+        self.len -= due.len();
+
+        due.sort_by_key(|(deadline, _)| *deadline);
+        due.into_iter().map(|(_, item)| item).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Records produced with a [`DELIVER_AT_HEADER`](crate::record::DELIVER_AT_HEADER)
+/// in the future, held in a [`TimingWheel`] until they are due, within the
+/// limits of a [`DelayConfig`].
+///
+/// They are only held in memory, so records not yet due when the broker
+/// stops are lost. Produces can't wait for them to be replicated.
+#[derive(Debug)]
+pub struct DelayedRecords {
+    config: DelayConfig,
+    state: Mutex<DelayState>,
+}
+
+#[derive(Debug)]
+struct DelayState {
+    wheel: TimingWheel<(TopicPartition, Record)>,
+    bytes: usize,
+    delivering: bool,
+}
+
+impl DelayedRecords {
+    pub fn new(config: DelayConfig) -> Self {
+        DelayedRecords {
+            config,
+            state: Mutex::new(DelayState {
+                wheel: TimingWheel::new(DELIVERY_TICK, WHEEL_SLOTS, now_ms()),
+                bytes: 0,
+                delivering: false,
+            }),
+        }
+    }
+
+    /// Holds `records` for `partition` until their
+    /// [`deliver_at`](Record::deliver_at) time, all of them or, if that
+    /// would be over the limits at `now`, none. Returns true when nothing is
+    /// delivering held records yet, in which case the caller is to start
+    /// delivering them.
+    pub fn hold(
+        &self,
+        partition: &TopicPartition,
+        records: Vec<Record>,
+        now: u64,
+    ) -> Result<bool, DelayError> {
+        let mut latest = now;
+        let mut bytes = 0;
+        for record in &records {
+            latest = latest.max(record.deliver_at().unwrap_or(now));
+            bytes += record.size();
+        }
+        let delay = Duration::from_millis(latest - now);
+        if delay > self.config.max_delay {
+            return Err(DelayError::TooLate(delay));
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if state.wheel.len() + records.len() > self.config.max_records
+            || state.bytes + bytes > self.config.max_bytes
+        {
+            return Err(DelayError::Full {
+                records: records.len(),
+                bytes,
+            });
+        }
+        state.bytes += bytes;
+        for record in records {
+            let deliver_at = record.deliver_at().unwrap_or(now);
+            state.wheel.insert(deliver_at, (partition.clone(), record));
+        }
+        Ok(!std::mem::replace(&mut state.delivering, true))
+    }
+
+    /// Takes the records due by `now`, grouped by partition in the order
+    /// they are due. `None` once no records are held, after which the next
+    /// [`hold`](DelayedRecords::hold) starts delivering again.
+    pub fn take_due(&self, now: u64) -> Option<BTreeMap<TopicPartition, Vec<Record>>> {
+        let mut state = self.state.lock().unwrap();
+        if state.wheel.is_empty() {
+            state.delivering = false;
+            return None;
+        }
+        let mut due: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for (partition, record) in state.wheel.advance(now) {
+            state.bytes -= record.size();
+            due.entry(partition).or_default().push(record);
+        }
+        Some(due)
+    }
+
+    /// Records held.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().wheel.len()
+    }
+
+    /// Bytes of records held.
+    pub fn bytes(&self) -> usize {
+        self.state.lock().unwrap().bytes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for DelayedRecords {
+    fn default() -> Self {
+        Self::new(DelayConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::broker::{LogHandler, Server};
+    use crate::client::Client;
+    use crate::protocol::ErrorCode;
+    use crate::record::RecordBatch;
+    use crate::request::{Acks, Fetch, Produce};
+    use crate::storage::{LogConfig, LogDirs};
+
+    #[test]
+    fn test_timing_wheel() {
+        let mut wheel = TimingWheel::new(Duration::from_millis(10), 4, 1000);
+        wheel.insert(1025, "b");
+        wheel.insert(1021, "a");
+        // More than a turn away
+        wheel.insert(1100, "d");
+        wheel.insert(1025, "c");
+        // Already due
+        wheel.insert(500, "now");
+        assert_eq!(wheel.len(), 5);
+
+        assert_eq!(wheel.advance(1005), ["now"]);
+        assert!(wheel.advance(1020).is_empty());
+        assert_eq!(wheel.advance(1025), ["a", "b", "c"]);
+        assert_eq!(wheel.advance(1099), Vec::<&str>::new());
+        assert_eq!(wheel.advance(5000), ["d"]);
+        assert!(wheel.is_empty());
+    }
+
+    fn record(value: &'static str, deliver_at: u64) -> Record {
+        Record::new(None, Some(Bytes::from_static(value.as_bytes()))).with_deliver_at(deliver_at)
+    }
+
+    #[test]
+    fn test_take_due() {
+        let delayed = DelayedRecords::default();
+        let partition = TopicPartition::new("jobs", 0);
+        let now = now_ms();
+        assert_eq!(
+            delayed.hold(&partition, vec![record("a", now + 60_000)], now),
+            Ok(true)
+        );
+        assert_eq!(
+            delayed.hold(&partition, vec![record("b", now)], now),
+            Ok(false)
+        );
+
+        let due = delayed.take_due(now).unwrap();
+        assert_eq!(due[&partition].len(), 1);
+        assert_eq!(delayed.len(), 1);
+        let due = delayed.take_due(now + 60_000).unwrap();
+        assert_eq!(due[&partition][0].value, record("a", 0).value);
+        assert_eq!(delayed.bytes(), 0);
+        // Delivery stops once nothing is held, the next hold starts it again
+        assert!(delayed.take_due(now + 60_000).is_none());
+        assert_eq!(
+            delayed.hold(&partition, vec![record("c", now)], now),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let now = now_ms();
+        let size = record("a", now).size();
+        let delayed = DelayedRecords::new(DelayConfig {
+            max_records: 3,
+            max_bytes: 2 * size,
+            max_delay: Duration::from_secs(60),
+        });
+        let partition = TopicPartition::new("jobs", 0);
+
+        assert_eq!(
+            delayed.hold(&partition, vec![record("a", now + 61_000)], now),
+            Err(DelayError::TooLate(Duration::from_secs(61)))
+        );
+        delayed
+            .hold(&partition, vec![record("a", now + 1000)], now)
+            .unwrap();
+        // Batches over the limits hold none of their records
+        let batch = vec![record("b", now + 1000), record("c", now + 1000)];
+        assert_eq!(
+            delayed.hold(&partition, batch, now),
+            Err(DelayError::Full {
+                records: 2,
+                bytes: 2 * size
+            })
+        );
+        assert_eq!(delayed.len(), 1);
+
+        // Delivering frees room
+        delayed.take_due(now + 1000).unwrap();
+        let batch = vec![record("b", now + 1000), record("c", now + 1000)];
+        delayed.hold(&partition, batch, now).unwrap();
+        assert_eq!(delayed.bytes(), 2 * size);
+    }
+
+    #[tokio::test]
+    async fn test_delayed_delivery() {
+        let handler = LogHandler::new(Arc::new(LogDirs::in_memory(LogConfig::default())));
+        let partition = TopicPartition::new("jobs", 0);
+        handler.logs().create(&partition).unwrap();
+        let server = Server::bind("127.0.0.1:0", handler.clone()).await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        handler.replicas().set_advertised_listener(addr.clone());
+        tokio::spawn(server.run());
+        let client = Client::new(vec![addr]);
+
+        let later =
+            Record::new(None, Some(Bytes::from_static(b"later"))).with_deliver_at(now_ms() + 200);
+        let now = Record::new(None, Some(Bytes::from_static(b"now")));
+        let produce = Produce::new("jobs".into(), 0, RecordBatch::new(vec![later, now])).unwrap();
+        client.produce(produce).await.unwrap();
+        assert_eq!(handler.delayed().len(), 1);
+
+        let fetch = |offset| Fetch::new("jobs".into(), 0, offset, u32::MAX).unwrap();
+        let values = |batches: Vec<RecordBatch>| {
+            batches
+                .into_iter()
+                .flat_map(|batch| batch.records)
+                .map(|record| record.value.unwrap())
+                .collect::<Vec<_>>()
+        };
+        let fetched = client.fetch(fetch(0)).await.unwrap();
+        assert_eq!(values(fetched.batches), ["now"]);
+
+        // Long polls are woken by the delivery
+        let fetched = client.fetch(fetch(1).wait_for(1, 5000)).await.unwrap();
+        let batches = fetched.batches;
+        assert_eq!(batches[0].base_offset, 1);
+        assert_eq!(values(batches), ["later"]);
+        assert!(handler.delayed().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delayed_produce_errors() {
+        let handler = LogHandler::new(Arc::new(LogDirs::in_memory(LogConfig::default())))
+            .with_delay_config(DelayConfig {
+                max_records: 1,
+                ..Default::default()
+            });
+        let partition = TopicPartition::new("jobs", 0);
+        handler.logs().create(&partition).unwrap();
+        let server = Server::bind("127.0.0.1:0", handler.clone()).await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        handler.replicas().set_advertised_listener(addr.clone());
+        tokio::spawn(server.run());
+        let client = Client::new(vec![addr]);
+
+        let produce = |deliver_at: u64| {
+            let records = vec![
+                Record::new(None, Some(Bytes::from_static(b"now"))),
+                record("later", deliver_at),
+            ];
+            Produce::new("jobs".into(), 0, RecordBatch::new(records)).unwrap()
+        };
+        let error = |produce| async {
+            let response = client.produce(produce).await.unwrap().unwrap();
+            response.error
+        };
+        let now = now_ms();
+        assert_eq!(
+            error(produce(now + 60_000).with_acks(Acks::All)).await,
+            ErrorCode::InvalidRequiredAcks
+        );
+        assert_eq!(
+            error(produce(now + 3_600_000)).await,
+            ErrorCode::InvalidTimestamp
+        );
+        assert_eq!(error(produce(now + 60_000)).await, ErrorCode::None);
+        assert_eq!(
+            error(produce(now + 60_000)).await,
+            ErrorCode::PolicyViolation
+        );
+
+        // Refused batches append none of their records
+        let log = handler.logs().get(&partition).unwrap();
+        assert_eq!(log.read().unwrap().next_offset(), 1);
+        assert_eq!(handler.delayed().len(), 1);
+    }
+}
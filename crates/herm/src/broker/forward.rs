@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::replication::{BrokerConnection, ReplicationError};
+use crate::request::Request;
+use crate::response::Response;
+
+/// How long connecting to a leader may take before the request fails.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the leader may take to answer before the request fails, by
+/// default. Covers the time fetches are held for new records.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connections kept open to each leader between requests.
+const MAX_IDLE_CONNECTIONS: usize = 8;
+
+/// Relays requests to the leader of their partition and hands back its
+/// response, for clients that can only reach some of the brokers.
+///
+/// Requests go over [`BrokerConnection`]s, so the leader sees them as coming
+/// from this broker, with its client id. Connections are reused once a
+/// response is read, those that fail or time out are dropped.
+#[derive(Debug)]
+pub struct Forwarder {
+    idle: Mutex<HashMap<String, Vec<BrokerConnection>>>,
+    request_timeout: Duration,
+}
+
+impl Default for Forwarder {
+    fn default() -> Self {
+        Forwarder {
+            idle: Mutex::default(),
+            request_timeout: REQUEST_TIMEOUT,
+        }
+    }
+}
+
+impl Forwarder {
+    pub fn new() -> Self {
+        Forwarder::default()
+    }
+
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Sends `request` to the broker at `leader` and waits for its response,
+    /// failing with [`ReplicationError::TimedOut`] if it takes longer than
+    /// the request timeout.
+    pub async fn forward(
+        &self,
+        leader: &str,
+        request: Request,
+    ) -> Result<Response, ReplicationError> {
+        let idle = self.idle.lock().unwrap().get_mut(leader).and_then(Vec::pop);
+        let mut connection = match idle {
+            Some(connection) => connection,
+            None => tokio::time::timeout(CONNECT_TIMEOUT, BrokerConnection::connect(leader))
+                .await
+                .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??,
+        };
+        // A response that comes late would be read as the next one's
+        let response = tokio::time::timeout(self.request_timeout, connection.call(request))
+            .await
+            .map_err(|_| ReplicationError::TimedOut)??;
+
+        let mut idle = self.idle.lock().unwrap();
+        let connections = idle.entry(leader.to_string()).or_default();
+        if connections.len() < MAX_IDLE_CONNECTIONS {
+            connections.push(connection);
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::request::ListOffsets;
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        // Takes connections but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let leader = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let mut streams = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+
+        let forwarder = Forwarder::new().with_request_timeout(Duration::from_millis(50));
+        let request = ListOffsets::latest("events".to_string(), 0).unwrap();
+        let result = forwarder.forward(&leader, request.into()).await;
+        assert!(matches!(result, Err(ReplicationError::TimedOut)));
+        assert!(forwarder.idle.lock().unwrap().get(&leader).is_none());
+    }
+}
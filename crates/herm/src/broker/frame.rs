@@ -0,0 +1,186 @@
+use std::io;
+
+use bytes::{Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Largest frame read unless configured otherwise.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 100 * 1024 * 1024;
+
+/// Buffer first reserved for a frame, grown as more of it is read.
+const INITIAL_FRAME_CAPACITY: usize = 64 * 1024;
+
+/// A frame read by [`read_frame_or_skip`].
+#[derive(Debug, PartialEq)]
+pub(super) enum Frame {
+    Whole(Bytes),
+    /// A frame over the limit, of which only the first bytes were kept and
+    /// the rest read past.
+    Oversized {
+        head: Bytes,
+        len: usize,
+    },
+}
+
+/// Reads one frame, a u32 length followed by that many bytes, returning the
+/// bytes. Returns `None` if the stream ends between frames.
+pub async fn read_frame(
+    reader: &mut (impl AsyncRead + Unpin),
+    max_size: usize,
+) -> io::Result<Option<Bytes>> {
+    let Some(len) = read_len(reader).await? else {
+        return Ok(None);
+    };
+    if len > max_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {} bytes is over the {} byte limit", len, max_size),
+        ));
+    }
+    read_body(reader, len).await.map(Some)
+}
+
+/// Like [`read_frame`], but a frame over `max_size` doesn't end the stream:
+/// its first `head_size` bytes are kept and the rest skipped, leaving the
+/// reader at the next frame.
+pub(super) async fn read_frame_or_skip(
+    reader: &mut (impl AsyncRead + Unpin),
+    max_size: usize,
+    head_size: usize,
+) -> io::Result<Option<Frame>> {
+    let Some(len) = read_len(reader).await? else {
+        return Ok(None);
+    };
+    if len <= max_size {
+        return Ok(Some(Frame::Whole(read_body(reader, len).await?)));
+    }
+
+    let head = read_body(reader, len.min(head_size)).await?;
+    let rest = (len - head.len()) as u64;
+    let skipped = tokio::io::copy(&mut (&mut *reader).take(rest), &mut tokio::io::sink()).await?;
+    if skipped < rest {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(Some(Frame::Oversized { head, len }))
+}
+
+/// Reads a frame's length, `None` if the stream ends first.
+async fn read_len(reader: &mut (impl AsyncRead + Unpin)) -> io::Result<Option<usize>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len).await {
+        Ok(_) => Ok(Some(u32::from_be_bytes(len) as usize)),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+async fn read_body(reader: &mut (impl AsyncRead + Unpin), len: usize) -> io::Result<Bytes> {
+    // Grow the buffer as bytes arrive rather than trusting the length, so
+    // a client claiming a huge frame has to actually send it
+    let mut frame = BytesMut::with_capacity(len.min(INITIAL_FRAME_CAPACITY));
+    while frame.len() < len {
+        let remaining = len - frame.len();
+        frame.reserve(remaining.min(frame.capacity().max(INITIAL_FRAME_CAPACITY)));
+        if (&mut *reader)
+            .take(remaining as u64)
+            .read_buf(&mut frame)
+            .await?
+            == 0
+        {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+    }
+    Ok(frame.freeze())
+}
+
+/// Writes `frame` with its u32 length prefix. Doesn't flush.
+pub async fn write_frame(writer: &mut (impl AsyncWrite + Unpin), frame: &[u8]) -> io::Result<()> {
+    writer.write_u32(frame.len() as u32).await?;
+    writer.write_all(frame).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let mut buf = Vec::new();
+        write_frame(&mut buf, b"hello").await.unwrap();
+        write_frame(&mut buf, b"").await.unwrap();
+        assert_eq!(buf.len(), 4 + 5 + 4);
+
+        let mut reader = buf.as_slice();
+        assert_eq!(
+            read_frame(&mut reader, 16).await.unwrap().unwrap(),
+            &b"hello"[..]
+        );
+        assert!(read_frame(&mut reader, 16)
+            .await
+            .unwrap()
+            .unwrap()
+            .is_empty());
+        assert!(read_frame(&mut reader, 16).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_large_frame() {
+        let payload: Vec<u8> = (0..5 * INITIAL_FRAME_CAPACITY + 3)
+            .map(|i| i as u8)
+            .collect();
+        let mut buf = Vec::new();
+        write_frame(&mut buf, &payload).await.unwrap();
+
+        let frame = read_frame(&mut buf.as_slice(), DEFAULT_MAX_FRAME_SIZE)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame, payload);
+    }
+
+    #[tokio::test]
+    async fn test_limits() {
+        let mut buf = Vec::new();
+        write_frame(&mut buf, b"hello").await.unwrap();
+
+        let err = read_frame(&mut buf.as_slice(), 4).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Ends inside the frame
+        let err = read_frame(&mut &buf[..6], 16).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn test_skip_oversized() {
+        let mut buf = Vec::new();
+        write_frame(&mut buf, b"hello world").await.unwrap();
+        write_frame(&mut buf, b"next").await.unwrap();
+
+        let mut reader = buf.as_slice();
+        assert_eq!(
+            read_frame_or_skip(&mut reader, 8, 5)
+                .await
+                .unwrap()
+                .unwrap(),
+            Frame::Oversized {
+                head: Bytes::from_static(b"hello"),
+                len: 11
+            }
+        );
+        assert_eq!(
+            read_frame_or_skip(&mut reader, 8, 5)
+                .await
+                .unwrap()
+                .unwrap(),
+            Frame::Whole(Bytes::from_static(b"next"))
+        );
+        assert!(read_frame_or_skip(&mut reader, 8, 5)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Ends inside the skipped bytes
+        let err = read_frame_or_skip(&mut &buf[..12], 8, 5).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
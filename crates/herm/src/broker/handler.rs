@@ -0,0 +1,557 @@
+use std::future::Future;
+
+use super::RequestContext;
+use crate::auth::{Authorizer, Operation, Resource};
+use crate::protocol::ErrorCode;
+use crate::request::{
+    BrokerHeartbeat, CreateTopic, DeleteTopic, DescribeCluster, DescribeGroups, DescribeLogDirs,
+    EstimateCleanup, Fetch, Heartbeat, JoinGroup, LeaderAndIsr, LeaveGroup, ListGroups,
+    ListOffsets, Metadata, OffsetCommit, Produce, ReassignPartition, Request, SyncGroup,
+};
+use crate::response::{
+    AdminResponse, DescribeClusterResponse, DescribeGroupsResponse, DescribeLogDirsResponse,
+    EstimateCleanupResponse, FetchResponse, GroupDescription, JoinGroupResponse,
+    ListGroupsResponse, ListOffsetsResponse, MetadataResponse, ProduceResponse, Response,
+    SyncGroupResponse,
+};
+
+/// What the broker does with each decoded request. The server only deals with
+/// framing and headers and hands the bodies to a handler, so a handler can be
+/// swapped in to stub out storage in tests or to forward requests elsewhere.
+///
+/// Failures are reported through the error code on the response, the
+/// connection stays open either way. Handlers may take their time, for
+/// example to hold a fetch until data arrives, without blocking other
+/// requests on the connection.
+pub trait Handler: Send + Sync + 'static {
+    fn handle_produce(
+        &self,
+        context: &RequestContext,
+        produce: Produce,
+    ) -> impl Future<Output = ProduceResponse> + Send;
+
+    fn handle_fetch(
+        &self,
+        context: &RequestContext,
+        fetch: Fetch,
+    ) -> impl Future<Output = FetchResponse> + Send;
+
+    /// Handlers without an offset index refuse offset lookups.
+    fn handle_list_offsets(
+        &self,
+        _context: &RequestContext,
+        _request: ListOffsets,
+    ) -> impl Future<Output = ListOffsetsResponse> + Send {
+        async { ListOffsetsResponse::error(ErrorCode::InvalidRequest) }
+    }
+
+    /// Handlers without log dirs refuse to create and delete topics.
+    fn handle_create_topic(
+        &self,
+        _context: &RequestContext,
+        _request: CreateTopic,
+    ) -> impl Future<Output = AdminResponse> + Send {
+        async { AdminResponse::error(ErrorCode::InvalidRequest) }
+    }
+
+    fn handle_delete_topic(
+        &self,
+        _context: &RequestContext,
+        _request: DeleteTopic,
+    ) -> impl Future<Output = AdminResponse> + Send {
+        async { AdminResponse::error(ErrorCode::InvalidRequest) }
+    }
+
+    /// Handlers without a cluster refuse metadata and heartbeats.
+    fn handle_metadata(
+        &self,
+        _context: &RequestContext,
+        _request: Metadata,
+    ) -> impl Future<Output = MetadataResponse> + Send {
+        async { MetadataResponse::error(ErrorCode::InvalidRequest) }
+    }
+
+    fn handle_broker_heartbeat(
+        &self,
+        _context: &RequestContext,
+        _request: BrokerHeartbeat,
+    ) -> impl Future<Output = AdminResponse> + Send {
+        async { AdminResponse::error(ErrorCode::InvalidRequest) }
+    }
+
+    fn handle_describe_cluster(
+        &self,
+        _context: &RequestContext,
+        _request: DescribeCluster,
+    ) -> impl Future<Output = DescribeClusterResponse> + Send {
+        async { DescribeClusterResponse::error(ErrorCode::InvalidRequest) }
+    }
+
+    /// Handlers without log dirs refuse to describe them.
+    fn handle_describe_log_dirs(
+        &self,
+        _context: &RequestContext,
+        _request: DescribeLogDirs,
+    ) -> impl Future<Output = DescribeLogDirsResponse> + Send {
+        async { DescribeLogDirsResponse::error(ErrorCode::InvalidRequest) }
+    }
+
+    /// Handlers without log dirs refuse to estimate cleanups.
+    fn handle_estimate_cleanup(
+        &self,
+        _context: &RequestContext,
+        _request: EstimateCleanup,
+    ) -> impl Future<Output = EstimateCleanupResponse> + Send {
+        async { EstimateCleanupResponse::error(ErrorCode::InvalidRequest) }
+    }
+
+    /// Handlers without a group coordinator refuse the group requests.
+    fn handle_offset_commit(
+        &self,
+        _context: &RequestContext,
+        _request: OffsetCommit,
+    ) -> impl Future<Output = AdminResponse> + Send {
+        async { AdminResponse::error(ErrorCode::InvalidRequest) }
+    }
+
+    fn handle_list_groups(
+        &self,
+        _context: &RequestContext,
+        _request: ListGroups,
+    ) -> impl Future<Output = ListGroupsResponse> + Send {
+        async { ListGroupsResponse::error(ErrorCode::InvalidRequest) }
+    }
+
+    fn handle_describe_groups(
+        &self,
+        _context: &RequestContext,
+        _request: DescribeGroups,
+    ) -> impl Future<Output = DescribeGroupsResponse> + Send {
+        async { DescribeGroupsResponse::error(ErrorCode::InvalidRequest) }
+    }
+
+    /// May hold the response until the group's other members rejoined.
+    fn handle_join_group(
+        &self,
+        _context: &RequestContext,
+        _request: JoinGroup,
+    ) -> impl Future<Output = JoinGroupResponse> + Send {
+        async { JoinGroupResponse::error(ErrorCode::InvalidRequest) }
+    }
+
+    /// May hold the response until the group leader sent the assignments.
+    fn handle_sync_group(
+        &self,
+        _context: &RequestContext,
+        _request: SyncGroup,
+    ) -> impl Future<Output = SyncGroupResponse> + Send {
+        async { SyncGroupResponse::error(ErrorCode::InvalidRequest) }
+    }
+
+    fn handle_heartbeat(
+        &self,
+        _context: &RequestContext,
+        _request: Heartbeat,
+    ) -> impl Future<Output = AdminResponse> + Send {
+        async { AdminResponse::error(ErrorCode::InvalidRequest) }
+    }
+
+    fn handle_leave_group(
+        &self,
+        _context: &RequestContext,
+        _request: LeaveGroup,
+    ) -> impl Future<Output = AdminResponse> + Send {
+        async { AdminResponse::error(ErrorCode::InvalidRequest) }
+    }
+
+    /// Handlers without replication refuse the admin requests.
+    fn handle_leader_and_isr(
+        &self,
+        _context: &RequestContext,
+        _request: LeaderAndIsr,
+    ) -> impl Future<Output = AdminResponse> + Send {
+        async { AdminResponse::error(ErrorCode::InvalidRequest) }
+    }
+
+    fn handle_reassign(
+        &self,
+        _context: &RequestContext,
+        _request: ReassignPartition,
+    ) -> impl Future<Output = AdminResponse> + Send {
+        async { AdminResponse::error(ErrorCode::InvalidRequest) }
+    }
+}
+
+/// Routes `request` to the `handler` method for its api key, once the
+/// `authorizer` lets the principal in `context` through. Produce needs write
+/// and fetch and listing offsets need read on the topic, creating and
+/// deleting a topic need create and delete on it, estimating a cleanup needs
+/// describe on the topic, the admin requests and
+/// heartbeats need alter on the cluster, and describing the cluster or its
+/// log dirs needs describe on it. Denied requests never reach the handler.
+///
+/// Metadata only lists the topics the principal may describe. Topics asked
+/// for by name that it may not come back with
+/// [`ErrorCode::TopicAuthorizationFailed`].
+///
+/// Committing offsets needs read on the group and on every topic committed
+/// to, joining, syncing, leaving and heartbeating need read on the group.
+/// Listing groups only lists those the principal may describe, and
+/// describing a group it may not comes back with
+/// [`ErrorCode::GroupAuthorizationFailed`].
+pub async fn dispatch<H: Handler>(
+    handler: &H,
+    authorizer: &dyn Authorizer,
+    context: &RequestContext,
+    request: Request,
+) -> Response {
+    let allowed = |operation, topic: &str| {
+        authorizer.authorize(&context.principal, operation, Resource::Topic(topic))
+    };
+    let allowed_group = |operation, group: &str| {
+        authorizer.authorize(&context.principal, operation, Resource::Group(group))
+    };
+    let allowed_cluster =
+        |operation| authorizer.authorize(&context.principal, operation, Resource::Cluster);
+
+    match request {
+        Request::Produce(produce) => {
+            if !allowed(Operation::Write, produce.topic()) {
+                return ProduceResponse::error(ErrorCode::TopicAuthorizationFailed).into();
+            }
+            handler.handle_produce(context, produce).await.into()
+        }
+        Request::Fetch(fetch) => {
+            if !allowed(Operation::Read, fetch.topic()) {
+                return FetchResponse::error(ErrorCode::TopicAuthorizationFailed).into();
+            }
+            // Replicas read past the high watermark and join the ISR
+            if fetch.replica_id().is_some() && !allowed_cluster(Operation::Alter) {
+                return FetchResponse::error(ErrorCode::ClusterAuthorizationFailed).into();
+            }
+            handler.handle_fetch(context, fetch).await.into()
+        }
+        Request::ListOffsets(request) => {
+            if !allowed(Operation::Read, request.topic()) {
+                return ListOffsetsResponse::error(ErrorCode::TopicAuthorizationFailed).into();
+            }
+            handler.handle_list_offsets(context, request).await.into()
+        }
+        Request::CreateTopic(request) => {
+            if !allowed(Operation::Create, request.topic()) {
+                return Response::CreateTopic(AdminResponse::error(
+                    ErrorCode::TopicAuthorizationFailed,
+                ));
+            }
+            Response::CreateTopic(handler.handle_create_topic(context, request).await)
+        }
+        Request::DeleteTopic(request) => {
+            if !allowed(Operation::Delete, request.topic()) {
+                return Response::DeleteTopic(AdminResponse::error(
+                    ErrorCode::TopicAuthorizationFailed,
+                ));
+            }
+            Response::DeleteTopic(handler.handle_delete_topic(context, request).await)
+        }
+        Request::EstimateCleanup(request) => {
+            if !allowed(Operation::Describe, request.topic()) {
+                return EstimateCleanupResponse::error(ErrorCode::TopicAuthorizationFailed).into();
+            }
+            handler
+                .handle_estimate_cleanup(context, request)
+                .await
+                .into()
+        }
+        Request::Metadata(request) => {
+            let named = !request.topics().is_empty();
+            let mut response = handler.handle_metadata(context, request).await;
+            response.topics.retain_mut(|metadata| {
+                if allowed(Operation::Describe, &metadata.topic) {
+                    return true;
+                }
+                metadata.error = ErrorCode::TopicAuthorizationFailed;
+                metadata.partitions.clear();
+                named
+            });
+            Response::Metadata(response)
+        }
+        Request::OffsetCommit(request) => {
+            if !allowed_group(Operation::Read, request.group()) {
+                return Response::OffsetCommit(AdminResponse::error(
+                    ErrorCode::GroupAuthorizationFailed,
+                ));
+            }
+            let topics_allowed = request
+                .offsets()
+                .iter()
+                .all(|(partition, _)| allowed(Operation::Read, &partition.topic));
+            if !topics_allowed {
+                return Response::OffsetCommit(AdminResponse::error(
+                    ErrorCode::TopicAuthorizationFailed,
+                ));
+            }
+            Response::OffsetCommit(handler.handle_offset_commit(context, request).await)
+        }
+        Request::ListGroups(request) => {
+            let mut response = handler.handle_list_groups(context, request).await;
+            response
+                .groups
+                .retain(|(group, _)| allowed_group(Operation::Describe, group));
+            response.into()
+        }
+        Request::DescribeGroups(request) => {
+            let mut response = handler.handle_describe_groups(context, request).await;
+            for description in &mut response.groups {
+                if !allowed_group(Operation::Describe, &description.group) {
+                    *description = GroupDescription::error(
+                        std::mem::take(&mut description.group),
+                        ErrorCode::GroupAuthorizationFailed,
+                    );
+                }
+            }
+            response.into()
+        }
+        Request::JoinGroup(request) => {
+            if !allowed_group(Operation::Read, request.group()) {
+                return JoinGroupResponse::error(ErrorCode::GroupAuthorizationFailed).into();
+            }
+            handler.handle_join_group(context, request).await.into()
+        }
+        Request::SyncGroup(request) => {
+            if !allowed_group(Operation::Read, request.group()) {
+                return SyncGroupResponse::error(ErrorCode::GroupAuthorizationFailed).into();
+            }
+            handler.handle_sync_group(context, request).await.into()
+        }
+        Request::Heartbeat(request) => {
+            if !allowed_group(Operation::Read, request.group()) {
+                return Response::Heartbeat(AdminResponse::error(
+                    ErrorCode::GroupAuthorizationFailed,
+                ));
+            }
+            Response::Heartbeat(handler.handle_heartbeat(context, request).await)
+        }
+        Request::LeaveGroup(request) => {
+            if !allowed_group(Operation::Read, request.group()) {
+                return Response::LeaveGroup(AdminResponse::error(
+                    ErrorCode::GroupAuthorizationFailed,
+                ));
+            }
+            Response::LeaveGroup(handler.handle_leave_group(context, request).await)
+        }
+        Request::LeaderAndIsr(request) => {
+            if !allowed_cluster(Operation::Alter) {
+                return Response::LeaderAndIsr(AdminResponse::error(
+                    ErrorCode::ClusterAuthorizationFailed,
+                ));
+            }
+            Response::LeaderAndIsr(handler.handle_leader_and_isr(context, request).await)
+        }
+        Request::ReassignPartition(request) => {
+            if !allowed_cluster(Operation::Alter) {
+                return Response::ReassignPartition(AdminResponse::error(
+                    ErrorCode::ClusterAuthorizationFailed,
+                ));
+            }
+            Response::ReassignPartition(handler.handle_reassign(context, request).await)
+        }
+        Request::BrokerHeartbeat(request) => {
+            if !allowed_cluster(Operation::Alter) {
+                return Response::BrokerHeartbeat(AdminResponse::error(
+                    ErrorCode::ClusterAuthorizationFailed,
+                ));
+            }
+            Response::BrokerHeartbeat(handler.handle_broker_heartbeat(context, request).await)
+        }
+        Request::DescribeCluster(request) => {
+            if !allowed_cluster(Operation::Describe) {
+                return DescribeClusterResponse::error(ErrorCode::ClusterAuthorizationFailed)
+                    .into();
+            }
+            handler
+                .handle_describe_cluster(context, request)
+                .await
+                .into()
+        }
+        Request::DescribeLogDirs(request) => {
+            if !allowed_cluster(Operation::Describe) {
+                return DescribeLogDirsResponse::error(ErrorCode::ClusterAuthorizationFailed)
+                    .into();
+            }
+            handler
+                .handle_describe_log_dirs(context, request)
+                .await
+                .into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::auth::{AclAuthorizer, AllowAll};
+    use crate::broker::Principal;
+    use crate::protocol::TopicPartition;
+    use crate::record::RecordBatch;
+    use crate::response::GroupState;
+
+    struct Stub;
+
+    impl Handler for Stub {
+        async fn handle_produce(&self, _: &RequestContext, produce: Produce) -> ProduceResponse {
+            ProduceResponse::new(produce.partition() as u64)
+        }
+
+        async fn handle_fetch(&self, _: &RequestContext, _: Fetch) -> FetchResponse {
+            FetchResponse::error(ErrorCode::OffsetOutOfRange)
+        }
+
+        async fn handle_list_groups(
+            &self,
+            _: &RequestContext,
+            _: ListGroups,
+        ) -> ListGroupsResponse {
+            ListGroupsResponse::new(vec![
+                ("billing".to_string(), GroupState::Empty),
+                ("search".to_string(), GroupState::Empty),
+            ])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dispatch() {
+        let produce = Produce::new("events".to_string(), 7, RecordBatch::new(vec![])).unwrap();
+        assert_eq!(
+            dispatch(&Stub, &AllowAll, &RequestContext::default(), produce.into()).await,
+            Response::Produce(ProduceResponse::new(7))
+        );
+
+        let fetch = Fetch::new("events".to_string(), 0, 0, 1024).unwrap();
+        assert_eq!(
+            dispatch(&Stub, &AllowAll, &RequestContext::default(), fetch.into()).await,
+            Response::Fetch(FetchResponse::error(ErrorCode::OffsetOutOfRange))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_unauthorized() {
+        let authorizer = AclAuthorizer::parse("allow User:alice * topic:events").unwrap();
+        let alice = RequestContext {
+            principal: Principal::User("alice".to_string()),
+            ..Default::default()
+        };
+        let produce = Produce::new("events".to_string(), 7, RecordBatch::new(vec![])).unwrap();
+        let fetch = Fetch::new("events".to_string(), 0, 0, 1024).unwrap();
+
+        assert_eq!(
+            dispatch(&Stub, &authorizer, &alice, produce.clone().into()).await,
+            Response::Produce(ProduceResponse::new(7))
+        );
+        assert_eq!(
+            dispatch(
+                &Stub,
+                &authorizer,
+                &RequestContext::default(),
+                produce.into()
+            )
+            .await,
+            Response::Produce(ProduceResponse::error(ErrorCode::TopicAuthorizationFailed))
+        );
+        assert_eq!(
+            dispatch(
+                &Stub,
+                &authorizer,
+                &RequestContext::default(),
+                fetch.clone().into()
+            )
+            .await,
+            Response::Fetch(FetchResponse::error(ErrorCode::TopicAuthorizationFailed))
+        );
+        // Reading the topic isn't enough to fetch as a replica
+        assert_eq!(
+            dispatch(
+                &Stub,
+                &authorizer,
+                &alice,
+                fetch.clone().from_replica(1).into()
+            )
+            .await,
+            Response::Fetch(FetchResponse::error(ErrorCode::ClusterAuthorizationFailed))
+        );
+
+        let reassign =
+            ReassignPartition::new("events".to_string(), 0, 1, "b:9092".to_string()).unwrap();
+        assert_eq!(
+            dispatch(&Stub, &authorizer, &alice, reassign.clone().into()).await,
+            Response::ReassignPartition(AdminResponse::error(
+                ErrorCode::ClusterAuthorizationFailed
+            ))
+        );
+        let authorizer = AclAuthorizer::parse(
+            "allow User:alice alter cluster\n\
+             allow User:alice read topic:events\n",
+        )
+        .unwrap();
+        assert_eq!(
+            dispatch(&Stub, &authorizer, &alice, reassign.into()).await,
+            Response::ReassignPartition(AdminResponse::error(ErrorCode::InvalidRequest))
+        );
+        assert_eq!(
+            dispatch(&Stub, &authorizer, &alice, fetch.from_replica(1).into()).await,
+            Response::Fetch(FetchResponse::error(ErrorCode::OffsetOutOfRange))
+        );
+        // Altering the cluster doesn't let alice describe it
+        assert_eq!(
+            dispatch(&Stub, &authorizer, &alice, DescribeCluster::new().into()).await,
+            Response::DescribeCluster(DescribeClusterResponse::error(
+                ErrorCode::ClusterAuthorizationFailed
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_groups_unauthorized() {
+        let authorizer = AclAuthorizer::parse(
+            "allow User:alice * group:billing\n\
+             allow User:alice read topic:events\n",
+        )
+        .unwrap();
+        let alice = RequestContext {
+            principal: Principal::User("alice".to_string()),
+            ..Default::default()
+        };
+
+        let Response::ListGroups(listed) =
+            dispatch(&Stub, &authorizer, &alice, ListGroups::new().into()).await
+        else {
+            panic!("expected a ListGroups response");
+        };
+        assert_eq!(
+            listed.groups,
+            vec![("billing".to_string(), GroupState::Empty)]
+        );
+
+        let commit = |group: &str, topic: &str| -> Request {
+            OffsetCommit::new(group.to_string(), vec![(TopicPartition::new(topic, 0), 1)])
+                .unwrap()
+                .into()
+        };
+        for (request, error) in [
+            (
+                commit("search", "events"),
+                ErrorCode::GroupAuthorizationFailed,
+            ),
+            (
+                commit("billing", "orders"),
+                ErrorCode::TopicAuthorizationFailed,
+            ),
+            (commit("billing", "events"), ErrorCode::InvalidRequest),
+        ] {
+            assert_eq!(
+                dispatch(&Stub, &authorizer, &alice, request).await,
+                Response::OffsetCommit(AdminResponse::error(error))
+            );
+        }
+    }
+}
@@ -0,0 +1,1029 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use tracing::Instrument;
+
+use super::{
+    DelayConfig, DelayError, DelayedRecords, FetchPurgatory, Forwarder, Handler, Principal,
+    RequestContext, DELIVERY_TICK,
+};
+use crate::auth::{AllowAll, Authorizer, Operation, Resource};
+use crate::cluster::{Cluster, ClusterConfig};
+use crate::events::{Event, EventBus};
+use crate::group::GroupCoordinator;
+use crate::protocol::{ErrorCode, TopicPartition};
+use crate::record::{now_ms, Record, RecordBatch};
+use crate::replication::{ReplicaConfig, ReplicaManager, ReplicationError, REPLICA_CLIENT_ID};
+use crate::request::{
+    Acks, BrokerHeartbeat, CreateTopic, DeleteTopic, DescribeCluster, DescribeGroups,
+    DescribeLogDirs, EstimateCleanup, Fetch, Heartbeat, JoinGroup, LeaderAndIsr, LeaveGroup,
+    ListGroups, ListOffsets, Metadata, OffsetCommit, Produce, ReassignPartition, Request,
+    SyncGroup, EARLIEST_TIMESTAMP, LATEST_TIMESTAMP,
+};
+use crate::response::{
+    AdminResponse, DescribeClusterResponse, DescribeGroupsResponse, DescribeLogDirsResponse,
+    EstimateCleanupResponse, FetchResponse, JoinGroupResponse, ListGroupsResponse,
+    ListOffsetsResponse, LogDirDescription, MetadataResponse, PartitionCleanupEstimate,
+    PartitionLogDescription, ProduceResponse, Response, SyncGroupResponse, NO_OFFSET,
+};
+use crate::storage::{FlushPolicy, Log, LogConfig, LogDirError, LogDirs, LogError};
+use crate::tenant::{NamespaceError, Namespaces};
+use crate::transform::TransformChain;
+
+/// Serves requests from the partitions in a set of log dirs. This is the
+/// handler the broker runs with. Fetches that ask to wait for data are
+/// parked in a [`FetchPurgatory`] until a produce to their partition, and
+/// [`Acks::All`] produces are held until their batch is flushed and below
+/// the high watermark.
+///
+/// Consumers only see records below the high watermark, which followers
+/// move along as they fetch, see
+/// [`IsrTracker`](crate::replication::IsrTracker). Partitions this broker
+/// follows refuse produces, see [`ReplicaManager`]. Metadata comes from
+/// the [`Cluster`] and the partitions led here. Consumer groups commit their
+/// offsets to a [`GroupCoordinator`], in memory unless one is given.
+///
+/// Records produced to be delivered later are held in [`DelayedRecords`]
+/// and appended once due. Their offsets aren't known when they are
+/// produced, so produces holding any are answered with [`NO_OFFSET`]. They
+/// are only held in memory, so [`Acks::All`] produces of them are refused
+/// with [`ErrorCode::InvalidRequiredAcks`].
+///
+/// Produces, fetches and offset lookups for partitions followed here are
+/// answered with [`ErrorCode::NotLeaderOrFollower`], or relayed to their
+/// leader by a [`Forwarder`] if [forwarding](LogHandler::with_forwarding).
+///
+/// Topics in [`Namespaces`] count towards their quotas, creating more than
+/// those allow fails with [`ErrorCode::PolicyViolation`].
+///
+/// Topics created and deleted, leadership and in-sync replica changes and
+/// segment rolls are published on an [`EventBus`], if given one.
+#[derive(Debug, Clone)]
+pub struct LogHandler {
+    logs: Arc<LogDirs>,
+    fetches: Arc<FetchPurgatory>,
+    delayed: Arc<DelayedRecords>,
+    /// Partitions of topics created on first use, off when `None`.
+    auto_create_partitions: Option<u32>,
+    /// Transforms of produced and fetched records, by topic.
+    transforms: Arc<HashMap<String, TransformChain>>,
+    authorizer: Arc<dyn Authorizer>,
+    replicas: Arc<ReplicaManager>,
+    cluster: Arc<Cluster>,
+    groups: Arc<GroupCoordinator>,
+    events: Option<EventBus>,
+    namespaces: Option<Arc<Namespaces>>,
+    /// Relays requests for followed partitions to their leader, off when
+    /// `None`.
+    forwarder: Option<Arc<Forwarder>>,
+}
+
+impl LogHandler {
+    pub fn new(logs: Arc<LogDirs>) -> Self {
+        LogHandler {
+            fetches: Arc::new(FetchPurgatory::new()),
+            delayed: Arc::new(DelayedRecords::default()),
+            auto_create_partitions: None,
+            transforms: Arc::new(HashMap::new()),
+            authorizer: Arc::new(AllowAll),
+            replicas: Arc::new(ReplicaManager::new(logs.clone(), ReplicaConfig::default())),
+            cluster: Arc::new(Cluster::new(ClusterConfig::default())),
+            groups: Arc::new(GroupCoordinator::new()),
+            events: None,
+            namespaces: None,
+            forwarder: None,
+            logs,
+        }
+    }
+
+    pub fn with_replica_config(mut self, config: ReplicaConfig) -> Self {
+        let mut replicas = ReplicaManager::new(self.logs.clone(), config);
+        if let Some(bus) = &self.events {
+            replicas = replicas.with_events(bus.clone());
+        }
+        self.replicas = Arc::new(replicas);
+        self
+    }
+
+    /// Publishes on `bus`, along with the log dirs and replica manager.
+    pub fn with_events(mut self, bus: EventBus) -> Self {
+        self.logs.publish_events(bus.clone());
+        let config = self.replicas.config().clone();
+        self.replicas =
+            Arc::new(ReplicaManager::new(self.logs.clone(), config).with_events(bus.clone()));
+        self.events = Some(bus);
+        self
+    }
+
+    pub fn with_cluster_config(mut self, config: ClusterConfig) -> Self {
+        self.cluster = Arc::new(Cluster::new(config));
+        self
+    }
+
+    /// Holds records produced for later delivery within `config`.
+    pub fn with_delay_config(mut self, config: DelayConfig) -> Self {
+        self.delayed = Arc::new(DelayedRecords::new(config));
+        self
+    }
+
+    /// Passes the records produced to and fetched from each topic through
+    /// its chain in `transforms`. Followers fetch them as they are in the
+    /// log.
+    pub fn with_transforms(mut self, transforms: HashMap<String, TransformChain>) -> Self {
+        self.transforms = Arc::new(transforms);
+        self
+    }
+
+    /// Counts topics towards the quotas of their namespaces in `namespaces`,
+    /// starting with those already held here, and creates topics with their
+    /// namespace's topic defaults.
+    pub fn with_namespaces(mut self, namespaces: Arc<Namespaces>) -> Self {
+        let mut topics: HashMap<String, u32> = HashMap::new();
+        for (partition, log) in self.logs.partitions() {
+            // Opened with the shared config, the defaults aren't stored
+            let configured =
+                namespaces.log_config(&partition.topic, self.logs.config(), &HashMap::new());
+            match configured {
+                Ok(config) => log.write().unwrap().set_config(config),
+                Err(err) => {
+                    tracing::warn!(%partition, %err, "namespace topic defaults not applied")
+                }
+            }
+            *topics.entry(partition.topic).or_default() += 1;
+        }
+        for (topic, partitions) in topics {
+            // Over quota or not, they exist
+            if let Err(err) = namespaces.add_topic(&topic, partitions) {
+                tracing::warn!(topic, %err, "topic not counted towards its namespace");
+            }
+        }
+        self.namespaces = Some(namespaces);
+        self
+    }
+
+    /// Relays produces, fetches and offset lookups for partitions followed
+    /// here to their leader, rather than refusing them, for clients that can
+    /// only reach this broker. Requests other brokers forwarded aren't
+    /// forwarded again.
+    pub fn with_forwarding(mut self) -> Self {
+        self.forwarder = Some(Arc::new(Forwarder::new()));
+        self
+    }
+
+    pub fn with_group_coordinator(mut self, groups: GroupCoordinator) -> Self {
+        self.groups = Arc::new(groups);
+        self
+    }
+
+    /// Creates topics no broker knows of with `partitions` partitions when
+    /// they are produced to, fetched from or asked for by name in metadata,
+    /// by principals the [`authorizer`](LogHandler::with_authorizer) lets
+    /// create them.
+    pub fn with_auto_create_topics(mut self, partitions: u32) -> Self {
+        self.auto_create_partitions = Some(partitions);
+        self
+    }
+
+    /// Checks who may auto-create topics with `authorizer`, the same one the
+    /// [`Server`](super::Server) checks requests with. Everything is allowed
+    /// without one.
+    pub fn with_authorizer(mut self, authorizer: impl Authorizer) -> Self {
+        self.authorizer = Arc::new(authorizer);
+        self
+    }
+
+    pub fn logs(&self) -> &Arc<LogDirs> {
+        &self.logs
+    }
+
+    pub fn replicas(&self) -> &Arc<ReplicaManager> {
+        &self.replicas
+    }
+
+    pub fn cluster(&self) -> &Arc<Cluster> {
+        &self.cluster
+    }
+
+    pub fn groups(&self) -> &Arc<GroupCoordinator> {
+        &self.groups
+    }
+
+    pub fn delayed(&self) -> &Arc<DelayedRecords> {
+        &self.delayed
+    }
+
+    /// Appends held records each tick as they come due, until none are left
+    /// held.
+    fn start_delivery(&self) {
+        let handler = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(DELIVERY_TICK);
+            loop {
+                interval.tick().await;
+                let Some(due) = handler.delayed.take_due(now_ms()) else {
+                    return;
+                };
+                for (partition, records) in due {
+                    handler.deliver(&partition, records);
+                }
+            }
+        });
+    }
+
+    fn deliver(&self, partition: &TopicPartition, records: Vec<Record>) {
+        let log = match self.logs.get(partition) {
+            Ok(log) => log,
+            Err(err) => {
+                tracing::warn!(%partition, %err, "dropping delayed records");
+                return;
+            }
+        };
+        let result = {
+            let mut log = log.write().unwrap();
+            if !self.replicas.is_leader(partition) {
+                tracing::warn!(%partition, "dropping delayed records, no longer leader");
+                return;
+            }
+            log.append(RecordBatch::new(records))
+        };
+        match result {
+            Ok(_) => self.fetches.complete(partition),
+            Err(err) => {
+                self.logs.handle_error(partition, &err);
+                tracing::warn!(%partition, %err, "failed to deliver delayed records");
+            }
+        }
+    }
+
+    fn read(&self, partition: &TopicPartition, log: &RwLock<Log>, fetch: &Fetch) -> FetchResponse {
+        let _span = tracing::debug_span!("read", offset = fetch.offset()).entered();
+        let log = log.read().unwrap();
+        // Fenced fetches don't count towards the in-sync replicas
+        if let Err(err) = log.check_leader_epoch(fetch.leader_epoch()) {
+            return FetchResponse {
+                leader_epoch: log.leader_epoch(),
+                ..FetchResponse::error(log_error_code(&err))
+            };
+        }
+        let (offset, max_bytes) = (fetch.offset(), fetch.max_bytes() as usize);
+        let leader_end = log.next_offset();
+
+        // Followers read up to the log end, consumers up to the high watermark
+        let isr = self.replicas.isr();
+        let (high_watermark, end) = match fetch.replica_id() {
+            Some(replica_id) => {
+                let before = isr.high_watermark(partition, leader_end);
+                let Some(after) = isr.record_fetch(partition, replica_id, offset, leader_end)
+                else {
+                    return FetchResponse::error(ErrorCode::BrokerIdNotRegistered);
+                };
+                if after > before {
+                    self.fetches.complete(partition);
+                }
+                (after, leader_end)
+            }
+            None => {
+                let high_watermark = isr.high_watermark(partition, leader_end);
+                (high_watermark, high_watermark)
+            }
+        };
+
+        // Sealed segments are sent on from their files, unless consumers
+        // need expired records filtered out of them or fetches transformed
+        let consumer = fetch.replica_id().is_none();
+        let transforms = self
+            .transforms
+            .get(&partition.topic)
+            .filter(|chain| consumer && chain.transforms_fetches());
+        let read = log
+            .may_expire(offset)
+            .and_then(|may_expire| {
+                if consumer && (may_expire || transforms.is_some()) {
+                    Ok(None)
+                } else {
+                    log.read_slices(offset, max_bytes, end)
+                }
+            })
+            .and_then(|slices| match slices {
+                Some(slices) => Ok(FetchResponse::from_slices(high_watermark, slices)),
+                None if consumer => {
+                    let now = now_ms();
+                    let batches = log.read_mapped(offset, max_bytes, end, |mut batch| {
+                        batch.retain_unexpired(now);
+                        match transforms {
+                            Some(chain) => chain.apply_fetch(batch),
+                            None => batch,
+                        }
+                    })?;
+                    Ok(FetchResponse::new(high_watermark, batches))
+                }
+                None => {
+                    let mut batches = log.read(offset, max_bytes)?;
+                    batches.retain(|batch| batch.next_offset() <= end);
+                    Ok(FetchResponse::new(high_watermark, batches))
+                }
+            });
+        let response = match read {
+            Ok(response) => response,
+            Err(err) => {
+                self.logs.handle_error(partition, &err);
+                FetchResponse::error(log_error_code(&err))
+            }
+        };
+        FetchResponse {
+            leader_epoch: log.leader_epoch(),
+            ..response
+        }
+    }
+
+    /// The log of `partition`, creating its topic first for `principal` if
+    /// that's on and the topic is unknown.
+    fn log_or_create(
+        &self,
+        principal: &Principal,
+        partition: &TopicPartition,
+    ) -> Result<Arc<RwLock<Log>>, LogDirError> {
+        match self.logs.get(partition) {
+            // Read again whether this or a racing request created it
+            Err(LogDirError::UnknownPartition(_))
+                if self.auto_create(principal, &partition.topic) =>
+            {
+                self.logs.get(partition)
+            }
+            result => result,
+        }
+    }
+
+    /// Creates `topic` if auto-creation is on, `principal` may create it or
+    /// create on the cluster, and no live broker leads any partition of it.
+    /// Returns whether it tried to, a request racing this one may have
+    /// created it first.
+    fn auto_create(&self, principal: &Principal, topic: &str) -> bool {
+        let Some(partitions) = self.auto_create_partitions else {
+            return false;
+        };
+        let allowed =
+            self.authorizer
+                .authorize(principal, Operation::Create, Resource::Topic(topic))
+                || self
+                    .authorizer
+                    .authorize(principal, Operation::Create, Resource::Cluster);
+        if !allowed {
+            return false;
+        }
+        let metadata = self
+            .cluster
+            .metadata("", self.replicas.leaders(), &[topic.to_string()]);
+        if metadata
+            .topics
+            .iter()
+            .any(|metadata| metadata.error.is_ok())
+        {
+            return false;
+        }
+        match self.create_topic(topic, partitions) {
+            Ok(()) => tracing::info!(topic, partitions, "auto-created topic"),
+            Err(err) => tracing::debug!(topic, %err, "topic not auto-created"),
+        }
+        true
+    }
+
+    /// Leader to forward requests for `partition` to, if forwarding and it
+    /// is followed here.
+    fn forward_target(
+        &self,
+        context: &RequestContext,
+        partition: &TopicPartition,
+    ) -> Option<String> {
+        self.forwarder.as_ref()?;
+        if context.client_id == REPLICA_CLIENT_ID {
+            return None;
+        }
+        self.replicas.leader_of(partition)
+    }
+
+    /// Relays `request` to `leader`. Fails with `RequestTimedOut` if the
+    /// leader didn't answer in time, so the client retries, and with
+    /// `NotLeaderOrFollower` if the request couldn't be relayed.
+    async fn forward(&self, leader: &str, request: Request) -> Result<Response, ErrorCode> {
+        let forwarder = self
+            .forwarder
+            .as_ref()
+            .ok_or(ErrorCode::NotLeaderOrFollower)?;
+        forwarder.forward(leader, request).await.map_err(|err| {
+            tracing::warn!(leader, %err, "failed to forward request");
+            match err {
+                ReplicationError::TimedOut => ErrorCode::RequestTimedOut,
+                _ => ErrorCode::NotLeaderOrFollower,
+            }
+        })
+    }
+
+    /// Creates the partitions of `topic` with the topic defaults of its
+    /// namespace, removing those created again if one fails.
+    fn create_topic(&self, topic: &str, partitions: u32) -> Result<(), ErrorCode> {
+        let config = match &self.namespaces {
+            Some(namespaces) => {
+                let config = namespaces
+                    .log_config(topic, self.logs.config(), &HashMap::new())
+                    .map_err(|err| namespace_error_code(&err))?;
+                namespaces
+                    .add_topic(topic, partitions)
+                    .map_err(|err| namespace_error_code(&err))?;
+                config
+            }
+            None => self.logs.config().clone(),
+        };
+        let mut created = vec![];
+        for partition in 0..partitions {
+            let partition = TopicPartition::new(topic, partition);
+            if let Err(err) = self.logs.create_with_config(&partition, config.clone()) {
+                tracing::warn!(%partition, %err, "failed to create partition");
+                for created in created {
+                    let _ = self.logs.remove(&created);
+                }
+                if let Some(namespaces) = &self.namespaces {
+                    namespaces.remove_topic(topic);
+                }
+                return Err(log_dir_error_code(&err));
+            }
+            created.push(partition);
+        }
+        self.publish(Event::TopicCreated {
+            topic: topic.to_string(),
+            partitions,
+        });
+        Ok(())
+    }
+
+    fn publish(&self, event: Event) {
+        if let Some(bus) = &self.events {
+            bus.publish(event);
+        }
+    }
+
+    /// Partitions of `topic` held here.
+    fn topic_partitions(&self, topic: &str) -> Vec<TopicPartition> {
+        self.logs
+            .partitions()
+            .into_iter()
+            .map(|(partition, _)| partition)
+            .filter(|partition| partition.topic == topic)
+            .collect()
+    }
+
+    /// Waits up to `timeout` for `log` to be flushed up to `end`. Logs that
+    /// only flush on request are flushed right away, the others are left to
+    /// their policy.
+    async fn wait_flushed(
+        &self,
+        partition: &TopicPartition,
+        log: &RwLock<Log>,
+        end: u64,
+        timeout: Duration,
+    ) -> ErrorCode {
+        let mut flushed = {
+            let mut log = log.write().unwrap();
+            if log.config().flush_policy == FlushPolicy::Never {
+                if let Err(err) = log.flush() {
+                    self.logs.handle_error(partition, &err);
+                    return log_error_code(&err);
+                }
+            }
+            log.watch_flushed()
+        };
+
+        let done = tokio::time::timeout(timeout, flushed.wait_for(|&offset| offset >= end))
+            .instrument(tracing::debug_span!("wait_flushed", end))
+            .await;
+        if matches!(done, Ok(Ok(_))) {
+            ErrorCode::None
+        } else {
+            ErrorCode::RequestTimedOut
+        }
+    }
+
+    /// Waits for the in-sync replicas to have `log` up to `end`, for the
+    /// high watermark to reach it, or `timeout` to pass.
+    async fn wait_replicated(
+        &self,
+        partition: &TopicPartition,
+        log: &RwLock<Log>,
+        end: u64,
+        timeout: Duration,
+    ) -> ErrorCode {
+        let isr = self.replicas.isr();
+        // Woken each time a follower moves the high watermark along
+        let done = self
+            .fetches
+            .wait(partition, timeout, || {
+                let leader_end = log.read().unwrap().next_offset();
+                (isr.high_watermark(partition, leader_end) >= end).then_some(())
+            })
+            .instrument(tracing::debug_span!("wait_replicated", end))
+            .await;
+        if done.is_some() {
+            ErrorCode::None
+        } else {
+            ErrorCode::RequestTimedOut
+        }
+    }
+}
+
+impl Handler for LogHandler {
+    async fn handle_produce(&self, context: &RequestContext, produce: Produce) -> ProduceResponse {
+        let partition = TopicPartition::new(produce.topic(), produce.partition());
+        if let Some(leader) = self.forward_target(context, &partition) {
+            return match self.forward(&leader, produce.into()).await {
+                Ok(Response::Produce(response)) => response,
+                Ok(_) => ProduceResponse::error(ErrorCode::NotLeaderOrFollower),
+                Err(error) => ProduceResponse::error(error),
+            };
+        }
+        let log = match self.log_or_create(&context.principal, &partition) {
+            Ok(log) => log,
+            Err(err) => return ProduceResponse::error(log_dir_error_code(&err)),
+        };
+
+        let acks = produce.acks();
+        let timeout = Duration::from_millis(produce.timeout_ms() as u64);
+        let leader_epoch = produce.leader_epoch();
+        let now = now_ms();
+        let batch = match self.transforms.get(&partition.topic) {
+            Some(chain) => chain.apply_produce(produce.into_batch()),
+            None => produce.into_batch(),
+        };
+        let attributes = batch.attributes;
+        let (delayed, records): (Vec<_>, Vec<_>) = batch
+            .records
+            .into_iter()
+            .partition(|record| record.deliver_at().is_some_and(|at| at > now));
+        let held = !delayed.is_empty();
+        // Held records are only in memory until due, they can't be waited on
+        // to be replicated
+        if held && acks == Acks::All {
+            return ProduceResponse::error(ErrorCode::InvalidRequiredAcks);
+        }
+        let result = {
+            let _span = tracing::debug_span!("append").entered();
+            let mut log = log.write().unwrap();
+            // Checked under the lock, a partition being handed off takes no
+            // writes once it is marked
+            if !self.replicas.is_leader(&partition) {
+                return ProduceResponse::error(ErrorCode::NotLeaderOrFollower);
+            }
+            if let Err(err) = log.check_leader_epoch(leader_epoch) {
+                return ProduceResponse::error(log_error_code(&err));
+            }
+            // Held first, so batches over the limits append nothing
+            if held {
+                match self.delayed.hold(&partition, delayed, now) {
+                    Ok(true) => self.start_delivery(),
+                    Ok(false) => {}
+                    Err(err) => return ProduceResponse::error(delay_error_code(&err)),
+                }
+            }
+            // Batches of only delayed records append nothing yet
+            if records.is_empty() && held {
+                Ok((NO_OFFSET, log.next_offset()))
+            } else {
+                let batch = RecordBatch {
+                    attributes,
+                    ..RecordBatch::new(records)
+                };
+                log.append(batch)
+                    .map(|base_offset| (base_offset, log.next_offset()))
+            }
+        };
+        let (mut base_offset, end) = match result {
+            Ok(appended) => appended,
+            Err(err) => {
+                self.logs.handle_error(&partition, &err);
+                return ProduceResponse::error(log_error_code(&err));
+            }
+        };
+        self.fetches.complete(&partition);
+        if held {
+            base_offset = NO_OFFSET;
+        }
+
+        if acks != Acks::All {
+            return ProduceResponse::new(base_offset);
+        }
+        let deadline = tokio::time::Instant::now() + timeout;
+        let error = match self.wait_flushed(&partition, &log, end, timeout).await {
+            ErrorCode::None => {
+                let timeout = deadline.saturating_duration_since(tokio::time::Instant::now());
+                self.wait_replicated(&partition, &log, end, timeout).await
+            }
+            error => error,
+        };
+        match error {
+            ErrorCode::None => ProduceResponse::new(base_offset),
+            error => ProduceResponse::error(error),
+        }
+    }
+
+    async fn handle_fetch(&self, context: &RequestContext, fetch: Fetch) -> FetchResponse {
+        let partition = TopicPartition::new(fetch.topic(), fetch.partition());
+        if let Some(leader) = self.forward_target(context, &partition) {
+            if fetch.replica_id().is_none() {
+                return match self.forward(&leader, fetch.into()).await {
+                    Ok(Response::Fetch(response)) => response,
+                    Ok(_) => FetchResponse::error(ErrorCode::NotLeaderOrFollower),
+                    Err(error) => FetchResponse::error(error),
+                };
+            }
+        }
+        let log = match self.log_or_create(&context.principal, &partition) {
+            Ok(log) => log,
+            Err(err) => return FetchResponse::error(log_dir_error_code(&err)),
+        };
+
+        let min_bytes = fetch.min_bytes() as usize;
+        if min_bytes == 0 || fetch.max_wait_ms() == 0 {
+            return self.read(&partition, &log, &fetch);
+        }
+
+        let max_wait = Duration::from_millis(fetch.max_wait_ms() as u64);
+        let ready = self
+            .fetches
+            .wait(&partition, max_wait, || {
+                let response = self.read(&partition, &log, &fetch);
+                let bytes = response.records_size();
+                (response.error != ErrorCode::None || bytes >= min_bytes).then_some(response)
+            })
+            .instrument(tracing::debug_span!(
+                "purgatory",
+                min_bytes,
+                max_wait_ms = fetch.max_wait_ms()
+            ))
+            .await;
+        // On timeout, answer with whatever there is
+        ready.unwrap_or_else(|| self.read(&partition, &log, &fetch))
+    }
+
+    async fn handle_list_offsets(
+        &self,
+        context: &RequestContext,
+        request: ListOffsets,
+    ) -> ListOffsetsResponse {
+        let partition = TopicPartition::new(request.topic(), request.partition());
+        if let Some(leader) = self.forward_target(context, &partition) {
+            return match self.forward(&leader, request.into()).await {
+                Ok(Response::ListOffsets(response)) => response,
+                Ok(_) => ListOffsetsResponse::error(ErrorCode::NotLeaderOrFollower),
+                Err(error) => ListOffsetsResponse::error(error),
+            };
+        }
+        let log = match self.logs.get(&partition) {
+            Ok(log) => log,
+            Err(err) => return ListOffsetsResponse::error(log_dir_error_code(&err)),
+        };
+        let log = log.read().unwrap();
+        // Consumers can't see past the high watermark
+        let high_watermark = self
+            .replicas
+            .isr()
+            .high_watermark(&partition, log.next_offset());
+        match request.timestamp() {
+            EARLIEST_TIMESTAMP => ListOffsetsResponse::new(log.start_offset()),
+            LATEST_TIMESTAMP => ListOffsetsResponse::new(high_watermark),
+            timestamp => match log.offset_for_timestamp(timestamp) {
+                Ok(offset) => ListOffsetsResponse::new(
+                    offset
+                        .filter(|&offset| offset < high_watermark)
+                        .unwrap_or(NO_OFFSET),
+                ),
+                Err(err) => ListOffsetsResponse::error(log_error_code(&err)),
+            },
+        }
+    }
+
+    /// Creates the partitions one by one, removing those created so far if
+    /// one fails.
+    async fn handle_create_topic(&self, _: &RequestContext, request: CreateTopic) -> AdminResponse {
+        if !self.topic_partitions(request.topic()).is_empty() {
+            return AdminResponse::error(ErrorCode::TopicAlreadyExists);
+        }
+        match self.create_topic(request.topic(), request.partitions()) {
+            Ok(()) => AdminResponse::default(),
+            Err(error) => AdminResponse::error(error),
+        }
+    }
+
+    async fn handle_delete_topic(&self, _: &RequestContext, request: DeleteTopic) -> AdminResponse {
+        let partitions = self.topic_partitions(request.topic());
+        if partitions.is_empty() {
+            return AdminResponse::error(ErrorCode::UnknownTopicOrPartition);
+        }
+        for partition in partitions {
+            if let Err(err) = self.logs.remove(&partition) {
+                tracing::warn!(%partition, %err, "failed to delete partition");
+                return AdminResponse::error(log_dir_error_code(&err));
+            }
+        }
+        if let Some(namespaces) = &self.namespaces {
+            namespaces.remove_topic(request.topic());
+        }
+        self.publish(Event::TopicDeleted {
+            topic: request.topic().to_string(),
+        });
+        AdminResponse::default()
+    }
+
+    async fn handle_metadata(
+        &self,
+        context: &RequestContext,
+        request: Metadata,
+    ) -> MetadataResponse {
+        for topic in request.topics() {
+            self.auto_create(&context.principal, topic);
+        }
+        let address = self.replicas.advertised_listener().unwrap_or_default();
+        self.cluster
+            .metadata(&address, self.replicas.leaders(), request.topics())
+    }
+
+    async fn handle_broker_heartbeat(
+        &self,
+        _: &RequestContext,
+        request: BrokerHeartbeat,
+    ) -> AdminResponse {
+        match self.cluster.record_heartbeat(request) {
+            Ok(()) => AdminResponse::new(),
+            Err(error) => AdminResponse::error(error),
+        }
+    }
+
+    async fn handle_describe_cluster(
+        &self,
+        _: &RequestContext,
+        _: DescribeCluster,
+    ) -> DescribeClusterResponse {
+        let address = self.replicas.advertised_listener().unwrap_or_default();
+        self.cluster.describe(&address)
+    }
+
+    async fn handle_describe_log_dirs(
+        &self,
+        _: &RequestContext,
+        request: DescribeLogDirs,
+    ) -> DescribeLogDirsResponse {
+        let topics = request.topics();
+        let dirs = self
+            .logs
+            .paths()
+            .into_iter()
+            .map(|path| {
+                let error = if self.logs.is_online(&path) {
+                    ErrorCode::None
+                } else {
+                    ErrorCode::StorageError
+                };
+                let partitions = self
+                    .logs
+                    .partitions_in(&path)
+                    .into_iter()
+                    .filter(|(partition, _)| topics.is_empty() || topics.contains(&partition.topic))
+                    .map(|(partition, log)| {
+                        let log = log.read().unwrap();
+                        PartitionLogDescription {
+                            topic: partition.topic,
+                            partition: partition.partition,
+                            size: log.size(),
+                            segments: log.segment_count() as u32,
+                            start_offset: log.start_offset(),
+                            next_offset: log.next_offset(),
+                        }
+                    })
+                    .collect();
+                LogDirDescription {
+                    error,
+                    path: path.display().to_string(),
+                    partitions,
+                }
+            })
+            .collect();
+        DescribeLogDirsResponse::new(dirs)
+    }
+
+    async fn handle_estimate_cleanup(
+        &self,
+        _: &RequestContext,
+        request: EstimateCleanup,
+    ) -> EstimateCleanupResponse {
+        let mut logs = self
+            .logs
+            .partitions()
+            .into_iter()
+            .filter(|(partition, _)| partition.topic == request.topic())
+            .collect::<Vec<_>>();
+        if logs.is_empty() {
+            return EstimateCleanupResponse::error(ErrorCode::UnknownTopicOrPartition);
+        }
+        logs.sort_by_key(|(partition, _)| partition.partition);
+        let topic = request.topic().to_string();
+
+        // Reads every sealed segment, keep it off the runtime's workers
+        let estimates = tokio::task::spawn_blocking(move || {
+            logs.into_iter()
+                .map(|(partition, log)| {
+                    let log = log.read().unwrap();
+                    let config = cleanup_config(&request, log.config());
+                    let estimate = log.estimate_cleanup(&config)?;
+                    Ok(PartitionCleanupEstimate {
+                        partition: partition.partition,
+                        size: log.size(),
+                        segments: estimate.segments as u32,
+                        records_removed: estimate.records_removed as u64,
+                        reclaimable_bytes: estimate.reclaimable_bytes,
+                    })
+                })
+                .collect::<Result<Vec<_>, LogError>>()
+        })
+        .await;
+        match estimates {
+            Ok(Ok(partitions)) => EstimateCleanupResponse::new(partitions),
+            Ok(Err(err)) => {
+                tracing::warn!(topic, %err, "cannot estimate cleanup");
+                EstimateCleanupResponse::error(log_error_code(&err))
+            }
+            Err(err) => {
+                tracing::error!(topic, %err, "cleanup estimate panicked");
+                EstimateCleanupResponse::error(ErrorCode::UnknownServerError)
+            }
+        }
+    }
+
+    async fn handle_offset_commit(
+        &self,
+        _: &RequestContext,
+        request: OffsetCommit,
+    ) -> AdminResponse {
+        let group = request.group().to_string();
+        match self.groups.commit(&group, request.into_offsets()).await {
+            Ok(()) => AdminResponse::new(),
+            Err(err) => {
+                tracing::warn!(group, %err, "cannot store committed offsets");
+                AdminResponse::error(ErrorCode::StorageError)
+            }
+        }
+    }
+
+    async fn handle_list_groups(&self, _: &RequestContext, _: ListGroups) -> ListGroupsResponse {
+        ListGroupsResponse::new(self.groups.list())
+    }
+
+    /// Lag is measured against the high watermark, the end of what
+    /// consumers can read.
+    async fn handle_describe_groups(
+        &self,
+        _: &RequestContext,
+        request: DescribeGroups,
+    ) -> DescribeGroupsResponse {
+        let isr = self.replicas.isr();
+        let end_offset = |partition: &TopicPartition| {
+            let log = self.logs.get(partition).ok()?;
+            let next_offset = log.read().unwrap().next_offset();
+            Some(isr.high_watermark(partition, next_offset))
+        };
+        let groups = request
+            .groups()
+            .iter()
+            .map(|group| self.groups.describe(group, end_offset))
+            .collect();
+        DescribeGroupsResponse::new(groups)
+    }
+
+    async fn handle_join_group(
+        &self,
+        context: &RequestContext,
+        request: JoinGroup,
+    ) -> JoinGroupResponse {
+        let host = context
+            .peer
+            .map(|peer| peer.ip().to_string())
+            .unwrap_or_default();
+        self.groups.join(request, &context.client_id, &host).await
+    }
+
+    async fn handle_sync_group(&self, _: &RequestContext, request: SyncGroup) -> SyncGroupResponse {
+        self.groups.sync(request).await
+    }
+
+    async fn handle_heartbeat(&self, _: &RequestContext, request: Heartbeat) -> AdminResponse {
+        AdminResponse::error(self.groups.heartbeat(&request))
+    }
+
+    async fn handle_leave_group(&self, _: &RequestContext, request: LeaveGroup) -> AdminResponse {
+        AdminResponse::error(self.groups.leave(&request))
+    }
+
+    async fn handle_leader_and_isr(
+        &self,
+        _: &RequestContext,
+        request: LeaderAndIsr,
+    ) -> AdminResponse {
+        let partition = TopicPartition::new(request.topic(), request.partition());
+        let result = match request.leader() {
+            Some(leader) => {
+                self.replicas
+                    .follow(&partition, leader.to_string(), request.leader_epoch())
+            }
+            None => self.replicas.lead(&partition, request.leader_epoch()),
+        };
+        match result {
+            Ok(()) => AdminResponse::default(),
+            Err(err) => AdminResponse::error(replication_error_code(&err)),
+        }
+    }
+
+    async fn handle_reassign(
+        &self,
+        _: &RequestContext,
+        request: ReassignPartition,
+    ) -> AdminResponse {
+        let partition = TopicPartition::new(request.topic(), request.partition());
+        let timeout = Duration::from_millis(request.timeout_ms() as u64);
+        let moved = self
+            .replicas
+            .reassign(&partition, request.target_id(), request.target(), timeout)
+            .await;
+        match moved {
+            Ok(()) => AdminResponse::default(),
+            Err(err) => {
+                tracing::warn!(%partition, target = request.target(), %err, "reassignment failed");
+                AdminResponse::error(replication_error_code(&err))
+            }
+        }
+    }
+}
+
+fn replication_error_code(err: &ReplicationError) -> ErrorCode {
+    match err {
+        ReplicationError::Log(err) => log_error_code(err),
+        ReplicationError::LogDir(err) => log_dir_error_code(err),
+        ReplicationError::NotLeader => ErrorCode::NotLeaderOrFollower,
+        ReplicationError::MoveInProgress => ErrorCode::ReassignmentInProgress,
+        ReplicationError::TimedOut => ErrorCode::RequestTimedOut,
+        ReplicationError::Leader(error) | ReplicationError::Target(error) => *error,
+        ReplicationError::LeaderAndIsr(_) => ErrorCode::InvalidRequest,
+        _ => ErrorCode::UnknownServerError,
+    }
+}
+
+/// `config` with the overrides of `request` applied.
+fn cleanup_config(request: &EstimateCleanup, config: &LogConfig) -> LogConfig {
+    let mut config = config.clone();
+    if let Some(cleanup_policy) = request.cleanup_policy() {
+        config.cleanup_policy = cleanup_policy;
+    }
+    if let Some(retention_ms) = request.retention_ms() {
+        config.retention_age = u64::try_from(retention_ms).ok().map(Duration::from_millis);
+    }
+    if let Some(retention_bytes) = request.retention_bytes() {
+        config.retention_bytes = u64::try_from(retention_bytes).ok();
+    }
+    config
+}
+
+fn log_dir_error_code(err: &LogDirError) -> ErrorCode {
+    match err {
+        LogDirError::Log(err) => log_error_code(err),
+        LogDirError::UnknownPartition(_) => ErrorCode::UnknownTopicOrPartition,
+        LogDirError::PartitionExists(_) => ErrorCode::InvalidRequest,
+        LogDirError::Offline { .. } | LogDirError::NoOnlineDirs => ErrorCode::StorageError,
+    }
+}
+
+fn namespace_error_code(err: &NamespaceError) -> ErrorCode {
+    match err {
+        NamespaceError::AccessDenied { .. } => ErrorCode::TopicAuthorizationFailed,
+        NamespaceError::UnknownNamespace(_)
+        | NamespaceError::AlreadyExists(_)
+        | NamespaceError::QuotaExceeded { .. } => ErrorCode::PolicyViolation,
+        NamespaceError::TopicConfig(_) => ErrorCode::InvalidConfig,
+    }
+}
+
+fn log_error_code(err: &LogError) -> ErrorCode {
+    match err {
+        LogError::Io(_) => ErrorCode::StorageError,
+        LogError::Batch(_) | LogError::CorruptIndex | LogError::CorruptSegment { .. } => {
+            ErrorCode::CorruptMessage
+        }
+        LogError::EmptyBatch | LogError::OffsetBehindEnd { .. } => ErrorCode::InvalidRequest,
+        LogError::OffsetOutOfRange { .. } => ErrorCode::OffsetOutOfRange,
+        LogError::FencedLeaderEpoch { .. } => ErrorCode::FencedLeaderEpoch,
+        LogError::UnknownLeaderEpoch { .. } => ErrorCode::UnknownLeaderEpoch,
+    }
+}
+
+fn delay_error_code(err: &DelayError) -> ErrorCode {
+    match err {
+        DelayError::TooLate(_) => ErrorCode::InvalidTimestamp,
+        DelayError::Full { .. } => ErrorCode::PolicyViolation,
+    }
+}
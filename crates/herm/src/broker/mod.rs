@@ -0,0 +1,27 @@
+mod audit;
+mod config;
+mod context;
+mod delay;
+mod forward;
+mod frame;
+mod handler;
+mod log_handler;
+mod purgatory;
+mod quota;
+mod server;
+pub use audit::{AuditLog, AuditRecord};
+#[cfg(feature = "tls")]
+pub use config::TlsSettings;
+pub use config::{
+    AuditSettings, Config, ConfigError, Limits, LogSettings, NamespaceSettings, PeerSettings,
+    QuotaSettings, ENV_PREFIX,
+};
+pub use context::{Principal, RequestContext};
+pub use delay::{DelayConfig, DelayError, DelayedRecords, TimingWheel, DELIVERY_TICK};
+pub use forward::Forwarder;
+pub use frame::{read_frame, write_frame, DEFAULT_MAX_FRAME_SIZE};
+pub use handler::{dispatch, Handler};
+pub use log_handler::LogHandler;
+pub use purgatory::FetchPurgatory;
+pub use quota::{QuotaConfig, QuotaKey, QuotaManager};
+pub use server::{Server, DEFAULT_DRAIN_TIMEOUT, DEFAULT_MAX_IN_FLIGHT};
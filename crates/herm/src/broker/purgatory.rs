@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use crate::protocol::TopicPartition;
+
+/// Fetches waiting for data, keyed by the partition they read. Produces call
+/// [`complete`](FetchPurgatory::complete) after appending, which wakes the
+/// fetches parked on that partition so they can read again.
+#[derive(Debug, Default)]
+pub struct FetchPurgatory {
+    waiting: Mutex<HashMap<TopicPartition, Arc<Notify>>>,
+}
+
+impl FetchPurgatory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `attempt` until it returns `Some`, retrying each time `partition`
+    /// is appended to. Returns `None` if `max_wait` passes first.
+    pub async fn wait<T>(
+        &self,
+        partition: &TopicPartition,
+        max_wait: Duration,
+        mut attempt: impl FnMut() -> Option<T>,
+    ) -> Option<T> {
+        let deadline = Instant::now() + max_wait;
+        loop {
+            // Register before attempting, so an append landing between the
+            // attempt and the wait still wakes us.
+            let notify = self.watch(partition);
+            let notified = notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if let Some(done) = attempt() {
+                return Some(done);
+            }
+            if !wait_until(notified, deadline).await {
+                return None;
+            }
+        }
+    }
+
+    /// Wakes every fetch parked on `partition`.
+    pub fn complete(&self, partition: &TopicPartition) {
+        if let Some(notify) = self.waiting.lock().unwrap().remove(partition) {
+            notify.notify_waiters();
+        }
+    }
+
+    /// Partitions with fetches parked on them.
+    pub fn len(&self) -> usize {
+        self.waiting.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn watch(&self, partition: &TopicPartition) -> Arc<Notify> {
+        self.waiting
+            .lock()
+            .unwrap()
+            .entry(partition.clone())
+            .or_default()
+            .clone()
+    }
+}
+
+/// Returns false if the deadline passed first.
+async fn wait_until(notified: impl Future<Output = ()>, deadline: Instant) -> bool {
+    tokio::select! {
+        _ = notified => true,
+        _ = tokio::time::sleep_until(deadline) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_complete_wakes_waiters() {
+        let purgatory = Arc::new(FetchPurgatory::new());
+        let partition = TopicPartition::new("events", 0);
+        let available = Arc::new(AtomicUsize::new(0));
+
+        let waiter = {
+            let (purgatory, partition, available) =
+                (purgatory.clone(), partition.clone(), available.clone());
+            tokio::spawn(async move {
+                purgatory
+                    .wait(&partition, Duration::from_secs(10), || {
+                        let bytes = available.load(Ordering::SeqCst);
+                        (bytes >= 2).then_some(bytes)
+                    })
+                    .await
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(purgatory.len(), 1);
+        available.store(1, Ordering::SeqCst);
+        purgatory.complete(&partition);
+        available.store(2, Ordering::SeqCst);
+        purgatory.complete(&TopicPartition::new("other", 0));
+        purgatory.complete(&partition);
+
+        assert_eq!(waiter.await.unwrap(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let purgatory = FetchPurgatory::new();
+        let partition = TopicPartition::new("events", 0);
+        let mut attempts = 0;
+
+        let result = purgatory
+            .wait(&partition, Duration::from_millis(20), || {
+                attempts += 1;
+                None::<()>
+            })
+            .await;
+        assert_eq!(result, None);
+        assert_eq!(attempts, 1);
+    }
+}
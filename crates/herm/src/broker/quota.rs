@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::time::Instant;
+
+use super::RequestContext;
+use crate::tenant::{NamespaceQuota, Namespaces};
+
+/// Clients tracked before idle ones are forgotten.
+const MAX_IDLE_CLIENTS: usize = 1024;
+
+/// What quotas are tracked by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuotaKey {
+    /// The client id clients send in each request header.
+    #[default]
+    ClientId,
+    /// Who the client authenticated as. Unauthenticated clients share one
+    /// quota.
+    Principal,
+}
+
+/// Byte rates each client is held to. Clients may burst up to a window's
+/// worth of bytes before being throttled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaConfig {
+    /// Bytes per second of produce requests, unlimited when `None`.
+    pub produce_rate: Option<u64>,
+    /// Bytes per second of fetch responses, unlimited when `None`.
+    pub fetch_rate: Option<u64>,
+    pub key: QuotaKey,
+    pub window: Duration,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        QuotaConfig {
+            produce_rate: None,
+            fetch_rate: None,
+            key: QuotaKey::ClientId,
+            window: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Tracks the produce and fetch byte rates of every client. Each client gets
+/// a bucket refilled at its quota rate. Bytes are taken out as they go, and a
+/// client that overdraws its bucket is throttled until it would be back to
+/// zero. The server holds back the response for that long, which also stops
+/// it reading more from the client once its in-flight limit is reached.
+///
+/// Given [`Namespaces`](QuotaManager::with_namespaces), the bytes produced to
+/// and fetched from the topics of each namespace are also held to its
+/// [`NamespaceQuota`](crate::tenant::NamespaceQuota), shared by every
+/// client. Clients are throttled by whichever quota they overdraw the most.
+#[derive(Debug, Default)]
+pub struct QuotaManager {
+    config: QuotaConfig,
+    produce: Mutex<HashMap<String, Bucket>>,
+    fetch: Mutex<HashMap<String, Bucket>>,
+    namespaces: Option<Arc<Namespaces>>,
+    namespace_produce: Mutex<HashMap<String, Bucket>>,
+    namespace_fetch: Mutex<HashMap<String, Bucket>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    /// Bytes the client may still send, negative once over quota.
+    available: f64,
+    updated: Instant,
+}
+
+impl QuotaManager {
+    pub fn new(config: QuotaConfig) -> Self {
+        QuotaManager {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_namespaces(mut self, namespaces: Arc<Namespaces>) -> Self {
+        self.namespaces = Some(namespaces);
+        self
+    }
+
+    pub fn config(&self) -> &QuotaConfig {
+        &self.config
+    }
+
+    /// Records `bytes` produced to `topic` by the client behind `context`,
+    /// returning how long to throttle it for.
+    pub fn record_produce(&self, context: &RequestContext, topic: &str, bytes: usize) -> Duration {
+        let now = Instant::now();
+        let client = self.record(
+            &self.produce,
+            self.config.produce_rate,
+            self.client_key(context),
+            bytes,
+            now,
+        );
+        let namespace = self
+            .namespace_quota(topic)
+            .map_or(Duration::ZERO, |(namespace, quota)| {
+                self.record(
+                    &self.namespace_produce,
+                    quota.produce_bytes_per_sec,
+                    namespace.to_string(),
+                    bytes,
+                    now,
+                )
+            });
+        client.max(namespace)
+    }
+
+    /// Records `bytes` fetched from `topic` by the client behind `context`,
+    /// returning how long to throttle it for.
+    pub fn record_fetch(&self, context: &RequestContext, topic: &str, bytes: usize) -> Duration {
+        let now = Instant::now();
+        let client = self.record(
+            &self.fetch,
+            self.config.fetch_rate,
+            self.client_key(context),
+            bytes,
+            now,
+        );
+        let namespace = self
+            .namespace_quota(topic)
+            .map_or(Duration::ZERO, |(namespace, quota)| {
+                self.record(
+                    &self.namespace_fetch,
+                    quota.fetch_bytes_per_sec,
+                    namespace.to_string(),
+                    bytes,
+                    now,
+                )
+            });
+        client.max(namespace)
+    }
+
+    fn client_key(&self, context: &RequestContext) -> String {
+        match self.config.key {
+            QuotaKey::ClientId => context.client_id.clone(),
+            QuotaKey::Principal => context.principal.to_string(),
+        }
+    }
+
+    fn namespace_quota<'a>(&'a self, topic: &'a str) -> Option<(&'a str, &'a NamespaceQuota)> {
+        self.namespaces.as_ref()?.quota_for_topic(topic)
+    }
+
+    fn record(
+        &self,
+        buckets: &Mutex<HashMap<String, Bucket>>,
+        rate: Option<u64>,
+        key: String,
+        bytes: usize,
+        now: Instant,
+    ) -> Duration {
+        let Some(rate) = rate.filter(|&rate| rate > 0) else {
+            return Duration::ZERO;
+        };
+        let rate = rate as f64;
+        let burst = rate * self.config.window.as_secs_f64();
+        let refill = |bucket: &Bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated);
+            (bucket.available + elapsed.as_secs_f64() * rate).min(burst)
+        };
+
+        let mut buckets = buckets.lock().unwrap();
+        if buckets.len() >= MAX_IDLE_CLIENTS {
+            // Full buckets are no different from new ones
+            buckets.retain(|_, bucket| refill(bucket) < burst);
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            available: burst,
+            updated: now,
+        });
+        bucket.available = refill(bucket) - bytes as f64;
+        bucket.updated = now;
+
+        if bucket.available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.available / rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::broker::Principal;
+    use crate::tenant::NamespaceConfig;
+
+    fn client(client_id: &str) -> RequestContext {
+        RequestContext {
+            client_id: client_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn produce(
+        quotas: &QuotaManager,
+        context: &RequestContext,
+        bytes: usize,
+        now: Instant,
+    ) -> u128 {
+        quotas
+            .record(
+                &quotas.produce,
+                quotas.config.produce_rate,
+                quotas.client_key(context),
+                bytes,
+                now,
+            )
+            .as_millis()
+    }
+
+    #[test]
+    fn test_throttle() {
+        let quotas = QuotaManager::new(QuotaConfig {
+            produce_rate: Some(1000),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        let a = client("a");
+
+        // A window's worth of burst, then throttled by what it overdraws
+        assert_eq!(produce(&quotas, &a, 1000, start), 0);
+        assert_eq!(produce(&quotas, &a, 500, start), 500);
+        assert_eq!(produce(&quotas, &a, 500, start), 1000);
+
+        // Paying off the debt over time
+        assert_eq!(
+            produce(&quotas, &a, 0, start + Duration::from_millis(600)),
+            400
+        );
+        assert_eq!(produce(&quotas, &a, 0, start + Duration::from_secs(2)), 0);
+        assert_eq!(
+            produce(&quotas, &a, 1000, start + Duration::from_secs(3)),
+            0
+        );
+
+        // Other clients have their own quota
+        assert_eq!(produce(&quotas, &client("b"), 1000, start), 0);
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let quotas = QuotaManager::new(QuotaConfig {
+            fetch_rate: Some(10),
+            ..Default::default()
+        });
+        let context = client("a");
+        assert_eq!(
+            quotas.record_produce(&context, "events", 1_000_000),
+            Duration::ZERO
+        );
+        assert!(quotas.record_fetch(&context, "events", 1_000_000) > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_principal_key() {
+        let quotas = QuotaManager::new(QuotaConfig {
+            produce_rate: Some(1000),
+            key: QuotaKey::Principal,
+            ..Default::default()
+        });
+        let start = Instant::now();
+        let alice = |client_id: &str| RequestContext {
+            principal: Principal::User("alice".to_string()),
+            ..client(client_id)
+        };
+
+        assert_eq!(produce(&quotas, &alice("a"), 1000, start), 0);
+        assert_eq!(produce(&quotas, &alice("b"), 100, start), 100);
+        assert_eq!(produce(&quotas, &client("a"), 100, start), 0);
+    }
+
+    #[test]
+    fn test_namespace_quotas() {
+        let mut namespaces = Namespaces::new();
+        let config = NamespaceConfig {
+            quota: NamespaceQuota {
+                produce_bytes_per_sec: Some(1000),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        namespaces.register("team-a", config).unwrap();
+        let quotas = QuotaManager::new(QuotaConfig {
+            produce_rate: Some(10_000),
+            ..Default::default()
+        })
+        .with_namespaces(Arc::new(namespaces));
+
+        // Clients share the namespace quota, on top of their own
+        assert_eq!(
+            quotas.record_produce(&client("a"), "team-a/orders", 1000),
+            Duration::ZERO
+        );
+        assert!(quotas.record_produce(&client("b"), "team-a/payments", 500) > Duration::ZERO);
+
+        // Topics outside it only count against the client quotas
+        assert_eq!(
+            quotas.record_produce(&client("b"), "orders", 5000),
+            Duration::ZERO
+        );
+        assert_eq!(
+            quotas.record_fetch(&client("b"), "team-a/orders", 5000),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_forgets_idle_clients() {
+        let quotas = QuotaManager::new(QuotaConfig {
+            produce_rate: Some(1000),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        for i in 0..MAX_IDLE_CLIENTS {
+            produce(&quotas, &client(&i.to_string()), 10, start);
+        }
+        produce(&quotas, &client("new"), 10, start + Duration::from_secs(1));
+        assert_eq!(quotas.produce.lock().unwrap().len(), 1);
+    }
+}
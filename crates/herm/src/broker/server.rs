@@ -0,0 +1,1881 @@
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+use bytes::{Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs, UnixListener, UnixStream};
+use tokio::sync::{mpsc, watch, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use tracing::Instrument;
+
+use super::frame::{read_frame_or_skip, Frame};
+use super::{
+    dispatch, write_frame, AuditLog, Handler, Principal, QuotaConfig, QuotaManager, RequestContext,
+    DEFAULT_MAX_FRAME_SIZE,
+};
+use crate::auth::{AllowAll, Authorizer};
+use crate::metrics::BrokerMetrics;
+use crate::protocol::{DecodeLimits, ErrorCode, HeaderError, RequestHeader, ResponseHeader};
+use crate::request::{Request, RequestError};
+use crate::response::Response;
+use crate::storage::FileSlice;
+use crate::tenant::Namespaces;
+#[cfg(feature = "tls")]
+use crate::tls::TlsAcceptor;
+
+/// Requests a connection may have outstanding before the server stops reading
+/// from it, unless configured otherwise.
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 64;
+
+/// How long a shutdown waits for connections to finish their requests.
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Reasons a connection is dropped. Requests that can't be decoded leave the
+/// stream in an unknown state, so the connection is closed rather than
+/// answered, once the requests before it have been.
+#[derive(Error, Debug)]
+enum ConnectionError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Header(#[from] HeaderError),
+    #[error(transparent)]
+    Request(#[from] RequestError),
+}
+
+/// Accepts connections and passes the requests on them to `handler`. Each
+/// connection gets its own task, and clients may pipeline requests on it.
+/// Responses carry the correlation id of their request and come back in
+/// whatever order the requests complete.
+pub struct Server<H> {
+    listener: TcpListener,
+    unix: Option<UnixSocket>,
+    handler: Arc<H>,
+    authorizer: Arc<dyn Authorizer>,
+    quotas: Arc<QuotaManager>,
+    metrics: Arc<BrokerMetrics>,
+    audit: Option<Arc<AuditLog>>,
+    limits: Limits,
+    drain_timeout: Duration,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsAcceptor>,
+}
+
+impl<H> fmt::Debug for Server<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Server");
+        debug
+            .field("listener", &self.listener)
+            .field("unix", &self.unix)
+            .field("quotas", self.quotas.config())
+            .field("audit", &self.audit.as_ref().map(|audit| audit.path()))
+            .field("limits", &self.limits)
+            .field("drain_timeout", &self.drain_timeout);
+        #[cfg(feature = "tls")]
+        debug.field("tls", &self.tls.is_some());
+        debug.finish_non_exhaustive()
+    }
+}
+
+/// A Unix domain socket listened on besides the TCP listener, removed again
+/// once the server stops.
+#[derive(Debug)]
+struct UnixSocket {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+/// Per-connection limits, see [`Server::with_max_frame_size`],
+/// [`Server::with_max_in_flight`] and [`Server::with_decode_limits`].
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    max_frame_size: usize,
+    max_in_flight: usize,
+    decode: DecodeLimits,
+}
+
+impl<H: Handler> Server<H> {
+    pub async fn bind(addr: impl ToSocketAddrs, handler: H) -> io::Result<Self> {
+        Ok(Server {
+            listener: TcpListener::bind(addr).await?,
+            unix: None,
+            handler: Arc::new(handler),
+            authorizer: Arc::new(AllowAll),
+            quotas: Arc::default(),
+            metrics: Arc::default(),
+            audit: None,
+            limits: Limits {
+                max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+                max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+                decode: DecodeLimits::default(),
+            },
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+            #[cfg(feature = "tls")]
+            tls: None,
+        })
+    }
+
+    /// Only takes TLS connections, handshaking with `acceptor`.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, acceptor: TlsAcceptor) -> Self {
+        self.tls = Some(acceptor);
+        self
+    }
+
+    /// Also takes connections on a Unix domain socket at `path`, for clients
+    /// on the same host. A socket left behind at `path` by an earlier run is
+    /// replaced. Connections on it are plaintext and anonymous, even with
+    /// [`with_tls`](Server::with_tls) set.
+    pub fn with_unix_socket(mut self, path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        if std::fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_socket()) {
+            std::fs::remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)?;
+        self.unix = Some(UnixSocket { listener, path });
+        Ok(self)
+    }
+
+    /// Checks every request with `authorizer`. Everything is allowed
+    /// otherwise.
+    pub fn with_authorizer(mut self, authorizer: impl Authorizer) -> Self {
+        self.authorizer = Arc::new(authorizer);
+        self
+    }
+
+    /// Throttles clients going over the byte rates in `config`, see
+    /// [`QuotaManager`].
+    pub fn with_quotas(mut self, config: QuotaConfig) -> Self {
+        self.quotas = Arc::new(QuotaManager::new(config));
+        self
+    }
+
+    /// Also throttles the clients of each namespace in `namespaces` as a
+    /// whole, see [`QuotaManager::with_namespaces`].
+    pub fn with_namespace_quotas(mut self, namespaces: Arc<Namespaces>) -> Self {
+        let config = self.quotas.config().clone();
+        self.quotas = Arc::new(QuotaManager::new(config).with_namespaces(namespaces));
+        self
+    }
+
+    /// Writes a line to `audit` for every request answered.
+    pub fn with_audit_log(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(Arc::new(audit));
+        self
+    }
+
+    /// Largest request accepted. Anything bigger is skipped and answered
+    /// with [`ErrorCode::MessageTooLarge`].
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.limits.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Caps on the sizes requests may claim. Requests over them are answered
+    /// with [`ErrorCode::MessageTooLarge`].
+    pub fn with_decode_limits(mut self, decode: DecodeLimits) -> Self {
+        self.limits.decode = decode;
+        self
+    }
+
+    /// Requests a connection may have outstanding before the server stops
+    /// reading from it.
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.limits.max_in_flight = max_in_flight;
+        self
+    }
+
+    /// How long [`run_until`](Server::run_until) waits for in-flight requests
+    /// once shutting down.
+    pub fn with_drain_timeout(mut self, drain_timeout: Duration) -> Self {
+        self.drain_timeout = drain_timeout;
+        self
+    }
+
+    /// Counters for every request this server answers, to read while it
+    /// runs.
+    pub fn metrics(&self) -> Arc<BrokerMetrics> {
+        self.metrics.clone()
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Path of the Unix domain socket, if listening on one.
+    pub fn unix_path(&self) -> Option<&Path> {
+        self.unix.as_ref().map(|unix| unix.path.as_path())
+    }
+
+    /// Runs the accept loop. Only returns if accepting fails.
+    pub async fn run(self) -> io::Result<()> {
+        self.run_until(std::future::pending()).await
+    }
+
+    /// Runs the accept loop until `shutdown` completes, then stops accepting
+    /// and drains the open connections: they stop reading new requests but
+    /// answer the ones in flight. Connections still busy after the drain
+    /// timeout are dropped.
+    pub async fn run_until(self, shutdown: impl Future<Output = ()>) -> io::Result<()> {
+        let (stop, stopping) = watch::channel(false);
+        let mut connections = JoinSet::new();
+        tokio::pin!(shutdown);
+
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => {
+                    let (stream, peer) = accepted?;
+                    let connection = self.connection(&stopping);
+                    connections.spawn(async move {
+                        if let Err(err) = connection.serve(stream).await {
+                            tracing::debug!(%peer, "closing connection: {}", err);
+                        }
+                    });
+                }
+                accepted = accept_unix(self.unix.as_ref()) => {
+                    let stream = accepted?;
+                    let connection = self.connection(&stopping);
+                    connections.spawn(async move {
+                        let served = connection
+                            .serve_stream(stream, Principal::Anonymous, None)
+                            .await;
+                        if let Err(err) = served {
+                            tracing::debug!("closing unix connection: {}", err);
+                        }
+                    });
+                }
+                // Reap closed connections as they go
+                Some(_) = connections.join_next() => {}
+                _ = &mut shutdown => break,
+            }
+        }
+
+        drop(self.listener);
+        if let Some(unix) = self.unix {
+            drop(unix.listener);
+            let _ = std::fs::remove_file(unix.path);
+        }
+        stop.send_replace(true);
+        let drain = async { while connections.join_next().await.is_some() {} };
+        // Whatever is left is aborted as the set drops
+        let _ = tokio::time::timeout(self.drain_timeout, drain).await;
+        Ok(())
+    }
+}
+
+impl<H> Server<H> {
+    fn connection(&self, stopping: &watch::Receiver<bool>) -> Connection<H> {
+        Connection {
+            handler: self.handler.clone(),
+            authorizer: self.authorizer.clone(),
+            quotas: self.quotas.clone(),
+            metrics: self.metrics.clone(),
+            audit: self.audit.clone(),
+            limits: self.limits,
+            stopping: stopping.clone(),
+            #[cfg(feature = "tls")]
+            tls: self.tls.clone(),
+        }
+    }
+}
+
+/// Accepts on `unix`, or never completes without one.
+async fn accept_unix(unix: Option<&UnixSocket>) -> io::Result<UnixStream> {
+    match unix {
+        Some(unix) => Ok(unix.listener.accept().await?.0),
+        None => std::future::pending().await,
+    }
+}
+
+/// What the accept loop hands each connection task.
+struct Connection<H> {
+    handler: Arc<H>,
+    authorizer: Arc<dyn Authorizer>,
+    quotas: Arc<QuotaManager>,
+    metrics: Arc<BrokerMetrics>,
+    audit: Option<Arc<AuditLog>>,
+    limits: Limits,
+    stopping: watch::Receiver<bool>,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsAcceptor>,
+}
+
+impl<H: Handler> Connection<H> {
+    async fn serve(self, stream: TcpStream) -> Result<(), ConnectionError> {
+        stream.set_nodelay(true)?;
+        let peer = stream.peer_addr().ok();
+
+        #[cfg(feature = "tls")]
+        if let Some(tls) = self.tls.clone() {
+            let stream = tls.accept(stream).await?;
+            // Only set when the acceptor requires client certificates
+            let principal = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .and_then(crate::tls::principal_name)
+                .map_or(Principal::Anonymous, Principal::User);
+            return self.serve_stream(stream, principal, peer).await;
+        }
+        self.serve_stream(stream, Principal::Anonymous, peer).await
+    }
+
+    async fn serve_stream(
+        self,
+        stream: impl AsyncRead + AsyncWrite + Send + 'static,
+        principal: Principal,
+        peer: Option<SocketAddr>,
+    ) -> Result<(), ConnectionError> {
+        let (reader, writer) = tokio::io::split(stream);
+        let (responses, pending) = mpsc::channel(self.limits.max_in_flight);
+
+        // The writer finishes once every in-flight request has sent its
+        // response and dropped its sender. Both halves live in this task, so
+        // dropping it closes the connection.
+        let (read, written) = tokio::join!(
+            self.read_requests(BufReader::new(reader), principal, peer, responses),
+            write_responses(BufWriter::new(writer), pending),
+        );
+        read?;
+        Ok(written?)
+    }
+
+    /// Reads requests and runs each in its own task, so a slow request
+    /// doesn't hold up the ones behind it. Stops taking new requests while
+    /// the in-flight limit is reached, and for good once the server is
+    /// stopping.
+    async fn read_requests(
+        mut self,
+        mut reader: impl AsyncRead + Unpin,
+        principal: Principal,
+        peer: Option<SocketAddr>,
+        responses: mpsc::Sender<EncodedResponse>,
+    ) -> Result<(), ConnectionError> {
+        let in_flight = Arc::new(Semaphore::new(self.limits.max_in_flight));
+        loop {
+            let max_frame_size = self.limits.max_frame_size;
+            let frame = tokio::select! {
+                frame = read_frame_or_skip(&mut reader, max_frame_size, RequestHeader::MAX_SIZE) => frame?,
+                _ = self.stopping.wait_for(|&stop| stop) => None,
+            };
+            let received = Instant::now();
+            // Oversized frames keep only their head, enough for the header
+            let (mut frame, bytes_in, oversized) = match frame {
+                Some(Frame::Whole(frame)) => {
+                    let len = frame.len();
+                    (frame, len, false)
+                }
+                Some(Frame::Oversized { head, len }) => (head, len, true),
+                None => break,
+            };
+            let header = RequestHeader::decode(&mut frame)?;
+            let span = tracing::info_span!(
+                "request",
+                api = %header.api_key,
+                correlation_id = header.correlation_id,
+                client_id = %header.client_id,
+                principal = %principal,
+                topic = tracing::field::Empty,
+                partition = tracing::field::Empty,
+            );
+            if oversized {
+                span.in_scope(|| {
+                    tracing::warn!(
+                        bytes = bytes_in,
+                        limit = max_frame_size,
+                        "request over the frame size limit"
+                    )
+                });
+                self.refuse(
+                    &header,
+                    ErrorCode::MessageTooLarge,
+                    bytes_in,
+                    received,
+                    &responses,
+                )
+                .await;
+                continue;
+            }
+            let decoded = span.in_scope(|| {
+                let _decode = tracing::debug_span!("decode", bytes = bytes_in).entered();
+                Request::decode_with_limits(header.api_key, frame, &self.limits.decode)
+            });
+            let request = match decoded {
+                Ok(request) => request,
+                Err(err) => {
+                    let Some(limit) = err.limit_exceeded() else {
+                        return Err(err.into());
+                    };
+                    span.in_scope(|| tracing::warn!("{}", limit));
+                    self.refuse(
+                        &header,
+                        ErrorCode::MessageTooLarge,
+                        bytes_in,
+                        received,
+                        &responses,
+                    )
+                    .await;
+                    continue;
+                }
+            };
+            if let (Some(topic), Some(partition)) = (request.topic(), request.partition()) {
+                span.record("topic", topic);
+                span.record("partition", partition);
+            }
+            let context = RequestContext::new(header, principal.clone()).with_peer(peer);
+
+            let permit = in_flight.clone().acquire_owned().await.unwrap();
+            let handler = self.handler.clone();
+            let authorizer = self.authorizer.clone();
+            let quotas = self.quotas.clone();
+            let metrics = self.metrics.clone();
+            let responses = responses.clone();
+            let audited = self.audit.clone().map(|audit| {
+                let target = (request.topic().map(str::to_string), request.partition());
+                (audit, target)
+            });
+            let answer = async move {
+                let respond = request.expects_response();
+                let request_size = request.size();
+                let topic = request.topic().map(str::to_string).unwrap_or_default();
+                let mut response =
+                    dispatch(handler.as_ref(), authorizer.as_ref(), &context, request)
+                        .instrument(tracing::debug_span!("dispatch"))
+                        .await;
+
+                // Holding the permit while throttled keeps a client that is
+                // over quota from piling up more requests
+                let throttle = match &response {
+                    Response::Produce(_) => quotas.record_produce(&context, &topic, request_size),
+                    Response::Fetch(fetch) => quotas.record_fetch(&context, &topic, fetch.size()),
+                    _ => Duration::ZERO,
+                };
+                if !throttle.is_zero() {
+                    let throttle_time_ms = throttle.as_millis().try_into().unwrap_or(u32::MAX);
+                    response.set_throttle_time_ms(throttle_time_ms);
+                    tracing::debug!(throttle_time_ms, "over quota, throttling");
+                    tokio::time::sleep(throttle).await;
+                }
+
+                let encoded = respond.then(|| {
+                    let _encode = tracing::debug_span!("encode").entered();
+                    let header = ResponseHeader::new(context.correlation_id);
+                    let mut buf = BytesMut::with_capacity(header.size() + response.size());
+                    header.encode_into(&mut buf);
+                    response.encode_into(&mut buf);
+                    let slices = match &mut response {
+                        Response::Fetch(fetch) => std::mem::take(&mut fetch.slices),
+                        _ => vec![],
+                    };
+                    EncodedResponse {
+                        frame: buf.freeze(),
+                        slices,
+                    }
+                });
+                let latency = received.elapsed();
+                let bytes_out = encoded.as_ref().map_or(0, EncodedResponse::len);
+                metrics.record(
+                    response.api_key(),
+                    bytes_in,
+                    bytes_out,
+                    response.error(),
+                    latency,
+                );
+                tracing::debug!(
+                    error = %response.error(),
+                    latency_us = latency.as_micros() as u64,
+                    bytes_out,
+                    "answered"
+                );
+                if let Some((audit, (topic, partition))) = audited {
+                    let api_key = response.api_key();
+                    let error = response.error();
+                    if let Err(err) = audit.record(
+                        &context,
+                        api_key,
+                        topic.as_deref(),
+                        partition,
+                        error,
+                        latency,
+                    ) {
+                        tracing::warn!(path = ?audit.path(), "cannot write audit log: {}", err);
+                    }
+                }
+                if let Some(encoded) = encoded {
+                    let _ = responses.send(encoded).await;
+                }
+                drop(permit);
+            };
+            tokio::spawn(answer.instrument(span));
+        }
+        Ok(())
+    }
+
+    /// Answers a request turned away before it could be decoded, leaving
+    /// the connection open for the ones behind it.
+    async fn refuse(
+        &self,
+        header: &RequestHeader,
+        error: ErrorCode,
+        bytes_in: usize,
+        received: Instant,
+        responses: &mpsc::Sender<EncodedResponse>,
+    ) {
+        let response = Response::error_for(header.api_key, error);
+        let response_header = ResponseHeader::new(header.correlation_id);
+        let mut buf = BytesMut::with_capacity(response_header.size() + response.size());
+        response_header.encode_into(&mut buf);
+        response.encode_into(&mut buf);
+        self.metrics.record(
+            header.api_key,
+            bytes_in,
+            buf.len(),
+            error,
+            received.elapsed(),
+        );
+        let encoded = EncodedResponse {
+            frame: buf.freeze(),
+            slices: vec![],
+        };
+        let _ = responses.send(encoded).await;
+    }
+}
+
+/// A response ready to be written: the encoded frame, then for fetches
+/// served from sealed segments the batches still in their files.
+#[derive(Debug)]
+struct EncodedResponse {
+    frame: Bytes,
+    slices: Vec<FileSlice>,
+}
+
+impl EncodedResponse {
+    fn len(&self) -> usize {
+        self.frame.len()
+            + self
+                .slices
+                .iter()
+                .map(|slice| slice.len() as usize)
+                .sum::<usize>()
+    }
+
+    /// Writes the response with its length prefix, copying the slices
+    /// straight from their files. Doesn't flush.
+    async fn write_to(&self, writer: &mut (impl AsyncWrite + Unpin)) -> io::Result<()> {
+        if self.slices.is_empty() {
+            return write_frame(writer, &self.frame).await;
+        }
+        writer.write_u32(self.len() as u32).await?;
+        writer.write_all(&self.frame).await?;
+        for slice in &self.slices {
+            slice.copy_to(writer).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes encoded responses as they complete, which may not be the order
+/// their requests came in. Clients match them up by correlation id.
+async fn write_responses(
+    mut writer: impl AsyncWrite + Unpin,
+    mut pending: mpsc::Receiver<EncodedResponse>,
+) -> io::Result<()> {
+    while let Some(mut next) = pending.recv().await {
+        loop {
+            next.write_to(&mut writer).await?;
+
+            // Flush once whatever has completed so far is written.
+            match pending.try_recv() {
+                Ok(response) => next = response,
+                Err(_) => break,
+            }
+        }
+        writer.flush().await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use bytes::BufMut;
+
+    use crate::auth::AclAuthorizer;
+    use crate::broker::{read_frame, LogHandler};
+    use crate::client::Client;
+    use crate::events::{Event, EventBus};
+    use crate::protocol::{ApiKey, TopicPartition};
+    use crate::record::{Record, RecordBatch};
+    use crate::replication::{FetcherConfig, ReplicaConfig};
+    use crate::request::{
+        Acks, CreateTopic, DeleteTopic, DescribeCluster, DescribeGroups, DescribeLogDirs,
+        EstimateCleanup, Fetch, LeaderAndIsr, ListGroups, ListOffsets, Metadata, OffsetCommit,
+        Produce, LATEST_TIMESTAMP,
+    };
+    use crate::response::{
+        AdminResponse, EstimateCleanupResponse, FetchResponse, GroupState, ListOffsetsResponse,
+        PartitionCleanupEstimate, ProduceResponse,
+    };
+    use crate::storage::{CleanupPolicy, FlushPolicy, LogConfig, LogDirs, Placement};
+    use crate::tenant::{NamespaceConfig, NamespaceQuota};
+
+    async fn start() -> (SocketAddr, tempfile::TempDir) {
+        start_with(LogConfig::default()).await
+    }
+
+    async fn start_with(config: LogConfig) -> (SocketAddr, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = LogDirs::open([dir.path().to_path_buf()], config, Placement::default());
+        logs.create(&TopicPartition::new("events", 0)).unwrap();
+
+        // Broker 1 may replicate from it
+        let replicas = ReplicaConfig {
+            peers: HashMap::from([(1, "127.0.0.1:1".to_string())]),
+            ..Default::default()
+        };
+        let handler = LogHandler::new(Arc::new(logs)).with_replica_config(replicas);
+        let server = Server::bind("127.0.0.1:0", handler).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+        (addr, dir)
+    }
+
+    async fn send(stream: &mut (impl AsyncWrite + Unpin), correlation_id: u32, request: &Request) {
+        let header =
+            RequestHeader::new(request.api_key(), correlation_id, "test".to_string()).unwrap();
+        let mut buf = BytesMut::new();
+        header.encode_into(&mut buf);
+        request.encode_into(&mut buf);
+        write_frame(stream, &buf).await.unwrap();
+        stream.flush().await.unwrap();
+    }
+
+    async fn receive(stream: &mut (impl AsyncRead + Unpin)) -> (u32, Bytes) {
+        let mut frame = read_frame(stream, DEFAULT_MAX_FRAME_SIZE)
+            .await
+            .unwrap()
+            .unwrap();
+        let header = ResponseHeader::decode(&mut frame).unwrap();
+        (header.correlation_id, frame)
+    }
+
+    async fn call(
+        stream: &mut (impl AsyncRead + AsyncWrite + Unpin),
+        correlation_id: u32,
+        request: Request,
+    ) -> Response {
+        send(stream, correlation_id, &request).await;
+        let (response_id, body) = receive(stream).await;
+        assert_eq!(response_id, correlation_id);
+        Response::decode(request.api_key(), body).unwrap()
+    }
+
+    fn produce(topic: &str, values: &[&'static str]) -> Request {
+        let batch = RecordBatch::new(
+            values
+                .iter()
+                .map(|value| Record::new(None, Some(Bytes::from_static(value.as_bytes()))))
+                .collect(),
+        );
+        Produce::new(topic.to_string(), 0, batch).unwrap().into()
+    }
+
+    #[tokio::test]
+    async fn test_produce_and_fetch() {
+        let (addr, _dir) = start().await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        let response = call(&mut stream, 1, produce("events", &["a", "b"])).await;
+        assert_eq!(response, Response::Produce(ProduceResponse::new(0)));
+        let response = call(&mut stream, 2, produce("events", &["c"])).await;
+        assert_eq!(response, Response::Produce(ProduceResponse::new(2)));
+
+        let fetch = Fetch::new("events".to_string(), 0, 1, 1024).unwrap();
+        let Response::Fetch(response) = call(&mut stream, 3, fetch.into()).await else {
+            panic!("expected a fetch response");
+        };
+        assert_eq!(response.error, ErrorCode::None);
+        assert_eq!(response.high_watermark, 3);
+        let values: Vec<_> = response
+            .batches
+            .iter()
+            .flat_map(|batch| batch.records.iter())
+            .map(|record| record.value.clone().unwrap())
+            .collect();
+        assert_eq!(values, vec!["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_high_watermark() {
+        let (addr, _dir) = start().await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let fetch = |offset, replica_id: Option<u32>| -> Request {
+            let fetch = Fetch::new("events".to_string(), 0, offset, 1024).unwrap();
+            match replica_id {
+                Some(replica_id) => fetch.from_replica(replica_id).into(),
+                None => fetch.into(),
+            }
+        };
+        let fetched = |response: Response| -> (u64, usize) {
+            let Response::Fetch(response) = response else {
+                panic!("expected a fetch response");
+            };
+            (response.high_watermark, response.batches.len())
+        };
+
+        // Brokers the partition isn't assigned to can't follow it
+        assert_eq!(
+            call(&mut stream, 0, fetch(0, Some(2))).await,
+            Response::Fetch(FetchResponse::error(ErrorCode::BrokerIdNotRegistered))
+        );
+
+        // The follower joins the in-sync replicas once caught up
+        call(&mut stream, 1, produce("events", &["a", "b"])).await;
+        assert_eq!(
+            fetched(call(&mut stream, 2, fetch(0, Some(1))).await),
+            (2, 1)
+        );
+        assert_eq!(
+            fetched(call(&mut stream, 3, fetch(2, Some(1))).await),
+            (2, 0)
+        );
+
+        // Consumers don't see what it hasn't fetched yet, followers do
+        call(&mut stream, 4, produce("events", &["c"])).await;
+        assert_eq!(fetched(call(&mut stream, 5, fetch(0, None)).await), (2, 1));
+        assert_eq!(
+            fetched(call(&mut stream, 6, fetch(2, Some(1))).await),
+            (2, 1)
+        );
+
+        // A parked consumer is woken once the follower has it
+        let mut consumer = TcpStream::connect(addr).await.unwrap();
+        let parked = Fetch::new("events".to_string(), 0, 2, 1024)
+            .unwrap()
+            .wait_for(1, 10_000);
+        send(&mut consumer, 7, &parked.into()).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(
+            fetched(call(&mut stream, 8, fetch(3, Some(1))).await),
+            (3, 0)
+        );
+        let (_, body) = receive(&mut consumer).await;
+        let response = Response::decode(ApiKey::Fetch, body).unwrap();
+        assert_eq!(fetched(response), (3, 1));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_sealed_segments() {
+        let (addr, _dir) = start_with(LogConfig {
+            segment_bytes: 64 * 1024,
+            ..Default::default()
+        })
+        .await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        // Each batch is bigger than a segment, so all but the last get sealed
+        let values: Vec<Bytes> = (0..3u8)
+            .map(|i| Bytes::from(vec![b'a' + i; 100 * 1024]))
+            .collect();
+        for value in &values {
+            let batch = RecordBatch::new(vec![Record::new(None, Some(value.clone()))]);
+            let produce = Produce::new("events".to_string(), 0, batch).unwrap();
+            call(&mut stream, 1, produce.into()).await;
+        }
+
+        for offset in 0..3 {
+            let fetch = Fetch::new("events".to_string(), 0, offset, 1024 * 1024).unwrap();
+            let Response::Fetch(response) = call(&mut stream, 2, fetch.into()).await else {
+                panic!("expected a fetch response");
+            };
+            assert_eq!(response.error, ErrorCode::None);
+            assert_eq!(response.high_watermark, 3);
+            assert_eq!(response.batches.len(), 1);
+            let record = &response.batches[0].records[0];
+            assert_eq!(record.offset, offset);
+            assert_eq!(record.value.as_ref(), Some(&values[offset as usize]));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        let server = Server::bind("127.0.0.1:0", Arc::new(Principals::default()))
+            .await
+            .unwrap();
+        let metrics = server.metrics();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = produce("events", &["a"]);
+        call(&mut stream, 1, request.clone()).await;
+        let fetch = Fetch::new("events".to_string(), 0, 0, 1024).unwrap();
+        call(&mut stream, 2, fetch.into()).await;
+
+        let snapshot = metrics.snapshot();
+        let produced = snapshot.api(ApiKey::Produce);
+        assert_eq!(produced.requests, 1);
+        assert_eq!(produced.errors, 0);
+        let header = RequestHeader::new(ApiKey::Produce, 1, "test".to_string()).unwrap();
+        assert_eq!(produced.bytes_in, (header.size() + request.size()) as u64);
+        assert_eq!(
+            produced.bytes_out,
+            (ResponseHeader::new(1).size() + ProduceResponse::new(0).size()) as u64
+        );
+        assert_eq!(produced.latency_us.count, 1);
+        assert_eq!(snapshot.api(ApiKey::Fetch).requests, 1);
+    }
+
+    #[tokio::test]
+    async fn test_describe() {
+        let (addr, dir) = start().await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        call(&mut stream, 1, produce("events", &["a", "b"])).await;
+
+        let request = DescribeCluster::new().into();
+        let Response::DescribeCluster(cluster) = call(&mut stream, 2, request).await else {
+            panic!("expected a DescribeCluster response");
+        };
+        assert_eq!(cluster.brokers.len(), 1);
+        assert_eq!(cluster.brokers[0].version, crate::VERSION);
+
+        let request = DescribeLogDirs::all().into();
+        let Response::DescribeLogDirs(described) = call(&mut stream, 3, request).await else {
+            panic!("expected a DescribeLogDirs response");
+        };
+        assert_eq!(described.dirs.len(), 1);
+        assert_eq!(described.dirs[0].path, dir.path().display().to_string());
+        let partition = &described.dirs[0].partitions[0];
+        assert_eq!(
+            (partition.topic.as_str(), partition.partition),
+            ("events", 0)
+        );
+        assert_eq!((partition.segments, partition.next_offset), (1, 2));
+        assert!(partition.size > 0);
+
+        let request = DescribeLogDirs::new(vec!["other".to_string()]).unwrap();
+        let Response::DescribeLogDirs(described) = call(&mut stream, 4, request.into()).await
+        else {
+            panic!("expected a DescribeLogDirs response");
+        };
+        assert!(described.dirs[0].partitions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_estimate_cleanup() {
+        let (addr, _dir) = start_with(LogConfig {
+            segment_bytes: 1,
+            ..Default::default()
+        })
+        .await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        for (i, value) in ["a", "b", "c"].into_iter().enumerate() {
+            call(&mut stream, i as u32, produce("events", &[value])).await;
+        }
+
+        let Response::EstimateCleanup(as_configured) = call(
+            &mut stream,
+            3,
+            EstimateCleanup::new("events".to_string()).unwrap().into(),
+        )
+        .await
+        else {
+            panic!("expected an EstimateCleanup response");
+        };
+        assert_eq!(as_configured.error, ErrorCode::None);
+        let partition = &as_configured.partitions[0];
+        assert_eq!((partition.segments, partition.reclaimable_bytes), (0, 0));
+
+        // Only the active segment would be left
+        let request = EstimateCleanup::new("events".to_string())
+            .unwrap()
+            .with_retention_bytes(0);
+        let Response::EstimateCleanup(retained) = call(&mut stream, 4, request.into()).await else {
+            panic!("expected an EstimateCleanup response");
+        };
+        let size = partition.size;
+        assert_eq!(
+            retained.partitions,
+            vec![PartitionCleanupEstimate {
+                partition: 0,
+                size,
+                segments: 2,
+                records_removed: 2,
+                reclaimable_bytes: size / 3 * 2,
+            }]
+        );
+
+        let request = EstimateCleanup::new("other".to_string()).unwrap();
+        assert_eq!(
+            call(&mut stream, 5, request.into()).await,
+            Response::EstimateCleanup(EstimateCleanupResponse::error(
+                ErrorCode::UnknownTopicOrPartition
+            ))
+        );
+
+        // Still all there
+        let fetch = Fetch::new("events".to_string(), 0, 0, 1024).unwrap();
+        let Response::Fetch(fetched) = call(&mut stream, 6, fetch.into()).await else {
+            panic!("expected a Fetch response");
+        };
+        assert_eq!(fetched.error, ErrorCode::None);
+        assert_eq!(fetched.high_watermark, 3);
+    }
+
+    #[tokio::test]
+    async fn test_estimate_cleanup_panics() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = LogDirs::open(
+            [dir.path().to_path_buf()],
+            LogConfig::default(),
+            Placement::default(),
+        );
+        let log = logs.create(&TopicPartition::new("events", 0)).unwrap();
+        // A panic while it was held leaves the log's lock poisoned
+        std::thread::spawn(move || {
+            let _log = log.write().unwrap();
+            panic!("poisoned");
+        })
+        .join()
+        .unwrap_err();
+
+        let server = Server::bind("127.0.0.1:0", LogHandler::new(Arc::new(logs)))
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = EstimateCleanup::new("events".to_string()).unwrap();
+        assert_eq!(
+            call(&mut stream, 1, request.into()).await,
+            Response::EstimateCleanup(EstimateCleanupResponse::error(
+                ErrorCode::UnknownServerError
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_groups() {
+        let (addr, _dir) = start().await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        call(&mut stream, 1, produce("events", &["a", "b", "c"])).await;
+
+        let offsets = vec![
+            (TopicPartition::new("events", 0), 1),
+            (TopicPartition::new("elsewhere", 0), 4),
+        ];
+        let commit = OffsetCommit::new("billing".to_string(), offsets).unwrap();
+        let response = call(&mut stream, 2, commit.into()).await;
+        assert_eq!(response, Response::OffsetCommit(AdminResponse::new()));
+
+        let Response::ListGroups(listed) = call(&mut stream, 3, ListGroups::new().into()).await
+        else {
+            panic!("expected a ListGroups response");
+        };
+        assert_eq!(
+            listed.groups,
+            vec![("billing".to_string(), GroupState::Empty)]
+        );
+
+        let request = DescribeGroups::new(vec!["billing".to_string(), "search".to_string()]);
+        let Response::DescribeGroups(described) =
+            call(&mut stream, 4, request.unwrap().into()).await
+        else {
+            panic!("expected a DescribeGroups response");
+        };
+        let billing = &described.groups[0];
+        let lags: Vec<_> = billing.offsets.iter().map(|offset| offset.lag()).collect();
+        // Sorted by topic, the other partition isn't stored here
+        assert_eq!(lags, vec![None, Some(2)]);
+        assert_eq!(described.groups[1].error, ErrorCode::GroupIdNotFound);
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let (addr, _dir) = start().await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        let response = call(&mut stream, 1, produce("missing", &["a"])).await;
+        assert_eq!(
+            response,
+            Response::Produce(ProduceResponse::error(ErrorCode::UnknownTopicOrPartition))
+        );
+
+        let fetch = Fetch::new("events".to_string(), 0, 10, 1024).unwrap();
+        let response = call(&mut stream, 2, fetch.into()).await;
+        assert_eq!(
+            response,
+            Response::Fetch(FetchResponse::error(ErrorCode::OffsetOutOfRange))
+        );
+
+        // The partition is still at epoch 0
+        let Request::Produce(fenced) = produce("events", &["a"]) else {
+            unreachable!()
+        };
+        let response = call(&mut stream, 3, fenced.with_leader_epoch(1).into()).await;
+        assert_eq!(
+            response,
+            Response::Produce(ProduceResponse::error(ErrorCode::UnknownLeaderEpoch))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_auto_create_topics() {
+        let handler = LogHandler::new(Arc::new(LogDirs::in_memory(LogConfig::default())))
+            .with_auto_create_topics(2);
+        let server = Server::bind("127.0.0.1:0", handler.clone()).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        handler.replicas().set_advertised_listener(addr.to_string());
+        tokio::spawn(server.run());
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        let response = call(&mut stream, 1, produce("events", &["a"])).await;
+        assert_eq!(response, Response::Produce(ProduceResponse::new(0)));
+        let fetch = Fetch::new("jobs".to_string(), 1, 0, 1024).unwrap();
+        let response = call(&mut stream, 2, fetch.into()).await;
+        assert_eq!(response, Response::Fetch(FetchResponse::new(0, vec![])));
+        assert_eq!(handler.logs().partitions().len(), 4);
+
+        // Partitions past the count of an existing topic stay unknown
+        let fetch = Fetch::new("jobs".to_string(), 2, 0, 1024).unwrap();
+        let response = call(&mut stream, 3, fetch.into()).await;
+        assert_eq!(
+            response,
+            Response::Fetch(FetchResponse::error(ErrorCode::UnknownTopicOrPartition))
+        );
+
+        // Clients ask for unknown topics by name
+        let client = Client::new(vec![addr.to_string()]);
+        assert_eq!(client.partition_count("tasks").await.unwrap(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_auto_create_topics_unauthorized() {
+        let authorizer = AclAuthorizer::parse(
+            "allow * write topic:events\n\
+             allow * describe topic:events\n\
+             allow * read topic:jobs\n\
+             allow * create topic:jobs\n",
+        )
+        .unwrap();
+        let handler = LogHandler::new(Arc::new(LogDirs::in_memory(LogConfig::default())))
+            .with_auto_create_topics(1)
+            .with_authorizer(authorizer.clone());
+        let server = Server::bind("127.0.0.1:0", handler.clone())
+            .await
+            .unwrap()
+            .with_authorizer(authorizer);
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        // Writing to or describing a topic doesn't let clients create it
+        let response = call(&mut stream, 1, produce("events", &["a"])).await;
+        assert_eq!(
+            response,
+            Response::Produce(ProduceResponse::error(ErrorCode::UnknownTopicOrPartition))
+        );
+        let Response::Metadata(response) = call(
+            &mut stream,
+            2,
+            Metadata::new(vec!["events".to_string()]).unwrap().into(),
+        )
+        .await
+        else {
+            panic!("expected a metadata response");
+        };
+        assert_eq!(response.topics[0].error, ErrorCode::UnknownTopicOrPartition);
+        assert!(handler.logs().partitions().is_empty());
+
+        let fetch = Fetch::new("jobs".to_string(), 0, 0, 1024).unwrap();
+        let response = call(&mut stream, 3, fetch.into()).await;
+        assert_eq!(response, Response::Fetch(FetchResponse::new(0, vec![])));
+        assert_eq!(handler.logs().partitions().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_auto_create_topics_concurrently() {
+        let handler = LogHandler::new(Arc::new(LogDirs::in_memory(LogConfig::default())))
+            .with_auto_create_topics(4);
+        let server = Server::bind("127.0.0.1:0", handler.clone()).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        // Every produce racing to create the topic lands
+        let produces = (0..8).map(|_| {
+            tokio::spawn(async move {
+                let mut stream = TcpStream::connect(addr).await.unwrap();
+                call(&mut stream, 1, produce("events", &["a"])).await
+            })
+        });
+        let mut offsets = vec![];
+        for produce in produces.collect::<Vec<_>>() {
+            let Response::Produce(response) = produce.await.unwrap() else {
+                panic!("expected a produce response");
+            };
+            assert_eq!(response.error, ErrorCode::None);
+            offsets.push(response.base_offset);
+        }
+        offsets.sort_unstable();
+        assert_eq!(offsets, (0..8).collect::<Vec<_>>());
+        assert_eq!(handler.logs().partitions().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_delayed_fetch() {
+        let (addr, _dir) = start().await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        // Completed by the produce behind it
+        let fetch = Fetch::new("events".to_string(), 0, 0, 1024)
+            .unwrap()
+            .wait_for(1, 10_000);
+        send(&mut stream, 1, &fetch.into()).await;
+        send(&mut stream, 2, &produce("events", &["a"])).await;
+        let (first, _) = receive(&mut stream).await;
+        assert_eq!(first, 2);
+        let (second, body) = receive(&mut stream).await;
+        assert_eq!(second, 1);
+        let Response::Fetch(response) = Response::decode(ApiKey::Fetch, body).unwrap() else {
+            panic!("expected a fetch response");
+        };
+        assert_eq!(response.high_watermark, 1);
+        assert_eq!(response.batches.len(), 1);
+
+        // Times out with whatever there is
+        let fetch = Fetch::new("events".to_string(), 0, 1, 1024)
+            .unwrap()
+            .wait_for(1, 50);
+        let response = call(&mut stream, 3, fetch.into()).await;
+        assert_eq!(response, Response::Fetch(FetchResponse::new(1, vec![])));
+    }
+
+    fn produce_with_acks(values: &[&'static str], acks: Acks, timeout_ms: u32) -> Request {
+        let Request::Produce(produce) = produce("events", values) else {
+            unreachable!()
+        };
+        produce.with_acks(acks).with_timeout(timeout_ms).into()
+    }
+
+    #[tokio::test]
+    async fn test_acks() {
+        let (addr, _dir) = start_with(LogConfig {
+            flush_policy: FlushPolicy::EveryNMessages(4),
+            ..LogConfig::default()
+        })
+        .await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        // Appended is enough for the leader, flushed or not
+        let response = call(&mut stream, 1, produce_with_acks(&["a"], Acks::Leader, 0)).await;
+        assert_eq!(response, Response::Produce(ProduceResponse::new(0)));
+
+        let response = call(&mut stream, 2, produce_with_acks(&["b"], Acks::All, 50)).await;
+        assert_eq!(
+            response,
+            Response::Produce(ProduceResponse::error(ErrorCode::RequestTimedOut))
+        );
+
+        // Not answered, but still appended. Its record makes the log flush,
+        // completing the produce waiting on it.
+        send(
+            &mut stream,
+            3,
+            &produce_with_acks(&["c"], Acks::All, 10_000),
+        )
+        .await;
+        send(
+            &mut stream,
+            4,
+            &produce_with_acks(&["d", "e"], Acks::None, 0),
+        )
+        .await;
+        assert_eq!(receive(&mut stream).await.0, 3);
+
+        let fetch = Fetch::new("events".to_string(), 0, 0, 1024).unwrap();
+        let Response::Fetch(response) = call(&mut stream, 5, fetch.into()).await else {
+            panic!("expected a fetch response");
+        };
+        assert_eq!(response.high_watermark, 5);
+
+        // Logs left to explicit flushes are flushed for the produce
+        let (addr, _dir) = start().await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let response = call(&mut stream, 1, produce_with_acks(&["a"], Acks::All, 0)).await;
+        assert_eq!(response, Response::Produce(ProduceResponse::new(0)));
+    }
+
+    #[tokio::test]
+    async fn test_acks_in_sync_replicas() {
+        let (addr, _dir) = start().await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let follow = |offset| {
+            Fetch::new("events".to_string(), 0, offset, 1024)
+                .unwrap()
+                .from_replica(1)
+        };
+
+        // The follower catches up and joins the in-sync replicas
+        call(&mut stream, 1, produce_with_acks(&["a"], Acks::Leader, 0)).await;
+        call(&mut stream, 2, follow(0).into()).await;
+        call(&mut stream, 3, follow(1).into()).await;
+
+        // While it lags, the produce isn't replicated in time
+        let response = call(&mut stream, 4, produce_with_acks(&["b"], Acks::All, 50)).await;
+        assert_eq!(
+            response,
+            Response::Produce(ProduceResponse::error(ErrorCode::RequestTimedOut))
+        );
+
+        // Its next fetch completes the produce waiting on it
+        let mut producer = TcpStream::connect(addr).await.unwrap();
+        send(
+            &mut producer,
+            5,
+            &produce_with_acks(&["c"], Acks::All, 10_000),
+        )
+        .await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        call(&mut stream, 6, follow(3).into()).await;
+        let (correlation_id, body) = receive(&mut producer).await;
+        assert_eq!(correlation_id, 5);
+        assert_eq!(
+            Response::decode(ApiKey::Produce, body).unwrap(),
+            Response::Produce(ProduceResponse::new(2))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_events() {
+        let logs = LogDirs::in_memory(LogConfig {
+            segment_bytes: 64 * 1024,
+            ..Default::default()
+        });
+        let replicas = ReplicaConfig {
+            peers: HashMap::from([(1, "127.0.0.1:1".to_string())]),
+            ..Default::default()
+        };
+        let bus = EventBus::new();
+        let events = bus.subscribe();
+        let handler = LogHandler::new(Arc::new(logs))
+            .with_events(bus)
+            .with_replica_config(replicas);
+        let broker_id = handler.replicas().broker_id();
+        let server = Server::bind("127.0.0.1:0", handler).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let partition = TopicPartition::new("logs", 0);
+
+        let create = CreateTopic::new("logs".to_string(), 1).unwrap();
+        call(&mut stream, 1, create.into()).await;
+        let lead = LeaderAndIsr::lead("logs".to_string(), 0, 1).unwrap();
+        call(&mut stream, 2, lead.into()).await;
+
+        // The second batch doesn't fit in the first segment
+        for i in 0..2 {
+            let value = Bytes::from(vec![b'a'; 40 * 1024]);
+            let batch = RecordBatch::new(vec![Record::new(None, Some(value))]);
+            let produce = Produce::new("logs".to_string(), 0, batch).unwrap();
+            call(&mut stream, 3 + i, produce.into()).await;
+        }
+
+        // The follower joins the in-sync replicas once caught up
+        let follow = Fetch::new("logs".to_string(), 0, 2, 1024)
+            .unwrap()
+            .from_replica(1);
+        call(&mut stream, 5, follow.into()).await;
+
+        let delete = DeleteTopic::new("logs".to_string()).unwrap();
+        call(&mut stream, 6, delete.into()).await;
+
+        assert_eq!(
+            events.drain(),
+            vec![
+                Event::TopicCreated {
+                    topic: "logs".to_string(),
+                    partitions: 1
+                },
+                Event::LeadershipChanged {
+                    partition: partition.clone(),
+                    leader: broker_id
+                },
+                Event::SegmentRolled {
+                    partition: partition.clone(),
+                    base_offset: 1
+                },
+                Event::IsrChanged {
+                    partition,
+                    isr: vec![1]
+                },
+                Event::TopicDeleted {
+                    topic: "logs".to_string()
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forward_to_leader() {
+        let (leader, _dir) = start().await;
+        // Not assigned the partition, so it doesn't hold back the high
+        // watermark
+        let follower = |forwarding: bool| async move {
+            let partition = TopicPartition::new("events", 0);
+            let logs = LogDirs::in_memory(LogConfig::default());
+            logs.create(&partition).unwrap();
+            let replicas = ReplicaConfig {
+                fetcher: FetcherConfig {
+                    replica_id: 2,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let handler = LogHandler::new(Arc::new(logs)).with_replica_config(replicas);
+            let handler = if forwarding {
+                handler.with_forwarding()
+            } else {
+                handler
+            };
+            handler
+                .replicas()
+                .follow(&partition, leader.to_string(), 0)
+                .unwrap();
+            let server = Server::bind("127.0.0.1:0", handler).await.unwrap();
+            let addr = server.local_addr().unwrap();
+            tokio::spawn(server.run());
+            TcpStream::connect(addr).await.unwrap()
+        };
+
+        let mut refusing = follower(false).await;
+        assert_eq!(
+            call(&mut refusing, 1, produce("events", &["a"])).await,
+            Response::Produce(ProduceResponse::error(ErrorCode::NotLeaderOrFollower))
+        );
+
+        let mut forwarding = follower(true).await;
+        assert_eq!(
+            call(&mut forwarding, 1, produce("events", &["a", "b"])).await,
+            Response::Produce(ProduceResponse::new(0))
+        );
+        assert_eq!(
+            call(&mut forwarding, 2, produce("events", &["c"])).await,
+            Response::Produce(ProduceResponse::new(2))
+        );
+
+        // Fetches and offsets come from the leader too, up to its high
+        // watermark
+        let mut stream = TcpStream::connect(leader).await.unwrap();
+        let fetch = Fetch::new("events".to_string(), 0, 0, 1024).unwrap();
+        let Response::Fetch(expected) = call(&mut stream, 1, fetch.clone().into()).await else {
+            panic!("expected a fetch response");
+        };
+        assert_eq!((expected.high_watermark, expected.batches.len()), (3, 2));
+        let Response::Fetch(response) = call(&mut forwarding, 3, fetch.into()).await else {
+            panic!("expected a fetch response");
+        };
+        assert_eq!(
+            (response.high_watermark, response.batches),
+            (expected.high_watermark, expected.batches)
+        );
+        let latest = ListOffsets::new("events".to_string(), 0, LATEST_TIMESTAMP).unwrap();
+        assert_eq!(
+            call(&mut forwarding, 4, latest.into()).await,
+            Response::ListOffsets(ListOffsetsResponse::new(expected.high_watermark))
+        );
+    }
+
+    /// Answers fetches only after a delay, so they finish after produces sent
+    /// behind them.
+    struct SlowFetches;
+
+    impl Handler for SlowFetches {
+        async fn handle_produce(&self, _: &RequestContext, _: Produce) -> ProduceResponse {
+            ProduceResponse::new(0)
+        }
+
+        async fn handle_fetch(&self, _: &RequestContext, _: Fetch) -> FetchResponse {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            FetchResponse::new(0, vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_requests() {
+        let server = Server::bind("127.0.0.1:0", SlowFetches).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        let fetch = Fetch::new("events".to_string(), 0, 0, 1024).unwrap();
+        send(&mut stream, 1, &fetch.into()).await;
+        send(&mut stream, 2, &produce("events", &["a"])).await;
+        send(&mut stream, 3, &produce("events", &["b"])).await;
+
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            ids.push(receive(&mut stream).await.0);
+        }
+        assert_eq!(ids.last(), Some(&1));
+        ids.sort();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_connections() {
+        let server = Server::bind("127.0.0.1:0", SlowFetches).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let (shutdown, shutdown_signal) = tokio::sync::oneshot::channel::<()>();
+        let running = tokio::spawn(server.run_until(async {
+            let _ = shutdown_signal.await;
+        }));
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        let fetch = Fetch::new("events".to_string(), 0, 0, 1024).unwrap();
+        send(&mut stream, 1, &fetch.into()).await;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        shutdown.send(()).unwrap();
+
+        // The fetch in flight is still answered, then the connection closes
+        assert_eq!(receive(&mut stream).await.0, 1);
+        assert!(read_frame(&mut stream, DEFAULT_MAX_FRAME_SIZE)
+            .await
+            .unwrap()
+            .is_none());
+        running.await.unwrap().unwrap();
+        assert!(TcpStream::connect(addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drain_timeout() {
+        let server = Server::bind("127.0.0.1:0", SlowFetches)
+            .await
+            .unwrap()
+            .with_drain_timeout(std::time::Duration::from_millis(20));
+        let addr = server.local_addr().unwrap();
+        let (shutdown, shutdown_signal) = tokio::sync::oneshot::channel::<()>();
+        let running = tokio::spawn(server.run_until(async {
+            let _ = shutdown_signal.await;
+        }));
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        let fetch = Fetch::new("events".to_string(), 0, 0, 1024).unwrap();
+        send(&mut stream, 1, &fetch.into()).await;
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        shutdown.send(()).unwrap();
+
+        // Gives up on the fetch and drops the connection
+        running.await.unwrap().unwrap();
+        assert!(matches!(
+            read_frame(&mut stream, DEFAULT_MAX_FRAME_SIZE).await,
+            Ok(None) | Err(_)
+        ));
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn test_tls() {
+        use crate::tls;
+
+        let dir = tempfile::tempdir().unwrap();
+        let generated = rcgen::generate_simple_self_signed(["localhost".to_string()]).unwrap();
+        let cert_path = dir.path().join("cert.pem");
+        let key_path = dir.path().join("key.pem");
+        std::fs::write(&cert_path, generated.cert.pem()).unwrap();
+        std::fs::write(&key_path, generated.key_pair.serialize_pem()).unwrap();
+
+        let server = Server::bind("127.0.0.1:0", SlowFetches)
+            .await
+            .unwrap()
+            .with_tls(tls::acceptor(&cert_path, &key_path).unwrap());
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let connector = tls::connector(&cert_path).unwrap();
+        let mut stream = tls::connect(addr, "localhost", &connector).await.unwrap();
+        let response = call(&mut stream, 1, produce("events", &["a"])).await;
+        assert_eq!(response, Response::Produce(ProduceResponse::new(0)));
+
+        // The certificate isn't valid for other names
+        assert!(tls::connect(addr, "example.com", &connector).await.is_err());
+
+        // Plaintext requests aren't answered
+        let mut plain = TcpStream::connect(addr).await.unwrap();
+        send(&mut plain, 1, &produce("events", &["a"])).await;
+        assert!(!matches!(
+            read_frame(&mut plain, DEFAULT_MAX_FRAME_SIZE).await,
+            Ok(Some(_))
+        ));
+    }
+
+    /// Remembers who sent each produce.
+    #[derive(Default)]
+    struct Principals(std::sync::Mutex<Vec<Principal>>);
+
+    impl Handler for Arc<Principals> {
+        async fn handle_produce(&self, context: &RequestContext, _: Produce) -> ProduceResponse {
+            self.0.lock().unwrap().push(context.principal.clone());
+            ProduceResponse::new(0)
+        }
+
+        async fn handle_fetch(&self, _: &RequestContext, _: Fetch) -> FetchResponse {
+            FetchResponse::new(0, vec![])
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn test_mutual_tls() {
+        use rcgen::{
+            BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair,
+        };
+
+        use crate::tls;
+
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, pem: String| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, pem).unwrap();
+            path
+        };
+
+        let server = rcgen::generate_simple_self_signed(["localhost".to_string()]).unwrap();
+        let cert_path = write("cert.pem", server.cert.pem());
+        let key_path = write("key.pem", server.key_pair.serialize_pem());
+
+        let ca_key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::default();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = params.self_signed(&ca_key).unwrap();
+        let ca_path = write("ca.pem", ca.pem());
+
+        let client_key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::default();
+        params.distinguished_name.push(DnType::CommonName, "alice");
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        let client = params.signed_by(&client_key, &ca, &ca_key).unwrap();
+        let client_cert_path = write("client.pem", client.pem());
+        let client_key_path = write("client-key.pem", client_key.serialize_pem());
+
+        let principals = Arc::new(Principals::default());
+        let server = Server::bind("127.0.0.1:0", principals.clone())
+            .await
+            .unwrap()
+            .with_tls(tls::client_auth_acceptor(&cert_path, &key_path, &ca_path).unwrap());
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let connector =
+            tls::client_auth_connector(&cert_path, &client_cert_path, &client_key_path).unwrap();
+        let mut stream = tls::connect(addr, "localhost", &connector).await.unwrap();
+        call(&mut stream, 1, produce("events", &["a"])).await;
+        assert_eq!(
+            *principals.0.lock().unwrap(),
+            vec![Principal::User("alice".to_string())]
+        );
+
+        // Clients without a certificate are turned away
+        let connector = tls::connector(&cert_path).unwrap();
+        if let Ok(mut stream) = tls::connect(addr, "localhost", &connector).await {
+            send(&mut stream, 1, &produce("events", &["a"])).await;
+            assert!(!matches!(
+                read_frame(&mut stream, DEFAULT_MAX_FRAME_SIZE).await,
+                Ok(Some(_))
+            ));
+        }
+        assert_eq!(principals.0.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_principal() {
+        let principals = Arc::new(Principals::default());
+        let server = Server::bind("127.0.0.1:0", principals.clone())
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        call(&mut stream, 1, produce("events", &["a"])).await;
+        assert_eq!(*principals.0.lock().unwrap(), vec![Principal::Anonymous]);
+    }
+
+    #[tokio::test]
+    async fn test_unix_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("herm.sock");
+        // Left behind by an earlier run
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let principals = Arc::new(Principals::default());
+        let server = Server::bind("127.0.0.1:0", principals.clone())
+            .await
+            .unwrap()
+            .with_unix_socket(&path)
+            .unwrap();
+        assert_eq!(server.unix_path(), Some(path.as_path()));
+        let addr = server.local_addr().unwrap();
+        let (shutdown, shutdown_signal) = tokio::sync::oneshot::channel::<()>();
+        let running = tokio::spawn(server.run_until(async {
+            let _ = shutdown_signal.await;
+        }));
+
+        // Both listeners serve requests
+        let mut unix = UnixStream::connect(&path).await.unwrap();
+        let response = call(&mut unix, 1, produce("events", &["a"])).await;
+        assert_eq!(response, Response::Produce(ProduceResponse::new(0)));
+        let mut tcp = TcpStream::connect(addr).await.unwrap();
+        call(&mut tcp, 1, produce("events", &["b"])).await;
+        assert_eq!(
+            *principals.0.lock().unwrap(),
+            vec![Principal::Anonymous, Principal::Anonymous]
+        );
+
+        // The socket is cleaned up on shutdown
+        drop((unix, tcp));
+        shutdown.send(()).unwrap();
+        running.await.unwrap().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let authorizer = AclAuthorizer::parse("allow * write topic:events").unwrap();
+        let server = Server::bind("127.0.0.1:0", Arc::new(Principals::default()))
+            .await
+            .unwrap()
+            .with_authorizer(authorizer)
+            .with_audit_log(AuditLog::open(&path, 1024 * 1024, 1).unwrap());
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        call(&mut stream, 1, produce("events", &["a"])).await;
+        let fetch = Fetch::new("events".to_string(), 0, 0, 1024).unwrap();
+        call(&mut stream, 2, fetch.into()).await;
+
+        // Denied requests are audited too
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["api"], "Produce");
+        assert_eq!(lines[0]["principal"], "User:ANONYMOUS");
+        assert_eq!(lines[0]["outcome"], "None");
+        assert_eq!(lines[1]["api"], "Fetch");
+        assert_eq!(lines[1]["topic"], "events");
+        assert_eq!(lines[1]["outcome"], "TopicAuthorizationFailed");
+    }
+
+    #[tokio::test]
+    async fn test_quotas() {
+        let principals = Arc::new(Principals::default());
+        let server = Server::bind("127.0.0.1:0", principals.clone())
+            .await
+            .unwrap()
+            .with_quotas(QuotaConfig {
+                produce_rate: Some(1000),
+                window: Duration::from_millis(1),
+                ..Default::default()
+            });
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = produce("events", &["a"]);
+        let started = std::time::Instant::now();
+        let Response::Produce(response) = call(&mut stream, 1, request.clone()).await else {
+            panic!("expected a produce response");
+        };
+        // Everything past the single byte of burst is over quota
+        let expected = request.size() as u32 - 1;
+        assert!(response.throttle_time_ms.abs_diff(expected) <= 1);
+        assert!(started.elapsed() >= Duration::from_millis(expected as u64 - 1));
+
+        // Fetches aren't limited
+        let fetch = Fetch::new("events".to_string(), 0, 0, 1024).unwrap();
+        assert_eq!(
+            call(&mut stream, 2, fetch.into()).await,
+            Response::Fetch(FetchResponse::new(0, vec![]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_authorizer() {
+        let principals = Arc::new(Principals::default());
+        let authorizer = AclAuthorizer::parse("allow * read topic:events").unwrap();
+        let server = Server::bind("127.0.0.1:0", principals.clone())
+            .await
+            .unwrap()
+            .with_authorizer(authorizer);
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let response = call(&mut stream, 1, produce("events", &["a"])).await;
+        assert_eq!(
+            response,
+            Response::Produce(ProduceResponse::error(ErrorCode::TopicAuthorizationFailed))
+        );
+        // Denied before reaching the handler
+        assert!(principals.0.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_namespace_quotas() {
+        let mut namespaces = Namespaces::new();
+        let config = NamespaceConfig {
+            quota: NamespaceQuota {
+                max_topics: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        namespaces.register("team-a", config).unwrap();
+        let logs = LogDirs::in_memory(LogConfig::default());
+        logs.create(&TopicPartition::new("team-a/orders", 0))
+            .unwrap();
+        let handler = LogHandler::new(Arc::new(logs)).with_namespaces(Arc::new(namespaces));
+        let server = Server::bind("127.0.0.1:0", handler).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let create = |topic: &str| CreateTopic::new(topic.to_string(), 1).unwrap().into();
+
+        // Topics held already count towards the quota
+        assert_eq!(
+            call(&mut stream, 1, create("team-a/payments")).await,
+            Response::CreateTopic(AdminResponse::error(ErrorCode::PolicyViolation))
+        );
+        assert_eq!(
+            call(&mut stream, 2, create("team-b/payments")).await,
+            Response::CreateTopic(AdminResponse::error(ErrorCode::PolicyViolation))
+        );
+        assert_eq!(
+            call(&mut stream, 3, create("payments")).await,
+            Response::CreateTopic(AdminResponse::default())
+        );
+
+        // Deleting one makes room again
+        let delete = DeleteTopic::new("team-a/orders".to_string()).unwrap();
+        call(&mut stream, 4, delete.into()).await;
+        assert_eq!(
+            call(&mut stream, 5, create("team-a/payments")).await,
+            Response::CreateTopic(AdminResponse::default())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_namespace_topic_defaults() {
+        let mut namespaces = Namespaces::new();
+        let config = NamespaceConfig {
+            topic_defaults: HashMap::from([
+                ("cleanup.policy".to_string(), "compact".to_string()),
+                ("retention.ms".to_string(), "1000".to_string()),
+            ]),
+            ..Default::default()
+        };
+        namespaces.register("team-a", config).unwrap();
+        let logs = Arc::new(LogDirs::in_memory(LogConfig::default()));
+        logs.create(&TopicPartition::new("team-a/orders", 0))
+            .unwrap();
+        let handler = LogHandler::new(logs.clone())
+            .with_auto_create_topics(1)
+            .with_namespaces(Arc::new(namespaces));
+        let server = Server::bind("127.0.0.1:0", handler).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        let create = CreateTopic::new("team-a/payments".to_string(), 1).unwrap();
+        call(&mut stream, 1, create.into()).await;
+        call(&mut stream, 2, produce("team-a/refunds", &["a"])).await;
+        let create = CreateTopic::new("payments".to_string(), 1).unwrap();
+        call(&mut stream, 3, create.into()).await;
+
+        // Held already, created and auto-created topics all get the defaults
+        for topic in ["team-a/orders", "team-a/payments", "team-a/refunds"] {
+            let log = logs.get(&TopicPartition::new(topic, 0)).unwrap();
+            let log = log.read().unwrap();
+            assert_eq!(log.config().cleanup_policy, CleanupPolicy::Compact);
+            assert_eq!(log.config().retention_age, Some(Duration::from_secs(1)));
+        }
+        let log = logs.get(&TopicPartition::new("payments", 0)).unwrap();
+        assert_eq!(*log.read().unwrap().config(), LogConfig::default());
+    }
+
+    #[tokio::test]
+    async fn test_closes_on_malformed_request() {
+        let (addr, _dir) = start().await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+
+        let mut buf = BytesMut::new();
+        RequestHeader::new(ApiKey::Fetch, 1, "test".to_string())
+            .unwrap()
+            .encode_into(&mut buf);
+        // A topic name cut short
+        buf.put_u16(4);
+        write_frame(&mut stream, &buf).await.unwrap();
+        stream.flush().await.unwrap();
+
+        assert!(read_frame(&mut stream, DEFAULT_MAX_FRAME_SIZE)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_refuses_oversized_request() {
+        let server = Server::bind("127.0.0.1:0", Arc::new(Principals::default()))
+            .await
+            .unwrap()
+            .with_max_frame_size(256);
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let values = ["x"; 256];
+        let response = call(&mut stream, 1, produce("events", &values)).await;
+        assert_eq!(response.error(), ErrorCode::MessageTooLarge);
+
+        // The rest of the frame was skipped, the connection still works
+        let response = call(&mut stream, 2, produce("events", &["a"])).await;
+        assert_eq!(response.error(), ErrorCode::None);
+    }
+
+    #[tokio::test]
+    async fn test_refuses_over_decode_limits() {
+        let server = Server::bind("127.0.0.1:0", Arc::new(Principals::default()))
+            .await
+            .unwrap()
+            .with_decode_limits(DecodeLimits {
+                max_records: 1,
+                ..Default::default()
+            });
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        call(&mut stream, 1, produce("events", &["a"])).await;
+        let response = call(&mut stream, 2, produce("events", &["a", "b"])).await;
+        assert_eq!(response.error(), ErrorCode::MessageTooLarge);
+        let response = call(&mut stream, 3, produce("events", &["a"])).await;
+        assert_eq!(response.error(), ErrorCode::None);
+    }
+}
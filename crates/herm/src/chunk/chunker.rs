@@ -0,0 +1,235 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+
+use super::ChunkError;
+use crate::record::{Header, Record};
+
+pub(super) const CHUNK_ID_HEADER: &str = "herm.chunk.id";
+pub(super) const CHUNK_INDEX_HEADER: &str = "herm.chunk.index";
+pub(super) const CHUNK_COUNT_HEADER: &str = "herm.chunk.count";
+
+/// A piece of a payload, sent as its own record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub headers: Vec<Header>,
+    pub value: Bytes,
+}
+
+/// Reassembly metadata carried in the headers of every chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct ChunkMeta {
+    pub id: String,
+    pub index: u32,
+    pub count: u32,
+}
+
+impl ChunkMeta {
+    pub fn is_header(key: &str) -> bool {
+        [CHUNK_ID_HEADER, CHUNK_INDEX_HEADER, CHUNK_COUNT_HEADER].contains(&key)
+    }
+
+    fn to_headers(&self) -> Vec<Header> {
+        vec![
+            Header::new(CHUNK_ID_HEADER, self.id.clone()),
+            Header::new(CHUNK_INDEX_HEADER, self.index.to_be_bytes().to_vec()),
+            Header::new(CHUNK_COUNT_HEADER, self.count.to_be_bytes().to_vec()),
+        ]
+    }
+
+    /// Returns `Ok(None)` when none of the chunk headers are present, and
+    /// `Err(())` when only some of them are or they can't be parsed.
+    pub fn from_headers(headers: &[Header]) -> Result<Option<Self>, ()> {
+        let find = |key: &str| headers.iter().find(|header| header.key == key);
+
+        let (id, index, count) = match (
+            find(CHUNK_ID_HEADER),
+            find(CHUNK_INDEX_HEADER),
+            find(CHUNK_COUNT_HEADER),
+        ) {
+            (None, None, None) => return Ok(None),
+            (Some(id), Some(index), Some(count)) => (id, index, count),
+            _ => return Err(()),
+        };
+
+        let parse_u32 = |header: &Header| -> Result<u32, ()> {
+            Ok(u32::from_be_bytes(
+                header.value.as_ref().try_into().map_err(|_| ())?,
+            ))
+        };
+
+        Ok(Some(ChunkMeta {
+            id: String::from_utf8(id.value.to_vec()).map_err(|_| ())?,
+            index: parse_u32(index)?,
+            count: parse_u32(count)?,
+        }))
+    }
+}
+
+/// Splits payloads larger than `max_chunk_size` into ordered chunks. Chunks of
+/// one payload must be produced to the same partition, e.g. by sharing a key,
+/// so a [`Reassembler`](super::Reassembler) on the consumer sees them in order.
+/// [`Producer::with_chunker`](crate::client::Producer::with_chunker) takes
+/// care of that.
+#[derive(Debug)]
+pub struct Chunker {
+    max_chunk_size: usize,
+    id_prefix: String,
+    next_id: u64,
+}
+
+impl Chunker {
+    pub fn new(max_chunk_size: usize) -> Result<Self, ChunkError> {
+        if max_chunk_size == 0 {
+            return Err(ChunkError::ZeroChunkSize);
+        }
+
+        // Ids only need to be unique among producers writing to the same
+        // partition, so time and pid are good enough.
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or_default();
+
+        Ok(Chunker {
+            max_chunk_size,
+            id_prefix: format!("{:x}-{:x}", nanos, std::process::id()),
+            next_id: 0,
+        })
+    }
+
+    pub fn max_chunk_size(&self) -> usize {
+        self.max_chunk_size
+    }
+
+    /// Splits the value of `record` into records that each carry a chunk
+    /// along with the key, timestamp and headers of `record`. Records with
+    /// a value that fits in one chunk come back as they are.
+    pub fn split_record(&mut self, record: Record) -> Vec<Record> {
+        let Some(value) = record.value.clone() else {
+            return vec![record];
+        };
+        let chunks = self.split(value);
+        if chunks.len() == 1 {
+            return vec![record];
+        }
+
+        chunks
+            .into_iter()
+            .map(|chunk| {
+                let mut piece = record.clone();
+                piece.value = Some(chunk.value);
+                piece.headers.extend(chunk.headers);
+                piece
+            })
+            .collect()
+    }
+
+    /// Payloads that fit in one chunk are passed through without headers.
+    pub fn split(&mut self, payload: Bytes) -> Vec<Chunk> {
+        if payload.len() <= self.max_chunk_size {
+            return vec![Chunk {
+                headers: vec![],
+                value: payload,
+            }];
+        }
+
+        let id = format!("{}-{}", self.id_prefix, self.next_id);
+        self.next_id += 1;
+
+        let count = payload.len().div_ceil(self.max_chunk_size) as u32;
+        (0..count)
+            .map(|index| {
+                let start = index as usize * self.max_chunk_size;
+                let end = (start + self.max_chunk_size).min(payload.len());
+                let meta = ChunkMeta {
+                    id: id.clone(),
+                    index,
+                    count,
+                };
+
+                Chunk {
+                    headers: meta.to_headers(),
+                    value: payload.slice(start..end),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_payload_passes_through() {
+        let mut chunker = Chunker::new(4).unwrap();
+        let chunks = chunker.split(Bytes::from_static(b"abcd"));
+
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].headers.is_empty());
+        assert_eq!(chunks[0].value, Bytes::from_static(b"abcd"));
+    }
+
+    #[test]
+    fn test_split() {
+        let mut chunker = Chunker::new(4).unwrap();
+        let chunks = chunker.split(Bytes::from_static(b"abcdefghij"));
+
+        let values: Vec<_> = chunks.iter().map(|chunk| chunk.value.clone()).collect();
+        assert_eq!(values, vec!["abcd", "efgh", "ij"]);
+
+        let metas: Vec<_> = chunks
+            .iter()
+            .map(|chunk| ChunkMeta::from_headers(&chunk.headers).unwrap().unwrap())
+            .collect();
+        assert!(metas.iter().all(|meta| meta.id == metas[0].id));
+        assert_eq!(
+            metas.iter().map(|meta| meta.index).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        assert!(metas.iter().all(|meta| meta.count == 3));
+
+        // Every payload gets a new id
+        let next = chunker.split(Bytes::from_static(b"abcdefghij"));
+        let next_meta = ChunkMeta::from_headers(&next[0].headers).unwrap().unwrap();
+        assert_ne!(next_meta.id, metas[0].id);
+    }
+
+    #[test]
+    fn test_zero_chunk_size() {
+        assert_eq!(Chunker::new(0).unwrap_err(), ChunkError::ZeroChunkSize);
+    }
+
+    #[test]
+    fn test_split_record() {
+        let mut chunker = Chunker::new(4).unwrap();
+        let record = Record::new(Some(Bytes::from("key")), Some(Bytes::from("abcdef")))
+            .with_header(Header::new("trace", "1"));
+
+        let pieces = chunker.split_record(record.clone());
+        assert_eq!(pieces.len(), 2);
+        for piece in &pieces {
+            assert_eq!(piece.key, record.key);
+            assert_eq!(piece.timestamp, record.timestamp);
+            assert_eq!(piece.headers[0], Header::new("trace", "1"));
+            assert!(ChunkMeta::from_headers(&piece.headers).unwrap().is_some());
+        }
+
+        let small = Record::new(None, Some(Bytes::from("abc")));
+        assert_eq!(chunker.split_record(small.clone()), vec![small]);
+    }
+
+    #[test]
+    fn test_partial_headers() {
+        let headers = vec![Header::new(CHUNK_ID_HEADER, "x")];
+        assert_eq!(ChunkMeta::from_headers(&headers), Err(()));
+
+        let headers = vec![
+            Header::new(CHUNK_ID_HEADER, "x"),
+            Header::new(CHUNK_INDEX_HEADER, vec![0]),
+            Header::new(CHUNK_COUNT_HEADER, vec![0, 0, 0, 1]),
+        ];
+        assert_eq!(ChunkMeta::from_headers(&headers), Err(()));
+    }
+}
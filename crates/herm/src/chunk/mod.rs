@@ -0,0 +1,4 @@
+mod chunker;
+mod reassembler;
+pub use chunker::{Chunk, Chunker};
+pub use reassembler::{ChunkError, Reassembler};
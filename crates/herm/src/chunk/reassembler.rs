@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+use thiserror::Error;
+
+use bytes::{Bytes, BytesMut};
+
+use super::chunker::ChunkMeta;
+use crate::record::{Header, Record};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ChunkError {
+    #[error("Malformed chunk headers")]
+    MalformedHeaders,
+    #[error("Chunk {index} of {id} arrived out of order")]
+    OutOfOrder { id: String, index: u32 },
+    #[error("Reassembly buffer is over its {0} byte limit")]
+    BufferFull(usize),
+    #[error("Chunk size must be positive")]
+    ZeroChunkSize,
+}
+
+#[derive(Debug)]
+struct Pending {
+    next_index: u32,
+    buf: BytesMut,
+    /// Offset of the first chunk, when pushed as a record.
+    offset: Option<u64>,
+}
+
+/// Joins chunks produced by a [`Chunker`](super::Chunker) back into the
+/// original payloads. One reassembler should be used per partition.
+#[derive(Debug)]
+pub struct Reassembler {
+    max_buffered_bytes: usize,
+    buffered_bytes: usize,
+    pending: HashMap<String, Pending>,
+}
+
+impl Reassembler {
+    pub fn new(max_buffered_bytes: usize) -> Self {
+        Reassembler {
+            max_buffered_bytes,
+            buffered_bytes: 0,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Feeds a consumed record in. Returns the payload once its last chunk
+    /// arrives, or straight away for records that weren't chunked.
+    pub fn push(&mut self, headers: &[Header], value: Bytes) -> Result<Option<Bytes>, ChunkError> {
+        self.push_at(headers, value, None)
+    }
+
+    fn push_at(
+        &mut self,
+        headers: &[Header],
+        value: Bytes,
+        offset: Option<u64>,
+    ) -> Result<Option<Bytes>, ChunkError> {
+        let Some(meta) =
+            ChunkMeta::from_headers(headers).map_err(|_| ChunkError::MalformedHeaders)?
+        else {
+            return Ok(Some(value));
+        };
+
+        if meta.index >= meta.count {
+            return Err(ChunkError::MalformedHeaders);
+        }
+
+        let next_index = self.pending.get(&meta.id).map_or(0, |p| p.next_index);
+        if meta.index != next_index {
+            // A gap means a chunk got lost, so the payload can't be rebuilt
+            self.discard(&meta.id);
+            return Err(ChunkError::OutOfOrder {
+                id: meta.id,
+                index: meta.index,
+            });
+        }
+
+        if meta.count == 1 {
+            return Ok(Some(value));
+        }
+
+        if self.buffered_bytes + value.len() > self.max_buffered_bytes {
+            self.discard(&meta.id);
+            return Err(ChunkError::BufferFull(self.max_buffered_bytes));
+        }
+
+        let pending = self.pending.entry(meta.id.clone()).or_insert(Pending {
+            next_index: 0,
+            buf: BytesMut::new(),
+            offset,
+        });
+        pending.buf.extend_from_slice(&value);
+        pending.next_index += 1;
+        self.buffered_bytes += value.len();
+
+        if pending.next_index < meta.count {
+            return Ok(None);
+        }
+
+        let pending = self.pending.remove(&meta.id).unwrap();
+        self.buffered_bytes -= pending.buf.len();
+        Ok(Some(pending.buf.freeze()))
+    }
+
+    /// Like [`push`](Reassembler::push) for a whole record. The record
+    /// handed back once the payload is complete is the last chunk's, so has
+    /// its offset, with the payload as its value and the chunk headers
+    /// taken off.
+    pub fn push_record(&mut self, mut record: Record) -> Result<Option<Record>, ChunkError> {
+        let Some(value) = record.value.take() else {
+            return Ok(Some(record));
+        };
+        let Some(payload) = self.push_at(&record.headers, value, Some(record.offset))? else {
+            return Ok(None);
+        };
+        record.value = Some(payload);
+        record
+            .headers
+            .retain(|header| !ChunkMeta::is_header(&header.key));
+        Ok(Some(record))
+    }
+
+    /// Number of payloads waiting on more chunks.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Offset of the earliest chunk pushed as a record whose payload is
+    /// still waiting on more. Reading from past it loses the payload.
+    pub fn first_pending_offset(&self) -> Option<u64> {
+        self.pending
+            .values()
+            .filter_map(|pending| pending.offset)
+            .min()
+    }
+
+    fn discard(&mut self, id: &str) {
+        if let Some(pending) = self.pending.remove(id) {
+            self.buffered_bytes -= pending.buf.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::chunk::Chunker;
+
+    #[test]
+    fn test_reassemble() {
+        let mut chunker = Chunker::new(3).unwrap();
+        let mut reassembler = Reassembler::new(1024);
+
+        let chunks = chunker.split(Bytes::from_static(b"hello world"));
+        let (last, rest) = chunks.split_last().unwrap();
+        for chunk in rest {
+            assert_eq!(
+                reassembler.push(&chunk.headers, chunk.value.clone()),
+                Ok(None)
+            );
+        }
+        assert_eq!(reassembler.pending(), 1);
+
+        assert_eq!(
+            reassembler.push(&last.headers, last.value.clone()),
+            Ok(Some(Bytes::from_static(b"hello world")))
+        );
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn test_interleaved_payloads() {
+        let mut chunker = Chunker::new(2).unwrap();
+        let mut reassembler = Reassembler::new(1024);
+
+        let first = chunker.split(Bytes::from_static(b"aaaa"));
+        let second = chunker.split(Bytes::from_static(b"bbbb"));
+
+        assert_eq!(
+            reassembler.push(&first[0].headers, first[0].value.clone()),
+            Ok(None)
+        );
+        assert_eq!(
+            reassembler.push(&second[0].headers, second[0].value.clone()),
+            Ok(None)
+        );
+        assert_eq!(
+            reassembler.push(&first[1].headers, first[1].value.clone()),
+            Ok(Some(Bytes::from_static(b"aaaa")))
+        );
+        assert_eq!(
+            reassembler.push(&second[1].headers, second[1].value.clone()),
+            Ok(Some(Bytes::from_static(b"bbbb")))
+        );
+    }
+
+    #[test]
+    fn test_reassemble_records() {
+        let mut chunker = Chunker::new(3).unwrap();
+        let mut reassembler = Reassembler::new(1024);
+
+        let record = Record::new(Some(Bytes::from("key")), Some(Bytes::from("hello world")))
+            .with_header(Header::new("trace", "1"));
+        let mut pieces = chunker.split_record(record.clone());
+        for (offset, piece) in pieces.iter_mut().enumerate() {
+            piece.offset = offset as u64;
+        }
+        let last = pieces.pop().unwrap();
+        for piece in pieces {
+            assert_eq!(reassembler.push_record(piece), Ok(None));
+        }
+        assert_eq!(reassembler.first_pending_offset(), Some(0));
+
+        let joined = reassembler.push_record(last).unwrap().unwrap();
+        assert_eq!(joined.offset, 3);
+        assert_eq!(reassembler.first_pending_offset(), None);
+        assert_eq!(
+            (joined.key, joined.value, joined.headers),
+            (record.key, record.value, record.headers)
+        );
+    }
+
+    #[test]
+    fn test_unchunked_record() {
+        let mut reassembler = Reassembler::new(0);
+        assert_eq!(
+            reassembler.push(&[], Bytes::from_static(b"plain")),
+            Ok(Some(Bytes::from_static(b"plain")))
+        );
+    }
+
+    #[test]
+    fn test_out_of_order() {
+        let mut chunker = Chunker::new(2).unwrap();
+        let mut reassembler = Reassembler::new(1024);
+
+        let chunks = chunker.split(Bytes::from_static(b"abcdef"));
+        reassembler
+            .push(&chunks[0].headers, chunks[0].value.clone())
+            .unwrap();
+
+        let err = reassembler
+            .push(&chunks[2].headers, chunks[2].value.clone())
+            .unwrap_err();
+        assert!(matches!(err, ChunkError::OutOfOrder { index: 2, .. }));
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn test_buffer_full() {
+        let mut chunker = Chunker::new(4).unwrap();
+        let mut reassembler = Reassembler::new(6);
+
+        let chunks = chunker.split(Bytes::from_static(b"abcdefghij"));
+        reassembler
+            .push(&chunks[0].headers, chunks[0].value.clone())
+            .unwrap();
+        assert_eq!(
+            reassembler.push(&chunks[1].headers, chunks[1].value.clone()),
+            Err(ChunkError::BufferFull(6))
+        );
+        assert_eq!(reassembler.pending(), 0);
+    }
+}
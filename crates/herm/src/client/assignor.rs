@@ -0,0 +1,284 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+
+use crate::protocol::TopicPartition;
+use crate::response::GroupMember;
+
+/// Splits the partitions of the topics a group subscribes to among its
+/// members. The group leader runs the one all members support.
+pub trait Assignor: Debug + Send + Sync {
+    /// Name members announce support by, the same for every member.
+    fn name(&self) -> &str;
+
+    /// Whether members keep reading the partitions they own through a
+    /// rebalance, giving up only those moving to another member.
+    fn is_cooperative(&self) -> bool {
+        false
+    }
+
+    /// Partitions of each member, every member included even when it gets
+    /// none. `partition_counts` holds the subscribed topics that exist.
+    fn assign(
+        &self,
+        members: &[GroupMember],
+        partition_counts: &BTreeMap<String, u32>,
+    ) -> BTreeMap<String, Vec<TopicPartition>>;
+}
+
+fn unassigned(members: &[GroupMember]) -> BTreeMap<String, Vec<TopicPartition>> {
+    members
+        .iter()
+        .map(|member| (member.member_id.clone(), vec![]))
+        .collect()
+}
+
+/// Members subscribed to `topic`, by id.
+fn subscribed<'a>(members: &'a [GroupMember], topic: &str) -> BTreeSet<&'a str> {
+    members
+        .iter()
+        .filter(|member| member.topics.iter().any(|subscribed| subscribed == topic))
+        .map(|member| member.member_id.as_str())
+        .collect()
+}
+
+/// Gives each member subscribed to a topic a consecutive range of its
+/// partitions, the first members one more when they don't split evenly.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RangeAssignor;
+
+impl Assignor for RangeAssignor {
+    fn name(&self) -> &str {
+        "range"
+    }
+
+    fn assign(
+        &self,
+        members: &[GroupMember],
+        partition_counts: &BTreeMap<String, u32>,
+    ) -> BTreeMap<String, Vec<TopicPartition>> {
+        let mut assignments = unassigned(members);
+        for (topic, &count) in partition_counts {
+            let subscribed = subscribed(members, topic);
+            if subscribed.is_empty() {
+                continue;
+            }
+            let per_member = count / subscribed.len() as u32;
+            let extra = count % subscribed.len() as u32;
+            let mut next = 0;
+            for (i, member_id) in subscribed.into_iter().enumerate() {
+                let take = per_member + u32::from((i as u32) < extra);
+                let assigned = assignments.get_mut(member_id).unwrap();
+                assigned.extend(
+                    (next..next + take).map(|partition| TopicPartition::new(topic, partition)),
+                );
+                next += take;
+            }
+        }
+        assignments
+    }
+}
+
+/// Deals the partitions of all topics out to the members in turn, skipping
+/// members not subscribed to a partition's topic.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RoundRobinAssignor;
+
+impl Assignor for RoundRobinAssignor {
+    fn name(&self) -> &str {
+        "roundrobin"
+    }
+
+    fn assign(
+        &self,
+        members: &[GroupMember],
+        partition_counts: &BTreeMap<String, u32>,
+    ) -> BTreeMap<String, Vec<TopicPartition>> {
+        let mut assignments = unassigned(members);
+        let member_ids: Vec<String> = assignments.keys().cloned().collect();
+        let mut next = 0;
+        for (topic, &count) in partition_counts {
+            let subscribed = subscribed(members, topic);
+            if subscribed.is_empty() {
+                continue;
+            }
+            for partition in 0..count {
+                while !subscribed.contains(member_ids[next % member_ids.len()].as_str()) {
+                    next += 1;
+                }
+                let member_id = &member_ids[next % member_ids.len()];
+                assignments
+                    .get_mut(member_id)
+                    .unwrap()
+                    .push(TopicPartition::new(topic, partition));
+                next += 1;
+            }
+        }
+        assignments
+    }
+}
+
+/// Splits each topic as evenly as [`RangeAssignor`] does, but leaves
+/// members the partitions they already own wherever the split allows, so
+/// only the partitions needed to balance the group move. Cooperative, so
+/// members read their other partitions straight through a rebalance.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CooperativeStickyAssignor;
+
+impl Assignor for CooperativeStickyAssignor {
+    fn name(&self) -> &str {
+        "cooperative-sticky"
+    }
+
+    fn is_cooperative(&self) -> bool {
+        true
+    }
+
+    fn assign(
+        &self,
+        members: &[GroupMember],
+        partition_counts: &BTreeMap<String, u32>,
+    ) -> BTreeMap<String, Vec<TopicPartition>> {
+        let mut assignments = unassigned(members);
+        for (topic, &count) in partition_counts {
+            let subscribed = subscribed(members, topic);
+            if subscribed.is_empty() {
+                continue;
+            }
+            // Partitions of the topic each member owns, each claimed once
+            let mut claimed = BTreeSet::new();
+            let mut owned: BTreeMap<&str, Vec<u32>> = BTreeMap::new();
+            for member in members {
+                if !subscribed.contains(member.member_id.as_str()) {
+                    continue;
+                }
+                let partitions = member
+                    .owned
+                    .iter()
+                    .filter(|owned| owned.topic == *topic && owned.partition < count)
+                    .filter(|owned| claimed.insert(owned.partition))
+                    .map(|owned| owned.partition)
+                    .collect();
+                owned.insert(member.member_id.as_str(), partitions);
+            }
+
+            // Members owning the most get the extra partitions, so fewer move
+            let mut by_owned: Vec<&str> = subscribed.into_iter().collect();
+            by_owned.sort_by_key(|member_id| std::cmp::Reverse(owned[member_id].len()));
+            let per_member = count as usize / by_owned.len();
+            let extra = count as usize % by_owned.len();
+            let mut kept = BTreeSet::new();
+            let mut quotas = BTreeMap::new();
+            for (i, member_id) in by_owned.into_iter().enumerate() {
+                let quota = per_member + usize::from(i < extra);
+                let assigned = assignments.get_mut(member_id).unwrap();
+                for &partition in owned[member_id].iter().take(quota) {
+                    kept.insert(partition);
+                    assigned.push(TopicPartition::new(topic, partition));
+                }
+                quotas.insert(member_id, quota);
+            }
+
+            let mut moving = (0..count).filter(|partition| !kept.contains(partition));
+            for (member_id, quota) in quotas {
+                let assigned = assignments.get_mut(member_id).unwrap();
+                let have = assigned.iter().filter(|tp| tp.topic == *topic).count();
+                for partition in moving.by_ref().take(quota - have) {
+                    assigned.push(TopicPartition::new(topic, partition));
+                }
+            }
+        }
+        for assigned in assignments.values_mut() {
+            assigned.sort();
+        }
+        assignments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(member_id: &str, topics: &[&str], owned: &[(&str, u32)]) -> GroupMember {
+        GroupMember {
+            member_id: member_id.to_string(),
+            topics: topics.iter().map(|topic| topic.to_string()).collect(),
+            owned: owned
+                .iter()
+                .map(|&(topic, partition)| TopicPartition::new(topic, partition))
+                .collect(),
+        }
+    }
+
+    fn events(partition: u32) -> TopicPartition {
+        TopicPartition::new("events", partition)
+    }
+
+    fn audit(partition: u32) -> TopicPartition {
+        TopicPartition::new("audit", partition)
+    }
+
+    #[test]
+    fn test_range() {
+        let members = [
+            member("a", &["events", "audit"], &[]),
+            member("b", &["events"], &[]),
+        ];
+        let counts = BTreeMap::from([("events".to_string(), 3), ("audit".to_string(), 2)]);
+        let assigned = RangeAssignor.assign(&members, &counts);
+        assert_eq!(
+            assigned["a"],
+            vec![audit(0), audit(1), events(0), events(1)]
+        );
+        assert_eq!(assigned["b"], vec![events(2)]);
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let members = [
+            member("a", &["events", "audit"], &[]),
+            member("b", &["events"], &[]),
+        ];
+        let counts = BTreeMap::from([("events".to_string(), 3), ("audit".to_string(), 2)]);
+        let assigned = RoundRobinAssignor.assign(&members, &counts);
+        // Only a reads audit, then events carries on after it
+        assert_eq!(assigned["a"], vec![audit(0), audit(1), events(1)]);
+        assert_eq!(assigned["b"], vec![events(0), events(2)]);
+    }
+
+    #[test]
+    fn test_cooperative_sticky() {
+        let counts = BTreeMap::from([("events".to_string(), 4)]);
+        let members = [member("b", &["events"], &[])];
+        let assigned = CooperativeStickyAssignor.assign(&members, &counts);
+        assert_eq!(assigned["b"], (0..4).map(events).collect::<Vec<_>>());
+
+        // A joining member takes only what balances the group
+        let members = [
+            member("a", &["events"], &[]),
+            member(
+                "b",
+                &["events"],
+                &[("events", 0), ("events", 1), ("events", 2), ("events", 3)],
+            ),
+        ];
+        let assigned = CooperativeStickyAssignor.assign(&members, &counts);
+        assert_eq!(assigned["a"], vec![events(2), events(3)]);
+        assert_eq!(assigned["b"], vec![events(0), events(1)]);
+
+        // The extra partition stays with the member owning it
+        let counts = BTreeMap::from([("events".to_string(), 5)]);
+        let members = [
+            member("a", &["events"], &[("events", 2), ("events", 3)]),
+            member(
+                "b",
+                &["events"],
+                &[("events", 0), ("events", 1), ("events", 4)],
+            ),
+            member("c", &["events"], &[]),
+        ];
+        let assigned = CooperativeStickyAssignor.assign(&members, &counts);
+        assert_eq!(assigned["a"], vec![events(2), events(3)]);
+        assert_eq!(assigned["b"], vec![events(0), events(1)]);
+        assert_eq!(assigned["c"], vec![events(4)]);
+    }
+}
@@ -0,0 +1,738 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+
+use super::connection::Connection;
+use super::observer::NoObserver;
+use super::{ClientError, ClientObserver, RetryPolicy, DEFAULT_CLIENT_ID};
+use crate::protocol::{ApiKey, ErrorCode, TopicPartition};
+use crate::request::{
+    BrokerHeartbeat, CreateTopic, DeleteTopic, DescribeCluster, DescribeGroups, DescribeLogDirs,
+    EstimateCleanup, Fetch, Heartbeat, JoinGroup, LeaderAndIsr, LeaveGroup, ListGroups,
+    ListOffsets, Metadata, OffsetCommit, Produce, ReassignPartition, Request, SyncGroup,
+};
+use crate::response::{
+    AdminResponse, DescribeClusterResponse, DescribeGroupsResponse, DescribeLogDirsResponse,
+    EstimateCleanupResponse, FetchResponse, JoinGroupResponse, ListGroupsResponse,
+    ListOffsetsResponse, MetadataResponse, ProduceResponse, Response, SyncGroupResponse,
+};
+
+/// Errors a partition's broker answers with when the cached leader is stale.
+const STALE_LEADER_ERRORS: [ErrorCode; 2] = [
+    ErrorCode::NotLeaderOrFollower,
+    ErrorCode::UnknownTopicOrPartition,
+];
+
+/// A client of a whole cluster. Requests about a partition go to its leader,
+/// as found in metadata cached from the last refresh, which happens whenever
+/// a leader isn't known or turns out to be stale. Other requests go to any
+/// broker that answers.
+///
+/// Keeps a connection per broker, opened on first use and replaced on
+/// failure or when a request on it is cancelled halfway. Requests to the
+/// same broker share it, many in flight at once. Produces, fetches, offset
+/// lookups and metadata requests are retried as the [`RetryPolicy`] says,
+/// other requests aren't. Errors brokers answer with are left in the
+/// responses.
+///
+/// Every request, response and retry is reported to the
+/// [`ClientObserver`], if one is set.
+#[derive(Debug)]
+pub struct Client {
+    bootstrap: Vec<String>,
+    client_id: String,
+    retry: RetryPolicy,
+    observer: Arc<dyn ClientObserver>,
+    metadata: RwLock<Option<MetadataResponse>>,
+    connections: Mutex<HashMap<String, Arc<Connection>>>,
+}
+
+impl Client {
+    /// Metadata is first fetched from `bootstrap`, any brokers of the
+    /// cluster will do. Nothing is connected to until the first request.
+    pub fn new(bootstrap: Vec<String>) -> Self {
+        Client {
+            bootstrap,
+            client_id: DEFAULT_CLIENT_ID.to_string(),
+            retry: RetryPolicy::default(),
+            observer: Arc::new(NoObserver),
+            metadata: RwLock::new(None),
+            connections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets the client id sent with requests, which brokers log and apply
+    /// quotas by.
+    pub fn with_client_id(mut self, client_id: impl Into<String>) -> Self {
+        self.client_id = client_id.into();
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Reports what the client and its producers do to `observer`, such
+    /// as a shared [`ClientMetrics`](crate::metrics::ClientMetrics).
+    pub fn with_observer(mut self, observer: Arc<dyn ClientObserver>) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    pub fn observer(&self) -> &dyn ClientObserver {
+        self.observer.as_ref()
+    }
+
+    /// Metadata as of the last refresh.
+    pub fn cached_metadata(&self) -> Option<MetadataResponse> {
+        self.metadata.read().unwrap().clone()
+    }
+
+    /// Fetches metadata about every topic and caches it.
+    pub async fn refresh_metadata(&self) -> Result<MetadataResponse, ClientError> {
+        let response = self.metadata(Metadata::all()).await?;
+        self.cache_metadata(&response);
+        Ok(response)
+    }
+
+    /// Partitions `topic` has, `None` when no broker leads any of them.
+    /// Refreshes metadata if the topic isn't in the cache, then asks for the
+    /// topic by name if it still isn't, which has brokers that auto-create
+    /// topics create it.
+    pub async fn partition_count(&self, topic: &str) -> Result<Option<u32>, ClientError> {
+        if let Some(count) = self.cached_partition_count(topic) {
+            return Ok(Some(count));
+        }
+        self.refresh_metadata().await?;
+        if let Some(count) = self.cached_partition_count(topic) {
+            return Ok(Some(count));
+        }
+
+        let Ok(request) = Metadata::new(vec![topic.to_string()]) else {
+            return Ok(None);
+        };
+        let response = self.metadata(request).await?;
+        if !response
+            .topics
+            .iter()
+            .any(|metadata| metadata.error.is_ok())
+        {
+            return Ok(None);
+        }
+        self.refresh_metadata().await?;
+        Ok(self.cached_partition_count(topic))
+    }
+
+    pub async fn produce(&self, produce: Produce) -> Result<Option<ProduceResponse>, ClientError> {
+        let partition = TopicPartition::new(produce.topic(), produce.partition());
+        let request = produce.into();
+        let response = self
+            .retrying(ApiKey::Produce, |retry| {
+                self.call_leader(&partition, &request, retry > 0)
+            })
+            .await?;
+        match response {
+            Some(Response::Produce(response)) => Ok(Some(response)),
+            None => Ok(None),
+            _ => unreachable!("a produce is answered with a produce response"),
+        }
+    }
+
+    pub async fn fetch(&self, fetch: Fetch) -> Result<FetchResponse, ClientError> {
+        let partition = TopicPartition::new(fetch.topic(), fetch.partition());
+        let request = fetch.into();
+        let response = self
+            .retrying(ApiKey::Fetch, |retry| {
+                self.call_leader(&partition, &request, retry > 0)
+            })
+            .await?;
+        match response {
+            Some(Response::Fetch(response)) => Ok(response),
+            _ => unreachable!("a fetch is answered with a fetch response"),
+        }
+    }
+
+    pub async fn list_offsets(
+        &self,
+        request: ListOffsets,
+    ) -> Result<ListOffsetsResponse, ClientError> {
+        let partition = TopicPartition::new(request.topic(), request.partition());
+        let request = request.into();
+        let response = self
+            .retrying(ApiKey::ListOffsets, |retry| {
+                self.call_leader(&partition, &request, retry > 0)
+            })
+            .await?;
+        match response {
+            Some(Response::ListOffsets(response)) => Ok(response),
+            _ => unreachable!("a ListOffsets is answered with its own response"),
+        }
+    }
+
+    /// Asks any broker, without touching the cache.
+    pub async fn metadata(&self, request: Metadata) -> Result<MetadataResponse, ClientError> {
+        let request = request.into();
+        match self
+            .retrying(ApiKey::Metadata, |_| self.call_any(&request))
+            .await?
+        {
+            Some(Response::Metadata(response)) => Ok(response),
+            _ => unreachable!("a Metadata is answered with its own response"),
+        }
+    }
+
+    pub async fn describe_cluster(
+        &self,
+        request: DescribeCluster,
+    ) -> Result<DescribeClusterResponse, ClientError> {
+        match self.call_any(&request.into()).await? {
+            Some(Response::DescribeCluster(response)) => Ok(response),
+            _ => unreachable!("a DescribeCluster is answered with its own response"),
+        }
+    }
+
+    /// Creates the topic on any broker, which leads all its partitions.
+    pub async fn create_topic(&self, request: CreateTopic) -> Result<AdminResponse, ClientError> {
+        match self.call_any(&request.into()).await? {
+            Some(Response::CreateTopic(response)) => Ok(response),
+            _ => unreachable!("a CreateTopic is answered with its own response"),
+        }
+    }
+
+    /// Deletes the topic's partitions held by `broker_id`.
+    pub async fn delete_topic(
+        &self,
+        broker_id: u32,
+        request: DeleteTopic,
+    ) -> Result<AdminResponse, ClientError> {
+        match self.call_broker(broker_id, request.into()).await? {
+            Some(Response::DeleteTopic(response)) => Ok(response),
+            _ => unreachable!("a DeleteTopic is answered with its own response"),
+        }
+    }
+
+    /// Describes the log dirs of `broker_id`.
+    pub async fn describe_log_dirs(
+        &self,
+        broker_id: u32,
+        request: DescribeLogDirs,
+    ) -> Result<DescribeLogDirsResponse, ClientError> {
+        match self.call_broker(broker_id, request.into()).await? {
+            Some(Response::DescribeLogDirs(response)) => Ok(response),
+            _ => unreachable!("a DescribeLogDirs is answered with its own response"),
+        }
+    }
+
+    /// Estimates what a cleanup would reclaim from the topic's partitions
+    /// held by `broker_id`.
+    pub async fn estimate_cleanup(
+        &self,
+        broker_id: u32,
+        request: EstimateCleanup,
+    ) -> Result<EstimateCleanupResponse, ClientError> {
+        match self.call_broker(broker_id, request.into()).await? {
+            Some(Response::EstimateCleanup(response)) => Ok(response),
+            _ => unreachable!("an EstimateCleanup is answered with its own response"),
+        }
+    }
+
+    pub async fn leader_and_isr(
+        &self,
+        broker_id: u32,
+        request: LeaderAndIsr,
+    ) -> Result<AdminResponse, ClientError> {
+        match self.call_broker(broker_id, request.into()).await? {
+            Some(Response::LeaderAndIsr(response)) => Ok(response),
+            _ => unreachable!("a LeaderAndIsr is answered with its own response"),
+        }
+    }
+
+    /// Asks the partition's current leader to move it.
+    pub async fn reassign(&self, request: ReassignPartition) -> Result<AdminResponse, ClientError> {
+        let partition = TopicPartition::new(request.topic(), request.partition());
+        match self.call_leader(&partition, &request.into(), false).await? {
+            Some(Response::ReassignPartition(response)) => Ok(response),
+            _ => unreachable!("a ReassignPartition is answered with its own response"),
+        }
+    }
+
+    pub async fn heartbeat(
+        &self,
+        broker_id: u32,
+        request: BrokerHeartbeat,
+    ) -> Result<AdminResponse, ClientError> {
+        match self.call_broker(broker_id, request.into()).await? {
+            Some(Response::BrokerHeartbeat(response)) => Ok(response),
+            _ => unreachable!("a BrokerHeartbeat is answered with its own response"),
+        }
+    }
+
+    pub async fn offset_commit(&self, request: OffsetCommit) -> Result<AdminResponse, ClientError> {
+        match self.call_coordinator(request.into()).await? {
+            Some(Response::OffsetCommit(response)) => Ok(response),
+            _ => unreachable!("an OffsetCommit is answered with its own response"),
+        }
+    }
+
+    pub async fn list_groups(
+        &self,
+        request: ListGroups,
+    ) -> Result<ListGroupsResponse, ClientError> {
+        match self.call_coordinator(request.into()).await? {
+            Some(Response::ListGroups(response)) => Ok(response),
+            _ => unreachable!("a ListGroups is answered with its own response"),
+        }
+    }
+
+    pub async fn describe_groups(
+        &self,
+        request: DescribeGroups,
+    ) -> Result<DescribeGroupsResponse, ClientError> {
+        match self.call_coordinator(request.into()).await? {
+            Some(Response::DescribeGroups(response)) => Ok(response),
+            _ => unreachable!("a DescribeGroups is answered with its own response"),
+        }
+    }
+
+    /// Held by the coordinator until the group's other members rejoined.
+    pub async fn join_group(&self, request: JoinGroup) -> Result<JoinGroupResponse, ClientError> {
+        match self.call_coordinator(request.into()).await? {
+            Some(Response::JoinGroup(response)) => Ok(response),
+            _ => unreachable!("a JoinGroup is answered with its own response"),
+        }
+    }
+
+    /// Held by the coordinator until the group leader sent the assignments.
+    pub async fn sync_group(&self, request: SyncGroup) -> Result<SyncGroupResponse, ClientError> {
+        match self.call_coordinator(request.into()).await? {
+            Some(Response::SyncGroup(response)) => Ok(response),
+            _ => unreachable!("a SyncGroup is answered with its own response"),
+        }
+    }
+
+    /// A group member's heartbeat, unlike [`Client::heartbeat`].
+    pub async fn group_heartbeat(&self, request: Heartbeat) -> Result<AdminResponse, ClientError> {
+        match self.call_coordinator(request.into()).await? {
+            Some(Response::Heartbeat(response)) => Ok(response),
+            _ => unreachable!("a Heartbeat is answered with its own response"),
+        }
+    }
+
+    pub async fn leave_group(&self, request: LeaveGroup) -> Result<AdminResponse, ClientError> {
+        match self.call_coordinator(request.into()).await? {
+            Some(Response::LeaveGroup(response)) => Ok(response),
+            _ => unreachable!("a LeaveGroup is answered with its own response"),
+        }
+    }
+
+    /// Calls `call` with the number of attempts that failed so far, until
+    /// it succeeds, fails for good or runs out of attempts.
+    async fn retrying<F, Fut>(
+        &self,
+        api_key: ApiKey,
+        mut call: F,
+    ) -> Result<Option<Response>, ClientError>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<Option<Response>, ClientError>>,
+    {
+        let mut failed = 0;
+        loop {
+            let result = call(failed).await;
+            let retriable = match &result {
+                Ok(response) => response
+                    .as_ref()
+                    .is_some_and(|response| response.error().is_retriable()),
+                Err(err) => err.is_retriable(),
+            };
+            failed += 1;
+            if !retriable || !self.retry.should_retry(failed) {
+                return result;
+            }
+            let backoff = self.retry.backoff(failed);
+            self.observer.on_retry(api_key, failed, backoff);
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    /// Sends `request` to the leader of `partition`, refreshing metadata and
+    /// trying once more if the leader turns out to be stale. Refreshes
+    /// first if `refresh`, as when retrying.
+    async fn call_leader(
+        &self,
+        partition: &TopicPartition,
+        request: &Request,
+        refresh: bool,
+    ) -> Result<Option<Response>, ClientError> {
+        let address = match self.cached_leader(partition).filter(|_| !refresh) {
+            Some(address) => address,
+            None => {
+                self.refresh_once().await?;
+                self.cached_leader(partition)
+                    .ok_or_else(|| ClientError::NoLeader(partition.clone()))?
+            }
+        };
+        let response = self.call(&address, request).await?;
+        if !matches!(&response, Some(response) if STALE_LEADER_ERRORS.contains(&response.error())) {
+            return Ok(response);
+        }
+
+        self.refresh_once().await?;
+        let address = self
+            .cached_leader(partition)
+            .ok_or_else(|| ClientError::NoLeader(partition.clone()))?;
+        self.call(&address, request).await
+    }
+
+    /// Refreshes metadata without retrying, for calls retried as a whole.
+    async fn refresh_once(&self) -> Result<(), ClientError> {
+        match self.call_any(&Metadata::all().into()).await? {
+            Some(Response::Metadata(response)) => {
+                self.cache_metadata(&response);
+                Ok(())
+            }
+            _ => unreachable!("a Metadata is answered with its own response"),
+        }
+    }
+
+    fn cache_metadata(&self, response: &MetadataResponse) {
+        if response.error.is_ok() {
+            *self.metadata.write().unwrap() = Some(response.clone());
+        }
+    }
+
+    /// Sends `request` to `broker_id`, refreshing metadata if its address
+    /// isn't known.
+    async fn call_broker(
+        &self,
+        broker_id: u32,
+        request: Request,
+    ) -> Result<Option<Response>, ClientError> {
+        let address = match self.cached_address(broker_id) {
+            Some(address) => address,
+            None => {
+                self.refresh_metadata().await?;
+                self.cached_address(broker_id)
+                    .ok_or(ClientError::UnknownBroker(broker_id))?
+            }
+        };
+        self.call(&address, &request).await
+    }
+
+    /// Sends a group request to the live broker with the lowest id, so every
+    /// client of a group commits to the same broker.
+    async fn call_coordinator(&self, request: Request) -> Result<Option<Response>, ClientError> {
+        let metadata = match self.cached_metadata() {
+            Some(metadata) => metadata,
+            None => self.refresh_metadata().await?,
+        };
+        let coordinator = metadata
+            .brokers
+            .iter()
+            .filter(|broker| !broker.address.is_empty())
+            .min_by_key(|broker| broker.id)
+            .ok_or(ClientError::NoBrokers)?;
+        self.call(&coordinator.address, &request).await
+    }
+
+    /// Sends `request` to the first broker that answers, the known ones
+    /// before the bootstrap ones.
+    async fn call_any(&self, request: &Request) -> Result<Option<Response>, ClientError> {
+        let mut addresses: Vec<String> = self
+            .cached_metadata()
+            .map(|metadata| metadata.brokers.into_iter().map(|b| b.address).collect())
+            .unwrap_or_default();
+        addresses.extend(self.bootstrap.iter().cloned());
+
+        let mut last_err = ClientError::NoBrokers;
+        for address in addresses.iter().filter(|address| !address.is_empty()) {
+            match self.call(address, request).await {
+                Ok(response) => return Ok(response),
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err)
+    }
+
+    async fn call(
+        &self,
+        address: &str,
+        request: &Request,
+    ) -> Result<Option<Response>, ClientError> {
+        let api_key = request.api_key();
+        let connection = match self.connection(address).await {
+            Ok(connection) => connection,
+            Err(err) => {
+                self.observer.on_request_failed(address, api_key, &err);
+                return Err(err);
+            }
+        };
+        self.observer
+            .on_request_sent(address, api_key, request.size());
+        let sent = Instant::now();
+        let result = connection.call(request).await;
+        match &result {
+            Ok(Some(response)) => self.observer.on_response_received(
+                address,
+                api_key,
+                response.size(),
+                response.error(),
+                sent.elapsed(),
+            ),
+            Ok(None) => {}
+            Err(err) => self.observer.on_request_failed(address, api_key, err),
+        }
+        if connection.is_closed() {
+            // Whatever is left on the stream can't be trusted
+            let mut connections = self.connections.lock().unwrap();
+            if connections
+                .get(address)
+                .is_some_and(|open| Arc::ptr_eq(open, &connection))
+            {
+                connections.remove(address);
+            }
+        }
+        result
+    }
+
+    /// The open connection to `address`, connecting if there is none or
+    /// it was closed.
+    async fn connection(&self, address: &str) -> Result<Arc<Connection>, ClientError> {
+        if let Some(open) = self.connections.lock().unwrap().get(address) {
+            if !open.is_closed() {
+                return Ok(open.clone());
+            }
+        }
+        let connection = Arc::new(Connection::connect(address, self.client_id.clone()).await?);
+        let mut connections = self.connections.lock().unwrap();
+        match connections.get(address) {
+            // Another caller connected first
+            Some(open) if !open.is_closed() => Ok(open.clone()),
+            _ => {
+                connections.insert(address.to_string(), connection.clone());
+                Ok(connection)
+            }
+        }
+    }
+
+    fn cached_leader(&self, partition: &TopicPartition) -> Option<String> {
+        let metadata = self.metadata.read().unwrap();
+        let leader = metadata
+            .as_ref()?
+            .leader(&partition.topic, partition.partition)?;
+        Some(leader.address.clone()).filter(|address| !address.is_empty())
+    }
+
+    /// One past the highest partition with a leader, as partitions without
+    /// one are left out of metadata.
+    fn cached_partition_count(&self, topic: &str) -> Option<u32> {
+        let metadata = self.metadata.read().unwrap();
+        metadata
+            .as_ref()?
+            .topics
+            .iter()
+            .find(|metadata| metadata.topic == topic && metadata.error.is_ok())?
+            .partitions
+            .iter()
+            .map(|partition| partition.partition + 1)
+            .max()
+    }
+
+    fn cached_address(&self, broker_id: u32) -> Option<String> {
+        let metadata = self.metadata.read().unwrap();
+        let broker = metadata
+            .as_ref()?
+            .brokers
+            .iter()
+            .find(|broker| broker.id == broker_id)?;
+        Some(broker.address.clone()).filter(|address| !address.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::broker::{LogHandler, Server};
+    use crate::record::{Record, RecordBatch};
+    use crate::storage::{LogConfig, LogDirs, Placement};
+
+    fn produce(topic: &str, partition: u32, values: &[&'static str]) -> Produce {
+        let batch = RecordBatch::new(
+            values
+                .iter()
+                .map(|value| Record::new(None, Some(Bytes::from_static(value.as_bytes()))))
+                .collect(),
+        );
+        Produce::new(topic.to_string(), partition, batch).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_routing() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = LogDirs::open(
+            [dir.path().to_path_buf()],
+            LogConfig::default(),
+            Placement::default(),
+        );
+        logs.create(&TopicPartition::new("events", 0)).unwrap();
+        let handler = LogHandler::new(Arc::new(logs));
+        let server = Server::bind("127.0.0.1:0", handler.clone()).await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        handler.replicas().set_advertised_listener(addr.clone());
+        tokio::spawn(server.run());
+
+        let client = Client::new(vec![addr.clone()]).with_client_id("test");
+        assert_eq!(client.cached_metadata(), None);
+        let response = client.produce(produce("events", 0, &["a", "b"])).await;
+        assert_eq!(response.unwrap().unwrap().error, ErrorCode::None);
+        assert!(client.cached_metadata().is_some());
+
+        let fetch = Fetch::new("events".to_string(), 0, 0, 1024 * 1024).unwrap();
+        let response = client.fetch(fetch).await.unwrap();
+        assert_eq!(response.high_watermark, 2);
+
+        // Created after the cache was filled, found on a refresh
+        handler
+            .logs()
+            .create(&TopicPartition::new("events", 1))
+            .unwrap();
+        let response = client.produce(produce("events", 1, &["c"])).await;
+        assert_eq!(response.unwrap().unwrap().error, ErrorCode::None);
+
+        let err = client.produce(produce("missing", 0, &["d"])).await;
+        assert!(
+            matches!(err, Err(ClientError::NoLeader(partition)) if partition.topic == "missing")
+        );
+
+        let response = client.describe_cluster(DescribeCluster::new()).await;
+        assert_eq!(response.unwrap().brokers[0].address, addr);
+        let response = client.describe_log_dirs(0, DescribeLogDirs::all()).await;
+        assert_eq!(response.unwrap().dirs[0].partitions.len(), 2);
+        assert!(matches!(
+            client.describe_log_dirs(7, DescribeLogDirs::all()).await,
+            Err(ClientError::UnknownBroker(7))
+        ));
+
+        let commit = OffsetCommit::new(
+            "billing".to_string(),
+            vec![(TopicPartition::new("events", 0), 1)],
+        )
+        .unwrap();
+        assert!(client.offset_commit(commit).await.unwrap().error.is_ok());
+        let response = client.list_groups(ListGroups::new()).await.unwrap();
+        assert_eq!(response.groups[0].0, "billing");
+    }
+
+    #[tokio::test]
+    async fn test_multiplexing() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = LogDirs::open(
+            [dir.path().to_path_buf()],
+            LogConfig::default(),
+            Placement::default(),
+        );
+        logs.create(&TopicPartition::new("events", 0)).unwrap();
+        let handler = LogHandler::new(Arc::new(logs));
+        let server = Server::bind("127.0.0.1:0", handler.clone()).await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        handler.replicas().set_advertised_listener(addr.clone());
+        tokio::spawn(server.run());
+
+        // The produce goes out while the fetch is held waiting for it
+        let client = Arc::new(Client::new(vec![addr]));
+        client.refresh_metadata().await.unwrap();
+        let fetch = Fetch::new("events".to_string(), 0, 0, 1024 * 1024)
+            .unwrap()
+            .wait_for(1, 10_000);
+        let fetched = tokio::spawn({
+            let client = client.clone();
+            async move { client.fetch(fetch).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let response = client.produce(produce("events", 0, &["a"])).await;
+        assert_eq!(response.unwrap().unwrap().error, ErrorCode::None);
+        let response = tokio::time::timeout(Duration::from_secs(5), fetched)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.high_watermark, 1);
+        assert_eq!(client.connections.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = LogDirs::open(
+            [dir.path().to_path_buf()],
+            LogConfig::default(),
+            Placement::default(),
+        );
+        let handler = LogHandler::new(Arc::new(logs));
+        let server = Server::bind("127.0.0.1:0", handler.clone()).await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        handler.replicas().set_advertised_listener(addr.clone());
+        tokio::spawn(server.run());
+
+        let client = Client::new(vec![addr.clone()]).with_retry_policy(RetryPolicy::never());
+        let err = client.produce(produce("events", 0, &["a"])).await;
+        assert!(matches!(err, Err(ClientError::NoLeader(_))));
+
+        // The partition comes up while the produce backs off
+        let client = Client::new(vec![addr]).with_retry_policy(RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(10),
+            ..Default::default()
+        });
+        let created = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            handler
+                .logs()
+                .create(&TopicPartition::new("events", 0))
+                .unwrap();
+        });
+        let response = client.produce(produce("events", 0, &["a"])).await;
+        assert_eq!(response.unwrap().unwrap().error, ErrorCode::None);
+        created.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_topic_admin() {
+        let logs = LogDirs::in_memory(LogConfig::default());
+        let handler = LogHandler::new(Arc::new(logs));
+        let server = Server::bind("127.0.0.1:0", handler.clone()).await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        handler.replicas().set_advertised_listener(addr.clone());
+        tokio::spawn(server.run());
+
+        let client = Client::new(vec![addr]);
+        let create = CreateTopic::new("events".to_string(), 2).unwrap();
+        let response = client.create_topic(create.clone()).await.unwrap();
+        assert_eq!(response.error, ErrorCode::None);
+        let response = client.create_topic(create).await.unwrap();
+        assert_eq!(response.error, ErrorCode::TopicAlreadyExists);
+        assert_eq!(client.partition_count("events").await.unwrap(), Some(2));
+
+        let delete = DeleteTopic::new("events".to_string()).unwrap();
+        let response = client.delete_topic(0, delete.clone()).await.unwrap();
+        assert_eq!(response.error, ErrorCode::None);
+        assert!(handler.logs().partitions().is_empty());
+        let response = client.delete_topic(0, delete).await.unwrap();
+        assert_eq!(response.error, ErrorCode::UnknownTopicOrPartition);
+    }
+
+    #[tokio::test]
+    async fn test_no_brokers() {
+        let client = Client::new(vec![]);
+        assert!(matches!(
+            client.refresh_metadata().await,
+            Err(ClientError::NoBrokers)
+        ));
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt::Debug;
+use std::future::Future;
+
+use super::observer::NoObserver;
+use super::{Client, ClientError, ClientObserver};
+use crate::request::{
+    DescribeGroups, Fetch, Heartbeat, JoinGroup, LeaveGroup, ListOffsets, OffsetCommit, Produce,
+    SyncGroup,
+};
+use crate::response::{
+    AdminResponse, DescribeGroupsResponse, FetchResponse, JoinGroupResponse, ListOffsetsResponse,
+    ProduceResponse, SyncGroupResponse,
+};
+
+/// The requests [`Producer`](super::Producer), [`Consumer`](super::Consumer)
+/// and [`GroupConsumer`](super::GroupConsumer) make of the cluster, so they
+/// can run on something other than a [`Client`], such as a
+/// [`MockClient`](super::mock::MockClient) in tests. See the [`Client`]
+/// methods of the same names.
+pub trait ClusterClient: Debug + Send + Sync + 'static {
+    fn produce(
+        &self,
+        produce: Produce,
+    ) -> impl Future<Output = Result<Option<ProduceResponse>, ClientError>> + Send;
+
+    fn fetch(
+        &self,
+        fetch: Fetch,
+    ) -> impl Future<Output = Result<FetchResponse, ClientError>> + Send;
+
+    fn list_offsets(
+        &self,
+        request: ListOffsets,
+    ) -> impl Future<Output = Result<ListOffsetsResponse, ClientError>> + Send;
+
+    fn partition_count(
+        &self,
+        topic: &str,
+    ) -> impl Future<Output = Result<Option<u32>, ClientError>> + Send;
+
+    fn offset_commit(
+        &self,
+        request: OffsetCommit,
+    ) -> impl Future<Output = Result<AdminResponse, ClientError>> + Send;
+
+    fn describe_groups(
+        &self,
+        request: DescribeGroups,
+    ) -> impl Future<Output = Result<DescribeGroupsResponse, ClientError>> + Send;
+
+    fn join_group(
+        &self,
+        request: JoinGroup,
+    ) -> impl Future<Output = Result<JoinGroupResponse, ClientError>> + Send;
+
+    fn sync_group(
+        &self,
+        request: SyncGroup,
+    ) -> impl Future<Output = Result<SyncGroupResponse, ClientError>> + Send;
+
+    fn group_heartbeat(
+        &self,
+        request: Heartbeat,
+    ) -> impl Future<Output = Result<AdminResponse, ClientError>> + Send;
+
+    fn leave_group(
+        &self,
+        request: LeaveGroup,
+    ) -> impl Future<Output = Result<AdminResponse, ClientError>> + Send;
+
+    /// Told about the batches producers send, nothing by default.
+    fn observer(&self) -> &dyn ClientObserver {
+        &NoObserver
+    }
+}
+
+impl ClusterClient for Client {
+    async fn produce(&self, produce: Produce) -> Result<Option<ProduceResponse>, ClientError> {
+        Client::produce(self, produce).await
+    }
+
+    async fn fetch(&self, fetch: Fetch) -> Result<FetchResponse, ClientError> {
+        Client::fetch(self, fetch).await
+    }
+
+    async fn list_offsets(&self, request: ListOffsets) -> Result<ListOffsetsResponse, ClientError> {
+        Client::list_offsets(self, request).await
+    }
+
+    async fn partition_count(&self, topic: &str) -> Result<Option<u32>, ClientError> {
+        Client::partition_count(self, topic).await
+    }
+
+    async fn offset_commit(&self, request: OffsetCommit) -> Result<AdminResponse, ClientError> {
+        Client::offset_commit(self, request).await
+    }
+
+    async fn describe_groups(
+        &self,
+        request: DescribeGroups,
+    ) -> Result<DescribeGroupsResponse, ClientError> {
+        Client::describe_groups(self, request).await
+    }
+
+    async fn join_group(&self, request: JoinGroup) -> Result<JoinGroupResponse, ClientError> {
+        Client::join_group(self, request).await
+    }
+
+    async fn sync_group(&self, request: SyncGroup) -> Result<SyncGroupResponse, ClientError> {
+        Client::sync_group(self, request).await
+    }
+
+    async fn group_heartbeat(&self, request: Heartbeat) -> Result<AdminResponse, ClientError> {
+        Client::group_heartbeat(self, request).await
+    }
+
+    async fn leave_group(&self, request: LeaveGroup) -> Result<AdminResponse, ClientError> {
+        Client::leave_group(self, request).await
+    }
+
+    fn observer(&self) -> &dyn ClientObserver {
+        Client::observer(self)
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bytes::BytesMut;
+use tokio::io::{AsyncWriteExt, BufReader, BufWriter};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::sync::{oneshot, Mutex as AsyncMutex};
+use tokio::task::JoinHandle;
+
+use super::ClientError;
+use crate::broker::{read_frame, write_frame, DEFAULT_MAX_FRAME_SIZE};
+use crate::protocol::{ApiKey, RequestHeader, ResponseHeader};
+use crate::request::Request;
+use crate::response::Response;
+
+/// Callers waiting for a response, by correlation id.
+type InFlight = HashMap<u32, (ApiKey, oneshot::Sender<Result<Response, ClientError>>)>;
+
+/// A client's connection to one broker, shared by every request to it.
+/// Requests are written one after the other without waiting for earlier
+/// responses, and a background task hands each response to its caller by
+/// correlation id, in whatever order the broker answers.
+#[derive(Debug)]
+pub(crate) struct Connection {
+    writer: AsyncMutex<BufWriter<OwnedWriteHalf>>,
+    client_id: String,
+    correlation_id: AtomicU32,
+    in_flight: Arc<Mutex<InFlight>>,
+    /// Set once the stream can't be trusted, after a failure or a request
+    /// cancelled halfway through being written.
+    closed: Arc<AtomicBool>,
+    reader: JoinHandle<()>,
+}
+
+impl Connection {
+    pub(crate) async fn connect(
+        addr: impl ToSocketAddrs,
+        client_id: String,
+    ) -> Result<Self, ClientError> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        let (reader, writer) = stream.into_split();
+        let in_flight = Arc::new(Mutex::new(HashMap::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+        let reader = tokio::spawn(read_responses(
+            BufReader::new(reader),
+            in_flight.clone(),
+            closed.clone(),
+        ));
+        Ok(Connection {
+            writer: AsyncMutex::new(BufWriter::new(writer)),
+            client_id,
+            correlation_id: AtomicU32::new(0),
+            in_flight,
+            closed,
+            reader,
+        })
+    }
+
+    /// Whether the connection failed, or a call was dropped halfway
+    /// through, leaving the stream in an unknown state.
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Sends `request`, returning its response, or `None` for requests that
+    /// aren't answered. Calls on the same connection don't wait for each
+    /// other's responses.
+    pub(crate) async fn call(&self, request: &Request) -> Result<Option<Response>, ClientError> {
+        let correlation_id = self
+            .correlation_id
+            .fetch_add(1, Ordering::Relaxed)
+            .wrapping_add(1);
+        let header = RequestHeader::new(request.api_key(), correlation_id, self.client_id.clone())?;
+        let mut buf = BytesMut::with_capacity(header.size() + request.size());
+        header.encode_into(&mut buf);
+        request.encode_into(&mut buf);
+
+        let mut writer = self.writer.lock().await;
+        // Registered before writing, the response can beat the write's return
+        let answered = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            // Checked under the lock the reader clears callers with
+            if self.is_closed() {
+                return Err(ClientError::Disconnected);
+            }
+            request.expects_response().then(|| {
+                let (answer, answered) = oneshot::channel();
+                in_flight.insert(correlation_id, (request.api_key(), answer));
+                answered
+            })
+        };
+        let writing = CloseOnDrop(Some(&self.closed));
+        let written = match write_frame(&mut *writer, &buf).await {
+            Ok(()) => writer.flush().await,
+            Err(err) => Err(err),
+        };
+        writing.defuse();
+        drop(writer);
+        if let Err(err) = written {
+            self.closed.store(true, Ordering::Release);
+            self.in_flight.lock().unwrap().remove(&correlation_id);
+            return Err(err.into());
+        }
+
+        let Some(answered) = answered else {
+            return Ok(None);
+        };
+        match answered.await {
+            Ok(response) => response.map(Some),
+            // The reader gave up on the connection
+            Err(_) => Err(ClientError::Disconnected),
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// Closes the connection if dropped before being defused, as when a call is
+/// cancelled with part of its request written.
+struct CloseOnDrop<'a>(Option<&'a AtomicBool>);
+
+impl CloseOnDrop<'_> {
+    fn defuse(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for CloseOnDrop<'_> {
+    fn drop(&mut self) {
+        if let Some(closed) = self.0 {
+            closed.store(true, Ordering::Release);
+        }
+    }
+}
+
+/// Hands every response to the caller waiting for it, until the broker
+/// closes the connection or answers with something that can't be matched
+/// to a request. Callers still waiting then fail.
+async fn read_responses(
+    mut reader: BufReader<OwnedReadHalf>,
+    in_flight: Arc<Mutex<InFlight>>,
+    closed: Arc<AtomicBool>,
+) {
+    while let Ok(Some(mut frame)) = read_frame(&mut reader, DEFAULT_MAX_FRAME_SIZE).await {
+        let Ok(header) = ResponseHeader::decode(&mut frame) else {
+            break;
+        };
+        let waiting = in_flight.lock().unwrap().remove(&header.correlation_id);
+        let Some((api_key, answer)) = waiting else {
+            break;
+        };
+        // Gone if the caller gave up waiting
+        let _ = answer.send(Response::decode(api_key, frame).map_err(ClientError::from));
+    }
+
+    let mut in_flight = in_flight.lock().unwrap();
+    closed.store(true, Ordering::Release);
+    // Dropping the senders fails every caller still waiting
+    in_flight.clear();
+}
@@ -0,0 +1,896 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use std::vec;
+use thiserror::Error;
+
+use futures_core::Stream;
+use tokio::task::JoinSet;
+
+use super::{Client, ClientError, ClusterClient, ProduceError};
+use crate::chunk::{ChunkError, Reassembler};
+use crate::protocol::{ErrorCode, TopicPartition};
+use crate::record::Record;
+use crate::request::{
+    DescribeGroups, Fetch, FetchCreationError, GroupCreationError, ListOffsets,
+    ListOffsetsCreationError, OffsetCommit, EARLIEST_TIMESTAMP, LATEST_TIMESTAMP,
+};
+
+/// Bytes fetched from a partition at a time unless configured otherwise.
+pub const DEFAULT_FETCH_MAX_BYTES: u32 = 1024 * 1024;
+
+/// How long a broker holds a fetch of a partition without new records
+/// unless configured otherwise.
+pub const DEFAULT_FETCH_MAX_WAIT: Duration = Duration::from_millis(500);
+
+/// How often offsets are committed unless configured otherwise.
+pub const DEFAULT_AUTO_COMMIT_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Error, Debug)]
+pub enum ConsumeError {
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    #[error("Broker answered {1} for {0}")]
+    Partition(TopicPartition, ErrorCode),
+    #[error("Broker answered with {0}")]
+    Broker(ErrorCode),
+    #[error("Consumer has no group to commit offsets to")]
+    NoGroup,
+    #[error("{0} isn't assigned to the consumer")]
+    NotAssigned(TopicPartition),
+    #[error(transparent)]
+    Fetch(#[from] FetchCreationError),
+    #[error(transparent)]
+    Group(#[from] GroupCreationError),
+    #[error(transparent)]
+    ListOffsets(#[from] ListOffsetsCreationError),
+    #[error("Failed to dead-letter a record: {0}")]
+    DeadLetter(ProduceError),
+    #[error("Dropped a chunked record of {0}: {1}")]
+    Chunk(TopicPartition, ChunkError),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsumerConfig {
+    /// Group whose committed offsets the consumer starts from and commits
+    /// to. Without one, every partition is read from the start and nothing
+    /// is committed.
+    pub group: Option<String>,
+    pub fetch_max_bytes: u32,
+    pub fetch_max_wait: Duration,
+    /// How often [`Consumer::poll`] commits what was consumed, `None` to
+    /// only commit on [`Consumer::commit`].
+    pub auto_commit_interval: Option<Duration>,
+    /// Joins records split by a producer [`Chunker`](crate::chunk::Chunker)
+    /// back together, holding up to this many bytes of chunks per partition
+    /// while the rest arrive. `None` hands chunks out as they are.
+    pub reassembly_buffer_bytes: Option<usize>,
+}
+
+impl Default for ConsumerConfig {
+    fn default() -> Self {
+        ConsumerConfig {
+            group: None,
+            fetch_max_bytes: DEFAULT_FETCH_MAX_BYTES,
+            fetch_max_wait: DEFAULT_FETCH_MAX_WAIT,
+            auto_commit_interval: Some(DEFAULT_AUTO_COMMIT_INTERVAL),
+            reassembly_buffer_bytes: None,
+        }
+    }
+}
+
+/// A record and the partition it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsumerRecord {
+    pub partition: TopicPartition,
+    pub record: Record,
+}
+
+/// Reads the partitions assigned to it through a [`Client`], or any other
+/// [`ClusterClient`], keeping the
+/// position it is at in each.
+///
+/// Offsets count as consumed once [`Consumer::poll`] returned their record,
+/// and committing stores the next offset to read. Chunks of a payload still
+/// being joined aren't consumed yet, so a commit stops at the first of them.
+/// Partitions with nothing committed are read from offset 0.
+#[derive(Debug)]
+pub struct Consumer<C = Client> {
+    client: Arc<C>,
+    config: ConsumerConfig,
+    /// Next offset to fetch.
+    positions: BTreeMap<TopicPartition, u64>,
+    /// Next offset to hand out, behind the position while records are
+    /// buffered.
+    consumed: BTreeMap<TopicPartition, u64>,
+    committed: BTreeMap<TopicPartition, u64>,
+    /// Assigned but not fetched from until resumed.
+    paused: BTreeSet<TopicPartition>,
+    /// Records fetched but not handed out yet, as a poll failed.
+    buffered: Vec<ConsumerRecord>,
+    /// Chunks waiting on the rest of their record.
+    reassemblers: BTreeMap<TopicPartition, Reassembler>,
+    last_commit: Instant,
+}
+
+impl<C: ClusterClient> Consumer<C> {
+    pub fn new(client: Arc<C>, config: ConsumerConfig) -> Self {
+        Consumer {
+            client,
+            config,
+            positions: BTreeMap::new(),
+            consumed: BTreeMap::new(),
+            committed: BTreeMap::new(),
+            paused: BTreeSet::new(),
+            buffered: vec![],
+            reassemblers: BTreeMap::new(),
+            last_commit: Instant::now(),
+        }
+    }
+
+    /// Reads `partitions` from now on, starting where the group committed
+    /// up to.
+    pub async fn assign(&mut self, partitions: Vec<TopicPartition>) -> Result<(), ConsumeError> {
+        for partition in &partitions {
+            Fetch::new(partition.topic.clone(), partition.partition, 0, 0)?;
+        }
+        let committed = self.fetch_committed().await?;
+
+        self.unassign();
+        self.add(partitions, &committed);
+        Ok(())
+    }
+
+    /// Reads `partitions` as well, starting where the group committed up
+    /// to. Partitions already assigned carry on where they are.
+    pub async fn incremental_assign(
+        &mut self,
+        partitions: Vec<TopicPartition>,
+    ) -> Result<(), ConsumeError> {
+        for partition in &partitions {
+            Fetch::new(partition.topic.clone(), partition.partition, 0, 0)?;
+        }
+        let committed = self.fetch_committed().await?;
+
+        let added = partitions
+            .into_iter()
+            .filter(|partition| !self.positions.contains_key(partition))
+            .collect();
+        self.add(added, &committed);
+        Ok(())
+    }
+
+    /// Stops reading `partitions`, dropping their buffered records. The
+    /// others carry on where they are.
+    pub fn incremental_unassign(&mut self, partitions: &[TopicPartition]) {
+        for partition in partitions {
+            self.positions.remove(partition);
+            self.consumed.remove(partition);
+            self.committed.remove(partition);
+            self.paused.remove(partition);
+            self.reassemblers.remove(partition);
+        }
+        self.buffered
+            .retain(|consumed| !partitions.contains(&consumed.partition));
+    }
+
+    fn add(&mut self, partitions: Vec<TopicPartition>, committed: &BTreeMap<TopicPartition, u64>) {
+        for partition in partitions {
+            let offset = committed.get(&partition).copied();
+            if let Some(offset) = offset {
+                self.committed.insert(partition.clone(), offset);
+            }
+            self.positions
+                .insert(partition.clone(), offset.unwrap_or_default());
+            self.consumed.insert(partition, offset.unwrap_or_default());
+        }
+    }
+
+    /// Assigns every partition of `topic`.
+    pub async fn subscribe(&mut self, topic: &str) -> Result<(), ConsumeError> {
+        let count = self.client.partition_count(topic).await?.ok_or_else(|| {
+            ConsumeError::Partition(
+                TopicPartition::new(topic, 0),
+                ErrorCode::UnknownTopicOrPartition,
+            )
+        })?;
+        let partitions = (0..count)
+            .map(|partition| TopicPartition::new(topic, partition))
+            .collect();
+        self.assign(partitions).await
+    }
+
+    /// Stops reading every partition, dropping the records buffered.
+    pub fn unassign(&mut self) {
+        self.positions.clear();
+        self.consumed.clear();
+        self.committed.clear();
+        self.paused.clear();
+        self.buffered.clear();
+        self.reassemblers.clear();
+    }
+
+    pub fn assignment(&self) -> Vec<TopicPartition> {
+        self.positions.keys().cloned().collect()
+    }
+
+    /// Stops fetching `partitions` until they are resumed, keeping their
+    /// positions. Their buffered records are dropped, to be fetched again
+    /// once resumed.
+    pub fn pause(&mut self, partitions: &[TopicPartition]) -> Result<(), ConsumeError> {
+        self.check_assigned(partitions)?;
+        for partition in partitions {
+            self.rewind(partition);
+            self.paused.insert(partition.clone());
+        }
+        Ok(())
+    }
+
+    /// Fetches `partitions` again from where they were paused.
+    pub fn resume(&mut self, partitions: &[TopicPartition]) {
+        for partition in partitions {
+            self.paused.remove(partition);
+        }
+    }
+
+    pub fn paused(&self) -> Vec<TopicPartition> {
+        self.paused.iter().cloned().collect()
+    }
+
+    /// Makes `offset` the next offset handed out from `partition`, to skip
+    /// ahead or read records again. Takes effect on the next poll, and is
+    /// committed like any other position.
+    pub fn seek(&mut self, partition: &TopicPartition, offset: u64) -> Result<(), ConsumeError> {
+        self.check_assigned(std::slice::from_ref(partition))?;
+        self.buffered
+            .retain(|buffered| buffered.partition != *partition);
+        self.reassemblers.remove(partition);
+        self.positions.insert(partition.clone(), offset);
+        self.consumed.insert(partition.clone(), offset);
+        Ok(())
+    }
+
+    /// Seeks every assigned partition to its first record with a timestamp
+    /// at or after `timestamp`, in milliseconds since the epoch, to replay
+    /// from a point in time. Partitions with no record that recent seek to
+    /// their end.
+    pub async fn seek_to_timestamp(&mut self, timestamp: u64) -> Result<(), ConsumeError> {
+        let mut offsets = self.list_offsets(timestamp).await?;
+        let past_end: Vec<_> = offsets
+            .iter()
+            .filter(|(_, offset)| offset.is_none())
+            .map(|(partition, _)| partition.clone())
+            .collect();
+        if !past_end.is_empty() {
+            let ends = self.list_offsets(LATEST_TIMESTAMP).await?;
+            for partition in past_end {
+                offsets.insert(partition.clone(), ends[&partition]);
+            }
+        }
+        self.seek_all(offsets)
+    }
+
+    /// Seeks every assigned partition to its first record still kept.
+    pub async fn seek_to_beginning(&mut self) -> Result<(), ConsumeError> {
+        let offsets = self.list_offsets(EARLIEST_TIMESTAMP).await?;
+        self.seek_all(offsets)
+    }
+
+    /// Seeks every assigned partition past its last record, to only read
+    /// records produced from now on.
+    pub async fn seek_to_end(&mut self) -> Result<(), ConsumeError> {
+        let offsets = self.list_offsets(LATEST_TIMESTAMP).await?;
+        self.seek_all(offsets)
+    }
+
+    /// Looks up the offset for `timestamp` of every assigned partition at
+    /// once.
+    async fn list_offsets(
+        &self,
+        timestamp: u64,
+    ) -> Result<BTreeMap<TopicPartition, Option<u64>>, ConsumeError> {
+        let mut lookups = JoinSet::new();
+        for partition in self.positions.keys() {
+            let request =
+                ListOffsets::new(partition.topic.clone(), partition.partition, timestamp)?;
+            let client = self.client.clone();
+            let partition = partition.clone();
+            lookups.spawn(async move { (partition, client.list_offsets(request).await) });
+        }
+
+        let mut offsets = BTreeMap::new();
+        while let Some(looked_up) = lookups.join_next().await {
+            let (partition, result) = looked_up.expect("lookups don't panic");
+            let response = result?;
+            if !response.error.is_ok() {
+                return Err(ConsumeError::Partition(partition, response.error));
+            }
+            offsets.insert(partition, response.found());
+        }
+        Ok(offsets)
+    }
+
+    fn seek_all(
+        &mut self,
+        offsets: BTreeMap<TopicPartition, Option<u64>>,
+    ) -> Result<(), ConsumeError> {
+        for (partition, offset) in offsets {
+            if let Some(offset) = offset {
+                self.seek(&partition, offset)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_assigned(&self, partitions: &[TopicPartition]) -> Result<(), ConsumeError> {
+        match partitions
+            .iter()
+            .find(|partition| !self.positions.contains_key(partition))
+        {
+            Some(partition) => Err(ConsumeError::NotAssigned(partition.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Drops the buffered records of `partition`, fetching from the next
+    /// one to hand out instead.
+    fn rewind(&mut self, partition: &TopicPartition) {
+        self.buffered
+            .retain(|buffered| buffered.partition != *partition);
+        self.reassemblers.remove(partition);
+        let consumed = self.consumed[partition];
+        self.positions.insert(partition.clone(), consumed);
+    }
+
+    /// Next offset [`Consumer::poll`] hands out from `partition`.
+    pub fn position(&self, partition: &TopicPartition) -> Option<u64> {
+        self.consumed.get(partition).copied()
+    }
+
+    /// Fetches every assigned partition that isn't paused at once,
+    /// returning the records that came back. Brokers hold fetches of
+    /// partitions without new records for up to the fetch max wait, and
+    /// with nothing to fetch the poll waits it out as well.
+    ///
+    /// When a fetch fails, the records of the others are kept for the next
+    /// poll. So are they when a chunked record can't be joined back
+    /// together, which is dropped.
+    pub async fn poll(&mut self) -> Result<Vec<ConsumerRecord>, ConsumeError> {
+        if let Some(interval) = self.config.auto_commit_interval {
+            if self.config.group.is_some() && self.last_commit.elapsed() >= interval {
+                self.commit().await?;
+            }
+        }
+        if !self.buffered.is_empty() {
+            return Ok(self.hand_out());
+        }
+
+        let max_wait_ms = self.config.fetch_max_wait.as_millis() as u32;
+        let mut fetches = JoinSet::new();
+        let fetched = self
+            .positions
+            .iter()
+            .filter(|(partition, _)| !self.paused.contains(partition));
+        for (partition, &offset) in fetched {
+            let fetch = Fetch::new(
+                partition.topic.clone(),
+                partition.partition,
+                offset,
+                self.config.fetch_max_bytes,
+            )?
+            .wait_for(1, max_wait_ms);
+            let client = self.client.clone();
+            let partition = partition.clone();
+            fetches.spawn(async move { (partition, client.fetch(fetch).await) });
+        }
+
+        if fetches.is_empty() {
+            tokio::time::sleep(self.config.fetch_max_wait).await;
+            return Ok(vec![]);
+        }
+
+        let mut failed = None;
+        while let Some(fetched) = fetches.join_next().await {
+            let (partition, result) = fetched.expect("fetches don't panic");
+            let response = match result {
+                Ok(response) if response.error.is_ok() => response,
+                Ok(response) => {
+                    failed.get_or_insert(ConsumeError::Partition(partition, response.error));
+                    continue;
+                }
+                Err(err) => {
+                    failed.get_or_insert(err.into());
+                    continue;
+                }
+            };
+
+            let position = self.positions.get_mut(&partition).unwrap();
+            let mut reassembler = self.config.reassembly_buffer_bytes.map(|max| {
+                self.reassemblers
+                    .entry(partition.clone())
+                    .or_insert_with(|| Reassembler::new(max))
+            });
+            for record in response.batches.into_iter().flat_map(|batch| batch.records) {
+                // Batches can start before the position
+                if record.offset < *position {
+                    continue;
+                }
+                *position = record.offset + 1;
+                let record = match reassembler.as_deref_mut() {
+                    None => record,
+                    Some(reassembler) => match reassembler.push_record(record) {
+                        Ok(Some(record)) => record,
+                        Ok(None) => continue,
+                        Err(err) => {
+                            failed.get_or_insert(ConsumeError::Chunk(partition.clone(), err));
+                            continue;
+                        }
+                    },
+                };
+                self.buffered.push(ConsumerRecord {
+                    partition: partition.clone(),
+                    record,
+                });
+            }
+        }
+
+        match failed {
+            Some(err) => Err(err),
+            None => Ok(self.hand_out()),
+        }
+    }
+
+    /// Commits the offsets consumed since the last commit.
+    pub async fn commit(&mut self) -> Result<(), ConsumeError> {
+        let group = self.config.group.clone().ok_or(ConsumeError::NoGroup)?;
+        let offsets: Vec<_> = self
+            .consumed
+            .iter()
+            .map(|(partition, &offset)| (partition, self.committable(partition, offset)))
+            .filter(|(partition, offset)| self.committed.get(partition) != Some(offset))
+            .map(|(partition, offset)| (partition.clone(), offset))
+            .collect();
+        self.last_commit = Instant::now();
+        if offsets.is_empty() {
+            return Ok(());
+        }
+
+        let response = self
+            .client
+            .offset_commit(OffsetCommit::new(group, offsets.clone())?)
+            .await?;
+        if !response.error.is_ok() {
+            return Err(ConsumeError::Broker(response.error));
+        }
+        self.committed.extend(offsets);
+        Ok(())
+    }
+
+    /// The records of every poll one at a time, polling again once the last
+    /// one is taken. Records not taken when the stream is dropped are handed
+    /// out again by the next poll.
+    pub fn stream(&mut self) -> ConsumerStream<'_, C> {
+        ConsumerStream {
+            state: StreamState::Idle(self),
+            ready: vec![].into_iter(),
+        }
+    }
+
+    /// `consumed` held back to the first chunk still waiting on the rest of
+    /// its payload, so the payload is fetched again after a restart.
+    fn committable(&self, partition: &TopicPartition, consumed: u64) -> u64 {
+        self.reassemblers
+            .get(partition)
+            .and_then(Reassembler::first_pending_offset)
+            .map_or(consumed, |pending| pending.min(consumed))
+    }
+
+    fn hand_out(&mut self) -> Vec<ConsumerRecord> {
+        let records = mem::take(&mut self.buffered);
+        for consumed in &records {
+            self.consumed
+                .insert(consumed.partition.clone(), consumed.record.offset + 1);
+        }
+        records
+    }
+
+    /// Takes back records handed out but not consumed, to hand out first.
+    pub(super) fn hand_back(&mut self, records: Vec<ConsumerRecord>) {
+        for returned in &records {
+            let consumed = self.consumed.entry(returned.partition.clone()).or_default();
+            *consumed = (*consumed).min(returned.record.offset);
+        }
+        self.buffered.splice(0..0, records);
+    }
+
+    /// Offsets the group committed, none without a group.
+    async fn fetch_committed(&self) -> Result<BTreeMap<TopicPartition, u64>, ConsumeError> {
+        let Some(group) = &self.config.group else {
+            return Ok(BTreeMap::new());
+        };
+        let request = DescribeGroups::new(vec![group.clone()])?;
+        let response = self.client.describe_groups(request).await?;
+        if !response.error.is_ok() {
+            return Err(ConsumeError::Broker(response.error));
+        }
+
+        let mut committed = BTreeMap::new();
+        for described in response.groups {
+            match described.error {
+                ErrorCode::None => {}
+                // Nothing committed yet
+                ErrorCode::GroupIdNotFound => continue,
+                error => return Err(ConsumeError::Broker(error)),
+            }
+            for offset in described.offsets {
+                committed.insert(
+                    TopicPartition::new(offset.topic, offset.partition),
+                    offset.committed,
+                );
+            }
+        }
+        Ok(committed)
+    }
+}
+
+type Polling<'a, C> = Pin<Box<dyn Future<Output = (&'a mut Consumer<C>, PollResult)> + Send + 'a>>;
+
+type PollResult = Result<Vec<ConsumerRecord>, ConsumeError>;
+
+/// Records of a [`Consumer`] as a [`Stream`], see [`Consumer::stream`]. A
+/// failed poll yields its error, the stream carries on after it.
+pub struct ConsumerStream<'a, C: ClusterClient = Client> {
+    state: StreamState<'a, C>,
+    ready: vec::IntoIter<ConsumerRecord>,
+}
+
+enum StreamState<'a, C> {
+    Idle(&'a mut Consumer<C>),
+    Polling(Polling<'a, C>),
+    /// Only while switching between the others.
+    Switching,
+}
+
+impl<C: ClusterClient> Stream for ConsumerStream<'_, C> {
+    type Item = Result<ConsumerRecord, ConsumeError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if let Some(record) = this.ready.next() {
+                return Poll::Ready(Some(Ok(record)));
+            }
+            match mem::replace(&mut this.state, StreamState::Switching) {
+                StreamState::Idle(consumer) => {
+                    this.state = StreamState::Polling(Box::pin(async move {
+                        let polled = consumer.poll().await;
+                        (consumer, polled)
+                    }));
+                }
+                StreamState::Polling(mut polling) => match polling.as_mut().poll(cx) {
+                    Poll::Pending => {
+                        this.state = StreamState::Polling(polling);
+                        return Poll::Pending;
+                    }
+                    Poll::Ready((consumer, polled)) => {
+                        this.state = StreamState::Idle(consumer);
+                        match polled {
+                            Ok(records) => this.ready = records.into_iter(),
+                            Err(err) => return Poll::Ready(Some(Err(err))),
+                        }
+                    }
+                },
+                StreamState::Switching => unreachable!("never left switching"),
+            }
+        }
+    }
+}
+
+impl<C: ClusterClient> Drop for ConsumerStream<'_, C> {
+    fn drop(&mut self) {
+        // Records are only ready while the consumer is idle
+        if let StreamState::Idle(consumer) = &mut self.state {
+            consumer.hand_back(mem::take(&mut self.ready).collect());
+        }
+    }
+}
+
+impl<C: ClusterClient> std::fmt::Debug for ConsumerStream<'_, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConsumerStream")
+            .field("ready", &self.ready.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::broker::{LogHandler, Server};
+    use crate::chunk::Chunker;
+    use crate::client::{Producer, ProducerConfig};
+    use crate::record::RecordBatch;
+    use crate::request::Produce;
+    use crate::storage::{LogConfig, LogDirs, Placement};
+
+    async fn start() -> (Arc<Client>, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = LogDirs::open(
+            [dir.path().to_path_buf()],
+            LogConfig::default(),
+            Placement::default(),
+        );
+        logs.create(&TopicPartition::new("events", 0)).unwrap();
+        logs.create(&TopicPartition::new("events", 1)).unwrap();
+        let handler = LogHandler::new(Arc::new(logs));
+        let server = Server::bind("127.0.0.1:0", handler.clone()).await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        handler.replicas().set_advertised_listener(addr.clone());
+        tokio::spawn(server.run());
+        (Arc::new(Client::new(vec![addr])), dir)
+    }
+
+    async fn produce(client: &Client, partition: u32, values: &[&'static str]) {
+        let batch = RecordBatch::new(
+            values
+                .iter()
+                .map(|value| Record::new(None, Some(Bytes::from_static(value.as_bytes()))))
+                .collect(),
+        );
+        let produce = Produce::new("events".to_string(), partition, batch).unwrap();
+        client.produce(produce).await.unwrap();
+    }
+
+    fn values(records: &[ConsumerRecord]) -> Vec<&[u8]> {
+        let mut values: Vec<_> = records
+            .iter()
+            .map(|consumed| consumed.record.value.as_deref().unwrap())
+            .collect();
+        values.sort();
+        values
+    }
+
+    fn config() -> ConsumerConfig {
+        ConsumerConfig {
+            group: Some("billing".to_string()),
+            fetch_max_wait: Duration::from_millis(10),
+            auto_commit_interval: None,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_poll_and_commit() {
+        let (client, _dir) = start().await;
+        produce(&client, 0, &["a", "b"]).await;
+        produce(&client, 1, &["c"]).await;
+
+        let mut consumer = Consumer::new(client.clone(), config());
+        consumer.subscribe("events").await.unwrap();
+        assert_eq!(consumer.assignment().len(), 2);
+        let records = consumer.poll().await.unwrap();
+        assert_eq!(values(&records), vec![&b"a"[..], b"b", b"c"]);
+        assert!(consumer.poll().await.unwrap().is_empty());
+        consumer.commit().await.unwrap();
+
+        // A new consumer of the group picks up after the commit
+        produce(&client, 0, &["d"]).await;
+        let mut consumer = Consumer::new(client.clone(), config());
+        consumer.subscribe("events").await.unwrap();
+        let events = |partition| TopicPartition::new("events", partition);
+        assert_eq!(consumer.position(&events(0)), Some(2));
+        assert_eq!(consumer.position(&events(1)), Some(1));
+        let records = consumer.poll().await.unwrap();
+        assert_eq!(values(&records), vec![&b"d"[..]]);
+        assert_eq!(records[0].record.offset, 2);
+
+        let mut consumer = Consumer::new(client, ConsumerConfig::default());
+        consumer.assign(vec![events(0)]).await.unwrap();
+        assert_eq!(consumer.poll().await.unwrap().len(), 3);
+        assert!(matches!(
+            consumer.commit().await,
+            Err(ConsumeError::NoGroup)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_auto_commit() {
+        let (client, _dir) = start().await;
+        produce(&client, 0, &["a", "b"]).await;
+
+        let mut consumer = Consumer::new(
+            client.clone(),
+            ConsumerConfig {
+                auto_commit_interval: Some(Duration::ZERO),
+                ..config()
+            },
+        );
+        consumer
+            .assign(vec![TopicPartition::new("events", 0)])
+            .await
+            .unwrap();
+        assert_eq!(consumer.poll().await.unwrap().len(), 2);
+        // Commits what the last poll handed out
+        consumer.poll().await.unwrap();
+
+        let request = DescribeGroups::new(vec!["billing".to_string()]).unwrap();
+        let described = client.describe_groups(request).await.unwrap();
+        assert_eq!(described.groups[0].offsets[0].committed, 2);
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let (client, _dir) = start().await;
+        produce(&client, 0, &["a", "b", "c"]).await;
+
+        let mut consumer = Consumer::new(client.clone(), config());
+        let events = TopicPartition::new("events", 0);
+        consumer.assign(vec![events.clone()]).await.unwrap();
+        let mut stream = consumer.stream();
+        let first = std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await;
+        let first = first.unwrap().unwrap();
+        assert_eq!(first.record.value.as_deref(), Some(&b"a"[..]));
+        drop(stream);
+
+        // What the stream didn't yield is polled again
+        assert_eq!(consumer.position(&events), Some(1));
+        let records = consumer.poll().await.unwrap();
+        assert_eq!(values(&records), vec![&b"b"[..], b"c"]);
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_seek() {
+        let (client, _dir) = start().await;
+        produce(&client, 0, &["a", "b"]).await;
+        produce(&client, 1, &["c"]).await;
+
+        let mut consumer = Consumer::new(client.clone(), config());
+        consumer.subscribe("events").await.unwrap();
+        let events = |partition| TopicPartition::new("events", partition);
+        consumer.pause(&[events(0)]).unwrap();
+        assert_eq!(consumer.paused(), vec![events(0)]);
+        let records = consumer.poll().await.unwrap();
+        assert_eq!(values(&records), vec![&b"c"[..]]);
+
+        consumer.resume(&[events(0)]);
+        let records = consumer.poll().await.unwrap();
+        assert_eq!(values(&records), vec![&b"a"[..], b"b"]);
+
+        // Reads the partition again from the offset sought
+        consumer.seek(&events(0), 1).unwrap();
+        assert_eq!(consumer.position(&events(0)), Some(1));
+        let records = consumer.poll().await.unwrap();
+        assert_eq!(values(&records), vec![&b"b"[..]]);
+
+        assert!(matches!(
+            consumer.seek(&events(2), 0),
+            Err(ConsumeError::NotAssigned(partition)) if partition == events(2)
+        ));
+        assert!(consumer.pause(&[events(2)]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_seek_to_timestamp() {
+        let (client, _dir) = start().await;
+        for (partition, records) in [
+            (0, vec![("a", 1_000), ("b", 2_000), ("c", 3_000)]),
+            (1, vec![("d", 1_000)]),
+        ] {
+            let records = records
+                .into_iter()
+                .map(|(value, timestamp)| {
+                    Record::new(None, Some(Bytes::from_static(value.as_bytes())))
+                        .with_timestamp(timestamp)
+                })
+                .collect();
+            let batch = RecordBatch::new(records);
+            let request = Produce::new("events".to_string(), partition, batch).unwrap();
+            client.produce(request).await.unwrap();
+        }
+
+        let mut consumer = Consumer::new(client.clone(), config());
+        consumer.subscribe("events").await.unwrap();
+        let events = |partition| TopicPartition::new("events", partition);
+        assert_eq!(consumer.poll().await.unwrap().len(), 4);
+
+        // Partition 1 has nothing that recent, so seeks to its end
+        consumer.seek_to_timestamp(1_500).await.unwrap();
+        assert_eq!(consumer.position(&events(0)), Some(1));
+        assert_eq!(consumer.position(&events(1)), Some(1));
+        let records = consumer.poll().await.unwrap();
+        assert_eq!(values(&records), vec![&b"b"[..], b"c"]);
+
+        consumer.seek_to_beginning().await.unwrap();
+        assert_eq!(consumer.poll().await.unwrap().len(), 4);
+        consumer.seek_to_end().await.unwrap();
+        assert_eq!(consumer.position(&events(0)), Some(3));
+        assert!(consumer.poll().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_chunked_records() {
+        let (client, _dir) = start().await;
+        // Every chunk goes out in a batch of its own
+        let producer_config = ProducerConfig {
+            batch_size: 1,
+            ..Default::default()
+        };
+        let producer =
+            Producer::new(client.clone(), producer_config).with_chunker(Chunker::new(4).unwrap());
+        let large = Record::new(Some(Bytes::from("key")), Some(Bytes::from("abcdefghij")));
+        let small = Record::new(None, Some(Bytes::from("k")));
+        let offsets = (
+            producer.send("events", 0, large).unwrap(),
+            producer.send("events", 0, small).unwrap(),
+        );
+        assert_eq!((offsets.0.await.unwrap(), offsets.1.await.unwrap()), (2, 3));
+
+        let mut consumer = Consumer::new(
+            client.clone(),
+            ConsumerConfig {
+                reassembly_buffer_bytes: Some(1024),
+                ..config()
+            },
+        );
+        consumer.subscribe("events").await.unwrap();
+        let records = consumer.poll().await.unwrap();
+        assert_eq!(values(&records), vec![&b"abcdefghij"[..], b"k"]);
+        let joined = records.iter().find(|consumed| consumed.record.offset == 2);
+        let joined = &joined.unwrap().record;
+        assert_eq!(joined.key.as_deref(), Some(&b"key"[..]));
+        assert!(joined.headers.is_empty());
+        assert_eq!(
+            consumer.position(&TopicPartition::new("events", 0)),
+            Some(4)
+        );
+
+        // Without a buffer the chunks come out as they are
+        let mut consumer = Consumer::new(client, config());
+        consumer.subscribe("events").await.unwrap();
+        let records = consumer.poll().await.unwrap();
+        assert_eq!(values(&records), vec![&b"abcd"[..], b"efgh", b"ij", b"k"]);
+    }
+
+    #[tokio::test]
+    async fn test_commit_with_pending_chunks() {
+        let (client, _dir) = start().await;
+        let mut chunker = Chunker::new(4).unwrap();
+        let first = chunker.split_record(Record::new(None, Some(Bytes::from("aaaaaa"))));
+        let second = chunker.split_record(Record::new(None, Some(Bytes::from("bbbbbb"))));
+        let produce_chunks = |chunks: Vec<Record>| {
+            let client = client.clone();
+            async move {
+                let batch = RecordBatch::new(chunks);
+                let request = Produce::new("events".to_string(), 0, batch).unwrap();
+                client.produce(request).await.unwrap();
+            }
+        };
+        produce_chunks(vec![first[0].clone(), second[0].clone(), second[1].clone()]).await;
+
+        let config = || ConsumerConfig {
+            reassembly_buffer_bytes: Some(1024),
+            ..config()
+        };
+        let events = TopicPartition::new("events", 0);
+        let mut consumer = Consumer::new(client.clone(), config());
+        consumer.assign(vec![events.clone()]).await.unwrap();
+        let records = consumer.poll().await.unwrap();
+        assert_eq!(values(&records), vec![&b"bbbbbb"[..]]);
+        assert_eq!(consumer.position(&events), Some(3));
+        consumer.commit().await.unwrap();
+
+        // The first payload's chunks are read again after a restart
+        produce_chunks(vec![first[1].clone()]).await;
+        let mut consumer = Consumer::new(client, config());
+        consumer.assign(vec![events.clone()]).await.unwrap();
+        assert_eq!(consumer.position(&events), Some(0));
+        let records = consumer.poll().await.unwrap();
+        assert_eq!(values(&records), vec![&b"aaaaaa"[..], b"bbbbbb"]);
+    }
+}
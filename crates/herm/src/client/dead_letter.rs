@@ -0,0 +1,327 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use thiserror::Error;
+
+use super::{
+    Client, ClusterClient, ConsumeError, Consumer, ConsumerRecord, ProduceError, Producer,
+    ProducerConfig,
+};
+use crate::record::{Header, Record};
+
+/// Appended to a topic's name for the topic its dead letters go to unless
+/// configured otherwise.
+pub const DEFAULT_DLQ_SUFFIX: &str = ".dlq";
+
+/// How often a record is processed before it is dead-lettered unless
+/// configured otherwise.
+pub const DEFAULT_DLQ_MAX_ATTEMPTS: u32 = 3;
+
+/// Header of a dead letter counting the attempts made at processing it.
+pub const DLQ_ATTEMPTS_HEADER: &str = "herm.dlq.attempts";
+
+/// Header of a dead letter holding the error of its last attempt.
+pub const DLQ_ERROR_HEADER: &str = "herm.dlq.error";
+
+/// Header of a dead letter holding the topic it was consumed from.
+pub const DLQ_TOPIC_HEADER: &str = "herm.dlq.topic";
+
+/// Header of a dead letter holding the partition it was consumed from.
+pub const DLQ_PARTITION_HEADER: &str = "herm.dlq.partition";
+
+/// Header of a dead letter holding the offset it was consumed from.
+pub const DLQ_OFFSET_HEADER: &str = "herm.dlq.offset";
+
+/// Why processing a record failed.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ProcessError {
+    /// Worth another attempt, until they run out.
+    #[error("{0}")]
+    Retry(String),
+    /// Never going to succeed, such as a record that doesn't deserialize,
+    /// so dead-lettered straight away.
+    #[error("{0}")]
+    Reject(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeadLetterConfig {
+    /// Attempts at processing a record, the first included.
+    pub max_attempts: u32,
+    /// Appended to a record's topic for the topic it is dead-lettered to.
+    pub suffix: String,
+}
+
+impl Default for DeadLetterConfig {
+    fn default() -> Self {
+        DeadLetterConfig {
+            max_attempts: DEFAULT_DLQ_MAX_ATTEMPTS,
+            suffix: DEFAULT_DLQ_SUFFIX.to_string(),
+        }
+    }
+}
+
+/// What became of a record handed to [`DeadLetterQueue::process`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Processed {
+    Done,
+    /// Sent to the dead letter topic at this offset.
+    DeadLettered(u64),
+}
+
+/// Moves records that keep failing processing out of the way, to
+/// `<topic>.dlq` by default, so a poison record doesn't hold up the rest
+/// of its partition.
+///
+/// Dead letters keep the key, value and headers of the record, with
+/// headers added for the attempts made, the last error and where the
+/// record was consumed from.
+#[derive(Debug)]
+pub struct DeadLetterQueue<C = Client> {
+    producer: Producer<C>,
+    config: DeadLetterConfig,
+}
+
+impl<C: ClusterClient> DeadLetterQueue<C> {
+    pub fn new(client: Arc<C>, config: DeadLetterConfig) -> Self {
+        DeadLetterQueue {
+            producer: Producer::new(client, ProducerConfig::default()),
+            config,
+        }
+    }
+
+    /// The topic records of `topic` are dead-lettered to.
+    pub fn topic_for(&self, topic: &str) -> String {
+        format!("{}{}", topic, self.config.suffix)
+    }
+
+    /// Runs `process` on `consumed` until it succeeds, is rejected, or
+    /// runs out of attempts, dead-lettering it in the last two cases.
+    pub async fn process<F, Fut>(
+        &self,
+        consumed: &ConsumerRecord,
+        mut process: F,
+    ) -> Result<Processed, ProduceError>
+    where
+        F: FnMut(&ConsumerRecord) -> Fut,
+        Fut: Future<Output = Result<(), ProcessError>>,
+    {
+        let mut attempts = 0;
+        let error = loop {
+            attempts += 1;
+            match process(consumed).await {
+                Ok(()) => return Ok(Processed::Done),
+                Err(ProcessError::Retry(_)) if attempts < self.config.max_attempts => continue,
+                Err(err) => break err,
+            }
+        };
+        let offset = self.send(consumed, attempts, &error.to_string()).await?;
+        Ok(Processed::DeadLettered(offset))
+    }
+
+    /// Sends `consumed` to its dead letter topic after `attempts` failed
+    /// with `error` the last time, returning the offset it got there.
+    pub async fn send(
+        &self,
+        consumed: &ConsumerRecord,
+        attempts: u32,
+        error: &str,
+    ) -> Result<u64, ProduceError> {
+        let source = &consumed.partition;
+        let mut record = Record {
+            offset: 0,
+            ..consumed.record.clone()
+        };
+        // Records dead-lettered again get fresh headers
+        record
+            .headers
+            .retain(|header| !header.key.starts_with("herm.dlq."));
+        for (key, value) in [
+            (DLQ_ATTEMPTS_HEADER, attempts.to_string()),
+            (DLQ_ERROR_HEADER, error.to_string()),
+            (DLQ_TOPIC_HEADER, source.topic.clone()),
+            (DLQ_PARTITION_HEADER, source.partition.to_string()),
+            (DLQ_OFFSET_HEADER, consumed.record.offset.to_string()),
+        ] {
+            record.headers.push(Header::new(key, Bytes::from(value)));
+        }
+
+        let delivery = self
+            .producer
+            .send_to_topic(&self.topic_for(&source.topic), record)
+            .await?;
+        self.producer.flush().await;
+        delivery.await
+    }
+}
+
+impl<C: ClusterClient> Consumer<C> {
+    /// Polls once and runs `process` on each record through `dlq`, so
+    /// records that keep failing are dead-lettered rather than blocking
+    /// their partition. Returns how many records were polled.
+    ///
+    /// If a dead letter can't be sent, the records from there on are handed
+    /// out again by the next poll.
+    pub async fn process<F, Fut>(
+        &mut self,
+        dlq: &DeadLetterQueue<C>,
+        mut process: F,
+    ) -> Result<usize, ConsumeError>
+    where
+        F: FnMut(&ConsumerRecord) -> Fut,
+        Fut: Future<Output = Result<(), ProcessError>>,
+    {
+        let mut records = self.poll().await?.into_iter();
+        let count = records.len();
+        while let Some(consumed) = records.next() {
+            if let Err(err) = dlq.process(&consumed, &mut process).await {
+                let mut left = vec![consumed];
+                left.extend(records);
+                self.hand_back(left);
+                return Err(ConsumeError::DeadLetter(err));
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::client::mock::MockClient;
+    use crate::client::{ClientError, ConsumerConfig};
+    use crate::protocol::{ApiKey, TopicPartition};
+    use crate::record::RecordBatch;
+    use crate::request::Request;
+    use crate::response::{FetchResponse, ProduceResponse};
+
+    fn consumer(client: &Arc<MockClient>) -> Consumer<MockClient> {
+        let config = ConsumerConfig {
+            fetch_max_wait: Duration::from_millis(10),
+            auto_commit_interval: None,
+            ..Default::default()
+        };
+        Consumer::new(client.clone(), config)
+    }
+
+    fn dead_letters(client: &MockClient) -> Vec<Record> {
+        client
+            .requests_of(ApiKey::Produce)
+            .into_iter()
+            .flat_map(|request| match request {
+                Request::Produce(produce) => {
+                    assert_eq!(produce.topic(), "events.dlq");
+                    produce.into_batch().records
+                }
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    fn header<'a>(record: &'a Record, key: &str) -> &'a [u8] {
+        let header = record.headers.iter().find(|header| header.key == key);
+        &header.unwrap().value
+    }
+
+    #[tokio::test]
+    async fn test_process() {
+        let client = Arc::new(
+            MockClient::new()
+                .with_topic("events", 1)
+                .with_topic("events.dlq", 1),
+        );
+        let values = ["ok", "flaky", "poison", "garbage"];
+        let records = values
+            .iter()
+            .map(|value| Record::new(None, Some(Bytes::from_static(value.as_bytes()))))
+            .collect();
+        client.respond(FetchResponse::new(4, vec![RecordBatch::new(records)]));
+        client.respond(ProduceResponse::new(0));
+        client.respond(ProduceResponse::new(1));
+
+        let mut consumer = consumer(&client);
+        consumer.subscribe("events").await.unwrap();
+        let dlq = DeadLetterQueue::new(client.clone(), DeadLetterConfig::default());
+        let attempts = Mutex::new(HashMap::new());
+        let processed = consumer
+            .process(&dlq, |consumed| {
+                let value = consumed.record.value.clone().unwrap();
+                let attempt = {
+                    let mut attempts = attempts.lock().unwrap();
+                    let attempt = attempts.entry(value.clone()).or_insert(0);
+                    *attempt += 1;
+                    *attempt
+                };
+                async move {
+                    match &value[..] {
+                        b"flaky" if attempt < 2 => Err(ProcessError::Retry("busy".into())),
+                        b"poison" => Err(ProcessError::Retry("boom".into())),
+                        b"garbage" => Err(ProcessError::Reject("not json".into())),
+                        _ => Ok(()),
+                    }
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(processed, 4);
+        let attempts = attempts.into_inner().unwrap();
+        assert_eq!(attempts.values().sum::<u32>(), 1 + 2 + 3 + 1);
+        assert_eq!(
+            consumer.position(&TopicPartition::new("events", 0)),
+            Some(4)
+        );
+
+        let dead_letters = dead_letters(&client);
+        assert_eq!(dead_letters.len(), 2);
+        let poison = &dead_letters[0];
+        assert_eq!(poison.value.as_deref(), Some(&b"poison"[..]));
+        assert_eq!(header(poison, DLQ_ATTEMPTS_HEADER), b"3");
+        assert_eq!(header(poison, DLQ_ERROR_HEADER), b"boom");
+        assert_eq!(header(poison, DLQ_TOPIC_HEADER), b"events");
+        assert_eq!(header(poison, DLQ_PARTITION_HEADER), b"0");
+        assert_eq!(header(poison, DLQ_OFFSET_HEADER), b"2");
+        assert_eq!(header(&dead_letters[1], DLQ_ATTEMPTS_HEADER), b"1");
+    }
+
+    #[tokio::test]
+    async fn test_failed_dead_letter() {
+        let client = Arc::new(
+            MockClient::new()
+                .with_topic("events", 1)
+                .with_topic("events.dlq", 1),
+        );
+        let records = ["poison", "next"]
+            .iter()
+            .map(|value| Record::new(None, Some(Bytes::from_static(value.as_bytes()))))
+            .collect();
+        client.respond(FetchResponse::new(2, vec![RecordBatch::new(records)]));
+        let config = DeadLetterConfig {
+            max_attempts: 1,
+            ..Default::default()
+        };
+        let dlq = DeadLetterQueue::new(client.clone(), config);
+        // Fails every attempt of the producer
+        for _ in 0..=ProducerConfig::default().retries {
+            client.fail(ApiKey::Produce, ClientError::Disconnected);
+        }
+
+        let mut consumer = consumer(&client);
+        consumer.subscribe("events").await.unwrap();
+        let fail = |_: &ConsumerRecord| async { Err(ProcessError::Retry("boom".into())) };
+        assert!(matches!(
+            consumer.process(&dlq, fail).await,
+            Err(ConsumeError::DeadLetter(_))
+        ));
+
+        // Both come back, without fetching them again
+        let records = consumer.poll().await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].record.offset, 0);
+        assert_eq!(client.requests_of(ApiKey::Fetch).len(), 1);
+    }
+}
@@ -0,0 +1,42 @@
+use thiserror::Error;
+
+use crate::protocol::{HeaderError, TopicPartition};
+use crate::response::ResponseError;
+
+/// Client id sent with requests unless set otherwise.
+pub const DEFAULT_CLIENT_ID: &str = "herm-client";
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Broker closed the connection")]
+    Disconnected,
+    #[error(transparent)]
+    Header(#[from] HeaderError),
+    #[error(transparent)]
+    Response(#[from] ResponseError),
+    #[error("Response to request {actual} while waiting for {expected}")]
+    CorrelationMismatch { expected: u32, actual: u32 },
+    #[error("No broker could be reached")]
+    NoBrokers,
+    #[error("No known leader for {0}")]
+    NoLeader(TopicPartition),
+    #[error("Broker {0} isn't in the cluster metadata")]
+    UnknownBroker(u32),
+}
+
+impl ClientError {
+    /// Whether the same request may succeed later, over a new connection
+    /// or to a new leader.
+    pub fn is_retriable(&self) -> bool {
+        matches!(
+            self,
+            ClientError::Io(_)
+                | ClientError::Disconnected
+                | ClientError::CorrelationMismatch { .. }
+                | ClientError::NoBrokers
+                | ClientError::NoLeader(_)
+        )
+    }
+}
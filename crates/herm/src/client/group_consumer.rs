@@ -0,0 +1,543 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+use super::{
+    Assignor, Client, ClusterClient, ConsumeError, Consumer, ConsumerConfig, ConsumerRecord,
+    RangeAssignor,
+};
+use crate::protocol::{ErrorCode, TopicPartition};
+use crate::request::{Heartbeat, JoinGroup, LeaveGroup, SyncGroup};
+use crate::response::JoinGroupResponse;
+
+/// How long a member stays in its group without a heartbeat unless
+/// configured otherwise.
+pub const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the coordinator waits for members to rejoin unless configured
+/// otherwise.
+pub const DEFAULT_REBALANCE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often members heartbeat unless configured otherwise, well within the
+/// session timeout.
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupConsumerConfig {
+    /// How partitions are read. Its group is replaced by the group joined.
+    pub consumer: ConsumerConfig,
+    pub session_timeout: Duration,
+    pub rebalance_timeout: Duration,
+    pub heartbeat_interval: Duration,
+}
+
+impl Default for GroupConsumerConfig {
+    fn default() -> Self {
+        GroupConsumerConfig {
+            consumer: ConsumerConfig::default(),
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+            rebalance_timeout: DEFAULT_REBALANCE_TIMEOUT,
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+        }
+    }
+}
+
+/// Told when partitions move to or away from a [`GroupConsumer`].
+pub trait RebalanceListener: Debug + Send + Sync {
+    /// The partitions are no longer read, their offsets were committed if
+    /// the consumer auto-commits.
+    fn on_revoked(&self, _partitions: &[TopicPartition]) {}
+
+    /// The partitions are read from now on, starting where the group
+    /// committed up to.
+    fn on_assigned(&self, _partitions: &[TopicPartition]) {}
+}
+
+/// A [`Consumer`] whose partitions come from a consumer group. It joins the
+/// group on the first poll, and rejoins whenever a background heartbeat
+/// finds the group rebalancing.
+///
+/// The member the coordinator picks as leader assigns the partitions of
+/// the subscribed topics with the first of its assignors every member
+/// supports, [`RangeAssignor`] unless configured otherwise. Every rejoin
+/// revokes all the partitions read before the new assignment comes in,
+/// unless all the assignors are cooperative. Then only the partitions
+/// moving to another member are revoked, and they are assigned to it in
+/// the rebalance that follows.
+///
+/// Only rejoins in [`GroupConsumer::poll`], so it has to be polled within
+/// the rebalance timeout.
+#[derive(Debug)]
+pub struct GroupConsumer<C: ClusterClient = Client> {
+    consumer: Consumer<C>,
+    client: Arc<C>,
+    group: String,
+    topics: Vec<String>,
+    config: GroupConsumerConfig,
+    /// Empty until the coordinator handed one out.
+    member_id: String,
+    /// `None` while not a member of a generation.
+    generation: Option<u32>,
+    /// Set by the heartbeat task once the member has to rejoin.
+    rejoin: Arc<AtomicBool>,
+    heartbeat: Option<JoinHandle<()>>,
+    listener: Option<Box<dyn RebalanceListener>>,
+    /// By preference.
+    assignors: Vec<Box<dyn Assignor>>,
+}
+
+impl<C: ClusterClient> GroupConsumer<C> {
+    /// Consumes `topics` as a member of `group`. Nothing is sent until the
+    /// first poll.
+    pub fn new(
+        client: Arc<C>,
+        group: impl Into<String>,
+        topics: Vec<String>,
+        mut config: GroupConsumerConfig,
+    ) -> Self {
+        let group = group.into();
+        config.consumer.group = Some(group.clone());
+        GroupConsumer {
+            consumer: Consumer::new(client.clone(), config.consumer.clone()),
+            client,
+            group,
+            topics,
+            config,
+            member_id: String::new(),
+            generation: None,
+            rejoin: Arc::new(AtomicBool::new(false)),
+            heartbeat: None,
+            listener: None,
+            assignors: vec![Box::new(RangeAssignor)],
+        }
+    }
+
+    pub fn with_listener(mut self, listener: impl RebalanceListener + 'static) -> Self {
+        self.listener = Some(Box::new(listener));
+        self
+    }
+
+    /// Assignors the member supports, most preferred first.
+    ///
+    /// # Panics
+    ///
+    /// If `assignors` is empty.
+    pub fn with_assignors(mut self, assignors: Vec<Box<dyn Assignor>>) -> Self {
+        assert!(!assignors.is_empty(), "a member needs an assignor");
+        self.assignors = assignors;
+        self
+    }
+
+    /// Id the coordinator knows the member by, `None` before it joined.
+    pub fn member_id(&self) -> Option<&str> {
+        (!self.member_id.is_empty()).then_some(self.member_id.as_str())
+    }
+
+    pub fn generation(&self) -> Option<u32> {
+        self.generation
+    }
+
+    pub fn assignment(&self) -> Vec<TopicPartition> {
+        self.consumer.assignment()
+    }
+
+    /// Next offset [`GroupConsumer::poll`] hands out from `partition`.
+    pub fn position(&self, partition: &TopicPartition) -> Option<u64> {
+        self.consumer.position(partition)
+    }
+
+    /// Joins or rejoins the group when needed, then polls the partitions
+    /// assigned, see [`Consumer::poll`].
+    pub async fn poll(&mut self) -> Result<Vec<ConsumerRecord>, ConsumeError> {
+        if self.generation.is_none() || self.rejoin.load(Ordering::Acquire) {
+            self.rebalance().await?;
+        }
+        self.consumer.poll().await
+    }
+
+    /// See [`Consumer::pause`]. Partitions revoked in a rebalance are
+    /// resumed, assigned ones start out resumed.
+    pub fn pause(&mut self, partitions: &[TopicPartition]) -> Result<(), ConsumeError> {
+        self.consumer.pause(partitions)
+    }
+
+    pub fn resume(&mut self, partitions: &[TopicPartition]) {
+        self.consumer.resume(partitions)
+    }
+
+    pub fn paused(&self) -> Vec<TopicPartition> {
+        self.consumer.paused()
+    }
+
+    /// See [`Consumer::seek`].
+    pub fn seek(&mut self, partition: &TopicPartition, offset: u64) -> Result<(), ConsumeError> {
+        self.consumer.seek(partition, offset)
+    }
+
+    /// See [`Consumer::seek_to_timestamp`]. Only the partitions assigned
+    /// at the time are sought.
+    pub async fn seek_to_timestamp(&mut self, timestamp: u64) -> Result<(), ConsumeError> {
+        self.consumer.seek_to_timestamp(timestamp).await
+    }
+
+    /// Commits the offsets consumed since the last commit.
+    pub async fn commit(&mut self) -> Result<(), ConsumeError> {
+        self.consumer.commit().await
+    }
+
+    /// Commits what was consumed if the consumer auto-commits, and leaves
+    /// the group so the others take over its partitions straight away.
+    pub async fn close(mut self) -> Result<(), ConsumeError> {
+        self.stop_heartbeat();
+        self.generation = None;
+        self.revoke(&self.consumer.assignment()).await?;
+        if self.member_id.is_empty() {
+            return Ok(());
+        }
+
+        let request = LeaveGroup::new(self.group.clone(), std::mem::take(&mut self.member_id))?;
+        let response = self.client.leave_group(request).await?;
+        match response.error {
+            // Already dropped from the group
+            ErrorCode::None | ErrorCode::UnknownMemberId => Ok(()),
+            error => Err(ConsumeError::Broker(error)),
+        }
+    }
+
+    /// Joins the group and reads the partitions it assigns. Eagerly gives
+    /// up all the partitions read so far first, cooperatively only those
+    /// assigned to another member after.
+    async fn rebalance(&mut self) -> Result<(), ConsumeError> {
+        self.stop_heartbeat();
+        self.rejoin.store(false, Ordering::Release);
+        self.generation = None;
+        if !self.is_cooperative() {
+            self.revoke(&self.consumer.assignment()).await?;
+        }
+
+        let mut owned = self.consumer.assignment();
+        let (generation, assignment) = loop {
+            let request = JoinGroup::new(
+                self.group.clone(),
+                self.member_id.clone(),
+                self.config.session_timeout.as_millis() as u32,
+                self.config.rebalance_timeout.as_millis() as u32,
+                self.topics.clone(),
+                self.assignors
+                    .iter()
+                    .map(|assignor| assignor.name().to_string())
+                    .collect(),
+                owned.clone(),
+            )?;
+            let joined = self.client.join_group(request).await?;
+            match joined.error {
+                ErrorCode::None => {}
+                ErrorCode::RebalanceInProgress => continue,
+                ErrorCode::UnknownMemberId => {
+                    // Dropped from the group, others may read what it owns
+                    self.member_id.clear();
+                    self.revoke(&owned).await?;
+                    owned.clear();
+                    continue;
+                }
+                error => return Err(ConsumeError::Broker(error)),
+            }
+            self.member_id = joined.member_id.clone();
+
+            let assignments = if joined.is_leader() {
+                self.assign(&joined).await?
+            } else {
+                vec![]
+            };
+            let request = SyncGroup::new(
+                self.group.clone(),
+                joined.generation,
+                self.member_id.clone(),
+                assignments,
+            )?;
+            let synced = self.client.sync_group(request).await?;
+            match synced.error {
+                ErrorCode::None => break (joined.generation, synced.assignment),
+                ErrorCode::RebalanceInProgress | ErrorCode::IllegalGeneration => continue,
+                ErrorCode::UnknownMemberId => {
+                    self.member_id.clear();
+                    self.revoke(&owned).await?;
+                    owned.clear();
+                    continue;
+                }
+                error => return Err(ConsumeError::Broker(error)),
+            }
+        };
+
+        let revoked: Vec<_> = owned
+            .iter()
+            .filter(|partition| !assignment.contains(partition))
+            .cloned()
+            .collect();
+        if !revoked.is_empty() {
+            self.revoke(&revoked).await?;
+            // Lets the group hand them to their new member
+            self.rejoin.store(true, Ordering::Release);
+        }
+        let assigned: Vec<_> = assignment
+            .into_iter()
+            .filter(|partition| !owned.contains(partition))
+            .collect();
+        self.consumer.incremental_assign(assigned.clone()).await?;
+        self.generation = Some(generation);
+        if let Some(listener) = &self.listener {
+            listener.on_assigned(&assigned);
+        }
+        self.start_heartbeat(generation)?;
+        Ok(())
+    }
+
+    fn is_cooperative(&self) -> bool {
+        self.assignors
+            .iter()
+            .all(|assignor| assignor.is_cooperative())
+    }
+
+    async fn revoke(&mut self, revoked: &[TopicPartition]) -> Result<(), ConsumeError> {
+        if revoked.is_empty() {
+            return Ok(());
+        }
+        if self.config.consumer.auto_commit_interval.is_some() {
+            self.consumer.commit().await?;
+        }
+        self.consumer.incremental_unassign(revoked);
+        if let Some(listener) = &self.listener {
+            listener.on_revoked(revoked);
+        }
+        Ok(())
+    }
+
+    /// Splits the partitions of every subscribed topic among the members
+    /// with the assignor the coordinator picked, as the group leader. A
+    /// cooperative assignor's partitions still owned by another member are
+    /// held back until it gave them up.
+    async fn assign(
+        &self,
+        joined: &JoinGroupResponse,
+    ) -> Result<Vec<(String, Vec<TopicPartition>)>, ConsumeError> {
+        let assignor = self
+            .assignors
+            .iter()
+            .find(|assignor| assignor.name() == joined.assignor)
+            .ok_or(ConsumeError::Broker(ErrorCode::InconsistentGroupProtocol))?;
+        let topics: BTreeSet<&String> = joined
+            .members
+            .iter()
+            .flat_map(|member| &member.topics)
+            .collect();
+        let mut partition_counts = BTreeMap::new();
+        for topic in topics {
+            // Topics that don't exist yet are left unassigned
+            if let Some(count) = self.client.partition_count(topic).await? {
+                partition_counts.insert(topic.clone(), count);
+            }
+        }
+
+        let mut assignments = assignor.assign(&joined.members, &partition_counts);
+        if assignor.is_cooperative() {
+            let owners: BTreeMap<&TopicPartition, &str> = joined
+                .members
+                .iter()
+                .flat_map(|member| {
+                    member
+                        .owned
+                        .iter()
+                        .map(|partition| (partition, member.member_id.as_str()))
+                })
+                .collect();
+            for (member_id, assigned) in &mut assignments {
+                assigned.retain(|partition| {
+                    owners
+                        .get(partition)
+                        .is_none_or(|owner| *owner == member_id)
+                });
+            }
+        }
+        Ok(assignments.into_iter().collect())
+    }
+
+    fn start_heartbeat(&mut self, generation: u32) -> Result<(), ConsumeError> {
+        let request = Heartbeat::new(self.group.clone(), generation, self.member_id.clone())?;
+        let client = self.client.clone();
+        let rejoin = self.rejoin.clone();
+        let interval = self.config.heartbeat_interval;
+        self.heartbeat = Some(tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                match client.group_heartbeat(request.clone()).await {
+                    Ok(response) if response.error.is_ok() => {}
+                    Ok(_) => {
+                        rejoin.store(true, Ordering::Release);
+                        return;
+                    }
+                    // Tried again next time, a session outlasts a few misses
+                    Err(_) => {}
+                }
+            }
+        }));
+        Ok(())
+    }
+
+    fn stop_heartbeat(&mut self) {
+        if let Some(heartbeat) = self.heartbeat.take() {
+            heartbeat.abort();
+        }
+    }
+}
+
+impl<C: ClusterClient> Drop for GroupConsumer<C> {
+    fn drop(&mut self) {
+        self.stop_heartbeat();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::broker::{LogHandler, Server};
+    use crate::client::CooperativeStickyAssignor;
+    use crate::storage::{LogConfig, LogDirs, Placement};
+
+    type Rebalanced = (&'static str, Vec<TopicPartition>);
+
+    /// Every revoke and assign, in order.
+    #[derive(Debug, Default, Clone)]
+    struct Recorder(Arc<Mutex<Vec<Rebalanced>>>);
+
+    impl RebalanceListener for Recorder {
+        fn on_revoked(&self, partitions: &[TopicPartition]) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(("revoked", partitions.to_vec()));
+        }
+
+        fn on_assigned(&self, partitions: &[TopicPartition]) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(("assigned", partitions.to_vec()));
+        }
+    }
+
+    async fn start() -> (String, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = LogDirs::open(
+            [dir.path().to_path_buf()],
+            LogConfig::default(),
+            Placement::default(),
+        );
+        logs.create(&TopicPartition::new("events", 0)).unwrap();
+        logs.create(&TopicPartition::new("events", 1)).unwrap();
+        let handler = LogHandler::new(Arc::new(logs));
+        let server = Server::bind("127.0.0.1:0", handler.clone()).await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        handler.replicas().set_advertised_listener(addr.clone());
+        tokio::spawn(server.run());
+        (addr, dir)
+    }
+
+    fn consumer(addr: &str) -> GroupConsumer {
+        let config = GroupConsumerConfig {
+            consumer: ConsumerConfig {
+                fetch_max_wait: Duration::from_millis(10),
+                ..Default::default()
+            },
+            heartbeat_interval: Duration::from_millis(20),
+            ..Default::default()
+        };
+        let client = Arc::new(Client::new(vec![addr.to_string()]));
+        GroupConsumer::new(client, "billing", vec!["events".to_string()], config)
+    }
+
+    #[tokio::test]
+    async fn test_rebalance() {
+        let (addr, _dir) = start().await;
+        let events = |partition| TopicPartition::new("events", partition);
+        let recorder = Recorder::default();
+        let mut first = consumer(&addr).with_listener(recorder.clone());
+        first.poll().await.unwrap();
+        assert_eq!(first.generation(), Some(1));
+        assert_eq!(first.assignment(), vec![events(0), events(1)]);
+
+        // The first member rejoins on a poll once its heartbeat finds the
+        // group rebalancing
+        let mut second = consumer(&addr);
+        let ((), joined) = tokio::join!(
+            async {
+                while first.generation() != Some(2) {
+                    first.poll().await.unwrap();
+                }
+            },
+            second.poll()
+        );
+        joined.unwrap();
+        assert_eq!(first.assignment(), vec![events(0)]);
+        assert_eq!(second.assignment(), vec![events(1)]);
+        assert_eq!(
+            recorder.0.lock().unwrap().clone(),
+            vec![
+                ("assigned", vec![events(0), events(1)]),
+                ("revoked", vec![events(0), events(1)]),
+                ("assigned", vec![events(0)]),
+            ]
+        );
+
+        // Leaving hands the partitions back
+        second.close().await.unwrap();
+        while first.generation() != Some(3) {
+            first.poll().await.unwrap();
+        }
+        assert_eq!(first.assignment(), vec![events(0), events(1)]);
+    }
+
+    #[tokio::test]
+    async fn test_cooperative_rebalance() {
+        let (addr, _dir) = start().await;
+        let events = |partition| TopicPartition::new("events", partition);
+        let cooperative =
+            || consumer(&addr).with_assignors(vec![Box::new(CooperativeStickyAssignor)]);
+        let recorder = Recorder::default();
+        let mut first = cooperative().with_listener(recorder.clone());
+        first.poll().await.unwrap();
+        assert_eq!(first.assignment(), vec![events(0), events(1)]);
+
+        // The first member gives up only the partition moving, which the
+        // second gets in the rebalance that follows
+        let mut second = cooperative();
+        tokio::join!(
+            async {
+                while first.generation() != Some(3) {
+                    first.poll().await.unwrap();
+                }
+            },
+            async {
+                while second.generation() != Some(3) {
+                    second.poll().await.unwrap();
+                }
+            }
+        );
+        assert_eq!(first.assignment(), vec![events(0)]);
+        assert_eq!(second.assignment(), vec![events(1)]);
+        assert_eq!(
+            recorder.0.lock().unwrap().clone(),
+            vec![
+                ("assigned", vec![events(0), events(1)]),
+                ("revoked", vec![events(1)]),
+                ("assigned", vec![]),
+                ("assigned", vec![]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use super::{ClientError, ClusterClient};
+use crate::protocol::ApiKey;
+use crate::request::{
+    DescribeGroups, Fetch, Heartbeat, JoinGroup, LeaveGroup, ListOffsets, OffsetCommit, Produce,
+    Request, SyncGroup,
+};
+use crate::response::{
+    AdminResponse, DescribeGroupsResponse, FetchResponse, JoinGroupResponse, ListOffsetsResponse,
+    ProduceResponse, Response, SyncGroupResponse,
+};
+
+type Responder = Box<dyn Fn(&Request) -> Result<Response, ClientError> + Send + Sync>;
+
+/// A [`ClusterClient`] answering from a script instead of a cluster, to
+/// unit test code built on producers and consumers without a broker.
+///
+/// Each request is answered by the next response queued for its api key,
+/// or else by the responder set for it. Requests with neither fail with
+/// [`ClientError::Disconnected`]. Every request is recorded, in the order
+/// it was made.
+#[derive(Default)]
+pub struct MockClient {
+    queued: Mutex<HashMap<ApiKey, VecDeque<Result<Response, ClientError>>>>,
+    responders: Mutex<HashMap<ApiKey, Responder>>,
+    partition_counts: Mutex<HashMap<String, u32>>,
+    requests: Mutex<Vec<Request>>,
+}
+
+impl MockClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `topic` exist with `partitions` partitions.
+    pub fn with_topic(self, topic: impl Into<String>, partitions: u32) -> Self {
+        self.partition_counts
+            .lock()
+            .unwrap()
+            .insert(topic.into(), partitions);
+        self
+    }
+
+    /// Answers the next request of the response's api key with `response`.
+    pub fn respond(&self, response: impl Into<Response>) {
+        let response = response.into();
+        self.queue(response.api_key(), Ok(response));
+    }
+
+    /// Fails the next request of `api_key` with `err`.
+    pub fn fail(&self, api_key: ApiKey, err: ClientError) {
+        self.queue(api_key, Err(err));
+    }
+
+    /// Answers every request of `api_key` nothing is queued for with
+    /// whatever `responder` makes of it. The response has to be of the
+    /// same api key.
+    pub fn respond_with(
+        &self,
+        api_key: ApiKey,
+        responder: impl Fn(&Request) -> Result<Response, ClientError> + Send + Sync + 'static,
+    ) {
+        self.responders
+            .lock()
+            .unwrap()
+            .insert(api_key, Box::new(responder));
+    }
+
+    /// Every request made so far, oldest first.
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Requests of `api_key` made so far, oldest first.
+    pub fn requests_of(&self, api_key: ApiKey) -> Vec<Request> {
+        let requests = self.requests.lock().unwrap();
+        requests
+            .iter()
+            .filter(|request| request.api_key() == api_key)
+            .cloned()
+            .collect()
+    }
+
+    fn queue(&self, api_key: ApiKey, response: Result<Response, ClientError>) {
+        self.queued
+            .lock()
+            .unwrap()
+            .entry(api_key)
+            .or_default()
+            .push_back(response);
+    }
+
+    /// Records `request` and finds its answer, `None` for requests that
+    /// get no response.
+    fn call(&self, request: Request) -> Result<Option<Response>, ClientError> {
+        let api_key = request.api_key();
+        self.requests.lock().unwrap().push(request.clone());
+        if !request.expects_response() {
+            return Ok(None);
+        }
+
+        let queued = self
+            .queued
+            .lock()
+            .unwrap()
+            .get_mut(&api_key)
+            .and_then(VecDeque::pop_front);
+        let response = match queued {
+            Some(response) => response?,
+            None => match self.responders.lock().unwrap().get(&api_key) {
+                Some(responder) => responder(&request)?,
+                None => return Err(ClientError::Disconnected),
+            },
+        };
+        assert_eq!(
+            response.api_key(),
+            api_key,
+            "a {} request answered with a {} response",
+            api_key,
+            response.api_key()
+        );
+        Ok(Some(response))
+    }
+}
+
+impl std::fmt::Debug for MockClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockClient")
+            .field("requests", &self.requests.lock().unwrap().len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ClusterClient for MockClient {
+    async fn produce(&self, produce: Produce) -> Result<Option<ProduceResponse>, ClientError> {
+        match self.call(produce.into())? {
+            Some(Response::Produce(response)) => Ok(Some(response)),
+            None => Ok(None),
+            _ => unreachable!("checked on call"),
+        }
+    }
+
+    async fn fetch(&self, fetch: Fetch) -> Result<FetchResponse, ClientError> {
+        match self.call(fetch.into())? {
+            Some(Response::Fetch(response)) => Ok(response),
+            _ => unreachable!("checked on call"),
+        }
+    }
+
+    async fn list_offsets(&self, request: ListOffsets) -> Result<ListOffsetsResponse, ClientError> {
+        match self.call(request.into())? {
+            Some(Response::ListOffsets(response)) => Ok(response),
+            _ => unreachable!("checked on call"),
+        }
+    }
+
+    async fn partition_count(&self, topic: &str) -> Result<Option<u32>, ClientError> {
+        Ok(self.partition_counts.lock().unwrap().get(topic).copied())
+    }
+
+    async fn offset_commit(&self, request: OffsetCommit) -> Result<AdminResponse, ClientError> {
+        match self.call(request.into())? {
+            Some(Response::OffsetCommit(response)) => Ok(response),
+            _ => unreachable!("checked on call"),
+        }
+    }
+
+    async fn describe_groups(
+        &self,
+        request: DescribeGroups,
+    ) -> Result<DescribeGroupsResponse, ClientError> {
+        match self.call(request.into())? {
+            Some(Response::DescribeGroups(response)) => Ok(response),
+            _ => unreachable!("checked on call"),
+        }
+    }
+
+    async fn join_group(&self, request: JoinGroup) -> Result<JoinGroupResponse, ClientError> {
+        match self.call(request.into())? {
+            Some(Response::JoinGroup(response)) => Ok(response),
+            _ => unreachable!("checked on call"),
+        }
+    }
+
+    async fn sync_group(&self, request: SyncGroup) -> Result<SyncGroupResponse, ClientError> {
+        match self.call(request.into())? {
+            Some(Response::SyncGroup(response)) => Ok(response),
+            _ => unreachable!("checked on call"),
+        }
+    }
+
+    async fn group_heartbeat(&self, request: Heartbeat) -> Result<AdminResponse, ClientError> {
+        match self.call(request.into())? {
+            Some(Response::Heartbeat(response)) => Ok(response),
+            _ => unreachable!("checked on call"),
+        }
+    }
+
+    async fn leave_group(&self, request: LeaveGroup) -> Result<AdminResponse, ClientError> {
+        match self.call(request.into())? {
+            Some(Response::LeaveGroup(response)) => Ok(response),
+            _ => unreachable!("checked on call"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::client::{ConsumeError, Consumer, ConsumerConfig, Producer, ProducerConfig};
+    use crate::protocol::TopicPartition;
+    use crate::record::{Record, RecordBatch};
+
+    fn record(value: &'static str) -> Record {
+        Record::new(None, Some(Bytes::from_static(value.as_bytes())))
+    }
+
+    #[tokio::test]
+    async fn test_consumer() {
+        let client = Arc::new(MockClient::new().with_topic("events", 1));
+        let mut batch = RecordBatch::new(vec![record("a"), record("b")]);
+        batch.set_base_offset(5);
+        client.respond(FetchResponse::new(7, vec![batch]));
+        client.fail(ApiKey::Fetch, ClientError::Disconnected);
+
+        let config = ConsumerConfig {
+            fetch_max_wait: Duration::from_millis(10),
+            auto_commit_interval: None,
+            ..Default::default()
+        };
+        let mut consumer = Consumer::new(client.clone(), config);
+        consumer.subscribe("events").await.unwrap();
+        let partition = TopicPartition::new("events", 0);
+        consumer.seek(&partition, 5).unwrap();
+
+        let records = consumer.poll().await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(consumer.position(&partition), Some(7));
+        assert!(matches!(
+            consumer.poll().await,
+            Err(ConsumeError::Client(ClientError::Disconnected))
+        ));
+
+        let offsets: Vec<_> = client
+            .requests_of(ApiKey::Fetch)
+            .into_iter()
+            .map(|request| match request {
+                Request::Fetch(fetch) => fetch.offset(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(offsets, vec![5, 7]);
+        assert_eq!(client.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_producer() {
+        let client = Arc::new(MockClient::new());
+        client.respond(ProduceResponse::new(42));
+        let producer = Producer::new(client.clone(), ProducerConfig::default());
+
+        let delivery = producer.send("events", 3, record("a")).unwrap();
+        producer.flush().await;
+        assert_eq!(delivery.await.unwrap(), 42);
+
+        let requests = client.requests_of(ApiKey::Produce);
+        let [Request::Produce(produce)] = requests.as_slice() else {
+            panic!("expected one produce, got {requests:?}");
+        };
+        assert_eq!((produce.topic(), produce.partition()), ("events", 3));
+    }
+}
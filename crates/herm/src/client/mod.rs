@@ -0,0 +1,41 @@
+mod assignor;
+mod async_client;
+mod cluster_client;
+mod connection;
+mod consumer;
+mod dead_letter;
+mod error;
+mod group_consumer;
+#[cfg(any(test, feature = "testing"))]
+pub mod mock;
+mod observer;
+mod partitioner;
+mod producer;
+mod retry;
+pub mod sync;
+pub use assignor::{Assignor, CooperativeStickyAssignor, RangeAssignor, RoundRobinAssignor};
+pub use async_client::Client;
+pub use cluster_client::ClusterClient;
+pub use consumer::{
+    ConsumeError, Consumer, ConsumerConfig, ConsumerRecord, ConsumerStream,
+    DEFAULT_AUTO_COMMIT_INTERVAL, DEFAULT_FETCH_MAX_BYTES, DEFAULT_FETCH_MAX_WAIT,
+};
+pub use dead_letter::{
+    DeadLetterConfig, DeadLetterQueue, ProcessError, Processed, DEFAULT_DLQ_MAX_ATTEMPTS,
+    DEFAULT_DLQ_SUFFIX, DLQ_ATTEMPTS_HEADER, DLQ_ERROR_HEADER, DLQ_OFFSET_HEADER,
+    DLQ_PARTITION_HEADER, DLQ_TOPIC_HEADER,
+};
+pub use error::{ClientError, DEFAULT_CLIENT_ID};
+pub use group_consumer::{
+    GroupConsumer, GroupConsumerConfig, RebalanceListener, DEFAULT_HEARTBEAT_INTERVAL,
+    DEFAULT_REBALANCE_TIMEOUT, DEFAULT_SESSION_TIMEOUT,
+};
+pub use observer::ClientObserver;
+pub use partitioner::{
+    murmur2, KeyHashPartitioner, Partitioner, RoundRobinPartitioner, StickyPartitioner,
+};
+pub use producer::{
+    DeliveryFuture, ProduceError, Producer, ProducerConfig, DEFAULT_BATCH_SIZE, DEFAULT_LINGER,
+    DEFAULT_RETRIES, DEFAULT_RETRY_BACKOFF, UNKNOWN_OFFSET,
+};
+pub use retry::{RetryPolicy, DEFAULT_INITIAL_BACKOFF, DEFAULT_MAX_ATTEMPTS, DEFAULT_MAX_BACKOFF};
@@ -0,0 +1,54 @@
+use std::fmt::Debug;
+use std::time::Duration;
+
+use super::ClientError;
+use crate::protocol::{ApiKey, ErrorCode, TopicPartition};
+
+/// Told what a [`Client`](super::Client) and the producers and consumers
+/// built on it are doing, to export metrics from the application's side.
+/// Every hook does nothing unless overridden, see
+/// [`ClientMetrics`](crate::metrics::ClientMetrics) for one recording them.
+///
+/// Hooks run inline on the task making the request, so they should be
+/// quick.
+pub trait ClientObserver: Debug + Send + Sync {
+    /// A request of `bytes` is about to be written to `broker`.
+    fn on_request_sent(&self, _broker: &str, _api_key: ApiKey, _bytes: usize) {}
+
+    /// `broker` answered with `bytes` and `error`, `latency` after the
+    /// request was sent.
+    fn on_response_received(
+        &self,
+        _broker: &str,
+        _api_key: ApiKey,
+        _bytes: usize,
+        _error: ErrorCode,
+        _latency: Duration,
+    ) {
+    }
+
+    /// A request to `broker` got no response, as it couldn't be sent or
+    /// the connection failed.
+    fn on_request_failed(&self, _broker: &str, _api_key: ApiKey, _err: &ClientError) {}
+
+    /// A request is retried after `backoff`, having failed `failed` times.
+    fn on_retry(&self, _api_key: ApiKey, _failed: u32, _backoff: Duration) {}
+
+    /// A producer batch of `records` and `bytes` was sent to `partition`,
+    /// retries included, which took `latency`.
+    fn on_batch_flushed(
+        &self,
+        _partition: &TopicPartition,
+        _records: usize,
+        _bytes: usize,
+        _latency: Duration,
+        _succeeded: bool,
+    ) {
+    }
+}
+
+/// Observes nothing, what clients start with.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct NoObserver;
+
+impl ClientObserver for NoObserver {}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Mutex;
+
+use super::DEFAULT_BATCH_SIZE;
+use crate::record::Record;
+
+/// Picks the partition of a topic a record is sent to.
+pub trait Partitioner: Debug + Send + Sync {
+    /// One of the `partitions` partitions of `topic`, numbered from 0.
+    fn partition(&self, topic: &str, record: &Record, partitions: u32) -> u32;
+}
+
+/// Kafka's murmur2 hash, so keyed records land on the same partition as
+/// they would with a Kafka client.
+pub fn murmur2(data: &[u8]) -> i32 {
+    const SEED: u32 = 0x9747_b28c;
+    const M: u32 = 0x5bd1_e995;
+    const R: u32 = 24;
+
+    let mut h = SEED ^ data.len() as u32;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M);
+        h ^= k;
+    }
+
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, &byte) in tail.iter().enumerate().rev() {
+            h ^= (byte as u32) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^= h >> 15;
+    h as i32
+}
+
+/// Partition of `key` among `partitions`, as Kafka clients pick it.
+fn key_partition(key: &[u8], partitions: u32) -> u32 {
+    (murmur2(key) & 0x7fff_ffff) as u32 % partitions
+}
+
+/// Sends keyed records to the partition their key hashes to, and keyless
+/// ones to each partition in turn.
+#[derive(Debug, Default)]
+pub struct KeyHashPartitioner {
+    keyless: RoundRobinPartitioner,
+}
+
+impl KeyHashPartitioner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Partitioner for KeyHashPartitioner {
+    fn partition(&self, topic: &str, record: &Record, partitions: u32) -> u32 {
+        match &record.key {
+            Some(key) => key_partition(key, partitions),
+            None => self.keyless.partition(topic, record, partitions),
+        }
+    }
+}
+
+/// Sends records to each partition in turn, keys or not.
+#[derive(Debug, Default)]
+pub struct RoundRobinPartitioner {
+    next: Mutex<HashMap<String, u32>>,
+}
+
+impl RoundRobinPartitioner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Partitioner for RoundRobinPartitioner {
+    fn partition(&self, topic: &str, _: &Record, partitions: u32) -> u32 {
+        let mut next = self.next.lock().unwrap();
+        let next = next.entry(topic.to_string()).or_default();
+        let partition = *next % partitions;
+        *next = next.wrapping_add(1);
+        partition
+    }
+}
+
+/// Sends keyed records to the partition their key hashes to. Keyless ones
+/// stick to a partition until `switch_bytes` of them went to it, then move
+/// on to the next, so they fill whole batches rather than many small ones.
+/// The producer's default.
+#[derive(Debug)]
+pub struct StickyPartitioner {
+    switch_bytes: usize,
+    sticky: Mutex<HashMap<String, Sticky>>,
+}
+
+#[derive(Debug, Default)]
+struct Sticky {
+    partition: u32,
+    bytes: usize,
+}
+
+impl StickyPartitioner {
+    /// Moves on after a batch worth of keyless records.
+    pub fn new() -> Self {
+        Self::with_switch_bytes(DEFAULT_BATCH_SIZE)
+    }
+
+    pub fn with_switch_bytes(switch_bytes: usize) -> Self {
+        StickyPartitioner {
+            switch_bytes,
+            sticky: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for StickyPartitioner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Partitioner for StickyPartitioner {
+    fn partition(&self, topic: &str, record: &Record, partitions: u32) -> u32 {
+        if let Some(key) = &record.key {
+            return key_partition(key, partitions);
+        }
+
+        let mut sticky = self.sticky.lock().unwrap();
+        let sticky = sticky.entry(topic.to_string()).or_default();
+        if sticky.bytes >= self.switch_bytes {
+            sticky.partition = sticky.partition.wrapping_add(1);
+            sticky.bytes = 0;
+        }
+        sticky.bytes += record.size();
+        sticky.partition % partitions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    fn record(key: Option<&'static str>) -> Record {
+        Record::new(
+            key.map(|key| Bytes::from_static(key.as_bytes())),
+            Some(Bytes::from_static(b"value")),
+        )
+    }
+
+    #[test]
+    fn test_murmur2() {
+        // Same as Kafka's own
+        assert_eq!(murmur2(b"21"), -973932308);
+        assert_eq!(murmur2(b"foobar"), -790332482);
+        assert_eq!(murmur2(b"a-little-bit-long-string"), -985981536);
+        assert_eq!(murmur2(b"a-little-bit-longer-string"), -1486304829);
+        assert_eq!(
+            murmur2(b"lkjh234lh9fiuh90y23oiuhsafujhadof229phr9h19h89h8"),
+            -58897971
+        );
+        assert_eq!(murmur2(b"abc"), 479470107);
+    }
+
+    #[test]
+    fn test_partitioners() {
+        let keyed = record(Some("foobar"));
+        let expected = (-790332482i32 & 0x7fff_ffff) as u32 % 6;
+        let partitioner = KeyHashPartitioner::new();
+        assert_eq!(partitioner.partition("events", &keyed, 6), expected);
+        assert_eq!(partitioner.partition("events", &keyed, 6), expected);
+        let keyless: Vec<_> = (0..4)
+            .map(|_| partitioner.partition("events", &record(None), 3))
+            .collect();
+        assert_eq!(keyless, vec![0, 1, 2, 0]);
+
+        let partitioner = RoundRobinPartitioner::new();
+        let picked: Vec<_> = (0..3)
+            .map(|_| partitioner.partition("events", &keyed, 2))
+            .collect();
+        assert_eq!(picked, vec![0, 1, 0]);
+        // Topics are counted apart
+        assert_eq!(partitioner.partition("other", &keyed, 2), 0);
+
+        let partitioner = StickyPartitioner::with_switch_bytes(2 * record(None).size());
+        assert_eq!(partitioner.partition("events", &keyed, 6), expected);
+        let picked: Vec<_> = (0..5)
+            .map(|_| partitioner.partition("events", &record(None), 2))
+            .collect();
+        assert_eq!(picked, vec![0, 0, 1, 1, 0]);
+    }
+}
@@ -0,0 +1,591 @@
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use tokio::sync::{oneshot, Notify};
+
+use super::{Client, ClientError, ClusterClient, Partitioner, StickyPartitioner};
+use crate::chunk::Chunker;
+use crate::protocol::{validate_topic_name, ApiKey, ErrorCode, TopicPartition};
+use crate::record::{Record, RecordBatch};
+use crate::request::{Acks, Produce, ProduceCreationError};
+
+/// Bytes of records a batch holds before it is sent unless configured
+/// otherwise.
+pub const DEFAULT_BATCH_SIZE: usize = 16 * 1024;
+
+/// How long a batch waits for more records unless configured otherwise.
+pub const DEFAULT_LINGER: Duration = Duration::from_millis(5);
+
+/// How often a failed batch is retried unless configured otherwise.
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// How long to wait before retrying a batch unless configured otherwise.
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Offset records sent with [`Acks::None`] resolve to, as the broker never
+/// says where they landed.
+pub const UNKNOWN_OFFSET: u64 = u64::MAX;
+
+#[derive(Error, Debug, Clone)]
+pub enum ProduceError {
+    #[error("Broker answered with {0}")]
+    Broker(ErrorCode),
+    #[error(transparent)]
+    Client(Arc<ClientError>),
+    #[error("Producer went away before the record was sent")]
+    Closed,
+}
+
+impl ProduceError {
+    /// Whether sending the batch again may succeed, after a leader moved
+    /// or a connection dropped.
+    fn is_retriable(&self) -> bool {
+        match self {
+            ProduceError::Broker(error) => error.is_retriable(),
+            ProduceError::Client(err) => err.is_retriable(),
+            ProduceError::Closed => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProducerConfig {
+    /// Bytes of records at which a batch is sent without waiting out its
+    /// linger. A larger record is sent in a batch of its own.
+    pub batch_size: usize,
+    /// How long a batch waits for more records after its first.
+    pub linger: Duration,
+    pub acks: Acks,
+    pub retries: u32,
+    pub retry_backoff: Duration,
+}
+
+impl Default for ProducerConfig {
+    fn default() -> Self {
+        ProducerConfig {
+            batch_size: DEFAULT_BATCH_SIZE,
+            linger: DEFAULT_LINGER,
+            acks: Acks::default(),
+            retries: DEFAULT_RETRIES,
+            retry_backoff: DEFAULT_RETRY_BACKOFF,
+        }
+    }
+}
+
+/// Batches records per partition and sends them through a [`Client`], or
+/// any other [`ClusterClient`]. A
+/// batch goes out once it is full or its linger runs out, and each
+/// partition has one batch in flight at a time, so records land in the
+/// order they were sent, retries included.
+///
+/// Batches still lingering when the producer is dropped are sent all the
+/// same, [`Producer::flush`] waits for them.
+#[derive(Debug)]
+pub struct Producer<C = Client> {
+    shared: Arc<Shared<C>>,
+    partitioner: Arc<dyn Partitioner>,
+    chunker: Option<Arc<Mutex<Chunker>>>,
+}
+
+#[derive(Debug)]
+struct Shared<C> {
+    client: Arc<C>,
+    config: ProducerConfig,
+    partitions: Mutex<HashMap<TopicPartition, Accumulator>>,
+    /// Notified whenever a partition runs out of batches to send.
+    idle: Notify,
+}
+
+#[derive(Debug, Default)]
+struct Accumulator {
+    open: Option<Batch>,
+    sealed: VecDeque<Batch>,
+    sending: bool,
+    next_seq: u64,
+}
+
+#[derive(Debug)]
+struct Batch {
+    /// Tells a linger timer whether the batch it was started for is still
+    /// open.
+    seq: u64,
+    records: Vec<Record>,
+    size: usize,
+    waiters: Vec<oneshot::Sender<Result<u64, ProduceError>>>,
+}
+
+impl<C: ClusterClient> Producer<C> {
+    pub fn new(client: Arc<C>, config: ProducerConfig) -> Self {
+        Producer {
+            shared: Arc::new(Shared {
+                client,
+                config,
+                partitions: Mutex::new(HashMap::new()),
+                idle: Notify::new(),
+            }),
+            partitioner: Arc::new(StickyPartitioner::new()),
+            chunker: None,
+        }
+    }
+
+    /// Splits values larger than the chunker's chunk size into records of
+    /// their own, see [`chunk`](crate::chunk). Consumers join them back
+    /// together when configured with a reassembly buffer.
+    pub fn with_chunker(mut self, chunker: Chunker) -> Self {
+        self.chunker = Some(Arc::new(Mutex::new(chunker)));
+        self
+    }
+
+    /// Sets how [`Producer::send_to_topic`] picks partitions, a
+    /// [`StickyPartitioner`] by default.
+    pub fn with_partitioner(mut self, partitioner: impl Partitioner + 'static) -> Self {
+        self.partitioner = Arc::new(partitioner);
+        self
+    }
+
+    /// Sends `record` to the partition of `topic` the partitioner picks
+    /// among those in the client's metadata.
+    pub async fn send_to_topic(
+        &self,
+        topic: &str,
+        record: Record,
+    ) -> Result<DeliveryFuture, ProduceError> {
+        let partitions = self
+            .shared
+            .client
+            .partition_count(topic)
+            .await
+            .map_err(|err| ProduceError::Client(Arc::new(err)))?
+            .ok_or(ProduceError::Broker(ErrorCode::UnknownTopicOrPartition))?;
+        let partition = self.partitioner.partition(topic, &record, partitions);
+        self.send(topic, partition, record)
+            .map_err(|_| ProduceError::Broker(ErrorCode::InvalidTopic))
+    }
+
+    /// Adds `record` to the open batch of the partition, returning a future
+    /// that resolves to its offset once the batch is written. A record split
+    /// into chunks resolves to the offset of its last chunk once all of them
+    /// are written, or to the first error.
+    pub fn send(
+        &self,
+        topic: &str,
+        partition: u32,
+        record: Record,
+    ) -> Result<DeliveryFuture, ProduceCreationError> {
+        if topic.len() > u16::MAX as usize {
+            return Err(ProduceCreationError::TopicTooLong);
+        }
+        validate_topic_name(topic)?;
+
+        let (tx, rx) = oneshot::channel();
+        let partition = TopicPartition::new(topic, partition);
+        let mut records = match &self.chunker {
+            Some(chunker) => chunker.lock().unwrap().split_record(record),
+            None => vec![record],
+        };
+
+        let mut partitions = self.shared.partitions.lock().unwrap();
+        let accumulator = partitions.entry(partition.clone()).or_default();
+        if records.len() == 1 {
+            self.append(&partition, accumulator, records.pop().unwrap(), tx);
+            return Ok(DeliveryFuture(rx));
+        }
+
+        // Chunks are appended together, so nothing lands between them
+        let chunks: Vec<_> = records
+            .into_iter()
+            .map(|record| {
+                let (tx, rx) = oneshot::channel();
+                self.append(&partition, accumulator, record, tx);
+                DeliveryFuture(rx)
+            })
+            .collect();
+        tokio::spawn(async move {
+            let mut result = Err(ProduceError::Closed);
+            for chunk in chunks {
+                result = chunk.await;
+                if result.is_err() {
+                    break;
+                }
+            }
+            let _ = tx.send(result);
+        });
+        Ok(DeliveryFuture(rx))
+    }
+
+    fn append(
+        &self,
+        partition: &TopicPartition,
+        accumulator: &mut Accumulator,
+        record: Record,
+        tx: oneshot::Sender<Result<u64, ProduceError>>,
+    ) {
+        let size = record.size();
+        let batch_size = self.shared.config.batch_size;
+        if accumulator
+            .open
+            .as_ref()
+            .is_some_and(|open| open.size + size > batch_size)
+        {
+            self.shared.seal(partition, accumulator);
+        }
+        if accumulator.open.is_none() {
+            let seq = accumulator.next_seq;
+            accumulator.next_seq += 1;
+            accumulator.open = Some(Batch {
+                seq,
+                records: vec![],
+                size: 0,
+                waiters: vec![],
+            });
+            tokio::spawn(self.shared.clone().linger(partition.clone(), seq));
+        }
+
+        let open = accumulator.open.as_mut().unwrap();
+        open.records.push(record);
+        open.size += size;
+        open.waiters.push(tx);
+        if open.size >= batch_size {
+            self.shared.seal(partition, accumulator);
+        }
+    }
+
+    /// Sends every open batch without waiting out its linger, and waits
+    /// until nothing is left in flight.
+    pub async fn flush(&self) {
+        {
+            let mut partitions = self.shared.partitions.lock().unwrap();
+            for (partition, accumulator) in partitions.iter_mut() {
+                self.shared.seal(partition, accumulator);
+            }
+        }
+
+        loop {
+            let idle = self.shared.idle.notified();
+            tokio::pin!(idle);
+            idle.as_mut().enable();
+            let busy = {
+                let partitions = self.shared.partitions.lock().unwrap();
+                partitions.values().any(|accumulator| accumulator.sending)
+            };
+            if !busy {
+                return;
+            }
+            idle.await;
+        }
+    }
+}
+
+// Derived, it would want `C: Clone`
+impl<C> Clone for Producer<C> {
+    fn clone(&self) -> Self {
+        Producer {
+            shared: self.shared.clone(),
+            partitioner: self.partitioner.clone(),
+            chunker: self.chunker.clone(),
+        }
+    }
+}
+
+impl<C: ClusterClient> Shared<C> {
+    /// Queues the open batch of `partition` to be sent, if there is one.
+    fn seal(self: &Arc<Self>, partition: &TopicPartition, accumulator: &mut Accumulator) {
+        let Some(batch) = accumulator.open.take() else {
+            return;
+        };
+        accumulator.sealed.push_back(batch);
+        if !accumulator.sending {
+            accumulator.sending = true;
+            tokio::spawn(self.clone().drain(partition.clone()));
+        }
+    }
+
+    async fn linger(self: Arc<Self>, partition: TopicPartition, seq: u64) {
+        tokio::time::sleep(self.config.linger).await;
+        let mut partitions = self.partitions.lock().unwrap();
+        if let Some(accumulator) = partitions.get_mut(&partition) {
+            if accumulator
+                .open
+                .as_ref()
+                .is_some_and(|open| open.seq == seq)
+            {
+                self.seal(&partition, accumulator);
+            }
+        }
+    }
+
+    /// Sends the sealed batches of `partition` one after the other until
+    /// none are left.
+    async fn drain(self: Arc<Self>, partition: TopicPartition) {
+        loop {
+            let batch = {
+                let mut partitions = self.partitions.lock().unwrap();
+                let accumulator = partitions.get_mut(&partition).unwrap();
+                match accumulator.sealed.pop_front() {
+                    Some(batch) => batch,
+                    None => {
+                        accumulator.sending = false;
+                        if accumulator.open.is_none() {
+                            partitions.remove(&partition);
+                        }
+                        break;
+                    }
+                }
+            };
+
+            let (records, started) = (batch.records.len(), Instant::now());
+            let result = self.send_batch(&partition, batch.records).await;
+            self.client.observer().on_batch_flushed(
+                &partition,
+                records,
+                batch.size,
+                started.elapsed(),
+                result.is_ok(),
+            );
+            for (delta, waiter) in batch.waiters.into_iter().enumerate() {
+                let offset = result.clone().map(|base_offset| match base_offset {
+                    UNKNOWN_OFFSET => UNKNOWN_OFFSET,
+                    base_offset => base_offset + delta as u64,
+                });
+                let _ = waiter.send(offset);
+            }
+        }
+        self.idle.notify_waiters();
+    }
+
+    /// Produces `records`, retrying as configured, returning the offset the
+    /// first one landed at.
+    async fn send_batch(
+        &self,
+        partition: &TopicPartition,
+        records: Vec<Record>,
+    ) -> Result<u64, ProduceError> {
+        let produce = Produce::new(
+            partition.topic.clone(),
+            partition.partition,
+            RecordBatch::new(records),
+        )
+        .expect("topic was validated on send")
+        .with_acks(self.config.acks);
+
+        let mut attempt = 0;
+        loop {
+            let err = match self.client.produce(produce.clone()).await {
+                Ok(None) => return Ok(UNKNOWN_OFFSET),
+                Ok(Some(response)) if response.error.is_ok() => return Ok(response.base_offset),
+                Ok(Some(response)) => ProduceError::Broker(response.error),
+                Err(err) => ProduceError::Client(Arc::new(err)),
+            };
+            if attempt >= self.config.retries || !err.is_retriable() {
+                return Err(err);
+            }
+            attempt += 1;
+            self.client
+                .observer()
+                .on_retry(ApiKey::Produce, attempt, self.config.retry_backoff);
+            tokio::time::sleep(self.config.retry_backoff).await;
+        }
+    }
+}
+
+/// Resolves to the offset a record was written at, once its batch is.
+#[derive(Debug)]
+pub struct DeliveryFuture(oneshot::Receiver<Result<u64, ProduceError>>);
+
+impl Future for DeliveryFuture {
+    type Output = Result<u64, ProduceError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0)
+            .poll(cx)
+            .map(|result| result.unwrap_or(Err(ProduceError::Closed)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::broker::{LogHandler, Server};
+    use crate::client::{KeyHashPartitioner, RetryPolicy};
+    use crate::metrics::ClientMetrics;
+    use crate::request::Fetch;
+    use crate::storage::{LogConfig, LogDirs, Placement};
+
+    async fn start() -> (Arc<Client>, tempfile::TempDir) {
+        let (addr, dir) = serve().await;
+        (Arc::new(Client::new(vec![addr])), dir)
+    }
+
+    async fn serve() -> (String, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = LogDirs::open(
+            [dir.path().to_path_buf()],
+            LogConfig::default(),
+            Placement::default(),
+        );
+        logs.create(&TopicPartition::new("events", 0)).unwrap();
+        logs.create(&TopicPartition::new("events", 1)).unwrap();
+        let handler = LogHandler::new(Arc::new(logs));
+        let server = Server::bind("127.0.0.1:0", handler.clone()).await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        handler.replicas().set_advertised_listener(addr.clone());
+        tokio::spawn(server.run());
+        (addr, dir)
+    }
+
+    fn record(value: &'static str) -> Record {
+        Record::new(None, Some(Bytes::from_static(value.as_bytes())))
+    }
+
+    async fn batches(client: &Client) -> Vec<usize> {
+        let fetch = Fetch::new("events".to_string(), 0, 0, 1024 * 1024).unwrap();
+        let response = client.fetch(fetch).await.unwrap();
+        response
+            .batches
+            .iter()
+            .map(|batch| batch.records.len())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_linger() {
+        let (client, _dir) = start().await;
+        let producer = Producer::new(
+            client.clone(),
+            ProducerConfig {
+                linger: Duration::from_millis(20),
+                ..Default::default()
+            },
+        );
+
+        let sent: Vec<_> = ["a", "b", "c"]
+            .into_iter()
+            .map(|value| producer.send("events", 0, record(value)).unwrap())
+            .collect();
+        for (offset, delivery) in sent.into_iter().enumerate() {
+            assert_eq!(delivery.await.unwrap(), offset as u64);
+        }
+        assert_eq!(batches(&client).await, vec![3]);
+    }
+
+    #[tokio::test]
+    async fn test_batch_size() {
+        let (client, _dir) = start().await;
+        let producer = Producer::new(
+            client.clone(),
+            ProducerConfig {
+                batch_size: 2 * record("a").size(),
+                linger: Duration::from_secs(60),
+                ..Default::default()
+            },
+        );
+
+        let full: Vec<_> = ["a", "b", "c"]
+            .into_iter()
+            .map(|value| producer.send("events", 0, record(value)).unwrap())
+            .collect();
+        let mut full = full.into_iter();
+        assert_eq!(full.next().unwrap().await.unwrap(), 0);
+        assert_eq!(full.next().unwrap().await.unwrap(), 1);
+
+        // The third is left lingering until flushed
+        producer.flush().await;
+        assert_eq!(full.next().unwrap().await.unwrap(), 2);
+        assert_eq!(batches(&client).await, vec![2, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_send_to_topic() {
+        let (client, _dir) = start().await;
+        let producer = Producer::new(client.clone(), ProducerConfig::default())
+            .with_partitioner(KeyHashPartitioner::new());
+
+        // Of 2 partitions, foobar hashes to 0 and abc to 1
+        let keyed = |key: &'static str| Record::new(Some(Bytes::from_static(key.as_bytes())), None);
+        for key in ["foobar", "abc", "abc"] {
+            let delivery = producer.send_to_topic("events", keyed(key)).await.unwrap();
+            delivery.await.unwrap();
+        }
+        assert_eq!(batches(&client).await, vec![1]);
+
+        let err = producer.send_to_topic("missing", keyed("abc")).await;
+        assert!(matches!(
+            err,
+            Err(ProduceError::Broker(ErrorCode::UnknownTopicOrPartition))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let (client, _dir) = start().await;
+        let producer = Producer::new(
+            client,
+            ProducerConfig {
+                linger: Duration::ZERO,
+                retries: 2,
+                retry_backoff: Duration::from_millis(1),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            producer.send("bad topic", 0, record("a")).unwrap_err(),
+            ProduceCreationError::InvalidTopicName(crate::protocol::InvalidTopicName::IllegalChar(
+                ' '
+            ))
+        );
+        let err = producer.send("missing", 0, record("a")).unwrap().await;
+        assert!(
+            matches!(err, Err(ProduceError::Client(err)) if matches!(*err, ClientError::NoLeader(_)))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_observer() {
+        let (addr, _dir) = serve().await;
+        let metrics = Arc::new(ClientMetrics::new());
+        let client = Client::new(vec![addr]).with_observer(metrics.clone());
+        let producer = Producer::new(Arc::new(client), ProducerConfig::default());
+        let sent: Vec<_> = ["a", "b", "c"]
+            .into_iter()
+            .map(|value| producer.send("events", 0, record(value)).unwrap())
+            .collect();
+        producer.flush().await;
+        for delivery in sent {
+            delivery.await.unwrap();
+        }
+
+        let snapshot = metrics.snapshot();
+        let produce = snapshot.api(ApiKey::Produce);
+        assert_eq!(produce.requests, 1);
+        assert_eq!(produce.errors, 0);
+        assert!(produce.bytes_out > 0 && produce.bytes_in > 0);
+        assert_eq!(produce.latency_us.count, 1);
+        assert_eq!(snapshot.api(ApiKey::Metadata).requests, 1);
+        assert_eq!(snapshot.batches, 1);
+        assert_eq!(snapshot.batch_records.max, 3);
+        assert_eq!(snapshot.retries, 0);
+
+        // Nothing listens on port 1, so every attempt fails to connect
+        let metrics = Arc::new(ClientMetrics::new());
+        let client = Client::new(vec!["127.0.0.1:1".to_string()])
+            .with_retry_policy(RetryPolicy {
+                max_attempts: 2,
+                initial_backoff: Duration::ZERO,
+                ..Default::default()
+            })
+            .with_observer(metrics.clone());
+        let fetch = Fetch::new("events".to_string(), 0, 0, 1024).unwrap();
+        assert!(client.fetch(fetch).await.is_err());
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.retries, 1);
+        assert_eq!(snapshot.api(ApiKey::Metadata).errors, 2);
+    }
+}
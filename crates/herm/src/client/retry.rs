@@ -0,0 +1,110 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::{Duration, Instant};
+
+/// Attempts a call gets unless configured otherwise, the first included.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Backoff before the first retry unless configured otherwise.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Longest backoff between retries unless configured otherwise.
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+/// How the client retries produces, fetches, offset lookups and metadata
+/// requests that failed in a way that may pass, see [`crate::protocol::ErrorCode::is_retriable`]
+/// and [`super::ClientError::is_retriable`]. The last failure is returned
+/// once the attempts run out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts a call gets, the first included, so 1 never retries.
+    pub max_attempts: u32,
+    /// Backoff before the first retry, doubling with each retry after.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Share of each backoff randomly taken off, from 0 to 1, so clients
+    /// failing together don't retry together.
+    pub jitter: f64,
+}
+
+impl RetryPolicy {
+    /// Fails on the first error.
+    pub fn never() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Whether a call that failed `attempts` times gets another.
+    pub fn should_retry(&self, attempts: u32) -> bool {
+        attempts < self.max_attempts
+    }
+
+    /// How long to wait before retry number `retry`, counted from 1.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let doublings = retry.saturating_sub(1).min(31);
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(1 << doublings)
+            .min(self.max_backoff);
+        backoff.mul_f64(1.0 - self.jitter.clamp(0.0, 1.0) * random())
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            jitter: 0.2,
+        }
+    }
+}
+
+/// Uniform in `[0, 1)`, good enough to spread retries.
+fn random() -> f64 {
+    // Every RandomState is keyed differently
+    let hash = RandomState::new().hash_one(Instant::now());
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            jitter: 0.0,
+            ..Default::default()
+        };
+        let backoffs: Vec<_> = (1..=6).map(|retry| policy.backoff(retry)).collect();
+        let millis = Duration::from_millis;
+        assert_eq!(
+            backoffs,
+            vec![
+                millis(100),
+                millis(200),
+                millis(400),
+                millis(800),
+                millis(1000),
+                millis(1000)
+            ]
+        );
+        assert_eq!(policy.backoff(u32::MAX), millis(1000));
+        assert!(policy.should_retry(2));
+        assert!(!policy.should_retry(3));
+        assert!(!RetryPolicy::never().should_retry(1));
+
+        let policy = RetryPolicy {
+            jitter: 0.5,
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            let backoff = policy.backoff(2);
+            assert!(backoff > millis(100) && backoff <= millis(200));
+        }
+    }
+}
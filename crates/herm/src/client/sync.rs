@@ -0,0 +1,199 @@
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use bytes::{Bytes, BytesMut};
+
+use super::{ClientError, DEFAULT_CLIENT_ID};
+use crate::broker::DEFAULT_MAX_FRAME_SIZE;
+use crate::protocol::{RequestHeader, ResponseHeader};
+use crate::request::{Fetch, Metadata, Produce, Request};
+use crate::response::{FetchResponse, MetadataResponse, ProduceResponse, Response};
+
+/// A blocking connection to one broker, for tools and applications that
+/// don't run an async runtime. Requests go one at a time, each blocking
+/// until its response arrives. Errors the broker answers with are left in
+/// the responses.
+#[derive(Debug)]
+pub struct Client {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+    client_id: String,
+    correlation_id: u32,
+}
+
+impl Client {
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self, ClientError> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Client {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+            client_id: DEFAULT_CLIENT_ID.to_string(),
+            correlation_id: 0,
+        })
+    }
+
+    /// Sets the client id sent with requests, which brokers log and apply
+    /// quotas by.
+    pub fn with_client_id(mut self, client_id: impl Into<String>) -> Self {
+        self.client_id = client_id.into();
+        self
+    }
+
+    /// Fails reads and writes that block for longer than `timeout`, or never
+    /// when `None`. A timed out connection should be dropped, a late response
+    /// would be taken for the next request's.
+    pub fn set_timeout(&self, timeout: Option<Duration>) -> Result<(), ClientError> {
+        let stream = self.writer.get_ref();
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+        Ok(())
+    }
+
+    pub fn fetch(&mut self, fetch: Fetch) -> Result<FetchResponse, ClientError> {
+        match self.call(fetch.into())? {
+            Some(Response::Fetch(response)) => Ok(response),
+            _ => unreachable!("a fetch is answered with a fetch response"),
+        }
+    }
+
+    /// Returns `None` for [`Acks::None`](crate::request::Acks::None)
+    /// produces, which aren't answered.
+    pub fn produce(&mut self, produce: Produce) -> Result<Option<ProduceResponse>, ClientError> {
+        match self.call(produce.into())? {
+            Some(Response::Produce(response)) => Ok(Some(response)),
+            None => Ok(None),
+            _ => unreachable!("a produce is answered with a produce response"),
+        }
+    }
+
+    pub fn metadata(&mut self, request: Metadata) -> Result<MetadataResponse, ClientError> {
+        match self.call(request.into())? {
+            Some(Response::Metadata(response)) => Ok(response),
+            _ => unreachable!("a Metadata is answered with its own response"),
+        }
+    }
+
+    fn call(&mut self, request: Request) -> Result<Option<Response>, ClientError> {
+        self.correlation_id = self.correlation_id.wrapping_add(1);
+        let header = RequestHeader::new(
+            request.api_key(),
+            self.correlation_id,
+            self.client_id.clone(),
+        )?;
+        let mut buf = BytesMut::with_capacity(header.size() + request.size());
+        header.encode_into(&mut buf);
+        request.encode_into(&mut buf);
+        self.writer.write_all(&(buf.len() as u32).to_be_bytes())?;
+        self.writer.write_all(&buf)?;
+        self.writer.flush()?;
+        if !request.expects_response() {
+            return Ok(None);
+        }
+
+        let mut frame = read_frame(&mut self.reader)?.ok_or(ClientError::Disconnected)?;
+        let header = ResponseHeader::decode(&mut frame)?;
+        if header.correlation_id != self.correlation_id {
+            return Err(ClientError::CorrelationMismatch {
+                expected: self.correlation_id,
+                actual: header.correlation_id,
+            });
+        }
+        Ok(Some(Response::decode(request.api_key(), frame)?))
+    }
+}
+
+/// Reads one length prefixed frame, `None` if the broker closed the
+/// connection between frames.
+fn read_frame(reader: &mut impl Read) -> io::Result<Option<Bytes>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len > DEFAULT_MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "frame of {} bytes is over the {} byte limit",
+                len, DEFAULT_MAX_FRAME_SIZE
+            ),
+        ));
+    }
+
+    // Grows as bytes arrive, a length is no reason to allocate
+    let mut frame = Vec::new();
+    reader.take(len as u64).read_to_end(&mut frame)?;
+    if frame.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(Some(Bytes::from(frame)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::broker::{LogHandler, Server};
+    use crate::protocol::{ErrorCode, TopicPartition};
+    use crate::record::{Record, RecordBatch};
+    use crate::request::Acks;
+    use crate::storage::{LogConfig, LogDirs, Placement};
+
+    fn batch(values: &[&'static str]) -> RecordBatch {
+        RecordBatch::new(
+            values
+                .iter()
+                .map(|value| Record::new(None, Some(Bytes::from_static(value.as_bytes()))))
+                .collect(),
+        )
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_client() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = LogDirs::open(
+            [dir.path().to_path_buf()],
+            LogConfig::default(),
+            Placement::default(),
+        );
+        logs.create(&TopicPartition::new("events", 0)).unwrap();
+        let server = Server::bind("127.0.0.1:0", LogHandler::new(Arc::new(logs)))
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        tokio::task::spawn_blocking(move || {
+            let mut client = Client::connect(addr).unwrap().with_client_id("test");
+            client.set_timeout(Some(Duration::from_secs(5))).unwrap();
+
+            let produce = Produce::new("events".to_string(), 0, batch(&["a", "b"])).unwrap();
+            let response = client.produce(produce).unwrap().unwrap();
+            assert_eq!(response.error, ErrorCode::None);
+            assert_eq!(response.base_offset, 0);
+
+            let response = client
+                .fetch(Fetch::new("events".to_string(), 0, 0, 1024 * 1024).unwrap())
+                .unwrap();
+            assert_eq!(response.error, ErrorCode::None);
+            assert_eq!(response.high_watermark, 2);
+
+            let response = client.metadata(Metadata::all()).unwrap();
+            assert_eq!(response.topics[0].topic, "events");
+
+            let produce = Produce::new("events".to_string(), 0, batch(&["c"])).unwrap();
+            assert!(client
+                .produce(produce.with_acks(Acks::None))
+                .unwrap()
+                .is_none());
+        })
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::Instrument;
+
+use super::Cluster;
+use crate::protocol::ErrorCode;
+use crate::replication::{BrokerConnection, ReplicaManager, ReplicationError};
+use crate::request::BrokerHeartbeat;
+
+/// Background tasks sending a [`BrokerHeartbeat`] to every peer of the
+/// [`Cluster`] each heartbeat interval, with the partitions the
+/// [`ReplicaManager`] leads. A peer that can't be reached is retried on the
+/// next beat. Stops when dropped.
+#[derive(Debug)]
+pub struct HeartbeatTask {
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl HeartbeatTask {
+    pub fn spawn(cluster: Arc<Cluster>, replicas: Arc<ReplicaManager>) -> Self {
+        let handles = cluster
+            .config()
+            .peers
+            .iter()
+            .map(|(&peer_id, addr)| {
+                let span = tracing::info_span!("heartbeat", peer_id, addr = %addr);
+                let beat = beat(cluster.clone(), replicas.clone(), addr.clone());
+                tokio::spawn(beat.instrument(span))
+            })
+            .collect();
+        HeartbeatTask { handles }
+    }
+}
+
+impl Drop for HeartbeatTask {
+    fn drop(&mut self) {
+        for handle in &self.handles {
+            handle.abort();
+        }
+    }
+}
+
+async fn beat(cluster: Arc<Cluster>, replicas: Arc<ReplicaManager>, addr: String) {
+    let mut interval = tokio::time::interval(cluster.config().heartbeat_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut connection = None;
+    loop {
+        interval.tick().await;
+        let heartbeat = match BrokerHeartbeat::new(cluster.broker_id(), replicas.leaders()) {
+            Ok(heartbeat) => heartbeat.with_rack(cluster.config().rack.clone()),
+            Err(err) => {
+                tracing::warn!(%err, "cannot build heartbeat");
+                continue;
+            }
+        };
+        match send(&mut connection, &addr, heartbeat, &cluster).await {
+            Ok(ErrorCode::None) => {}
+            Ok(error) => tracing::warn!(%error, "heartbeat refused"),
+            Err(err) => {
+                // Reconnects on the next beat
+                connection = None;
+                tracing::debug!(%err, "heartbeat failed");
+            }
+        }
+    }
+}
+
+/// Sends `heartbeat` over `connection`, connecting first if needed. Bounded
+/// by the session timeout, past which the peer counts as down anyway.
+async fn send(
+    connection: &mut Option<BrokerConnection>,
+    addr: &str,
+    heartbeat: BrokerHeartbeat,
+    cluster: &Cluster,
+) -> Result<ErrorCode, ReplicationError> {
+    let sent = async {
+        if connection.is_none() {
+            *connection = Some(BrokerConnection::connect(addr).await?);
+        }
+        let connection = connection.as_mut().unwrap();
+        Ok(connection.heartbeat(heartbeat).await?.error)
+    };
+    tokio::time::timeout(cluster.config().session_timeout, sent)
+        .await
+        .map_err(|_| ReplicationError::TimedOut)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use crate::broker::{LogHandler, Server};
+    use crate::cluster::ClusterConfig;
+    use crate::protocol::TopicPartition;
+    use crate::replication::ReplicaConfig;
+    use crate::request::Metadata;
+    use crate::response::MetadataResponse;
+    use crate::storage::{LogConfig, LogDirs, Placement};
+
+    fn logs(dir: &tempfile::TempDir) -> Arc<LogDirs> {
+        Arc::new(LogDirs::open(
+            [dir.path()],
+            LogConfig::default(),
+            Placement::default(),
+        ))
+    }
+
+    /// Asks `addr` for metadata until `done` holds.
+    async fn wait_for_metadata(
+        addr: &str,
+        done: impl Fn(&MetadataResponse) -> bool,
+    ) -> MetadataResponse {
+        let mut connection = BrokerConnection::connect(addr).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let metadata = connection.metadata(Metadata::all()).await.unwrap();
+                if done(&metadata) {
+                    return metadata;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_heartbeats() {
+        let (dir, peer_dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let handler = LogHandler::new(logs(&dir)).with_cluster_config(ClusterConfig {
+            broker_id: 0,
+            peers: HashMap::from([(1, "broker-1:9092".to_string())]),
+            session_timeout: Duration::from_millis(200),
+            ..Default::default()
+        });
+        handler
+            .logs()
+            .create(&TopicPartition::new("events", 0))
+            .unwrap();
+        let server = Server::bind("127.0.0.1:0", handler.clone()).await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        handler.replicas().set_advertised_listener(addr.clone());
+        tokio::spawn(server.run());
+
+        // Broker 1 only heartbeats, leading a partition of its own
+        let peer_logs = logs(&peer_dir);
+        let led = peer_logs.create(&TopicPartition::new("events", 1)).unwrap();
+        led.write().unwrap().set_leader_epoch(2).unwrap();
+        let cluster = Arc::new(Cluster::new(ClusterConfig {
+            broker_id: 1,
+            peers: HashMap::from([(0, addr.clone())]),
+            heartbeat_interval: Duration::from_millis(20),
+            ..Default::default()
+        }));
+        let replicas = Arc::new(ReplicaManager::new(peer_logs, ReplicaConfig::default()));
+        let heartbeats = HeartbeatTask::spawn(cluster, replicas);
+
+        let metadata = wait_for_metadata(&addr, |metadata| metadata.brokers.len() == 2).await;
+        assert_eq!(metadata.leader("events", 0).unwrap().address, addr);
+        assert_eq!(
+            metadata.leader("events", 1).unwrap().address,
+            "broker-1:9092"
+        );
+        assert_eq!(metadata.topics[0].partitions[1].leader_epoch, 2);
+
+        // Broker 1 drops out once it goes quiet
+        drop(heartbeats);
+        let metadata = wait_for_metadata(&addr, |metadata| metadata.brokers.len() == 1).await;
+        assert_eq!(metadata.leader("events", 1), None);
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::protocol::{ErrorCode, TopicPartition};
+use crate::request::BrokerHeartbeat;
+use crate::response::{
+    BrokerDescription, BrokerMetadata, DescribeClusterResponse, MetadataResponse,
+    PartitionMetadata, TopicMetadata,
+};
+
+/// How often brokers heartbeat their peers unless configured otherwise.
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long a peer counts as alive after its last heartbeat unless
+/// configured otherwise.
+pub const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterConfig {
+    pub broker_id: u32,
+    /// Rack this broker is in, sent along with its heartbeats.
+    pub rack: Option<String>,
+    /// The other brokers by id, with the address they are reached at.
+    pub peers: HashMap<u32, String>,
+    pub heartbeat_interval: Duration,
+    pub session_timeout: Duration,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        ClusterConfig {
+            broker_id: 0,
+            rack: None,
+            peers: HashMap::new(),
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+        }
+    }
+}
+
+/// A broker's view of the cluster. Membership is static: every broker is
+/// configured with the same peers, and only those are let in. Peers
+/// heartbeat each other with the partitions they lead, see
+/// [`HeartbeatTask`](super::HeartbeatTask), and a peer is alive while its
+/// last heartbeat is within the session timeout.
+///
+/// The view is only kept in memory, a restarted broker knows no leaders but
+/// its own until the next round of heartbeats.
+#[derive(Debug)]
+pub struct Cluster {
+    config: ClusterConfig,
+    peers: Mutex<HashMap<u32, Peer>>,
+}
+
+#[derive(Debug, Default)]
+struct Peer {
+    last_heartbeat: Option<Instant>,
+    version: String,
+    rack: Option<String>,
+    leaders: Vec<(TopicPartition, u32)>,
+}
+
+impl Cluster {
+    pub fn new(config: ClusterConfig) -> Self {
+        let peers = config
+            .peers
+            .keys()
+            .map(|&id| (id, Peer::default()))
+            .collect();
+        Cluster {
+            config,
+            peers: Mutex::new(peers),
+        }
+    }
+
+    pub fn broker_id(&self) -> u32 {
+        self.config.broker_id
+    }
+
+    pub fn config(&self) -> &ClusterConfig {
+        &self.config
+    }
+
+    /// Takes in a heartbeat, failing with
+    /// [`ErrorCode::BrokerIdNotRegistered`] for brokers that aren't peers.
+    pub fn record_heartbeat(&self, heartbeat: BrokerHeartbeat) -> Result<(), ErrorCode> {
+        self.record_heartbeat_at(heartbeat, Instant::now())
+    }
+
+    /// Ids of the peers currently alive, in order.
+    pub fn live_peers(&self) -> Vec<u32> {
+        self.live_peers_at(Instant::now())
+    }
+
+    /// The cluster as seen from here, for this broker at `address`, leading
+    /// `leaders`. Lists the partitions of `topics`, or of every topic when
+    /// empty. Partitions claimed by several brokers, as while leadership
+    /// moves, go to the claim with the highest leader epoch.
+    pub fn metadata(
+        &self,
+        address: &str,
+        leaders: Vec<(TopicPartition, u32)>,
+        topics: &[String],
+    ) -> MetadataResponse {
+        self.metadata_at(address, leaders, topics, Instant::now())
+    }
+
+    /// This broker at `address` and the live peers, with the version and
+    /// rack they last heartbeat with.
+    pub fn describe(&self, address: &str) -> DescribeClusterResponse {
+        self.describe_at(address, Instant::now())
+    }
+
+    fn record_heartbeat_at(
+        &self,
+        heartbeat: BrokerHeartbeat,
+        now: Instant,
+    ) -> Result<(), ErrorCode> {
+        let mut peers = self.peers.lock().unwrap();
+        let peer = peers
+            .get_mut(&heartbeat.broker_id())
+            .ok_or(ErrorCode::BrokerIdNotRegistered)?;
+        peer.last_heartbeat = Some(now);
+        peer.version = heartbeat.version().to_string();
+        peer.rack = heartbeat.rack().map(str::to_string);
+        peer.leaders = heartbeat.into_leaders();
+        Ok(())
+    }
+
+    fn live_peers_at(&self, now: Instant) -> Vec<u32> {
+        self.live_ids(&self.peers.lock().unwrap(), now)
+    }
+
+    /// Ids of the peers alive at `now`, in order.
+    fn live_ids(&self, peers: &HashMap<u32, Peer>, now: Instant) -> Vec<u32> {
+        let mut live: Vec<_> = peers
+            .iter()
+            .filter(|(_, peer)| {
+                peer.last_heartbeat
+                    .is_some_and(|last| now.duration_since(last) <= self.config.session_timeout)
+            })
+            .map(|(&id, _)| id)
+            .collect();
+        live.sort_unstable();
+        live
+    }
+
+    fn metadata_at(
+        &self,
+        address: &str,
+        leaders: Vec<(TopicPartition, u32)>,
+        topics: &[String],
+        now: Instant,
+    ) -> MetadataResponse {
+        let mut brokers = vec![BrokerMetadata {
+            id: self.config.broker_id,
+            address: address.to_string(),
+        }];
+        // Topic, then partition, to (leader id, leader epoch)
+        let mut partitions: BTreeMap<String, BTreeMap<u32, (u32, u32)>> = BTreeMap::new();
+        let mut claim = |broker_id, (partition, leader_epoch): (TopicPartition, u32)| {
+            let claimed = partitions
+                .entry(partition.topic)
+                .or_default()
+                .entry(partition.partition)
+                .or_insert((broker_id, leader_epoch));
+            if leader_epoch > claimed.1 {
+                *claimed = (broker_id, leader_epoch);
+            }
+        };
+        for leader in leaders {
+            claim(self.config.broker_id, leader);
+        }
+
+        let peers = self.peers.lock().unwrap();
+        for id in self.live_ids(&peers, now) {
+            let peer = &peers[&id];
+            brokers.push(BrokerMetadata {
+                id,
+                address: self.config.peers[&id].clone(),
+            });
+            for leader in &peer.leaders {
+                claim(id, leader.clone());
+            }
+        }
+
+        let topic_metadata = |topic: &str, partitions: Option<&BTreeMap<u32, (u32, u32)>>| {
+            let Some(partitions) = partitions else {
+                return TopicMetadata {
+                    error: ErrorCode::UnknownTopicOrPartition,
+                    topic: topic.to_string(),
+                    partitions: vec![],
+                };
+            };
+            TopicMetadata {
+                error: ErrorCode::None,
+                topic: topic.to_string(),
+                partitions: partitions
+                    .iter()
+                    .map(
+                        |(&partition, &(leader_id, leader_epoch))| PartitionMetadata {
+                            partition,
+                            leader_id,
+                            leader_epoch,
+                        },
+                    )
+                    .collect(),
+            }
+        };
+        let topics = if topics.is_empty() {
+            partitions
+                .iter()
+                .map(|(topic, partitions)| topic_metadata(topic, Some(partitions)))
+                .collect()
+        } else {
+            topics
+                .iter()
+                .map(|topic| topic_metadata(topic, partitions.get(topic)))
+                .collect()
+        };
+        MetadataResponse::new(brokers, topics)
+    }
+
+    fn describe_at(&self, address: &str, now: Instant) -> DescribeClusterResponse {
+        let mut brokers = vec![BrokerDescription {
+            id: self.config.broker_id,
+            address: address.to_string(),
+            version: crate::VERSION.to_string(),
+            rack: self.config.rack.clone(),
+        }];
+        let peers = self.peers.lock().unwrap();
+        for id in self.live_ids(&peers, now) {
+            let peer = &peers[&id];
+            brokers.push(BrokerDescription {
+                id,
+                address: self.config.peers[&id].clone(),
+                version: peer.version.clone(),
+                rack: peer.rack.clone(),
+            });
+        }
+        DescribeClusterResponse::new(brokers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cluster() -> Cluster {
+        Cluster::new(ClusterConfig {
+            broker_id: 0,
+            peers: HashMap::from([(1, "b1:9092".to_string()), (2, "b2:9092".to_string())]),
+            session_timeout: Duration::from_secs(10),
+            ..Default::default()
+        })
+    }
+
+    fn heartbeat(broker_id: u32, leaders: &[(&str, u32, u32)]) -> BrokerHeartbeat {
+        let leaders = leaders
+            .iter()
+            .map(|&(topic, partition, epoch)| (TopicPartition::new(topic, partition), epoch))
+            .collect();
+        BrokerHeartbeat::new(broker_id, leaders).unwrap()
+    }
+
+    #[test]
+    fn test_liveness() {
+        let cluster = cluster();
+        let start = Instant::now();
+        assert!(cluster.live_peers_at(start).is_empty());
+
+        assert_eq!(
+            cluster.record_heartbeat_at(heartbeat(7, &[]), start),
+            Err(ErrorCode::BrokerIdNotRegistered)
+        );
+        cluster
+            .record_heartbeat_at(heartbeat(2, &[]), start)
+            .unwrap();
+        cluster
+            .record_heartbeat_at(heartbeat(1, &[]), start)
+            .unwrap();
+        assert_eq!(cluster.live_peers_at(start), vec![1, 2]);
+
+        // Broker 1 keeps up, broker 2 goes quiet
+        let later = start + Duration::from_secs(8);
+        cluster
+            .record_heartbeat_at(heartbeat(1, &[]), later)
+            .unwrap();
+        assert_eq!(
+            cluster.live_peers_at(later + Duration::from_secs(5)),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn test_metadata() {
+        let cluster = cluster();
+        let now = Instant::now();
+        cluster
+            .record_heartbeat_at(heartbeat(1, &[("events", 1, 0), ("events", 0, 2)]), now)
+            .unwrap();
+        let own = vec![
+            (TopicPartition::new("events", 0), 1),
+            (TopicPartition::new("logs", 0), 0),
+        ];
+
+        let metadata = cluster.metadata_at("b0:9092", own.clone(), &[], now);
+        let ids: Vec<_> = metadata.brokers.iter().map(|broker| broker.id).collect();
+        assert_eq!(ids, vec![0, 1]);
+        let topics: Vec<_> = metadata.topics.iter().map(|t| t.topic.as_str()).collect();
+        assert_eq!(topics, vec!["events", "logs"]);
+        // The newer epoch wins
+        assert_eq!(metadata.leader("events", 0).unwrap().address, "b1:9092");
+        assert_eq!(metadata.leader("events", 1).unwrap().id, 1);
+        assert_eq!(metadata.leader("logs", 0).unwrap().address, "b0:9092");
+
+        let named = cluster.metadata_at(
+            "b0:9092",
+            own.clone(),
+            &["logs".to_string(), "missing".to_string()],
+            now,
+        );
+        assert_eq!(named.topics.len(), 2);
+        assert_eq!(named.topics[1].error, ErrorCode::UnknownTopicOrPartition);
+
+        // Dead brokers and their partitions drop out
+        let metadata = cluster.metadata_at("b0:9092", own, &[], now + Duration::from_secs(11));
+        assert_eq!(metadata.brokers.len(), 1);
+        assert_eq!(metadata.leader("events", 1), None);
+        assert_eq!(metadata.leader("events", 0).unwrap().id, 0);
+    }
+
+    #[test]
+    fn test_describe() {
+        let cluster = cluster();
+        let now = Instant::now();
+        let heartbeat = heartbeat(2, &[]).with_rack(Some("eu-west-1b".to_string()));
+        cluster.record_heartbeat_at(heartbeat, now).unwrap();
+
+        let described = cluster.describe_at("b0:9092", now);
+        assert_eq!(described.brokers.len(), 2);
+        assert_eq!(described.brokers[0].address, "b0:9092");
+        assert_eq!(described.brokers[0].rack, None);
+        let peer = &described.brokers[1];
+        assert_eq!((peer.id, peer.address.as_str()), (2, "b2:9092"));
+        assert_eq!(peer.version, crate::VERSION);
+        assert_eq!(peer.rack.as_deref(), Some("eu-west-1b"));
+    }
+}
@@ -0,0 +1,4 @@
+mod heartbeat;
+mod membership;
+pub use heartbeat::HeartbeatTask;
+pub use membership::{Cluster, ClusterConfig, DEFAULT_HEARTBEAT_INTERVAL, DEFAULT_SESSION_TIMEOUT};
@@ -0,0 +1,186 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::protocol::TopicPartition;
+
+/// Lifecycle events published by broker subsystems.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    TopicCreated {
+        topic: String,
+        partitions: u32,
+    },
+    TopicDeleted {
+        topic: String,
+    },
+    LeadershipChanged {
+        partition: TopicPartition,
+        leader: u32,
+    },
+    IsrChanged {
+        partition: TopicPartition,
+        isr: Vec<u32>,
+    },
+    SegmentRolled {
+        partition: TopicPartition,
+        base_offset: u64,
+    },
+}
+
+type Filter = Box<dyn Fn(&Event) -> bool + Send>;
+
+struct Subscriber {
+    sender: Sender<Event>,
+    filter: Option<Filter>,
+}
+
+/// Fans events out to every subscriber, so subsystems can react to each
+/// other without holding direct references. Clones share subscribers.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        EventBus::default()
+    }
+
+    pub fn subscribe(&self) -> Subscription {
+        self.add_subscriber(None)
+    }
+
+    /// Subscribes to only the events matching `filter`.
+    pub fn subscribe_filtered(
+        &self,
+        filter: impl Fn(&Event) -> bool + Send + 'static,
+    ) -> Subscription {
+        self.add_subscriber(Some(Box::new(filter)))
+    }
+
+    /// Delivers `event` to every live subscriber. Subscribers whose
+    /// [`Subscription`] was dropped are forgotten.
+    pub fn publish(&self, event: Event) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|subscriber| {
+            if matches!(&subscriber.filter, Some(filter) if !filter(&event)) {
+                return true;
+            }
+            subscriber.sender.send(event.clone()).is_ok()
+        });
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.lock().unwrap().len()
+    }
+
+    fn add_subscriber(&self, filter: Option<Filter>) -> Subscription {
+        let (sender, receiver) = channel();
+        self.subscribers
+            .lock()
+            .unwrap()
+            .push(Subscriber { sender, filter });
+        Subscription { receiver }
+    }
+}
+
+impl std::fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus")
+            .field("subscribers", &self.subscriber_count())
+            .finish()
+    }
+}
+
+/// Receiving end of [`EventBus::subscribe`]. Events queue up until read.
+#[derive(Debug)]
+pub struct Subscription {
+    receiver: Receiver<Event>,
+}
+
+impl Subscription {
+    /// Returns the next queued event without blocking.
+    pub fn try_next(&self) -> Option<Event> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Waits up to `timeout` for the next event.
+    pub fn next_timeout(&self, timeout: Duration) -> Option<Event> {
+        self.receiver.recv_timeout(timeout).ok()
+    }
+
+    /// Drains every queued event.
+    pub fn drain(&self) -> Vec<Event> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn created(topic: &str) -> Event {
+        Event::TopicCreated {
+            topic: topic.to_string(),
+            partitions: 1,
+        }
+    }
+
+    #[test]
+    fn test_publish_to_all_subscribers() {
+        let bus = EventBus::new();
+        let first = bus.subscribe();
+        let second = bus.clone().subscribe();
+
+        bus.publish(created("a"));
+        bus.publish(created("b"));
+
+        assert_eq!(first.drain(), vec![created("a"), created("b")]);
+        assert_eq!(second.drain(), vec![created("a"), created("b")]);
+        assert_eq!(first.try_next(), None);
+    }
+
+    #[test]
+    fn test_filtered_subscription() {
+        let bus = EventBus::new();
+        let rolls = bus.subscribe_filtered(|event| matches!(event, Event::SegmentRolled { .. }));
+
+        let rolled = Event::SegmentRolled {
+            partition: TopicPartition::new("a", 0),
+            base_offset: 10,
+        };
+        bus.publish(created("a"));
+        bus.publish(rolled.clone());
+
+        assert_eq!(rolls.drain(), vec![rolled]);
+    }
+
+    #[test]
+    fn test_dropped_subscribers_are_removed() {
+        let bus = EventBus::new();
+        let kept = bus.subscribe();
+        drop(bus.subscribe());
+        assert_eq!(bus.subscriber_count(), 2);
+
+        bus.publish(created("a"));
+        assert_eq!(bus.subscriber_count(), 1);
+        assert_eq!(
+            kept.next_timeout(Duration::from_millis(10)),
+            Some(created("a"))
+        );
+    }
+
+    #[test]
+    fn test_publish_across_threads() {
+        let bus = EventBus::new();
+        let subscription = bus.subscribe();
+
+        let publisher = bus.clone();
+        std::thread::spawn(move || publisher.publish(created("a")))
+            .join()
+            .unwrap();
+
+        assert_eq!(subscription.try_next(), Some(created("a")));
+    }
+}
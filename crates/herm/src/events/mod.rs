@@ -0,0 +1,2 @@
+mod bus;
+pub use bus::{Event, EventBus, Subscription};
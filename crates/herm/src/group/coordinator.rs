@@ -0,0 +1,743 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tokio::sync::{oneshot, OwnedMutexGuard};
+
+use super::offset_log::{OffsetLog, Offsets};
+use crate::protocol::{ErrorCode, TopicPartition};
+use crate::request::{Heartbeat, JoinGroup, LeaveGroup, SyncGroup};
+use crate::response::{
+    GroupDescription, GroupMember, GroupState, JoinGroupResponse, MemberDescription,
+    PartitionOffset, SyncGroupResponse, UNKNOWN_END_OFFSET,
+};
+
+/// File in the first data dir committed offsets are kept in.
+pub const OFFSETS_FILE: &str = "consumer-offsets";
+
+/// Keeps the consumer groups that commit offsets to this broker. Every
+/// broker coordinates the groups its clients commit to, there is no
+/// assignment of groups to brokers.
+///
+/// Every commit is appended to the offsets file before it is acknowledged,
+/// and the file is compacted down to the latest offsets now and then. The
+/// writes run on the blocking pool, one commit at a time.
+///
+/// Members are kept in memory only. A rebalance starts whenever a member
+/// joins, leaves or misses its session timeout. The coordinator then holds
+/// every JoinGroup until all known members rejoined, or the longest
+/// rebalance timeout among them passed, dropping those that didn't. The
+/// members then wait in SyncGroup for the leader's assignment, and a leader
+/// that doesn't send one within the rebalance timeout is dropped too.
+#[derive(Debug)]
+pub struct GroupCoordinator {
+    log: Option<Arc<tokio::sync::Mutex<OffsetLog>>>,
+    groups: Mutex<BTreeMap<String, Group>>,
+    /// Keeps the member ids handed out apart from those before a restart.
+    incarnation: u64,
+    next_member: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct Group {
+    offsets: BTreeMap<TopicPartition, u64>,
+    state: GroupState,
+    generation: u32,
+    leader: Option<String>,
+    members: BTreeMap<String, Member>,
+    /// Bumped with every rebalance phase, so the timer of an earlier one
+    /// leaves the group alone.
+    phase: u64,
+}
+
+#[derive(Debug)]
+struct Member {
+    client_id: String,
+    host: String,
+    topics: Vec<String>,
+    /// Assignors the member supports, by preference.
+    assignors: Vec<String>,
+    owned: Vec<TopicPartition>,
+    session_timeout: Duration,
+    rebalance_timeout: Duration,
+    last_heartbeat: Instant,
+    assignment: Vec<TopicPartition>,
+    /// Set while the member waits for the join phase to complete.
+    joining: Option<oneshot::Sender<JoinGroupResponse>>,
+    /// Set while the member waits for the leader's assignment.
+    syncing: Option<oneshot::Sender<SyncGroupResponse>>,
+}
+
+impl Group {
+    fn is_unknown(&self) -> bool {
+        self.offsets.is_empty() && self.members.is_empty()
+    }
+
+    fn all_joined(&self) -> bool {
+        self.members.values().all(|member| member.joining.is_some())
+    }
+
+    /// Drops the members whose session timed out, true if there were any.
+    /// Members waiting on the coordinator can't heartbeat, so they stay.
+    fn expire(&mut self, now: Instant) -> bool {
+        let before = self.members.len();
+        self.members.retain(|_, member| {
+            member.joining.is_some()
+                || member.syncing.is_some()
+                || now.duration_since(member.last_heartbeat) < member.session_timeout
+        });
+        self.members.len() != before
+    }
+
+    fn rebalance_timeout(&self) -> Duration {
+        self.members
+            .values()
+            .map(|member| member.rebalance_timeout)
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+impl Default for GroupCoordinator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GroupCoordinator {
+    /// Keeps offsets in memory only, they are lost on restart.
+    pub fn new() -> Self {
+        Self::with_log(None, BTreeMap::new())
+    }
+
+    fn with_log(log: Option<OffsetLog>, groups: BTreeMap<String, Group>) -> Self {
+        let incarnation = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        GroupCoordinator {
+            log: log.map(|log| Arc::new(tokio::sync::Mutex::new(log))),
+            groups: Mutex::new(groups),
+            incarnation,
+            next_member: AtomicU64::new(0),
+        }
+    }
+
+    /// Loads the offsets committed to the file at `path`, which later
+    /// commits are stored in.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let (log, offsets) = OffsetLog::open(path.into())?;
+        let groups = offsets
+            .into_iter()
+            .map(|(name, offsets)| {
+                let group = Group {
+                    offsets,
+                    ..Default::default()
+                };
+                (name, group)
+            })
+            .collect();
+        Ok(Self::with_log(Some(log), groups))
+    }
+
+    /// Stores the next offsets `group` reads from, creating the group if
+    /// needed. Nothing changes when they can't be written out.
+    pub async fn commit(&self, group: &str, offsets: Vec<(TopicPartition, u64)>) -> io::Result<()> {
+        // Held until the offsets are in memory too, so commits take effect
+        // in the order they were written
+        let _log = match &self.log {
+            Some(log) => Some(
+                self.store(log.clone().lock_owned().await, group, &offsets)
+                    .await?,
+            ),
+            None => None,
+        };
+        let mut groups = self.groups.lock().unwrap();
+        let entry = groups.entry(group.to_string()).or_default();
+        entry.offsets.extend(offsets);
+        Ok(())
+    }
+
+    /// The offsets `group` committed, in partition order.
+    pub fn committed(&self, group: &str) -> Option<Vec<(TopicPartition, u64)>> {
+        let groups = self.groups.lock().unwrap();
+        let found = groups.get(group).filter(|found| !found.is_unknown())?;
+        Some(found.offsets.iter().map(|(p, &o)| (p.clone(), o)).collect())
+    }
+
+    /// Every group, in order.
+    pub fn list(&self) -> Vec<(String, GroupState)> {
+        let groups = self.groups.lock().unwrap();
+        groups
+            .iter()
+            .filter(|(_, group)| !group.is_unknown())
+            .map(|(name, group)| (name.clone(), group.state))
+            .collect()
+    }
+
+    /// Describes `group`, with the lag of each partition against the
+    /// `end_offset` it returns, `None` for partitions not stored here.
+    pub fn describe(
+        &self,
+        group: &str,
+        end_offset: impl Fn(&TopicPartition) -> Option<u64>,
+    ) -> GroupDescription {
+        let mut groups = self.groups.lock().unwrap();
+        let Some(found) = groups.get_mut(group).filter(|found| !found.is_unknown()) else {
+            return GroupDescription::error(group.to_string(), ErrorCode::GroupIdNotFound);
+        };
+        let offsets = found
+            .offsets
+            .iter()
+            .map(|(partition, &committed)| PartitionOffset {
+                end_offset: end_offset(partition).unwrap_or(UNKNOWN_END_OFFSET),
+                topic: partition.topic.clone(),
+                partition: partition.partition,
+                committed,
+            })
+            .collect();
+        let members = found
+            .members
+            .iter()
+            .map(|(member_id, member)| MemberDescription {
+                member_id: member_id.clone(),
+                client_id: member.client_id.clone(),
+                host: member.host.clone(),
+                assignment: member.assignment.clone(),
+            })
+            .collect();
+        GroupDescription {
+            error: ErrorCode::None,
+            group: group.to_string(),
+            state: found.state,
+            members,
+            offsets,
+        }
+    }
+
+    /// Adds the member to the group, or rejoins it, and starts a rebalance.
+    /// Returns once the join phase completed, with the new generation and
+    /// the leader, which alone gets every member's subscription.
+    pub async fn join(
+        self: &Arc<Self>,
+        request: JoinGroup,
+        client_id: &str,
+        host: &str,
+    ) -> JoinGroupResponse {
+        let joined = {
+            let mut groups = self.groups.lock().unwrap();
+            let name = request.group();
+            let group = groups.entry(name.to_string()).or_default();
+            let now = Instant::now();
+            self.check_sessions(name, group, now);
+
+            let member_id = if request.member_id().is_empty() {
+                let next = self.next_member.fetch_add(1, Ordering::Relaxed);
+                format!("{}-{:x}-{}", client_id, self.incarnation, next)
+            } else if group.members.contains_key(request.member_id()) {
+                request.member_id().to_string()
+            } else {
+                if group.is_unknown() {
+                    groups.remove(name);
+                }
+                return JoinGroupResponse::error(ErrorCode::UnknownMemberId);
+            };
+            let supported = |assignor: &String| {
+                group
+                    .members
+                    .iter()
+                    .filter(|(other, _)| **other != member_id)
+                    .all(|(_, other)| other.assignors.contains(assignor))
+            };
+            if !request.assignors().iter().any(supported) {
+                if group.is_unknown() {
+                    groups.remove(name);
+                }
+                return JoinGroupResponse::error(ErrorCode::InconsistentGroupProtocol);
+            }
+
+            let (joining, joined) = oneshot::channel();
+            let member = Member {
+                client_id: client_id.to_string(),
+                host: host.to_string(),
+                topics: request.topics().to_vec(),
+                assignors: request.assignors().to_vec(),
+                owned: request.owned().to_vec(),
+                session_timeout: Duration::from_millis(request.session_timeout_ms() as u64),
+                rebalance_timeout: Duration::from_millis(request.rebalance_timeout_ms() as u64),
+                last_heartbeat: now,
+                assignment: vec![],
+                joining: Some(joining),
+                syncing: None,
+            };
+            if let Some(previous) = group.members.insert(member_id, member) {
+                if let Some(syncing) = previous.syncing {
+                    let _ = syncing.send(SyncGroupResponse::error(ErrorCode::RebalanceInProgress));
+                }
+            }
+
+            if group.state != GroupState::PreparingRebalance {
+                self.prepare_rebalance(name, group);
+            }
+            if group.all_joined() {
+                self.complete_join(name, group);
+            }
+            joined
+        };
+        joined
+            .await
+            .unwrap_or_else(|_| JoinGroupResponse::error(ErrorCode::RebalanceInProgress))
+    }
+
+    /// Hands the member its assignment for the generation. The leader's
+    /// request carries every member's, the others wait for it.
+    pub async fn sync(self: &Arc<Self>, request: SyncGroup) -> SyncGroupResponse {
+        let synced = {
+            let mut groups = self.groups.lock().unwrap();
+            let name = request.group();
+            let Some(group) = groups.get_mut(name) else {
+                return SyncGroupResponse::error(ErrorCode::UnknownMemberId);
+            };
+            let now = Instant::now();
+            self.check_sessions(name, group, now);
+
+            let member_id = request.member_id();
+            let is_leader = group.leader.as_deref() == Some(member_id);
+            let Some(member) = group.members.get_mut(member_id) else {
+                return SyncGroupResponse::error(ErrorCode::UnknownMemberId);
+            };
+            if request.generation() != group.generation {
+                return SyncGroupResponse::error(ErrorCode::IllegalGeneration);
+            }
+            member.last_heartbeat = now;
+            match group.state {
+                GroupState::CompletingRebalance => {}
+                GroupState::Stable => return SyncGroupResponse::new(member.assignment.clone()),
+                GroupState::Empty | GroupState::PreparingRebalance => {
+                    return SyncGroupResponse::error(ErrorCode::RebalanceInProgress)
+                }
+            }
+
+            if !is_leader {
+                let (syncing, synced) = oneshot::channel();
+                member.syncing = Some(syncing);
+                synced
+            } else {
+                let member_id = member_id.to_string();
+                for (assigned, partitions) in request.into_assignments() {
+                    if let Some(member) = group.members.get_mut(&assigned) {
+                        member.assignment = partitions;
+                    }
+                }
+                group.state = GroupState::Stable;
+                group.phase += 1;
+                for member in group.members.values_mut() {
+                    member.last_heartbeat = now;
+                    if let Some(syncing) = member.syncing.take() {
+                        let _ = syncing.send(SyncGroupResponse::new(member.assignment.clone()));
+                    }
+                }
+                return SyncGroupResponse::new(group.members[&member_id].assignment.clone());
+            }
+        };
+        synced
+            .await
+            .unwrap_or_else(|_| SyncGroupResponse::error(ErrorCode::RebalanceInProgress))
+    }
+
+    /// Keeps the member's session alive. Tells it to rejoin when the group
+    /// is rebalancing or moved on to a later generation.
+    pub fn heartbeat(self: &Arc<Self>, request: &Heartbeat) -> ErrorCode {
+        let mut groups = self.groups.lock().unwrap();
+        let name = request.group();
+        let Some(group) = groups.get_mut(name) else {
+            return ErrorCode::UnknownMemberId;
+        };
+        let now = Instant::now();
+        self.check_sessions(name, group, now);
+
+        let Some(member) = group.members.get_mut(request.member_id()) else {
+            return ErrorCode::UnknownMemberId;
+        };
+        member.last_heartbeat = now;
+        if group.state == GroupState::PreparingRebalance {
+            return ErrorCode::RebalanceInProgress;
+        }
+        if request.generation() != group.generation {
+            return ErrorCode::IllegalGeneration;
+        }
+        ErrorCode::None
+    }
+
+    /// Takes the member out of the group, rebalancing the others.
+    pub fn leave(self: &Arc<Self>, request: &LeaveGroup) -> ErrorCode {
+        let mut groups = self.groups.lock().unwrap();
+        let name = request.group();
+        let Some(group) = groups.get_mut(name) else {
+            return ErrorCode::UnknownMemberId;
+        };
+        if group.members.remove(request.member_id()).is_none() {
+            return ErrorCode::UnknownMemberId;
+        }
+        self.members_gone(name, group);
+        ErrorCode::None
+    }
+
+    /// Drops the members whose session timed out and rebalances the rest.
+    fn check_sessions(self: &Arc<Self>, name: &str, group: &mut Group, now: Instant) {
+        if group.expire(now) {
+            self.members_gone(name, group);
+        }
+    }
+
+    /// Moves the group on after members were dropped. A group that is
+    /// rebalancing already may have been waiting on just those.
+    fn members_gone(self: &Arc<Self>, name: &str, group: &mut Group) {
+        if group.members.is_empty() {
+            group.state = GroupState::Empty;
+            group.leader = None;
+            group.phase += 1;
+        } else if group.state != GroupState::PreparingRebalance {
+            self.prepare_rebalance(name, group);
+        } else if group.all_joined() {
+            self.complete_join(name, group);
+        }
+    }
+
+    fn prepare_rebalance(self: &Arc<Self>, name: &str, group: &mut Group) {
+        group.state = GroupState::PreparingRebalance;
+        for member in group.members.values_mut() {
+            member.assignment.clear();
+            if let Some(syncing) = member.syncing.take() {
+                let _ = syncing.send(SyncGroupResponse::error(ErrorCode::RebalanceInProgress));
+            }
+        }
+        self.start_phase(name, group);
+    }
+
+    /// Bumps the generation, picks a leader, keeping the current one if it
+    /// rejoined, and answers the held joins. The leader's first assignor
+    /// that every member supports is the one it assigns with, joins leave
+    /// at least one.
+    fn complete_join(self: &Arc<Self>, name: &str, group: &mut Group) {
+        if group.members.is_empty() {
+            return self.members_gone(name, group);
+        }
+        group.generation = group.generation.wrapping_add(1);
+        let leader = match group.leader.take() {
+            Some(leader) if group.members.contains_key(&leader) => leader,
+            _ => group.members.keys().next().unwrap().clone(),
+        };
+        group.state = GroupState::CompletingRebalance;
+        let assignor = group.members[&leader]
+            .assignors
+            .iter()
+            .find(|assignor| {
+                group
+                    .members
+                    .values()
+                    .all(|member| member.assignors.contains(assignor))
+            })
+            .cloned()
+            .unwrap_or_default();
+
+        let subscriptions: Vec<_> = group
+            .members
+            .iter()
+            .map(|(member_id, member)| GroupMember {
+                member_id: member_id.clone(),
+                topics: member.topics.clone(),
+                owned: member.owned.clone(),
+            })
+            .collect();
+        let now = Instant::now();
+        for (member_id, member) in &mut group.members {
+            member.last_heartbeat = now;
+            let Some(joining) = member.joining.take() else {
+                continue;
+            };
+            let members = if *member_id == leader {
+                subscriptions.clone()
+            } else {
+                vec![]
+            };
+            let _ = joining.send(JoinGroupResponse {
+                error: ErrorCode::None,
+                throttle_time_ms: 0,
+                generation: group.generation,
+                assignor: assignor.clone(),
+                leader_id: leader.clone(),
+                member_id: member_id.clone(),
+                members,
+            });
+        }
+        group.leader = Some(leader);
+        self.start_phase(name, group);
+    }
+
+    /// Starts a rebalance phase, timed out after the longest rebalance
+    /// timeout of the members.
+    fn start_phase(self: &Arc<Self>, name: &str, group: &mut Group) {
+        group.phase += 1;
+        let phase = group.phase;
+        let timeout = group.rebalance_timeout();
+        let coordinator = Arc::clone(self);
+        let name = name.to_string();
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+            coordinator.phase_timed_out(&name, phase);
+        });
+    }
+
+    /// Drops the members that didn't rejoin in time, or the leader that
+    /// didn't send the assignment.
+    fn phase_timed_out(self: &Arc<Self>, name: &str, phase: u64) {
+        let mut groups = self.groups.lock().unwrap();
+        let Some(group) = groups.get_mut(name).filter(|group| group.phase == phase) else {
+            return;
+        };
+        match group.state {
+            GroupState::PreparingRebalance => {
+                group.members.retain(|_, member| member.joining.is_some());
+                self.complete_join(name, group);
+            }
+            GroupState::CompletingRebalance => {
+                if let Some(leader) = group.leader.take() {
+                    group.members.remove(&leader);
+                }
+                self.members_gone(name, group);
+            }
+            GroupState::Empty | GroupState::Stable => {}
+        }
+    }
+
+    /// Appends a commit to the offsets log, or compacts the log with it
+    /// once enough piled up.
+    async fn store(
+        &self,
+        mut log: OwnedMutexGuard<OffsetLog>,
+        group: &str,
+        offsets: &[(TopicPartition, u64)],
+    ) -> io::Result<OwnedMutexGuard<OffsetLog>> {
+        let latest = log.should_compact().then(|| {
+            let groups = self.groups.lock().unwrap();
+            let mut latest: Offsets = groups
+                .iter()
+                .filter(|(_, found)| !found.offsets.is_empty())
+                .map(|(name, found)| (name.clone(), found.offsets.clone()))
+                .collect();
+            latest
+                .entry(group.to_string())
+                .or_default()
+                .extend(offsets.iter().cloned());
+            latest
+        });
+        let group = group.to_string();
+        let offsets = offsets.to_vec();
+        tokio::task::spawn_blocking(move || {
+            match latest {
+                Some(latest) => log.compact(&latest)?,
+                None => log.append(&group, &offsets)?,
+            }
+            Ok(log)
+        })
+        .await
+        .expect("offsets write panicked")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_commit_and_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(OFFSETS_FILE);
+        let coordinator = GroupCoordinator::open(&path).unwrap();
+        assert!(coordinator.list().is_empty());
+
+        let events = |partition| TopicPartition::new("events", partition);
+        coordinator
+            .commit("billing", vec![(events(0), 5), (events(1), 3)])
+            .await
+            .unwrap();
+        coordinator
+            .commit("billing", vec![(events(0), 9)])
+            .await
+            .unwrap();
+        coordinator
+            .commit("search", vec![(events(0), 1)])
+            .await
+            .unwrap();
+
+        let reopened = GroupCoordinator::open(&path).unwrap();
+        assert_eq!(
+            reopened.list(),
+            vec![
+                ("billing".to_string(), GroupState::Empty),
+                ("search".to_string(), GroupState::Empty)
+            ]
+        );
+        assert_eq!(
+            reopened.committed("billing"),
+            Some(vec![(events(0), 9), (events(1), 3)])
+        );
+
+        fs::write(&path, b"\x00\x00\x00\x01").unwrap();
+        assert!(GroupCoordinator::open(&path).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_describe() {
+        let coordinator = GroupCoordinator::new();
+        let events = |partition| TopicPartition::new("events", partition);
+        coordinator
+            .commit("billing", vec![(events(0), 5), (events(1), 3)])
+            .await
+            .unwrap();
+
+        // Only partition 0 is stored here
+        let described = coordinator.describe("billing", |partition| {
+            (partition.partition == 0).then_some(12)
+        });
+        assert_eq!(described.error, ErrorCode::None);
+        assert_eq!(described.offsets[0].lag(), Some(7));
+        assert_eq!(described.offsets[1].lag(), None);
+
+        let missing = coordinator.describe("missing", |_| None);
+        assert_eq!(missing.error, ErrorCode::GroupIdNotFound);
+    }
+
+    fn join(member_id: &str, rebalance_timeout_ms: u32) -> JoinGroup {
+        let topics = vec!["events".to_string()];
+        JoinGroup::new(
+            "billing".to_string(),
+            member_id.to_string(),
+            10_000,
+            rebalance_timeout_ms,
+            topics,
+            vec!["range".to_string()],
+            vec![],
+        )
+        .unwrap()
+    }
+
+    fn sync(
+        generation: u32,
+        member_id: &str,
+        assignments: Vec<(String, Vec<TopicPartition>)>,
+    ) -> SyncGroup {
+        SyncGroup::new(
+            "billing".to_string(),
+            generation,
+            member_id.to_string(),
+            assignments,
+        )
+        .unwrap()
+    }
+
+    fn heartbeat(generation: u32, member_id: &str) -> Heartbeat {
+        Heartbeat::new("billing".to_string(), generation, member_id.to_string()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rebalance() {
+        let coordinator = Arc::new(GroupCoordinator::new());
+        let events = |partition| TopicPartition::new("events", partition);
+
+        // Alone in the group, the first member leads it straight away
+        let first = coordinator.join(join("", 10_000), "app", "10.0.0.1").await;
+        assert_eq!(first.generation, 1);
+        assert!(first.is_leader());
+        let a = first.member_id.clone();
+        let assignments = vec![(a.clone(), vec![events(0), events(1)])];
+        let synced = coordinator.sync(sync(1, &a, assignments)).await;
+        assert_eq!(synced.assignment, vec![events(0), events(1)]);
+        assert_eq!(coordinator.heartbeat(&heartbeat(1, &a)), ErrorCode::None);
+
+        // A second member is held until the first rejoins
+        let second = tokio::spawn({
+            let coordinator = coordinator.clone();
+            async move { coordinator.join(join("", 10_000), "app", "10.0.0.2").await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(
+            coordinator.heartbeat(&heartbeat(1, &a)),
+            ErrorCode::RebalanceInProgress
+        );
+        let first = coordinator.join(join(&a, 100), "app", "10.0.0.1").await;
+        let second = second.await.unwrap();
+        assert_eq!(first.generation, 2);
+        assert_eq!(first.leader_id, a);
+        assert_eq!(first.members.len(), 2);
+        assert!(second.members.is_empty());
+        let b = second.member_id.clone();
+
+        // The follower waits for the leader's assignment
+        let follower = tokio::spawn({
+            let coordinator = coordinator.clone();
+            let request = sync(2, &b, vec![]);
+            async move { coordinator.sync(request).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let assignments = vec![(a.clone(), vec![events(0)]), (b.clone(), vec![events(1)])];
+        let synced = coordinator.sync(sync(2, &a, assignments)).await;
+        assert_eq!(synced.assignment, vec![events(0)]);
+        assert_eq!(follower.await.unwrap().assignment, vec![events(1)]);
+        assert_eq!(
+            coordinator.heartbeat(&heartbeat(1, &b)),
+            ErrorCode::IllegalGeneration
+        );
+
+        let described = coordinator.describe("billing", |_| None);
+        assert_eq!(described.state, GroupState::Stable);
+        let hosts: Vec<_> = described.members.iter().map(|m| m.host.as_str()).collect();
+        assert_eq!(hosts.len(), 2);
+        assert!(hosts.contains(&"10.0.0.2"));
+
+        // Leaving rebalances the rest
+        let leave = LeaveGroup::new("billing".to_string(), b.clone()).unwrap();
+        assert_eq!(coordinator.leave(&leave), ErrorCode::None);
+        assert_eq!(coordinator.leave(&leave), ErrorCode::UnknownMemberId);
+        assert_eq!(
+            coordinator.heartbeat(&heartbeat(2, &a)),
+            ErrorCode::RebalanceInProgress
+        );
+
+        // Members that don't rejoin within the rebalance timeout are dropped
+        let third = coordinator.join(join("", 50), "app", "10.0.0.3").await;
+        assert_eq!(third.generation, 3);
+        assert!(third.is_leader());
+        assert_eq!(
+            coordinator.heartbeat(&heartbeat(3, &a)),
+            ErrorCode::UnknownMemberId
+        );
+        assert_eq!(
+            coordinator.join(join("unknown", 50), "app", "").await.error,
+            ErrorCode::UnknownMemberId
+        );
+
+        // Joining takes an assignor the group supports
+        assert_eq!(third.assignor, "range");
+        let request = JoinGroup::new(
+            "billing".to_string(),
+            String::new(),
+            10_000,
+            50,
+            vec!["events".to_string()],
+            vec!["roundrobin".to_string()],
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(
+            coordinator.join(request, "app", "").await.error,
+            ErrorCode::InconsistentGroupProtocol
+        );
+    }
+}
@@ -0,0 +1,3 @@
+mod coordinator;
+mod offset_log;
+pub use coordinator::{GroupCoordinator, OFFSETS_FILE};
@@ -0,0 +1,316 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::protocol::{get_str, put_str, TopicPartition};
+use crate::record::crc32c;
+
+/// Starts the log, telling it apart from the single snapshot offsets were
+/// kept in before.
+const MAGIC: &[u8] = b"herm-offsets-1\n";
+
+/// Every entry is framed by its length and a crc32c of its bytes, both u32,
+/// like the batches of a segment file.
+const ENTRY_HEADER_SIZE: usize = 4 + 4;
+
+/// Written out in full, then renamed over the log when compacting, so a
+/// crash never leaves a torn file behind.
+const TMP_SUFFIX: &str = ".tmp";
+
+/// Entries appended before the log is rewritten with just the latest
+/// offsets.
+const COMPACT_AFTER: usize = 1000;
+
+/// Committed offsets by group, then partition.
+pub(super) type Offsets = BTreeMap<String, BTreeMap<TopicPartition, u64>>;
+
+/// The file committed offsets are kept in. Each commit appends an entry,
+/// and later entries win over earlier ones for the same partition. Every
+/// so many entries the log is compacted down to one holding the latest
+/// offsets.
+///
+/// An entry is a u32 count, then for each offset its group, topic,
+/// partition and offset. A file from before offsets were appended holds
+/// one such entry without framing, and is rewritten as a log when opened.
+#[derive(Debug)]
+pub(super) struct OffsetLog {
+    path: PathBuf,
+    file: File,
+    len: u64,
+    /// Entries since the log was last compacted.
+    entries: usize,
+}
+
+impl OffsetLog {
+    /// Opens the log at `path` with the offsets it holds, by group. An
+    /// incomplete or torn entry at the end, left by a crash while
+    /// appending, is cut off. Corruption anywhere else is an error.
+    pub(super) fn open(path: PathBuf) -> io::Result<(Self, Offsets)> {
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => Bytes::from(bytes),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Bytes::new(),
+            Err(err) => return Err(err),
+        };
+
+        if !bytes.starts_with(MAGIC) {
+            // Empty, cut short while being created, or an old snapshot
+            let offsets = if MAGIC.starts_with(&bytes) {
+                Offsets::new()
+            } else {
+                decode(bytes).ok_or_else(corrupt)?
+            };
+            let (file, len) = write_new(&path, &offsets)?;
+            let log = OffsetLog {
+                path,
+                file,
+                len,
+                entries: 1,
+            };
+            return Ok((log, offsets));
+        }
+
+        let (offsets, len, entries) = read_entries(&bytes)?;
+        let file = OpenOptions::new().append(true).open(&path)?;
+        if len < bytes.len() as u64 {
+            file.set_len(len)?;
+            file.sync_all()?;
+        }
+        let log = OffsetLog {
+            path,
+            file,
+            len,
+            entries,
+        };
+        Ok((log, offsets))
+    }
+
+    /// Appends the offsets `group` committed, synced before returning. A
+    /// failed append leaves the log as it was.
+    pub(super) fn append(
+        &mut self,
+        group: &str,
+        offsets: &[(TopicPartition, u64)],
+    ) -> io::Result<()> {
+        let entry = encode_entry(
+            offsets
+                .iter()
+                .map(|(partition, offset)| (group, partition, *offset)),
+        );
+        let written = self
+            .file
+            .write_all(&entry)
+            .and_then(|()| self.file.sync_data());
+        if let Err(err) = written {
+            // Don't leave part of the entry for the next one to follow
+            let _ = self.file.set_len(self.len);
+            return Err(err);
+        }
+        self.len += entry.len() as u64;
+        self.entries += 1;
+        Ok(())
+    }
+
+    pub(super) fn should_compact(&self) -> bool {
+        self.entries >= COMPACT_AFTER
+    }
+
+    /// Rewrites the log as one entry holding `offsets`, the latest of every
+    /// group.
+    pub(super) fn compact(&mut self, offsets: &Offsets) -> io::Result<()> {
+        let (file, len) = write_new(&self.path, offsets)?;
+        self.file = file;
+        self.len = len;
+        self.entries = 1;
+        Ok(())
+    }
+}
+
+fn corrupt() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "corrupt offsets file")
+}
+
+/// Replaces the file at `path` with a log of one entry holding `offsets`,
+/// returning it opened for appends along with its length.
+fn write_new(path: &Path, offsets: &Offsets) -> io::Result<(File, u64)> {
+    let entry = encode_entry(offsets.iter().flat_map(|(group, committed)| {
+        committed
+            .iter()
+            .map(move |(partition, offset)| (group.as_str(), partition, *offset))
+    }));
+    let mut tmp = path.to_path_buf().into_os_string();
+    tmp.push(TMP_SUFFIX);
+
+    let mut file = File::create(&tmp)?;
+    file.write_all(MAGIC)?;
+    file.write_all(&entry)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+
+    let file = OpenOptions::new().append(true).open(path)?;
+    Ok((file, (MAGIC.len() + entry.len()) as u64))
+}
+
+/// The offsets in the entries after the magic, with the length up to the
+/// end of the last whole one and the number of entries.
+fn read_entries(bytes: &Bytes) -> io::Result<(Offsets, u64, usize)> {
+    let mut offsets = Offsets::new();
+    let mut entries = 0;
+    let mut position = MAGIC.len();
+    while bytes.len() - position >= ENTRY_HEADER_SIZE {
+        let mut header = &bytes[position..position + ENTRY_HEADER_SIZE];
+        let len = header.get_u32() as usize;
+        let crc = header.get_u32();
+        let end = position + ENTRY_HEADER_SIZE + len;
+        if end > bytes.len() {
+            break;
+        }
+
+        let entry = bytes.slice(position + ENTRY_HEADER_SIZE..end);
+        if crc32c(&entry) != crc {
+            // Only the last entry can be torn by a crash
+            if end == bytes.len() {
+                break;
+            }
+            return Err(corrupt());
+        }
+        for (group, committed) in decode(entry).ok_or_else(corrupt)? {
+            offsets.entry(group).or_default().extend(committed);
+        }
+        entries += 1;
+        position = end;
+    }
+    Ok((offsets, position as u64, entries))
+}
+
+fn encode_entry<'a>(offsets: impl Iterator<Item = (&'a str, &'a TopicPartition, u64)>) -> Bytes {
+    let mut buf = BytesMut::new();
+    buf.put_u32(0);
+    let mut count = 0u32;
+    for (group, partition, offset) in offsets {
+        put_str(&mut buf, group);
+        put_str(&mut buf, &partition.topic);
+        buf.put_u32(partition.partition);
+        buf.put_u64(offset);
+        count += 1;
+    }
+    buf[..4].copy_from_slice(&count.to_be_bytes());
+
+    let mut entry = BytesMut::with_capacity(ENTRY_HEADER_SIZE + buf.len());
+    entry.put_u32(buf.len() as u32);
+    entry.put_u32(crc32c(&buf));
+    entry.put_slice(&buf);
+    entry.freeze()
+}
+
+/// Reads the offsets of one entry, which has to be all of `bytes`.
+fn decode(mut bytes: Bytes) -> Option<Offsets> {
+    if bytes.remaining() < 4 {
+        return None;
+    }
+    let mut offsets = Offsets::new();
+    for _ in 0..bytes.get_u32() {
+        let group = get_str(&mut bytes)?;
+        let topic = get_str(&mut bytes)?;
+        if bytes.remaining() < 4 + 8 {
+            return None;
+        }
+        let partition = TopicPartition::new(topic, bytes.get_u32());
+        offsets
+            .entry(group)
+            .or_default()
+            .insert(partition, bytes.get_u64());
+    }
+    (!bytes.has_remaining()).then_some(offsets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(partition: u32) -> TopicPartition {
+        TopicPartition::new("events", partition)
+    }
+
+    #[test]
+    fn test_append_and_compact() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("offsets");
+
+        let (mut log, offsets) = OffsetLog::open(path.clone()).unwrap();
+        assert!(offsets.is_empty());
+        log.append("billing", &[(events(0), 5), (events(1), 3)])
+            .unwrap();
+        log.append("billing", &[(events(0), 9)]).unwrap();
+        log.append("search", &[(events(0), 1)]).unwrap();
+
+        let (mut log, offsets) = OffsetLog::open(path.clone()).unwrap();
+        assert_eq!(
+            offsets["billing"],
+            BTreeMap::from([(events(0), 9), (events(1), 3)])
+        );
+        assert_eq!(offsets["search"], BTreeMap::from([(events(0), 1)]));
+        assert_eq!(log.entries, 4);
+        assert!(!log.should_compact());
+
+        let whole = fs::metadata(&path).unwrap().len();
+        log.compact(&offsets).unwrap();
+        assert!(fs::metadata(&path).unwrap().len() < whole);
+        log.append("search", &[(events(0), 4)]).unwrap();
+
+        let (log, reopened) = OffsetLog::open(path).unwrap();
+        assert_eq!(log.entries, 2);
+        assert_eq!(reopened["billing"], offsets["billing"]);
+        assert_eq!(reopened["search"], BTreeMap::from([(events(0), 4)]));
+    }
+
+    #[test]
+    fn test_torn_and_corrupt_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("offsets");
+        let (mut log, _) = OffsetLog::open(path.clone()).unwrap();
+        log.append("billing", &[(events(0), 5)]).unwrap();
+        log.append("billing", &[(events(0), 9)]).unwrap();
+        let whole = fs::read(&path).unwrap();
+
+        // A crash while appending leaves part of an entry behind
+        let mut torn = whole.clone();
+        torn.extend_from_slice(&encode_entry([("billing", &events(0), 12)].into_iter())[..10]);
+        fs::write(&path, &torn).unwrap();
+        let (_, offsets) = OffsetLog::open(path.clone()).unwrap();
+        assert_eq!(offsets["billing"], BTreeMap::from([(events(0), 9)]));
+        assert_eq!(fs::read(&path).unwrap(), whole);
+
+        // Or all of one whose bytes never made it to disk
+        let mut torn = whole.clone();
+        let last = torn.len() - 1;
+        torn[last] ^= 0xFF;
+        fs::write(&path, &torn).unwrap();
+        let (_, offsets) = OffsetLog::open(path.clone()).unwrap();
+        assert_eq!(offsets["billing"], BTreeMap::from([(events(0), 5)]));
+
+        // Anywhere before the last entry it's corruption
+        let mut corrupt = whole.clone();
+        corrupt[MAGIC.len() + ENTRY_HEADER_SIZE] ^= 0xFF;
+        fs::write(&path, &corrupt).unwrap();
+        let err = OffsetLog::open(path.clone()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(fs::read(&path).unwrap(), corrupt);
+    }
+
+    #[test]
+    fn test_snapshot_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("offsets");
+        let snapshot = encode_entry([("billing", &events(0), 5)].into_iter());
+        fs::write(&path, &snapshot[ENTRY_HEADER_SIZE..]).unwrap();
+
+        let (_, offsets) = OffsetLog::open(path.clone()).unwrap();
+        assert_eq!(offsets["billing"], BTreeMap::from([(events(0), 5)]));
+        assert!(fs::read(&path).unwrap().starts_with(MAGIC));
+        let (_, reopened) = OffsetLog::open(path).unwrap();
+        assert_eq!(reopened, offsets);
+    }
+}
@@ -1 +1,28 @@
-pub mod request;
\ No newline at end of file
+#[cfg(all(feature = "io-uring", not(target_os = "linux")))]
+compile_error!("the io-uring feature is only supported on Linux");
+
+/// Version of herm, as brokers report it to each other.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub mod auth;
+pub mod broker;
+pub mod chunk;
+pub mod client;
+pub mod cluster;
+pub mod events;
+pub mod group;
+pub mod metrics;
+pub mod mirror;
+pub mod protocol;
+pub mod record;
+pub mod replication;
+pub mod request;
+pub mod response;
+pub mod storage;
+pub mod tenant;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod transform;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
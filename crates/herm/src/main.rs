@@ -1,8 +1,152 @@
-use herm::request::Fetch;
+use std::path::PathBuf;
+use std::sync::Arc;
 
-fn main() {
-    println!(
-        "Fetch request - {:?}",
-        Fetch::new("test".to_string(), 0, 0, 1024).unwrap(),
-    );
+use tokio::signal::unix::{signal, SignalKind};
+
+use herm::auth::{AclAuthorizer, AllowAll, Authorizer};
+use herm::broker::{AuditLog, Config, LogHandler, Server};
+use herm::cluster::HeartbeatTask;
+use herm::events::EventBus;
+use herm::group::{GroupCoordinator, OFFSETS_FILE};
+use herm::storage::{LogDirs, RetentionTask};
+use herm::tenant::NamespaceAuthorizer;
+
+/// Usage: `herm [config-file]`, with `HERM_` environment variables
+/// overriding the file.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let path = std::env::args().nth(1).map(PathBuf::from);
+    let config = Config::load(path.as_deref())?;
+
+    let logs = Arc::new(LogDirs::open(
+        config.data_dirs.clone(),
+        config.log_config(),
+        config.placement,
+    ));
+    let retention_logs = logs.clone();
+    let retention =
+        RetentionTask::spawn(config.log.cleanup_interval(), move || retention_logs.logs());
+
+    let handler = LogHandler::new(logs.clone())
+        .with_events(EventBus::new())
+        .with_replica_config(config.replica_config())
+        .with_cluster_config(config.cluster_config())
+        .with_delay_config(config.limits.delay_config())
+        .with_transforms(config.transforms()?)
+        .with_group_coordinator(GroupCoordinator::open(
+            config.data_dirs[0].join(OFFSETS_FILE),
+        )?);
+    let handler = if config.auto_create_topics {
+        handler.with_auto_create_topics(config.default_partitions)
+    } else {
+        handler
+    };
+    let handler = if config.forward_to_leader {
+        handler.with_forwarding()
+    } else {
+        handler
+    };
+    let acls: Arc<dyn Authorizer> = match &config.acl_path {
+        Some(path) => Arc::new(AclAuthorizer::load(path)?),
+        None => Arc::new(AllowAll),
+    };
+    let namespaces = if config.namespaces.is_empty() {
+        None
+    } else {
+        Some(Arc::new(config.namespaces()?))
+    };
+    let authorizer: Arc<dyn Authorizer> = match &namespaces {
+        Some(namespaces) => Arc::new(NamespaceAuthorizer::new(namespaces.clone(), acls)),
+        None => acls,
+    };
+    let handler = handler.with_authorizer(authorizer.clone());
+    let handler = match &namespaces {
+        Some(namespaces) => handler.with_namespaces(namespaces.clone()),
+        None => handler,
+    };
+    let replicas = handler.replicas().clone();
+    let cluster = handler.cluster().clone();
+    let server = Server::bind(&config.listen, handler)
+        .await?
+        .with_max_frame_size(config.limits.max_frame_size)
+        .with_max_in_flight(config.limits.max_in_flight)
+        .with_decode_limits(config.limits.decode_limits())
+        .with_drain_timeout(config.limits.drain_timeout())
+        .with_quotas(config.quotas.quota_config());
+    let server = match &config.audit {
+        Some(audit) => server.with_audit_log(AuditLog::open(
+            &audit.path,
+            audit.max_file_bytes,
+            audit.max_files,
+        )?),
+        None => server,
+    };
+    let server = match &config.listen_unix {
+        Some(path) => server.with_unix_socket(path)?,
+        None => server,
+    };
+    let server = server.with_authorizer(authorizer);
+    let server = match namespaces {
+        Some(namespaces) => server.with_namespace_quotas(namespaces),
+        None => server,
+    };
+    #[cfg(feature = "tls")]
+    let server = match &config.tls {
+        Some(tls) => server.with_tls(match &tls.client_ca_path {
+            Some(ca_path) => {
+                herm::tls::client_auth_acceptor(&tls.cert_path, &tls.key_path, ca_path)?
+            }
+            None => herm::tls::acceptor(&tls.cert_path, &tls.key_path)?,
+        }),
+        None => server,
+    };
+    #[cfg(feature = "prometheus")]
+    if let Some(listen) = &config.metrics_listen {
+        let metrics_server =
+            herm::metrics::MetricsServer::bind(listen, server.metrics(), logs.clone()).await?;
+        println!("Serving metrics on {}", metrics_server.local_addr()?);
+        tokio::spawn(metrics_server.run());
+    }
+    replicas.set_advertised_listener(match &config.advertised_listen {
+        Some(addr) => addr.clone(),
+        None => server.local_addr()?.to_string(),
+    });
+    if let Some(leader) = &config.replicate_from {
+        for (partition, log) in logs.partitions() {
+            let leader_epoch = log.read().unwrap().leader_epoch();
+            replicas.follow(&partition, leader.clone(), leader_epoch)?;
+        }
+    }
+    let heartbeats = HeartbeatTask::spawn(cluster, replicas.clone());
+    println!("Listening on {}", server.local_addr()?);
+    if let Some(path) = server.unix_path() {
+        println!("Listening on {}", path.display());
+    }
+    let handoff_timeout = config.controlled_shutdown_timeout();
+    server
+        .run_until(async {
+            shutdown_signal().await;
+            // Followers take over while they can still fetch from here
+            if !handoff_timeout.is_zero() {
+                let handed_off = replicas.hand_off_leadership(handoff_timeout).await;
+                println!("Handed off leadership of {} partitions", handed_off);
+            }
+        })
+        .await?;
+
+    println!("Shutting down");
+    drop(heartbeats);
+    replicas.stop();
+    drop(retention);
+    logs.flush()?;
+    Ok(())
+}
+
+/// Completes on the first SIGINT or SIGTERM.
+async fn shutdown_signal() {
+    let mut terminate = signal(SignalKind::terminate()).expect("failed to listen for SIGTERM");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
 }
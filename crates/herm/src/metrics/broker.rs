@@ -0,0 +1,164 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use super::{Histogram, HistogramSnapshot};
+use crate::protocol::{ApiKey, ErrorCode};
+
+/// Counters for the requests of one api key.
+#[derive(Debug, Default)]
+pub struct ApiMetrics {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    /// Time from a request to its response, in microseconds. The broker
+    /// counts from reading the request to the response being ready.
+    latency: Histogram,
+}
+
+impl ApiMetrics {
+    pub(super) fn add_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn add_bytes(&self, bytes_in: usize, bytes_out: usize) {
+        self.bytes_in.fetch_add(bytes_in as u64, Ordering::Relaxed);
+        self.bytes_out
+            .fetch_add(bytes_out as u64, Ordering::Relaxed);
+    }
+
+    /// Records an answer with `error`, `latency` after its request.
+    pub(super) fn add_response(&self, error: ErrorCode, latency: Duration) {
+        if !error.is_ok() {
+            self.add_error();
+        }
+        self.latency
+            .record(latency.as_micros().try_into().unwrap_or(u64::MAX));
+    }
+
+    pub(super) fn add_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn snapshot(&self, api_key: ApiKey) -> ApiSnapshot {
+        ApiSnapshot {
+            api_key,
+            requests: self.requests.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            latency_us: self.latency.snapshot(),
+        }
+    }
+}
+
+/// What the broker has served since it started, per api key. The server
+/// records every request it answers, see
+/// [`Server::metrics`](crate::broker::Server::metrics).
+#[derive(Debug, Default)]
+pub struct BrokerMetrics {
+    apis: [ApiMetrics; ApiKey::ALL.len()],
+}
+
+impl BrokerMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a request of `bytes_in` answered with `bytes_out`, which took
+    /// `latency` and finished with `error`.
+    pub fn record(
+        &self,
+        api_key: ApiKey,
+        bytes_in: usize,
+        bytes_out: usize,
+        error: ErrorCode,
+        latency: Duration,
+    ) {
+        let api = &self.apis[api_key.index()];
+        api.add_request();
+        api.add_bytes(bytes_in, bytes_out);
+        api.add_response(error, latency);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            apis: ApiKey::ALL
+                .iter()
+                .map(|&api_key| self.apis[api_key.index()].snapshot(api_key))
+                .collect(),
+        }
+    }
+}
+
+/// [`BrokerMetrics`] at one point in time. Counters only ever go up, so
+/// rates come from comparing two snapshots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricsSnapshot {
+    /// One per api key, in [`ApiKey::ALL`] order.
+    pub apis: Vec<ApiSnapshot>,
+}
+
+impl MetricsSnapshot {
+    pub fn api(&self, api_key: ApiKey) -> &ApiSnapshot {
+        &self.apis[api_key.index()]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiSnapshot {
+    pub api_key: ApiKey,
+    pub requests: u64,
+    /// Requests answered with an error code.
+    pub errors: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub latency_us: HistogramSnapshot,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let metrics = BrokerMetrics::new();
+        metrics.record(
+            ApiKey::Produce,
+            100,
+            10,
+            ErrorCode::None,
+            Duration::from_millis(2),
+        );
+        metrics.record(
+            ApiKey::Produce,
+            50,
+            10,
+            ErrorCode::StorageError,
+            Duration::from_millis(4),
+        );
+        metrics.record(
+            ApiKey::Fetch,
+            20,
+            500,
+            ErrorCode::None,
+            Duration::from_micros(3),
+        );
+
+        let snapshot = metrics.snapshot();
+        let produce = snapshot.api(ApiKey::Produce);
+        assert_eq!(produce.api_key, ApiKey::Produce);
+        assert_eq!(produce.requests, 2);
+        assert_eq!(produce.errors, 1);
+        assert_eq!(produce.bytes_in, 150);
+        assert_eq!(produce.bytes_out, 20);
+        assert_eq!(produce.latency_us.count, 2);
+        assert_eq!(produce.latency_us.max, 4000);
+
+        let fetch = snapshot.api(ApiKey::Fetch);
+        assert_eq!(fetch.requests, 1);
+        assert_eq!(fetch.errors, 0);
+        assert_eq!(fetch.bytes_out, 500);
+        assert_eq!(fetch.latency_us.percentile(0.5), 3);
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use super::{ApiMetrics, ApiSnapshot, Histogram, HistogramSnapshot};
+use crate::client::{ClientError, ClientObserver};
+use crate::protocol::{ApiKey, ErrorCode, TopicPartition};
+
+/// What a client has asked of the cluster, per api key, and the batches
+/// its producers sent. Set as the client's observer to record, see
+/// [`Client::with_observer`](crate::client::Client::with_observer).
+///
+/// Bytes out are requests sent and bytes in responses received. Errors
+/// count responses with an error code and requests that got no response.
+#[derive(Debug, Default)]
+pub struct ClientMetrics {
+    apis: [ApiMetrics; ApiKey::ALL.len()],
+    retries: AtomicU64,
+    batches: AtomicU64,
+    failed_batches: AtomicU64,
+    batch_records: Histogram,
+    batch_bytes: Histogram,
+    /// Time to send a batch, retries included, in microseconds.
+    batch_latency: Histogram,
+}
+
+impl ClientMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> ClientMetricsSnapshot {
+        ClientMetricsSnapshot {
+            apis: ApiKey::ALL
+                .iter()
+                .map(|&api_key| self.apis[api_key.index()].snapshot(api_key))
+                .collect(),
+            retries: self.retries.load(Ordering::Relaxed),
+            batches: self.batches.load(Ordering::Relaxed),
+            failed_batches: self.failed_batches.load(Ordering::Relaxed),
+            batch_records: self.batch_records.snapshot(),
+            batch_bytes: self.batch_bytes.snapshot(),
+            batch_latency_us: self.batch_latency.snapshot(),
+        }
+    }
+}
+
+impl ClientObserver for ClientMetrics {
+    fn on_request_sent(&self, _: &str, api_key: ApiKey, bytes: usize) {
+        let api = &self.apis[api_key.index()];
+        api.add_request();
+        api.add_bytes(0, bytes);
+    }
+
+    fn on_response_received(
+        &self,
+        _: &str,
+        api_key: ApiKey,
+        bytes: usize,
+        error: ErrorCode,
+        latency: Duration,
+    ) {
+        let api = &self.apis[api_key.index()];
+        api.add_bytes(bytes, 0);
+        api.add_response(error, latency);
+    }
+
+    fn on_request_failed(&self, _: &str, api_key: ApiKey, _: &ClientError) {
+        self.apis[api_key.index()].add_error();
+    }
+
+    fn on_retry(&self, _: ApiKey, _: u32, _: Duration) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_batch_flushed(
+        &self,
+        _: &TopicPartition,
+        records: usize,
+        bytes: usize,
+        latency: Duration,
+        succeeded: bool,
+    ) {
+        self.batches.fetch_add(1, Ordering::Relaxed);
+        if !succeeded {
+            self.failed_batches.fetch_add(1, Ordering::Relaxed);
+        }
+        self.batch_records.record(records as u64);
+        self.batch_bytes.record(bytes as u64);
+        self.batch_latency
+            .record(latency.as_micros().try_into().unwrap_or(u64::MAX));
+    }
+}
+
+/// [`ClientMetrics`] at one point in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientMetricsSnapshot {
+    /// One per api key, in [`ApiKey::ALL`] order.
+    pub apis: Vec<ApiSnapshot>,
+    pub retries: u64,
+    pub batches: u64,
+    pub failed_batches: u64,
+    pub batch_records: HistogramSnapshot,
+    pub batch_bytes: HistogramSnapshot,
+    pub batch_latency_us: HistogramSnapshot,
+}
+
+impl ClientMetricsSnapshot {
+    pub fn api(&self, api_key: ApiKey) -> &ApiSnapshot {
+        &self.apis[api_key.index()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let metrics = ClientMetrics::new();
+        metrics.on_request_sent("a:9092", ApiKey::Produce, 100);
+        metrics.on_response_received(
+            "a:9092",
+            ApiKey::Produce,
+            10,
+            ErrorCode::NotLeaderOrFollower,
+            Duration::from_millis(2),
+        );
+        metrics.on_retry(ApiKey::Produce, 1, Duration::from_millis(100));
+        metrics.on_request_sent("b:9092", ApiKey::Produce, 100);
+        metrics.on_request_failed("b:9092", ApiKey::Produce, &ClientError::Disconnected);
+        metrics.on_batch_flushed(
+            &TopicPartition::new("events", 0),
+            5,
+            100,
+            Duration::from_millis(3),
+            false,
+        );
+
+        let snapshot = metrics.snapshot();
+        let produce = snapshot.api(ApiKey::Produce);
+        assert_eq!(produce.requests, 2);
+        assert_eq!(produce.errors, 2);
+        assert_eq!(produce.bytes_out, 200);
+        assert_eq!(produce.bytes_in, 10);
+        assert_eq!(produce.latency_us.count, 1);
+        assert_eq!(snapshot.retries, 1);
+        assert_eq!(snapshot.batches, 1);
+        assert_eq!(snapshot.failed_batches, 1);
+        assert_eq!(snapshot.batch_records.max, 5);
+        assert_eq!(snapshot.batch_latency_us.max, 3000);
+        assert_eq!(snapshot.api(ApiKey::Fetch).requests, 0);
+    }
+}
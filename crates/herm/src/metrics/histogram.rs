@@ -0,0 +1,174 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Buckets per power of two, as a number of bits.
+const SUB_BUCKET_BITS: u32 = 3;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+const BUCKETS: usize = ((64 - SUB_BUCKET_BITS + 1) as u64 * SUB_BUCKETS) as usize;
+
+/// Counts of recorded values in log-linear buckets: every power of two is
+/// split into 8 buckets, so percentiles read from it are within 12.5% of the
+/// real value. Recording is lock free and can happen from any thread.
+#[derive(Debug)]
+pub struct Histogram {
+    buckets: Box<[AtomicU64]>,
+    count: AtomicU64,
+    sum: AtomicU64,
+    max: AtomicU64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, value: u64) {
+        self.buckets[bucket_index(value)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+        self.max.fetch_max(value, Ordering::Relaxed);
+    }
+
+    /// Copies out the counts. Values recorded while this runs may be only
+    /// partly included.
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            buckets: self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.load(Ordering::Relaxed))
+                .collect(),
+            count: self.count.load(Ordering::Relaxed),
+            sum: self.sum.load(Ordering::Relaxed),
+            max: self.max.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A [`Histogram`] at one point in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    buckets: Vec<u64>,
+    pub count: u64,
+    pub sum: u64,
+    pub max: u64,
+}
+
+impl HistogramSnapshot {
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum as f64 / self.count as f64
+        }
+    }
+
+    /// Value at or below which a `quantile` (0.0 to 1.0) of the recorded
+    /// values fall, rounded up to the edge of its bucket. 0 when empty.
+    pub fn percentile(&self, quantile: f64) -> u64 {
+        let rank = ((quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return bucket_upper_bound(index).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    /// How many recorded values fell in buckets wholly at or below `value`.
+    pub fn count_at_most(&self, value: u64) -> u64 {
+        self.buckets
+            .iter()
+            .enumerate()
+            .take_while(|&(index, _)| bucket_upper_bound(index) <= value)
+            .map(|(_, &count)| count)
+            .sum()
+    }
+
+    /// Non-empty buckets as their inclusive upper bound and count, in order.
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(index, &count)| (bucket_upper_bound(index), count))
+    }
+}
+
+fn bucket_index(value: u64) -> usize {
+    if value < SUB_BUCKETS {
+        return value as usize;
+    }
+    let exponent = 63 - value.leading_zeros();
+    let shift = exponent - SUB_BUCKET_BITS;
+    let sub_bucket = (value >> shift) & (SUB_BUCKETS - 1);
+    ((exponent - SUB_BUCKET_BITS + 1) as u64 * SUB_BUCKETS + sub_bucket) as usize
+}
+
+fn bucket_upper_bound(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return index;
+    }
+    let shift = index / SUB_BUCKETS - 1;
+    let lower = (SUB_BUCKETS + index % SUB_BUCKETS) << shift;
+    lower + ((1 << shift) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets() {
+        for value in [0, 1, 7, 8, 9, 15, 16, 17, 100, 1000, 123_456, u64::MAX] {
+            let index = bucket_index(value);
+            assert!(bucket_upper_bound(index) >= value, "{}", value);
+            if index > 0 {
+                assert!(bucket_upper_bound(index - 1) < value, "{}", value);
+            }
+        }
+        assert_eq!(bucket_index(u64::MAX), BUCKETS - 1);
+        assert_eq!(bucket_upper_bound(BUCKETS - 1), u64::MAX);
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let histogram = Histogram::new();
+        assert_eq!(histogram.snapshot().percentile(0.5), 0);
+
+        for value in 1..=1000 {
+            histogram.record(value);
+        }
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.count, 1000);
+        assert_eq!(snapshot.max, 1000);
+        assert_eq!(snapshot.mean(), 500.5);
+
+        for (quantile, exact) in [(0.5, 500.0), (0.9, 900.0), (0.99, 990.0)] {
+            let value = snapshot.percentile(quantile) as f64;
+            assert!(value >= exact && value <= exact * 1.125, "{}", value);
+        }
+        assert_eq!(snapshot.percentile(1.0), 1000);
+        assert_eq!(snapshot.percentile(0.0), 1);
+        assert_eq!(snapshot.count_at_most(7), 7);
+        assert_eq!(snapshot.count_at_most(100), 95);
+        assert_eq!(snapshot.count_at_most(u64::MAX), 1000);
+        assert_eq!(
+            snapshot.buckets().map(|(_, count)| count).sum::<u64>(),
+            1000
+        );
+    }
+}
@@ -0,0 +1,10 @@
+mod broker;
+mod client;
+mod histogram;
+#[cfg(feature = "prometheus")]
+mod prometheus;
+pub use broker::{ApiMetrics, ApiSnapshot, BrokerMetrics, MetricsSnapshot};
+pub use client::{ClientMetrics, ClientMetricsSnapshot};
+pub use histogram::{Histogram, HistogramSnapshot};
+#[cfg(feature = "prometheus")]
+pub use prometheus::{render, MetricsServer};
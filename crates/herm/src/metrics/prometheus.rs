@@ -0,0 +1,345 @@
+use std::fmt::Write;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use super::{ApiSnapshot, BrokerMetrics, MetricsSnapshot};
+use crate::storage::LogDirs;
+
+/// Upper bounds of the latency histogram buckets exported, in seconds.
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.0001, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0, 5.0,
+];
+
+/// Longest request head read before giving up on a scrape.
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+/// Renders `snapshot` and the storage gauges of `logs` in the Prometheus text
+/// exposition format.
+pub fn render(snapshot: &MetricsSnapshot, logs: &LogDirs) -> String {
+    let mut out = String::new();
+
+    counter(
+        &mut out,
+        snapshot,
+        "herm_requests_total",
+        "Requests answered.",
+        |api| api.requests,
+    );
+    counter(
+        &mut out,
+        snapshot,
+        "herm_request_errors_total",
+        "Requests answered with an error code.",
+        |api| api.errors,
+    );
+    counter(
+        &mut out,
+        snapshot,
+        "herm_request_bytes_total",
+        "Bytes of requests read, headers included.",
+        |api| api.bytes_in,
+    );
+    counter(
+        &mut out,
+        snapshot,
+        "herm_response_bytes_total",
+        "Bytes of responses written, headers included.",
+        |api| api.bytes_out,
+    );
+
+    let name = "herm_request_latency_seconds";
+    header(
+        &mut out,
+        name,
+        "Time from reading a request to its response being ready.",
+        "histogram",
+    );
+    for api in &snapshot.apis {
+        let latency = &api.latency_us;
+        for bound in LATENCY_BUCKETS {
+            let count = latency.count_at_most((bound * 1_000_000.0) as u64);
+            let _ = writeln!(
+                out,
+                "{}_bucket{{api=\"{}\",le=\"{}\"}} {}",
+                name, api.api_key, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{api=\"{}\",le=\"+Inf\"}} {}",
+            name, api.api_key, latency.count
+        );
+        let sum = latency.sum as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{}_sum{{api=\"{}\"}} {}", name, api.api_key, sum);
+        let _ = writeln!(
+            out,
+            "{}_count{{api=\"{}\"}} {}",
+            name, api.api_key, latency.count
+        );
+    }
+
+    let partitions: Vec<_> = logs
+        .partitions()
+        .into_iter()
+        .map(|(partition, log)| {
+            let log = log.read().unwrap();
+            let labels = format!(
+                "topic=\"{}\",partition=\"{}\"",
+                escape(&partition.topic),
+                partition.partition
+            );
+            let values = [
+                log.size(),
+                log.segment_count() as u64,
+                log.start_offset(),
+                log.next_offset(),
+            ];
+            (labels, values)
+        })
+        .collect();
+    let gauges = [
+        ("herm_log_size_bytes", "Bytes stored for a partition."),
+        ("herm_log_segments", "Segments of a partition."),
+        (
+            "herm_log_start_offset",
+            "First offset still stored for a partition.",
+        ),
+        (
+            "herm_log_end_offset",
+            "Offset the next record appended to a partition gets.",
+        ),
+    ];
+    for (index, (name, help)) in gauges.into_iter().enumerate() {
+        header(&mut out, name, help, "gauge");
+        for (labels, values) in &partitions {
+            let _ = writeln!(out, "{}{{{}}} {}", name, labels, values[index]);
+        }
+    }
+
+    let name = "herm_offline_partitions";
+    header(
+        &mut out,
+        name,
+        "Partitions whose log dir is offline.",
+        "gauge",
+    );
+    let _ = writeln!(out, "{} {}", name, logs.offline_partitions().len());
+    out
+}
+
+/// A counter with a sample per api key.
+fn counter(
+    out: &mut String,
+    snapshot: &MetricsSnapshot,
+    name: &str,
+    help: &str,
+    value: impl Fn(&ApiSnapshot) -> u64,
+) {
+    header(out, name, help, "counter");
+    for api in &snapshot.apis {
+        let _ = writeln!(out, "{}{{api=\"{}\"}} {}", name, api.api_key, value(api));
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Serves [`render`] on `GET /metrics` for Prometheus to scrape. Only speaks
+/// enough HTTP/1.1 for that, every response closes its connection.
+#[derive(Debug)]
+pub struct MetricsServer {
+    listener: TcpListener,
+    metrics: Arc<BrokerMetrics>,
+    logs: Arc<LogDirs>,
+}
+
+impl MetricsServer {
+    pub async fn bind(
+        addr: impl ToSocketAddrs,
+        metrics: Arc<BrokerMetrics>,
+        logs: Arc<LogDirs>,
+    ) -> io::Result<Self> {
+        Ok(MetricsServer {
+            listener: TcpListener::bind(addr).await?,
+            metrics,
+            logs,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Runs the accept loop. Only returns if accepting fails.
+    pub async fn run(self) -> io::Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            let metrics = self.metrics.clone();
+            let logs = self.logs.clone();
+            tokio::spawn(async move {
+                let _ = scrape(stream, &metrics, &logs).await;
+            });
+        }
+    }
+}
+
+async fn scrape(mut stream: TcpStream, metrics: &BrokerMetrics, logs: &LogDirs) -> io::Result<()> {
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 || head.len() + read > MAX_REQUEST_HEAD {
+            return Ok(());
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+
+    let request_line = head.split(|&byte| byte == b'\r').next().unwrap_or_default();
+    let mut parts = request_line.split(|&byte| byte == b' ');
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some(b"GET"), Some(b"/metrics")) => ("200 OK", render(&metrics.snapshot(), logs)),
+        (Some(b"GET"), _) => ("404 Not Found", String::new()),
+        _ => ("405 Method Not Allowed", String::new()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use bytes::Bytes;
+
+    use crate::protocol::{ApiKey, ErrorCode, TopicPartition};
+    use crate::record::{Record, RecordBatch};
+    use crate::storage::{LogConfig, Placement};
+
+    fn logs(dir: &tempfile::TempDir) -> Arc<LogDirs> {
+        let logs = LogDirs::open([dir.path()], LogConfig::default(), Placement::default());
+        let log = logs.create(&TopicPartition::new("events", 0)).unwrap();
+        let batch = RecordBatch::new(vec![
+            Record::new(None, Some(Bytes::from_static(b"a"))),
+            Record::new(None, Some(Bytes::from_static(b"b"))),
+        ]);
+        log.write().unwrap().append(batch).unwrap();
+        Arc::new(logs)
+    }
+
+    #[test]
+    fn test_render() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = logs(&dir);
+        let metrics = BrokerMetrics::new();
+        metrics.record(
+            ApiKey::Produce,
+            100,
+            20,
+            ErrorCode::None,
+            Duration::from_millis(3),
+        );
+        metrics.record(
+            ApiKey::Produce,
+            100,
+            20,
+            ErrorCode::StorageError,
+            Duration::from_secs(2),
+        );
+
+        let text = render(&metrics.snapshot(), &logs);
+        for line in [
+            "# TYPE herm_requests_total counter",
+            "herm_requests_total{api=\"Produce\"} 2",
+            "herm_requests_total{api=\"Fetch\"} 0",
+            "herm_request_errors_total{api=\"Produce\"} 1",
+            "herm_request_bytes_total{api=\"Produce\"} 200",
+            "herm_response_bytes_total{api=\"Produce\"} 40",
+            "# TYPE herm_request_latency_seconds histogram",
+            "herm_request_latency_seconds_bucket{api=\"Produce\",le=\"0.001\"} 0",
+            "herm_request_latency_seconds_bucket{api=\"Produce\",le=\"0.005\"} 1",
+            "herm_request_latency_seconds_bucket{api=\"Produce\",le=\"5\"} 2",
+            "herm_request_latency_seconds_bucket{api=\"Produce\",le=\"+Inf\"} 2",
+            "herm_request_latency_seconds_sum{api=\"Produce\"} 2.003",
+            "herm_request_latency_seconds_count{api=\"Produce\"} 2",
+            "herm_log_segments{topic=\"events\",partition=\"0\"} 1",
+            "herm_log_start_offset{topic=\"events\",partition=\"0\"} 0",
+            "herm_log_end_offset{topic=\"events\",partition=\"0\"} 2",
+            "herm_offline_partitions 0",
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "{} missing in\n{}",
+                line,
+                text
+            );
+        }
+        let size = logs.partitions()[0].1.read().unwrap().size();
+        assert!(size > 0);
+        assert!(text.contains(&format!(
+            "herm_log_size_bytes{{topic=\"events\",partition=\"0\"}} {}",
+            size
+        )));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    async fn get(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_server() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Arc::new(BrokerMetrics::new());
+        let server = MetricsServer::bind("127.0.0.1:0", metrics, logs(&dir))
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let response = get(addr, "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains(&format!("Content-Length: {}", body.len())));
+        assert!(body.contains("herm_log_end_offset{topic=\"events\",partition=\"0\"} 2"));
+
+        let response = get(addr, "GET / HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        let response = get(addr, "POST /metrics HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    }
+}
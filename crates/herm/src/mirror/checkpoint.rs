@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::protocol::{get_str, put_str, TopicPartition};
+
+/// Default name of the file a [`Mirror`](super::Mirror) keeps its
+/// checkpoints in.
+pub const CHECKPOINT_FILE: &str = "mirror-checkpoints";
+
+/// Written out in full, then renamed over the checkpoint file, so a crash
+/// never leaves torn checkpoints behind.
+const TMP_SUFFIX: &str = ".tmp";
+
+/// How far a source partition has been mirrored, and where that is in the
+/// target, so consumers failing over can translate their offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Next source offset to mirror.
+    pub source_offset: u64,
+    /// Offset the record at `source_offset` gets in the target.
+    pub target_offset: u64,
+}
+
+/// Checkpoints of every mirrored partition, kept in a file when given one.
+#[derive(Debug, Default)]
+pub struct CheckpointStore {
+    path: Option<PathBuf>,
+    checkpoints: BTreeMap<TopicPartition, Checkpoint>,
+}
+
+impl CheckpointStore {
+    /// Checkpoints kept in memory only, lost when the mirror stops.
+    pub fn in_memory() -> Self {
+        CheckpointStore::default()
+    }
+
+    /// Loads the checkpoints stored at `path`, none if it doesn't exist yet.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let checkpoints = match fs::read(&path) {
+            Ok(bytes) => decode(Bytes::from(bytes)).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "corrupt checkpoint file")
+            })?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(CheckpointStore {
+            path: Some(path),
+            checkpoints,
+        })
+    }
+
+    pub fn get(&self, partition: &TopicPartition) -> Option<Checkpoint> {
+        self.checkpoints.get(partition).copied()
+    }
+
+    pub fn all(&self) -> &BTreeMap<TopicPartition, Checkpoint> {
+        &self.checkpoints
+    }
+
+    /// Records `checkpoints` and writes every checkpoint out. Nothing
+    /// changes when they can't be written.
+    pub fn store(
+        &mut self,
+        checkpoints: impl IntoIterator<Item = (TopicPartition, Checkpoint)>,
+    ) -> io::Result<()> {
+        let previous = self.checkpoints.clone();
+        self.checkpoints.extend(checkpoints);
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(TMP_SUFFIX);
+        let written = File::create(&tmp)
+            .and_then(|mut file| {
+                file.write_all(&encode(&self.checkpoints))?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&tmp, path));
+        if written.is_err() {
+            self.checkpoints = previous;
+        }
+        written
+    }
+}
+
+/// A u32 count, then for each checkpoint its topic, partition, source
+/// offset and target offset.
+fn encode(checkpoints: &BTreeMap<TopicPartition, Checkpoint>) -> Bytes {
+    let mut buf = BytesMut::new();
+    buf.put_u32(checkpoints.len() as u32);
+    for (partition, checkpoint) in checkpoints {
+        put_str(&mut buf, &partition.topic);
+        buf.put_u32(partition.partition);
+        buf.put_u64(checkpoint.source_offset);
+        buf.put_u64(checkpoint.target_offset);
+    }
+    buf.freeze()
+}
+
+fn decode(mut bytes: Bytes) -> Option<BTreeMap<TopicPartition, Checkpoint>> {
+    if bytes.remaining() < 4 {
+        return None;
+    }
+    let mut checkpoints = BTreeMap::new();
+    for _ in 0..bytes.get_u32() {
+        let topic = get_str(&mut bytes)?;
+        if bytes.remaining() < 4 + 8 + 8 {
+            return None;
+        }
+        let partition = TopicPartition::new(topic, bytes.get_u32());
+        let checkpoint = Checkpoint {
+            source_offset: bytes.get_u64(),
+            target_offset: bytes.get_u64(),
+        };
+        checkpoints.insert(partition, checkpoint);
+    }
+    (!bytes.has_remaining()).then_some(checkpoints)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CHECKPOINT_FILE);
+        let mut store = CheckpointStore::open(&path).unwrap();
+        assert!(store.all().is_empty());
+
+        let events = |partition| TopicPartition::new("events", partition);
+        let checkpoint = |source_offset, target_offset| Checkpoint {
+            source_offset,
+            target_offset,
+        };
+        store
+            .store([(events(0), checkpoint(5, 2)), (events(1), checkpoint(3, 3))])
+            .unwrap();
+        store.store([(events(0), checkpoint(9, 6))]).unwrap();
+
+        let reopened = CheckpointStore::open(&path).unwrap();
+        assert_eq!(reopened.get(&events(0)), Some(checkpoint(9, 6)));
+        assert_eq!(reopened.get(&events(1)), Some(checkpoint(3, 3)));
+        assert_eq!(reopened.get(&events(2)), None);
+
+        fs::write(&path, b"\x00\x00\x00\x01").unwrap();
+        assert!(CheckpointStore::open(&path).is_err());
+    }
+}
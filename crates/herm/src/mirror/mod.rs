@@ -0,0 +1,6 @@
+mod checkpoint;
+mod runner;
+mod target;
+pub use checkpoint::{Checkpoint, CheckpointStore, CHECKPOINT_FILE};
+pub use runner::{Mirror, MirrorConfig, MirrorError};
+pub use target::{ClusterTarget, MirrorTarget, TargetError};
@@ -0,0 +1,344 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use super::{Checkpoint, CheckpointStore, ClusterTarget, MirrorTarget, TargetError};
+use crate::client::{
+    Client, ClientError, ClusterClient, ConsumeError, Consumer, ConsumerConfig,
+    DEFAULT_FETCH_MAX_BYTES, DEFAULT_FETCH_MAX_WAIT,
+};
+use crate::protocol::TopicPartition;
+use crate::record::Record;
+
+#[derive(Error, Debug)]
+pub enum MirrorError {
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    #[error(transparent)]
+    Consume(#[from] ConsumeError),
+    #[error(transparent)]
+    Target(#[from] TargetError),
+    #[error("Unknown source topic {0}")]
+    UnknownTopic(String),
+    #[error("Failed to store checkpoints: {0}")]
+    Checkpoint(#[from] std::io::Error),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MirrorConfig {
+    /// Source topics mirrored, every partition of each.
+    pub topics: Vec<String>,
+    /// Prepended to the names of the topics in the target, e.g. `dc1.` to
+    /// tell mirrored topics apart from local ones.
+    pub target_prefix: String,
+    pub fetch_max_bytes: u32,
+    pub fetch_max_wait: Duration,
+}
+
+impl Default for MirrorConfig {
+    fn default() -> Self {
+        MirrorConfig {
+            topics: vec![],
+            target_prefix: String::new(),
+            fetch_max_bytes: DEFAULT_FETCH_MAX_BYTES,
+            fetch_max_wait: DEFAULT_FETCH_MAX_WAIT,
+        }
+    }
+}
+
+/// Copies topics of a source cluster into a [`MirrorTarget`], partition by
+/// partition so records keep their order, for disaster recovery or to
+/// migrate between clusters.
+///
+/// Each partition is checkpointed once its records are in the target, and
+/// a mirror started over the same [`CheckpointStore`] picks up from there.
+/// Records are mirrored at least once: a crash between appending and
+/// checkpointing mirrors them again.
+#[derive(Debug)]
+pub struct Mirror<S = Client, T = ClusterTarget> {
+    consumer: Consumer<S>,
+    target: T,
+    config: MirrorConfig,
+    checkpoints: CheckpointStore,
+}
+
+impl<S: ClusterClient, T: MirrorTarget> Mirror<S, T> {
+    /// Assigns every partition of the configured topics, each starting at
+    /// its checkpoint, or at its first record kept if it has none.
+    pub async fn start(
+        source: Arc<S>,
+        target: T,
+        config: MirrorConfig,
+        checkpoints: CheckpointStore,
+    ) -> Result<Self, MirrorError> {
+        let mut partitions = vec![];
+        for topic in &config.topics {
+            let count = source
+                .partition_count(topic)
+                .await?
+                .ok_or_else(|| MirrorError::UnknownTopic(topic.clone()))?;
+            partitions.extend((0..count).map(|partition| TopicPartition::new(topic, partition)));
+        }
+
+        let consumer_config = ConsumerConfig {
+            group: None,
+            fetch_max_bytes: config.fetch_max_bytes,
+            fetch_max_wait: config.fetch_max_wait,
+            auto_commit_interval: None,
+            // Chunks are copied as they are, for the target's consumers to join
+            reassembly_buffer_bytes: None,
+        };
+        let mut consumer = Consumer::new(source, consumer_config);
+        consumer.assign(partitions.clone()).await?;
+        consumer.seek_to_beginning().await?;
+        for partition in &partitions {
+            if let Some(checkpoint) = checkpoints.get(partition) {
+                consumer.seek(partition, checkpoint.source_offset)?;
+            }
+        }
+
+        Ok(Mirror {
+            consumer,
+            target,
+            config,
+            checkpoints,
+        })
+    }
+
+    /// Appends what one poll of the source returns to the target and
+    /// checkpoints it, returning how many records were mirrored.
+    ///
+    /// A partition whose append fails goes back to where it was, to be read
+    /// again by the next poll, and the first failure is returned once the
+    /// others are done.
+    pub async fn poll(&mut self) -> Result<usize, MirrorError> {
+        let mut polled: BTreeMap<TopicPartition, Vec<Record>> = BTreeMap::new();
+        for consumed in self.consumer.poll().await? {
+            polled
+                .entry(consumed.partition)
+                .or_default()
+                .push(consumed.record);
+        }
+
+        let mut mirrored = 0;
+        let mut checkpoints = vec![];
+        let mut failed = None;
+        for (partition, records) in polled {
+            let first = records[0].offset;
+            let next = records[records.len() - 1].offset + 1;
+            let count = records.len();
+            match self
+                .target
+                .append(&self.target_partition(&partition), records)
+                .await
+            {
+                Ok(base_offset) => {
+                    let checkpoint = Checkpoint {
+                        source_offset: next,
+                        target_offset: base_offset + count as u64,
+                    };
+                    checkpoints.push((partition, checkpoint));
+                    mirrored += count;
+                }
+                Err(err) => {
+                    self.consumer.seek(&partition, first)?;
+                    failed.get_or_insert(err.into());
+                }
+            }
+        }
+
+        self.checkpoints.store(checkpoints)?;
+        match failed {
+            Some(err) => Err(err),
+            None => Ok(mirrored),
+        }
+    }
+
+    /// Mirrors until a poll fails.
+    pub async fn run(mut self) -> Result<(), MirrorError> {
+        loop {
+            self.poll().await?;
+        }
+    }
+
+    /// Where each partition mirrored so far is at, by source partition.
+    pub fn checkpoints(&self) -> &BTreeMap<TopicPartition, Checkpoint> {
+        self.checkpoints.all()
+    }
+
+    /// The partition of the target `partition` of the source is mirrored
+    /// to, the same one of the prefixed topic.
+    pub fn target_partition(&self, partition: &TopicPartition) -> TopicPartition {
+        TopicPartition::new(
+            format!("{}{}", self.config.target_prefix, partition.topic),
+            partition.partition,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::broker::{LogHandler, Server};
+    use crate::mirror::CHECKPOINT_FILE;
+    use crate::protocol::ErrorCode;
+    use crate::record::RecordBatch;
+    use crate::request::Produce;
+    use crate::storage::{LogConfig, LogDirs};
+
+    async fn broker(partitions: &[TopicPartition]) -> (LogHandler, Arc<Client>) {
+        let handler = LogHandler::new(Arc::new(LogDirs::in_memory(LogConfig::default())));
+        for partition in partitions {
+            handler.logs().create(partition).unwrap();
+        }
+        let server = Server::bind("127.0.0.1:0", handler.clone()).await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        handler.replicas().set_advertised_listener(addr.clone());
+        tokio::spawn(server.run());
+        (handler, Arc::new(Client::new(vec![addr])))
+    }
+
+    async fn produce(client: &Client, topic: &str, partition: u32, values: &[&'static str]) {
+        let records = values
+            .iter()
+            .map(|value| Record::new(None, Some(Bytes::from_static(value.as_bytes()))))
+            .collect();
+        let produce =
+            Produce::new(topic.to_string(), partition, RecordBatch::new(records)).unwrap();
+        client.produce(produce).await.unwrap();
+    }
+
+    fn values(handler: &LogHandler, partition: &TopicPartition) -> Vec<Bytes> {
+        let log = handler.logs().get(partition).unwrap();
+        let batches = log.read().unwrap().read(0, usize::MAX).unwrap();
+        batches
+            .into_iter()
+            .flat_map(|batch| batch.records)
+            .map(|record| record.value.unwrap())
+            .collect()
+    }
+
+    fn config() -> MirrorConfig {
+        MirrorConfig {
+            topics: vec!["events".to_string()],
+            target_prefix: "dc1.".to_string(),
+            fetch_max_wait: Duration::from_millis(10),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mirror_and_resume() {
+        let source_partitions = [0, 1].map(|partition| TopicPartition::new("events", partition));
+        let target_partitions =
+            [0, 1].map(|partition| TopicPartition::new("dc1.events", partition));
+        let (_, source) = broker(&source_partitions).await;
+        let (target_handler, target) = broker(&target_partitions).await;
+        // Target offsets run ahead of the source ones
+        produce(&target, "dc1.events", 0, &["local"]).await;
+        produce(&source, "events", 0, &["a", "b"]).await;
+        produce(&source, "events", 1, &["c"]).await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CHECKPOINT_FILE);
+        let mut mirror = Mirror::start(
+            source.clone(),
+            ClusterTarget::new(target.clone()),
+            config(),
+            CheckpointStore::open(&path).unwrap(),
+        )
+        .await
+        .unwrap();
+        let mut mirrored = 0;
+        while mirrored < 3 {
+            mirrored += mirror.poll().await.unwrap();
+        }
+        assert_eq!(
+            values(&target_handler, &target_partitions[0]),
+            ["local", "a", "b"]
+        );
+        assert_eq!(values(&target_handler, &target_partitions[1]), ["c"]);
+        assert_eq!(
+            mirror.checkpoints().get(&source_partitions[0]),
+            Some(&Checkpoint {
+                source_offset: 2,
+                target_offset: 3
+            })
+        );
+        drop(mirror);
+
+        produce(&source, "events", 0, &["d"]).await;
+        let mut mirror = Mirror::start(
+            source,
+            ClusterTarget::new(target),
+            config(),
+            CheckpointStore::open(&path).unwrap(),
+        )
+        .await
+        .unwrap();
+        while mirror.poll().await.unwrap() == 0 {}
+        assert_eq!(
+            values(&target_handler, &target_partitions[0]),
+            ["local", "a", "b", "d"]
+        );
+        assert_eq!(values(&target_handler, &target_partitions[1]), ["c"]);
+    }
+
+    /// Fails the first append, then keeps what it is given.
+    #[derive(Debug, Default)]
+    struct FlakyTarget {
+        failed: Mutex<bool>,
+        appended: Mutex<Vec<Record>>,
+    }
+
+    impl MirrorTarget for Arc<FlakyTarget> {
+        async fn append(
+            &self,
+            partition: &TopicPartition,
+            records: Vec<Record>,
+        ) -> Result<u64, TargetError> {
+            if !std::mem::replace(&mut *self.failed.lock().unwrap(), true) {
+                return Err(TargetError::Partition(
+                    partition.clone(),
+                    ErrorCode::NotLeaderOrFollower,
+                ));
+            }
+            let mut appended = self.appended.lock().unwrap();
+            let base_offset = appended.len() as u64;
+            appended.extend(records);
+            Ok(base_offset)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_append() {
+        let (_, source) = broker(&[TopicPartition::new("events", 0)]).await;
+        produce(&source, "events", 0, &["a", "b"]).await;
+
+        let target = Arc::new(FlakyTarget::default());
+        let mut mirror = Mirror::start(
+            source,
+            target.clone(),
+            config(),
+            CheckpointStore::in_memory(),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            mirror.poll().await,
+            Err(MirrorError::Target(TargetError::Partition(_, _)))
+        ));
+        assert!(mirror.checkpoints().is_empty());
+
+        assert_eq!(mirror.poll().await.unwrap(), 2);
+        let appended = target.appended.lock().unwrap();
+        assert_eq!(appended.len(), 2);
+        assert_eq!(appended[0].value.as_deref(), Some(&b"a"[..]));
+    }
+}
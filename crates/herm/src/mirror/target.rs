@@ -0,0 +1,70 @@
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::Arc;
+
+use thiserror::Error;
+
+use crate::client::{Client, ClientError, ClusterClient};
+use crate::protocol::{ErrorCode, TopicPartition};
+use crate::record::{Record, RecordBatch};
+use crate::request::{Produce, ProduceCreationError};
+
+#[derive(Error, Debug)]
+pub enum TargetError {
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    #[error(transparent)]
+    Produce(#[from] ProduceCreationError),
+    #[error("Target answered {1} for {0}")]
+    Partition(TopicPartition, ErrorCode),
+}
+
+/// Where a [`Mirror`](super::Mirror) writes the records it reads. A
+/// [`ClusterTarget`] writes to another herm cluster, other systems plug in
+/// by implementing it.
+pub trait MirrorTarget: Debug + Send + Sync + 'static {
+    /// Appends `records` to `partition` in order, returning the offset the
+    /// first one got.
+    fn append(
+        &self,
+        partition: &TopicPartition,
+        records: Vec<Record>,
+    ) -> impl Future<Output = Result<u64, TargetError>> + Send;
+}
+
+/// Produces to a herm cluster through a [`Client`], or any other
+/// [`ClusterClient`], a batch per append.
+#[derive(Debug)]
+pub struct ClusterTarget<C = Client> {
+    client: Arc<C>,
+}
+
+impl<C: ClusterClient> ClusterTarget<C> {
+    pub fn new(client: Arc<C>) -> Self {
+        ClusterTarget { client }
+    }
+}
+
+impl<C: ClusterClient> MirrorTarget for ClusterTarget<C> {
+    async fn append(
+        &self,
+        partition: &TopicPartition,
+        records: Vec<Record>,
+    ) -> Result<u64, TargetError> {
+        let produce = Produce::new(
+            partition.topic.clone(),
+            partition.partition,
+            RecordBatch::new(records),
+        )?;
+        // Produced with the default acks, which are always answered
+        let response = self
+            .client
+            .produce(produce)
+            .await?
+            .ok_or(ClientError::Disconnected)?;
+        if !response.error.is_ok() {
+            return Err(TargetError::Partition(partition.clone(), response.error));
+        }
+        Ok(response.base_offset)
+    }
+}
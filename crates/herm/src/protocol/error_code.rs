@@ -0,0 +1,187 @@
+use std::fmt::Display;
+
+/// Outcome of a request, carried by every response. Numbered after their
+/// Kafka counterparts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(i16)]
+pub enum ErrorCode {
+    UnknownServerError = -1,
+    None = 0,
+    OffsetOutOfRange = 1,
+    CorruptMessage = 2,
+    UnknownTopicOrPartition = 3,
+    NotLeaderOrFollower = 6,
+    RequestTimedOut = 7,
+    /// The request is over the broker's frame or decode limits.
+    MessageTooLarge = 10,
+    InvalidTopic = 17,
+    /// The produce's acks can't be honoured for its records.
+    InvalidRequiredAcks = 21,
+    /// A group member is behind on the group's generation.
+    IllegalGeneration = 22,
+    /// A member joining shares no assignor with the rest of its group.
+    InconsistentGroupProtocol = 23,
+    UnknownMemberId = 25,
+    /// The group is rebalancing, the member has to rejoin.
+    RebalanceInProgress = 27,
+    TopicAuthorizationFailed = 29,
+    GroupAuthorizationFailed = 30,
+    ClusterAuthorizationFailed = 31,
+    /// A record's time is out of the range the broker accepts.
+    InvalidTimestamp = 32,
+    TopicAlreadyExists = 36,
+    InvalidPartitions = 37,
+    InvalidRequest = 42,
+    /// A topic config is unknown or has a value it can't take.
+    InvalidConfig = 40,
+    /// The request is over a limit the broker is configured with.
+    PolicyViolation = 44,
+    StorageError = 56,
+    ReassignmentInProgress = 60,
+    GroupIdNotFound = 69,
+    FencedLeaderEpoch = 74,
+    UnknownLeaderEpoch = 76,
+    BrokerIdNotRegistered = 102,
+}
+
+impl ErrorCode {
+    /// Codes this broker doesn't know are read as [`ErrorCode::UnknownServerError`].
+    pub fn from_code(code: i16) -> Self {
+        match code {
+            0 => ErrorCode::None,
+            1 => ErrorCode::OffsetOutOfRange,
+            2 => ErrorCode::CorruptMessage,
+            3 => ErrorCode::UnknownTopicOrPartition,
+            6 => ErrorCode::NotLeaderOrFollower,
+            7 => ErrorCode::RequestTimedOut,
+            10 => ErrorCode::MessageTooLarge,
+            17 => ErrorCode::InvalidTopic,
+            21 => ErrorCode::InvalidRequiredAcks,
+            22 => ErrorCode::IllegalGeneration,
+            23 => ErrorCode::InconsistentGroupProtocol,
+            25 => ErrorCode::UnknownMemberId,
+            27 => ErrorCode::RebalanceInProgress,
+            29 => ErrorCode::TopicAuthorizationFailed,
+            30 => ErrorCode::GroupAuthorizationFailed,
+            31 => ErrorCode::ClusterAuthorizationFailed,
+            32 => ErrorCode::InvalidTimestamp,
+            36 => ErrorCode::TopicAlreadyExists,
+            37 => ErrorCode::InvalidPartitions,
+            40 => ErrorCode::InvalidConfig,
+            42 => ErrorCode::InvalidRequest,
+            44 => ErrorCode::PolicyViolation,
+            56 => ErrorCode::StorageError,
+            60 => ErrorCode::ReassignmentInProgress,
+            69 => ErrorCode::GroupIdNotFound,
+            74 => ErrorCode::FencedLeaderEpoch,
+            76 => ErrorCode::UnknownLeaderEpoch,
+            102 => ErrorCode::BrokerIdNotRegistered,
+            _ => ErrorCode::UnknownServerError,
+        }
+    }
+
+    pub fn code(&self) -> i16 {
+        *self as i16
+    }
+
+    pub fn is_ok(&self) -> bool {
+        *self == ErrorCode::None
+    }
+
+    /// Whether the same request may succeed later, once a leader moved,
+    /// a partition came up or a broker caught up.
+    pub fn is_retriable(&self) -> bool {
+        matches!(
+            self,
+            ErrorCode::CorruptMessage
+                | ErrorCode::UnknownTopicOrPartition
+                | ErrorCode::NotLeaderOrFollower
+                | ErrorCode::RequestTimedOut
+                | ErrorCode::StorageError
+                | ErrorCode::FencedLeaderEpoch
+                | ErrorCode::UnknownLeaderEpoch
+        )
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorCode::UnknownServerError => "UnknownServerError",
+            ErrorCode::None => "None",
+            ErrorCode::OffsetOutOfRange => "OffsetOutOfRange",
+            ErrorCode::CorruptMessage => "CorruptMessage",
+            ErrorCode::UnknownTopicOrPartition => "UnknownTopicOrPartition",
+            ErrorCode::NotLeaderOrFollower => "NotLeaderOrFollower",
+            ErrorCode::RequestTimedOut => "RequestTimedOut",
+            ErrorCode::MessageTooLarge => "MessageTooLarge",
+            ErrorCode::InvalidTopic => "InvalidTopic",
+            ErrorCode::InvalidRequiredAcks => "InvalidRequiredAcks",
+            ErrorCode::IllegalGeneration => "IllegalGeneration",
+            ErrorCode::InconsistentGroupProtocol => "InconsistentGroupProtocol",
+            ErrorCode::UnknownMemberId => "UnknownMemberId",
+            ErrorCode::RebalanceInProgress => "RebalanceInProgress",
+            ErrorCode::TopicAuthorizationFailed => "TopicAuthorizationFailed",
+            ErrorCode::GroupAuthorizationFailed => "GroupAuthorizationFailed",
+            ErrorCode::ClusterAuthorizationFailed => "ClusterAuthorizationFailed",
+            ErrorCode::InvalidTimestamp => "InvalidTimestamp",
+            ErrorCode::TopicAlreadyExists => "TopicAlreadyExists",
+            ErrorCode::InvalidPartitions => "InvalidPartitions",
+            ErrorCode::InvalidConfig => "InvalidConfig",
+            ErrorCode::InvalidRequest => "InvalidRequest",
+            ErrorCode::PolicyViolation => "PolicyViolation",
+            ErrorCode::StorageError => "StorageError",
+            ErrorCode::ReassignmentInProgress => "ReassignmentInProgress",
+            ErrorCode::GroupIdNotFound => "GroupIdNotFound",
+            ErrorCode::FencedLeaderEpoch => "FencedLeaderEpoch",
+            ErrorCode::UnknownLeaderEpoch => "UnknownLeaderEpoch",
+            ErrorCode::BrokerIdNotRegistered => "BrokerIdNotRegistered",
+        }
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes() {
+        for code in [
+            ErrorCode::UnknownServerError,
+            ErrorCode::None,
+            ErrorCode::OffsetOutOfRange,
+            ErrorCode::RequestTimedOut,
+            ErrorCode::MessageTooLarge,
+            ErrorCode::TopicAuthorizationFailed,
+            ErrorCode::StorageError,
+            ErrorCode::NotLeaderOrFollower,
+            ErrorCode::ReassignmentInProgress,
+            ErrorCode::FencedLeaderEpoch,
+            ErrorCode::UnknownLeaderEpoch,
+            ErrorCode::BrokerIdNotRegistered,
+            ErrorCode::GroupAuthorizationFailed,
+            ErrorCode::GroupIdNotFound,
+            ErrorCode::IllegalGeneration,
+            ErrorCode::InconsistentGroupProtocol,
+            ErrorCode::UnknownMemberId,
+            ErrorCode::RebalanceInProgress,
+            ErrorCode::TopicAlreadyExists,
+            ErrorCode::InvalidPartitions,
+            ErrorCode::InvalidRequiredAcks,
+            ErrorCode::InvalidTimestamp,
+            ErrorCode::InvalidConfig,
+            ErrorCode::PolicyViolation,
+        ] {
+            assert_eq!(ErrorCode::from_code(code.code()), code);
+        }
+        assert_eq!(ErrorCode::from_code(1000), ErrorCode::UnknownServerError);
+        assert!(ErrorCode::None.is_ok());
+        assert!(ErrorCode::NotLeaderOrFollower.is_retriable());
+        assert!(!ErrorCode::None.is_retriable());
+        assert!(!ErrorCode::TopicAuthorizationFailed.is_retriable());
+    }
+}
@@ -0,0 +1,302 @@
+use std::fmt::Display;
+use thiserror::Error;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use super::wire::{get_str, put_str, str_size};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum HeaderError {
+    #[error("Client id is too long")]
+    ClientIdTooLong,
+    #[error("Unknown api key {0}")]
+    UnknownApiKey(u16),
+    #[error("Malformed bytes")]
+    MalformedBytes,
+}
+
+/// Identifies the request type. Numbered after their Kafka counterparts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u16)]
+pub enum ApiKey {
+    Produce = 0,
+    Fetch = 1,
+    /// Finds a partition's offset for a timestamp, see
+    /// [`ListOffsets`](crate::request::ListOffsets).
+    ListOffsets = 2,
+    Metadata = 3,
+    /// Sent between brokers to make one lead or follow a partition.
+    LeaderAndIsr = 4,
+    /// Stores a consumer group's offsets, see
+    /// [`GroupCoordinator`](crate::group::GroupCoordinator).
+    OffsetCommit = 8,
+    JoinGroup = 11,
+    /// A consumer group member's heartbeat, unlike
+    /// [`BrokerHeartbeat`](ApiKey::BrokerHeartbeat).
+    Heartbeat = 12,
+    LeaveGroup = 13,
+    SyncGroup = 14,
+    DescribeGroups = 15,
+    ListGroups = 16,
+    /// Kafka's CreateTopics, for a single topic.
+    CreateTopic = 19,
+    /// Kafka's DeleteTopics, for a single topic.
+    DeleteTopic = 20,
+    DescribeLogDirs = 35,
+    /// Kafka's AlterPartitionReassignments, for a single partition.
+    ReassignPartition = 45,
+    DescribeCluster = 60,
+    /// Sent between brokers to say they are alive, see
+    /// [`BrokerHeartbeat`](crate::request::BrokerHeartbeat).
+    BrokerHeartbeat = 63,
+    /// herm's own, see [`EstimateCleanup`](crate::request::EstimateCleanup).
+    EstimateCleanup = 1000,
+}
+
+impl ApiKey {
+    /// Every api key, in order.
+    pub const ALL: [ApiKey; 19] = [
+        ApiKey::Produce,
+        ApiKey::Fetch,
+        ApiKey::ListOffsets,
+        ApiKey::Metadata,
+        ApiKey::LeaderAndIsr,
+        ApiKey::OffsetCommit,
+        ApiKey::JoinGroup,
+        ApiKey::Heartbeat,
+        ApiKey::LeaveGroup,
+        ApiKey::SyncGroup,
+        ApiKey::DescribeGroups,
+        ApiKey::ListGroups,
+        ApiKey::CreateTopic,
+        ApiKey::DeleteTopic,
+        ApiKey::DescribeLogDirs,
+        ApiKey::ReassignPartition,
+        ApiKey::DescribeCluster,
+        ApiKey::BrokerHeartbeat,
+        ApiKey::EstimateCleanup,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ApiKey::Produce => "Produce",
+            ApiKey::Fetch => "Fetch",
+            ApiKey::ListOffsets => "ListOffsets",
+            ApiKey::Metadata => "Metadata",
+            ApiKey::LeaderAndIsr => "LeaderAndIsr",
+            ApiKey::OffsetCommit => "OffsetCommit",
+            ApiKey::JoinGroup => "JoinGroup",
+            ApiKey::Heartbeat => "Heartbeat",
+            ApiKey::LeaveGroup => "LeaveGroup",
+            ApiKey::SyncGroup => "SyncGroup",
+            ApiKey::DescribeGroups => "DescribeGroups",
+            ApiKey::ListGroups => "ListGroups",
+            ApiKey::CreateTopic => "CreateTopic",
+            ApiKey::DeleteTopic => "DeleteTopic",
+            ApiKey::DescribeLogDirs => "DescribeLogDirs",
+            ApiKey::ReassignPartition => "ReassignPartition",
+            ApiKey::DescribeCluster => "DescribeCluster",
+            ApiKey::BrokerHeartbeat => "BrokerHeartbeat",
+            ApiKey::EstimateCleanup => "EstimateCleanup",
+        }
+    }
+
+    /// Position in [`ApiKey::ALL`], the numbering has gaps.
+    pub fn index(&self) -> usize {
+        ApiKey::ALL
+            .iter()
+            .position(|api_key| api_key == self)
+            .unwrap()
+    }
+}
+
+impl TryFrom<u16> for ApiKey {
+    type Error = HeaderError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ApiKey::Produce),
+            1 => Ok(ApiKey::Fetch),
+            2 => Ok(ApiKey::ListOffsets),
+            3 => Ok(ApiKey::Metadata),
+            4 => Ok(ApiKey::LeaderAndIsr),
+            8 => Ok(ApiKey::OffsetCommit),
+            11 => Ok(ApiKey::JoinGroup),
+            12 => Ok(ApiKey::Heartbeat),
+            13 => Ok(ApiKey::LeaveGroup),
+            14 => Ok(ApiKey::SyncGroup),
+            15 => Ok(ApiKey::DescribeGroups),
+            16 => Ok(ApiKey::ListGroups),
+            19 => Ok(ApiKey::CreateTopic),
+            20 => Ok(ApiKey::DeleteTopic),
+            35 => Ok(ApiKey::DescribeLogDirs),
+            45 => Ok(ApiKey::ReassignPartition),
+            60 => Ok(ApiKey::DescribeCluster),
+            63 => Ok(ApiKey::BrokerHeartbeat),
+            1000 => Ok(ApiKey::EstimateCleanup),
+            _ => Err(HeaderError::UnknownApiKey(value)),
+        }
+    }
+}
+
+impl Display for ApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Prefixes every request on the wire, ahead of the request body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestHeader {
+    pub api_key: ApiKey,
+    pub correlation_id: u32,
+    pub client_id: String,
+}
+
+impl RequestHeader {
+    /// Largest a header can be encoded, with the longest client id.
+    pub const MAX_SIZE: usize = 2 + 4 + 2 + u16::MAX as usize;
+
+    pub fn new(
+        api_key: ApiKey,
+        correlation_id: u32,
+        client_id: String,
+    ) -> Result<Self, HeaderError> {
+        if client_id.len() > u16::MAX as usize {
+            return Err(HeaderError::ClientIdTooLong);
+        }
+
+        Ok(RequestHeader {
+            api_key,
+            correlation_id,
+            client_id,
+        })
+    }
+
+    /// Reads the header off the front of `bytes`, leaving the body behind.
+    pub fn decode(bytes: &mut Bytes) -> Result<Self, HeaderError> {
+        if bytes.remaining() < 2 + 4 {
+            return Err(HeaderError::MalformedBytes);
+        }
+
+        let api_key = ApiKey::try_from(bytes.get_u16())?;
+        let correlation_id = bytes.get_u32();
+        let client_id = get_str(bytes).ok_or(HeaderError::MalformedBytes)?;
+
+        Ok(RequestHeader {
+            api_key,
+            correlation_id,
+            client_id,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_u16(self.api_key as u16);
+        buf.put_u32(self.correlation_id);
+        put_str(buf, &self.client_id);
+    }
+
+    pub fn size(&self) -> usize {
+        2 + 4 + str_size(&self.client_id)
+    }
+}
+
+/// Prefixes every response on the wire, tying it to its request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseHeader {
+    pub correlation_id: u32,
+}
+
+impl ResponseHeader {
+    pub fn new(correlation_id: u32) -> Self {
+        ResponseHeader { correlation_id }
+    }
+
+    /// Reads the header off the front of `bytes`, leaving the body behind.
+    pub fn decode(bytes: &mut Bytes) -> Result<Self, HeaderError> {
+        if bytes.remaining() < 4 {
+            return Err(HeaderError::MalformedBytes);
+        }
+
+        Ok(ResponseHeader {
+            correlation_id: bytes.get_u32(),
+        })
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_u32(self.correlation_id);
+    }
+
+    pub fn size(&self) -> usize {
+        4
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let header = RequestHeader::new(ApiKey::Fetch, 7, "client-1".to_string()).unwrap();
+        let mut bytes = header.to_bytes();
+        assert_eq!(bytes.len(), header.size());
+
+        assert_eq!(RequestHeader::decode(&mut bytes).unwrap(), header);
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_api_keys() {
+        for (index, api_key) in ApiKey::ALL.into_iter().enumerate() {
+            assert_eq!(ApiKey::try_from(api_key as u16), Ok(api_key));
+            assert_eq!(api_key.index(), index);
+        }
+    }
+
+    #[test]
+    fn test_decode_leaves_body() {
+        let header = RequestHeader::new(ApiKey::Fetch, 7, "".to_string()).unwrap();
+        let mut buf = BytesMut::new();
+        header.encode_into(&mut buf);
+        buf.put_u32(42);
+
+        let mut bytes = buf.freeze();
+        RequestHeader::decode(&mut bytes).unwrap();
+        assert_eq!(bytes.get_u32(), 42);
+    }
+
+    #[test]
+    fn test_malformed_bytes() {
+        assert_eq!(
+            RequestHeader::decode(&mut Bytes::new()),
+            Err(HeaderError::MalformedBytes)
+        );
+
+        // Unknown api key
+        assert_eq!(
+            RequestHeader::decode(&mut Bytes::from_static(&[
+                0xFF, 0xFF, // Api key
+                0x00, 0x00, 0x00, 0x01, // Correlation id
+                0x00, 0x00, // Client id length
+            ])),
+            Err(HeaderError::UnknownApiKey(0xFFFF))
+        );
+
+        // Client id is cut short
+        assert_eq!(
+            RequestHeader::decode(&mut Bytes::from_static(&[
+                0x00, 0x01, // Api key
+                0x00, 0x00, 0x00, 0x01, // Correlation id
+                0x00, 0x04, b'a', b'b', // Client id
+            ])),
+            Err(HeaderError::MalformedBytes)
+        );
+    }
+}
@@ -0,0 +1,513 @@
+use std::fmt::Display;
+use thiserror::Error;
+
+use super::header::ApiKey;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum InspectError {
+    #[error("Unknown api key {0}")]
+    UnknownApiKey(u16),
+    #[error("Bytes end inside {field} at offset {offset}")]
+    Truncated { field: String, offset: usize },
+    #[error("Field {field} at offset {offset} is not valid utf-8")]
+    InvalidUtf8 { field: String, offset: usize },
+    #[error("{len} unexpected bytes after the message at offset {offset}")]
+    TrailingBytes { offset: usize, len: usize },
+}
+
+/// One decoded field, or a group of them, with its position in the input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InspectedField {
+    pub name: String,
+    pub offset: usize,
+    pub len: usize,
+    pub value: Option<String>,
+    pub children: Vec<InspectedField>,
+}
+
+impl InspectedField {
+    fn write_tree(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        let range = format!("[{}..{}]", self.offset, self.offset + self.len);
+        write!(
+            f,
+            "{:indent$}{:<12} {}",
+            "",
+            range,
+            self.name,
+            indent = depth * 2
+        )?;
+        if let Some(value) = &self.value {
+            write!(f, ": {}", value)?;
+        }
+        writeln!(f)?;
+
+        for child in &self.children {
+            child.write_tree(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// A request broken down field by field, see [`inspect`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct InspectedMessage {
+    pub api_key: ApiKey,
+    pub len: usize,
+    pub fields: Vec<InspectedField>,
+}
+
+impl InspectedMessage {
+    /// Looks up a field by its dotted path, like `header.correlation_id`.
+    pub fn field(&self, path: &str) -> Option<&InspectedField> {
+        let mut fields = &self.fields;
+        let mut found = None;
+
+        for name in path.split('.') {
+            let field = fields.iter().find(|field| field.name == name)?;
+            fields = &field.children;
+            found = Some(field);
+        }
+        found
+    }
+}
+
+impl Display for InspectedMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} request ({} bytes)", self.api_key, self.len)?;
+        for field in &self.fields {
+            field.write_tree(f, 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Decodes a raw request (header followed by body) into a tree of fields with
+/// their offsets, for debugging captured traffic. Unlike the regular decoders
+/// it does not validate field values, so malformed requests can be looked at.
+pub fn inspect(bytes: &[u8]) -> Result<InspectedMessage, InspectError> {
+    let mut cursor = Cursor { bytes, pos: 0 };
+
+    let header_start = cursor.pos;
+    let raw_api_key = cursor.read_u16("api_key")?;
+    let api_key =
+        ApiKey::try_from(raw_api_key).map_err(|_| InspectError::UnknownApiKey(raw_api_key))?;
+    let header = vec![
+        cursor.field(
+            "api_key",
+            header_start,
+            format!("{} ({})", raw_api_key, api_key),
+        ),
+        cursor.u32("correlation_id")?,
+        cursor.string("client_id")?,
+    ];
+    let header = cursor.group("header", header_start, header);
+
+    let body_start = cursor.pos;
+    let body = match api_key {
+        ApiKey::Produce => vec![
+            cursor.string("topic")?,
+            cursor.u32("partition")?,
+            cursor.i16("acks")?,
+            cursor.u32("timeout_ms")?,
+            cursor.u32("leader_epoch")?,
+            cursor.record_batch("batch")?,
+        ],
+        ApiKey::Fetch => vec![
+            cursor.string("topic")?,
+            cursor.u32("partition")?,
+            cursor.u64("offset")?,
+            cursor.u32("size")?,
+            cursor.u32("min_bytes")?,
+            cursor.u32("max_wait_ms")?,
+            cursor.u32("replica_id")?,
+            cursor.u32("leader_epoch")?,
+        ],
+        ApiKey::ListOffsets => vec![
+            cursor.string("topic")?,
+            cursor.u32("partition")?,
+            cursor.u64("timestamp")?,
+        ],
+        ApiKey::Metadata => {
+            vec![cursor.array("topics", |cursor| Ok(vec![cursor.string("topic")?]))?]
+        }
+        ApiKey::LeaderAndIsr => vec![
+            cursor.string("topic")?,
+            cursor.u32("partition")?,
+            cursor.u32("leader_epoch")?,
+            cursor.string("leader")?,
+        ],
+        ApiKey::DescribeLogDirs => {
+            vec![cursor.array("topics", |cursor| Ok(vec![cursor.string("topic")?]))?]
+        }
+        ApiKey::DescribeCluster | ApiKey::ListGroups => vec![],
+        ApiKey::OffsetCommit => vec![
+            cursor.string("group")?,
+            cursor.array("offsets", |cursor| {
+                Ok(vec![
+                    cursor.string("topic")?,
+                    cursor.u32("partition")?,
+                    cursor.u64("offset")?,
+                ])
+            })?,
+        ],
+        ApiKey::DescribeGroups => {
+            vec![cursor.array("groups", |cursor| Ok(vec![cursor.string("group")?]))?]
+        }
+        ApiKey::JoinGroup => vec![
+            cursor.string("group")?,
+            cursor.string("member_id")?,
+            cursor.u32("session_timeout_ms")?,
+            cursor.u32("rebalance_timeout_ms")?,
+            cursor.array("topics", |cursor| Ok(vec![cursor.string("topic")?]))?,
+            cursor.array("assignors", |cursor| Ok(vec![cursor.string("assignor")?]))?,
+            cursor.array("owned", |cursor| {
+                Ok(vec![cursor.string("topic")?, cursor.u32("partition")?])
+            })?,
+        ],
+        ApiKey::SyncGroup => vec![
+            cursor.string("group")?,
+            cursor.u32("generation")?,
+            cursor.string("member_id")?,
+            cursor.array("assignments", |cursor| {
+                Ok(vec![
+                    cursor.string("member_id")?,
+                    cursor.array("partitions", |cursor| {
+                        Ok(vec![cursor.string("topic")?, cursor.u32("partition")?])
+                    })?,
+                ])
+            })?,
+        ],
+        ApiKey::Heartbeat => vec![
+            cursor.string("group")?,
+            cursor.u32("generation")?,
+            cursor.string("member_id")?,
+        ],
+        ApiKey::LeaveGroup => vec![cursor.string("group")?, cursor.string("member_id")?],
+        ApiKey::CreateTopic => vec![cursor.string("topic")?, cursor.u32("partitions")?],
+        ApiKey::DeleteTopic => vec![cursor.string("topic")?],
+        ApiKey::EstimateCleanup => vec![
+            cursor.string("topic")?,
+            cursor.u8("cleanup_policy")?,
+            cursor.i64("retention_ms")?,
+            cursor.i64("retention_bytes")?,
+        ],
+        ApiKey::ReassignPartition => vec![
+            cursor.string("topic")?,
+            cursor.u32("partition")?,
+            cursor.u32("target_id")?,
+            cursor.string("target")?,
+            cursor.u32("timeout_ms")?,
+        ],
+        ApiKey::BrokerHeartbeat => vec![
+            cursor.u32("broker_id")?,
+            cursor.string("version")?,
+            cursor.string("rack")?,
+            cursor.array("leaders", |cursor| {
+                Ok(vec![
+                    cursor.string("topic")?,
+                    cursor.u32("partition")?,
+                    cursor.u32("leader_epoch")?,
+                ])
+            })?,
+        ],
+    };
+    let body = cursor.group("body", body_start, body);
+
+    if cursor.pos != bytes.len() {
+        return Err(InspectError::TrailingBytes {
+            offset: cursor.pos,
+            len: bytes.len() - cursor.pos,
+        });
+    }
+
+    Ok(InspectedMessage {
+        api_key,
+        len: bytes.len(),
+        fields: vec![header, body],
+    })
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, name: &str, len: usize) -> Result<&'a [u8], InspectError> {
+        if self.bytes.len() - self.pos < len {
+            return Err(InspectError::Truncated {
+                field: name.to_string(),
+                offset: self.pos,
+            });
+        }
+
+        let taken = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(taken)
+    }
+
+    fn field(&self, name: &str, start: usize, value: String) -> InspectedField {
+        InspectedField {
+            name: name.to_string(),
+            offset: start,
+            len: self.pos - start,
+            value: Some(value),
+            children: vec![],
+        }
+    }
+
+    fn group(&self, name: &str, start: usize, children: Vec<InspectedField>) -> InspectedField {
+        InspectedField {
+            name: name.to_string(),
+            offset: start,
+            len: self.pos - start,
+            value: None,
+            children,
+        }
+    }
+
+    fn read_u16(&mut self, name: &str) -> Result<u16, InspectError> {
+        Ok(u16::from_be_bytes(self.take(name, 2)?.try_into().unwrap()))
+    }
+
+    fn u8(&mut self, name: &str) -> Result<InspectedField, InspectError> {
+        let start = self.pos;
+        let value = self.take(name, 1)?[0];
+        Ok(self.field(name, start, value.to_string()))
+    }
+
+    fn u16(&mut self, name: &str) -> Result<InspectedField, InspectError> {
+        let start = self.pos;
+        let value = self.read_u16(name)?;
+        Ok(self.field(name, start, value.to_string()))
+    }
+
+    fn i16(&mut self, name: &str) -> Result<InspectedField, InspectError> {
+        let start = self.pos;
+        let value = self.read_u16(name)? as i16;
+        Ok(self.field(name, start, value.to_string()))
+    }
+
+    fn u32(&mut self, name: &str) -> Result<InspectedField, InspectError> {
+        let start = self.pos;
+        let value = u32::from_be_bytes(self.take(name, 4)?.try_into().unwrap());
+        Ok(self.field(name, start, value.to_string()))
+    }
+
+    fn u64(&mut self, name: &str) -> Result<InspectedField, InspectError> {
+        let start = self.pos;
+        let value = u64::from_be_bytes(self.take(name, 8)?.try_into().unwrap());
+        Ok(self.field(name, start, value.to_string()))
+    }
+
+    fn i64(&mut self, name: &str) -> Result<InspectedField, InspectError> {
+        let start = self.pos;
+        let value = i64::from_be_bytes(self.take(name, 8)?.try_into().unwrap());
+        Ok(self.field(name, start, value.to_string()))
+    }
+
+    /// A u32 count, then that many items read by `item`, each grouped
+    /// under its index.
+    fn array(
+        &mut self,
+        name: &str,
+        mut item: impl FnMut(&mut Self) -> Result<Vec<InspectedField>, InspectError>,
+    ) -> Result<InspectedField, InspectError> {
+        let start = self.pos;
+        let count = u32::from_be_bytes(self.take("count", 4)?.try_into().unwrap());
+        let mut children = vec![self.field("count", start, count.to_string())];
+        for index in 0..count {
+            let item_start = self.pos;
+            let fields = item(self)?;
+            children.push(self.group(&index.to_string(), item_start, fields));
+        }
+        Ok(self.group(name, start, children))
+    }
+
+    /// Batch header fields, with the records left as one opaque field.
+    fn record_batch(&mut self, name: &str) -> Result<InspectedField, InspectError> {
+        let start = self.pos;
+        let base_offset = self.u64("base_offset")?;
+
+        let length_start = self.pos;
+        let length = u32::from_be_bytes(self.take("length", 4)?.try_into().unwrap());
+        let length_field = self.field("length", length_start, length.to_string());
+
+        let mut children = vec![
+            base_offset,
+            length_field,
+            self.u32("crc")?,
+            self.u16("attributes")?,
+            self.u32("last_offset_delta")?,
+            self.u64("first_timestamp")?,
+            self.u64("max_timestamp")?,
+            self.u32("count")?,
+        ];
+
+        let records_len = (length as usize).saturating_sub(4 + 2 + 4 + 8 + 8 + 4);
+        let records_start = self.pos;
+        self.take("records", records_len)?;
+        children.push(self.field("records", records_start, format!("{} bytes", records_len)));
+
+        Ok(self.group(name, start, children))
+    }
+
+    fn string(&mut self, name: &str) -> Result<InspectedField, InspectError> {
+        let start = self.pos;
+        let len = self.read_u16(name)? as usize;
+        let raw = self.take(name, len)?;
+        let value = std::str::from_utf8(raw).map_err(|_| InspectError::InvalidUtf8 {
+            field: name.to_string(),
+            offset: start,
+        })?;
+        Ok(self.field(name, start, format!("{:?} ({} bytes)", value, len)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bytes::BytesMut;
+
+    use crate::protocol::RequestHeader;
+    use crate::protocol::TopicPartition;
+    use crate::record::{Record, RecordBatch};
+    use crate::request::{BrokerHeartbeat, Fetch, Produce};
+
+    fn fetch_bytes() -> Vec<u8> {
+        let mut buf = BytesMut::new();
+        RequestHeader::new(ApiKey::Fetch, 7, "cli".to_string())
+            .unwrap()
+            .encode_into(&mut buf);
+        Fetch::new("test".to_string(), 3, 42, 1024)
+            .unwrap()
+            .encode_into(&mut buf);
+        buf.to_vec()
+    }
+
+    #[test]
+    fn test_inspect_fetch() {
+        let bytes = fetch_bytes();
+        let message = inspect(&bytes).unwrap();
+
+        assert_eq!(message.api_key, ApiKey::Fetch);
+        assert_eq!(message.len, bytes.len());
+
+        let api_key = message.field("header.api_key").unwrap();
+        assert_eq!((api_key.offset, api_key.len), (0, 2));
+        assert_eq!(api_key.value.as_deref(), Some("1 (Fetch)"));
+
+        let client_id = message.field("header.client_id").unwrap();
+        assert_eq!((client_id.offset, client_id.len), (6, 5));
+
+        let body = message.field("body").unwrap();
+        assert_eq!((body.offset, body.len), (11, bytes.len() - 11));
+
+        let offset = message.field("body.offset").unwrap();
+        assert_eq!((offset.offset, offset.len), (11 + 6 + 4, 8));
+        assert_eq!(offset.value.as_deref(), Some("42"));
+    }
+
+    #[test]
+    fn test_display() {
+        let message = inspect(&fetch_bytes()).unwrap();
+        let expected = "\
+Fetch request (49 bytes)
+  [0..11]      header
+    [0..2]       api_key: 1 (Fetch)
+    [2..6]       correlation_id: 7
+    [6..11]      client_id: \"cli\" (3 bytes)
+  [11..49]     body
+    [11..17]     topic: \"test\" (4 bytes)
+    [17..21]     partition: 3
+    [21..29]     offset: 42
+    [29..33]     size: 1024
+    [33..37]     min_bytes: 0
+    [37..41]     max_wait_ms: 0
+    [41..45]     replica_id: 4294967295
+    [45..49]     leader_epoch: 4294967295
+";
+        assert_eq!(message.to_string(), expected);
+    }
+
+    #[test]
+    fn test_inspect_errors() {
+        let bytes = fetch_bytes();
+
+        assert_eq!(
+            inspect(&[0xFF, 0xFF]),
+            Err(InspectError::UnknownApiKey(0xFFFF))
+        );
+
+        assert_eq!(
+            inspect(&bytes[..bytes.len() - 1]),
+            Err(InspectError::Truncated {
+                field: "leader_epoch".to_string(),
+                offset: 45,
+            })
+        );
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            inspect(&trailing),
+            Err(InspectError::TrailingBytes {
+                offset: bytes.len(),
+                len: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_inspect_produce() {
+        let mut buf = BytesMut::new();
+        RequestHeader::new(ApiKey::Produce, 7, "cli".to_string())
+            .unwrap()
+            .encode_into(&mut buf);
+        let batch = RecordBatch::new(vec![Record::new(None, None).with_timestamp(5)]);
+        Produce::new("test".to_string(), 3, batch.clone())
+            .unwrap()
+            .encode_into(&mut buf);
+
+        let message = inspect(&buf).unwrap();
+        assert_eq!(message.api_key, ApiKey::Produce);
+
+        let field = message.field("body.batch").unwrap();
+        assert_eq!(
+            (field.offset, field.len),
+            (11 + 6 + 4 + 2 + 4 + 4, batch.size())
+        );
+        let count = message.field("body.batch.count").unwrap();
+        assert_eq!(count.value.as_deref(), Some("1"));
+        let records = message.field("body.batch.records").unwrap();
+        assert_eq!(records.offset + records.len, buf.len());
+    }
+
+    #[test]
+    fn test_inspect_heartbeat() {
+        let mut buf = BytesMut::new();
+        RequestHeader::new(ApiKey::BrokerHeartbeat, 7, "cli".to_string())
+            .unwrap()
+            .encode_into(&mut buf);
+        let leaders = vec![
+            (TopicPartition::new("a", 0), 1),
+            (TopicPartition::new("b", 2), 3),
+        ];
+        BrokerHeartbeat::new(4, leaders)
+            .unwrap()
+            .encode_into(&mut buf);
+
+        let message = inspect(&buf).unwrap();
+        assert_eq!(message.api_key, ApiKey::BrokerHeartbeat);
+        let count = message.field("body.leaders.count").unwrap();
+        assert_eq!(count.value.as_deref(), Some("2"));
+        let second = message.field("body.leaders.1").unwrap();
+        assert_eq!(
+            (second.offset, second.len),
+            (11 + 4 + 2 + crate::VERSION.len() + 2 + 4 + 11, 11)
+        );
+        let partition = message.field("body.leaders.1.partition").unwrap();
+        assert_eq!(partition.value.as_deref(), Some("2"));
+    }
+}
@@ -0,0 +1,72 @@
+use thiserror::Error;
+
+use super::DEFAULT_MAX_TOPIC_NAME_LEN;
+
+/// Largest record batch a produce may carry unless configured otherwise,
+/// like Kafka's `message.max.bytes`.
+pub const DEFAULT_MAX_BATCH_BYTES: usize = 1024 * 1024;
+
+/// Most records a produced batch may hold unless configured otherwise.
+pub const DEFAULT_MAX_RECORDS: usize = 64 * 1024;
+
+/// Caps on the sizes a request may claim. Length prefixes and counts are
+/// checked against these before anything is allocated for them, so a client
+/// can't make the broker reserve memory for data it never sends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    pub max_topic_len: usize,
+    pub max_batch_bytes: usize,
+    pub max_records: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits {
+            max_topic_len: DEFAULT_MAX_TOPIC_NAME_LEN,
+            max_batch_bytes: DEFAULT_MAX_BATCH_BYTES,
+            max_records: DEFAULT_MAX_RECORDS,
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitExceeded {
+    #[error("Topic name of {len} bytes is over the {limit} byte limit")]
+    TopicLength { len: usize, limit: usize },
+    #[error("Batch of {size} bytes is over the {limit} byte limit")]
+    BatchSize { size: usize, limit: usize },
+    #[error("Batch of {count} records is over the {limit} record limit")]
+    RecordCount { count: usize, limit: usize },
+}
+
+impl DecodeLimits {
+    pub fn check_topic_len(&self, len: usize) -> Result<(), LimitExceeded> {
+        if len > self.max_topic_len {
+            return Err(LimitExceeded::TopicLength {
+                len,
+                limit: self.max_topic_len,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn check_batch_size(&self, size: usize) -> Result<(), LimitExceeded> {
+        if size > self.max_batch_bytes {
+            return Err(LimitExceeded::BatchSize {
+                size,
+                limit: self.max_batch_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn check_record_count(&self, count: usize) -> Result<(), LimitExceeded> {
+        if count > self.max_records {
+            return Err(LimitExceeded::RecordCount {
+                count,
+                limit: self.max_records,
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,15 @@
+mod error_code;
+mod header;
+mod inspect;
+mod limits;
+mod topic;
+mod wire;
+pub use error_code::ErrorCode;
+pub use header::{ApiKey, HeaderError, RequestHeader, ResponseHeader};
+pub use inspect::{inspect, InspectError, InspectedField, InspectedMessage};
+pub use limits::{DecodeLimits, LimitExceeded, DEFAULT_MAX_BATCH_BYTES, DEFAULT_MAX_RECORDS};
+pub use topic::{
+    split_namespace, validate_topic_name, validate_topic_name_with_max_len, InvalidTopicName,
+    TopicPartition, DEFAULT_MAX_TOPIC_NAME_LEN, NAMESPACE_SEPARATOR,
+};
+pub(crate) use wire::{get_str, put_str, str_size, NO_LEADER_EPOCH};
@@ -0,0 +1,163 @@
+use std::fmt::Display;
+use thiserror::Error;
+
+/// Default upper bound on topic name length, same as Kafka's.
+pub const DEFAULT_MAX_TOPIC_NAME_LEN: usize = 249;
+
+/// Separates the namespace from the topic in `tenant/topic` names.
+pub const NAMESPACE_SEPARATOR: char = '/';
+
+/// Identifies a single partition of a topic.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TopicPartition {
+    pub topic: String,
+    pub partition: u32,
+}
+
+impl TopicPartition {
+    pub fn new(topic: impl Into<String>, partition: u32) -> Self {
+        TopicPartition {
+            topic: topic.into(),
+            partition,
+        }
+    }
+}
+
+impl Display for TopicPartition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.topic, self.partition)
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum InvalidTopicName {
+    #[error("Topic name is empty")]
+    Empty,
+    #[error("Topic namespace is empty")]
+    EmptyNamespace,
+    #[error("Topic name is longer than {max_len} characters")]
+    TooLong { max_len: usize },
+    #[error("Topic name contains illegal character {0:?}")]
+    IllegalChar(char),
+}
+
+/// Validates topic name against [`DEFAULT_MAX_TOPIC_NAME_LEN`].
+pub fn validate_topic_name(name: &str) -> Result<(), InvalidTopicName> {
+    validate_topic_name_with_max_len(name, DEFAULT_MAX_TOPIC_NAME_LEN)
+}
+
+/// Topic names must be non-empty, at most `max_len` bytes, and only contain
+/// ASCII alphanumerics, `.`, `_` and `-`. They may be prefixed by a single
+/// namespace, as in `tenant/topic`, which follows the same charset rules.
+pub fn validate_topic_name_with_max_len(
+    name: &str,
+    max_len: usize,
+) -> Result<(), InvalidTopicName> {
+    if name.len() > max_len {
+        return Err(InvalidTopicName::TooLong { max_len });
+    }
+
+    let (namespace, topic) = split_namespace(name);
+
+    if let Some(namespace) = namespace {
+        if namespace.is_empty() {
+            return Err(InvalidTopicName::EmptyNamespace);
+        }
+        validate_chars(namespace)?;
+    }
+
+    if topic.is_empty() {
+        return Err(InvalidTopicName::Empty);
+    }
+    validate_chars(topic)
+}
+
+/// Splits `tenant/topic` into its namespace and topic parts. Names without
+/// a separator have no namespace.
+pub fn split_namespace(name: &str) -> (Option<&str>, &str) {
+    match name.split_once(NAMESPACE_SEPARATOR) {
+        Some((namespace, topic)) => (Some(namespace), topic),
+        None => (None, name),
+    }
+}
+
+fn validate_chars(part: &str) -> Result<(), InvalidTopicName> {
+    match part.chars().find(|c| !is_legal_char(*c)) {
+        Some(c) => Err(InvalidTopicName::IllegalChar(c)),
+        None => Ok(()),
+    }
+}
+
+fn is_legal_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_names() {
+        assert!(validate_topic_name("test").is_ok());
+        assert!(validate_topic_name("my-topic.v2_events").is_ok());
+        assert!(validate_topic_name(&"X".repeat(DEFAULT_MAX_TOPIC_NAME_LEN)).is_ok());
+    }
+
+    #[test]
+    fn test_empty_name() {
+        assert_eq!(validate_topic_name(""), Err(InvalidTopicName::Empty));
+    }
+
+    #[test]
+    fn test_too_long_name() {
+        assert_eq!(
+            validate_topic_name(&"X".repeat(DEFAULT_MAX_TOPIC_NAME_LEN + 1)),
+            Err(InvalidTopicName::TooLong {
+                max_len: DEFAULT_MAX_TOPIC_NAME_LEN
+            })
+        );
+
+        // Custom max length
+        assert!(validate_topic_name_with_max_len("test", 4).is_ok());
+        assert_eq!(
+            validate_topic_name_with_max_len("tests", 4),
+            Err(InvalidTopicName::TooLong { max_len: 4 })
+        );
+    }
+
+    #[test]
+    fn test_illegal_chars() {
+        assert_eq!(
+            validate_topic_name("my topic"),
+            Err(InvalidTopicName::IllegalChar(' '))
+        );
+        assert_eq!(
+            validate_topic_name("a/b/c"),
+            Err(InvalidTopicName::IllegalChar('/'))
+        );
+        assert_eq!(
+            validate_topic_name("tést"),
+            Err(InvalidTopicName::IllegalChar('é'))
+        );
+    }
+
+    #[test]
+    fn test_namespaced_names() {
+        assert!(validate_topic_name("team-a/orders").is_ok());
+        assert_eq!(
+            validate_topic_name("/orders"),
+            Err(InvalidTopicName::EmptyNamespace)
+        );
+        assert_eq!(validate_topic_name("team-a/"), Err(InvalidTopicName::Empty));
+        assert_eq!(
+            validate_topic_name("team a/orders"),
+            Err(InvalidTopicName::IllegalChar(' '))
+        );
+    }
+
+    #[test]
+    fn test_split_namespace() {
+        assert_eq!(split_namespace("orders"), (None, "orders"));
+        assert_eq!(split_namespace("team-a/orders"), (Some("team-a"), "orders"));
+    }
+}
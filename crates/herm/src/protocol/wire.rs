@@ -0,0 +1,28 @@
+use bytes::{Buf, BufMut, Bytes};
+
+/// Leader epoch sent by requests that don't check it.
+pub(crate) const NO_LEADER_EPOCH: u32 = u32::MAX;
+
+/// Writes `s` with its length prefix encoded as u16.
+pub(crate) fn put_str(buf: &mut impl BufMut, s: &str) {
+    buf.put_u16(s.len() as u16);
+    buf.put(s.as_bytes());
+}
+
+/// Reads a u16 length prefixed string, advancing `bytes` past it.
+pub(crate) fn get_str(bytes: &mut Bytes) -> Option<String> {
+    if bytes.remaining() < 2 {
+        return None;
+    }
+
+    let len = bytes.get_u16() as usize;
+    if bytes.remaining() < len {
+        return None;
+    }
+
+    String::from_utf8(bytes.split_to(len).to_vec()).ok()
+}
+
+pub(crate) fn str_size(s: &str) -> usize {
+    2 + s.len()
+}
@@ -0,0 +1,470 @@
+use std::fmt::Display;
+use thiserror::Error;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use super::crc::crc32c;
+use super::{Header, Record};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum RecordBatchError {
+    #[error("Malformed bytes")]
+    MalformedBytes,
+    #[error("Batch crc {actual:#010x} doesn't match {expected:#010x}")]
+    CrcMismatch { expected: u32, actual: u32 },
+}
+
+/// A group of records stored and sent together, with offsets relative to a
+/// shared base offset.
+///
+/// Encoded as:
+///
+/// ```text
+/// base_offset: u64
+/// length: u32               -- bytes after this field
+/// crc: u32                  -- crc32c of everything after this field
+/// attributes: u16
+/// last_offset_delta: u32
+/// first_timestamp: u64
+/// max_timestamp: u64
+/// record_count: u32
+/// records: [
+///   offset_delta: u32
+///   timestamp: u64
+///   key: i32 length (-1 for none) + bytes
+///   value: i32 length (-1 for none) + bytes
+///   header_count: u16
+///   headers: [u16 length + key, u32 length + value]
+/// ]
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordBatch {
+    pub base_offset: u64,
+    pub attributes: u16,
+    pub records: Vec<Record>,
+}
+
+impl RecordBatch {
+    /// Size of `base_offset` and `length`, which frame every batch.
+    pub const LOG_OVERHEAD: usize = 8 + 4;
+
+    /// Size of the fields between `length` and the records.
+    const HEADER_SIZE: usize = 4 + 2 + 4 + 8 + 8 + 4;
+
+    /// Size of everything before the records, which is all the `peek_`
+    /// functions look at.
+    pub const PREFIX_SIZE: usize = Self::LOG_OVERHEAD + Self::HEADER_SIZE;
+
+    /// Attribute set by the log on batches holding records with an expiry,
+    /// so batches without any are served without looking at their records.
+    pub const EXPIRING: u16 = 1;
+
+    /// Builds a batch with offsets numbered from 0. The log assigns real
+    /// offsets on append.
+    pub fn new(records: Vec<Record>) -> Self {
+        let mut batch = RecordBatch {
+            base_offset: 0,
+            attributes: 0,
+            records,
+        };
+        for (delta, record) in batch.records.iter_mut().enumerate() {
+            record.offset = delta as u64;
+        }
+        batch
+    }
+
+    /// Moves the batch and its records to start at `base_offset`.
+    pub fn set_base_offset(&mut self, base_offset: u64) {
+        for record in &mut self.records {
+            record.offset = record.offset - self.base_offset + base_offset;
+        }
+        self.base_offset = base_offset;
+    }
+
+    /// Offset of the last record, or the base offset of an empty batch.
+    pub fn last_offset(&self) -> u64 {
+        self.records
+            .last()
+            .map_or(self.base_offset, |record| record.offset)
+    }
+
+    /// Offset the record after this batch gets.
+    pub fn next_offset(&self) -> u64 {
+        match self.records.last() {
+            Some(record) => record.offset + 1,
+            None => self.base_offset,
+        }
+    }
+
+    pub fn first_timestamp(&self) -> u64 {
+        self.records.first().map_or(0, |record| record.timestamp)
+    }
+
+    pub fn max_timestamp(&self) -> u64 {
+        self.records
+            .iter()
+            .map(|record| record.timestamp)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Reads the total encoded size of the batch starting at `bytes` from its
+    /// framing, without decoding it.
+    pub fn peek_size(bytes: &[u8]) -> Option<usize> {
+        if bytes.len() < Self::LOG_OVERHEAD {
+            return None;
+        }
+        let length = u32::from_be_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        Some(Self::LOG_OVERHEAD + length)
+    }
+
+    /// Reads the attributes of the batch starting at `bytes`, without
+    /// decoding it.
+    pub fn peek_attributes(bytes: &[u8]) -> Option<u16> {
+        let at = Self::LOG_OVERHEAD + 4;
+        let attributes = bytes.get(at..at + 2)?;
+        Some(u16::from_be_bytes(attributes.try_into().unwrap()))
+    }
+
+    /// Whether the batch holds records with an expiry, going by its
+    /// attributes.
+    pub fn is_expiring(&self) -> bool {
+        self.attributes & Self::EXPIRING != 0
+    }
+
+    /// Leaves out the records expired by `now`.
+    pub fn retain_unexpired(&mut self, now: u64) {
+        if self.is_expiring() {
+            self.records.retain(|record| !record.is_expired(now));
+        }
+    }
+
+    /// Reads the record count of the batch starting at `bytes`, without
+    /// decoding it.
+    pub fn peek_record_count(bytes: &[u8]) -> Option<usize> {
+        let at = Self::LOG_OVERHEAD + Self::HEADER_SIZE - 4;
+        let count = bytes.get(at..at + 4)?;
+        Some(u32::from_be_bytes(count.try_into().unwrap()) as usize)
+    }
+
+    /// Reads the offset after the batch starting at `bytes`, like
+    /// [`next_offset`](RecordBatch::next_offset), without decoding it.
+    pub fn peek_next_offset(bytes: &[u8]) -> Option<u64> {
+        let base_offset = u64::from_be_bytes(bytes.get(0..8)?.try_into().unwrap());
+        let at = Self::LOG_OVERHEAD + 4 + 2;
+        let last_offset_delta = u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().unwrap());
+        Some(match Self::peek_record_count(bytes)? {
+            0 => base_offset,
+            _ => base_offset + last_offset_delta as u64 + 1,
+        })
+    }
+
+    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, RecordBatchError> {
+        let batch = Self::decode(&mut bytes)?;
+
+        if bytes.has_remaining() {
+            return Err(RecordBatchError::MalformedBytes);
+        }
+        Ok(batch)
+    }
+
+    /// Reads one batch off the front of `bytes`, verifying its crc.
+    pub fn decode(bytes: &mut Bytes) -> Result<Self, RecordBatchError> {
+        let size = Self::peek_size(bytes).ok_or(RecordBatchError::MalformedBytes)?;
+        if bytes.remaining() < size || size < Self::LOG_OVERHEAD + Self::HEADER_SIZE {
+            return Err(RecordBatchError::MalformedBytes);
+        }
+
+        let mut bytes = bytes.split_to(size);
+        let base_offset = bytes.get_u64();
+        bytes.advance(4);
+
+        let expected = bytes.get_u32();
+        let actual = crc32c(&bytes);
+        if expected != actual {
+            return Err(RecordBatchError::CrcMismatch { expected, actual });
+        }
+
+        let attributes = bytes.get_u16();
+        let _last_offset_delta = bytes.get_u32();
+        let _first_timestamp = bytes.get_u64();
+        let _max_timestamp = bytes.get_u64();
+        let record_count = bytes.get_u32();
+
+        // Every record takes at least 22 bytes, don't trust huge counts
+        let mut records = Vec::with_capacity((record_count as usize).min(bytes.remaining() / 22));
+        for _ in 0..record_count {
+            records.push(decode_record(&mut bytes, base_offset)?);
+        }
+
+        if bytes.has_remaining() {
+            return Err(RecordBatchError::MalformedBytes);
+        }
+
+        Ok(RecordBatch {
+            base_offset,
+            attributes,
+            records,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        // The crc covers the body, so it has to be laid out first
+        let mut body = BytesMut::with_capacity(self.size() - Self::LOG_OVERHEAD - 4);
+        body.put_u16(self.attributes);
+        body.put_u32((self.last_offset() - self.base_offset) as u32);
+        body.put_u64(self.first_timestamp());
+        body.put_u64(self.max_timestamp());
+        body.put_u32(self.records.len() as u32);
+        for record in &self.records {
+            encode_record(&mut body, record, self.base_offset);
+        }
+
+        buf.put_u64(self.base_offset);
+        buf.put_u32((4 + body.len()) as u32);
+        buf.put_u32(crc32c(&body));
+        buf.put(body);
+    }
+
+    pub fn size(&self) -> usize {
+        Self::LOG_OVERHEAD + Self::HEADER_SIZE + self.records.iter().map(record_size).sum::<usize>()
+    }
+}
+
+impl Display for RecordBatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "RecordBatch(baseOffset:{} lastOffset:{} records:{})",
+            self.base_offset,
+            self.last_offset(),
+            self.records.len()
+        )
+    }
+}
+
+fn put_nullable(buf: &mut impl BufMut, bytes: &Option<Bytes>) {
+    match bytes {
+        Some(bytes) => {
+            buf.put_i32(bytes.len() as i32);
+            buf.put(bytes.as_ref());
+        }
+        None => buf.put_i32(-1),
+    }
+}
+
+fn get_nullable(bytes: &mut Bytes) -> Result<Option<Bytes>, RecordBatchError> {
+    if bytes.remaining() < 4 {
+        return Err(RecordBatchError::MalformedBytes);
+    }
+
+    let len = bytes.get_i32();
+    if len < 0 {
+        return Ok(None);
+    }
+    if bytes.remaining() < len as usize {
+        return Err(RecordBatchError::MalformedBytes);
+    }
+    Ok(Some(bytes.split_to(len as usize)))
+}
+
+fn nullable_size(bytes: &Option<Bytes>) -> usize {
+    4 + bytes.as_ref().map_or(0, |bytes| bytes.len())
+}
+
+fn encode_record(buf: &mut impl BufMut, record: &Record, base_offset: u64) {
+    buf.put_u32((record.offset - base_offset) as u32);
+    buf.put_u64(record.timestamp);
+    put_nullable(buf, &record.key);
+    put_nullable(buf, &record.value);
+
+    buf.put_u16(record.headers.len() as u16);
+    for header in &record.headers {
+        buf.put_u16(header.key.len() as u16);
+        buf.put(header.key.as_bytes());
+        buf.put_u32(header.value.len() as u32);
+        buf.put(header.value.as_ref());
+    }
+}
+
+fn decode_record(bytes: &mut Bytes, base_offset: u64) -> Result<Record, RecordBatchError> {
+    if bytes.remaining() < 4 + 8 {
+        return Err(RecordBatchError::MalformedBytes);
+    }
+
+    let offset = base_offset + bytes.get_u32() as u64;
+    let timestamp = bytes.get_u64();
+    let key = get_nullable(bytes)?;
+    let value = get_nullable(bytes)?;
+
+    if bytes.remaining() < 2 {
+        return Err(RecordBatchError::MalformedBytes);
+    }
+    let header_count = bytes.get_u16();
+    let mut headers = Vec::new();
+    for _ in 0..header_count {
+        if bytes.remaining() < 2 {
+            return Err(RecordBatchError::MalformedBytes);
+        }
+        let key_len = bytes.get_u16() as usize;
+        if bytes.remaining() < key_len {
+            return Err(RecordBatchError::MalformedBytes);
+        }
+        let key = String::from_utf8(bytes.split_to(key_len).to_vec())
+            .map_err(|_| RecordBatchError::MalformedBytes)?;
+
+        if bytes.remaining() < 4 {
+            return Err(RecordBatchError::MalformedBytes);
+        }
+        let value_len = bytes.get_u32() as usize;
+        if bytes.remaining() < value_len {
+            return Err(RecordBatchError::MalformedBytes);
+        }
+        headers.push(Header::new(key, bytes.split_to(value_len)));
+    }
+
+    Ok(Record {
+        offset,
+        timestamp,
+        key,
+        value,
+        headers,
+    })
+}
+
+pub(super) fn record_size(record: &Record) -> usize {
+    4 + 8
+        + nullable_size(&record.key)
+        + nullable_size(&record.value)
+        + 2
+        + record
+            .headers
+            .iter()
+            .map(|header| 2 + header.key.len() + 4 + header.value.len())
+            .sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch() -> RecordBatch {
+        RecordBatch::new(vec![
+            Record::new(
+                Some(Bytes::from_static(b"k1")),
+                Some(Bytes::from_static(b"v1")),
+            )
+            .with_timestamp(100),
+            Record::new(None, Some(Bytes::from_static(b"v2")))
+                .with_timestamp(300)
+                .with_header(Header::new("h", Bytes::from_static(b"x"))),
+            Record::new(Some(Bytes::from_static(b"k1")), None).with_timestamp(200),
+        ])
+    }
+
+    #[test]
+    fn test_new() {
+        let batch = batch();
+        assert_eq!(batch.base_offset, 0);
+        assert_eq!(
+            batch.records.iter().map(|r| r.offset).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        assert_eq!(batch.last_offset(), 2);
+        assert_eq!(batch.next_offset(), 3);
+        assert_eq!(batch.first_timestamp(), 100);
+        assert_eq!(batch.max_timestamp(), 300);
+        assert!(batch.records[2].is_tombstone());
+    }
+
+    #[test]
+    fn test_set_base_offset() {
+        let mut batch = batch();
+        batch.set_base_offset(10);
+        assert_eq!(batch.base_offset, 10);
+        assert_eq!(
+            batch.records.iter().map(|r| r.offset).collect::<Vec<_>>(),
+            vec![10, 11, 12]
+        );
+        assert_eq!(batch.next_offset(), 13);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut batch = batch();
+        batch.set_base_offset(1 << 40);
+
+        let bytes = batch.to_bytes();
+        assert_eq!(bytes.len(), batch.size());
+        assert_eq!(RecordBatch::peek_size(&bytes), Some(batch.size()));
+        assert_eq!(
+            RecordBatch::peek_record_count(&bytes),
+            Some(batch.records.len())
+        );
+        assert_eq!(RecordBatch::peek_record_count(&bytes[..41]), None);
+        assert_eq!(
+            RecordBatch::peek_next_offset(&bytes[..RecordBatch::PREFIX_SIZE]),
+            Some(batch.next_offset())
+        );
+        assert_eq!(
+            RecordBatch::peek_next_offset(&RecordBatch::new(vec![]).to_bytes()),
+            Some(0)
+        );
+        assert_eq!(RecordBatch::from_bytes(bytes).unwrap(), batch);
+    }
+
+    #[test]
+    fn test_decode_consecutive_batches() {
+        let first = batch();
+        let mut second = batch();
+        second.set_base_offset(3);
+
+        let mut buf = BytesMut::new();
+        first.encode_into(&mut buf);
+        second.encode_into(&mut buf);
+
+        let mut bytes = buf.freeze();
+        assert_eq!(RecordBatch::decode(&mut bytes).unwrap(), first);
+        assert_eq!(RecordBatch::decode(&mut bytes).unwrap(), second);
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn test_crc_mismatch() {
+        let mut bytes = batch().to_bytes().to_vec();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+
+        assert!(matches!(
+            RecordBatch::from_bytes(Bytes::from(bytes)),
+            Err(RecordBatchError::CrcMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_malformed_bytes() {
+        let bytes = batch().to_bytes();
+
+        assert_eq!(
+            RecordBatch::from_bytes(Bytes::new()),
+            Err(RecordBatchError::MalformedBytes)
+        );
+        assert_eq!(
+            RecordBatch::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(RecordBatchError::MalformedBytes)
+        );
+
+        let mut trailing = bytes.to_vec();
+        trailing.push(0);
+        assert_eq!(
+            RecordBatch::from_bytes(Bytes::from(trailing)),
+            Err(RecordBatchError::MalformedBytes)
+        );
+    }
+}
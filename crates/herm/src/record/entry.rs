@@ -0,0 +1,105 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+
+use super::batch::record_size;
+use super::Header;
+
+/// Header holding when a record expires, in milliseconds since the unix
+/// epoch written out as a decimal number.
+pub const EXPIRES_AT_HEADER: &str = "herm.expires-at";
+
+/// Header holding when a record is to be delivered, in milliseconds since
+/// the unix epoch written out as a decimal number.
+pub const DELIVER_AT_HEADER: &str = "herm.deliver-at";
+
+/// A single message. A record without a value is a tombstone, which marks
+/// its key as deleted for compacted topics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Assigned by the log on append.
+    pub offset: u64,
+    /// Milliseconds since the unix epoch.
+    pub timestamp: u64,
+    pub key: Option<Bytes>,
+    pub value: Option<Bytes>,
+    pub headers: Vec<Header>,
+}
+
+impl Record {
+    /// A record timestamped with the current time.
+    pub fn new(key: Option<Bytes>, value: Option<Bytes>) -> Self {
+        Record {
+            offset: 0,
+            timestamp: now_ms(),
+            key,
+            value,
+            headers: vec![],
+        }
+    }
+
+    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub fn with_header(mut self, header: Header) -> Self {
+        self.headers.push(header);
+        self
+    }
+
+    /// Has the record expire at `expires_at`, in milliseconds since the unix
+    /// epoch. Expired records are no longer handed to consumers and are
+    /// removed from the log by its next cleanup.
+    pub fn with_expiry(self, expires_at: u64) -> Self {
+        self.with_header(Header::new(
+            EXPIRES_AT_HEADER,
+            Bytes::from(expires_at.to_string()),
+        ))
+    }
+
+    /// When the record expires, if it was given an expiry.
+    pub fn expires_at(&self) -> Option<u64> {
+        self.time_header(EXPIRES_AT_HEADER)
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at()
+            .is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Has the broker hold the record back until `deliver_at`, in
+    /// milliseconds since the unix epoch, appending it to the log then.
+    pub fn with_deliver_at(self, deliver_at: u64) -> Self {
+        self.with_header(Header::new(
+            DELIVER_AT_HEADER,
+            Bytes::from(deliver_at.to_string()),
+        ))
+    }
+
+    /// When the record is to be delivered, if it was given a time.
+    pub fn deliver_at(&self) -> Option<u64> {
+        self.time_header(DELIVER_AT_HEADER)
+    }
+
+    pub fn is_tombstone(&self) -> bool {
+        self.value.is_none()
+    }
+
+    /// Bytes the record takes up in a batch.
+    pub fn size(&self) -> usize {
+        record_size(self)
+    }
+
+    fn time_header(&self, key: &str) -> Option<u64> {
+        let header = self.headers.iter().find(|header| header.key == key)?;
+        std::str::from_utf8(&header.value).ok()?.parse().ok()
+    }
+}
+
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,17 @@
+use bytes::Bytes;
+
+/// Application supplied key-value metadata attached to a record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub key: String,
+    pub value: Bytes,
+}
+
+impl Header {
+    pub fn new(key: impl Into<String>, value: impl Into<Bytes>) -> Self {
+        Header {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+}
@@ -0,0 +1,9 @@
+mod batch;
+mod crc;
+mod entry;
+mod header;
+pub use batch::{RecordBatch, RecordBatchError};
+pub(crate) use crc::crc32c;
+pub(crate) use entry::now_ms;
+pub use entry::{Record, DELIVER_AT_HEADER, EXPIRES_AT_HEADER};
+pub use header::Header;
@@ -0,0 +1,132 @@
+use bytes::BytesMut;
+use tokio::io::{AsyncWriteExt, BufStream};
+use tokio::net::{TcpStream, ToSocketAddrs};
+
+use super::ReplicationError;
+use crate::broker::{read_frame, write_frame, DEFAULT_MAX_FRAME_SIZE};
+use crate::protocol::{RequestHeader, ResponseHeader};
+use crate::request::{
+    BrokerHeartbeat, DescribeCluster, DescribeLogDirs, Fetch, LeaderAndIsr, Metadata,
+    ReassignPartition, Request,
+};
+use crate::response::{
+    AdminResponse, DescribeClusterResponse, DescribeLogDirsResponse, FetchResponse,
+    MetadataResponse, Response,
+};
+
+/// Client id brokers send with their requests to each other.
+pub const REPLICA_CLIENT_ID: &str = "herm-replica-fetcher";
+
+/// A broker's connection to another, used by followers to fetch from their
+/// leader, by leaders moving partitions and for heartbeats. Requests go one at a time, each
+/// waiting for its response.
+#[derive(Debug)]
+pub struct BrokerConnection {
+    stream: BufStream<TcpStream>,
+    correlation_id: u32,
+}
+
+impl BrokerConnection {
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<Self, ReplicationError> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        Ok(BrokerConnection {
+            stream: BufStream::new(stream),
+            correlation_id: 0,
+        })
+    }
+
+    pub async fn fetch(&mut self, fetch: Fetch) -> Result<FetchResponse, ReplicationError> {
+        match self.call(fetch.into()).await? {
+            Response::Fetch(response) => Ok(response),
+            _ => unreachable!("a fetch is answered with a fetch response"),
+        }
+    }
+
+    pub async fn leader_and_isr(
+        &mut self,
+        request: LeaderAndIsr,
+    ) -> Result<AdminResponse, ReplicationError> {
+        match self.call(request.into()).await? {
+            Response::LeaderAndIsr(response) => Ok(response),
+            _ => unreachable!("a LeaderAndIsr is answered with its own response"),
+        }
+    }
+
+    pub async fn reassign(
+        &mut self,
+        request: ReassignPartition,
+    ) -> Result<AdminResponse, ReplicationError> {
+        match self.call(request.into()).await? {
+            Response::ReassignPartition(response) => Ok(response),
+            _ => unreachable!("a ReassignPartition is answered with its own response"),
+        }
+    }
+
+    pub async fn metadata(
+        &mut self,
+        request: Metadata,
+    ) -> Result<MetadataResponse, ReplicationError> {
+        match self.call(request.into()).await? {
+            Response::Metadata(response) => Ok(response),
+            _ => unreachable!("a Metadata is answered with its own response"),
+        }
+    }
+
+    pub async fn heartbeat(
+        &mut self,
+        request: BrokerHeartbeat,
+    ) -> Result<AdminResponse, ReplicationError> {
+        match self.call(request.into()).await? {
+            Response::BrokerHeartbeat(response) => Ok(response),
+            _ => unreachable!("a BrokerHeartbeat is answered with its own response"),
+        }
+    }
+
+    pub async fn describe_cluster(
+        &mut self,
+        request: DescribeCluster,
+    ) -> Result<DescribeClusterResponse, ReplicationError> {
+        match self.call(request.into()).await? {
+            Response::DescribeCluster(response) => Ok(response),
+            _ => unreachable!("a DescribeCluster is answered with its own response"),
+        }
+    }
+
+    pub async fn describe_log_dirs(
+        &mut self,
+        request: DescribeLogDirs,
+    ) -> Result<DescribeLogDirsResponse, ReplicationError> {
+        match self.call(request.into()).await? {
+            Response::DescribeLogDirs(response) => Ok(response),
+            _ => unreachable!("a DescribeLogDirs is answered with its own response"),
+        }
+    }
+
+    /// Sends any request and waits for its response.
+    pub async fn call(&mut self, request: Request) -> Result<Response, ReplicationError> {
+        self.correlation_id = self.correlation_id.wrapping_add(1);
+        let header = RequestHeader::new(
+            request.api_key(),
+            self.correlation_id,
+            REPLICA_CLIENT_ID.to_string(),
+        )?;
+        let mut buf = BytesMut::with_capacity(header.size() + request.size());
+        header.encode_into(&mut buf);
+        request.encode_into(&mut buf);
+        write_frame(&mut self.stream, &buf).await?;
+        self.stream.flush().await?;
+
+        let mut frame = read_frame(&mut self.stream, DEFAULT_MAX_FRAME_SIZE)
+            .await?
+            .ok_or(ReplicationError::Disconnected)?;
+        let header = ResponseHeader::decode(&mut frame)?;
+        if header.correlation_id != self.correlation_id {
+            return Err(ReplicationError::CorrelationMismatch {
+                expected: self.correlation_id,
+                actual: header.correlation_id,
+            });
+        }
+        Ok(Response::decode(request.api_key(), frame)?)
+    }
+}
@@ -0,0 +1,391 @@
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use thiserror::Error;
+
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+use super::BrokerConnection;
+use crate::protocol::{ErrorCode, HeaderError, TopicPartition};
+use crate::record::RecordBatch;
+use crate::request::{Fetch, FetchCreationError, LeaderAndIsrCreationError};
+use crate::response::ResponseError;
+use crate::storage::{Log, LogDirError, LogError};
+
+#[derive(Error, Debug)]
+pub enum ReplicationError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Leader closed the connection")]
+    Disconnected,
+    #[error(transparent)]
+    Header(#[from] HeaderError),
+    #[error(transparent)]
+    Response(#[from] ResponseError),
+    #[error(transparent)]
+    Fetch(#[from] FetchCreationError),
+    #[error(transparent)]
+    LeaderAndIsr(#[from] LeaderAndIsrCreationError),
+    #[error("Response to request {actual} while waiting for {expected}")]
+    CorrelationMismatch { expected: u32, actual: u32 },
+    #[error("Leader answered with {0}")]
+    Leader(ErrorCode),
+    #[error(transparent)]
+    Log(#[from] LogError),
+    #[error(transparent)]
+    LogDir(#[from] LogDirError),
+    #[error("Partition isn't led here")]
+    NotLeader,
+    #[error("Partition is already being moved")]
+    MoveInProgress,
+    #[error("No address set for other brokers to reach this one at")]
+    NoAdvertisedListener,
+    #[error("Target answered with {0}")]
+    Target(ErrorCode),
+    #[error("Target didn't catch up in time")]
+    TimedOut,
+}
+
+/// How a [`ReplicaFetcher`] pulls from its leader.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetcherConfig {
+    /// Id the leader tracks this follower by.
+    pub replica_id: u32,
+    /// Most bytes asked for in one fetch.
+    pub max_bytes: u32,
+    /// How long the leader may hold a fetch while it has nothing new.
+    pub max_wait: Duration,
+    /// Pause after a failed fetch before trying again.
+    pub backoff: Duration,
+}
+
+impl Default for FetcherConfig {
+    fn default() -> Self {
+        FetcherConfig {
+            replica_id: 0,
+            max_bytes: 1024 * 1024,
+            max_wait: Duration::from_millis(500),
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Background task keeping a follower's copy of a partition up to date: it
+/// fetches from the leader at the local log end offset, and appends what comes
+/// back with the leader's offsets. Fetches wait at the leader for new data, so
+/// a caught up follower isn't busy polling. Fetching as a replica moves the
+/// leader's high watermark along, see
+/// [`IsrTracker`](super::IsrTracker). Failures are retried after a
+/// backoff, reconnecting if the connection broke. Stops when dropped.
+///
+/// Fetches carry the follower's leader epoch. A leader at a newer epoch fences
+/// them, and the follower moves up to its epoch; a leader at an older one has
+/// been deposed, and nothing more is copied from it.
+///
+/// Only ever appends, a follower whose log has gone past the leader's keeps
+/// getting [`ErrorCode::OffsetOutOfRange`].
+#[derive(Debug)]
+pub struct ReplicaFetcher {
+    partition: TopicPartition,
+    leader: String,
+    handle: JoinHandle<()>,
+}
+
+impl ReplicaFetcher {
+    pub fn spawn(
+        leader: String,
+        partition: TopicPartition,
+        log: Arc<RwLock<Log>>,
+        config: FetcherConfig,
+    ) -> Self {
+        let span = tracing::info_span!(
+            "replica_fetcher",
+            leader = %leader,
+            topic = %partition.topic,
+            partition = partition.partition,
+        );
+        let fetcher = Fetcher {
+            leader: leader.clone(),
+            partition: partition.clone(),
+            log,
+            config,
+            connection: None,
+        };
+        ReplicaFetcher {
+            partition,
+            leader,
+            handle: tokio::spawn(fetcher.run().instrument(span)),
+        }
+    }
+
+    pub fn partition(&self) -> &TopicPartition {
+        &self.partition
+    }
+
+    /// Address of the leader fetched from.
+    pub fn leader(&self) -> &str {
+        &self.leader
+    }
+}
+
+impl Drop for ReplicaFetcher {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+struct Fetcher {
+    leader: String,
+    partition: TopicPartition,
+    log: Arc<RwLock<Log>>,
+    config: FetcherConfig,
+    connection: Option<BrokerConnection>,
+}
+
+impl Fetcher {
+    async fn run(mut self) {
+        loop {
+            if let Err(err) = self.fetch_once().await {
+                tracing::warn!("replica fetch failed: {}", err);
+                if !matches!(err, ReplicationError::Leader(_) | ReplicationError::Log(_)) {
+                    self.connection = None;
+                }
+                tokio::time::sleep(self.config.backoff).await;
+            }
+        }
+    }
+
+    /// Fetches once from the log end offset, returning how many records were
+    /// appended.
+    async fn fetch_once(&mut self) -> Result<usize, ReplicationError> {
+        let (offset, leader_epoch) = {
+            let log = self.log.read().unwrap();
+            (log.next_offset(), log.leader_epoch())
+        };
+        let fetch = Fetch::new(
+            self.partition.topic.clone(),
+            self.partition.partition,
+            offset,
+            self.config.max_bytes,
+        )?
+        .wait_for(
+            1,
+            self.config
+                .max_wait
+                .as_millis()
+                .try_into()
+                .unwrap_or(u32::MAX),
+        )
+        .from_replica(self.config.replica_id)
+        .with_leader_epoch(leader_epoch);
+
+        let connection = match &mut self.connection {
+            Some(connection) => connection,
+            None => self
+                .connection
+                .insert(BrokerConnection::connect(self.leader.as_str()).await?),
+        };
+        let response = connection.fetch(fetch).await?;
+        if response.error == ErrorCode::FencedLeaderEpoch && response.leader_epoch > leader_epoch {
+            tracing::info!(
+                from = leader_epoch,
+                to = response.leader_epoch,
+                "leader epoch moved"
+            );
+            self.log
+                .write()
+                .unwrap()
+                .set_leader_epoch(response.leader_epoch)?;
+            return Ok(0);
+        }
+        if response.error != ErrorCode::None {
+            return Err(ReplicationError::Leader(response.error));
+        }
+
+        let mut log = self.log.write().unwrap();
+        let mut appended = 0;
+        for batch in response.batches {
+            let Some(batch) = past(batch, log.next_offset()) else {
+                continue;
+            };
+            appended += batch.records.len();
+            log.append_replicated(batch)?;
+        }
+        if appended > 0 {
+            tracing::debug!(offset, appended, "replicated");
+        }
+        Ok(appended)
+    }
+}
+
+/// The part of `batch` at or after `offset`. Fetches return whole batches, so
+/// the first one can start before the offset asked for.
+fn past(mut batch: RecordBatch, offset: u64) -> Option<RecordBatch> {
+    if batch.base_offset >= offset {
+        return Some(batch);
+    }
+    batch.records.retain(|record| record.offset >= offset);
+    batch.base_offset = batch.records.first()?.offset;
+    Some(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use bytes::Bytes;
+
+    use crate::broker::{LogHandler, Server};
+    use crate::record::Record;
+    use crate::replication::ReplicaConfig;
+    use crate::storage::{LogConfig, LogDirs, Placement};
+
+    fn batch(values: &[&'static str]) -> RecordBatch {
+        RecordBatch::new(
+            values
+                .iter()
+                .map(|value| Record::new(None, Some(Bytes::from_static(value.as_bytes()))))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_past() {
+        let mut batch = batch(&["a", "b", "c"]);
+        batch.set_base_offset(10);
+        assert_eq!(past(batch.clone(), 10), Some(batch.clone()));
+
+        let trimmed = past(batch.clone(), 12).unwrap();
+        assert_eq!(trimmed.base_offset, 12);
+        assert_eq!(trimmed.records.len(), 1);
+        assert_eq!(trimmed.records[0].offset, 12);
+        assert_eq!(past(batch, 13), None);
+    }
+
+    /// A leader of `logs` the default replica follows.
+    fn leader(logs: LogDirs) -> LogHandler {
+        let config = ReplicaConfig {
+            peers: HashMap::from([(0, "127.0.0.1:1".to_string())]),
+            ..Default::default()
+        };
+        LogHandler::new(Arc::new(logs)).with_replica_config(config)
+    }
+
+    async fn wait_for_offset(log: &RwLock<Log>, offset: u64) {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while log.read().unwrap().next_offset() < offset {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replicate() {
+        let leader_dir = tempfile::tempdir().unwrap();
+        let logs = LogDirs::open(
+            [leader_dir.path()],
+            LogConfig::default(),
+            Placement::default(),
+        );
+        let partition = TopicPartition::new("events", 0);
+        let leader_log = logs.create(&partition).unwrap();
+        leader_log
+            .write()
+            .unwrap()
+            .append(batch(&["a", "b"]))
+            .unwrap();
+        leader_log.write().unwrap().append(batch(&["c"])).unwrap();
+
+        let server = Server::bind("127.0.0.1:0", leader(logs)).await.unwrap();
+        let leader = server.local_addr().unwrap().to_string();
+        tokio::spawn(server.run());
+
+        let follower_dir = tempfile::tempdir().unwrap();
+        let follower_log = Arc::new(RwLock::new(
+            Log::open(follower_dir.path(), LogConfig::default()).unwrap(),
+        ));
+        let config = FetcherConfig {
+            max_wait: Duration::from_millis(20),
+            backoff: Duration::from_millis(20),
+            ..Default::default()
+        };
+        let fetcher =
+            ReplicaFetcher::spawn(leader, partition.clone(), follower_log.clone(), config);
+        assert_eq!(fetcher.partition(), &partition);
+
+        // Catches up, then follows new appends
+        wait_for_offset(&follower_log, 3).await;
+        leader_log.write().unwrap().append(batch(&["d"])).unwrap();
+        wait_for_offset(&follower_log, 4).await;
+
+        let read = |log: &RwLock<Log>| log.read().unwrap().read(0, usize::MAX).unwrap();
+        assert_eq!(read(&follower_log), read(&leader_log));
+    }
+
+    #[tokio::test]
+    async fn test_leader_epochs() {
+        let leader_dir = tempfile::tempdir().unwrap();
+        let logs = LogDirs::open(
+            [leader_dir.path()],
+            LogConfig::default(),
+            Placement::default(),
+        );
+        let partition = TopicPartition::new("events", 0);
+        let leader_log = logs.create(&partition).unwrap();
+        leader_log.write().unwrap().append(batch(&["a"])).unwrap();
+        leader_log.write().unwrap().set_leader_epoch(2).unwrap();
+
+        let server = Server::bind("127.0.0.1:0", leader(logs)).await.unwrap();
+        let leader = server.local_addr().unwrap().to_string();
+        tokio::spawn(server.run());
+
+        let follower_dir = tempfile::tempdir().unwrap();
+        let follower_log = Arc::new(RwLock::new(
+            Log::open(follower_dir.path(), LogConfig::default()).unwrap(),
+        ));
+        let mut fetcher = Fetcher {
+            leader,
+            partition,
+            log: follower_log.clone(),
+            config: FetcherConfig::default(),
+            connection: None,
+        };
+
+        // Fenced at first, then fetches at the leader's epoch
+        assert_eq!(fetcher.fetch_once().await.unwrap(), 0);
+        assert_eq!(follower_log.read().unwrap().leader_epoch(), 2);
+        assert_eq!(fetcher.fetch_once().await.unwrap(), 1);
+
+        // Nothing is taken from a leader that has fallen behind
+        follower_log.write().unwrap().set_leader_epoch(3).unwrap();
+        leader_log.write().unwrap().append(batch(&["b"])).unwrap();
+        assert!(matches!(
+            fetcher.fetch_once().await,
+            Err(ReplicationError::Leader(ErrorCode::UnknownLeaderEpoch))
+        ));
+        assert_eq!(follower_log.read().unwrap().next_offset(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_leader() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = Arc::new(RwLock::new(
+            Log::open(dir.path(), LogConfig::default()).unwrap(),
+        ));
+        let mut fetcher = Fetcher {
+            leader: "127.0.0.1:1".to_string(),
+            partition: TopicPartition::new("events", 0),
+            log,
+            config: FetcherConfig::default(),
+            connection: None,
+        };
+        assert!(matches!(
+            fetcher.fetch_once().await,
+            Err(ReplicationError::Io(_))
+        ));
+    }
+}
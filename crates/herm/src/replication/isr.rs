@@ -0,0 +1,341 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::events::{Event, EventBus};
+use crate::protocol::TopicPartition;
+
+/// How long a follower may go without catching up before it drops out of
+/// the in-sync replicas, unless configured otherwise.
+pub const DEFAULT_REPLICA_LAG_TIME_MAX: Duration = Duration::from_secs(30);
+
+/// Tracks the in-sync replicas (ISR) of the partitions this broker leads,
+/// and the high watermark they allow.
+///
+/// Followers report their log end offset with every fetch. A follower is
+/// caught up when it fetches from the leader's log end offset, or from where
+/// the leader's log ended at its previous fetch, and in sync while it last
+/// caught up within `max_lag`. The high watermark is the lowest log end offset
+/// among the leader and its in-sync followers, so everything below it is on
+/// every in-sync replica. It never moves back, and without in-sync followers
+/// it is the leader's log end offset.
+///
+/// Only fetches by a partition's assigned replicas count: the brokers it is
+/// [`assigned`](IsrTracker::with_assigned) to, and those it is being moved to,
+/// see [`add_replica`](IsrTracker::add_replica). Followers join once they
+/// catch up, the leader can't wait for one it hasn't heard from. The state is
+/// only kept in memory, a restarted leader starts over from its log end
+/// offset.
+///
+/// Changes to the in-sync followers are published as [`Event::IsrChanged`]
+/// once seen, given an [`EventBus`](IsrTracker::with_events).
+#[derive(Debug)]
+pub struct IsrTracker {
+    max_lag: Duration,
+    assigned: HashSet<u32>,
+    partitions: Mutex<HashMap<TopicPartition, Replicas>>,
+    events: Option<EventBus>,
+}
+
+#[derive(Debug, Default)]
+struct Replicas {
+    high_watermark: u64,
+    /// Replicas added to the assigned ones for this partition.
+    added: HashSet<u32>,
+    followers: HashMap<u32, Follower>,
+    /// In-sync followers as last seen, sorted by id.
+    isr: Vec<u32>,
+}
+
+#[derive(Debug)]
+struct Follower {
+    end_offset: u64,
+    /// Leader log end offset when the follower last fetched.
+    leader_end: u64,
+    caught_up: Option<Instant>,
+}
+
+impl Default for IsrTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_REPLICA_LAG_TIME_MAX)
+    }
+}
+
+impl IsrTracker {
+    pub fn new(max_lag: Duration) -> Self {
+        IsrTracker {
+            max_lag,
+            assigned: HashSet::new(),
+            partitions: Mutex::new(HashMap::new()),
+            events: None,
+        }
+    }
+
+    /// Publishes changes to the in-sync followers on `bus`.
+    pub fn with_events(mut self, bus: EventBus) -> Self {
+        self.events = Some(bus);
+        self
+    }
+
+    /// Assigns every partition to the brokers `replica_ids`.
+    pub fn with_assigned(mut self, replica_ids: impl IntoIterator<Item = u32>) -> Self {
+        self.assigned = replica_ids.into_iter().collect();
+        self
+    }
+
+    /// Assigns `partition` to `replica_id` too, until it is
+    /// [`remove`](IsrTracker::remove)d.
+    pub fn add_replica(&self, partition: &TopicPartition, replica_id: u32) {
+        let mut partitions = self.partitions.lock().unwrap();
+        let replicas = partitions.entry(partition.clone()).or_default();
+        replicas.added.insert(replica_id);
+    }
+
+    /// Whether `replica_id` is an assigned replica of `partition`.
+    pub fn is_assigned(&self, partition: &TopicPartition, replica_id: u32) -> bool {
+        self.assigned.contains(&replica_id)
+            || self
+                .partitions
+                .lock()
+                .unwrap()
+                .get(partition)
+                .is_some_and(|replicas| replicas.added.contains(&replica_id))
+    }
+
+    /// Records a fetch by follower `replica_id` from `offset`, its log end
+    /// offset, while the leader's log ends at `leader_end`. Returns the high
+    /// watermark after it, or `None` if `replica_id` isn't an assigned
+    /// replica of `partition`.
+    pub fn record_fetch(
+        &self,
+        partition: &TopicPartition,
+        replica_id: u32,
+        offset: u64,
+        leader_end: u64,
+    ) -> Option<u64> {
+        self.record_fetch_at(partition, replica_id, offset, leader_end, Instant::now())
+    }
+
+    /// High watermark of `partition`, whose log ends at `leader_end` here.
+    pub fn high_watermark(&self, partition: &TopicPartition, leader_end: u64) -> u64 {
+        self.high_watermark_at(partition, leader_end, Instant::now())
+    }
+
+    /// Followers of `partition` currently in sync, by id.
+    pub fn isr(&self, partition: &TopicPartition) -> Vec<u32> {
+        self.isr_at(partition, Instant::now())
+    }
+
+    /// Log end offset follower `replica_id` last fetched with.
+    pub fn end_offset(&self, partition: &TopicPartition, replica_id: u32) -> Option<u64> {
+        let partitions = self.partitions.lock().unwrap();
+        let follower = partitions.get(partition)?.followers.get(&replica_id)?;
+        Some(follower.end_offset)
+    }
+
+    /// Forgets `partition`, once it isn't led here anymore.
+    pub fn remove(&self, partition: &TopicPartition) {
+        self.partitions.lock().unwrap().remove(partition);
+    }
+
+    fn record_fetch_at(
+        &self,
+        partition: &TopicPartition,
+        replica_id: u32,
+        offset: u64,
+        leader_end: u64,
+        now: Instant,
+    ) -> Option<u64> {
+        if !self.is_assigned(partition, replica_id) {
+            return None;
+        }
+        let mut partitions = self.partitions.lock().unwrap();
+        let replicas = partitions.entry(partition.clone()).or_default();
+        let follower = replicas.followers.entry(replica_id).or_insert(Follower {
+            end_offset: offset,
+            leader_end,
+            caught_up: None,
+        });
+        if offset >= leader_end || offset >= follower.leader_end {
+            follower.caught_up = Some(now);
+        }
+        follower.end_offset = offset;
+        follower.leader_end = leader_end;
+        let high_watermark = replicas.advance(leader_end, self.max_lag, now);
+        self.publish_isr(partition, replicas, now);
+        Some(high_watermark)
+    }
+
+    fn high_watermark_at(&self, partition: &TopicPartition, leader_end: u64, now: Instant) -> u64 {
+        let mut partitions = self.partitions.lock().unwrap();
+        let Some(replicas) = partitions.get_mut(partition) else {
+            return leader_end;
+        };
+        let high_watermark = replicas.advance(leader_end, self.max_lag, now);
+        self.publish_isr(partition, replicas, now);
+        high_watermark
+    }
+
+    /// Publishes the in-sync followers of `partition` if they changed since
+    /// last seen.
+    fn publish_isr(&self, partition: &TopicPartition, replicas: &mut Replicas, now: Instant) {
+        let isr = replicas.in_sync(self.max_lag, now);
+        if isr == replicas.isr {
+            return;
+        }
+        replicas.isr = isr.clone();
+        if let Some(bus) = &self.events {
+            bus.publish(Event::IsrChanged {
+                partition: partition.clone(),
+                isr,
+            });
+        }
+    }
+
+    fn isr_at(&self, partition: &TopicPartition, now: Instant) -> Vec<u32> {
+        let partitions = self.partitions.lock().unwrap();
+        partitions
+            .get(partition)
+            .map_or_else(Vec::new, |replicas| replicas.in_sync(self.max_lag, now))
+    }
+}
+
+impl Replicas {
+    fn in_sync(&self, max_lag: Duration, now: Instant) -> Vec<u32> {
+        let mut isr: Vec<u32> = self
+            .followers
+            .iter()
+            .filter(|(_, follower)| follower.in_sync(max_lag, now))
+            .map(|(&id, _)| id)
+            .collect();
+        isr.sort_unstable();
+        isr
+    }
+
+    fn advance(&mut self, leader_end: u64, max_lag: Duration, now: Instant) -> u64 {
+        let lowest = self
+            .followers
+            .values()
+            .filter(|follower| follower.in_sync(max_lag, now))
+            .map(|follower| follower.end_offset)
+            .fold(leader_end, u64::min);
+        // Only a truncated leader log takes it back
+        self.high_watermark = self.high_watermark.max(lowest).min(leader_end);
+        self.high_watermark
+    }
+}
+
+impl Follower {
+    fn in_sync(&self, max_lag: Duration, now: Instant) -> bool {
+        self.caught_up
+            .is_some_and(|caught_up| now.saturating_duration_since(caught_up) <= max_lag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_high_watermark() {
+        let tracker = IsrTracker::new(Duration::from_secs(10)).with_assigned([1, 2]);
+        let partition = TopicPartition::new("events", 0);
+        let start = Instant::now();
+
+        // Without followers it follows the leader
+        assert_eq!(tracker.high_watermark_at(&partition, 5, start), 5);
+
+        // A follower behind isn't in sync yet, and doesn't hold it back
+        assert_eq!(tracker.record_fetch_at(&partition, 1, 2, 5, start), Some(5));
+        assert!(tracker.isr_at(&partition, start).is_empty());
+
+        // Catching up to where the leader was on the last fetch counts
+        assert_eq!(tracker.record_fetch_at(&partition, 1, 5, 8, start), Some(5));
+        assert_eq!(tracker.isr_at(&partition, start), vec![1]);
+        assert_eq!(tracker.high_watermark_at(&partition, 9, start), 5);
+        assert_eq!(tracker.record_fetch_at(&partition, 1, 8, 9, start), Some(8));
+
+        // A second in-sync follower holds it at the slowest
+        tracker.record_fetch_at(&partition, 2, 9, 9, start);
+        assert_eq!(tracker.isr_at(&partition, start), vec![1, 2]);
+        assert_eq!(tracker.high_watermark_at(&partition, 12, start), 8);
+
+        // Followers that fall behind for too long drop out, and it moves on
+        let later = start + Duration::from_secs(11);
+        tracker.record_fetch_at(&partition, 2, 12, 12, later);
+        assert_eq!(tracker.isr_at(&partition, later), vec![2]);
+        assert_eq!(tracker.high_watermark_at(&partition, 12, later), 12);
+
+        // And never moves back when they return
+        tracker.record_fetch_at(&partition, 1, 12, 12, later);
+        assert_eq!(tracker.high_watermark_at(&partition, 14, later), 12);
+
+        assert_eq!(tracker.end_offset(&partition, 1), Some(12));
+        assert_eq!(tracker.end_offset(&partition, 3), None);
+
+        // Other partitions are tracked on their own
+        let other = TopicPartition::new("events", 1);
+        assert_eq!(tracker.high_watermark_at(&other, 3, later), 3);
+        assert!(tracker.isr_at(&other, later).is_empty());
+
+        tracker.remove(&partition);
+        assert_eq!(tracker.end_offset(&partition, 1), None);
+    }
+
+    #[test]
+    fn test_assigned_replicas() {
+        let tracker = IsrTracker::new(Duration::from_secs(10)).with_assigned([1]);
+        let partition = TopicPartition::new("events", 0);
+        let start = Instant::now();
+
+        // Replicas it isn't assigned to don't join
+        assert_eq!(tracker.record_fetch_at(&partition, 3, 5, 5, start), None);
+        assert!(tracker.isr_at(&partition, start).is_empty());
+
+        // Unless it is being moved to them
+        tracker.add_replica(&partition, 3);
+        assert!(tracker.is_assigned(&partition, 3));
+        assert!(!tracker.is_assigned(&TopicPartition::new("events", 1), 3));
+        assert_eq!(tracker.record_fetch_at(&partition, 3, 5, 5, start), Some(5));
+        assert_eq!(tracker.isr_at(&partition, start), vec![3]);
+
+        tracker.remove(&partition);
+        assert!(!tracker.is_assigned(&partition, 3));
+        assert!(tracker.is_assigned(&partition, 1));
+    }
+
+    #[test]
+    fn test_isr_events() {
+        let bus = EventBus::new();
+        let events = bus.subscribe();
+        let tracker = IsrTracker::new(Duration::from_secs(10))
+            .with_assigned([1])
+            .with_events(bus);
+        let partition = TopicPartition::new("events", 0);
+        let start = Instant::now();
+
+        // Fetches that don't change who is in sync publish nothing
+        tracker.record_fetch_at(&partition, 1, 2, 5, start);
+        assert_eq!(events.try_next(), None);
+
+        tracker.record_fetch_at(&partition, 1, 5, 5, start);
+        tracker.record_fetch_at(&partition, 1, 5, 5, start);
+        assert_eq!(
+            events.drain(),
+            vec![Event::IsrChanged {
+                partition: partition.clone(),
+                isr: vec![1]
+            }]
+        );
+
+        // Dropping out is seen on the next look at the partition
+        tracker.high_watermark_at(&partition, 8, start + Duration::from_secs(11));
+        assert_eq!(
+            events.drain(),
+            vec![Event::IsrChanged {
+                partition,
+                isr: vec![]
+            }]
+        );
+    }
+}
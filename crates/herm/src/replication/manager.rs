@@ -0,0 +1,644 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use super::{
+    BrokerConnection, FetcherConfig, IsrTracker, ReplicaFetcher, ReplicationError,
+    DEFAULT_REPLICA_LAG_TIME_MAX,
+};
+use crate::events::{Event, EventBus};
+use crate::protocol::{ErrorCode, TopicPartition};
+use crate::request::LeaderAndIsr;
+use crate::storage::{Log, LogDirError, LogDirs};
+
+/// How often a move checks whether the target has caught up.
+const CATCH_UP_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How a [`ReplicaManager`] replicates. This broker's id is the
+/// `replica_id` its fetchers send.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplicaConfig {
+    /// How long a follower may go without catching up before it leaves the
+    /// in-sync replicas.
+    pub replica_lag_time_max: Duration,
+    pub fetcher: FetcherConfig,
+    /// Addresses of the other brokers by id. Partitions are assigned to
+    /// them, only followers listed here can fetch from this broker or take
+    /// over partitions on shutdown.
+    pub peers: HashMap<u32, String>,
+}
+
+impl Default for ReplicaConfig {
+    fn default() -> Self {
+        ReplicaConfig {
+            replica_lag_time_max: DEFAULT_REPLICA_LAG_TIME_MAX,
+            fetcher: FetcherConfig::default(),
+            peers: HashMap::new(),
+        }
+    }
+}
+
+/// Where a partition being moved away is at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Move {
+    /// The target is copying the partition, writes still come here.
+    CatchingUp,
+    /// Writes are refused until the target takes over.
+    HandingOff,
+}
+
+/// Keeps track of which partitions in the log dirs this broker leads and
+/// which it follows, running a [`ReplicaFetcher`] for each followed one.
+/// Partitions lead unless told to follow, see [`LeaderAndIsr`].
+///
+/// Also moves partitions to other brokers, see
+/// [`reassign`](ReplicaManager::reassign), and hands leadership over to
+/// followers on shutdown, see
+/// [`hand_off_leadership`](ReplicaManager::hand_off_leadership).
+///
+/// Given an [`EventBus`](ReplicaManager::with_events), publishes
+/// [`Event::LeadershipChanged`] when it starts leading or following a
+/// partition, and the in-sync replica changes of the partitions it leads.
+#[derive(Debug)]
+pub struct ReplicaManager {
+    logs: Arc<LogDirs>,
+    config: ReplicaConfig,
+    isr: IsrTracker,
+    advertised_listener: RwLock<Option<String>>,
+    followers: Mutex<HashMap<TopicPartition, ReplicaFetcher>>,
+    moves: Mutex<HashMap<TopicPartition, Move>>,
+    events: Option<EventBus>,
+}
+
+impl ReplicaManager {
+    pub fn new(logs: Arc<LogDirs>, config: ReplicaConfig) -> Self {
+        ReplicaManager {
+            logs,
+            isr: IsrTracker::new(config.replica_lag_time_max)
+                .with_assigned(config.peers.keys().copied()),
+            config,
+            advertised_listener: RwLock::new(None),
+            followers: Mutex::new(HashMap::new()),
+            moves: Mutex::new(HashMap::new()),
+            events: None,
+        }
+    }
+
+    pub fn with_events(mut self, bus: EventBus) -> Self {
+        self.isr = std::mem::take(&mut self.isr).with_events(bus.clone());
+        self.events = Some(bus);
+        self
+    }
+
+    pub fn config(&self) -> &ReplicaConfig {
+        &self.config
+    }
+
+    pub fn broker_id(&self) -> u32 {
+        self.config.fetcher.replica_id
+    }
+
+    pub fn isr(&self) -> &IsrTracker {
+        &self.isr
+    }
+
+    /// Sets the address other brokers reach this one at, which they follow
+    /// partitions moved to them from.
+    pub fn set_advertised_listener(&self, addr: String) {
+        *self.advertised_listener.write().unwrap() = Some(addr);
+    }
+
+    /// Whether writes to `partition` are taken here: it isn't followed, nor
+    /// being handed off to another broker.
+    pub fn is_leader(&self, partition: &TopicPartition) -> bool {
+        !self.followers.lock().unwrap().contains_key(partition)
+            && self.moves.lock().unwrap().get(partition) != Some(&Move::HandingOff)
+    }
+
+    /// Every partition led here, with its leader epoch.
+    pub fn leaders(&self) -> Vec<(TopicPartition, u32)> {
+        self.logs
+            .partitions()
+            .into_iter()
+            .filter(|(partition, _)| self.is_leader(partition))
+            .map(|(partition, log)| {
+                let leader_epoch = log.read().unwrap().leader_epoch();
+                (partition, leader_epoch)
+            })
+            .collect()
+    }
+
+    /// Address other brokers reach this one at, once set.
+    pub fn advertised_listener(&self) -> Option<String> {
+        self.advertised_listener.read().unwrap().clone()
+    }
+
+    /// Address of the leader `partition` is copied from, if it is followed.
+    pub fn leader_of(&self, partition: &TopicPartition) -> Option<String> {
+        let followers = self.followers.lock().unwrap();
+        Some(followers.get(partition)?.leader().to_string())
+    }
+
+    /// Follows `leader` for `partition` at `leader_epoch`, creating the
+    /// partition if it isn't held here yet.
+    pub fn follow(
+        &self,
+        partition: &TopicPartition,
+        leader: String,
+        leader_epoch: u32,
+    ) -> Result<(), ReplicationError> {
+        let log = match self.logs.get(partition) {
+            Ok(log) => log,
+            Err(LogDirError::UnknownPartition(_)) => self.logs.create(partition)?,
+            Err(err) => return Err(err.into()),
+        };
+        log.write().unwrap().set_leader_epoch(leader_epoch)?;
+        self.isr.remove(partition);
+
+        tracing::info!(%partition, leader = %leader, leader_epoch, "following");
+        // Leaders outside the peers have no id to publish
+        let leader_id = self
+            .config
+            .peers
+            .iter()
+            .find(|(_, addr)| **addr == leader)
+            .map(|(&id, _)| id);
+        if let Some(leader_id) = leader_id {
+            self.publish_leader(partition, leader_id);
+        }
+        let fetcher =
+            ReplicaFetcher::spawn(leader, partition.clone(), log, self.config.fetcher.clone());
+        // Replacing a fetcher stops it
+        self.followers
+            .lock()
+            .unwrap()
+            .insert(partition.clone(), fetcher);
+        Ok(())
+    }
+
+    /// Leads `partition` from `leader_epoch` on, no longer following anyone.
+    pub fn lead(
+        &self,
+        partition: &TopicPartition,
+        leader_epoch: u32,
+    ) -> Result<(), ReplicationError> {
+        let log = self.logs.get(partition)?;
+        log.write().unwrap().set_leader_epoch(leader_epoch)?;
+        self.followers.lock().unwrap().remove(partition);
+        tracing::info!(%partition, leader_epoch, "leading");
+        self.publish_leader(partition, self.broker_id());
+        Ok(())
+    }
+
+    fn publish_leader(&self, partition: &TopicPartition, leader: u32) {
+        if let Some(bus) = &self.events {
+            bus.publish(Event::LeadershipChanged {
+                partition: partition.clone(),
+                leader,
+            });
+        }
+    }
+
+    /// Stops every fetcher, leaving the partitions where they are.
+    pub fn stop(&self) {
+        self.followers.lock().unwrap().clear();
+    }
+
+    /// Moves `partition`, led here, to the broker `target_id` at `target`:
+    ///
+    /// 1. The target is told to follow this broker, and copies the partition.
+    /// 2. Once it is in sync, writes here are refused with
+    ///    [`ErrorCode::NotLeaderOrFollower`] and it copies the last of them.
+    /// 3. The target is told to lead at the next leader epoch, which fences
+    ///    clients still on the old one, and the partition is removed here.
+    ///
+    /// Writes go on as usual until the target is in sync, so the partition is
+    /// only unavailable for as long as the last step takes. Fails with
+    /// [`ReplicationError::TimedOut`] if the target isn't in sync within
+    /// `timeout`, leaving the partition led here.
+    pub async fn reassign(
+        &self,
+        partition: &TopicPartition,
+        target_id: u32,
+        target: &str,
+        timeout: Duration,
+    ) -> Result<(), ReplicationError> {
+        let advertised = self
+            .advertised_listener
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or(ReplicationError::NoAdvertisedListener)?;
+        let log = self.logs.get(partition)?;
+        if !self.is_leader(partition) {
+            return Err(ReplicationError::NotLeader);
+        }
+        let _move = MoveGuard::start(self, partition)?;
+        self.isr.add_replica(partition, target_id);
+        let deadline = Instant::now() + timeout;
+        let epoch = log.read().unwrap().leader_epoch();
+        let (topic, index) = (partition.topic.clone(), partition.partition);
+
+        let mut connection = connect(target, deadline).await?;
+        let follow = LeaderAndIsr::follow(topic, index, epoch, advertised)?;
+        send(&mut connection, follow, deadline).await?;
+        tracing::info!(%partition, target_id, target, "target following, catching up");
+        self.wait_in_sync(partition, &log, target_id, deadline)
+            .await?;
+
+        self.hand_off(partition, &log, target_id, &mut connection, deadline)
+            .await?;
+        tracing::info!(%partition, target_id, target, "handed off");
+        self.isr.remove(partition);
+        self.logs.remove(partition)?;
+        Ok(())
+    }
+
+    /// Hands every partition led here to one of its in-sync followers, so
+    /// they stay available while this broker is down. Runs on shutdown while
+    /// the server still serves, for the followers to fetch the last writes.
+    /// For each partition:
+    ///
+    /// 1. Writes are refused with [`ErrorCode::NotLeaderOrFollower`].
+    /// 2. The in-sync follower furthest along, among the
+    ///    [`peers`](ReplicaConfig::peers), copies the last of them.
+    /// 3. It is told to lead at the next leader epoch. The other in-sync
+    ///    followers, and this broker, are told to follow it.
+    ///
+    /// Partitions without such a follower, or that aren't handed off within
+    /// `timeout`, stay led here. Returns how many were handed off.
+    pub async fn hand_off_leadership(&self, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        let mut handed_off = 0;
+        for (partition, log) in self.logs.partitions() {
+            if !self.is_leader(&partition) {
+                continue;
+            }
+            match self.hand_off_to_follower(&partition, &log, deadline).await {
+                Ok(true) => handed_off += 1,
+                Ok(false) => tracing::info!(%partition, "no in-sync follower to hand off to"),
+                Err(err) => tracing::warn!(%partition, %err, "leadership not handed off"),
+            }
+        }
+        handed_off
+    }
+
+    /// Hands `partition` to its best in-sync follower, if it has one.
+    async fn hand_off_to_follower(
+        &self,
+        partition: &TopicPartition,
+        log: &RwLock<Log>,
+        deadline: Instant,
+    ) -> Result<bool, ReplicationError> {
+        let Some((target_id, target)) = self.successor(partition) else {
+            return Ok(false);
+        };
+        let _move = MoveGuard::start(self, partition)?;
+        let mut connection = connect(&target, deadline).await?;
+        let epoch = self
+            .hand_off(partition, log, target_id, &mut connection, deadline)
+            .await?;
+        tracing::info!(%partition, target_id, target, epoch, "handed off");
+
+        // Followers left behind would keep fetching from here
+        let (topic, index) = (partition.topic.clone(), partition.partition);
+        for replica_id in self.isr.isr(partition) {
+            let Some(addr) = self.config.peers.get(&replica_id) else {
+                continue;
+            };
+            if replica_id == target_id {
+                continue;
+            }
+            let follow = LeaderAndIsr::follow(topic.clone(), index, epoch, target.clone())?;
+            let moved = match connect(addr, deadline).await {
+                Ok(mut connection) => send(&mut connection, follow, deadline).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = moved {
+                tracing::warn!(%partition, replica_id, %err, "follower not moved to new leader");
+            }
+        }
+        self.follow(partition, target, epoch)?;
+        Ok(true)
+    }
+
+    /// The in-sync follower of `partition` furthest along, among the peers.
+    fn successor(&self, partition: &TopicPartition) -> Option<(u32, String)> {
+        self.isr
+            .isr(partition)
+            .into_iter()
+            .filter_map(|replica_id| Some((replica_id, self.config.peers.get(&replica_id)?)))
+            .max_by_key(|&(replica_id, _)| self.isr.end_offset(partition, replica_id))
+            .map(|(replica_id, addr)| (replica_id, addr.clone()))
+    }
+
+    /// Refuses writes to `partition`, waits for `target_id` to have all of
+    /// `log`, then makes it the leader at the next epoch, which it returns.
+    async fn hand_off(
+        &self,
+        partition: &TopicPartition,
+        log: &RwLock<Log>,
+        target_id: u32,
+        connection: &mut BrokerConnection,
+        deadline: Instant,
+    ) -> Result<u32, ReplicationError> {
+        let epoch = log.read().unwrap().leader_epoch() + 1;
+        {
+            // Taking the write lock waits out appends already under way
+            let _appends = log.write().unwrap();
+            self.moves
+                .lock()
+                .unwrap()
+                .insert(partition.clone(), Move::HandingOff);
+        }
+        self.wait_in_sync(partition, log, target_id, deadline)
+            .await?;
+
+        let lead = LeaderAndIsr::lead(partition.topic.clone(), partition.partition, epoch)?;
+        send(connection, lead, deadline).await?;
+        Ok(epoch)
+    }
+
+    /// Waits for `target_id` to be in sync and to have all of `log`.
+    async fn wait_in_sync(
+        &self,
+        partition: &TopicPartition,
+        log: &RwLock<Log>,
+        target_id: u32,
+        deadline: Instant,
+    ) -> Result<(), ReplicationError> {
+        loop {
+            let end = log.read().unwrap().next_offset();
+            let in_sync = self.isr.isr(partition).contains(&target_id);
+            if in_sync && self.isr.end_offset(partition, target_id) >= Some(end) {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(ReplicationError::TimedOut);
+            }
+            tokio::time::sleep(CATCH_UP_POLL_INTERVAL).await;
+        }
+    }
+}
+
+async fn connect(addr: &str, deadline: Instant) -> Result<BrokerConnection, ReplicationError> {
+    tokio::time::timeout_at(deadline, BrokerConnection::connect(addr))
+        .await
+        .map_err(|_| ReplicationError::TimedOut)?
+}
+
+/// Sends `request`, failing unless the broker took it.
+async fn send(
+    connection: &mut BrokerConnection,
+    request: LeaderAndIsr,
+    deadline: Instant,
+) -> Result<(), ReplicationError> {
+    let response = tokio::time::timeout_at(deadline, connection.leader_and_isr(request))
+        .await
+        .map_err(|_| ReplicationError::TimedOut)??;
+    match response.error {
+        ErrorCode::None => Ok(()),
+        error => Err(ReplicationError::Target(error)),
+    }
+}
+
+/// Marks a partition as being moved for as long as it is held, so a failed
+/// or dropped move takes writes again.
+struct MoveGuard<'a> {
+    manager: &'a ReplicaManager,
+    partition: &'a TopicPartition,
+}
+
+impl<'a> MoveGuard<'a> {
+    fn start(
+        manager: &'a ReplicaManager,
+        partition: &'a TopicPartition,
+    ) -> Result<Self, ReplicationError> {
+        let mut moves = manager.moves.lock().unwrap();
+        if moves.contains_key(partition) {
+            return Err(ReplicationError::MoveInProgress);
+        }
+        moves.insert(partition.clone(), Move::CatchingUp);
+        Ok(MoveGuard { manager, partition })
+    }
+}
+
+impl Drop for MoveGuard<'_> {
+    fn drop(&mut self) {
+        self.manager.moves.lock().unwrap().remove(self.partition);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bytes::Bytes;
+
+    use crate::broker::{LogHandler, Server};
+    use crate::record::{Record, RecordBatch};
+    use crate::request::ReassignPartition;
+    use crate::storage::{LogConfig, Placement};
+
+    fn logs(dir: &tempfile::TempDir) -> Arc<LogDirs> {
+        Arc::new(LogDirs::open(
+            [dir.path()],
+            LogConfig::default(),
+            Placement::default(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_lead_and_follow() {
+        let dir = tempfile::tempdir().unwrap();
+        let replicas = ReplicaManager::new(logs(&dir), ReplicaConfig::default());
+        let partition = TopicPartition::new("events", 0);
+
+        // Following creates the partition
+        replicas
+            .follow(&partition, "127.0.0.1:1".to_string(), 3)
+            .unwrap();
+        assert!(!replicas.is_leader(&partition));
+        assert_eq!(
+            replicas.leader_of(&partition).as_deref(),
+            Some("127.0.0.1:1")
+        );
+        let log = replicas.logs.get(&partition).unwrap();
+        assert_eq!(log.read().unwrap().leader_epoch(), 3);
+
+        // Leadership never goes back to an older epoch
+        assert!(matches!(
+            replicas.lead(&partition, 2),
+            Err(ReplicationError::Log(_))
+        ));
+        replicas.lead(&partition, 4).unwrap();
+        assert!(replicas.is_leader(&partition));
+        assert_eq!(replicas.leader_of(&partition), None);
+        assert_eq!(log.read().unwrap().leader_epoch(), 4);
+    }
+
+    /// Runs a broker with `broker_id` on the partitions in `dir`.
+    async fn broker(
+        dir: &tempfile::TempDir,
+        broker_id: u32,
+        peers: HashMap<u32, String>,
+    ) -> (LogHandler, String) {
+        let config = ReplicaConfig {
+            fetcher: FetcherConfig {
+                replica_id: broker_id,
+                max_wait: Duration::from_millis(20),
+                backoff: Duration::from_millis(20),
+                ..Default::default()
+            },
+            peers,
+            ..Default::default()
+        };
+        let handler = LogHandler::new(logs(dir)).with_replica_config(config);
+        let server = Server::bind("127.0.0.1:0", handler.clone()).await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        handler.replicas().set_advertised_listener(addr.clone());
+        tokio::spawn(server.run());
+        (handler, addr)
+    }
+
+    #[tokio::test]
+    async fn test_reassign() {
+        let (source_dir, target_dir) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let (source, source_addr) = broker(&source_dir, 0, HashMap::new()).await;
+        let (target, target_addr) = broker(&target_dir, 1, HashMap::new()).await;
+        let partition = TopicPartition::new("events", 0);
+        let log = source.logs().create(&partition).unwrap();
+        for value in ["a", "b", "c"] {
+            log.write()
+                .unwrap()
+                .append(RecordBatch::new(vec![Record::new(
+                    None,
+                    Some(Bytes::from_static(value.as_bytes())),
+                )]))
+                .unwrap();
+        }
+        let records = log.read().unwrap().read(0, usize::MAX).unwrap();
+
+        let mut connection = BrokerConnection::connect(&source_addr).await.unwrap();
+        let reassign = ReassignPartition::new("events".to_string(), 0, 1, target_addr)
+            .unwrap()
+            .with_timeout(10_000);
+        let response = connection.reassign(reassign).await.unwrap();
+        assert_eq!(response.error, ErrorCode::None);
+
+        // The target leads at the next epoch with everything written
+        assert!(target.replicas().is_leader(&partition));
+        let moved = target.logs().get(&partition).unwrap();
+        assert_eq!(moved.read().unwrap().leader_epoch(), 1);
+        assert_eq!(moved.read().unwrap().read(0, usize::MAX).unwrap(), records);
+        assert!(matches!(
+            source.logs().get(&partition),
+            Err(LogDirError::UnknownPartition(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_hand_off_leadership() {
+        let (leader_dir, follower_dir) =
+            (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let (follower, follower_addr) = broker(&follower_dir, 1, HashMap::new()).await;
+        let peers = HashMap::from([(1, follower_addr.clone())]);
+        let (leader, leader_addr) = broker(&leader_dir, 0, peers).await;
+        let partition = TopicPartition::new("events", 0);
+        let log = leader.logs().create(&partition).unwrap();
+        log.write()
+            .unwrap()
+            .append(RecordBatch::new(vec![Record::new(
+                None,
+                Some(Bytes::from_static(b"a")),
+            )]))
+            .unwrap();
+        // Nobody follows this one
+        let unfollowed = TopicPartition::new("events", 1);
+        leader.logs().create(&unfollowed).unwrap();
+
+        follower
+            .replicas()
+            .follow(&partition, leader_addr.clone(), 0)
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while leader.replicas().isr().isr(&partition).is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let handed_off = leader
+            .replicas()
+            .hand_off_leadership(Duration::from_secs(10))
+            .await;
+        assert_eq!(handed_off, 1);
+        assert!(leader.replicas().is_leader(&unfollowed));
+
+        // The leaders swap places at the next epoch
+        assert!(follower.replicas().is_leader(&partition));
+        let copy = follower.logs().get(&partition).unwrap();
+        assert_eq!(copy.read().unwrap().leader_epoch(), 1);
+        assert_eq!(
+            copy.read().unwrap().read(0, usize::MAX).unwrap(),
+            log.read().unwrap().read(0, usize::MAX).unwrap()
+        );
+        assert!(!leader.replicas().is_leader(&partition));
+        assert_eq!(leader.replicas().leader_of(&partition), Some(follower_addr));
+        assert_eq!(log.read().unwrap().leader_epoch(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reassign_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = logs(&dir);
+        let replicas = ReplicaManager::new(logs.clone(), ReplicaConfig::default());
+        let partition = TopicPartition::new("events", 0);
+        let timeout = Duration::from_millis(100);
+
+        assert!(matches!(
+            replicas
+                .reassign(&partition, 1, "127.0.0.1:1", timeout)
+                .await,
+            Err(ReplicationError::NoAdvertisedListener)
+        ));
+        replicas.set_advertised_listener("127.0.0.1:9092".to_string());
+        assert!(matches!(
+            replicas
+                .reassign(&partition, 1, "127.0.0.1:1", timeout)
+                .await,
+            Err(ReplicationError::LogDir(LogDirError::UnknownPartition(_)))
+        ));
+
+        // An unreachable target leaves the partition led here
+        let log = logs.create(&partition).unwrap();
+        log.write()
+            .unwrap()
+            .append(RecordBatch::new(vec![Record::new(
+                None,
+                Some(Bytes::from_static(b"a")),
+            )]))
+            .unwrap();
+        assert!(matches!(
+            replicas
+                .reassign(&partition, 1, "127.0.0.1:1", timeout)
+                .await,
+            Err(ReplicationError::Io(_))
+        ));
+        assert!(replicas.is_leader(&partition));
+        assert!(logs.get(&partition).is_ok());
+
+        replicas
+            .follow(&partition, "127.0.0.1:1".to_string(), 0)
+            .unwrap();
+        assert!(matches!(
+            replicas
+                .reassign(&partition, 1, "127.0.0.1:1", timeout)
+                .await,
+            Err(ReplicationError::NotLeader)
+        ));
+    }
+}
@@ -0,0 +1,8 @@
+mod connection;
+mod fetcher;
+mod isr;
+mod manager;
+pub use connection::{BrokerConnection, REPLICA_CLIENT_ID};
+pub use fetcher::{FetcherConfig, ReplicaFetcher, ReplicationError};
+pub use isr::{IsrTracker, DEFAULT_REPLICA_LAG_TIME_MAX};
+pub use manager::{ReplicaConfig, ReplicaManager};
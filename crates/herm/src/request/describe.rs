@@ -0,0 +1,172 @@
+use std::fmt::Display;
+use thiserror::Error;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::protocol::{
+    get_str, put_str, str_size, validate_topic_name, DecodeLimits, InvalidTopicName, LimitExceeded,
+};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum DescribeCreationError {
+    #[error("Topic name is too long")]
+    TopicTooLong,
+    #[error("Malformed bytes")]
+    MalformedBytes,
+    #[error(transparent)]
+    InvalidTopicName(#[from] InvalidTopicName),
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitExceeded),
+}
+
+/// Asks for the live brokers of the cluster, with their version and rack.
+/// Has no body.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DescribeCluster;
+
+impl DescribeCluster {
+    pub fn new() -> Self {
+        DescribeCluster
+    }
+
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, DescribeCreationError> {
+        if !bytes.is_empty() {
+            return Err(DescribeCreationError::MalformedBytes);
+        }
+        Ok(DescribeCluster)
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        Bytes::new()
+    }
+
+    pub fn encode_into(&self, _buf: &mut impl BufMut) {}
+
+    pub fn size(&self) -> usize {
+        0
+    }
+}
+
+impl Display for DescribeCluster {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DescribeClusterRequest()")
+    }
+}
+
+/// Asks a broker for its log dirs and the size of the partitions of
+/// `topics` on them, or of every topic when empty. Only covers the
+/// answering broker, tooling asks each broker from a [`DescribeCluster`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DescribeLogDirs {
+    topics: Vec<String>,
+}
+
+impl DescribeLogDirs {
+    /// Asks about every topic.
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    pub fn new(topics: Vec<String>) -> Result<Self, DescribeCreationError> {
+        for topic in &topics {
+            if topic.len() > u16::MAX as usize {
+                return Err(DescribeCreationError::TopicTooLong);
+            }
+            validate_topic_name(topic)?;
+        }
+
+        Ok(DescribeLogDirs { topics })
+    }
+
+    /// Topics asked about, empty for all of them.
+    pub fn topics(&self) -> &[String] {
+        &self.topics
+    }
+
+    /// Decodes a request within the [`DecodeLimits`] defaults.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, DescribeCreationError> {
+        Self::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    pub fn from_bytes_with_limits(
+        mut bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<Self, DescribeCreationError> {
+        if bytes.remaining() < 4 {
+            return Err(DescribeCreationError::MalformedBytes);
+        }
+        let count = bytes.get_u32();
+
+        // Not reserved up front, the count is the client's word
+        let mut topics = Vec::new();
+        for _ in 0..count {
+            if bytes.remaining() < 2 {
+                return Err(DescribeCreationError::MalformedBytes);
+            }
+            limits.check_topic_len(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)?;
+            topics.push(get_str(&mut bytes).ok_or(DescribeCreationError::MalformedBytes)?);
+        }
+        if bytes.has_remaining() {
+            return Err(DescribeCreationError::MalformedBytes);
+        }
+
+        Self::new(topics)
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_u32(self.topics.len() as u32);
+        for topic in &self.topics {
+            put_str(buf, topic);
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        4 + self
+            .topics
+            .iter()
+            .map(|topic| str_size(topic))
+            .sum::<usize>()
+    }
+}
+
+impl Display for DescribeLogDirs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "DescribeLogDirsRequest(topics:[{}])",
+            self.topics.join(",")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        assert_eq!(
+            DescribeCluster::from_bytes(DescribeCluster::new().to_bytes()),
+            Ok(DescribeCluster)
+        );
+        assert_eq!(
+            DescribeCluster::from_bytes(Bytes::from_static(&[0x00])),
+            Err(DescribeCreationError::MalformedBytes)
+        );
+
+        let request = DescribeLogDirs::new(vec!["events".to_string()]).unwrap();
+        let bytes = request.to_bytes();
+        assert_eq!(bytes.len(), request.size());
+        assert_eq!(DescribeLogDirs::from_bytes(bytes.clone()).unwrap(), request);
+        assert_eq!(
+            DescribeLogDirs::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(DescribeCreationError::MalformedBytes)
+        );
+    }
+}
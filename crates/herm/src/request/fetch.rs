@@ -3,22 +3,37 @@ use thiserror::Error;
 
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 
+use crate::protocol::{
+    validate_topic_name, DecodeLimits, InvalidTopicName, LimitExceeded, NO_LEADER_EPOCH,
+};
+
 #[derive(Error, Debug, PartialEq)]
 pub enum FetchCreationError {
     #[error("Topic name is too long")]
     TopicTooLong,
     #[error("Malformed bytes")]
     MalformedBytes,
+    #[error(transparent)]
+    InvalidTopicName(#[from] InvalidTopicName),
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitExceeded),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Fetch {
     topic: String,
     partition: u32,
     offset: u64,
     size: u32,
+    min_bytes: u32,
+    max_wait_ms: u32,
+    replica_id: Option<u32>,
+    leader_epoch: Option<u32>,
 }
 
+/// Replica id sent by fetches that don't come from a follower.
+const NO_REPLICA_ID: u32 = u32::MAX;
+
 impl Fetch {
     pub fn new(
         topic: String,
@@ -32,29 +47,102 @@ impl Fetch {
             return Err(FetchCreationError::TopicTooLong);
         }
 
+        validate_topic_name(&topic)?;
+
         Ok(Fetch {
             topic,
             partition,
             offset,
             size,
+            min_bytes: 0,
+            max_wait_ms: 0,
+            replica_id: None,
+            leader_epoch: None,
         })
     }
 
-    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, FetchCreationError> {
+    /// Asks the broker to hold the fetch for up to `max_wait_ms` until at
+    /// least `min_bytes` are available, rather than answering straight away.
+    pub fn wait_for(mut self, min_bytes: u32, max_wait_ms: u32) -> Self {
+        self.min_bytes = min_bytes;
+        self.max_wait_ms = max_wait_ms;
+        self
+    }
+
+    /// Marks the fetch as coming from the follower `replica_id`, which may
+    /// read past the high watermark and moves it along as it catches up.
+    pub fn from_replica(mut self, replica_id: u32) -> Self {
+        self.replica_id = (replica_id != NO_REPLICA_ID).then_some(replica_id);
+        self
+    }
+
+    /// Has the broker check the partition is still at `leader_epoch`, see
+    /// [`ErrorCode::FencedLeaderEpoch`](crate::protocol::ErrorCode::FencedLeaderEpoch).
+    pub fn with_leader_epoch(mut self, leader_epoch: u32) -> Self {
+        self.leader_epoch = (leader_epoch != NO_LEADER_EPOCH).then_some(leader_epoch);
+        self
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub fn partition(&self) -> u32 {
+        self.partition
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Upper bound on the bytes of batches to return.
+    pub fn max_bytes(&self) -> u32 {
+        self.size
+    }
+
+    pub fn min_bytes(&self) -> u32 {
+        self.min_bytes
+    }
+
+    pub fn max_wait_ms(&self) -> u32 {
+        self.max_wait_ms
+    }
+
+    /// The follower fetching, `None` for consumers.
+    pub fn replica_id(&self) -> Option<u32> {
+        self.replica_id
+    }
+
+    /// The leader epoch the fetcher knows of, `None` if it isn't checked.
+    pub fn leader_epoch(&self) -> Option<u32> {
+        self.leader_epoch
+    }
+
+    /// Decodes a fetch within the [`DecodeLimits`] defaults.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, FetchCreationError> {
+        Self::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    pub fn from_bytes_with_limits(
+        mut bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<Self, FetchCreationError> {
         // Check if topic length is present
         if bytes.remaining() < 2 {
             return Err(FetchCreationError::MalformedBytes);
         }
 
         let topic_len = bytes.get_u16() as usize;
+        limits.check_topic_len(topic_len)?;
 
         // Check bytes has the right length
-        if bytes.len() != topic_len + 4 + 8 + 4 {
+        if bytes.len() != topic_len + 4 + 8 + 4 + 4 + 4 + 4 + 4 {
             return Err(FetchCreationError::MalformedBytes);
         }
 
         let topic = String::from_utf8(bytes.slice(0..topic_len).to_vec())
             .map_err(|_| FetchCreationError::MalformedBytes)?;
+        validate_topic_name(&topic)?;
 
         // Advance bytes over topic name
         bytes.advance(topic_len);
@@ -64,12 +152,22 @@ impl Fetch {
             partition: bytes.get_u32(),
             offset: bytes.get_u64(),
             size: bytes.get_u32(),
+            min_bytes: bytes.get_u32(),
+            max_wait_ms: bytes.get_u32(),
+            replica_id: Some(bytes.get_u32()).filter(|&id| id != NO_REPLICA_ID),
+            leader_epoch: Some(bytes.get_u32()).filter(|&epoch| epoch != NO_LEADER_EPOCH),
         })
     }
 
     pub fn to_bytes(&self) -> Bytes {
         let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
 
+    /// Writes the encoded request into `buf`, so many messages can share one
+    /// output buffer.
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
         // Write String with length prefix encoded as u16
         buf.put_u16(self.topic.len() as u16);
         buf.put(self.topic.as_bytes());
@@ -78,12 +176,14 @@ impl Fetch {
         buf.put_u32(self.partition);
         buf.put_u64(self.offset);
         buf.put_u32(self.size);
-
-        buf.freeze()
+        buf.put_u32(self.min_bytes);
+        buf.put_u32(self.max_wait_ms);
+        buf.put_u32(self.replica_id.unwrap_or(NO_REPLICA_ID));
+        buf.put_u32(self.leader_epoch.unwrap_or(NO_LEADER_EPOCH));
     }
 
     pub fn size(&self) -> usize {
-        2 + self.topic.len() + 4 + 8 + 4
+        2 + self.topic.len() + 4 + 8 + 4 + 4 + 4 + 4 + 4
     }
 }
 
@@ -91,8 +191,8 @@ impl Display for Fetch {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "FetchRequest(topic:{}, part:{} offset:{} maxSize:{})",
-            self.topic, self.partition, self.offset, self.size
+            "FetchRequest(topic:{}, part:{} offset:{} maxSize:{} minBytes:{} maxWaitMs:{})",
+            self.topic, self.partition, self.offset, self.size, self.min_bytes, self.max_wait_ms
         )
     }
 }
@@ -123,6 +223,36 @@ mod tests {
         assert_eq!(fetch.unwrap_err(), FetchCreationError::TopicTooLong)
     }
 
+    #[test]
+    fn test_new_invalid_topic_name() {
+        let fetch = Fetch::new("".to_string(), 0, 0, 1024);
+        assert_eq!(
+            fetch.unwrap_err(),
+            FetchCreationError::InvalidTopicName(InvalidTopicName::Empty)
+        );
+
+        let fetch = Fetch::new("bad topic".to_string(), 0, 0, 1024);
+        assert_eq!(
+            fetch.unwrap_err(),
+            FetchCreationError::InvalidTopicName(InvalidTopicName::IllegalChar(' '))
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let bytes = Fetch::new("test".to_string(), 0, 0, 1024)
+            .unwrap()
+            .to_bytes();
+        let limits = DecodeLimits {
+            max_topic_len: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            Fetch::from_bytes_with_limits(bytes, &limits),
+            Err(LimitExceeded::TopicLength { len: 4, limit: 3 }.into())
+        );
+    }
+
     #[test]
     fn test_from_bytes() {
         let fetch = Fetch::new("test".to_string(), 0, 0, 1024).unwrap();
@@ -133,6 +263,33 @@ mod tests {
         assert_eq!(fetch.partition, fetch_from_bytes.partition);
         assert_eq!(fetch.offset, fetch_from_bytes.offset);
         assert_eq!(fetch.size, fetch_from_bytes.size);
+
+        let fetch = fetch.wait_for(64, 500);
+        assert_eq!(Fetch::from_bytes(fetch.to_bytes()).unwrap(), fetch);
+
+        let fetch = fetch.from_replica(2);
+        assert_eq!(fetch.replica_id(), Some(2));
+        assert_eq!(Fetch::from_bytes(fetch.to_bytes()).unwrap(), fetch);
+
+        let fetch = fetch.with_leader_epoch(3);
+        assert_eq!(fetch.leader_epoch(), Some(3));
+        assert_eq!(Fetch::from_bytes(fetch.to_bytes()).unwrap(), fetch);
+    }
+
+    #[test]
+    fn test_encode_into() {
+        let first = Fetch::new("test".to_string(), 0, 0, 1024).unwrap();
+        let second = Fetch::new("other".to_string(), 1, 8, 512).unwrap();
+
+        let mut buf = BytesMut::new();
+        first.encode_into(&mut buf);
+        second.encode_into(&mut buf);
+        assert_eq!(buf.len(), first.size() + second.size());
+
+        let mut bytes = buf.freeze();
+        let first_bytes = bytes.split_to(first.size());
+        assert_eq!(first_bytes, first.to_bytes());
+        assert_eq!(bytes, second.to_bytes());
     }
 
     #[test]
@@ -179,6 +336,33 @@ mod tests {
         assert!(fetch.is_err());
         assert_eq!(fetch.unwrap_err(), FetchCreationError::MalformedBytes);
 
+        // Missing min bytes and max wait
+        let fetch = Fetch::from_bytes(Bytes::from_static(&[
+            0x00, 0x04, // Length of topic name
+            b't', b'e', b's', b't', // Topic name
+            0x00, 0x00, 0x00, 0x06, // Partition
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, // Offset
+            0x00, 0x00, 0x00, 0x03, // Size
+        ]));
+        assert_eq!(fetch.unwrap_err(), FetchCreationError::MalformedBytes);
+
+        // Illegal topic name
+        let fetch = Fetch::from_bytes(Bytes::from_static(&[
+            0x00, 0x04, // Length of topic name
+            b't', b'e', b's', b' ', // Topic name
+            0x00, 0x00, 0x00, 0x06, // Partition
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, // Offset
+            0x00, 0x00, 0x00, 0x03, // Size
+            0x00, 0x00, 0x00, 0x10, // Min bytes
+            0x00, 0x00, 0x01, 0xF4, // Max wait
+            0xFF, 0xFF, 0xFF, 0xFF, // Replica id
+            0xFF, 0xFF, 0xFF, 0xFF, // Leader epoch
+        ]));
+        assert_eq!(
+            fetch.unwrap_err(),
+            FetchCreationError::InvalidTopicName(InvalidTopicName::IllegalChar(' '))
+        );
+
         // Works
         let fetch = Fetch::from_bytes(Bytes::from_static(&[
             0x00, 0x04, // Length of topic name
@@ -186,6 +370,10 @@ mod tests {
             0x00, 0x00, 0x00, 0x06, // Partition
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, // Offset
             0x00, 0x00, 0x00, 0x03, // Size
+            0x00, 0x00, 0x00, 0x10, // Min bytes
+            0x00, 0x00, 0x01, 0xF4, // Max wait
+            0xFF, 0xFF, 0xFF, 0xFF, // Replica id
+            0x00, 0x00, 0x00, 0x02, // Leader epoch
         ]));
         assert!(fetch.is_ok());
 
@@ -194,11 +382,15 @@ mod tests {
         assert_eq!(fetch.partition, 6);
         assert_eq!(fetch.offset, 8);
         assert_eq!(fetch.size, 3);
+        assert_eq!(fetch.min_bytes, 16);
+        assert_eq!(fetch.max_wait_ms, 500);
+        assert_eq!(fetch.replica_id, None);
+        assert_eq!(fetch.leader_epoch, Some(2));
     }
 
     #[test]
     fn test_size() {
         let fetch = Fetch::new("test".to_string(), 0, 0, 1024).unwrap();
-        assert_eq!(fetch.size(), 2 + 4 + 8 + 4 + 4);
+        assert_eq!(fetch.size(), 2 + 4 + 4 + 8 + 4 + 4 + 4 + 4 + 4);
     }
 }
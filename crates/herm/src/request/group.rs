@@ -0,0 +1,874 @@
+use std::fmt::Display;
+use thiserror::Error;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::protocol::{
+    get_str, put_str, str_size, validate_topic_name, DecodeLimits, InvalidTopicName, LimitExceeded,
+    TopicPartition,
+};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum GroupCreationError {
+    #[error("Group id is empty")]
+    EmptyGroupId,
+    #[error("Group id is too long")]
+    GroupIdTooLong,
+    #[error("Member id is too long")]
+    MemberIdTooLong,
+    #[error("Assignor name is too long")]
+    AssignorTooLong,
+    #[error("Topic name is too long")]
+    TopicTooLong,
+    #[error("Malformed bytes")]
+    MalformedBytes,
+    #[error(transparent)]
+    InvalidTopicName(#[from] InvalidTopicName),
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitExceeded),
+}
+
+fn validate_group_id(group: &str) -> Result<(), GroupCreationError> {
+    if group.is_empty() {
+        return Err(GroupCreationError::EmptyGroupId);
+    }
+    if group.len() > u16::MAX as usize {
+        return Err(GroupCreationError::GroupIdTooLong);
+    }
+    Ok(())
+}
+
+fn validate_member_id(member_id: &str) -> Result<(), GroupCreationError> {
+    if member_id.len() > u16::MAX as usize {
+        return Err(GroupCreationError::MemberIdTooLong);
+    }
+    Ok(())
+}
+
+/// Stores the offsets a consumer group has consumed up to, the next offset
+/// each partition should be read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OffsetCommit {
+    group: String,
+    offsets: Vec<(TopicPartition, u64)>,
+}
+
+impl OffsetCommit {
+    pub fn new(
+        group: String,
+        offsets: Vec<(TopicPartition, u64)>,
+    ) -> Result<Self, GroupCreationError> {
+        validate_group_id(&group)?;
+        for (partition, _) in &offsets {
+            if partition.topic.len() > u16::MAX as usize {
+                return Err(GroupCreationError::TopicTooLong);
+            }
+            validate_topic_name(&partition.topic)?;
+        }
+
+        Ok(OffsetCommit { group, offsets })
+    }
+
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    pub fn offsets(&self) -> &[(TopicPartition, u64)] {
+        &self.offsets
+    }
+
+    pub fn into_offsets(self) -> Vec<(TopicPartition, u64)> {
+        self.offsets
+    }
+
+    /// Decodes a request within the [`DecodeLimits`] defaults.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, GroupCreationError> {
+        Self::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    pub fn from_bytes_with_limits(
+        mut bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<Self, GroupCreationError> {
+        let group = get_str(&mut bytes).ok_or(GroupCreationError::MalformedBytes)?;
+        if bytes.remaining() < 4 {
+            return Err(GroupCreationError::MalformedBytes);
+        }
+        let count = bytes.get_u32();
+
+        // Not reserved up front, the count is the client's word
+        let mut offsets = Vec::new();
+        for _ in 0..count {
+            if bytes.remaining() < 2 {
+                return Err(GroupCreationError::MalformedBytes);
+            }
+            limits.check_topic_len(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)?;
+            let topic = get_str(&mut bytes).ok_or(GroupCreationError::MalformedBytes)?;
+            if bytes.remaining() < 4 + 8 {
+                return Err(GroupCreationError::MalformedBytes);
+            }
+            let partition = bytes.get_u32();
+            let offset = bytes.get_u64();
+            offsets.push((TopicPartition::new(topic, partition), offset));
+        }
+        if bytes.has_remaining() {
+            return Err(GroupCreationError::MalformedBytes);
+        }
+
+        Self::new(group, offsets)
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        put_str(buf, &self.group);
+        buf.put_u32(self.offsets.len() as u32);
+        for (partition, offset) in &self.offsets {
+            put_str(buf, &partition.topic);
+            buf.put_u32(partition.partition);
+            buf.put_u64(*offset);
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        let offsets: usize = self
+            .offsets
+            .iter()
+            .map(|(partition, _)| str_size(&partition.topic) + 4 + 8)
+            .sum();
+        str_size(&self.group) + 4 + offsets
+    }
+}
+
+impl Display for OffsetCommit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "OffsetCommitRequest(group:{} offsets:{})",
+            self.group,
+            self.offsets.len()
+        )
+    }
+}
+
+/// Asks for every consumer group the broker coordinates. Has no body.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ListGroups;
+
+impl ListGroups {
+    pub fn new() -> Self {
+        ListGroups
+    }
+
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, GroupCreationError> {
+        if !bytes.is_empty() {
+            return Err(GroupCreationError::MalformedBytes);
+        }
+        Ok(ListGroups)
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        Bytes::new()
+    }
+
+    pub fn encode_into(&self, _buf: &mut impl BufMut) {}
+
+    pub fn size(&self) -> usize {
+        0
+    }
+}
+
+impl Display for ListGroups {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ListGroupsRequest()")
+    }
+}
+
+/// Asks for the members, committed offsets and lag of `groups`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescribeGroups {
+    groups: Vec<String>,
+}
+
+impl DescribeGroups {
+    pub fn new(groups: Vec<String>) -> Result<Self, GroupCreationError> {
+        for group in &groups {
+            validate_group_id(group)?;
+        }
+
+        Ok(DescribeGroups { groups })
+    }
+
+    pub fn groups(&self) -> &[String] {
+        &self.groups
+    }
+
+    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, GroupCreationError> {
+        if bytes.remaining() < 4 {
+            return Err(GroupCreationError::MalformedBytes);
+        }
+        let count = bytes.get_u32();
+
+        let mut groups = Vec::new();
+        for _ in 0..count {
+            groups.push(get_str(&mut bytes).ok_or(GroupCreationError::MalformedBytes)?);
+        }
+        if bytes.has_remaining() {
+            return Err(GroupCreationError::MalformedBytes);
+        }
+
+        Self::new(groups)
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_u32(self.groups.len() as u32);
+        for group in &self.groups {
+            put_str(buf, group);
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        4 + self
+            .groups
+            .iter()
+            .map(|group| str_size(group))
+            .sum::<usize>()
+    }
+}
+
+impl Display for DescribeGroups {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "DescribeGroupsRequest(groups:[{}])",
+            self.groups.join(",")
+        )
+    }
+}
+
+/// Joins `group`, or rejoins it when `member_id` isn't empty, subscribed to
+/// `topics`. Answered once every member has (re)joined or the rebalance
+/// timeout passed.
+///
+/// Lists the assignors the member can assign partitions with, by
+/// preference, and the partitions it owns going into the rebalance, which
+/// sticky assignors keep where they are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoinGroup {
+    group: String,
+    member_id: String,
+    session_timeout_ms: u32,
+    rebalance_timeout_ms: u32,
+    topics: Vec<String>,
+    assignors: Vec<String>,
+    owned: Vec<TopicPartition>,
+}
+
+impl JoinGroup {
+    pub fn new(
+        group: String,
+        member_id: String,
+        session_timeout_ms: u32,
+        rebalance_timeout_ms: u32,
+        topics: Vec<String>,
+        assignors: Vec<String>,
+        owned: Vec<TopicPartition>,
+    ) -> Result<Self, GroupCreationError> {
+        validate_group_id(&group)?;
+        validate_member_id(&member_id)?;
+        for topic in &topics {
+            validate_topic(topic)?;
+        }
+        if assignors.iter().any(|name| name.len() > u16::MAX as usize) {
+            return Err(GroupCreationError::AssignorTooLong);
+        }
+        for partition in &owned {
+            validate_topic(&partition.topic)?;
+        }
+
+        Ok(JoinGroup {
+            group,
+            member_id,
+            session_timeout_ms,
+            rebalance_timeout_ms,
+            topics,
+            assignors,
+            owned,
+        })
+    }
+
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    /// Empty for a consumer joining for the first time.
+    pub fn member_id(&self) -> &str {
+        &self.member_id
+    }
+
+    /// How long the member stays in the group without a heartbeat.
+    pub fn session_timeout_ms(&self) -> u32 {
+        self.session_timeout_ms
+    }
+
+    /// How long the coordinator waits for the other members to rejoin.
+    pub fn rebalance_timeout_ms(&self) -> u32 {
+        self.rebalance_timeout_ms
+    }
+
+    pub fn topics(&self) -> &[String] {
+        &self.topics
+    }
+
+    pub fn assignors(&self) -> &[String] {
+        &self.assignors
+    }
+
+    pub fn owned(&self) -> &[TopicPartition] {
+        &self.owned
+    }
+
+    /// Decodes a request within the [`DecodeLimits`] defaults.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, GroupCreationError> {
+        Self::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    pub fn from_bytes_with_limits(
+        mut bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<Self, GroupCreationError> {
+        let group = get_str(&mut bytes).ok_or(GroupCreationError::MalformedBytes)?;
+        let member_id = get_str(&mut bytes).ok_or(GroupCreationError::MalformedBytes)?;
+        if bytes.remaining() < 4 + 4 + 4 {
+            return Err(GroupCreationError::MalformedBytes);
+        }
+        let session_timeout_ms = bytes.get_u32();
+        let rebalance_timeout_ms = bytes.get_u32();
+        let count = bytes.get_u32();
+
+        let mut topics = Vec::new();
+        for _ in 0..count {
+            topics.push(get_topic(&mut bytes, limits)?);
+        }
+
+        if bytes.remaining() < 4 {
+            return Err(GroupCreationError::MalformedBytes);
+        }
+        let count = bytes.get_u32();
+        let mut assignors = Vec::new();
+        for _ in 0..count {
+            assignors.push(get_str(&mut bytes).ok_or(GroupCreationError::MalformedBytes)?);
+        }
+
+        let owned = get_partitions(&mut bytes, limits)?;
+        if bytes.has_remaining() {
+            return Err(GroupCreationError::MalformedBytes);
+        }
+
+        Self::new(
+            group,
+            member_id,
+            session_timeout_ms,
+            rebalance_timeout_ms,
+            topics,
+            assignors,
+            owned,
+        )
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        put_str(buf, &self.group);
+        put_str(buf, &self.member_id);
+        buf.put_u32(self.session_timeout_ms);
+        buf.put_u32(self.rebalance_timeout_ms);
+        buf.put_u32(self.topics.len() as u32);
+        for topic in &self.topics {
+            put_str(buf, topic);
+        }
+        buf.put_u32(self.assignors.len() as u32);
+        for name in &self.assignors {
+            put_str(buf, name);
+        }
+        put_partitions(buf, &self.owned);
+    }
+
+    pub fn size(&self) -> usize {
+        let topics: usize = self.topics.iter().map(|topic| str_size(topic)).sum();
+        let assignors: usize = self.assignors.iter().map(|name| str_size(name)).sum();
+        str_size(&self.group)
+            + str_size(&self.member_id)
+            + 4
+            + 4
+            + 4
+            + topics
+            + 4
+            + assignors
+            + partitions_size(&self.owned)
+    }
+}
+
+impl Display for JoinGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "JoinGroupRequest(group:{} member:{} topics:[{}] assignors:[{}])",
+            self.group,
+            self.member_id,
+            self.topics.join(","),
+            self.assignors.join(",")
+        )
+    }
+}
+
+/// Fetches the partitions assigned to a member in `generation` of `group`.
+/// The group leader sends every member's assignment along, the other
+/// members wait for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncGroup {
+    group: String,
+    generation: u32,
+    member_id: String,
+    assignments: Vec<(String, Vec<TopicPartition>)>,
+}
+
+impl SyncGroup {
+    pub fn new(
+        group: String,
+        generation: u32,
+        member_id: String,
+        assignments: Vec<(String, Vec<TopicPartition>)>,
+    ) -> Result<Self, GroupCreationError> {
+        validate_group_id(&group)?;
+        validate_member_id(&member_id)?;
+        for (member, partitions) in &assignments {
+            validate_member_id(member)?;
+            for partition in partitions {
+                validate_topic(&partition.topic)?;
+            }
+        }
+
+        Ok(SyncGroup {
+            group,
+            generation,
+            member_id,
+            assignments,
+        })
+    }
+
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    pub fn member_id(&self) -> &str {
+        &self.member_id
+    }
+
+    /// The partitions of each member, empty unless sent by the leader.
+    pub fn assignments(&self) -> &[(String, Vec<TopicPartition>)] {
+        &self.assignments
+    }
+
+    pub fn into_assignments(self) -> Vec<(String, Vec<TopicPartition>)> {
+        self.assignments
+    }
+
+    /// Decodes a request within the [`DecodeLimits`] defaults.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, GroupCreationError> {
+        Self::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    pub fn from_bytes_with_limits(
+        mut bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<Self, GroupCreationError> {
+        let group = get_str(&mut bytes).ok_or(GroupCreationError::MalformedBytes)?;
+        if bytes.remaining() < 4 {
+            return Err(GroupCreationError::MalformedBytes);
+        }
+        let generation = bytes.get_u32();
+        let member_id = get_str(&mut bytes).ok_or(GroupCreationError::MalformedBytes)?;
+        if bytes.remaining() < 4 {
+            return Err(GroupCreationError::MalformedBytes);
+        }
+        let count = bytes.get_u32();
+
+        let mut assignments = Vec::new();
+        for _ in 0..count {
+            let member = get_str(&mut bytes).ok_or(GroupCreationError::MalformedBytes)?;
+            assignments.push((member, get_partitions(&mut bytes, limits)?));
+        }
+        if bytes.has_remaining() {
+            return Err(GroupCreationError::MalformedBytes);
+        }
+
+        Self::new(group, generation, member_id, assignments)
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        put_str(buf, &self.group);
+        buf.put_u32(self.generation);
+        put_str(buf, &self.member_id);
+        buf.put_u32(self.assignments.len() as u32);
+        for (member, partitions) in &self.assignments {
+            put_str(buf, member);
+            put_partitions(buf, partitions);
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        let assignments: usize = self
+            .assignments
+            .iter()
+            .map(|(member, partitions)| str_size(member) + partitions_size(partitions))
+            .sum();
+        str_size(&self.group) + 4 + str_size(&self.member_id) + 4 + assignments
+    }
+}
+
+impl Display for SyncGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "SyncGroupRequest(group:{} generation:{} member:{} assignments:{})",
+            self.group,
+            self.generation,
+            self.member_id,
+            self.assignments.len()
+        )
+    }
+}
+
+/// Tells the coordinator a member of `group` is still alive. Answered with
+/// [`ErrorCode::RebalanceInProgress`](crate::protocol::ErrorCode) when the
+/// member has to rejoin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heartbeat {
+    group: String,
+    generation: u32,
+    member_id: String,
+}
+
+impl Heartbeat {
+    pub fn new(
+        group: String,
+        generation: u32,
+        member_id: String,
+    ) -> Result<Self, GroupCreationError> {
+        validate_group_id(&group)?;
+        validate_member_id(&member_id)?;
+
+        Ok(Heartbeat {
+            group,
+            generation,
+            member_id,
+        })
+    }
+
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    pub fn member_id(&self) -> &str {
+        &self.member_id
+    }
+
+    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, GroupCreationError> {
+        let group = get_str(&mut bytes).ok_or(GroupCreationError::MalformedBytes)?;
+        if bytes.remaining() < 4 {
+            return Err(GroupCreationError::MalformedBytes);
+        }
+        let generation = bytes.get_u32();
+        let member_id = get_str(&mut bytes).ok_or(GroupCreationError::MalformedBytes)?;
+        if bytes.has_remaining() {
+            return Err(GroupCreationError::MalformedBytes);
+        }
+
+        Self::new(group, generation, member_id)
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        put_str(buf, &self.group);
+        buf.put_u32(self.generation);
+        put_str(buf, &self.member_id);
+    }
+
+    pub fn size(&self) -> usize {
+        str_size(&self.group) + 4 + str_size(&self.member_id)
+    }
+}
+
+impl Display for Heartbeat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "HeartbeatRequest(group:{} generation:{} member:{})",
+            self.group, self.generation, self.member_id
+        )
+    }
+}
+
+/// Takes a member out of `group`, so the others rebalance its partitions
+/// without waiting for its session to expire.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaveGroup {
+    group: String,
+    member_id: String,
+}
+
+impl LeaveGroup {
+    pub fn new(group: String, member_id: String) -> Result<Self, GroupCreationError> {
+        validate_group_id(&group)?;
+        validate_member_id(&member_id)?;
+
+        Ok(LeaveGroup { group, member_id })
+    }
+
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    pub fn member_id(&self) -> &str {
+        &self.member_id
+    }
+
+    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, GroupCreationError> {
+        let group = get_str(&mut bytes).ok_or(GroupCreationError::MalformedBytes)?;
+        let member_id = get_str(&mut bytes).ok_or(GroupCreationError::MalformedBytes)?;
+        if bytes.has_remaining() {
+            return Err(GroupCreationError::MalformedBytes);
+        }
+
+        Self::new(group, member_id)
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        put_str(buf, &self.group);
+        put_str(buf, &self.member_id);
+    }
+
+    pub fn size(&self) -> usize {
+        str_size(&self.group) + str_size(&self.member_id)
+    }
+}
+
+impl Display for LeaveGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "LeaveGroupRequest(group:{} member:{})",
+            self.group, self.member_id
+        )
+    }
+}
+
+fn validate_topic(topic: &str) -> Result<(), GroupCreationError> {
+    if topic.len() > u16::MAX as usize {
+        return Err(GroupCreationError::TopicTooLong);
+    }
+    Ok(validate_topic_name(topic)?)
+}
+
+/// A u32 count, then the topic and partition of each.
+fn get_partitions(
+    bytes: &mut Bytes,
+    limits: &DecodeLimits,
+) -> Result<Vec<TopicPartition>, GroupCreationError> {
+    if bytes.remaining() < 4 {
+        return Err(GroupCreationError::MalformedBytes);
+    }
+    let count = bytes.get_u32();
+    let mut partitions = Vec::new();
+    for _ in 0..count {
+        let topic = get_topic(bytes, limits)?;
+        if bytes.remaining() < 4 {
+            return Err(GroupCreationError::MalformedBytes);
+        }
+        partitions.push(TopicPartition::new(topic, bytes.get_u32()));
+    }
+    Ok(partitions)
+}
+
+fn put_partitions(buf: &mut impl BufMut, partitions: &[TopicPartition]) {
+    buf.put_u32(partitions.len() as u32);
+    for partition in partitions {
+        put_str(buf, &partition.topic);
+        buf.put_u32(partition.partition);
+    }
+}
+
+fn partitions_size(partitions: &[TopicPartition]) -> usize {
+    let partitions: usize = partitions
+        .iter()
+        .map(|partition| str_size(&partition.topic) + 4)
+        .sum();
+    4 + partitions
+}
+
+fn get_topic(bytes: &mut Bytes, limits: &DecodeLimits) -> Result<String, GroupCreationError> {
+    if bytes.remaining() < 2 {
+        return Err(GroupCreationError::MalformedBytes);
+    }
+    limits.check_topic_len(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)?;
+    get_str(bytes).ok_or(GroupCreationError::MalformedBytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let commit = OffsetCommit::new(
+            "billing".to_string(),
+            vec![
+                (TopicPartition::new("events", 0), 12),
+                (TopicPartition::new("events", 1), 0),
+            ],
+        )
+        .unwrap();
+        let bytes = commit.to_bytes();
+        assert_eq!(bytes.len(), commit.size());
+        assert_eq!(OffsetCommit::from_bytes(bytes.clone()).unwrap(), commit);
+        assert_eq!(
+            OffsetCommit::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(GroupCreationError::MalformedBytes)
+        );
+
+        assert_eq!(
+            ListGroups::from_bytes(ListGroups::new().to_bytes()),
+            Ok(ListGroups)
+        );
+
+        let describe = DescribeGroups::new(vec!["billing".to_string()]).unwrap();
+        let bytes = describe.to_bytes();
+        assert_eq!(bytes.len(), describe.size());
+        assert_eq!(DescribeGroups::from_bytes(bytes).unwrap(), describe);
+
+        let join = JoinGroup::new(
+            "billing".to_string(),
+            String::new(),
+            10_000,
+            30_000,
+            vec!["events".to_string()],
+            vec!["cooperative-sticky".to_string(), "range".to_string()],
+            vec![TopicPartition::new("events", 1)],
+        )
+        .unwrap();
+        let bytes = join.to_bytes();
+        assert_eq!(bytes.len(), join.size());
+        assert_eq!(JoinGroup::from_bytes(bytes.clone()).unwrap(), join);
+        assert_eq!(
+            JoinGroup::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(GroupCreationError::MalformedBytes)
+        );
+
+        let sync = SyncGroup::new(
+            "billing".to_string(),
+            3,
+            "app-1".to_string(),
+            vec![
+                ("app-1".to_string(), vec![TopicPartition::new("events", 0)]),
+                ("app-2".to_string(), vec![]),
+            ],
+        )
+        .unwrap();
+        let bytes = sync.to_bytes();
+        assert_eq!(bytes.len(), sync.size());
+        assert_eq!(SyncGroup::from_bytes(bytes.clone()).unwrap(), sync);
+        assert_eq!(
+            SyncGroup::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(GroupCreationError::MalformedBytes)
+        );
+
+        let heartbeat = Heartbeat::new("billing".to_string(), 3, "app-1".to_string()).unwrap();
+        let bytes = heartbeat.to_bytes();
+        assert_eq!(bytes.len(), heartbeat.size());
+        assert_eq!(Heartbeat::from_bytes(bytes).unwrap(), heartbeat);
+
+        let leave = LeaveGroup::new("billing".to_string(), "app-1".to_string()).unwrap();
+        let bytes = leave.to_bytes();
+        assert_eq!(bytes.len(), leave.size());
+        assert_eq!(LeaveGroup::from_bytes(bytes).unwrap(), leave);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(
+            OffsetCommit::new(String::new(), vec![]),
+            Err(GroupCreationError::EmptyGroupId)
+        );
+        assert_eq!(
+            DescribeGroups::new(vec![String::new()]),
+            Err(GroupCreationError::EmptyGroupId)
+        );
+        assert_eq!(
+            Heartbeat::new(String::new(), 0, "app-1".to_string()),
+            Err(GroupCreationError::EmptyGroupId)
+        );
+        assert_eq!(
+            JoinGroup::new(
+                "billing".to_string(),
+                String::new(),
+                0,
+                0,
+                vec!["bad topic".to_string()],
+                vec![],
+                vec![]
+            ),
+            Err(GroupCreationError::InvalidTopicName(
+                InvalidTopicName::IllegalChar(' ')
+            ))
+        );
+        assert_eq!(
+            OffsetCommit::new(
+                "billing".to_string(),
+                vec![(TopicPartition::new("bad topic", 0), 0)]
+            ),
+            Err(GroupCreationError::InvalidTopicName(
+                InvalidTopicName::IllegalChar(' ')
+            ))
+        );
+    }
+}
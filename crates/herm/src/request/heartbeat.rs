@@ -0,0 +1,200 @@
+use std::fmt::Display;
+use thiserror::Error;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::protocol::{
+    get_str, put_str, str_size, validate_topic_name, DecodeLimits, InvalidTopicName, LimitExceeded,
+    TopicPartition,
+};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum HeartbeatCreationError {
+    #[error("Topic name is too long")]
+    TopicTooLong,
+    #[error("Malformed bytes")]
+    MalformedBytes,
+    #[error(transparent)]
+    InvalidTopicName(#[from] InvalidTopicName),
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitExceeded),
+}
+
+/// Sent by every broker to its peers, to tell them it is alive, what it
+/// runs, and which partitions it leads, at which leader epoch. Peers answer
+/// [`Metadata`](super::Metadata) and
+/// [`DescribeCluster`](super::DescribeCluster) requests from the heartbeats
+/// they got.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokerHeartbeat {
+    broker_id: u32,
+    version: String,
+    rack: Option<String>,
+    leaders: Vec<(TopicPartition, u32)>,
+}
+
+impl BrokerHeartbeat {
+    /// A heartbeat from a broker running this [`VERSION`](crate::VERSION),
+    /// in no rack.
+    pub fn new(
+        broker_id: u32,
+        leaders: Vec<(TopicPartition, u32)>,
+    ) -> Result<Self, HeartbeatCreationError> {
+        for (partition, _) in &leaders {
+            if partition.topic.len() > u16::MAX as usize {
+                return Err(HeartbeatCreationError::TopicTooLong);
+            }
+            validate_topic_name(&partition.topic)?;
+        }
+
+        Ok(BrokerHeartbeat {
+            broker_id,
+            version: crate::VERSION.to_string(),
+            rack: None,
+            leaders,
+        })
+    }
+
+    /// Sent as an empty string when unset.
+    pub fn with_rack(mut self, rack: Option<String>) -> Self {
+        self.rack = rack.filter(|rack| !rack.is_empty());
+        self
+    }
+
+    pub fn broker_id(&self) -> u32 {
+        self.broker_id
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn rack(&self) -> Option<&str> {
+        self.rack.as_deref()
+    }
+
+    /// Partitions the broker leads, with their leader epochs.
+    pub fn leaders(&self) -> &[(TopicPartition, u32)] {
+        &self.leaders
+    }
+
+    pub fn into_leaders(self) -> Vec<(TopicPartition, u32)> {
+        self.leaders
+    }
+
+    /// Decodes a request within the [`DecodeLimits`] defaults.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, HeartbeatCreationError> {
+        Self::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    pub fn from_bytes_with_limits(
+        mut bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<Self, HeartbeatCreationError> {
+        if bytes.remaining() < 4 {
+            return Err(HeartbeatCreationError::MalformedBytes);
+        }
+        let broker_id = bytes.get_u32();
+        let version = get_str(&mut bytes).ok_or(HeartbeatCreationError::MalformedBytes)?;
+        let rack = get_str(&mut bytes).ok_or(HeartbeatCreationError::MalformedBytes)?;
+        if bytes.remaining() < 4 {
+            return Err(HeartbeatCreationError::MalformedBytes);
+        }
+        let count = bytes.get_u32();
+
+        // Not reserved up front, the count is the client's word
+        let mut leaders = Vec::new();
+        for _ in 0..count {
+            if bytes.remaining() < 2 {
+                return Err(HeartbeatCreationError::MalformedBytes);
+            }
+            limits.check_topic_len(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)?;
+            let topic = get_str(&mut bytes).ok_or(HeartbeatCreationError::MalformedBytes)?;
+            if bytes.remaining() < 4 + 4 {
+                return Err(HeartbeatCreationError::MalformedBytes);
+            }
+            let partition = bytes.get_u32();
+            let leader_epoch = bytes.get_u32();
+            leaders.push((TopicPartition::new(topic, partition), leader_epoch));
+        }
+        if bytes.has_remaining() {
+            return Err(HeartbeatCreationError::MalformedBytes);
+        }
+
+        let mut heartbeat = Self::new(broker_id, leaders)?.with_rack(Some(rack));
+        heartbeat.version = version;
+        Ok(heartbeat)
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_u32(self.broker_id);
+        put_str(buf, &self.version);
+        put_str(buf, self.rack.as_deref().unwrap_or_default());
+        buf.put_u32(self.leaders.len() as u32);
+        for (partition, leader_epoch) in &self.leaders {
+            put_str(buf, &partition.topic);
+            buf.put_u32(partition.partition);
+            buf.put_u32(*leader_epoch);
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        let leaders: usize = self
+            .leaders
+            .iter()
+            .map(|(partition, _)| str_size(&partition.topic) + 4 + 4)
+            .sum();
+        4 + str_size(&self.version)
+            + str_size(self.rack.as_deref().unwrap_or_default())
+            + 4
+            + leaders
+    }
+}
+
+impl Display for BrokerHeartbeat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "BrokerHeartbeatRequest(broker:{} leaders:{})",
+            self.broker_id,
+            self.leaders.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let heartbeat = BrokerHeartbeat::new(
+            2,
+            vec![
+                (TopicPartition::new("events", 0), 3),
+                (TopicPartition::new("logs", 1), 0),
+            ],
+        )
+        .unwrap();
+        let bytes = heartbeat.to_bytes();
+        assert_eq!(bytes.len(), heartbeat.size());
+        assert_eq!(BrokerHeartbeat::from_bytes(bytes).unwrap(), heartbeat);
+
+        let heartbeat = heartbeat.with_rack(Some("eu-west-1a".to_string()));
+        let decoded = BrokerHeartbeat::from_bytes(heartbeat.to_bytes()).unwrap();
+        assert_eq!(decoded.rack(), Some("eu-west-1a"));
+        assert_eq!(decoded.version(), crate::VERSION);
+
+        let bytes = heartbeat.to_bytes();
+        assert_eq!(
+            BrokerHeartbeat::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(HeartbeatCreationError::MalformedBytes)
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use std::fmt::Display;
+use thiserror::Error;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::protocol::{
+    get_str, put_str, str_size, validate_topic_name, DecodeLimits, InvalidTopicName, LimitExceeded,
+};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum LeaderAndIsrCreationError {
+    #[error("Topic name is too long")]
+    TopicTooLong,
+    #[error("Leader address is too long")]
+    LeaderTooLong,
+    #[error("Malformed bytes")]
+    MalformedBytes,
+    #[error(transparent)]
+    InvalidTopicName(#[from] InvalidTopicName),
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitExceeded),
+}
+
+/// Tells a broker to lead a partition, or to follow the broker at `leader`,
+/// from `leader_epoch` on. Brokers send it to each other while moving a
+/// partition, a follower creates the partition if it doesn't have it yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaderAndIsr {
+    topic: String,
+    partition: u32,
+    leader_epoch: u32,
+    /// Address of the leader to follow, `None` to lead. Empty on the wire.
+    leader: Option<String>,
+}
+
+impl LeaderAndIsr {
+    /// Makes the receiving broker the leader at `leader_epoch`.
+    pub fn lead(
+        topic: String,
+        partition: u32,
+        leader_epoch: u32,
+    ) -> Result<Self, LeaderAndIsrCreationError> {
+        Self::new(topic, partition, leader_epoch, None)
+    }
+
+    /// Makes the receiving broker copy the partition from `leader`.
+    pub fn follow(
+        topic: String,
+        partition: u32,
+        leader_epoch: u32,
+        leader: String,
+    ) -> Result<Self, LeaderAndIsrCreationError> {
+        if leader.len() > u16::MAX as usize {
+            return Err(LeaderAndIsrCreationError::LeaderTooLong);
+        }
+        Self::new(topic, partition, leader_epoch, Some(leader))
+    }
+
+    fn new(
+        topic: String,
+        partition: u32,
+        leader_epoch: u32,
+        leader: Option<String>,
+    ) -> Result<Self, LeaderAndIsrCreationError> {
+        if topic.len() > u16::MAX as usize {
+            return Err(LeaderAndIsrCreationError::TopicTooLong);
+        }
+        validate_topic_name(&topic)?;
+
+        Ok(LeaderAndIsr {
+            topic,
+            partition,
+            leader_epoch,
+            leader: leader.filter(|leader| !leader.is_empty()),
+        })
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub fn partition(&self) -> u32 {
+        self.partition
+    }
+
+    pub fn leader_epoch(&self) -> u32 {
+        self.leader_epoch
+    }
+
+    /// The broker to follow, `None` when asked to lead.
+    pub fn leader(&self) -> Option<&str> {
+        self.leader.as_deref()
+    }
+
+    /// Decodes a request within the [`DecodeLimits`] defaults.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, LeaderAndIsrCreationError> {
+        Self::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    pub fn from_bytes_with_limits(
+        mut bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<Self, LeaderAndIsrCreationError> {
+        if bytes.remaining() < 2 {
+            return Err(LeaderAndIsrCreationError::MalformedBytes);
+        }
+        limits.check_topic_len(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)?;
+        let topic = get_str(&mut bytes).ok_or(LeaderAndIsrCreationError::MalformedBytes)?;
+        validate_topic_name(&topic)?;
+
+        if bytes.remaining() < 4 + 4 {
+            return Err(LeaderAndIsrCreationError::MalformedBytes);
+        }
+        let partition = bytes.get_u32();
+        let leader_epoch = bytes.get_u32();
+        let leader = get_str(&mut bytes).ok_or(LeaderAndIsrCreationError::MalformedBytes)?;
+        if bytes.has_remaining() {
+            return Err(LeaderAndIsrCreationError::MalformedBytes);
+        }
+
+        Self::new(topic, partition, leader_epoch, Some(leader))
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        put_str(buf, &self.topic);
+        buf.put_u32(self.partition);
+        buf.put_u32(self.leader_epoch);
+        put_str(buf, self.leader().unwrap_or(""));
+    }
+
+    pub fn size(&self) -> usize {
+        str_size(&self.topic) + 4 + 4 + str_size(self.leader().unwrap_or(""))
+    }
+}
+
+impl Display for LeaderAndIsr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "LeaderAndIsrRequest(topic:{}, part:{} epoch:{} leader:{})",
+            self.topic,
+            self.partition,
+            self.leader_epoch,
+            self.leader().unwrap_or("self")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let lead = LeaderAndIsr::lead("test".to_string(), 2, 5).unwrap();
+        assert_eq!(lead.leader(), None);
+        let bytes = lead.to_bytes();
+        assert_eq!(bytes.len(), lead.size());
+        assert_eq!(LeaderAndIsr::from_bytes(bytes).unwrap(), lead);
+
+        let follow =
+            LeaderAndIsr::follow("test".to_string(), 2, 5, "broker-1:9092".to_string()).unwrap();
+        assert_eq!(follow.leader(), Some("broker-1:9092"));
+        assert_eq!(LeaderAndIsr::from_bytes(follow.to_bytes()).unwrap(), follow);
+    }
+
+    #[test]
+    fn test_malformed_bytes() {
+        assert_eq!(
+            LeaderAndIsr::from_bytes(Bytes::new()),
+            Err(LeaderAndIsrCreationError::MalformedBytes)
+        );
+
+        let bytes = LeaderAndIsr::lead("test".to_string(), 0, 1)
+            .unwrap()
+            .to_bytes();
+
+        // Leader cut off
+        assert_eq!(
+            LeaderAndIsr::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(LeaderAndIsrCreationError::MalformedBytes)
+        );
+
+        // Trailing bytes
+        let mut trailing = bytes.to_vec();
+        trailing.push(0);
+        assert_eq!(
+            LeaderAndIsr::from_bytes(trailing.into()),
+            Err(LeaderAndIsrCreationError::MalformedBytes)
+        );
+    }
+}
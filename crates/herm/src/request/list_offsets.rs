@@ -0,0 +1,165 @@
+use std::fmt::Display;
+use thiserror::Error;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::protocol::{
+    get_str, put_str, str_size, validate_topic_name, DecodeLimits, InvalidTopicName, LimitExceeded,
+};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ListOffsetsCreationError {
+    #[error("Topic name is too long")]
+    TopicTooLong,
+    #[error("Malformed bytes")]
+    MalformedBytes,
+    #[error(transparent)]
+    InvalidTopicName(#[from] InvalidTopicName),
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitExceeded),
+}
+
+/// Timestamp asking for the first offset of a partition.
+pub const EARLIEST_TIMESTAMP: u64 = u64::MAX - 1;
+
+/// Timestamp asking for the offset past the last record consumers may read,
+/// the high watermark.
+pub const LATEST_TIMESTAMP: u64 = u64::MAX;
+
+/// Looks up the offset of the first record of a partition with a timestamp
+/// at or after `timestamp`, or the partition's first or latest offset, see
+/// [`EARLIEST_TIMESTAMP`] and [`LATEST_TIMESTAMP`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListOffsets {
+    topic: String,
+    partition: u32,
+    timestamp: u64,
+}
+
+impl ListOffsets {
+    pub fn new(
+        topic: String,
+        partition: u32,
+        timestamp: u64,
+    ) -> Result<Self, ListOffsetsCreationError> {
+        if topic.len() > u16::MAX as usize {
+            return Err(ListOffsetsCreationError::TopicTooLong);
+        }
+        validate_topic_name(&topic)?;
+
+        Ok(ListOffsets {
+            topic,
+            partition,
+            timestamp,
+        })
+    }
+
+    pub fn earliest(topic: String, partition: u32) -> Result<Self, ListOffsetsCreationError> {
+        Self::new(topic, partition, EARLIEST_TIMESTAMP)
+    }
+
+    pub fn latest(topic: String, partition: u32) -> Result<Self, ListOffsetsCreationError> {
+        Self::new(topic, partition, LATEST_TIMESTAMP)
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub fn partition(&self) -> u32 {
+        self.partition
+    }
+
+    /// Milliseconds since the epoch, or one of the sentinels.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Decodes a request within the [`DecodeLimits`] defaults.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, ListOffsetsCreationError> {
+        Self::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    pub fn from_bytes_with_limits(
+        mut bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<Self, ListOffsetsCreationError> {
+        if bytes.remaining() < 2 {
+            return Err(ListOffsetsCreationError::MalformedBytes);
+        }
+        limits.check_topic_len(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)?;
+        let topic = get_str(&mut bytes).ok_or(ListOffsetsCreationError::MalformedBytes)?;
+        if bytes.remaining() != 4 + 8 {
+            return Err(ListOffsetsCreationError::MalformedBytes);
+        }
+        let partition = bytes.get_u32();
+        let timestamp = bytes.get_u64();
+
+        Self::new(topic, partition, timestamp)
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        put_str(buf, &self.topic);
+        buf.put_u32(self.partition);
+        buf.put_u64(self.timestamp);
+    }
+
+    pub fn size(&self) -> usize {
+        str_size(&self.topic) + 4 + 8
+    }
+}
+
+impl Display for ListOffsets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let timestamp = match self.timestamp {
+            EARLIEST_TIMESTAMP => "earliest".to_string(),
+            LATEST_TIMESTAMP => "latest".to_string(),
+            timestamp => timestamp.to_string(),
+        };
+        write!(
+            f,
+            "ListOffsetsRequest(topic:{}, part:{} timestamp:{})",
+            self.topic, self.partition, timestamp
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        for request in [
+            ListOffsets::new("test".to_string(), 1, 1_700_000_000_000).unwrap(),
+            ListOffsets::earliest("test".to_string(), 0).unwrap(),
+            ListOffsets::latest("test".to_string(), 0).unwrap(),
+        ] {
+            let bytes = request.to_bytes();
+            assert_eq!(bytes.len(), request.size());
+            assert_eq!(ListOffsets::from_bytes(bytes).unwrap(), request);
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(
+            ListOffsets::new("bad topic".to_string(), 0, 0).unwrap_err(),
+            ListOffsetsCreationError::InvalidTopicName(InvalidTopicName::IllegalChar(' '))
+        );
+
+        let bytes = ListOffsets::latest("test".to_string(), 0)
+            .unwrap()
+            .to_bytes();
+        assert_eq!(
+            ListOffsets::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(ListOffsetsCreationError::MalformedBytes)
+        );
+    }
+}
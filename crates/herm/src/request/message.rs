@@ -0,0 +1,389 @@
+use thiserror::Error;
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use super::{
+    Acks, BrokerHeartbeat, CreateTopic, DeleteTopic, DescribeCluster, DescribeCreationError,
+    DescribeGroups, DescribeLogDirs, EstimateCleanup, Fetch, FetchCreationError,
+    GroupCreationError, Heartbeat, HeartbeatCreationError, JoinGroup, LeaderAndIsr,
+    LeaderAndIsrCreationError, LeaveGroup, ListGroups, ListOffsets, ListOffsetsCreationError,
+    Metadata, MetadataCreationError, OffsetCommit, Produce, ProduceCreationError,
+    ReassignCreationError, ReassignPartition, SyncGroup, TopicAdminCreationError,
+};
+use crate::protocol::{ApiKey, DecodeLimits, LimitExceeded};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum RequestError {
+    #[error(transparent)]
+    Produce(#[from] ProduceCreationError),
+    #[error(transparent)]
+    Fetch(#[from] FetchCreationError),
+    #[error(transparent)]
+    ListOffsets(#[from] ListOffsetsCreationError),
+    #[error(transparent)]
+    Metadata(#[from] MetadataCreationError),
+    #[error(transparent)]
+    LeaderAndIsr(#[from] LeaderAndIsrCreationError),
+    #[error(transparent)]
+    ReassignPartition(#[from] ReassignCreationError),
+    #[error(transparent)]
+    BrokerHeartbeat(#[from] HeartbeatCreationError),
+    #[error(transparent)]
+    Describe(#[from] DescribeCreationError),
+    #[error(transparent)]
+    Group(#[from] GroupCreationError),
+    #[error(transparent)]
+    TopicAdmin(#[from] TopicAdminCreationError),
+}
+
+impl RequestError {
+    /// The decode limit the request went over, if that's why it failed.
+    pub fn limit_exceeded(&self) -> Option<LimitExceeded> {
+        match self {
+            RequestError::Produce(ProduceCreationError::LimitExceeded(limit))
+            | RequestError::Fetch(FetchCreationError::LimitExceeded(limit))
+            | RequestError::ListOffsets(ListOffsetsCreationError::LimitExceeded(limit))
+            | RequestError::Metadata(MetadataCreationError::LimitExceeded(limit))
+            | RequestError::LeaderAndIsr(LeaderAndIsrCreationError::LimitExceeded(limit))
+            | RequestError::ReassignPartition(ReassignCreationError::LimitExceeded(limit))
+            | RequestError::BrokerHeartbeat(HeartbeatCreationError::LimitExceeded(limit))
+            | RequestError::Describe(DescribeCreationError::LimitExceeded(limit))
+            | RequestError::Group(GroupCreationError::LimitExceeded(limit))
+            | RequestError::TopicAdmin(TopicAdminCreationError::LimitExceeded(limit)) => {
+                Some(*limit)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Any request body, tagged by its api key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    Produce(Produce),
+    Fetch(Fetch),
+    ListOffsets(ListOffsets),
+    Metadata(Metadata),
+    LeaderAndIsr(LeaderAndIsr),
+    ReassignPartition(ReassignPartition),
+    BrokerHeartbeat(BrokerHeartbeat),
+    DescribeCluster(DescribeCluster),
+    DescribeLogDirs(DescribeLogDirs),
+    OffsetCommit(OffsetCommit),
+    ListGroups(ListGroups),
+    DescribeGroups(DescribeGroups),
+    JoinGroup(JoinGroup),
+    SyncGroup(SyncGroup),
+    Heartbeat(Heartbeat),
+    LeaveGroup(LeaveGroup),
+    CreateTopic(CreateTopic),
+    DeleteTopic(DeleteTopic),
+    EstimateCleanup(EstimateCleanup),
+}
+
+impl Request {
+    pub fn api_key(&self) -> ApiKey {
+        match self {
+            Request::Produce(_) => ApiKey::Produce,
+            Request::Fetch(_) => ApiKey::Fetch,
+            Request::ListOffsets(_) => ApiKey::ListOffsets,
+            Request::Metadata(_) => ApiKey::Metadata,
+            Request::LeaderAndIsr(_) => ApiKey::LeaderAndIsr,
+            Request::ReassignPartition(_) => ApiKey::ReassignPartition,
+            Request::BrokerHeartbeat(_) => ApiKey::BrokerHeartbeat,
+            Request::DescribeCluster(_) => ApiKey::DescribeCluster,
+            Request::DescribeLogDirs(_) => ApiKey::DescribeLogDirs,
+            Request::OffsetCommit(_) => ApiKey::OffsetCommit,
+            Request::ListGroups(_) => ApiKey::ListGroups,
+            Request::DescribeGroups(_) => ApiKey::DescribeGroups,
+            Request::JoinGroup(_) => ApiKey::JoinGroup,
+            Request::SyncGroup(_) => ApiKey::SyncGroup,
+            Request::Heartbeat(_) => ApiKey::Heartbeat,
+            Request::LeaveGroup(_) => ApiKey::LeaveGroup,
+            Request::CreateTopic(_) => ApiKey::CreateTopic,
+            Request::DeleteTopic(_) => ApiKey::DeleteTopic,
+            Request::EstimateCleanup(_) => ApiKey::EstimateCleanup,
+        }
+    }
+
+    /// Topic of the requests about a single partition or topic, `None` for
+    /// the others.
+    pub fn topic(&self) -> Option<&str> {
+        match self {
+            Request::Produce(produce) => Some(produce.topic()),
+            Request::Fetch(fetch) => Some(fetch.topic()),
+            Request::ListOffsets(request) => Some(request.topic()),
+            Request::LeaderAndIsr(request) => Some(request.topic()),
+            Request::ReassignPartition(request) => Some(request.topic()),
+            Request::CreateTopic(request) => Some(request.topic()),
+            Request::DeleteTopic(request) => Some(request.topic()),
+            Request::EstimateCleanup(request) => Some(request.topic()),
+            Request::Metadata(_)
+            | Request::BrokerHeartbeat(_)
+            | Request::DescribeCluster(_)
+            | Request::DescribeLogDirs(_)
+            | Request::OffsetCommit(_)
+            | Request::ListGroups(_)
+            | Request::DescribeGroups(_)
+            | Request::JoinGroup(_)
+            | Request::SyncGroup(_)
+            | Request::Heartbeat(_)
+            | Request::LeaveGroup(_) => None,
+        }
+    }
+
+    pub fn partition(&self) -> Option<u32> {
+        match self {
+            Request::Produce(produce) => Some(produce.partition()),
+            Request::Fetch(fetch) => Some(fetch.partition()),
+            Request::ListOffsets(request) => Some(request.partition()),
+            Request::LeaderAndIsr(request) => Some(request.partition()),
+            Request::ReassignPartition(request) => Some(request.partition()),
+            Request::Metadata(_)
+            | Request::CreateTopic(_)
+            | Request::DeleteTopic(_)
+            | Request::EstimateCleanup(_)
+            | Request::BrokerHeartbeat(_)
+            | Request::DescribeCluster(_)
+            | Request::DescribeLogDirs(_)
+            | Request::OffsetCommit(_)
+            | Request::ListGroups(_)
+            | Request::DescribeGroups(_)
+            | Request::JoinGroup(_)
+            | Request::SyncGroup(_)
+            | Request::Heartbeat(_)
+            | Request::LeaveGroup(_) => None,
+        }
+    }
+
+    /// False for requests the client doesn't wait on, which get no response.
+    pub fn expects_response(&self) -> bool {
+        !matches!(self, Request::Produce(produce) if produce.acks() == Acks::None)
+    }
+
+    /// Decodes a body sent under `api_key`, as read from its header, within
+    /// the [`DecodeLimits`] defaults.
+    pub fn decode(api_key: ApiKey, bytes: Bytes) -> Result<Self, RequestError> {
+        Self::decode_with_limits(api_key, bytes, &DecodeLimits::default())
+    }
+
+    pub fn decode_with_limits(
+        api_key: ApiKey,
+        bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<Self, RequestError> {
+        Ok(match api_key {
+            ApiKey::Produce => Request::Produce(Produce::from_bytes_with_limits(bytes, limits)?),
+            ApiKey::Fetch => Request::Fetch(Fetch::from_bytes_with_limits(bytes, limits)?),
+            ApiKey::ListOffsets => {
+                Request::ListOffsets(ListOffsets::from_bytes_with_limits(bytes, limits)?)
+            }
+            ApiKey::Metadata => Request::Metadata(Metadata::from_bytes_with_limits(bytes, limits)?),
+            ApiKey::LeaderAndIsr => {
+                Request::LeaderAndIsr(LeaderAndIsr::from_bytes_with_limits(bytes, limits)?)
+            }
+            ApiKey::ReassignPartition => Request::ReassignPartition(
+                ReassignPartition::from_bytes_with_limits(bytes, limits)?,
+            ),
+            ApiKey::BrokerHeartbeat => {
+                Request::BrokerHeartbeat(BrokerHeartbeat::from_bytes_with_limits(bytes, limits)?)
+            }
+            ApiKey::DescribeCluster => {
+                Request::DescribeCluster(DescribeCluster::from_bytes(bytes)?)
+            }
+            ApiKey::DescribeLogDirs => {
+                Request::DescribeLogDirs(DescribeLogDirs::from_bytes_with_limits(bytes, limits)?)
+            }
+            ApiKey::OffsetCommit => {
+                Request::OffsetCommit(OffsetCommit::from_bytes_with_limits(bytes, limits)?)
+            }
+            ApiKey::ListGroups => Request::ListGroups(ListGroups::from_bytes(bytes)?),
+            ApiKey::DescribeGroups => Request::DescribeGroups(DescribeGroups::from_bytes(bytes)?),
+            ApiKey::JoinGroup => {
+                Request::JoinGroup(JoinGroup::from_bytes_with_limits(bytes, limits)?)
+            }
+            ApiKey::SyncGroup => {
+                Request::SyncGroup(SyncGroup::from_bytes_with_limits(bytes, limits)?)
+            }
+            ApiKey::Heartbeat => Request::Heartbeat(Heartbeat::from_bytes(bytes)?),
+            ApiKey::LeaveGroup => Request::LeaveGroup(LeaveGroup::from_bytes(bytes)?),
+            ApiKey::CreateTopic => {
+                Request::CreateTopic(CreateTopic::from_bytes_with_limits(bytes, limits)?)
+            }
+            ApiKey::DeleteTopic => {
+                Request::DeleteTopic(DeleteTopic::from_bytes_with_limits(bytes, limits)?)
+            }
+            ApiKey::EstimateCleanup => {
+                Request::EstimateCleanup(EstimateCleanup::from_bytes_with_limits(bytes, limits)?)
+            }
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        match self {
+            Request::Produce(produce) => produce.encode_into(buf),
+            Request::Fetch(fetch) => fetch.encode_into(buf),
+            Request::ListOffsets(request) => request.encode_into(buf),
+            Request::Metadata(request) => request.encode_into(buf),
+            Request::LeaderAndIsr(request) => request.encode_into(buf),
+            Request::ReassignPartition(request) => request.encode_into(buf),
+            Request::BrokerHeartbeat(request) => request.encode_into(buf),
+            Request::DescribeCluster(request) => request.encode_into(buf),
+            Request::DescribeLogDirs(request) => request.encode_into(buf),
+            Request::OffsetCommit(request) => request.encode_into(buf),
+            Request::ListGroups(request) => request.encode_into(buf),
+            Request::DescribeGroups(request) => request.encode_into(buf),
+            Request::JoinGroup(request) => request.encode_into(buf),
+            Request::SyncGroup(request) => request.encode_into(buf),
+            Request::Heartbeat(request) => request.encode_into(buf),
+            Request::LeaveGroup(request) => request.encode_into(buf),
+            Request::CreateTopic(request) => request.encode_into(buf),
+            Request::DeleteTopic(request) => request.encode_into(buf),
+            Request::EstimateCleanup(request) => request.encode_into(buf),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Request::Produce(produce) => produce.size(),
+            Request::Fetch(fetch) => fetch.size(),
+            Request::ListOffsets(request) => request.size(),
+            Request::Metadata(request) => request.size(),
+            Request::LeaderAndIsr(request) => request.size(),
+            Request::ReassignPartition(request) => request.size(),
+            Request::BrokerHeartbeat(request) => request.size(),
+            Request::DescribeCluster(request) => request.size(),
+            Request::DescribeLogDirs(request) => request.size(),
+            Request::OffsetCommit(request) => request.size(),
+            Request::ListGroups(request) => request.size(),
+            Request::DescribeGroups(request) => request.size(),
+            Request::JoinGroup(request) => request.size(),
+            Request::SyncGroup(request) => request.size(),
+            Request::Heartbeat(request) => request.size(),
+            Request::LeaveGroup(request) => request.size(),
+            Request::CreateTopic(request) => request.size(),
+            Request::DeleteTopic(request) => request.size(),
+            Request::EstimateCleanup(request) => request.size(),
+        }
+    }
+}
+
+impl From<Produce> for Request {
+    fn from(produce: Produce) -> Self {
+        Request::Produce(produce)
+    }
+}
+
+impl From<Fetch> for Request {
+    fn from(fetch: Fetch) -> Self {
+        Request::Fetch(fetch)
+    }
+}
+
+impl From<ListOffsets> for Request {
+    fn from(request: ListOffsets) -> Self {
+        Request::ListOffsets(request)
+    }
+}
+
+impl From<Metadata> for Request {
+    fn from(request: Metadata) -> Self {
+        Request::Metadata(request)
+    }
+}
+
+impl From<LeaderAndIsr> for Request {
+    fn from(request: LeaderAndIsr) -> Self {
+        Request::LeaderAndIsr(request)
+    }
+}
+
+impl From<ReassignPartition> for Request {
+    fn from(request: ReassignPartition) -> Self {
+        Request::ReassignPartition(request)
+    }
+}
+
+impl From<BrokerHeartbeat> for Request {
+    fn from(request: BrokerHeartbeat) -> Self {
+        Request::BrokerHeartbeat(request)
+    }
+}
+
+impl From<DescribeCluster> for Request {
+    fn from(request: DescribeCluster) -> Self {
+        Request::DescribeCluster(request)
+    }
+}
+
+impl From<DescribeLogDirs> for Request {
+    fn from(request: DescribeLogDirs) -> Self {
+        Request::DescribeLogDirs(request)
+    }
+}
+
+impl From<OffsetCommit> for Request {
+    fn from(request: OffsetCommit) -> Self {
+        Request::OffsetCommit(request)
+    }
+}
+
+impl From<ListGroups> for Request {
+    fn from(request: ListGroups) -> Self {
+        Request::ListGroups(request)
+    }
+}
+
+impl From<DescribeGroups> for Request {
+    fn from(request: DescribeGroups) -> Self {
+        Request::DescribeGroups(request)
+    }
+}
+
+impl From<JoinGroup> for Request {
+    fn from(request: JoinGroup) -> Self {
+        Request::JoinGroup(request)
+    }
+}
+
+impl From<SyncGroup> for Request {
+    fn from(request: SyncGroup) -> Self {
+        Request::SyncGroup(request)
+    }
+}
+
+impl From<Heartbeat> for Request {
+    fn from(request: Heartbeat) -> Self {
+        Request::Heartbeat(request)
+    }
+}
+
+impl From<LeaveGroup> for Request {
+    fn from(request: LeaveGroup) -> Self {
+        Request::LeaveGroup(request)
+    }
+}
+
+impl From<CreateTopic> for Request {
+    fn from(request: CreateTopic) -> Self {
+        Request::CreateTopic(request)
+    }
+}
+
+impl From<DeleteTopic> for Request {
+    fn from(request: DeleteTopic) -> Self {
+        Request::DeleteTopic(request)
+    }
+}
+
+impl From<EstimateCleanup> for Request {
+    fn from(request: EstimateCleanup) -> Self {
+        Request::EstimateCleanup(request)
+    }
+}
@@ -0,0 +1,149 @@
+use std::fmt::Display;
+use thiserror::Error;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::protocol::{
+    get_str, put_str, str_size, validate_topic_name, DecodeLimits, InvalidTopicName, LimitExceeded,
+};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum MetadataCreationError {
+    #[error("Topic name is too long")]
+    TopicTooLong,
+    #[error("Malformed bytes")]
+    MalformedBytes,
+    #[error(transparent)]
+    InvalidTopicName(#[from] InvalidTopicName),
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitExceeded),
+}
+
+/// Asks for the brokers of the cluster and the leaders of the partitions of
+/// `topics`, or of every topic when empty. Any broker answers it, so clients
+/// can bootstrap from one and learn the rest.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Metadata {
+    topics: Vec<String>,
+}
+
+impl Metadata {
+    /// Asks about every topic.
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    pub fn new(topics: Vec<String>) -> Result<Self, MetadataCreationError> {
+        for topic in &topics {
+            if topic.len() > u16::MAX as usize {
+                return Err(MetadataCreationError::TopicTooLong);
+            }
+            validate_topic_name(topic)?;
+        }
+
+        Ok(Metadata { topics })
+    }
+
+    /// Topics asked about, empty for all of them.
+    pub fn topics(&self) -> &[String] {
+        &self.topics
+    }
+
+    /// Decodes a request within the [`DecodeLimits`] defaults.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, MetadataCreationError> {
+        Self::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    pub fn from_bytes_with_limits(
+        mut bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<Self, MetadataCreationError> {
+        if bytes.remaining() < 4 {
+            return Err(MetadataCreationError::MalformedBytes);
+        }
+        let count = bytes.get_u32();
+
+        // Not reserved up front, the count is the client's word
+        let mut topics = Vec::new();
+        for _ in 0..count {
+            if bytes.remaining() < 2 {
+                return Err(MetadataCreationError::MalformedBytes);
+            }
+            limits.check_topic_len(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)?;
+            topics.push(get_str(&mut bytes).ok_or(MetadataCreationError::MalformedBytes)?);
+        }
+        if bytes.has_remaining() {
+            return Err(MetadataCreationError::MalformedBytes);
+        }
+
+        Self::new(topics)
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_u32(self.topics.len() as u32);
+        for topic in &self.topics {
+            put_str(buf, topic);
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        4 + self
+            .topics
+            .iter()
+            .map(|topic| str_size(topic))
+            .sum::<usize>()
+    }
+}
+
+impl Display for Metadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MetadataRequest(topics:[{}])", self.topics.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let all = Metadata::all();
+        assert_eq!(all.to_bytes().len(), all.size());
+        assert_eq!(Metadata::from_bytes(all.to_bytes()).unwrap(), all);
+
+        let metadata = Metadata::new(vec!["events".to_string(), "logs".to_string()]).unwrap();
+        let bytes = metadata.to_bytes();
+        assert_eq!(bytes.len(), metadata.size());
+        assert_eq!(Metadata::from_bytes(bytes).unwrap(), metadata);
+    }
+
+    #[test]
+    fn test_malformed_bytes() {
+        assert_eq!(
+            Metadata::new(vec!["bad topic".to_string()]),
+            Err(MetadataCreationError::InvalidTopicName(
+                InvalidTopicName::IllegalChar(' ')
+            ))
+        );
+
+        // Claims more topics than it carries
+        assert_eq!(
+            Metadata::from_bytes(Bytes::from_static(&[0xff, 0xff, 0xff, 0xff])),
+            Err(MetadataCreationError::MalformedBytes)
+        );
+
+        let bytes = Metadata::new(vec!["events".to_string()])
+            .unwrap()
+            .to_bytes();
+        assert_eq!(
+            Metadata::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(MetadataCreationError::MalformedBytes)
+        );
+    }
+}
@@ -1,2 +1,27 @@
+mod describe;
 mod fetch;
-pub use fetch::Fetch;
\ No newline at end of file
+mod group;
+mod heartbeat;
+mod leader_and_isr;
+mod list_offsets;
+mod message;
+mod metadata;
+mod produce;
+mod reassign;
+mod topic_admin;
+pub use describe::{DescribeCluster, DescribeCreationError, DescribeLogDirs};
+pub use fetch::{Fetch, FetchCreationError};
+pub use group::{
+    DescribeGroups, GroupCreationError, Heartbeat, JoinGroup, LeaveGroup, ListGroups, OffsetCommit,
+    SyncGroup,
+};
+pub use heartbeat::{BrokerHeartbeat, HeartbeatCreationError};
+pub use leader_and_isr::{LeaderAndIsr, LeaderAndIsrCreationError};
+pub use list_offsets::{
+    ListOffsets, ListOffsetsCreationError, EARLIEST_TIMESTAMP, LATEST_TIMESTAMP,
+};
+pub use message::{Request, RequestError};
+pub use metadata::{Metadata, MetadataCreationError};
+pub use produce::{Acks, Produce, ProduceCreationError, DEFAULT_PRODUCE_TIMEOUT_MS};
+pub use reassign::{ReassignCreationError, ReassignPartition, DEFAULT_REASSIGN_TIMEOUT_MS};
+pub use topic_admin::{CreateTopic, DeleteTopic, EstimateCleanup, TopicAdminCreationError};
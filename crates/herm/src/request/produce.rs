@@ -0,0 +1,356 @@
+use std::fmt::Display;
+use thiserror::Error;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::protocol::{
+    get_str, put_str, str_size, validate_topic_name, DecodeLimits, InvalidTopicName, LimitExceeded,
+    NO_LEADER_EPOCH,
+};
+use crate::record::{RecordBatch, RecordBatchError};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ProduceCreationError {
+    #[error("Topic name is too long")]
+    TopicTooLong,
+    #[error("Malformed bytes")]
+    MalformedBytes,
+    #[error(transparent)]
+    InvalidTopicName(#[from] InvalidTopicName),
+    #[error(transparent)]
+    Batch(#[from] RecordBatchError),
+    #[error("Invalid acks {0}")]
+    InvalidAcks(i16),
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitExceeded),
+}
+
+/// How far along the write has to be before a produce is answered. Numbered
+/// after their Kafka counterparts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(i16)]
+pub enum Acks {
+    /// Never answered, so the client can't tell whether the write landed.
+    None = 0,
+    /// Answered once appended to the leader's log.
+    #[default]
+    Leader = 1,
+    /// Answered once flushed and on every in-sync replica, or failed with
+    /// `RequestTimedOut` if that takes longer than the produce timeout.
+    All = -1,
+}
+
+impl Acks {
+    pub fn code(&self) -> i16 {
+        *self as i16
+    }
+}
+
+impl TryFrom<i16> for Acks {
+    type Error = ProduceCreationError;
+
+    fn try_from(value: i16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Acks::None),
+            1 => Ok(Acks::Leader),
+            -1 => Ok(Acks::All),
+            _ => Err(ProduceCreationError::InvalidAcks(value)),
+        }
+    }
+}
+
+/// How long an [`Acks::All`] produce waits to be flushed and replicated
+/// unless told otherwise.
+pub const DEFAULT_PRODUCE_TIMEOUT_MS: u32 = 30_000;
+
+/// Appends a batch of records to one partition. Offsets in the batch are
+/// ignored, the broker assigns them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Produce {
+    topic: String,
+    partition: u32,
+    acks: Acks,
+    timeout_ms: u32,
+    leader_epoch: Option<u32>,
+    batch: RecordBatch,
+}
+
+impl Produce {
+    pub fn new(
+        topic: String,
+        partition: u32,
+        batch: RecordBatch,
+    ) -> Result<Self, ProduceCreationError> {
+        if topic.len() > u16::MAX as usize {
+            return Err(ProduceCreationError::TopicTooLong);
+        }
+
+        validate_topic_name(&topic)?;
+
+        Ok(Produce {
+            topic,
+            partition,
+            acks: Acks::default(),
+            timeout_ms: DEFAULT_PRODUCE_TIMEOUT_MS,
+            leader_epoch: None,
+            batch,
+        })
+    }
+
+    pub fn with_acks(mut self, acks: Acks) -> Self {
+        self.acks = acks;
+        self
+    }
+
+    /// How long the broker may hold an [`Acks::All`] produce waiting for the
+    /// batch to be flushed and replicated.
+    pub fn with_timeout(mut self, timeout_ms: u32) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    /// Has the broker refuse the write unless the partition is still at
+    /// `leader_epoch`, so a producer with stale metadata can't write to a
+    /// deposed leader.
+    pub fn with_leader_epoch(mut self, leader_epoch: u32) -> Self {
+        self.leader_epoch = (leader_epoch != NO_LEADER_EPOCH).then_some(leader_epoch);
+        self
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub fn partition(&self) -> u32 {
+        self.partition
+    }
+
+    pub fn acks(&self) -> Acks {
+        self.acks
+    }
+
+    pub fn timeout_ms(&self) -> u32 {
+        self.timeout_ms
+    }
+
+    /// The leader epoch the producer knows of, `None` if it isn't checked.
+    pub fn leader_epoch(&self) -> Option<u32> {
+        self.leader_epoch
+    }
+
+    pub fn batch(&self) -> &RecordBatch {
+        &self.batch
+    }
+
+    pub fn into_batch(self) -> RecordBatch {
+        self.batch
+    }
+
+    /// Decodes a produce within the [`DecodeLimits`] defaults.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, ProduceCreationError> {
+        Self::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    pub fn from_bytes_with_limits(
+        mut bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<Self, ProduceCreationError> {
+        if bytes.remaining() < 2 {
+            return Err(ProduceCreationError::MalformedBytes);
+        }
+        limits.check_topic_len(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)?;
+        let topic = get_str(&mut bytes).ok_or(ProduceCreationError::MalformedBytes)?;
+        validate_topic_name(&topic)?;
+
+        if bytes.remaining() < 4 + 2 + 4 + 4 {
+            return Err(ProduceCreationError::MalformedBytes);
+        }
+        let partition = bytes.get_u32();
+        let acks = Acks::try_from(bytes.get_i16())?;
+        let timeout_ms = bytes.get_u32();
+        let leader_epoch = Some(bytes.get_u32()).filter(|&epoch| epoch != NO_LEADER_EPOCH);
+
+        // The batch takes up the rest of the request
+        if RecordBatch::peek_size(&bytes) != Some(bytes.len()) {
+            return Err(ProduceCreationError::MalformedBytes);
+        }
+        limits.check_batch_size(bytes.len())?;
+        let record_count =
+            RecordBatch::peek_record_count(&bytes).ok_or(ProduceCreationError::MalformedBytes)?;
+        limits.check_record_count(record_count)?;
+        let batch = RecordBatch::from_bytes(bytes)?;
+
+        Ok(Produce {
+            topic,
+            partition,
+            acks,
+            timeout_ms,
+            leader_epoch,
+            batch,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        put_str(buf, &self.topic);
+        buf.put_u32(self.partition);
+        buf.put_i16(self.acks.code());
+        buf.put_u32(self.timeout_ms);
+        buf.put_u32(self.leader_epoch.unwrap_or(NO_LEADER_EPOCH));
+        self.batch.encode_into(buf);
+    }
+
+    pub fn size(&self) -> usize {
+        str_size(&self.topic) + 4 + 2 + 4 + 4 + self.batch.size()
+    }
+}
+
+impl Display for Produce {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ProduceRequest(topic:{}, part:{} records:{} acks:{} timeoutMs:{})",
+            self.topic,
+            self.partition,
+            self.batch.records.len(),
+            self.acks.code(),
+            self.timeout_ms
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::record::Record;
+
+    fn batch() -> RecordBatch {
+        RecordBatch::new(vec![
+            Record::new(None, Some(Bytes::from_static(b"a"))).with_timestamp(1),
+            Record::new(Some(Bytes::from_static(b"k")), None).with_timestamp(2),
+        ])
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let produce = Produce::new("test".to_string(), 3, batch()).unwrap();
+        let bytes = produce.to_bytes();
+        assert_eq!(bytes.len(), produce.size());
+        assert_eq!(Produce::from_bytes(bytes).unwrap(), produce);
+
+        let produce = produce
+            .with_acks(Acks::All)
+            .with_timeout(500)
+            .with_leader_epoch(4);
+        assert_eq!(produce.leader_epoch(), Some(4));
+        assert_eq!(Produce::from_bytes(produce.to_bytes()).unwrap(), produce);
+    }
+
+    #[test]
+    fn test_limits() {
+        let bytes = Produce::new("test".to_string(), 0, batch())
+            .unwrap()
+            .to_bytes();
+        let limits = DecodeLimits::default();
+        assert!(Produce::from_bytes_with_limits(bytes.clone(), &limits).is_ok());
+
+        let check = |limits| Produce::from_bytes_with_limits(bytes.clone(), &limits).unwrap_err();
+        assert_eq!(
+            check(DecodeLimits {
+                max_topic_len: 3,
+                ..limits
+            }),
+            LimitExceeded::TopicLength { len: 4, limit: 3 }.into()
+        );
+        assert_eq!(
+            check(DecodeLimits {
+                max_batch_bytes: 10,
+                ..limits
+            }),
+            LimitExceeded::BatchSize {
+                size: batch().size(),
+                limit: 10
+            }
+            .into()
+        );
+        assert_eq!(
+            check(DecodeLimits {
+                max_records: 1,
+                ..limits
+            }),
+            LimitExceeded::RecordCount { count: 2, limit: 1 }.into()
+        );
+    }
+
+    #[test]
+    fn test_new_invalid_topic_name() {
+        assert_eq!(
+            Produce::new("bad topic".to_string(), 0, batch()).unwrap_err(),
+            ProduceCreationError::InvalidTopicName(InvalidTopicName::IllegalChar(' '))
+        );
+        assert_eq!(
+            Produce::new("X".repeat(u16::MAX as usize + 1), 0, batch()).unwrap_err(),
+            ProduceCreationError::TopicTooLong
+        );
+    }
+
+    #[test]
+    fn test_malformed_bytes() {
+        assert_eq!(
+            Produce::from_bytes(Bytes::new()),
+            Err(ProduceCreationError::MalformedBytes)
+        );
+
+        // Missing partition
+        assert_eq!(
+            Produce::from_bytes(Bytes::from_static(&[0x00, 0x01, b't', 0x00])),
+            Err(ProduceCreationError::MalformedBytes)
+        );
+
+        // Missing timeout
+        assert_eq!(
+            Produce::from_bytes(Bytes::from_static(&[
+                0x00, 0x01, b't', 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF
+            ])),
+            Err(ProduceCreationError::MalformedBytes)
+        );
+
+        // Unknown acks
+        let mut bytes = Produce::new("t".to_string(), 0, batch())
+            .unwrap()
+            .to_bytes()
+            .to_vec();
+        bytes[7..9].copy_from_slice(&2i16.to_be_bytes());
+        assert_eq!(
+            Produce::from_bytes(Bytes::from(bytes)),
+            Err(ProduceCreationError::InvalidAcks(2))
+        );
+
+        let bytes = Produce::new("test".to_string(), 0, batch())
+            .unwrap()
+            .to_bytes();
+
+        // Batch cut short
+        assert_eq!(
+            Produce::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(ProduceCreationError::MalformedBytes)
+        );
+
+        // Batch corrupted
+        let mut corrupt = bytes.to_vec();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xFF;
+        assert!(matches!(
+            Produce::from_bytes(Bytes::from(corrupt)),
+            Err(ProduceCreationError::Batch(
+                RecordBatchError::CrcMismatch { .. }
+            ))
+        ));
+    }
+}
@@ -0,0 +1,189 @@
+use std::fmt::Display;
+use thiserror::Error;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::protocol::{
+    get_str, put_str, str_size, validate_topic_name, DecodeLimits, InvalidTopicName, LimitExceeded,
+};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ReassignCreationError {
+    #[error("Topic name is too long")]
+    TopicTooLong,
+    #[error("Target address is too long")]
+    TargetTooLong,
+    #[error("Target address is empty")]
+    EmptyTarget,
+    #[error("Malformed bytes")]
+    MalformedBytes,
+    #[error(transparent)]
+    InvalidTopicName(#[from] InvalidTopicName),
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitExceeded),
+}
+
+/// How long a reassignment may take unless told otherwise.
+pub const DEFAULT_REASSIGN_TIMEOUT_MS: u32 = 60_000;
+
+/// Moves a partition from the broker it is sent to, which must lead it, to
+/// the broker `target_id` at address `target`. The leader has the target copy
+/// the partition and catch up, hands leadership over, then drops its own
+/// copy. Answered once the move is done, or failed with `RequestTimedOut` if
+/// the target hasn't caught up within `timeout_ms`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReassignPartition {
+    topic: String,
+    partition: u32,
+    target_id: u32,
+    target: String,
+    timeout_ms: u32,
+}
+
+impl ReassignPartition {
+    pub fn new(
+        topic: String,
+        partition: u32,
+        target_id: u32,
+        target: String,
+    ) -> Result<Self, ReassignCreationError> {
+        if topic.len() > u16::MAX as usize {
+            return Err(ReassignCreationError::TopicTooLong);
+        }
+        validate_topic_name(&topic)?;
+        if target.len() > u16::MAX as usize {
+            return Err(ReassignCreationError::TargetTooLong);
+        }
+        if target.is_empty() {
+            return Err(ReassignCreationError::EmptyTarget);
+        }
+
+        Ok(ReassignPartition {
+            topic,
+            partition,
+            target_id,
+            target,
+            timeout_ms: DEFAULT_REASSIGN_TIMEOUT_MS,
+        })
+    }
+
+    pub fn with_timeout(mut self, timeout_ms: u32) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub fn partition(&self) -> u32 {
+        self.partition
+    }
+
+    /// Broker id the target fetches with.
+    pub fn target_id(&self) -> u32 {
+        self.target_id
+    }
+
+    /// Address the leader reaches the target at.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    pub fn timeout_ms(&self) -> u32 {
+        self.timeout_ms
+    }
+
+    /// Decodes a request within the [`DecodeLimits`] defaults.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, ReassignCreationError> {
+        Self::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    pub fn from_bytes_with_limits(
+        mut bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<Self, ReassignCreationError> {
+        if bytes.remaining() < 2 {
+            return Err(ReassignCreationError::MalformedBytes);
+        }
+        limits.check_topic_len(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)?;
+        let topic = get_str(&mut bytes).ok_or(ReassignCreationError::MalformedBytes)?;
+
+        if bytes.remaining() < 4 + 4 {
+            return Err(ReassignCreationError::MalformedBytes);
+        }
+        let partition = bytes.get_u32();
+        let target_id = bytes.get_u32();
+        let target = get_str(&mut bytes).ok_or(ReassignCreationError::MalformedBytes)?;
+        if bytes.remaining() != 4 {
+            return Err(ReassignCreationError::MalformedBytes);
+        }
+        let timeout_ms = bytes.get_u32();
+
+        Ok(Self::new(topic, partition, target_id, target)?.with_timeout(timeout_ms))
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        put_str(buf, &self.topic);
+        buf.put_u32(self.partition);
+        buf.put_u32(self.target_id);
+        put_str(buf, &self.target);
+        buf.put_u32(self.timeout_ms);
+    }
+
+    pub fn size(&self) -> usize {
+        str_size(&self.topic) + 4 + 4 + str_size(&self.target) + 4
+    }
+}
+
+impl Display for ReassignPartition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ReassignPartitionRequest(topic:{}, part:{} target:{}@{} timeoutMs:{})",
+            self.topic, self.partition, self.target_id, self.target, self.timeout_ms
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let reassign =
+            ReassignPartition::new("test".to_string(), 1, 2, "broker-2:9092".to_string())
+                .unwrap()
+                .with_timeout(500);
+        let bytes = reassign.to_bytes();
+        assert_eq!(bytes.len(), reassign.size());
+        assert_eq!(ReassignPartition::from_bytes(bytes).unwrap(), reassign);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(
+            ReassignPartition::new("test".to_string(), 0, 1, String::new()).unwrap_err(),
+            ReassignCreationError::EmptyTarget
+        );
+        assert_eq!(
+            ReassignPartition::new("bad topic".to_string(), 0, 1, "b:1".to_string()).unwrap_err(),
+            ReassignCreationError::InvalidTopicName(InvalidTopicName::IllegalChar(' '))
+        );
+
+        let bytes = ReassignPartition::new("test".to_string(), 0, 1, "b:1".to_string())
+            .unwrap()
+            .to_bytes();
+        assert_eq!(
+            ReassignPartition::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(ReassignCreationError::MalformedBytes)
+        );
+    }
+}
@@ -0,0 +1,359 @@
+use std::fmt::Display;
+use thiserror::Error;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::protocol::{
+    get_str, put_str, str_size, validate_topic_name, DecodeLimits, InvalidTopicName, LimitExceeded,
+};
+use crate::storage::CleanupPolicy;
+
+/// Sent in place of a retention limit left as the topic has it.
+const AS_CONFIGURED: i64 = -2;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum TopicAdminCreationError {
+    #[error("Topic name is too long")]
+    TopicTooLong,
+    #[error("A topic needs at least one partition")]
+    NoPartitions,
+    #[error("Malformed bytes")]
+    MalformedBytes,
+    #[error("Unknown cleanup policy {0}")]
+    UnknownCleanupPolicy(u8),
+    #[error(transparent)]
+    InvalidTopicName(#[from] InvalidTopicName),
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitExceeded),
+}
+
+/// Creates `partitions` partitions of a new topic on the broker it is sent
+/// to, which leads them all. Fails with `TopicAlreadyExists` if the broker
+/// already has a partition of the topic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateTopic {
+    topic: String,
+    partitions: u32,
+}
+
+impl CreateTopic {
+    pub fn new(topic: String, partitions: u32) -> Result<Self, TopicAdminCreationError> {
+        check_topic(&topic)?;
+        if partitions == 0 {
+            return Err(TopicAdminCreationError::NoPartitions);
+        }
+
+        Ok(CreateTopic { topic, partitions })
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub fn partitions(&self) -> u32 {
+        self.partitions
+    }
+
+    /// Decodes a request within the [`DecodeLimits`] defaults.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, TopicAdminCreationError> {
+        Self::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    pub fn from_bytes_with_limits(
+        mut bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<Self, TopicAdminCreationError> {
+        let topic = get_topic(&mut bytes, limits)?;
+        if bytes.remaining() != 4 {
+            return Err(TopicAdminCreationError::MalformedBytes);
+        }
+        let partitions = bytes.get_u32();
+
+        Self::new(topic, partitions)
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        put_str(buf, &self.topic);
+        buf.put_u32(self.partitions);
+    }
+
+    pub fn size(&self) -> usize {
+        str_size(&self.topic) + 4
+    }
+}
+
+impl Display for CreateTopic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "CreateTopicRequest(topic:{}, partitions:{})",
+            self.topic, self.partitions
+        )
+    }
+}
+
+/// Deletes every partition of a topic held by the broker it is sent to,
+/// along with their files. Fails with `UnknownTopicOrPartition` if there
+/// are none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeleteTopic {
+    topic: String,
+}
+
+impl DeleteTopic {
+    pub fn new(topic: String) -> Result<Self, TopicAdminCreationError> {
+        check_topic(&topic)?;
+        Ok(DeleteTopic { topic })
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Decodes a request within the [`DecodeLimits`] defaults.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, TopicAdminCreationError> {
+        Self::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    pub fn from_bytes_with_limits(
+        mut bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<Self, TopicAdminCreationError> {
+        let topic = get_topic(&mut bytes, limits)?;
+        if bytes.has_remaining() {
+            return Err(TopicAdminCreationError::MalformedBytes);
+        }
+
+        Self::new(topic)
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        put_str(buf, &self.topic);
+    }
+
+    pub fn size(&self) -> usize {
+        str_size(&self.topic)
+    }
+}
+
+impl Display for DeleteTopic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DeleteTopicRequest(topic:{})", self.topic)
+    }
+}
+
+/// Estimates how much a cleanup would reclaim from every partition of a
+/// topic held by the broker it is sent to, without cleaning anything up.
+/// The cleanup policy and retention limits can be overridden to see the
+/// effect of a config change before making it, those left unset are as the
+/// topic has them. Fails with `UnknownTopicOrPartition` if there are no
+/// partitions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EstimateCleanup {
+    topic: String,
+    cleanup_policy: Option<CleanupPolicy>,
+    retention_ms: Option<i64>,
+    retention_bytes: Option<i64>,
+}
+
+impl EstimateCleanup {
+    pub fn new(topic: String) -> Result<Self, TopicAdminCreationError> {
+        check_topic(&topic)?;
+        Ok(EstimateCleanup {
+            topic,
+            cleanup_policy: None,
+            retention_ms: None,
+            retention_bytes: None,
+        })
+    }
+
+    pub fn with_cleanup_policy(mut self, cleanup_policy: CleanupPolicy) -> Self {
+        self.cleanup_policy = Some(cleanup_policy);
+        self
+    }
+
+    /// A negative retention means no limit.
+    pub fn with_retention_ms(mut self, retention_ms: i64) -> Self {
+        self.retention_ms = Some(retention_ms.max(-1));
+        self
+    }
+
+    /// A negative retention means no limit.
+    pub fn with_retention_bytes(mut self, retention_bytes: i64) -> Self {
+        self.retention_bytes = Some(retention_bytes.max(-1));
+        self
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub fn cleanup_policy(&self) -> Option<CleanupPolicy> {
+        self.cleanup_policy
+    }
+
+    /// `-1` for no limit.
+    pub fn retention_ms(&self) -> Option<i64> {
+        self.retention_ms
+    }
+
+    /// `-1` for no limit.
+    pub fn retention_bytes(&self) -> Option<i64> {
+        self.retention_bytes
+    }
+
+    /// Decodes a request within the [`DecodeLimits`] defaults.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, TopicAdminCreationError> {
+        Self::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    pub fn from_bytes_with_limits(
+        mut bytes: Bytes,
+        limits: &DecodeLimits,
+    ) -> Result<Self, TopicAdminCreationError> {
+        let topic = get_topic(&mut bytes, limits)?;
+        if bytes.remaining() != 1 + 8 + 8 {
+            return Err(TopicAdminCreationError::MalformedBytes);
+        }
+        let mut request = Self::new(topic)?;
+        match bytes.get_u8() {
+            0 => {}
+            1 => request = request.with_cleanup_policy(CleanupPolicy::Delete),
+            2 => request = request.with_cleanup_policy(CleanupPolicy::Compact),
+            policy => return Err(TopicAdminCreationError::UnknownCleanupPolicy(policy)),
+        }
+        let retention = |value: i64| Some(value).filter(|&value| value != AS_CONFIGURED);
+        request.retention_ms = retention(bytes.get_i64()).map(|value| value.max(-1));
+        request.retention_bytes = retention(bytes.get_i64()).map(|value| value.max(-1));
+
+        Ok(request)
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        put_str(buf, &self.topic);
+        buf.put_u8(match self.cleanup_policy {
+            None => 0,
+            Some(CleanupPolicy::Delete) => 1,
+            Some(CleanupPolicy::Compact) => 2,
+        });
+        buf.put_i64(self.retention_ms.unwrap_or(AS_CONFIGURED));
+        buf.put_i64(self.retention_bytes.unwrap_or(AS_CONFIGURED));
+    }
+
+    pub fn size(&self) -> usize {
+        str_size(&self.topic) + 1 + 8 + 8
+    }
+}
+
+impl Display for EstimateCleanup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "EstimateCleanupRequest(topic:{}, cleanup_policy:{:?}, retention_ms:{:?}, retention_bytes:{:?})",
+            self.topic, self.cleanup_policy, self.retention_ms, self.retention_bytes
+        )
+    }
+}
+
+fn check_topic(topic: &str) -> Result<(), TopicAdminCreationError> {
+    if topic.len() > u16::MAX as usize {
+        return Err(TopicAdminCreationError::TopicTooLong);
+    }
+    validate_topic_name(topic)?;
+    Ok(())
+}
+
+fn get_topic(bytes: &mut Bytes, limits: &DecodeLimits) -> Result<String, TopicAdminCreationError> {
+    if bytes.remaining() < 2 {
+        return Err(TopicAdminCreationError::MalformedBytes);
+    }
+    limits.check_topic_len(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)?;
+    get_str(bytes).ok_or(TopicAdminCreationError::MalformedBytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let create = CreateTopic::new("events".to_string(), 3).unwrap();
+        let bytes = create.to_bytes();
+        assert_eq!(bytes.len(), create.size());
+        assert_eq!(CreateTopic::from_bytes(bytes).unwrap(), create);
+
+        let delete = DeleteTopic::new("events".to_string()).unwrap();
+        let bytes = delete.to_bytes();
+        assert_eq!(bytes.len(), delete.size());
+        assert_eq!(DeleteTopic::from_bytes(bytes).unwrap(), delete);
+
+        let estimate = EstimateCleanup::new("events".to_string()).unwrap();
+        assert_eq!(
+            EstimateCleanup::from_bytes(estimate.to_bytes()).unwrap(),
+            estimate
+        );
+        let estimate = estimate
+            .with_cleanup_policy(CleanupPolicy::Compact)
+            .with_retention_ms(-5)
+            .with_retention_bytes(1 << 30);
+        assert_eq!(estimate.retention_ms(), Some(-1));
+        let bytes = estimate.to_bytes();
+        assert_eq!(bytes.len(), estimate.size());
+        assert_eq!(EstimateCleanup::from_bytes(bytes).unwrap(), estimate);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(
+            CreateTopic::new("events".to_string(), 0).unwrap_err(),
+            TopicAdminCreationError::NoPartitions
+        );
+        assert_eq!(
+            DeleteTopic::new("bad topic".to_string()).unwrap_err(),
+            TopicAdminCreationError::InvalidTopicName(InvalidTopicName::IllegalChar(' '))
+        );
+
+        let bytes = CreateTopic::new("events".to_string(), 1)
+            .unwrap()
+            .to_bytes();
+        assert_eq!(
+            CreateTopic::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(TopicAdminCreationError::MalformedBytes)
+        );
+        let mut bytes =
+            BytesMut::from(&DeleteTopic::new("events".to_string()).unwrap().to_bytes()[..]);
+        bytes.put_u8(0);
+        assert_eq!(
+            DeleteTopic::from_bytes(bytes.freeze()),
+            Err(TopicAdminCreationError::MalformedBytes)
+        );
+
+        let mut bytes = BytesMut::new();
+        put_str(&mut bytes, "events");
+        bytes.put_u8(3);
+        bytes.put_i64(AS_CONFIGURED);
+        bytes.put_i64(AS_CONFIGURED);
+        assert_eq!(
+            EstimateCleanup::from_bytes(bytes.freeze()),
+            Err(TopicAdminCreationError::UnknownCleanupPolicy(3))
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use super::ResponseError;
+use crate::protocol::ErrorCode;
+
+/// Answers the requests that only report whether they worked: the admin
+/// requests, [`LeaderAndIsr`](crate::request::LeaderAndIsr) and
+/// [`ReassignPartition`](crate::request::ReassignPartition), heartbeats,
+/// [`OffsetCommit`](crate::request::OffsetCommit) and creating or deleting
+/// topics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdminResponse {
+    pub error: ErrorCode,
+    /// How long the broker held the response back for the client being over
+    /// its quota.
+    pub throttle_time_ms: u32,
+}
+
+impl AdminResponse {
+    pub fn new() -> Self {
+        Self::error(ErrorCode::None)
+    }
+
+    pub fn error(error: ErrorCode) -> Self {
+        AdminResponse {
+            error,
+            throttle_time_ms: 0,
+        }
+    }
+
+    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, ResponseError> {
+        if bytes.len() != 2 + 4 {
+            return Err(ResponseError::MalformedBytes);
+        }
+
+        Ok(AdminResponse {
+            error: ErrorCode::from_code(bytes.get_i16()),
+            throttle_time_ms: bytes.get_u32(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_i16(self.error.code());
+        buf.put_u32(self.throttle_time_ms);
+    }
+
+    pub fn size(&self) -> usize {
+        2 + 4
+    }
+}
+
+impl Default for AdminResponse {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let response = AdminResponse::new();
+        assert_eq!(
+            AdminResponse::from_bytes(response.to_bytes()).unwrap(),
+            response
+        );
+
+        let mut response = AdminResponse::error(ErrorCode::NotLeaderOrFollower);
+        response.throttle_time_ms = 250;
+        assert_eq!(
+            AdminResponse::from_bytes(response.to_bytes()).unwrap(),
+            response
+        );
+
+        assert_eq!(
+            AdminResponse::from_bytes(Bytes::from_static(&[0x00])),
+            Err(ResponseError::MalformedBytes)
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use super::ResponseError;
+use crate::protocol::ErrorCode;
+
+/// Answers an [`EstimateCleanup`](crate::request::EstimateCleanup) request
+/// with an estimate for every partition of the topic the answering broker
+/// holds, in partition order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EstimateCleanupResponse {
+    pub error: ErrorCode,
+    /// How long the broker held the response back for the client being over
+    /// its quota.
+    pub throttle_time_ms: u32,
+    pub partitions: Vec<PartitionCleanupEstimate>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartitionCleanupEstimate {
+    pub partition: u32,
+    /// Bytes of all its segment files, as they are now.
+    pub size: u64,
+    /// Segments that would be deleted or rewritten.
+    pub segments: u32,
+    pub records_removed: u64,
+    pub reclaimable_bytes: u64,
+}
+
+impl EstimateCleanupResponse {
+    pub fn new(partitions: Vec<PartitionCleanupEstimate>) -> Self {
+        EstimateCleanupResponse {
+            error: ErrorCode::None,
+            throttle_time_ms: 0,
+            partitions,
+        }
+    }
+
+    pub fn error(error: ErrorCode) -> Self {
+        EstimateCleanupResponse {
+            error,
+            ..Self::new(vec![])
+        }
+    }
+
+    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, ResponseError> {
+        if bytes.remaining() < 2 + 4 + 4 {
+            return Err(ResponseError::MalformedBytes);
+        }
+        let error = ErrorCode::from_code(bytes.get_i16());
+        let throttle_time_ms = bytes.get_u32();
+
+        let mut partitions = Vec::new();
+        for _ in 0..bytes.get_u32() {
+            if bytes.remaining() < 4 + 8 + 4 + 8 + 8 {
+                return Err(ResponseError::MalformedBytes);
+            }
+            partitions.push(PartitionCleanupEstimate {
+                partition: bytes.get_u32(),
+                size: bytes.get_u64(),
+                segments: bytes.get_u32(),
+                records_removed: bytes.get_u64(),
+                reclaimable_bytes: bytes.get_u64(),
+            });
+        }
+        if bytes.has_remaining() {
+            return Err(ResponseError::MalformedBytes);
+        }
+
+        Ok(EstimateCleanupResponse {
+            error,
+            throttle_time_ms,
+            partitions,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_i16(self.error.code());
+        buf.put_u32(self.throttle_time_ms);
+        buf.put_u32(self.partitions.len() as u32);
+        for partition in &self.partitions {
+            buf.put_u32(partition.partition);
+            buf.put_u64(partition.size);
+            buf.put_u32(partition.segments);
+            buf.put_u64(partition.records_removed);
+            buf.put_u64(partition.reclaimable_bytes);
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        2 + 4 + 4 + self.partitions.len() * (4 + 8 + 4 + 8 + 8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let response = EstimateCleanupResponse::new(vec![
+            PartitionCleanupEstimate {
+                partition: 0,
+                size: 4096,
+                segments: 2,
+                records_removed: 30,
+                reclaimable_bytes: 1024,
+            },
+            PartitionCleanupEstimate {
+                partition: 3,
+                size: 100,
+                segments: 0,
+                records_removed: 0,
+                reclaimable_bytes: 0,
+            },
+        ]);
+        let bytes = response.to_bytes();
+        assert_eq!(bytes.len(), response.size());
+        assert_eq!(
+            EstimateCleanupResponse::from_bytes(bytes.clone()).unwrap(),
+            response
+        );
+        assert_eq!(
+            EstimateCleanupResponse::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(ResponseError::MalformedBytes)
+        );
+    }
+}
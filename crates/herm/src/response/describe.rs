@@ -0,0 +1,304 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use super::ResponseError;
+use crate::protocol::{get_str, put_str, str_size, ErrorCode};
+
+/// Answers a [`DescribeCluster`](crate::request::DescribeCluster) request
+/// with the live brokers the answering broker knows of, itself first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescribeClusterResponse {
+    pub error: ErrorCode,
+    /// How long the broker held the response back for the client being over
+    /// its quota.
+    pub throttle_time_ms: u32,
+    pub brokers: Vec<BrokerDescription>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokerDescription {
+    pub id: u32,
+    pub address: String,
+    /// Version of herm the broker runs.
+    pub version: String,
+    /// Sent as an empty string when unset.
+    pub rack: Option<String>,
+}
+
+impl DescribeClusterResponse {
+    pub fn new(brokers: Vec<BrokerDescription>) -> Self {
+        DescribeClusterResponse {
+            error: ErrorCode::None,
+            throttle_time_ms: 0,
+            brokers,
+        }
+    }
+
+    pub fn error(error: ErrorCode) -> Self {
+        DescribeClusterResponse {
+            error,
+            ..Self::new(vec![])
+        }
+    }
+
+    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, ResponseError> {
+        if bytes.remaining() < 2 + 4 + 4 {
+            return Err(ResponseError::MalformedBytes);
+        }
+        let error = ErrorCode::from_code(bytes.get_i16());
+        let throttle_time_ms = bytes.get_u32();
+
+        let mut brokers = Vec::new();
+        for _ in 0..bytes.get_u32() {
+            if bytes.remaining() < 4 {
+                return Err(ResponseError::MalformedBytes);
+            }
+            let id = bytes.get_u32();
+            let address = get_str(&mut bytes).ok_or(ResponseError::MalformedBytes)?;
+            let version = get_str(&mut bytes).ok_or(ResponseError::MalformedBytes)?;
+            let rack = get_str(&mut bytes).ok_or(ResponseError::MalformedBytes)?;
+            brokers.push(BrokerDescription {
+                id,
+                address,
+                version,
+                rack: Some(rack).filter(|rack| !rack.is_empty()),
+            });
+        }
+        if bytes.has_remaining() {
+            return Err(ResponseError::MalformedBytes);
+        }
+
+        Ok(DescribeClusterResponse {
+            error,
+            throttle_time_ms,
+            brokers,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_i16(self.error.code());
+        buf.put_u32(self.throttle_time_ms);
+        buf.put_u32(self.brokers.len() as u32);
+        for broker in &self.brokers {
+            buf.put_u32(broker.id);
+            put_str(buf, &broker.address);
+            put_str(buf, &broker.version);
+            put_str(buf, broker.rack.as_deref().unwrap_or_default());
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        let brokers: usize = self
+            .brokers
+            .iter()
+            .map(|broker| {
+                4 + str_size(&broker.address)
+                    + str_size(&broker.version)
+                    + str_size(broker.rack.as_deref().unwrap_or_default())
+            })
+            .sum();
+        2 + 4 + 4 + brokers
+    }
+}
+
+/// Answers a [`DescribeLogDirs`](crate::request::DescribeLogDirs) request
+/// with every log dir of the answering broker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescribeLogDirsResponse {
+    pub error: ErrorCode,
+    /// How long the broker held the response back for the client being over
+    /// its quota.
+    pub throttle_time_ms: u32,
+    pub dirs: Vec<LogDirDescription>,
+}
+
+/// A log dir and the partitions on it. Offline dirs come back with
+/// [`ErrorCode::StorageError`] and no partitions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogDirDescription {
+    pub error: ErrorCode,
+    pub path: String,
+    pub partitions: Vec<PartitionLogDescription>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartitionLogDescription {
+    pub topic: String,
+    pub partition: u32,
+    /// Bytes of all its segment files.
+    pub size: u64,
+    pub segments: u32,
+    pub start_offset: u64,
+    pub next_offset: u64,
+}
+
+impl DescribeLogDirsResponse {
+    pub fn new(dirs: Vec<LogDirDescription>) -> Self {
+        DescribeLogDirsResponse {
+            error: ErrorCode::None,
+            throttle_time_ms: 0,
+            dirs,
+        }
+    }
+
+    pub fn error(error: ErrorCode) -> Self {
+        DescribeLogDirsResponse {
+            error,
+            ..Self::new(vec![])
+        }
+    }
+
+    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, ResponseError> {
+        if bytes.remaining() < 2 + 4 + 4 {
+            return Err(ResponseError::MalformedBytes);
+        }
+        let error = ErrorCode::from_code(bytes.get_i16());
+        let throttle_time_ms = bytes.get_u32();
+
+        let mut dirs = Vec::new();
+        for _ in 0..bytes.get_u32() {
+            if bytes.remaining() < 2 {
+                return Err(ResponseError::MalformedBytes);
+            }
+            let error = ErrorCode::from_code(bytes.get_i16());
+            let path = get_str(&mut bytes).ok_or(ResponseError::MalformedBytes)?;
+            if bytes.remaining() < 4 {
+                return Err(ResponseError::MalformedBytes);
+            }
+            let mut partitions = Vec::new();
+            for _ in 0..bytes.get_u32() {
+                let topic = get_str(&mut bytes).ok_or(ResponseError::MalformedBytes)?;
+                if bytes.remaining() < 4 + 8 + 4 + 8 + 8 {
+                    return Err(ResponseError::MalformedBytes);
+                }
+                partitions.push(PartitionLogDescription {
+                    topic,
+                    partition: bytes.get_u32(),
+                    size: bytes.get_u64(),
+                    segments: bytes.get_u32(),
+                    start_offset: bytes.get_u64(),
+                    next_offset: bytes.get_u64(),
+                });
+            }
+            dirs.push(LogDirDescription {
+                error,
+                path,
+                partitions,
+            });
+        }
+        if bytes.has_remaining() {
+            return Err(ResponseError::MalformedBytes);
+        }
+
+        Ok(DescribeLogDirsResponse {
+            error,
+            throttle_time_ms,
+            dirs,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_i16(self.error.code());
+        buf.put_u32(self.throttle_time_ms);
+        buf.put_u32(self.dirs.len() as u32);
+        for dir in &self.dirs {
+            buf.put_i16(dir.error.code());
+            put_str(buf, &dir.path);
+            buf.put_u32(dir.partitions.len() as u32);
+            for partition in &dir.partitions {
+                put_str(buf, &partition.topic);
+                buf.put_u32(partition.partition);
+                buf.put_u64(partition.size);
+                buf.put_u32(partition.segments);
+                buf.put_u64(partition.start_offset);
+                buf.put_u64(partition.next_offset);
+            }
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        let dirs: usize = self
+            .dirs
+            .iter()
+            .map(|dir| {
+                let partitions: usize = dir
+                    .partitions
+                    .iter()
+                    .map(|partition| str_size(&partition.topic) + 4 + 8 + 4 + 8 + 8)
+                    .sum();
+                2 + str_size(&dir.path) + 4 + partitions
+            })
+            .sum();
+        2 + 4 + 4 + dirs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let response = DescribeClusterResponse::new(vec![
+            BrokerDescription {
+                id: 0,
+                address: "broker-0:9092".to_string(),
+                version: "0.1.0".to_string(),
+                rack: Some("eu-west-1a".to_string()),
+            },
+            BrokerDescription {
+                id: 1,
+                address: "broker-1:9092".to_string(),
+                version: "0.1.0".to_string(),
+                rack: None,
+            },
+        ]);
+        let bytes = response.to_bytes();
+        assert_eq!(bytes.len(), response.size());
+        assert_eq!(
+            DescribeClusterResponse::from_bytes(bytes).unwrap(),
+            response
+        );
+
+        let response = DescribeLogDirsResponse::new(vec![
+            LogDirDescription {
+                error: ErrorCode::None,
+                path: "/var/lib/herm/a".to_string(),
+                partitions: vec![PartitionLogDescription {
+                    topic: "events".to_string(),
+                    partition: 2,
+                    size: 4096,
+                    segments: 3,
+                    start_offset: 10,
+                    next_offset: 250,
+                }],
+            },
+            LogDirDescription {
+                error: ErrorCode::StorageError,
+                path: "/var/lib/herm/b".to_string(),
+                partitions: vec![],
+            },
+        ]);
+        let bytes = response.to_bytes();
+        assert_eq!(bytes.len(), response.size());
+        assert_eq!(
+            DescribeLogDirsResponse::from_bytes(bytes.clone()).unwrap(),
+            response
+        );
+        assert_eq!(
+            DescribeLogDirsResponse::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(ResponseError::MalformedBytes)
+        );
+    }
+}
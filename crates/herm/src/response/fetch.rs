@@ -0,0 +1,190 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use super::ResponseError;
+use crate::protocol::ErrorCode;
+use crate::record::RecordBatch;
+use crate::storage::FileSlice;
+
+/// Answers a [`Fetch`](crate::request::Fetch) with the batches read, the
+/// offset up to which the partition can be read, and the partition's leader
+/// epoch.
+///
+/// Batches from sealed segments may be left in their files as `slices`, and
+/// sent on from there by the broker. They go on the wire after `batches`, and
+/// come back as `batches` when decoded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchResponse {
+    pub error: ErrorCode,
+    /// How long the broker held the response back for the client being over
+    /// its quota.
+    pub throttle_time_ms: u32,
+    pub high_watermark: u64,
+    /// Epoch of the partition at the broker, also sent with errors so a
+    /// fenced follower learns the one to fetch with.
+    pub leader_epoch: u32,
+    pub batches: Vec<RecordBatch>,
+    pub slices: Vec<FileSlice>,
+}
+
+impl FetchResponse {
+    pub fn new(high_watermark: u64, batches: Vec<RecordBatch>) -> Self {
+        FetchResponse {
+            error: ErrorCode::None,
+            throttle_time_ms: 0,
+            high_watermark,
+            leader_epoch: 0,
+            batches,
+            slices: vec![],
+        }
+    }
+
+    /// Answers with batches still in segment files.
+    pub fn from_slices(high_watermark: u64, slices: Vec<FileSlice>) -> Self {
+        FetchResponse {
+            slices,
+            ..Self::new(high_watermark, vec![])
+        }
+    }
+
+    pub fn error(error: ErrorCode) -> Self {
+        FetchResponse {
+            error,
+            throttle_time_ms: 0,
+            high_watermark: 0,
+            leader_epoch: 0,
+            batches: vec![],
+            slices: vec![],
+        }
+    }
+
+    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, ResponseError> {
+        if bytes.remaining() < 2 + 4 + 8 + 4 + 4 {
+            return Err(ResponseError::MalformedBytes);
+        }
+
+        let error = ErrorCode::from_code(bytes.get_i16());
+        let throttle_time_ms = bytes.get_u32();
+        let high_watermark = bytes.get_u64();
+        let leader_epoch = bytes.get_u32();
+        let count = bytes.get_u32();
+
+        let mut batches = Vec::new();
+        for _ in 0..count {
+            batches.push(RecordBatch::decode(&mut bytes)?);
+        }
+        if bytes.has_remaining() {
+            return Err(ResponseError::MalformedBytes);
+        }
+
+        Ok(FetchResponse {
+            error,
+            throttle_time_ms,
+            high_watermark,
+            leader_epoch,
+            batches,
+            slices: vec![],
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    /// Encodes everything but the bytes of `slices`, which have to be
+    /// written right after, see [`FileSlice::copy_to`].
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_i16(self.error.code());
+        buf.put_u32(self.throttle_time_ms);
+        buf.put_u64(self.high_watermark);
+        buf.put_u32(self.leader_epoch);
+        buf.put_u32((self.batches.len() + self.slices.len()) as u32);
+        for batch in &self.batches {
+            batch.encode_into(buf);
+        }
+    }
+
+    /// Encoded size, `slices` included.
+    pub fn size(&self) -> usize {
+        2 + 4 + 8 + 4 + 4 + self.records_size()
+    }
+
+    /// Bytes of the batches and slices.
+    pub fn records_size(&self) -> usize {
+        let batches: usize = self.batches.iter().map(RecordBatch::size).sum();
+        let slices: u64 = self.slices.iter().map(FileSlice::len).sum();
+        batches + slices as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::record::Record;
+
+    #[test]
+    fn test_from_bytes() {
+        let mut second = RecordBatch::new(vec![Record::new(None, None).with_timestamp(5)]);
+        second.set_base_offset(1);
+        let response = FetchResponse::new(
+            2,
+            vec![
+                RecordBatch::new(vec![Record::new(None, None).with_timestamp(4)]),
+                second,
+            ],
+        );
+
+        let bytes = response.to_bytes();
+        assert_eq!(bytes.len(), response.size());
+        assert_eq!(FetchResponse::from_bytes(bytes).unwrap(), response);
+
+        let mut response = FetchResponse::error(ErrorCode::FencedLeaderEpoch);
+        response.throttle_time_ms = 250;
+        response.leader_epoch = 3;
+        assert_eq!(
+            FetchResponse::from_bytes(response.to_bytes()).unwrap(),
+            response
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slices() {
+        let batches = [
+            RecordBatch::new(vec![Record::new(None, None).with_timestamp(4)]),
+            RecordBatch::new(vec![Record::new(None, None).with_timestamp(5)]),
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("segment");
+        std::fs::write(&path, [b"junk".as_slice(), &batches[1].to_bytes()].concat()).unwrap();
+        let file = std::sync::Arc::new(std::fs::File::open(&path).unwrap());
+        let slice = FileSlice::new(file, 4, batches[1].size() as u64);
+
+        let mut response = FetchResponse::from_slices(2, vec![slice.clone()]);
+        response.batches.push(batches[0].clone());
+        let mut buf = BytesMut::new();
+        response.encode_into(&mut buf);
+        let mut encoded = buf.to_vec();
+        slice.copy_to(&mut encoded).await.unwrap();
+        assert_eq!(encoded.len(), response.size());
+
+        // Slices come back as batches
+        let decoded = FetchResponse::from_bytes(encoded.into()).unwrap();
+        assert_eq!(decoded, FetchResponse::new(2, batches.to_vec()));
+    }
+
+    #[test]
+    fn test_malformed_bytes() {
+        assert_eq!(
+            FetchResponse::from_bytes(Bytes::new()),
+            Err(ResponseError::MalformedBytes)
+        );
+
+        // Claims a batch that isn't there
+        let mut buf = BytesMut::new();
+        FetchResponse::new(0, vec![]).encode_into(&mut buf);
+        buf[21] = 1;
+        assert!(FetchResponse::from_bytes(buf.freeze()).is_err());
+    }
+}
@@ -0,0 +1,687 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use super::ResponseError;
+use crate::protocol::{get_str, put_str, str_size, ErrorCode, TopicPartition};
+
+/// End offset of partitions whose log isn't on the answering broker.
+pub const UNKNOWN_END_OFFSET: u64 = u64::MAX;
+
+/// Where a consumer group is at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum GroupState {
+    /// No members, only committed offsets.
+    #[default]
+    Empty = 0,
+    Stable = 1,
+    /// Waiting for the members to rejoin.
+    PreparingRebalance = 2,
+    /// Waiting for the leader to send the members' assignments.
+    CompletingRebalance = 3,
+}
+
+impl GroupState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GroupState::Empty => "Empty",
+            GroupState::Stable => "Stable",
+            GroupState::PreparingRebalance => "PreparingRebalance",
+            GroupState::CompletingRebalance => "CompletingRebalance",
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(GroupState::Empty),
+            1 => Some(GroupState::Stable),
+            2 => Some(GroupState::PreparingRebalance),
+            3 => Some(GroupState::CompletingRebalance),
+            _ => None,
+        }
+    }
+}
+
+/// Answers a [`ListGroups`](crate::request::ListGroups) request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListGroupsResponse {
+    pub error: ErrorCode,
+    /// How long the broker held the response back for the client being over
+    /// its quota.
+    pub throttle_time_ms: u32,
+    pub groups: Vec<(String, GroupState)>,
+}
+
+impl ListGroupsResponse {
+    pub fn new(groups: Vec<(String, GroupState)>) -> Self {
+        ListGroupsResponse {
+            error: ErrorCode::None,
+            throttle_time_ms: 0,
+            groups,
+        }
+    }
+
+    pub fn error(error: ErrorCode) -> Self {
+        ListGroupsResponse {
+            error,
+            ..Self::new(vec![])
+        }
+    }
+
+    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, ResponseError> {
+        if bytes.remaining() < 2 + 4 + 4 {
+            return Err(ResponseError::MalformedBytes);
+        }
+        let error = ErrorCode::from_code(bytes.get_i16());
+        let throttle_time_ms = bytes.get_u32();
+
+        let mut groups = Vec::new();
+        for _ in 0..bytes.get_u32() {
+            let group = get_str(&mut bytes).ok_or(ResponseError::MalformedBytes)?;
+            groups.push((group, get_state(&mut bytes)?));
+        }
+        if bytes.has_remaining() {
+            return Err(ResponseError::MalformedBytes);
+        }
+
+        Ok(ListGroupsResponse {
+            error,
+            throttle_time_ms,
+            groups,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_i16(self.error.code());
+        buf.put_u32(self.throttle_time_ms);
+        buf.put_u32(self.groups.len() as u32);
+        for (group, state) in &self.groups {
+            put_str(buf, group);
+            buf.put_u8(*state as u8);
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        let groups: usize = self
+            .groups
+            .iter()
+            .map(|(group, _)| str_size(group) + 1)
+            .sum();
+        2 + 4 + 4 + groups
+    }
+}
+
+/// Answers a [`DescribeGroups`](crate::request::DescribeGroups) request,
+/// with one description per group asked about, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescribeGroupsResponse {
+    pub error: ErrorCode,
+    /// How long the broker held the response back for the client being over
+    /// its quota.
+    pub throttle_time_ms: u32,
+    pub groups: Vec<GroupDescription>,
+}
+
+/// A group, its members and how far it has consumed. Groups the broker
+/// doesn't know come back with [`ErrorCode::GroupIdNotFound`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupDescription {
+    pub error: ErrorCode,
+    pub group: String,
+    pub state: GroupState,
+    pub members: Vec<MemberDescription>,
+    pub offsets: Vec<PartitionOffset>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemberDescription {
+    pub member_id: String,
+    pub client_id: String,
+    /// Address the member connected from.
+    pub host: String,
+    pub assignment: Vec<TopicPartition>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartitionOffset {
+    pub topic: String,
+    pub partition: u32,
+    /// The next offset the group reads from the partition.
+    pub committed: u64,
+    /// The high watermark of the partition, [`UNKNOWN_END_OFFSET`] when its
+    /// log isn't on the answering broker.
+    pub end_offset: u64,
+}
+
+impl PartitionOffset {
+    /// Records the group has yet to consume, `None` when the end offset is
+    /// unknown.
+    pub fn lag(&self) -> Option<u64> {
+        (self.end_offset != UNKNOWN_END_OFFSET)
+            .then(|| self.end_offset.saturating_sub(self.committed))
+    }
+}
+
+impl GroupDescription {
+    /// A group the broker doesn't know, or won't describe, with `error`.
+    pub fn error(group: String, error: ErrorCode) -> Self {
+        GroupDescription {
+            error,
+            group,
+            state: GroupState::Empty,
+            members: vec![],
+            offsets: vec![],
+        }
+    }
+
+    /// Records the group has yet to consume across the partitions with a
+    /// known end offset.
+    pub fn total_lag(&self) -> u64 {
+        self.offsets.iter().filter_map(PartitionOffset::lag).sum()
+    }
+
+    fn size(&self) -> usize {
+        let members: usize = self
+            .members
+            .iter()
+            .map(|member| {
+                let assignment: usize = member
+                    .assignment
+                    .iter()
+                    .map(|partition| str_size(&partition.topic) + 4)
+                    .sum();
+                str_size(&member.member_id)
+                    + str_size(&member.client_id)
+                    + str_size(&member.host)
+                    + 4
+                    + assignment
+            })
+            .sum();
+        let offsets: usize = self
+            .offsets
+            .iter()
+            .map(|offset| str_size(&offset.topic) + 4 + 8 + 8)
+            .sum();
+        2 + str_size(&self.group) + 1 + 4 + members + 4 + offsets
+    }
+}
+
+impl DescribeGroupsResponse {
+    pub fn new(groups: Vec<GroupDescription>) -> Self {
+        DescribeGroupsResponse {
+            error: ErrorCode::None,
+            throttle_time_ms: 0,
+            groups,
+        }
+    }
+
+    pub fn error(error: ErrorCode) -> Self {
+        DescribeGroupsResponse {
+            error,
+            ..Self::new(vec![])
+        }
+    }
+
+    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, ResponseError> {
+        if bytes.remaining() < 2 + 4 + 4 {
+            return Err(ResponseError::MalformedBytes);
+        }
+        let error = ErrorCode::from_code(bytes.get_i16());
+        let throttle_time_ms = bytes.get_u32();
+
+        let mut groups = Vec::new();
+        for _ in 0..bytes.get_u32() {
+            groups.push(get_group(&mut bytes)?);
+        }
+        if bytes.has_remaining() {
+            return Err(ResponseError::MalformedBytes);
+        }
+
+        Ok(DescribeGroupsResponse {
+            error,
+            throttle_time_ms,
+            groups,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_i16(self.error.code());
+        buf.put_u32(self.throttle_time_ms);
+        buf.put_u32(self.groups.len() as u32);
+        for group in &self.groups {
+            buf.put_i16(group.error.code());
+            put_str(buf, &group.group);
+            buf.put_u8(group.state as u8);
+            buf.put_u32(group.members.len() as u32);
+            for member in &group.members {
+                put_str(buf, &member.member_id);
+                put_str(buf, &member.client_id);
+                put_str(buf, &member.host);
+                buf.put_u32(member.assignment.len() as u32);
+                for partition in &member.assignment {
+                    put_str(buf, &partition.topic);
+                    buf.put_u32(partition.partition);
+                }
+            }
+            buf.put_u32(group.offsets.len() as u32);
+            for offset in &group.offsets {
+                put_str(buf, &offset.topic);
+                buf.put_u32(offset.partition);
+                buf.put_u64(offset.committed);
+                buf.put_u64(offset.end_offset);
+            }
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        let groups: usize = self.groups.iter().map(GroupDescription::size).sum();
+        2 + 4 + 4 + groups
+    }
+}
+
+/// Answers a [`JoinGroup`](crate::request::JoinGroup) request once the
+/// group's members have rejoined.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoinGroupResponse {
+    pub error: ErrorCode,
+    /// How long the broker held the response back for the client being over
+    /// its quota.
+    pub throttle_time_ms: u32,
+    pub generation: u32,
+    /// The assignor the leader assigns partitions with, the first of its
+    /// own every member supports.
+    pub assignor: String,
+    /// The member that assigns partitions to the others.
+    pub leader_id: String,
+    /// Id the member rejoins, heartbeats and syncs with.
+    pub member_id: String,
+    /// Every member with its subscription, sent to the leader only.
+    pub members: Vec<GroupMember>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupMember {
+    pub member_id: String,
+    pub topics: Vec<String>,
+    /// Partitions the member owned going into the rebalance.
+    pub owned: Vec<TopicPartition>,
+}
+
+impl JoinGroupResponse {
+    pub fn error(error: ErrorCode) -> Self {
+        JoinGroupResponse {
+            error,
+            throttle_time_ms: 0,
+            generation: 0,
+            assignor: String::new(),
+            leader_id: String::new(),
+            member_id: String::new(),
+            members: vec![],
+        }
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.error.is_ok() && self.leader_id == self.member_id
+    }
+
+    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, ResponseError> {
+        if bytes.remaining() < 2 + 4 + 4 {
+            return Err(ResponseError::MalformedBytes);
+        }
+        let error = ErrorCode::from_code(bytes.get_i16());
+        let throttle_time_ms = bytes.get_u32();
+        let generation = bytes.get_u32();
+        let assignor = get_str(&mut bytes).ok_or(ResponseError::MalformedBytes)?;
+        let leader_id = get_str(&mut bytes).ok_or(ResponseError::MalformedBytes)?;
+        let member_id = get_str(&mut bytes).ok_or(ResponseError::MalformedBytes)?;
+
+        if bytes.remaining() < 4 {
+            return Err(ResponseError::MalformedBytes);
+        }
+        let mut members = Vec::new();
+        for _ in 0..bytes.get_u32() {
+            let member_id = get_str(&mut bytes).ok_or(ResponseError::MalformedBytes)?;
+            if bytes.remaining() < 4 {
+                return Err(ResponseError::MalformedBytes);
+            }
+            let mut topics = Vec::new();
+            for _ in 0..bytes.get_u32() {
+                topics.push(get_str(&mut bytes).ok_or(ResponseError::MalformedBytes)?);
+            }
+            let owned = get_partitions(&mut bytes)?;
+            members.push(GroupMember {
+                member_id,
+                topics,
+                owned,
+            });
+        }
+        if bytes.has_remaining() {
+            return Err(ResponseError::MalformedBytes);
+        }
+
+        Ok(JoinGroupResponse {
+            error,
+            throttle_time_ms,
+            generation,
+            assignor,
+            leader_id,
+            member_id,
+            members,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_i16(self.error.code());
+        buf.put_u32(self.throttle_time_ms);
+        buf.put_u32(self.generation);
+        put_str(buf, &self.assignor);
+        put_str(buf, &self.leader_id);
+        put_str(buf, &self.member_id);
+        buf.put_u32(self.members.len() as u32);
+        for member in &self.members {
+            put_str(buf, &member.member_id);
+            buf.put_u32(member.topics.len() as u32);
+            for topic in &member.topics {
+                put_str(buf, topic);
+            }
+            put_partitions(buf, &member.owned);
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        let members: usize = self
+            .members
+            .iter()
+            .map(|member| {
+                let topics: usize = member.topics.iter().map(|topic| str_size(topic)).sum();
+                str_size(&member.member_id) + 4 + topics + partitions_size(&member.owned)
+            })
+            .sum();
+        2 + 4
+            + 4
+            + str_size(&self.assignor)
+            + str_size(&self.leader_id)
+            + str_size(&self.member_id)
+            + 4
+            + members
+    }
+}
+
+/// Answers a [`SyncGroup`](crate::request::SyncGroup) request with the
+/// partitions assigned to the member.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncGroupResponse {
+    pub error: ErrorCode,
+    /// How long the broker held the response back for the client being over
+    /// its quota.
+    pub throttle_time_ms: u32,
+    pub assignment: Vec<TopicPartition>,
+}
+
+impl SyncGroupResponse {
+    pub fn new(assignment: Vec<TopicPartition>) -> Self {
+        SyncGroupResponse {
+            error: ErrorCode::None,
+            throttle_time_ms: 0,
+            assignment,
+        }
+    }
+
+    pub fn error(error: ErrorCode) -> Self {
+        SyncGroupResponse {
+            error,
+            ..Self::new(vec![])
+        }
+    }
+
+    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, ResponseError> {
+        if bytes.remaining() < 2 + 4 + 4 {
+            return Err(ResponseError::MalformedBytes);
+        }
+        let error = ErrorCode::from_code(bytes.get_i16());
+        let throttle_time_ms = bytes.get_u32();
+        let assignment = get_partitions(&mut bytes)?;
+        if bytes.has_remaining() {
+            return Err(ResponseError::MalformedBytes);
+        }
+
+        Ok(SyncGroupResponse {
+            error,
+            throttle_time_ms,
+            assignment,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_i16(self.error.code());
+        buf.put_u32(self.throttle_time_ms);
+        put_partitions(buf, &self.assignment);
+    }
+
+    pub fn size(&self) -> usize {
+        2 + 4 + partitions_size(&self.assignment)
+    }
+}
+
+/// A u32 count, then the topic and partition of each.
+fn get_partitions(bytes: &mut Bytes) -> Result<Vec<TopicPartition>, ResponseError> {
+    if bytes.remaining() < 4 {
+        return Err(ResponseError::MalformedBytes);
+    }
+    let mut partitions = Vec::new();
+    for _ in 0..bytes.get_u32() {
+        let topic = get_str(bytes).ok_or(ResponseError::MalformedBytes)?;
+        if bytes.remaining() < 4 {
+            return Err(ResponseError::MalformedBytes);
+        }
+        partitions.push(TopicPartition::new(topic, bytes.get_u32()));
+    }
+    Ok(partitions)
+}
+
+fn put_partitions(buf: &mut impl BufMut, partitions: &[TopicPartition]) {
+    buf.put_u32(partitions.len() as u32);
+    for partition in partitions {
+        put_str(buf, &partition.topic);
+        buf.put_u32(partition.partition);
+    }
+}
+
+fn partitions_size(partitions: &[TopicPartition]) -> usize {
+    let partitions: usize = partitions
+        .iter()
+        .map(|partition| str_size(&partition.topic) + 4)
+        .sum();
+    4 + partitions
+}
+
+fn get_state(bytes: &mut Bytes) -> Result<GroupState, ResponseError> {
+    if bytes.remaining() < 1 {
+        return Err(ResponseError::MalformedBytes);
+    }
+    GroupState::from_code(bytes.get_u8()).ok_or(ResponseError::MalformedBytes)
+}
+
+fn get_group(bytes: &mut Bytes) -> Result<GroupDescription, ResponseError> {
+    if bytes.remaining() < 2 {
+        return Err(ResponseError::MalformedBytes);
+    }
+    let error = ErrorCode::from_code(bytes.get_i16());
+    let group = get_str(bytes).ok_or(ResponseError::MalformedBytes)?;
+    let state = get_state(bytes)?;
+
+    if bytes.remaining() < 4 {
+        return Err(ResponseError::MalformedBytes);
+    }
+    let mut members = Vec::new();
+    for _ in 0..bytes.get_u32() {
+        let member_id = get_str(bytes).ok_or(ResponseError::MalformedBytes)?;
+        let client_id = get_str(bytes).ok_or(ResponseError::MalformedBytes)?;
+        let host = get_str(bytes).ok_or(ResponseError::MalformedBytes)?;
+        if bytes.remaining() < 4 {
+            return Err(ResponseError::MalformedBytes);
+        }
+        let mut assignment = Vec::new();
+        for _ in 0..bytes.get_u32() {
+            let topic = get_str(bytes).ok_or(ResponseError::MalformedBytes)?;
+            if bytes.remaining() < 4 {
+                return Err(ResponseError::MalformedBytes);
+            }
+            assignment.push(TopicPartition::new(topic, bytes.get_u32()));
+        }
+        members.push(MemberDescription {
+            member_id,
+            client_id,
+            host,
+            assignment,
+        });
+    }
+
+    if bytes.remaining() < 4 {
+        return Err(ResponseError::MalformedBytes);
+    }
+    let mut offsets = Vec::new();
+    for _ in 0..bytes.get_u32() {
+        let topic = get_str(bytes).ok_or(ResponseError::MalformedBytes)?;
+        if bytes.remaining() < 4 + 8 + 8 {
+            return Err(ResponseError::MalformedBytes);
+        }
+        offsets.push(PartitionOffset {
+            topic,
+            partition: bytes.get_u32(),
+            committed: bytes.get_u64(),
+            end_offset: bytes.get_u64(),
+        });
+    }
+
+    Ok(GroupDescription {
+        error,
+        group,
+        state,
+        members,
+        offsets,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let response = ListGroupsResponse::new(vec![
+            ("billing".to_string(), GroupState::Empty),
+            ("search".to_string(), GroupState::Stable),
+        ]);
+        let bytes = response.to_bytes();
+        assert_eq!(bytes.len(), response.size());
+        assert_eq!(ListGroupsResponse::from_bytes(bytes).unwrap(), response);
+
+        let response = DescribeGroupsResponse::new(vec![
+            GroupDescription {
+                error: ErrorCode::None,
+                group: "billing".to_string(),
+                state: GroupState::Stable,
+                members: vec![MemberDescription {
+                    member_id: "billing-1".to_string(),
+                    client_id: "app".to_string(),
+                    host: "10.0.0.7".to_string(),
+                    assignment: vec![TopicPartition::new("events", 0)],
+                }],
+                offsets: vec![
+                    PartitionOffset {
+                        topic: "events".to_string(),
+                        partition: 0,
+                        committed: 40,
+                        end_offset: 100,
+                    },
+                    PartitionOffset {
+                        topic: "events".to_string(),
+                        partition: 1,
+                        committed: 7,
+                        end_offset: UNKNOWN_END_OFFSET,
+                    },
+                ],
+            },
+            GroupDescription::error("missing".to_string(), ErrorCode::GroupIdNotFound),
+        ]);
+        let bytes = response.to_bytes();
+        assert_eq!(bytes.len(), response.size());
+        assert_eq!(
+            DescribeGroupsResponse::from_bytes(bytes.clone()).unwrap(),
+            response
+        );
+        assert_eq!(
+            DescribeGroupsResponse::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(ResponseError::MalformedBytes)
+        );
+
+        let billing = &response.groups[0];
+        assert_eq!(billing.offsets[0].lag(), Some(60));
+        assert_eq!(billing.offsets[1].lag(), None);
+        assert_eq!(billing.total_lag(), 60);
+    }
+
+    #[test]
+    fn test_join_and_sync() {
+        let response = JoinGroupResponse {
+            error: ErrorCode::None,
+            throttle_time_ms: 0,
+            generation: 2,
+            assignor: "range".to_string(),
+            leader_id: "app-1".to_string(),
+            member_id: "app-1".to_string(),
+            members: vec![
+                GroupMember {
+                    member_id: "app-1".to_string(),
+                    topics: vec!["events".to_string()],
+                    owned: vec![TopicPartition::new("events", 0)],
+                },
+                GroupMember {
+                    member_id: "app-2".to_string(),
+                    topics: vec!["events".to_string(), "audit".to_string()],
+                    owned: vec![],
+                },
+            ],
+        };
+        assert!(response.is_leader());
+        let bytes = response.to_bytes();
+        assert_eq!(bytes.len(), response.size());
+        assert_eq!(
+            JoinGroupResponse::from_bytes(bytes.clone()).unwrap(),
+            response
+        );
+        assert_eq!(
+            JoinGroupResponse::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(ResponseError::MalformedBytes)
+        );
+
+        let response = SyncGroupResponse::new(vec![TopicPartition::new("events", 1)]);
+        let bytes = response.to_bytes();
+        assert_eq!(bytes.len(), response.size());
+        assert_eq!(SyncGroupResponse::from_bytes(bytes).unwrap(), response);
+    }
+}
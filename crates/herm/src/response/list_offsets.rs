@@ -0,0 +1,95 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use super::ResponseError;
+use crate::protocol::ErrorCode;
+
+/// Offset of a [`ListOffsetsResponse`] when no record is as recent as the
+/// timestamp asked for.
+pub const NO_OFFSET: u64 = u64::MAX;
+
+/// Answers a [`ListOffsets`](crate::request::ListOffsets) with the offset
+/// found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListOffsetsResponse {
+    pub error: ErrorCode,
+    pub throttle_time_ms: u32,
+    pub offset: u64,
+}
+
+impl ListOffsetsResponse {
+    pub fn new(offset: u64) -> Self {
+        ListOffsetsResponse {
+            error: ErrorCode::None,
+            throttle_time_ms: 0,
+            offset,
+        }
+    }
+
+    pub fn error(error: ErrorCode) -> Self {
+        ListOffsetsResponse {
+            error,
+            throttle_time_ms: 0,
+            offset: NO_OFFSET,
+        }
+    }
+
+    /// The offset, `None` for [`NO_OFFSET`].
+    pub fn found(&self) -> Option<u64> {
+        (self.offset != NO_OFFSET).then_some(self.offset)
+    }
+
+    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, ResponseError> {
+        if bytes.len() != 2 + 4 + 8 {
+            return Err(ResponseError::MalformedBytes);
+        }
+
+        Ok(ListOffsetsResponse {
+            error: ErrorCode::from_code(bytes.get_i16()),
+            throttle_time_ms: bytes.get_u32(),
+            offset: bytes.get_u64(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_i16(self.error.code());
+        buf.put_u32(self.throttle_time_ms);
+        buf.put_u64(self.offset);
+    }
+
+    pub fn size(&self) -> usize {
+        2 + 4 + 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let response = ListOffsetsResponse::new(42);
+        assert_eq!(response.found(), Some(42));
+        assert_eq!(
+            ListOffsetsResponse::from_bytes(response.to_bytes()).unwrap(),
+            response
+        );
+
+        let response = ListOffsetsResponse::error(ErrorCode::UnknownTopicOrPartition);
+        assert_eq!(response.found(), None);
+        assert_eq!(
+            ListOffsetsResponse::from_bytes(response.to_bytes()).unwrap(),
+            response
+        );
+
+        assert_eq!(
+            ListOffsetsResponse::from_bytes(Bytes::from_static(&[0x00])),
+            Err(ResponseError::MalformedBytes)
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use thiserror::Error;
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use super::{
+    AdminResponse, DescribeClusterResponse, DescribeGroupsResponse, DescribeLogDirsResponse,
+    EstimateCleanupResponse, FetchResponse, JoinGroupResponse, ListGroupsResponse,
+    ListOffsetsResponse, MetadataResponse, ProduceResponse, SyncGroupResponse,
+};
+use crate::protocol::{ApiKey, ErrorCode};
+use crate::record::RecordBatchError;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ResponseError {
+    #[error("Malformed bytes")]
+    MalformedBytes,
+    #[error(transparent)]
+    Batch(#[from] RecordBatchError),
+}
+
+/// Any response body. Responses don't carry their api key, it comes from the
+/// request they answer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    Produce(ProduceResponse),
+    Fetch(FetchResponse),
+    ListOffsets(ListOffsetsResponse),
+    Metadata(MetadataResponse),
+    LeaderAndIsr(AdminResponse),
+    ReassignPartition(AdminResponse),
+    BrokerHeartbeat(AdminResponse),
+    DescribeCluster(DescribeClusterResponse),
+    DescribeLogDirs(DescribeLogDirsResponse),
+    OffsetCommit(AdminResponse),
+    ListGroups(ListGroupsResponse),
+    DescribeGroups(DescribeGroupsResponse),
+    JoinGroup(JoinGroupResponse),
+    SyncGroup(SyncGroupResponse),
+    Heartbeat(AdminResponse),
+    LeaveGroup(AdminResponse),
+    CreateTopic(AdminResponse),
+    DeleteTopic(AdminResponse),
+    EstimateCleanup(EstimateCleanupResponse),
+}
+
+impl Response {
+    pub fn api_key(&self) -> ApiKey {
+        match self {
+            Response::Produce(_) => ApiKey::Produce,
+            Response::Fetch(_) => ApiKey::Fetch,
+            Response::ListOffsets(_) => ApiKey::ListOffsets,
+            Response::Metadata(_) => ApiKey::Metadata,
+            Response::LeaderAndIsr(_) => ApiKey::LeaderAndIsr,
+            Response::ReassignPartition(_) => ApiKey::ReassignPartition,
+            Response::BrokerHeartbeat(_) => ApiKey::BrokerHeartbeat,
+            Response::DescribeCluster(_) => ApiKey::DescribeCluster,
+            Response::DescribeLogDirs(_) => ApiKey::DescribeLogDirs,
+            Response::OffsetCommit(_) => ApiKey::OffsetCommit,
+            Response::ListGroups(_) => ApiKey::ListGroups,
+            Response::DescribeGroups(_) => ApiKey::DescribeGroups,
+            Response::JoinGroup(_) => ApiKey::JoinGroup,
+            Response::SyncGroup(_) => ApiKey::SyncGroup,
+            Response::Heartbeat(_) => ApiKey::Heartbeat,
+            Response::LeaveGroup(_) => ApiKey::LeaveGroup,
+            Response::CreateTopic(_) => ApiKey::CreateTopic,
+            Response::DeleteTopic(_) => ApiKey::DeleteTopic,
+            Response::EstimateCleanup(_) => ApiKey::EstimateCleanup,
+        }
+    }
+
+    /// Decodes the body of a response to a request sent under `api_key`.
+    /// The response to a request for `api_key` that failed with `error`
+    /// before it could be handled.
+    pub fn error_for(api_key: ApiKey, error: ErrorCode) -> Self {
+        match api_key {
+            ApiKey::Produce => Response::Produce(ProduceResponse::error(error)),
+            ApiKey::Fetch => Response::Fetch(FetchResponse::error(error)),
+            ApiKey::ListOffsets => Response::ListOffsets(ListOffsetsResponse::error(error)),
+            ApiKey::Metadata => Response::Metadata(MetadataResponse::error(error)),
+            ApiKey::LeaderAndIsr => Response::LeaderAndIsr(AdminResponse::error(error)),
+            ApiKey::ReassignPartition => Response::ReassignPartition(AdminResponse::error(error)),
+            ApiKey::BrokerHeartbeat => Response::BrokerHeartbeat(AdminResponse::error(error)),
+            ApiKey::DescribeCluster => {
+                Response::DescribeCluster(DescribeClusterResponse::error(error))
+            }
+            ApiKey::DescribeLogDirs => {
+                Response::DescribeLogDirs(DescribeLogDirsResponse::error(error))
+            }
+            ApiKey::OffsetCommit => Response::OffsetCommit(AdminResponse::error(error)),
+            ApiKey::ListGroups => Response::ListGroups(ListGroupsResponse::error(error)),
+            ApiKey::DescribeGroups => {
+                Response::DescribeGroups(DescribeGroupsResponse::error(error))
+            }
+            ApiKey::JoinGroup => Response::JoinGroup(JoinGroupResponse::error(error)),
+            ApiKey::SyncGroup => Response::SyncGroup(SyncGroupResponse::error(error)),
+            ApiKey::Heartbeat => Response::Heartbeat(AdminResponse::error(error)),
+            ApiKey::LeaveGroup => Response::LeaveGroup(AdminResponse::error(error)),
+            ApiKey::CreateTopic => Response::CreateTopic(AdminResponse::error(error)),
+            ApiKey::DeleteTopic => Response::DeleteTopic(AdminResponse::error(error)),
+            ApiKey::EstimateCleanup => {
+                Response::EstimateCleanup(EstimateCleanupResponse::error(error))
+            }
+        }
+    }
+
+    pub fn decode(api_key: ApiKey, bytes: Bytes) -> Result<Self, ResponseError> {
+        Ok(match api_key {
+            ApiKey::Produce => Response::Produce(ProduceResponse::from_bytes(bytes)?),
+            ApiKey::Fetch => Response::Fetch(FetchResponse::from_bytes(bytes)?),
+            ApiKey::ListOffsets => Response::ListOffsets(ListOffsetsResponse::from_bytes(bytes)?),
+            ApiKey::Metadata => Response::Metadata(MetadataResponse::from_bytes(bytes)?),
+            ApiKey::LeaderAndIsr => Response::LeaderAndIsr(AdminResponse::from_bytes(bytes)?),
+            ApiKey::ReassignPartition => {
+                Response::ReassignPartition(AdminResponse::from_bytes(bytes)?)
+            }
+            ApiKey::BrokerHeartbeat => Response::BrokerHeartbeat(AdminResponse::from_bytes(bytes)?),
+            ApiKey::DescribeCluster => {
+                Response::DescribeCluster(DescribeClusterResponse::from_bytes(bytes)?)
+            }
+            ApiKey::DescribeLogDirs => {
+                Response::DescribeLogDirs(DescribeLogDirsResponse::from_bytes(bytes)?)
+            }
+            ApiKey::OffsetCommit => Response::OffsetCommit(AdminResponse::from_bytes(bytes)?),
+            ApiKey::ListGroups => Response::ListGroups(ListGroupsResponse::from_bytes(bytes)?),
+            ApiKey::DescribeGroups => {
+                Response::DescribeGroups(DescribeGroupsResponse::from_bytes(bytes)?)
+            }
+            ApiKey::JoinGroup => Response::JoinGroup(JoinGroupResponse::from_bytes(bytes)?),
+            ApiKey::SyncGroup => Response::SyncGroup(SyncGroupResponse::from_bytes(bytes)?),
+            ApiKey::Heartbeat => Response::Heartbeat(AdminResponse::from_bytes(bytes)?),
+            ApiKey::LeaveGroup => Response::LeaveGroup(AdminResponse::from_bytes(bytes)?),
+            ApiKey::CreateTopic => Response::CreateTopic(AdminResponse::from_bytes(bytes)?),
+            ApiKey::DeleteTopic => Response::DeleteTopic(AdminResponse::from_bytes(bytes)?),
+            ApiKey::EstimateCleanup => {
+                Response::EstimateCleanup(EstimateCleanupResponse::from_bytes(bytes)?)
+            }
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    /// Encodes the body, leaving out the bytes of any file slices, see
+    /// [`FetchResponse::encode_into`].
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        match self {
+            Response::Produce(produce) => produce.encode_into(buf),
+            Response::Fetch(fetch) => fetch.encode_into(buf),
+            Response::ListOffsets(list) => list.encode_into(buf),
+            Response::Metadata(metadata) => metadata.encode_into(buf),
+            Response::DescribeCluster(describe) => describe.encode_into(buf),
+            Response::DescribeLogDirs(describe) => describe.encode_into(buf),
+            Response::ListGroups(groups) => groups.encode_into(buf),
+            Response::DescribeGroups(groups) => groups.encode_into(buf),
+            Response::JoinGroup(join) => join.encode_into(buf),
+            Response::SyncGroup(sync) => sync.encode_into(buf),
+            Response::EstimateCleanup(estimate) => estimate.encode_into(buf),
+            Response::LeaderAndIsr(admin)
+            | Response::ReassignPartition(admin)
+            | Response::BrokerHeartbeat(admin)
+            | Response::OffsetCommit(admin)
+            | Response::Heartbeat(admin)
+            | Response::LeaveGroup(admin)
+            | Response::CreateTopic(admin)
+            | Response::DeleteTopic(admin) => admin.encode_into(buf),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Response::Produce(produce) => produce.size(),
+            Response::Fetch(fetch) => fetch.size(),
+            Response::ListOffsets(list) => list.size(),
+            Response::Metadata(metadata) => metadata.size(),
+            Response::DescribeCluster(describe) => describe.size(),
+            Response::DescribeLogDirs(describe) => describe.size(),
+            Response::ListGroups(groups) => groups.size(),
+            Response::DescribeGroups(groups) => groups.size(),
+            Response::JoinGroup(join) => join.size(),
+            Response::SyncGroup(sync) => sync.size(),
+            Response::EstimateCleanup(estimate) => estimate.size(),
+            Response::LeaderAndIsr(admin)
+            | Response::ReassignPartition(admin)
+            | Response::BrokerHeartbeat(admin)
+            | Response::OffsetCommit(admin)
+            | Response::Heartbeat(admin)
+            | Response::LeaveGroup(admin)
+            | Response::CreateTopic(admin)
+            | Response::DeleteTopic(admin) => admin.size(),
+        }
+    }
+
+    pub fn error(&self) -> ErrorCode {
+        match self {
+            Response::Produce(produce) => produce.error,
+            Response::Fetch(fetch) => fetch.error,
+            Response::ListOffsets(list) => list.error,
+            Response::Metadata(metadata) => metadata.error,
+            Response::DescribeCluster(describe) => describe.error,
+            Response::DescribeLogDirs(describe) => describe.error,
+            Response::ListGroups(groups) => groups.error,
+            Response::DescribeGroups(groups) => groups.error,
+            Response::JoinGroup(join) => join.error,
+            Response::SyncGroup(sync) => sync.error,
+            Response::EstimateCleanup(estimate) => estimate.error,
+            Response::LeaderAndIsr(admin)
+            | Response::ReassignPartition(admin)
+            | Response::BrokerHeartbeat(admin)
+            | Response::OffsetCommit(admin)
+            | Response::Heartbeat(admin)
+            | Response::LeaveGroup(admin)
+            | Response::CreateTopic(admin)
+            | Response::DeleteTopic(admin) => admin.error,
+        }
+    }
+
+    pub fn set_throttle_time_ms(&mut self, throttle_time_ms: u32) {
+        match self {
+            Response::Produce(produce) => produce.throttle_time_ms = throttle_time_ms,
+            Response::Fetch(fetch) => fetch.throttle_time_ms = throttle_time_ms,
+            Response::ListOffsets(list) => list.throttle_time_ms = throttle_time_ms,
+            Response::Metadata(metadata) => metadata.throttle_time_ms = throttle_time_ms,
+            Response::DescribeCluster(describe) => describe.throttle_time_ms = throttle_time_ms,
+            Response::DescribeLogDirs(describe) => describe.throttle_time_ms = throttle_time_ms,
+            Response::ListGroups(groups) => groups.throttle_time_ms = throttle_time_ms,
+            Response::DescribeGroups(groups) => groups.throttle_time_ms = throttle_time_ms,
+            Response::JoinGroup(join) => join.throttle_time_ms = throttle_time_ms,
+            Response::SyncGroup(sync) => sync.throttle_time_ms = throttle_time_ms,
+            Response::EstimateCleanup(estimate) => estimate.throttle_time_ms = throttle_time_ms,
+            Response::LeaderAndIsr(admin)
+            | Response::ReassignPartition(admin)
+            | Response::BrokerHeartbeat(admin)
+            | Response::OffsetCommit(admin)
+            | Response::Heartbeat(admin)
+            | Response::LeaveGroup(admin)
+            | Response::CreateTopic(admin)
+            | Response::DeleteTopic(admin) => admin.throttle_time_ms = throttle_time_ms,
+        }
+    }
+}
+
+impl From<ProduceResponse> for Response {
+    fn from(response: ProduceResponse) -> Self {
+        Response::Produce(response)
+    }
+}
+
+impl From<FetchResponse> for Response {
+    fn from(response: FetchResponse) -> Self {
+        Response::Fetch(response)
+    }
+}
+
+impl From<ListOffsetsResponse> for Response {
+    fn from(response: ListOffsetsResponse) -> Self {
+        Response::ListOffsets(response)
+    }
+}
+
+impl From<MetadataResponse> for Response {
+    fn from(response: MetadataResponse) -> Self {
+        Response::Metadata(response)
+    }
+}
+
+impl From<DescribeClusterResponse> for Response {
+    fn from(response: DescribeClusterResponse) -> Self {
+        Response::DescribeCluster(response)
+    }
+}
+
+impl From<DescribeLogDirsResponse> for Response {
+    fn from(response: DescribeLogDirsResponse) -> Self {
+        Response::DescribeLogDirs(response)
+    }
+}
+
+impl From<ListGroupsResponse> for Response {
+    fn from(response: ListGroupsResponse) -> Self {
+        Response::ListGroups(response)
+    }
+}
+
+impl From<DescribeGroupsResponse> for Response {
+    fn from(response: DescribeGroupsResponse) -> Self {
+        Response::DescribeGroups(response)
+    }
+}
+
+impl From<JoinGroupResponse> for Response {
+    fn from(response: JoinGroupResponse) -> Self {
+        Response::JoinGroup(response)
+    }
+}
+
+impl From<SyncGroupResponse> for Response {
+    fn from(response: SyncGroupResponse) -> Self {
+        Response::SyncGroup(response)
+    }
+}
+
+impl From<EstimateCleanupResponse> for Response {
+    fn from(response: EstimateCleanupResponse) -> Self {
+        Response::EstimateCleanup(response)
+    }
+}
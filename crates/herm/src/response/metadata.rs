@@ -0,0 +1,232 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use super::ResponseError;
+use crate::protocol::{get_str, put_str, str_size, ErrorCode};
+
+/// Answers a [`Metadata`](crate::request::Metadata) request with the live
+/// brokers and the partition leaders the answering broker knows of.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataResponse {
+    pub error: ErrorCode,
+    /// How long the broker held the response back for the client being over
+    /// its quota.
+    pub throttle_time_ms: u32,
+    pub brokers: Vec<BrokerMetadata>,
+    pub topics: Vec<TopicMetadata>,
+}
+
+/// A broker and the address clients reach it at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokerMetadata {
+    pub id: u32,
+    pub address: String,
+}
+
+/// The partitions of a topic with a live leader. A topic asked about that
+/// no live broker leads comes back with
+/// [`ErrorCode::UnknownTopicOrPartition`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopicMetadata {
+    pub error: ErrorCode,
+    pub topic: String,
+    pub partitions: Vec<PartitionMetadata>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartitionMetadata {
+    pub partition: u32,
+    /// Id of the leading broker, one of the response's brokers.
+    pub leader_id: u32,
+    pub leader_epoch: u32,
+}
+
+impl MetadataResponse {
+    pub fn new(brokers: Vec<BrokerMetadata>, topics: Vec<TopicMetadata>) -> Self {
+        MetadataResponse {
+            error: ErrorCode::None,
+            throttle_time_ms: 0,
+            brokers,
+            topics,
+        }
+    }
+
+    pub fn error(error: ErrorCode) -> Self {
+        MetadataResponse {
+            error,
+            ..Self::new(vec![], vec![])
+        }
+    }
+
+    /// The broker leading `partition` of `topic`, if known.
+    pub fn leader(&self, topic: &str, partition: u32) -> Option<&BrokerMetadata> {
+        let leader_id = self
+            .topics
+            .iter()
+            .find(|metadata| metadata.topic == topic)?
+            .partitions
+            .iter()
+            .find(|metadata| metadata.partition == partition)?
+            .leader_id;
+        self.brokers.iter().find(|broker| broker.id == leader_id)
+    }
+
+    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, ResponseError> {
+        if bytes.remaining() < 2 + 4 + 4 {
+            return Err(ResponseError::MalformedBytes);
+        }
+        let error = ErrorCode::from_code(bytes.get_i16());
+        let throttle_time_ms = bytes.get_u32();
+
+        let mut brokers = Vec::new();
+        for _ in 0..bytes.get_u32() {
+            if bytes.remaining() < 4 {
+                return Err(ResponseError::MalformedBytes);
+            }
+            let id = bytes.get_u32();
+            let address = get_str(&mut bytes).ok_or(ResponseError::MalformedBytes)?;
+            brokers.push(BrokerMetadata { id, address });
+        }
+
+        if bytes.remaining() < 4 {
+            return Err(ResponseError::MalformedBytes);
+        }
+        let mut topics = Vec::new();
+        for _ in 0..bytes.get_u32() {
+            if bytes.remaining() < 2 {
+                return Err(ResponseError::MalformedBytes);
+            }
+            let error = ErrorCode::from_code(bytes.get_i16());
+            let topic = get_str(&mut bytes).ok_or(ResponseError::MalformedBytes)?;
+            if bytes.remaining() < 4 {
+                return Err(ResponseError::MalformedBytes);
+            }
+            let mut partitions = Vec::new();
+            for _ in 0..bytes.get_u32() {
+                if bytes.remaining() < 4 + 4 + 4 {
+                    return Err(ResponseError::MalformedBytes);
+                }
+                partitions.push(PartitionMetadata {
+                    partition: bytes.get_u32(),
+                    leader_id: bytes.get_u32(),
+                    leader_epoch: bytes.get_u32(),
+                });
+            }
+            topics.push(TopicMetadata {
+                error,
+                topic,
+                partitions,
+            });
+        }
+        if bytes.has_remaining() {
+            return Err(ResponseError::MalformedBytes);
+        }
+
+        Ok(MetadataResponse {
+            error,
+            throttle_time_ms,
+            brokers,
+            topics,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_i16(self.error.code());
+        buf.put_u32(self.throttle_time_ms);
+        buf.put_u32(self.brokers.len() as u32);
+        for broker in &self.brokers {
+            buf.put_u32(broker.id);
+            put_str(buf, &broker.address);
+        }
+        buf.put_u32(self.topics.len() as u32);
+        for topic in &self.topics {
+            buf.put_i16(topic.error.code());
+            put_str(buf, &topic.topic);
+            buf.put_u32(topic.partitions.len() as u32);
+            for partition in &topic.partitions {
+                buf.put_u32(partition.partition);
+                buf.put_u32(partition.leader_id);
+                buf.put_u32(partition.leader_epoch);
+            }
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        let brokers: usize = self
+            .brokers
+            .iter()
+            .map(|broker| 4 + str_size(&broker.address))
+            .sum();
+        let topics: usize = self
+            .topics
+            .iter()
+            .map(|topic| 2 + str_size(&topic.topic) + 4 + topic.partitions.len() * (4 + 4 + 4))
+            .sum();
+        2 + 4 + 4 + brokers + 4 + topics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let response = MetadataResponse::new(
+            vec![
+                BrokerMetadata {
+                    id: 0,
+                    address: "broker-0:9092".to_string(),
+                },
+                BrokerMetadata {
+                    id: 1,
+                    address: "broker-1:9092".to_string(),
+                },
+            ],
+            vec![
+                TopicMetadata {
+                    error: ErrorCode::None,
+                    topic: "events".to_string(),
+                    partitions: vec![
+                        PartitionMetadata {
+                            partition: 0,
+                            leader_id: 1,
+                            leader_epoch: 2,
+                        },
+                        PartitionMetadata {
+                            partition: 1,
+                            leader_id: 0,
+                            leader_epoch: 0,
+                        },
+                    ],
+                },
+                TopicMetadata {
+                    error: ErrorCode::UnknownTopicOrPartition,
+                    topic: "logs".to_string(),
+                    partitions: vec![],
+                },
+            ],
+        );
+        let bytes = response.to_bytes();
+        assert_eq!(bytes.len(), response.size());
+        assert_eq!(MetadataResponse::from_bytes(bytes).unwrap(), response);
+
+        assert_eq!(
+            response.leader("events", 0).map(|broker| broker.id),
+            Some(1)
+        );
+        assert_eq!(response.leader("events", 2), None);
+        assert_eq!(response.leader("logs", 0), None);
+
+        let bytes = response.to_bytes();
+        assert_eq!(
+            MetadataResponse::from_bytes(bytes.slice(..bytes.len() - 1)),
+            Err(ResponseError::MalformedBytes)
+        );
+    }
+}
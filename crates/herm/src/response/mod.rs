@@ -0,0 +1,24 @@
+mod admin;
+mod cleanup;
+mod describe;
+mod fetch;
+mod group;
+mod list_offsets;
+mod message;
+mod metadata;
+mod produce;
+pub use admin::AdminResponse;
+pub use cleanup::{EstimateCleanupResponse, PartitionCleanupEstimate};
+pub use describe::{
+    BrokerDescription, DescribeClusterResponse, DescribeLogDirsResponse, LogDirDescription,
+    PartitionLogDescription,
+};
+pub use fetch::FetchResponse;
+pub use group::{
+    DescribeGroupsResponse, GroupDescription, GroupMember, GroupState, JoinGroupResponse,
+    ListGroupsResponse, MemberDescription, PartitionOffset, SyncGroupResponse, UNKNOWN_END_OFFSET,
+};
+pub use list_offsets::{ListOffsetsResponse, NO_OFFSET};
+pub use message::{Response, ResponseError};
+pub use metadata::{BrokerMetadata, MetadataResponse, PartitionMetadata, TopicMetadata};
+pub use produce::ProduceResponse;
@@ -0,0 +1,87 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use super::ResponseError;
+use crate::protocol::ErrorCode;
+
+/// Answers a [`Produce`](crate::request::Produce) with the offset given to
+/// the first record of the batch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProduceResponse {
+    pub error: ErrorCode,
+    /// How long the broker held the response back for the client being over
+    /// its quota.
+    pub throttle_time_ms: u32,
+    pub base_offset: u64,
+}
+
+impl ProduceResponse {
+    pub fn new(base_offset: u64) -> Self {
+        ProduceResponse {
+            error: ErrorCode::None,
+            throttle_time_ms: 0,
+            base_offset,
+        }
+    }
+
+    pub fn error(error: ErrorCode) -> Self {
+        ProduceResponse {
+            error,
+            throttle_time_ms: 0,
+            base_offset: 0,
+        }
+    }
+
+    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, ResponseError> {
+        if bytes.len() != 2 + 4 + 8 {
+            return Err(ResponseError::MalformedBytes);
+        }
+
+        Ok(ProduceResponse {
+            error: ErrorCode::from_code(bytes.get_i16()),
+            throttle_time_ms: bytes.get_u32(),
+            base_offset: bytes.get_u64(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.size());
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    pub fn encode_into(&self, buf: &mut impl BufMut) {
+        buf.put_i16(self.error.code());
+        buf.put_u32(self.throttle_time_ms);
+        buf.put_u64(self.base_offset);
+    }
+
+    pub fn size(&self) -> usize {
+        2 + 4 + 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let response = ProduceResponse::new(42);
+        assert_eq!(
+            ProduceResponse::from_bytes(response.to_bytes()).unwrap(),
+            response
+        );
+
+        let mut response = ProduceResponse::error(ErrorCode::UnknownTopicOrPartition);
+        response.throttle_time_ms = 250;
+        assert_eq!(
+            ProduceResponse::from_bytes(response.to_bytes()).unwrap(),
+            response
+        );
+
+        assert_eq!(
+            ProduceResponse::from_bytes(Bytes::from_static(&[0x00])),
+            Err(ResponseError::MalformedBytes)
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use std::fmt::Debug;
+use std::fs::File;
+use std::io;
+use std::sync::Arc;
+
+/// Where a log keeps its segment and index files. Files are addressed by
+/// flat names, like `00000000000000000000.log`, scoped to one log.
+///
+/// Files are only ever appended to, read at a position, cut short or
+/// replaced whole by a rename, which is all segments need.
+pub trait Backend: Send + Sync + Debug {
+    /// Creates `name` if it doesn't exist yet, returning its size.
+    fn open(&self, name: &str) -> io::Result<u64>;
+
+    /// Writes `data` at the end of `name`, creating it if needed.
+    fn append(&self, name: &str, data: &[u8]) -> io::Result<()>;
+
+    /// Fills `buf` with the bytes of `name` starting at `position`. Reading
+    /// past the end fails with [`io::ErrorKind::UnexpectedEof`].
+    fn read(&self, name: &str, position: u64, buf: &mut [u8]) -> io::Result<()>;
+
+    /// Cuts `name` down to `len` bytes.
+    fn truncate(&self, name: &str, len: u64) -> io::Result<()>;
+
+    /// Makes everything appended to `name` durable.
+    fn flush(&self, name: &str) -> io::Result<()>;
+
+    /// Names of every file held.
+    fn list(&self) -> io::Result<Vec<String>>;
+
+    /// Removes `name`. Removing a file that doesn't exist isn't an error.
+    fn remove(&self, name: &str) -> io::Result<()>;
+
+    /// Replaces `to` with `from`.
+    fn rename(&self, from: &str, to: &str) -> io::Result<()>;
+
+    /// An open handle on the file behind `name`, for backends that keep
+    /// files on disk, so sealed segments can be sent from it without being
+    /// read into memory. `None` for any other backend.
+    fn file_handle(&self, _name: &str) -> io::Result<Option<Arc<File>>> {
+        Ok(None)
+    }
+
+    /// Tells the backend `name` won't be appended to again, so it may serve
+    /// reads of it some faster way.
+    fn seal(&self, _name: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use thiserror::Error;
+
+use serde::Deserialize;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum TopicConfigError {
+    #[error("Unknown topic config {0}")]
+    Unknown(String),
+    #[error("Invalid value {value} for topic config {name}")]
+    InvalidValue { name: String, value: String },
+}
+
+/// How old data is cleaned up, set per topic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CleanupPolicy {
+    /// Delete whole segments past the retention limits.
+    #[default]
+    Delete,
+    /// Keep only the latest record for every key.
+    Compact,
+}
+
+/// When appended data is synced to disk. Anything not yet synced may be lost
+/// if the machine crashes, but syncing less often gives more throughput.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlushPolicy {
+    EveryWrite,
+    EveryNMessages(u64),
+    Interval(Duration),
+    /// Leave it to the OS, or to explicit [`Log::flush`](super::Log::flush)
+    /// calls.
+    #[default]
+    Never,
+}
+
+/// Per-log storage settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogConfig {
+    /// Roll to a new segment once the active one would grow past this size.
+    pub segment_bytes: u64,
+    /// Roll to a new segment once the first record of the active one is this
+    /// old.
+    pub segment_age: Duration,
+    /// Delete segments whose newest record is older than this.
+    pub retention_age: Option<Duration>,
+    /// Delete the oldest segments while the log is bigger than this.
+    pub retention_bytes: Option<u64>,
+    pub cleanup_policy: CleanupPolicy,
+    /// How long compaction keeps tombstones around, so consumers get the
+    /// chance to see deletes before they disappear.
+    pub tombstone_retention: Duration,
+    pub flush_policy: FlushPolicy,
+    /// Read sealed segments through memory maps rather than read syscalls.
+    /// Applies to logs opened with [`Log::open`](super::Log::open), other
+    /// backends decide for themselves.
+    #[cfg(feature = "mmap")]
+    pub mmap_reads: bool,
+    /// Do segment I/O through io_uring rather than plain syscalls. Like
+    /// `mmap_reads`, only applies to logs opened with [`Log::open`].
+    ///
+    /// [`Log::open`]: super::Log::open
+    #[cfg(feature = "io-uring")]
+    pub io_uring: bool,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            segment_bytes: 1024 * 1024 * 1024,
+            segment_age: Duration::from_secs(7 * 24 * 60 * 60),
+            retention_age: Some(Duration::from_secs(7 * 24 * 60 * 60)),
+            retention_bytes: None,
+            cleanup_policy: CleanupPolicy::Delete,
+            tombstone_retention: Duration::from_secs(24 * 60 * 60),
+            flush_policy: FlushPolicy::Never,
+            #[cfg(feature = "mmap")]
+            mmap_reads: false,
+            #[cfg(feature = "io-uring")]
+            io_uring: false,
+        }
+    }
+}
+
+impl LogConfig {
+    /// This config with the topic configs in `configs` applied over it, by
+    /// their Kafka names, such as `retention.ms` or `cleanup.policy`.
+    pub fn with_topic_configs(
+        &self,
+        configs: &HashMap<String, String>,
+    ) -> Result<LogConfig, TopicConfigError> {
+        let mut config = self.clone();
+        for (name, value) in configs {
+            let invalid = || TopicConfigError::InvalidValue {
+                name: name.clone(),
+                value: value.clone(),
+            };
+            let millis = || {
+                value
+                    .parse()
+                    .map(Duration::from_millis)
+                    .map_err(|_| invalid())
+            };
+            // -1 turns a retention limit off
+            let limit = || match value.parse::<i64>() {
+                Ok(-1) => Ok(None),
+                Ok(limit) if limit >= 0 => Ok(Some(limit as u64)),
+                _ => Err(invalid()),
+            };
+            match name.as_str() {
+                "segment.bytes" => {
+                    config.segment_bytes = value
+                        .parse()
+                        .ok()
+                        .filter(|&bytes| bytes > 0)
+                        .ok_or_else(invalid)?
+                }
+                "segment.ms" => config.segment_age = millis()?,
+                "retention.ms" => config.retention_age = limit()?.map(Duration::from_millis),
+                "retention.bytes" => config.retention_bytes = limit()?,
+                "cleanup.policy" => {
+                    config.cleanup_policy = match value.as_str() {
+                        "delete" => CleanupPolicy::Delete,
+                        "compact" => CleanupPolicy::Compact,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "delete.retention.ms" => config.tombstone_retention = millis()?,
+                _ => return Err(TopicConfigError::Unknown(name.clone())),
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_configs() {
+        let configs = HashMap::from([
+            ("retention.ms".to_string(), "-1".to_string()),
+            ("retention.bytes".to_string(), "4096".to_string()),
+            ("cleanup.policy".to_string(), "compact".to_string()),
+            ("segment.ms".to_string(), "1000".to_string()),
+        ]);
+        let config = LogConfig::default().with_topic_configs(&configs).unwrap();
+        assert_eq!(config.retention_age, None);
+        assert_eq!(config.retention_bytes, Some(4096));
+        assert_eq!(config.cleanup_policy, CleanupPolicy::Compact);
+        assert_eq!(config.segment_age, Duration::from_secs(1));
+        assert_eq!(config.segment_bytes, LogConfig::default().segment_bytes);
+
+        let unknown = HashMap::from([("max.message.bytes".to_string(), "1".to_string())]);
+        assert_eq!(
+            LogConfig::default().with_topic_configs(&unknown),
+            Err(TopicConfigError::Unknown("max.message.bytes".to_string()))
+        );
+        let invalid = HashMap::from([("segment.bytes".to_string(), "0".to_string())]);
+        assert_eq!(
+            LogConfig::default().with_topic_configs(&invalid),
+            Err(TopicConfigError::InvalidValue {
+                name: "segment.bytes".to_string(),
+                value: "0".to_string()
+            })
+        );
+    }
+}
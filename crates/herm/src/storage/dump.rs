@@ -0,0 +1,217 @@
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use thiserror::Error;
+
+use super::index::OffsetIndex;
+use super::segment::{ENTRY_HEADER_SIZE, INDEX_SUFFIX, LOG_SUFFIX, TIME_INDEX_SUFFIX};
+use super::time_index::TimeIndex;
+use crate::record::{crc32c, RecordBatch, RecordBatchError};
+
+#[derive(Error, Debug)]
+pub enum DumpError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("{0:?} isn't named like a segment, offset index or time index file")]
+    UnknownFile(PathBuf),
+}
+
+/// What [`dump_file`] read, depending on the kind of file.
+#[derive(Debug, PartialEq)]
+pub enum Dump {
+    Segment(SegmentDump),
+    /// Absolute offsets and the positions of their batches.
+    OffsetIndex(IndexDump),
+    /// Max timestamps and the positions of the batches that raised them.
+    TimeIndex(IndexDump),
+}
+
+/// Every entry of a segment file, intact or not.
+#[derive(Debug, PartialEq)]
+pub struct SegmentDump {
+    pub base_offset: u64,
+    pub entries: Vec<DumpedEntry>,
+    /// Bytes at the end too short to hold the entry they start, as a crash
+    /// mid-append leaves behind.
+    pub torn_bytes: usize,
+}
+
+/// A length and crc framed entry of a segment file.
+#[derive(Debug, PartialEq)]
+pub struct DumpedEntry {
+    /// Of the entry in the file.
+    pub position: u64,
+    /// Of the entry, its frame included.
+    pub size: usize,
+    /// Whether the frame's crc matches the batch bytes.
+    pub crc_valid: bool,
+    pub batch: Result<RecordBatch, RecordBatchError>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexDump {
+    pub base_offset: u64,
+    pub entries: Vec<(u64, u32)>,
+    /// Bytes at the end too short for an entry.
+    pub torn_bytes: usize,
+}
+
+impl SegmentDump {
+    /// Walks the entries of `bytes`, read from the segment starting at
+    /// `base_offset`. Entries that fail their crc or don't decode are
+    /// reported and skipped over, as long as their length is in bounds.
+    pub fn parse(base_offset: u64, bytes: &[u8]) -> Self {
+        let mut entries = vec![];
+        let mut position = 0;
+        while bytes.len() - position >= ENTRY_HEADER_SIZE {
+            let header = &bytes[position..position + ENTRY_HEADER_SIZE];
+            let len = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+            let crc = u32::from_be_bytes(header[4..8].try_into().unwrap());
+            let start = position + ENTRY_HEADER_SIZE;
+            if len > bytes.len() - start {
+                break;
+            }
+
+            let encoded = &bytes[start..start + len];
+            entries.push(DumpedEntry {
+                position: position as u64,
+                size: ENTRY_HEADER_SIZE + len,
+                crc_valid: crc32c(encoded) == crc,
+                batch: RecordBatch::from_bytes(Bytes::copy_from_slice(encoded)),
+            });
+            position = start + len;
+        }
+
+        SegmentDump {
+            base_offset,
+            entries,
+            torn_bytes: bytes.len() - position,
+        }
+    }
+}
+
+impl IndexDump {
+    pub fn parse_offsets(base_offset: u64, bytes: &[u8]) -> Self {
+        IndexDump {
+            base_offset,
+            entries: OffsetIndex::decode(bytes)
+                .into_iter()
+                .map(|(relative, position)| (base_offset + relative as u64, position))
+                .collect(),
+            torn_bytes: bytes.len() % OffsetIndex::ENTRY_SIZE,
+        }
+    }
+
+    pub fn parse_timestamps(base_offset: u64, bytes: &[u8]) -> Self {
+        IndexDump {
+            base_offset,
+            entries: TimeIndex::decode(bytes),
+            torn_bytes: bytes.len() % TimeIndex::ENTRY_SIZE,
+        }
+    }
+}
+
+/// Reads a segment, offset index or time index file, told apart by their
+/// names, without opening the log they belong to. Meant for looking into
+/// the files of a broker that isn't running.
+pub fn dump_file(path: &Path) -> Result<Dump, DumpError> {
+    let unknown = || DumpError::UnknownFile(path.to_path_buf());
+    let base_offset = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.parse::<u64>().ok())
+        .ok_or_else(unknown)?;
+    let suffix = path.extension().and_then(|ext| ext.to_str());
+    if !matches!(suffix, Some(LOG_SUFFIX | INDEX_SUFFIX | TIME_INDEX_SUFFIX)) {
+        return Err(unknown());
+    }
+
+    let bytes = std::fs::read(path)?;
+    Ok(match suffix {
+        Some(LOG_SUFFIX) => Dump::Segment(SegmentDump::parse(base_offset, &bytes)),
+        Some(INDEX_SUFFIX) => Dump::OffsetIndex(IndexDump::parse_offsets(base_offset, &bytes)),
+        _ => Dump::TimeIndex(IndexDump::parse_timestamps(base_offset, &bytes)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use super::*;
+    use crate::record::Record;
+    use crate::storage::segment::encode_entry;
+    use crate::storage::{Log, LogConfig};
+
+    fn batch(base_offset: u64, values: &[&'static str]) -> RecordBatch {
+        let mut batch = RecordBatch::new(
+            values
+                .iter()
+                .map(|value| Record::new(None, Some(Bytes::from_static(value.as_bytes()))))
+                .collect(),
+        );
+        batch.set_base_offset(base_offset);
+        batch
+    }
+
+    #[test]
+    fn test_parse_segment() {
+        let first = batch(10, &["a", "b"]);
+        let mut buf = BytesMut::new();
+        encode_entry(&first, &mut buf);
+        let corrupt_at = buf.len();
+        encode_entry(&batch(12, &["c"]), &mut buf);
+        encode_entry(&batch(13, &["d"]), &mut buf);
+        // Flip a byte of the second batch and tear the third
+        buf[corrupt_at + ENTRY_HEADER_SIZE + 20] ^= 0xff;
+        buf.truncate(buf.len() - 3);
+
+        let dump = SegmentDump::parse(10, &buf);
+        assert_eq!(dump.entries.len(), 2);
+        assert_eq!(dump.entries[0].position, 0);
+        assert!(dump.entries[0].crc_valid);
+        assert_eq!(dump.entries[0].batch, Ok(first));
+        assert_eq!(dump.entries[1].position, corrupt_at as u64);
+        assert!(!dump.entries[1].crc_valid);
+        assert!(dump.entries[1].batch.is_err());
+        assert_eq!(
+            dump.torn_bytes,
+            buf.len() - corrupt_at - dump.entries[1].size
+        );
+    }
+
+    #[test]
+    fn test_dump_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        log.append(batch(0, &["a", "b"])).unwrap();
+        log.append(batch(0, &["c"])).unwrap();
+        log.flush().unwrap();
+
+        let path = |suffix| dir.path().join(format!("{:020}.{}", 0, suffix));
+        let Dump::Segment(segment) = dump_file(&path(LOG_SUFFIX)).unwrap() else {
+            panic!("expected a segment");
+        };
+        let offsets: Vec<_> = segment
+            .entries
+            .iter()
+            .map(|entry| entry.batch.as_ref().unwrap().base_offset)
+            .collect();
+        assert_eq!(offsets, vec![0, 2]);
+        assert_eq!(segment.torn_bytes, 0);
+
+        let Dump::OffsetIndex(index) = dump_file(&path(INDEX_SUFFIX)).unwrap() else {
+            panic!("expected an offset index");
+        };
+        assert_eq!(index.entries[1], (2, segment.entries[1].position as u32));
+        assert!(matches!(
+            dump_file(&path(TIME_INDEX_SUFFIX)).unwrap(),
+            Dump::TimeIndex(_)
+        ));
+
+        assert!(matches!(
+            dump_file(&dir.path().join("leader-epoch-checkpoint")),
+            Err(DumpError::UnknownFile(_))
+        ));
+    }
+}
@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// Most of a slice held in memory at once while copying it out.
+const COPY_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A range of a file, passed along without being read into memory first.
+/// Holding one keeps the file open, so the range can still be read after its
+/// segment is deleted or compacted away.
+#[derive(Debug, Clone)]
+pub struct FileSlice {
+    file: Arc<File>,
+    position: u64,
+    len: u64,
+}
+
+impl FileSlice {
+    pub fn new(file: Arc<File>, position: u64, len: u64) -> Self {
+        FileSlice {
+            file,
+            position,
+            len,
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Reads the whole range into memory.
+    pub fn read(&self) -> io::Result<Bytes> {
+        let mut buf = vec![0; self.len as usize];
+        self.file.read_exact_at(&mut buf, self.position)?;
+        Ok(buf.into())
+    }
+
+    /// Writes the range to `writer` a chunk at a time, so a large slice never
+    /// takes more than a chunk of memory. Reading blocks the thread, like the
+    /// log's own reads.
+    pub async fn copy_to(&self, writer: &mut (impl AsyncWrite + Unpin)) -> io::Result<()> {
+        let mut chunk = vec![0; (self.len as usize).min(COPY_CHUNK_SIZE)];
+        let mut copied = 0;
+        while copied < self.len {
+            let len = ((self.len - copied) as usize).min(chunk.len());
+            self.file
+                .read_exact_at(&mut chunk[..len], self.position + copied)?;
+            writer.write_all(&chunk[..len]).await?;
+            copied += len as u64;
+        }
+        Ok(())
+    }
+}
+
+/// Slices are the same if they cover the same range of the same open file.
+impl PartialEq for FileSlice {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.file, &other.file)
+            && self.position == other.position
+            && self.len == other.len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_copy_to() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a");
+        let data: Vec<u8> = (0..COPY_CHUNK_SIZE * 2 + 10).map(|i| i as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+        let file = Arc::new(File::open(&path).unwrap());
+
+        let slice = FileSlice::new(file.clone(), 5, data.len() as u64 - 10);
+        let mut copied = Vec::new();
+        slice.copy_to(&mut copied).await.unwrap();
+        assert_eq!(copied, &data[5..data.len() - 5]);
+        assert_eq!(slice.read().unwrap(), &data[5..data.len() - 5]);
+
+        // Still readable once the file is gone
+        std::fs::remove_file(&path).unwrap();
+        let slice = FileSlice::new(file, 0, 3);
+        assert_eq!(slice.read().unwrap(), &data[..3]);
+
+        let past_end = FileSlice::new(slice.file.clone(), data.len() as u64, 1);
+        assert!(past_end.copy_to(&mut Vec::new()).await.is_err());
+    }
+}
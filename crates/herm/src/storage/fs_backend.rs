@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+#[cfg(feature = "io-uring")]
+use super::uring;
+use super::Backend;
+
+/// Keeps files in a directory on disk. Open handles are cached, so only the
+/// first access to a file pays for opening it.
+#[derive(Debug)]
+pub struct FsBackend {
+    dir: PathBuf,
+    files: RwLock<HashMap<String, Arc<FsFile>>>,
+    #[cfg(feature = "mmap")]
+    mmap_reads: bool,
+    #[cfg(feature = "io-uring")]
+    io_uring: bool,
+}
+
+#[derive(Debug)]
+struct FsFile {
+    file: Arc<File>,
+    #[cfg(feature = "mmap")]
+    mmap: Option<memmap2::Mmap>,
+}
+
+impl FsBackend {
+    /// Uses `dir`, creating it if it doesn't exist.
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        Ok(FsBackend {
+            dir,
+            files: RwLock::new(HashMap::new()),
+            #[cfg(feature = "mmap")]
+            mmap_reads: false,
+            #[cfg(feature = "io-uring")]
+            io_uring: false,
+        })
+    }
+
+    /// Serves reads of sealed files from memory maps instead of read
+    /// syscalls.
+    #[cfg(feature = "mmap")]
+    pub fn with_mmap_reads(mut self, mmap_reads: bool) -> Self {
+        self.mmap_reads = mmap_reads;
+        self
+    }
+
+    /// Appends, reads and flushes through io_uring instead of plain
+    /// syscalls. Reads of mapped files still come from the map.
+    #[cfg(feature = "io-uring")]
+    pub fn with_io_uring(mut self, io_uring: bool) -> Self {
+        self.io_uring = io_uring;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn file(&self, name: &str) -> io::Result<Arc<FsFile>> {
+        if let Some(file) = self.files.read().unwrap().get(name) {
+            return Ok(file.clone());
+        }
+
+        let mut files = self.files.write().unwrap();
+        if let Some(file) = files.get(name) {
+            return Ok(file.clone());
+        }
+        let file = Arc::new(FsFile {
+            file: Arc::new(
+                OpenOptions::new()
+                    .read(true)
+                    .append(true)
+                    .create(true)
+                    .open(self.dir.join(name))?,
+            ),
+            #[cfg(feature = "mmap")]
+            mmap: None,
+        });
+        files.insert(name.to_string(), file.clone());
+        Ok(file)
+    }
+
+    /// Drops the cached handle, and any map, of `name`.
+    fn forget(&self, name: &str) {
+        self.files.write().unwrap().remove(name);
+    }
+}
+
+impl Backend for FsBackend {
+    fn open(&self, name: &str) -> io::Result<u64> {
+        Ok(self.file(name)?.file.metadata()?.len())
+    }
+
+    fn append(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        let file = self.file(name)?;
+        #[cfg(feature = "io-uring")]
+        if self.io_uring {
+            return uring::append(&file.file, data);
+        }
+        (&*file.file).write_all(data)
+    }
+
+    fn read(&self, name: &str, position: u64, buf: &mut [u8]) -> io::Result<()> {
+        let file = self.file(name)?;
+
+        #[cfg(feature = "mmap")]
+        if let Some(mmap) = &file.mmap {
+            let start = position as usize;
+            let src = mmap.get(start..start + buf.len()).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::UnexpectedEof, "read past mapped file")
+            })?;
+            buf.copy_from_slice(src);
+            return Ok(());
+        }
+
+        #[cfg(feature = "io-uring")]
+        if self.io_uring {
+            return uring::read_exact_at(&file.file, buf, position);
+        }
+        file.file.read_exact_at(buf, position)
+    }
+
+    fn truncate(&self, name: &str, len: u64) -> io::Result<()> {
+        self.file(name)?.file.set_len(len)?;
+        // A map over the cut off part would fault on access
+        self.forget(name);
+        Ok(())
+    }
+
+    fn flush(&self, name: &str) -> io::Result<()> {
+        let file = self.file(name)?;
+        #[cfg(feature = "io-uring")]
+        if self.io_uring {
+            return uring::sync_data(&file.file);
+        }
+        file.file.sync_data()
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            if let Ok(name) = entry.file_name().into_string() {
+                names.push(name);
+            }
+        }
+        Ok(names)
+    }
+
+    fn remove(&self, name: &str) -> io::Result<()> {
+        self.forget(name);
+        match std::fs::remove_file(self.dir.join(name)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        self.forget(from);
+        self.forget(to);
+        std::fs::rename(self.dir.join(from), self.dir.join(to))
+    }
+
+    fn file_handle(&self, name: &str) -> io::Result<Option<Arc<File>>> {
+        Ok(Some(self.file(name)?.file.clone()))
+    }
+
+    #[cfg_attr(not(feature = "mmap"), allow(unused_variables))]
+    fn seal(&self, name: &str) -> io::Result<()> {
+        #[cfg(feature = "mmap")]
+        if self.mmap_reads {
+            let file = self.file(name)?.file.clone();
+            if file.metadata()?.len() > 0 {
+                // Safety: sealed files are never written again. Compaction
+                // renames a new file over them rather than changing them, and
+                // truncation drops the map first.
+                let mmap = unsafe { memmap2::Mmap::map(&*file)? };
+                self.files.write().unwrap().insert(
+                    name.to_string(),
+                    Arc::new(FsFile {
+                        file,
+                        mmap: Some(mmap),
+                    }),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = FsBackend::new(dir.path().join("log")).unwrap();
+        assert_eq!(backend.open("a").unwrap(), 0);
+        backend.append("a", b"hello world").unwrap();
+
+        let mut buf = [0u8; 5];
+        backend.read("a", 6, &mut buf).unwrap();
+        assert_eq!(&buf, b"world");
+
+        let handle = backend.file_handle("a").unwrap().unwrap();
+        let mut buf = [0u8; 5];
+        handle.read_exact_at(&mut buf, 0).unwrap();
+        assert_eq!(&buf, b"hello");
+
+        backend.truncate("a", 5).unwrap();
+        backend.rename("a", "b").unwrap();
+        assert_eq!(backend.list().unwrap(), vec!["b".to_string()]);
+        assert_eq!(
+            std::fs::read(backend.dir().join("b")).unwrap(),
+            b"hello".to_vec()
+        );
+
+        backend.remove("b").unwrap();
+        backend.remove("b").unwrap();
+        assert!(backend.list().unwrap().is_empty());
+    }
+
+    #[cfg(feature = "io-uring")]
+    #[test]
+    fn test_io_uring() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = FsBackend::new(dir.path()).unwrap().with_io_uring(true);
+        backend.append("a", b"hello world").unwrap();
+        backend.flush("a").unwrap();
+        assert_eq!(backend.open("a").unwrap(), 11);
+
+        let mut buf = [0u8; 5];
+        backend.read("a", 6, &mut buf).unwrap();
+        assert_eq!(&buf, b"world");
+        assert_eq!(
+            backend.read("a", 7, &mut buf).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        // Still appends at the end after being cut short
+        backend.truncate("a", 5).unwrap();
+        backend.append("a", b"!").unwrap();
+        assert_eq!(std::fs::read(dir.path().join("a")).unwrap(), b"hello!");
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_mmap_reads() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = FsBackend::new(dir.path()).unwrap().with_mmap_reads(true);
+        backend.append("a", b"hello world").unwrap();
+        backend.seal("a").unwrap();
+        assert!(backend.file("a").unwrap().mmap.is_some());
+
+        let mut buf = [0u8; 5];
+        backend.read("a", 6, &mut buf).unwrap();
+        assert_eq!(&buf, b"world");
+        assert_eq!(
+            backend.read("a", 7, &mut buf).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use super::Backend;
+
+/// Maps offsets, relative to the segment base offset, to the file position of
+/// the batch starting there. One entry is written per appended batch.
+#[derive(Debug)]
+pub(super) struct OffsetIndex {
+    backend: Arc<dyn Backend>,
+    name: String,
+    entries: Vec<(u32, u32)>,
+}
+
+impl OffsetIndex {
+    pub(super) const ENTRY_SIZE: usize = 4 + 4;
+
+    pub fn open(backend: Arc<dyn Backend>, name: String) -> std::io::Result<Self> {
+        let mut raw = vec![0u8; backend.open(&name)? as usize];
+        backend.read(&name, 0, &mut raw)?;
+        let entries = Self::decode(&raw);
+
+        Ok(OffsetIndex {
+            backend,
+            name,
+            entries,
+        })
+    }
+
+    /// Entries of an index file, ignoring a torn one at the end.
+    pub(super) fn decode(raw: &[u8]) -> Vec<(u32, u32)> {
+        raw.chunks_exact(Self::ENTRY_SIZE)
+            .map(|entry| {
+                (
+                    u32::from_be_bytes(entry[0..4].try_into().unwrap()),
+                    u32::from_be_bytes(entry[4..8].try_into().unwrap()),
+                )
+            })
+            .collect()
+    }
+
+    pub fn append(&mut self, relative_offset: u32, position: u32) -> std::io::Result<()> {
+        let mut entry = [0u8; Self::ENTRY_SIZE];
+        entry[0..4].copy_from_slice(&relative_offset.to_be_bytes());
+        entry[4..8].copy_from_slice(&position.to_be_bytes());
+        self.backend.append(&self.name, &entry)?;
+
+        self.entries.push((relative_offset, position));
+        Ok(())
+    }
+
+    /// Position of the last batch starting at or before `relative_offset`.
+    pub fn lookup(&self, relative_offset: u32) -> Option<u32> {
+        let idx = self
+            .entries
+            .partition_point(|(offset, _)| *offset <= relative_offset);
+        idx.checked_sub(1).map(|idx| self.entries[idx].1)
+    }
+
+    pub fn last(&self) -> Option<(u32, u32)> {
+        self.entries.last().copied()
+    }
+
+    pub fn flush(&self) -> std::io::Result<()> {
+        self.backend.flush(&self.name)
+    }
+
+    /// Drops every entry, on disk too.
+    pub fn clear(&mut self) -> std::io::Result<()> {
+        self.backend.truncate(&self.name, 0)?;
+        self.entries.clear();
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::storage::MemBackend;
+
+    #[test]
+    fn test_lookup() {
+        let mut index = OffsetIndex::open(Arc::new(MemBackend::new()), "0.index".into()).unwrap();
+        assert_eq!(index.lookup(0), None);
+
+        index.append(0, 0).unwrap();
+        index.append(5, 100).unwrap();
+        index.append(9, 250).unwrap();
+
+        assert_eq!(index.lookup(0), Some(0));
+        assert_eq!(index.lookup(4), Some(0));
+        assert_eq!(index.lookup(5), Some(100));
+        assert_eq!(index.lookup(8), Some(100));
+        assert_eq!(index.lookup(1000), Some(250));
+        assert_eq!(index.last(), Some((9, 250)));
+    }
+
+    #[test]
+    fn test_reopen() {
+        let backend = Arc::new(MemBackend::new());
+
+        let mut index = OffsetIndex::open(backend.clone(), "0.index".into()).unwrap();
+        index.append(0, 0).unwrap();
+        index.append(3, 42).unwrap();
+        drop(index);
+
+        let index = OffsetIndex::open(backend.clone(), "0.index".into()).unwrap();
+        assert_eq!(index.lookup(3), Some(42));
+        assert_eq!(index.last(), Some((3, 42)));
+    }
+}
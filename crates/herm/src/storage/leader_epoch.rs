@@ -0,0 +1,48 @@
+use std::io;
+
+use super::Backend;
+
+/// File holding a log's leader epoch, next to its segments.
+pub const LEADER_EPOCH_FILE: &str = "leader-epoch";
+
+/// Written out in full, then renamed over [`LEADER_EPOCH_FILE`], so a crash
+/// never leaves a torn epoch behind.
+const TMP_SUFFIX: &str = ".tmp";
+
+/// Reads the stored epoch, 0 for a log that never had one.
+pub fn load(backend: &dyn Backend) -> io::Result<u32> {
+    // Opening would create it
+    if !backend.list()?.iter().any(|name| name == LEADER_EPOCH_FILE) {
+        return Ok(0);
+    }
+    let mut buf = [0u8; 4];
+    backend.read(LEADER_EPOCH_FILE, 0, &mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+pub fn store(backend: &dyn Backend, epoch: u32) -> io::Result<()> {
+    let tmp = format!("{}{}", LEADER_EPOCH_FILE, TMP_SUFFIX);
+    backend.remove(&tmp)?;
+    backend.append(&tmp, &epoch.to_be_bytes())?;
+    backend.flush(&tmp)?;
+    backend.rename(&tmp, LEADER_EPOCH_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::storage::MemBackend;
+
+    #[test]
+    fn test_store_and_load() {
+        let backend = MemBackend::new();
+        assert_eq!(load(&backend).unwrap(), 0);
+
+        store(&backend, 3).unwrap();
+        assert_eq!(load(&backend).unwrap(), 3);
+        store(&backend, 7).unwrap();
+        assert_eq!(load(&backend).unwrap(), 7);
+        assert_eq!(backend.list().unwrap(), vec![LEADER_EPOCH_FILE.to_string()]);
+    }
+}
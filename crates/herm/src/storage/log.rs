@@ -0,0 +1,1546 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use thiserror::Error;
+
+use bytes::Bytes;
+use tokio::sync::watch;
+
+use super::leader_epoch;
+use super::segment::{entry_size, Segment, LOG_SUFFIX};
+use super::{Backend, CleanupPolicy, FileSlice, FlushPolicy, FsBackend, LogConfig};
+use crate::events::{Event, EventBus};
+use crate::protocol::TopicPartition;
+use crate::record::{now_ms, Record, RecordBatch, RecordBatchError};
+
+#[derive(Error, Debug)]
+pub enum LogError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Batch(#[from] RecordBatchError),
+    #[error("Offset index points past the end of the segment")]
+    CorruptIndex,
+    #[error("Corrupt entry at position {position} of segment {base_offset}")]
+    CorruptSegment { base_offset: u64, position: u64 },
+    #[error("Cannot append an empty batch")]
+    EmptyBatch,
+    #[error("Offset {offset} is outside of the log range [{start}, {end}]")]
+    OffsetOutOfRange { offset: u64, start: u64, end: u64 },
+    #[error("Batch at offset {offset} is behind the log end offset {end}")]
+    OffsetBehindEnd { offset: u64, end: u64 },
+    #[error("Leader epoch {epoch} is older than the current {current}")]
+    FencedLeaderEpoch { epoch: u32, current: u32 },
+    #[error("Leader epoch {epoch} is newer than the current {current}")]
+    UnknownLeaderEpoch { epoch: u32, current: u32 },
+}
+
+/// Outcome of a [`Log::compact`] pass.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompactionStats {
+    pub segments: usize,
+    pub records_removed: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+/// What a cleanup would remove, see [`Log::estimate_cleanup`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CleanupEstimate {
+    /// Segments that would be deleted or rewritten.
+    pub segments: usize,
+    pub records_removed: usize,
+    pub reclaimable_bytes: u64,
+}
+
+/// Which records compaction keeps, see [`Log::compact`].
+struct CompactionFilter {
+    /// Offset of the latest record of each key.
+    latest: HashMap<Bytes, u64>,
+    tombstone_cutoff: u64,
+    now: u64,
+}
+
+impl CompactionFilter {
+    fn new(segments: &[Segment], config: &LogConfig, now: u64) -> Result<Self, LogError> {
+        let mut latest = HashMap::new();
+        for segment in segments {
+            segment.for_each_batch(|batch| {
+                for record in batch.records {
+                    if let Some(key) = record.key {
+                        latest.insert(key, record.offset);
+                    }
+                }
+            })?;
+        }
+        Ok(CompactionFilter {
+            latest,
+            tombstone_cutoff: now.saturating_sub(config.tombstone_retention.as_millis() as u64),
+            now,
+        })
+    }
+
+    fn keeps(&self, record: &Record) -> bool {
+        !record.is_expired(self.now)
+            && match &record.key {
+                None => true,
+                Some(key) => {
+                    self.latest.get(key) == Some(&record.offset)
+                        && !(record.is_tombstone() && record.timestamp < self.tombstone_cutoff)
+                }
+            }
+    }
+}
+
+/// Append-only log of record batches for one partition, stored as segment
+/// files in a [`Backend`]. Offsets are assigned on append and increase by one
+/// for every record.
+///
+/// The log also keeps the partition's leader epoch, which goes up every time
+/// leadership moves. Requests made with another epoch are refused, see
+/// [`check_leader_epoch`](Log::check_leader_epoch).
+#[derive(Debug)]
+pub struct Log {
+    backend: Arc<dyn Backend>,
+    config: LogConfig,
+    leader_epoch: u32,
+    segments: Vec<Segment>,
+    events: Option<(TopicPartition, EventBus)>,
+    flushed_offset: watch::Sender<u64>,
+    unflushed_messages: u64,
+    last_flush: Instant,
+}
+
+impl Log {
+    /// Opens the log in `dir`, creating it if it doesn't exist. Any partially
+    /// written tail left by a crash is truncated.
+    pub fn open(dir: impl AsRef<Path>, config: LogConfig) -> Result<Self, LogError> {
+        let backend = FsBackend::new(dir)?;
+        #[cfg(feature = "mmap")]
+        let backend = backend.with_mmap_reads(config.mmap_reads);
+        #[cfg(feature = "io-uring")]
+        let backend = backend.with_io_uring(config.io_uring);
+        Self::with_backend(Arc::new(backend), config)
+    }
+
+    /// Opens the log kept in `backend`, like [`Log::open`].
+    pub fn with_backend(backend: Arc<dyn Backend>, config: LogConfig) -> Result<Self, LogError> {
+        let mut base_offsets: Vec<u64> = backend
+            .list()?
+            .iter()
+            .filter_map(|name| name.strip_suffix(LOG_SUFFIX)?.strip_suffix('.'))
+            .filter_map(|stem| stem.parse().ok())
+            .collect();
+        base_offsets.sort_unstable();
+
+        if base_offsets.is_empty() {
+            base_offsets.push(0);
+        }
+
+        // Only the active segment is ever written to, so it is the only one a
+        // crash can leave torn
+        let active = base_offsets.pop().unwrap();
+        let mut segments = base_offsets
+            .into_iter()
+            .map(|base_offset| {
+                let segment = Segment::open(backend.clone(), base_offset)?;
+                segment.seal()?;
+                Ok(segment)
+            })
+            .collect::<Result<Vec<_>, LogError>>()?;
+        segments.push(Segment::recover(backend.clone(), active)?.0);
+
+        // What survived recovery is as good as flushed
+        let flushed_offset = segments.last().unwrap().next_offset();
+        let leader_epoch = leader_epoch::load(&*backend)?;
+
+        Ok(Log {
+            backend,
+            config,
+            leader_epoch,
+            segments,
+            events: None,
+            flushed_offset: watch::Sender::new(flushed_offset),
+            unflushed_messages: 0,
+            last_flush: Instant::now(),
+        })
+    }
+
+    /// Publishes segment rolls of this log, as `partition`, to `bus`.
+    pub fn publish_events(&mut self, partition: TopicPartition, bus: EventBus) {
+        self.events = Some((partition, bus));
+    }
+
+    pub fn config(&self) -> &LogConfig {
+        &self.config
+    }
+
+    /// Replaces the config, applying from the next append or cleanup on.
+    pub fn set_config(&mut self, config: LogConfig) {
+        self.config = config;
+    }
+
+    pub fn backend(&self) -> &Arc<dyn Backend> {
+        &self.backend
+    }
+
+    /// First offset still held by the log.
+    pub fn start_offset(&self) -> u64 {
+        self.segments[0].base_offset()
+    }
+
+    /// Offset the next appended record gets, also known as the log end offset.
+    pub fn next_offset(&self) -> u64 {
+        self.active_segment().next_offset()
+    }
+
+    pub fn leader_epoch(&self) -> u32 {
+        self.leader_epoch
+    }
+
+    /// Moves the log to `epoch`, kept across restarts. Going back to an older
+    /// epoch fails with [`LogError::FencedLeaderEpoch`].
+    pub fn set_leader_epoch(&mut self, epoch: u32) -> Result<(), LogError> {
+        if epoch < self.leader_epoch {
+            return Err(LogError::FencedLeaderEpoch {
+                epoch,
+                current: self.leader_epoch,
+            });
+        }
+        if epoch > self.leader_epoch {
+            leader_epoch::store(&*self.backend, epoch)?;
+            self.leader_epoch = epoch;
+        }
+        Ok(())
+    }
+
+    /// Checks a request's leader epoch against the log's. An older one comes
+    /// from a client or follower that missed a leadership change, a newer one
+    /// means this broker missed it. Requests without an epoch aren't checked.
+    pub fn check_leader_epoch(&self, epoch: Option<u32>) -> Result<(), LogError> {
+        let current = self.leader_epoch;
+        match epoch {
+            Some(epoch) if epoch < current => Err(LogError::FencedLeaderEpoch { epoch, current }),
+            Some(epoch) if epoch > current => Err(LogError::UnknownLeaderEpoch { epoch, current }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Total bytes of all segment files.
+    pub fn size(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.size()).sum()
+    }
+
+    /// Appends `batch`, returning the offset assigned to its first record.
+    pub fn append(&mut self, mut batch: RecordBatch) -> Result<u64, LogError> {
+        if batch.records.is_empty() {
+            return Err(LogError::EmptyBatch);
+        }
+
+        let base_offset = self.next_offset();
+        batch.set_base_offset(base_offset);
+        if batch
+            .records
+            .iter()
+            .any(|record| record.expires_at().is_some())
+        {
+            batch.attributes |= RecordBatch::EXPIRING;
+        }
+        self.append_batch(batch)?;
+        Ok(base_offset)
+    }
+
+    /// Appends `batch` with the offsets it already has, as followers do with
+    /// batches copied from the leader. Offsets may skip ahead where the
+    /// leader compacted records away, but never go back.
+    pub fn append_replicated(&mut self, batch: RecordBatch) -> Result<(), LogError> {
+        if batch.records.is_empty() {
+            return Err(LogError::EmptyBatch);
+        }
+        let end = self.next_offset();
+        if batch.base_offset < end {
+            return Err(LogError::OffsetBehindEnd {
+                offset: batch.base_offset,
+                end,
+            });
+        }
+        self.append_batch(batch)
+    }
+
+    fn append_batch(&mut self, batch: RecordBatch) -> Result<(), LogError> {
+        if self.should_roll(&batch) {
+            self.roll()?;
+        }
+        self.active_segment_mut().append(&batch)?;
+
+        self.unflushed_messages += batch.records.len() as u64;
+        self.maybe_flush()
+    }
+
+    /// Syncs everything appended so far to disk.
+    pub fn flush(&mut self) -> Result<(), LogError> {
+        let flushed_offset = self.flushed_offset();
+        if flushed_offset < self.next_offset() {
+            // Rolls leave earlier segments unflushed too
+            for segment in self.segments.iter().rev() {
+                if segment.next_offset() <= flushed_offset {
+                    break;
+                }
+                segment.flush()?;
+            }
+        }
+
+        self.flushed_offset.send_replace(self.next_offset());
+        self.unflushed_messages = 0;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    /// Flushes if the [`FlushPolicy`] says it's time. Called after every
+    /// append, and meant to be called periodically for interval policies.
+    pub fn maybe_flush(&mut self) -> Result<(), LogError> {
+        let due = match self.config.flush_policy {
+            FlushPolicy::EveryWrite => true,
+            FlushPolicy::EveryNMessages(n) => self.unflushed_messages >= n,
+            FlushPolicy::Interval(interval) => self.last_flush.elapsed() >= interval,
+            FlushPolicy::Never => false,
+        };
+
+        if due && self.unflushed_messages > 0 {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Offset up to which the log is known to be on disk.
+    pub fn flushed_offset(&self) -> u64 {
+        *self.flushed_offset.borrow()
+    }
+
+    /// Follows [`flushed_offset`](Log::flushed_offset) as flushes happen,
+    /// whichever thread runs them.
+    pub fn watch_flushed(&self) -> watch::Receiver<u64> {
+        self.flushed_offset.subscribe()
+    }
+
+    /// Earliest offset of a record with a timestamp at or after `timestamp`,
+    /// or `None` if every record is older.
+    pub fn offset_for_timestamp(&self, timestamp: u64) -> Result<Option<u64>, LogError> {
+        for segment in &self.segments {
+            if matches!(segment.max_timestamp(), Some(max) if max >= timestamp) {
+                if let Some(offset) = segment.offset_for_timestamp(timestamp)? {
+                    return Ok(Some(offset));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Cleans up old data as the configured [`CleanupPolicy`] says.
+    pub fn cleanup(&mut self) -> Result<(), LogError> {
+        match self.config.cleanup_policy {
+            CleanupPolicy::Delete => {
+                self.enforce_retention()?;
+                self.remove_expired(now_ms()).map(|_| ())
+            }
+            CleanupPolicy::Compact => self.compact().map(|_| ()),
+        }
+    }
+
+    /// Rewrites every segment but the active one, keeping only the latest
+    /// record for each key. Tombstones are kept until they are older than
+    /// `tombstone_retention`. Records without a key are always kept, and
+    /// expired records never are.
+    pub fn compact(&mut self) -> Result<CompactionStats, LogError> {
+        let filter = CompactionFilter::new(&self.segments, &self.config, now_ms())?;
+        let mut stats = CompactionStats::default();
+
+        let last = self.segments.len() - 1;
+        for segment in &mut self.segments[..last] {
+            let bytes_before = segment.size();
+            let mut removed = 0;
+            // Stays in place, as it was, if this fails
+            segment.rewrite(|record| {
+                let keep = filter.keeps(record);
+                removed += !keep as usize;
+                keep
+            })?;
+            segment.seal()?;
+
+            stats.segments += 1;
+            stats.records_removed += removed;
+            stats.bytes_before += bytes_before;
+            stats.bytes_after += segment.size();
+        }
+        Ok(stats)
+    }
+
+    /// Deletes whole segments that fall outside the retention limits,
+    /// advancing the start offset. The active segment is always kept.
+    /// Returns the number of segments deleted.
+    pub fn enforce_retention(&mut self) -> Result<usize, LogError> {
+        let deleted = self.past_retention(&self.config, now_ms());
+        for _ in 0..deleted {
+            self.segments.remove(0).delete()?;
+        }
+        Ok(deleted)
+    }
+
+    /// How many of the oldest segments fall outside the retention limits of
+    /// `config` at `now`. Never the active one.
+    fn past_retention(&self, config: &LogConfig, now: u64) -> usize {
+        let mut size = self.size();
+        let mut deleted = 0;
+        for oldest in &self.segments[..self.segments.len() - 1] {
+            let expired = matches!(
+                (config.retention_age, oldest.max_timestamp()),
+                (Some(age), Some(max)) if now.saturating_sub(max) > age.as_millis() as u64
+            );
+            let oversized = matches!(
+                config.retention_bytes,
+                Some(limit) if size - oldest.size() >= limit
+            );
+            if !expired && !oversized {
+                break;
+            }
+            size -= oldest.size();
+            deleted += 1;
+        }
+        deleted
+    }
+
+    /// Estimates what [`cleanup`](Log::cleanup) would remove were the log
+    /// configured with `config`, without changing anything. Reads every
+    /// segment but the active one.
+    pub fn estimate_cleanup(&self, config: &LogConfig) -> Result<CleanupEstimate, LogError> {
+        let now = now_ms();
+        let sealed = &self.segments[..self.segments.len() - 1];
+        let mut estimate = CleanupEstimate::default();
+        let mut rewrite = |segment: &Segment, keep: &dyn Fn(&Record) -> bool| {
+            let (removed, size) = segment.rewritten(keep)?;
+            if removed > 0 {
+                estimate.segments += 1;
+                estimate.records_removed += removed;
+                estimate.reclaimable_bytes += segment.size() - size;
+            }
+            Ok::<_, LogError>(())
+        };
+
+        match config.cleanup_policy {
+            CleanupPolicy::Delete => {
+                let deleted = self.past_retention(config, now);
+                for segment in &sealed[..deleted] {
+                    rewrite(segment, &|_| false)?;
+                }
+                for segment in &sealed[deleted..] {
+                    if segment.has_expiring()? {
+                        rewrite(segment, &|record| !record.is_expired(now))?;
+                    }
+                }
+            }
+            CleanupPolicy::Compact => {
+                let filter = CompactionFilter::new(&self.segments, config, now)?;
+                for segment in sealed {
+                    rewrite(segment, &|record| filter.keeps(record))?;
+                }
+            }
+        }
+        Ok(estimate)
+    }
+
+    /// Rewrites the segments but the active one that hold records expired
+    /// by `now` without them. Returns the number of records removed.
+    pub fn remove_expired(&mut self, now: u64) -> Result<usize, LogError> {
+        let mut removed = 0;
+        for i in 0..self.segments.len() - 1 {
+            let segment = &self.segments[i];
+            if !segment.has_expiring()? {
+                continue;
+            }
+            let mut expired = false;
+            segment.for_each_batch(|batch| {
+                expired |= batch.is_expiring()
+                    && batch.records.iter().any(|record| record.is_expired(now));
+            })?;
+            if !expired {
+                continue;
+            }
+
+            // Stays in place, as it was, if this fails
+            let segment = &mut self.segments[i];
+            let mut expired = 0;
+            segment.rewrite(|record| {
+                let keep = !record.is_expired(now);
+                expired += !keep as usize;
+                keep
+            })?;
+            segment.seal()?;
+            removed += expired;
+        }
+        Ok(removed)
+    }
+
+    /// Starts a new active segment at the log end offset.
+    pub fn roll(&mut self) -> Result<(), LogError> {
+        let base_offset = self.next_offset();
+        if self.active_segment().base_offset() == base_offset {
+            return Ok(());
+        }
+
+        let segment = Segment::open(self.backend.clone(), base_offset)?;
+        self.active_segment().seal()?;
+        self.segments.push(segment);
+
+        if let Some((partition, bus)) = &self.events {
+            bus.publish(Event::SegmentRolled {
+                partition: partition.clone(),
+                base_offset,
+            });
+        }
+        Ok(())
+    }
+
+    /// Reads batches from `offset` onwards, up to about `max_bytes`. Reading
+    /// at the log end offset returns nothing.
+    pub fn read(&self, offset: u64, max_bytes: usize) -> Result<Vec<RecordBatch>, LogError> {
+        let (start, end) = (self.start_offset(), self.next_offset());
+        if offset < start || offset > end {
+            return Err(LogError::OffsetOutOfRange { offset, start, end });
+        }
+
+        let first = self
+            .segments
+            .partition_point(|segment| segment.base_offset() <= offset)
+            - 1;
+
+        for segment in &self.segments[first..] {
+            let batches = segment.read(offset.max(segment.base_offset()), max_bytes)?;
+            if !batches.is_empty() {
+                return Ok(batches);
+            }
+        }
+        Ok(vec![])
+    }
+
+    /// Like [`read`](Log::read) up to `max_offset`, but with records expired
+    /// by `now` left out.
+    pub fn read_unexpired(
+        &self,
+        offset: u64,
+        max_bytes: usize,
+        max_offset: u64,
+        now: u64,
+    ) -> Result<Vec<RecordBatch>, LogError> {
+        self.read_mapped(offset, max_bytes, max_offset, |mut batch| {
+            batch.retain_unexpired(now);
+            batch
+        })
+    }
+
+    /// Like [`read`](Log::read) up to `max_offset`, with the batches passed
+    /// through `map`. Reads on past batches `map` leaves with nothing from
+    /// `offset` on, so a run of records left out doesn't look like the end
+    /// of the log.
+    pub fn read_mapped(
+        &self,
+        mut offset: u64,
+        max_bytes: usize,
+        max_offset: u64,
+        mut map: impl FnMut(RecordBatch) -> RecordBatch,
+    ) -> Result<Vec<RecordBatch>, LogError> {
+        loop {
+            let mut batches = self.read(offset, max_bytes)?;
+            batches.retain(|batch| batch.next_offset() <= max_offset);
+            let Some(next) = batches.last().map(RecordBatch::next_offset) else {
+                return Ok(vec![]);
+            };
+
+            // The first batch can start before the offset, it only counts
+            // with records left from there on
+            let batches: Vec<_> = batches
+                .into_iter()
+                .map(&mut map)
+                .filter(|batch| {
+                    batch
+                        .records
+                        .last()
+                        .is_some_and(|last| last.offset >= offset)
+                })
+                .collect();
+            if !batches.is_empty() || next <= offset {
+                return Ok(batches);
+            }
+            offset = next;
+        }
+    }
+
+    /// Whether the segment holding `offset` has records with an expiry,
+    /// which [`read_slices`](Log::read_slices) would hand out unfiltered.
+    pub fn may_expire(&self, offset: u64) -> Result<bool, LogError> {
+        let index = self
+            .segments
+            .partition_point(|segment| segment.base_offset() <= offset)
+            .max(1)
+            - 1;
+        self.segments[index].has_expiring()
+    }
+
+    /// Like [`read`](Log::read), but for offsets in sealed segments returns
+    /// where the batches are in the segment file instead of reading them.
+    /// Batches ending past `max_offset` are left out. `None` when the batches
+    /// have to be read, because `offset` is in the active segment or the
+    /// backend keeps no files.
+    pub fn read_slices(
+        &self,
+        offset: u64,
+        max_bytes: usize,
+        max_offset: u64,
+    ) -> Result<Option<Vec<FileSlice>>, LogError> {
+        let (start, end) = (self.start_offset(), self.next_offset());
+        if offset < start || offset > end {
+            return Err(LogError::OffsetOutOfRange { offset, start, end });
+        }
+
+        let index = self
+            .segments
+            .partition_point(|segment| segment.base_offset() <= offset)
+            - 1;
+        if index == self.segments.len() - 1 {
+            return Ok(None);
+        }
+        let segment = &self.segments[index];
+        // Nothing left past `offset` in this segment, left to `read` to look
+        // further along
+        Ok(segment
+            .read_slices(offset.max(segment.base_offset()), max_bytes, max_offset)?
+            .filter(|slices| !slices.is_empty()))
+    }
+
+    fn should_roll(&self, batch: &RecordBatch) -> bool {
+        let active = self.active_segment();
+        let Some(first_timestamp) = active.first_timestamp() else {
+            return false;
+        };
+
+        let too_big = active.size() + entry_size(batch) as u64 > self.config.segment_bytes;
+        let too_old =
+            now_ms().saturating_sub(first_timestamp) >= self.config.segment_age.as_millis() as u64;
+        // The offset index stores offsets relative to the segment
+        let too_many = batch.last_offset() - active.base_offset() > u32::MAX as u64;
+
+        too_big || too_old || too_many
+    }
+
+    fn active_segment(&self) -> &Segment {
+        self.segments.last().unwrap()
+    }
+
+    fn active_segment_mut(&mut self) -> &mut Segment {
+        self.segments.last_mut().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    use bytes::Bytes;
+
+    use crate::record::Record;
+    use crate::storage::segment::{encode_entry, segment_name, INDEX_SUFFIX};
+    use crate::storage::MemBackend;
+
+    fn batch(values: &[&'static str]) -> RecordBatch {
+        RecordBatch::new(
+            values
+                .iter()
+                .map(|value| Record::new(None, Some(Bytes::from_static(value.as_bytes()))))
+                .collect(),
+        )
+    }
+
+    fn values(batches: &[RecordBatch]) -> Vec<Bytes> {
+        batches
+            .iter()
+            .flat_map(|batch| batch.records.iter())
+            .map(|record| record.value.clone().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_append_assigns_offsets() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        assert_eq!(log.start_offset(), 0);
+        assert_eq!(log.next_offset(), 0);
+
+        assert_eq!(log.append(batch(&["a", "b"])).unwrap(), 0);
+        assert_eq!(log.append(batch(&["c"])).unwrap(), 2);
+        assert_eq!(log.next_offset(), 3);
+
+        let batches = log.read(0, usize::MAX).unwrap();
+        assert_eq!(values(&batches), vec!["a", "b", "c"]);
+        assert_eq!(
+            batches
+                .iter()
+                .flat_map(|batch| batch.records.iter())
+                .map(|record| record.offset)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn test_read_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        log.append(batch(&["a", "b"])).unwrap();
+        log.append(batch(&["c"])).unwrap();
+
+        assert_eq!(values(&log.read(2, usize::MAX).unwrap()), vec!["c"]);
+        assert!(log.read(3, usize::MAX).unwrap().is_empty());
+        assert!(matches!(
+            log.read(4, usize::MAX),
+            Err(LogError::OffsetOutOfRange {
+                offset: 4,
+                start: 0,
+                end: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn test_empty_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        assert!(matches!(
+            log.append(RecordBatch::new(vec![])),
+            Err(LogError::EmptyBatch)
+        ));
+    }
+
+    #[test]
+    fn test_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        log.append(batch(&["a", "b"])).unwrap();
+        log.append(batch(&["c"])).unwrap();
+        let size = log.size();
+        drop(log);
+
+        let mut log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        assert_eq!(log.next_offset(), 3);
+        assert_eq!(log.size(), size);
+        assert_eq!(log.append(batch(&["d"])).unwrap(), 3);
+        assert_eq!(
+            values(&log.read(0, usize::MAX).unwrap()),
+            vec!["a", "b", "c", "d"]
+        );
+    }
+
+    #[test]
+    fn test_append_replicated() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        let at = |base_offset, values| {
+            let mut batch = batch(values);
+            batch.set_base_offset(base_offset);
+            batch
+        };
+
+        log.append_replicated(at(0, &["a", "b"])).unwrap();
+        // Compacted away on the leader
+        log.append_replicated(at(5, &["c"])).unwrap();
+        assert_eq!(log.next_offset(), 6);
+        assert_eq!(log.append(batch(&["d"])).unwrap(), 6);
+        assert_eq!(
+            values(&log.read(0, usize::MAX).unwrap()),
+            vec!["a", "b", "c", "d"]
+        );
+        assert_eq!(log.read(5, usize::MAX).unwrap()[0].base_offset, 5);
+
+        assert!(matches!(
+            log.append_replicated(at(6, &["e"])),
+            Err(LogError::OffsetBehindEnd { offset: 6, end: 7 })
+        ));
+        assert!(matches!(
+            log.append_replicated(RecordBatch::new(vec![])),
+            Err(LogError::EmptyBatch)
+        ));
+    }
+
+    #[test]
+    fn test_mem_backend() {
+        let backend = MemBackend::new();
+        let config = LogConfig {
+            segment_bytes: 1,
+            ..Default::default()
+        };
+        let mut log = Log::with_backend(Arc::new(backend.clone()), config.clone()).unwrap();
+        log.append(batch(&["a"])).unwrap();
+        log.append(batch(&["b"])).unwrap();
+        log.append(batch(&["c"])).unwrap();
+        assert_eq!(log.segment_count(), 3);
+
+        log.compact().unwrap();
+        drop(log);
+
+        // Nothing is left behind by the rewrite
+        assert_eq!(backend.list().unwrap().len(), 9);
+
+        let log = Log::with_backend(Arc::new(backend), config).unwrap();
+        assert_eq!(log.next_offset(), 3);
+        assert_eq!(values(&log.read(1, usize::MAX).unwrap()), vec!["b"]);
+    }
+
+    #[test]
+    fn test_roll_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let batch_size = entry_size(&batch(&["a"])) as u64;
+        let config = LogConfig {
+            segment_bytes: 2 * batch_size,
+            ..Default::default()
+        };
+
+        let mut log = Log::open(dir.path(), config.clone()).unwrap();
+        for _ in 0..5 {
+            log.append(batch(&["a"])).unwrap();
+        }
+        assert_eq!(log.segment_count(), 3);
+
+        let mut names: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with(".log"))
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "00000000000000000000.log",
+                "00000000000000000002.log",
+                "00000000000000000004.log"
+            ]
+        );
+
+        // Reads span segments
+        assert_eq!(values(&log.read(3, usize::MAX).unwrap()), vec!["a"]);
+        let offsets: Vec<_> = (0..5)
+            .map(|offset| log.read(offset, 1).unwrap()[0].base_offset)
+            .collect();
+        assert_eq!(offsets, vec![0, 1, 2, 3, 4]);
+
+        drop(log);
+        let log = Log::open(dir.path(), config).unwrap();
+        assert_eq!(log.segment_count(), 3);
+        assert_eq!(log.next_offset(), 5);
+    }
+
+    #[test]
+    fn test_roll_by_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogConfig {
+            segment_age: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let mut log = Log::open(dir.path(), config).unwrap();
+
+        let old =
+            RecordBatch::new(vec![Record::new(None, Some(Bytes::from_static(b"old")))
+                .with_timestamp(now_ms() - 120_000)]);
+        log.append(old).unwrap();
+        log.append(batch(&["a"])).unwrap();
+        assert_eq!(log.segment_count(), 2);
+
+        // The new segment is young, so it stays active
+        log.append(batch(&["b"])).unwrap();
+        assert_eq!(log.segment_count(), 2);
+    }
+
+    #[test]
+    fn test_roll_publishes_event() {
+        let dir = tempfile::tempdir().unwrap();
+        let bus = EventBus::new();
+        let events = bus.subscribe();
+
+        let mut log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        log.publish_events(TopicPartition::new("test", 0), bus);
+
+        // Rolling an empty segment is a no-op
+        log.roll().unwrap();
+        assert_eq!(events.try_next(), None);
+
+        log.append(batch(&["a", "b"])).unwrap();
+        log.roll().unwrap();
+        assert_eq!(
+            events.try_next(),
+            Some(Event::SegmentRolled {
+                partition: TopicPartition::new("test", 0),
+                base_offset: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_offset_for_timestamp() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogConfig {
+            segment_bytes: 1,
+            ..Default::default()
+        };
+        let mut log = Log::open(dir.path(), config).unwrap();
+
+        for timestamp in [100, 200, 300] {
+            log.append(RecordBatch::new(vec![
+                Record::new(None, None).with_timestamp(timestamp)
+            ]))
+            .unwrap();
+        }
+        assert_eq!(log.segment_count(), 3);
+
+        assert_eq!(log.offset_for_timestamp(50).unwrap(), Some(0));
+        assert_eq!(log.offset_for_timestamp(150).unwrap(), Some(1));
+        assert_eq!(log.offset_for_timestamp(300).unwrap(), Some(2));
+        assert_eq!(log.offset_for_timestamp(301).unwrap(), None);
+    }
+
+    fn open_rolled(dir: &Path, config: LogConfig, timestamps: &[u64]) -> Log {
+        let config = LogConfig {
+            segment_bytes: 1,
+            ..config
+        };
+        let mut log = Log::open(dir, config).unwrap();
+        for timestamp in timestamps {
+            log.append(RecordBatch::new(vec![Record::new(
+                None,
+                Some(Bytes::from_static(b"a")),
+            )
+            .with_timestamp(*timestamp)]))
+                .unwrap();
+        }
+        log
+    }
+
+    #[test]
+    fn test_retention_by_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = now_ms();
+        let config = LogConfig {
+            retention_age: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let mut log = open_rolled(dir.path(), config, &[now - 120_000, now - 90_000, now, now]);
+        assert_eq!(log.segment_count(), 4);
+
+        assert_eq!(log.enforce_retention().unwrap(), 2);
+        assert_eq!(log.segment_count(), 2);
+        assert_eq!(log.start_offset(), 2);
+        assert_eq!(log.next_offset(), 4);
+
+        // Nothing more to delete
+        assert_eq!(log.enforce_retention().unwrap(), 0);
+
+        assert!(matches!(
+            log.read(1, usize::MAX),
+            Err(LogError::OffsetOutOfRange {
+                offset: 1,
+                start: 2,
+                end: 4
+            })
+        ));
+        assert_eq!(values(&log.read(2, usize::MAX).unwrap()), vec!["a"]);
+        assert!(!dir.path().join(segment_name(0, LOG_SUFFIX)).exists());
+        assert!(!dir.path().join(segment_name(1, INDEX_SUFFIX)).exists());
+    }
+
+    #[test]
+    fn test_retention_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = now_ms();
+        let segment_size = entry_size(&batch(&["a"])) as u64;
+        let config = LogConfig {
+            retention_bytes: Some(2 * segment_size),
+            ..Default::default()
+        };
+        let mut log = open_rolled(dir.path(), config, &[now; 5]);
+
+        assert_eq!(log.enforce_retention().unwrap(), 3);
+        assert_eq!(log.size(), 2 * segment_size);
+        assert_eq!(log.start_offset(), 3);
+    }
+
+    #[test]
+    fn test_retention_keeps_active_segment() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogConfig {
+            retention_age: Some(Duration::from_secs(60)),
+            retention_bytes: Some(0),
+            ..Default::default()
+        };
+        let mut log = open_rolled(dir.path(), config, &[0, 0]);
+
+        assert_eq!(log.enforce_retention().unwrap(), 1);
+        assert_eq!(log.segment_count(), 1);
+        assert_eq!(log.start_offset(), 1);
+
+        drop(log);
+        let log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        assert_eq!(log.start_offset(), 1);
+        assert_eq!(log.next_offset(), 2);
+    }
+
+    fn keyed(key: &'static str, value: Option<&'static str>) -> RecordBatch {
+        RecordBatch::new(vec![Record::new(
+            Some(Bytes::from_static(key.as_bytes())),
+            value.map(|value| Bytes::from_static(value.as_bytes())),
+        )])
+    }
+
+    /// Fails writes to rewritten segment files while `fail` is set.
+    #[derive(Debug, Default)]
+    struct FailingRewrites {
+        files: MemBackend,
+        fail: AtomicBool,
+    }
+
+    impl Backend for FailingRewrites {
+        fn open(&self, name: &str) -> io::Result<u64> {
+            self.files.open(name)
+        }
+
+        fn append(&self, name: &str, data: &[u8]) -> io::Result<()> {
+            if self.fail.load(Ordering::SeqCst) && name.ends_with(".cleaned") {
+                return Err(io::Error::other("disk full"));
+            }
+            self.files.append(name, data)
+        }
+
+        fn read(&self, name: &str, position: u64, buf: &mut [u8]) -> io::Result<()> {
+            self.files.read(name, position, buf)
+        }
+
+        fn truncate(&self, name: &str, len: u64) -> io::Result<()> {
+            self.files.truncate(name, len)
+        }
+
+        fn flush(&self, name: &str) -> io::Result<()> {
+            self.files.flush(name)
+        }
+
+        fn list(&self) -> io::Result<Vec<String>> {
+            self.files.list()
+        }
+
+        fn remove(&self, name: &str) -> io::Result<()> {
+            self.files.remove(name)
+        }
+
+        fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+            self.files.rename(from, to)
+        }
+    }
+
+    fn records(log: &Log) -> Vec<(u64, Option<Bytes>, Option<Bytes>)> {
+        let mut records = Vec::new();
+        for segment in &log.segments {
+            segment
+                .for_each_batch(|batch| {
+                    records.extend(
+                        batch
+                            .records
+                            .into_iter()
+                            .map(|record| (record.offset, record.key, record.value)),
+                    )
+                })
+                .unwrap();
+        }
+        records
+    }
+
+    #[test]
+    fn test_compact() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogConfig {
+            segment_bytes: 1,
+            cleanup_policy: CleanupPolicy::Compact,
+            ..Default::default()
+        };
+        let mut log = Log::open(dir.path(), config).unwrap();
+
+        log.append(keyed("a", Some("1"))).unwrap();
+        log.append(keyed("b", Some("1"))).unwrap();
+        log.append(keyed("a", Some("2"))).unwrap();
+        log.append(keyed("b", None)).unwrap();
+        log.append(batch(&["no key"])).unwrap();
+        log.append(keyed("a", Some("3"))).unwrap();
+
+        let stats = log.compact().unwrap();
+        assert_eq!(stats.segments, 5);
+        assert_eq!(stats.records_removed, 3);
+        assert!(stats.bytes_after < stats.bytes_before);
+
+        // The fresh tombstone for b stays, a's latest value is in the active
+        // segment
+        let bytes = |s: &'static str| Some(Bytes::from_static(s.as_bytes()));
+        assert_eq!(
+            records(&log),
+            vec![
+                (3, bytes("b"), None),
+                (4, None, bytes("no key")),
+                (5, bytes("a"), bytes("3")),
+            ]
+        );
+        assert_eq!(log.start_offset(), 0);
+        assert_eq!(log.next_offset(), 6);
+
+        // Reads from the start skip over what was removed
+        assert_eq!(log.read(0, usize::MAX).unwrap()[0].records[0].offset, 3);
+    }
+
+    #[test]
+    fn test_failed_compaction() {
+        let backend = Arc::new(FailingRewrites::default());
+        let config = LogConfig {
+            segment_bytes: 1,
+            cleanup_policy: CleanupPolicy::Compact,
+            ..Default::default()
+        };
+        let mut log = Log::with_backend(backend.clone(), config).unwrap();
+        log.append(keyed("a", Some("1"))).unwrap();
+        log.append(keyed("b", Some("2"))).unwrap();
+        log.append(keyed("a", Some("3"))).unwrap();
+        let before = records(&log);
+
+        backend.fail.store(true, Ordering::SeqCst);
+        // The first segment is compacted, the second keeps b and fails
+        assert!(matches!(log.compact(), Err(LogError::Io(_))));
+        assert_eq!(log.segment_count(), 3);
+        assert_eq!(records(&log), before[1..]);
+        assert_eq!(values(&log.read(0, usize::MAX).unwrap()), vec!["2"]);
+
+        // Nothing half written is left for the next try
+        assert!(!backend
+            .list()
+            .unwrap()
+            .iter()
+            .any(|name| name.ends_with(".cleaned")));
+        backend.fail.store(false, Ordering::SeqCst);
+        assert_eq!(log.compact().unwrap().records_removed, 0);
+        assert_eq!(records(&log), before[1..]);
+    }
+
+    #[test]
+    fn test_compact_drops_old_tombstones() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogConfig {
+            segment_bytes: 1,
+            cleanup_policy: CleanupPolicy::Compact,
+            tombstone_retention: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let mut log = Log::open(dir.path(), config).unwrap();
+
+        log.append(keyed("a", Some("1"))).unwrap();
+        let mut tombstone = keyed("a", None);
+        tombstone.records[0].timestamp = now_ms() - 120_000;
+        log.append(tombstone).unwrap();
+        log.append(keyed("b", Some("1"))).unwrap();
+
+        log.cleanup().unwrap();
+        assert_eq!(
+            records(&log),
+            vec![(
+                2,
+                Some(Bytes::from_static(b"b")),
+                Some(Bytes::from_static(b"1"))
+            )]
+        );
+    }
+
+    #[test]
+    fn test_compact_policy_skips_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogConfig {
+            segment_bytes: 1,
+            cleanup_policy: CleanupPolicy::Compact,
+            retention_bytes: Some(0),
+            ..Default::default()
+        };
+        let mut log = Log::open(dir.path(), config).unwrap();
+        log.append(keyed("a", Some("1"))).unwrap();
+        log.append(keyed("b", Some("1"))).unwrap();
+
+        log.cleanup().unwrap();
+        assert_eq!(log.segment_count(), 2);
+        assert_eq!(log.start_offset(), 0);
+    }
+
+    fn expiring(value: &'static str, expires_at: u64) -> RecordBatch {
+        RecordBatch::new(vec![Record::new(
+            None,
+            Some(Bytes::from_static(value.as_bytes())),
+        )
+        .with_expiry(expires_at)])
+    }
+
+    #[test]
+    fn test_estimate_cleanup() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogConfig {
+            segment_bytes: 1,
+            ..Default::default()
+        };
+        let mut log = Log::open(dir.path(), config.clone()).unwrap();
+        log.append(keyed("a", Some("1"))).unwrap();
+        log.append(keyed("b", Some("1"))).unwrap();
+        log.append(keyed("a", Some("2"))).unwrap();
+        log.append(keyed("a", Some("3"))).unwrap();
+
+        // As configured nothing is past retention
+        assert_eq!(
+            log.estimate_cleanup(&config).unwrap(),
+            CleanupEstimate::default()
+        );
+
+        let segment_size = entry_size(&keyed("a", Some("1"))) as u64;
+        let retained = LogConfig {
+            retention_bytes: Some(2 * segment_size),
+            ..config.clone()
+        };
+        assert_eq!(
+            log.estimate_cleanup(&retained).unwrap(),
+            CleanupEstimate {
+                segments: 2,
+                records_removed: 2,
+                reclaimable_bytes: 2 * segment_size,
+            }
+        );
+
+        let compacted = LogConfig {
+            cleanup_policy: CleanupPolicy::Compact,
+            ..config
+        };
+        let estimate = log.estimate_cleanup(&compacted).unwrap();
+        assert_eq!(
+            estimate,
+            CleanupEstimate {
+                segments: 2,
+                records_removed: 2,
+                reclaimable_bytes: 2 * segment_size,
+            }
+        );
+
+        // Nothing was touched, and the estimate matches the real thing
+        assert_eq!(log.segment_count(), 4);
+        log.config.cleanup_policy = CleanupPolicy::Compact;
+        let stats = log.compact().unwrap();
+        assert_eq!(stats.records_removed, estimate.records_removed);
+        assert_eq!(
+            stats.bytes_before - stats.bytes_after,
+            estimate.reclaimable_bytes
+        );
+    }
+
+    #[test]
+    fn test_read_unexpired() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        log.append(batch(&["a"])).unwrap();
+        log.append(expiring("b", 100)).unwrap();
+        log.append(expiring("c", 100)).unwrap();
+        log.append(expiring("d", 300)).unwrap();
+        assert!(log.read(0, usize::MAX).unwrap()[1].is_expiring());
+        assert!(!log.read(0, usize::MAX).unwrap()[0].is_expiring());
+
+        let read = log.read_unexpired(0, usize::MAX, 4, 200).unwrap();
+        assert_eq!(values(&read), ["a", "d"]);
+        // Reads on past the expired records
+        let read = log.read_unexpired(1, 1, 4, 200).unwrap();
+        assert_eq!(values(&read), ["d"]);
+        assert_eq!(read[0].records[0].offset, 3);
+        assert!(log
+            .read_unexpired(1, usize::MAX, 3, 200)
+            .unwrap()
+            .is_empty());
+        assert!(log
+            .read_unexpired(1, usize::MAX, 4, 300)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_read_mapped() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        log.append(batch(&["a", "b", "c"])).unwrap();
+        log.append(batch(&["d"])).unwrap();
+        let drop_c = |mut batch: RecordBatch| {
+            batch
+                .records
+                .retain(|record| record.value.as_deref() != Some(&b"c"[..]));
+            batch
+        };
+
+        let read = log.read_mapped(1, usize::MAX, 4, drop_c).unwrap();
+        assert_eq!(values(&read), ["a", "b", "d"]);
+        // Reading from a record left out goes on to the next batch
+        let read = log.read_mapped(2, usize::MAX, 4, drop_c).unwrap();
+        assert_eq!(values(&read), ["d"]);
+        assert!(log
+            .read_mapped(2, usize::MAX, 3, drop_c)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_remove_expired() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogConfig {
+            segment_bytes: 1,
+            ..Default::default()
+        };
+        let mut log = Log::open(dir.path(), config).unwrap();
+        let now = now_ms();
+        log.append(expiring("a", now - 1)).unwrap();
+        log.append(batch(&["b"])).unwrap();
+        log.append(expiring("c", now + 60_000)).unwrap();
+        log.append(expiring("d", now - 1)).unwrap();
+
+        assert!(log.may_expire(0).unwrap());
+        assert!(!log.may_expire(1).unwrap());
+        log.cleanup().unwrap();
+        assert_eq!(values(&log.read(0, usize::MAX).unwrap()), ["b"]);
+        assert_eq!(log.next_offset(), 4);
+        // The active segment is left alone
+        let read = log.read(3, usize::MAX).unwrap();
+        assert_eq!(values(&read), ["d"]);
+        assert_eq!(log.remove_expired(now + 60_000).unwrap(), 1);
+        assert_eq!(log.read_unexpired(0, usize::MAX, 4, now).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_failed_expiry() {
+        let backend = Arc::new(FailingRewrites::default());
+        let config = LogConfig {
+            segment_bytes: 1,
+            ..Default::default()
+        };
+        let mut log = Log::with_backend(backend.clone(), config).unwrap();
+        let now = now_ms();
+        let value = |value: &'static str| Some(Bytes::from_static(value.as_bytes()));
+        log.append(RecordBatch::new(vec![
+            Record::new(None, value("a")).with_expiry(now - 1),
+            Record::new(None, value("b")),
+        ]))
+        .unwrap();
+        log.append(batch(&["c"])).unwrap();
+        let before = records(&log);
+
+        backend.fail.store(true, Ordering::SeqCst);
+        assert!(matches!(log.remove_expired(now), Err(LogError::Io(_))));
+        assert_eq!(log.segment_count(), 2);
+        assert_eq!(records(&log), before);
+
+        backend.fail.store(false, Ordering::SeqCst);
+        assert_eq!(log.remove_expired(now).unwrap(), 1);
+        assert_eq!(values(&log.read(0, usize::MAX).unwrap()), ["b"]);
+    }
+
+    #[test]
+    fn test_reopen_after_torn_write() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        log.append(batch(&["a", "b"])).unwrap();
+        drop(log);
+
+        let mut torn = Vec::new();
+        encode_entry(&batch(&["c"]), &mut torn);
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(segment_name(0, LOG_SUFFIX)))
+            .unwrap()
+            .write_all(&torn[..torn.len() - 3])
+            .unwrap();
+
+        let mut log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        assert_eq!(log.next_offset(), 2);
+        assert_eq!(log.append(batch(&["c"])).unwrap(), 2);
+        assert_eq!(
+            values(&log.read(0, usize::MAX).unwrap()),
+            vec!["a", "b", "c"]
+        );
+    }
+
+    fn open_with_flush_policy(dir: &Path, flush_policy: FlushPolicy) -> Log {
+        let config = LogConfig {
+            flush_policy,
+            ..Default::default()
+        };
+        Log::open(dir, config).unwrap()
+    }
+
+    #[test]
+    fn test_flush_every_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = open_with_flush_policy(dir.path(), FlushPolicy::EveryWrite);
+
+        log.append(batch(&["a", "b"])).unwrap();
+        assert_eq!(log.flushed_offset(), 2);
+    }
+
+    #[test]
+    fn test_flush_every_n_messages() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = open_with_flush_policy(dir.path(), FlushPolicy::EveryNMessages(3));
+
+        log.append(batch(&["a", "b"])).unwrap();
+        assert_eq!(log.flushed_offset(), 0);
+        log.append(batch(&["c"])).unwrap();
+        assert_eq!(log.flushed_offset(), 3);
+        log.append(batch(&["d"])).unwrap();
+        assert_eq!(log.flushed_offset(), 3);
+    }
+
+    #[test]
+    fn test_flush_interval() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log =
+            open_with_flush_policy(dir.path(), FlushPolicy::Interval(Duration::from_millis(20)));
+
+        log.append(batch(&["a"])).unwrap();
+        assert_eq!(log.flushed_offset(), 0);
+
+        std::thread::sleep(Duration::from_millis(25));
+        log.maybe_flush().unwrap();
+        assert_eq!(log.flushed_offset(), 1);
+    }
+
+    #[test]
+    fn test_explicit_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogConfig {
+            segment_bytes: 1,
+            ..Default::default()
+        };
+        let mut log = Log::open(dir.path(), config).unwrap();
+
+        log.append(batch(&["a"])).unwrap();
+        log.append(batch(&["b"])).unwrap();
+        assert_eq!(log.flushed_offset(), 0);
+
+        log.flush().unwrap();
+        assert_eq!(log.flushed_offset(), 2);
+
+        // Reopening counts recovered data as flushed
+        drop(log);
+        let log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        assert_eq!(log.flushed_offset(), 2);
+    }
+
+    #[test]
+    fn test_leader_epoch() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        assert_eq!(log.leader_epoch(), 0);
+        log.check_leader_epoch(None).unwrap();
+        log.check_leader_epoch(Some(0)).unwrap();
+
+        log.set_leader_epoch(2).unwrap();
+        assert!(matches!(
+            log.check_leader_epoch(Some(1)),
+            Err(LogError::FencedLeaderEpoch {
+                epoch: 1,
+                current: 2
+            })
+        ));
+        assert!(matches!(
+            log.check_leader_epoch(Some(3)),
+            Err(LogError::UnknownLeaderEpoch {
+                epoch: 3,
+                current: 2
+            })
+        ));
+        assert!(matches!(
+            log.set_leader_epoch(1),
+            Err(LogError::FencedLeaderEpoch { .. })
+        ));
+
+        // Kept across restarts
+        drop(log);
+        let log = Log::open(dir.path(), LogConfig::default()).unwrap();
+        assert_eq!(log.leader_epoch(), 2);
+    }
+
+    #[test]
+    fn test_read_slices() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogConfig {
+            segment_bytes: 200,
+            ..Default::default()
+        };
+        let mut log = Log::open(dir.path(), config.clone()).unwrap();
+        for value in ["a", "b", "c", "d", "e"] {
+            log.append(batch(&[value])).unwrap();
+        }
+        assert!(log.segment_count() > 1);
+
+        // Slices of sealed segments decode to what reading gives
+        let decode = |slices: Vec<FileSlice>| -> Vec<RecordBatch> {
+            slices
+                .iter()
+                .map(|slice| RecordBatch::from_bytes(slice.read().unwrap()).unwrap())
+                .collect()
+        };
+        let slices = log.read_slices(1, usize::MAX, u64::MAX).unwrap().unwrap();
+        assert_eq!(decode(slices), log.read(1, usize::MAX).unwrap());
+        let slices = log.read_slices(0, 1, u64::MAX).unwrap().unwrap();
+        assert_eq!(values(&decode(slices)), vec!["a"]);
+        let slices = log.read_slices(0, usize::MAX, 2).unwrap().unwrap();
+        assert_eq!(values(&decode(slices)), vec!["a", "b"]);
+
+        // The active segment is read as usual
+        assert_eq!(log.read_slices(4, usize::MAX, u64::MAX).unwrap(), None);
+        assert_eq!(log.read_slices(5, usize::MAX, u64::MAX).unwrap(), None);
+        assert!(matches!(
+            log.read_slices(6, usize::MAX, u64::MAX),
+            Err(LogError::OffsetOutOfRange { .. })
+        ));
+
+        // Backends without files always read
+        let log = Log::with_backend(Arc::new(MemBackend::new()), config).unwrap();
+        assert_eq!(log.read_slices(0, usize::MAX, u64::MAX).unwrap(), None);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_mmap_reads() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogConfig {
+            segment_bytes: 1,
+            mmap_reads: true,
+            ..Default::default()
+        };
+        let mut log = Log::open(dir.path(), config.clone()).unwrap();
+        log.append(batch(&["a"])).unwrap();
+        log.append(batch(&["b"])).unwrap();
+        log.append(batch(&["c"])).unwrap();
+        assert_eq!(values(&log.read(0, usize::MAX).unwrap()), vec!["a"]);
+        assert_eq!(values(&log.read(1, usize::MAX).unwrap()), vec!["b"]);
+
+        drop(log);
+        let log = Log::open(dir.path(), config).unwrap();
+        assert_eq!(values(&log.read(1, usize::MAX).unwrap()), vec!["b"]);
+        assert_eq!(values(&log.read(2, usize::MAX).unwrap()), vec!["c"]);
+    }
+}
@@ -0,0 +1,581 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+
+use serde::Deserialize;
+
+use super::{Log, LogConfig, LogError, MemBackend};
+use crate::events::EventBus;
+use crate::protocol::{validate_topic_name, TopicPartition, NAMESPACE_SEPARATOR};
+
+/// Stands in for the namespace separator in partition directory names, which
+/// can't hold a `/`. Topic names never contain it otherwise.
+const DIR_NAMESPACE_SEPARATOR: char = '+';
+
+/// Path the one dir of [`LogDirs::in_memory`] goes by.
+pub const IN_MEMORY_DIR: &str = ":memory:";
+
+#[derive(Error, Debug)]
+pub enum LogDirError {
+    #[error(transparent)]
+    Log(#[from] LogError),
+    #[error("Unknown partition {0}")]
+    UnknownPartition(TopicPartition),
+    #[error("Partition {0} already exists")]
+    PartitionExists(TopicPartition),
+    #[error("Partition {partition} is offline, {dir:?} failed")]
+    Offline {
+        partition: TopicPartition,
+        dir: PathBuf,
+    },
+    #[error("No log dir is online")]
+    NoOnlineDirs,
+}
+
+/// How new partitions pick a log dir.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Placement {
+    /// The dir with the most free bytes on its filesystem.
+    #[default]
+    MostFreeSpace,
+    /// The dir holding the fewest partitions.
+    FewestPartitions,
+}
+
+#[derive(Debug)]
+struct LogDir {
+    path: PathBuf,
+    online: AtomicBool,
+}
+
+#[derive(Debug)]
+struct Partition {
+    dir: usize,
+    log: Arc<RwLock<Log>>,
+}
+
+/// The partitions stored across a set of log dirs. Each partition lives in
+/// its own directory, named `topic-partition`, under one of them.
+///
+/// A dir that fails, say by going read-only, is taken offline along with all
+/// of its partitions. The other dirs keep serving.
+#[derive(Debug)]
+pub struct LogDirs {
+    dirs: Vec<LogDir>,
+    config: LogConfig,
+    placement: Placement,
+    partitions: RwLock<HashMap<TopicPartition, Partition>>,
+    /// Whether logs are kept in [`MemBackend`]s rather than on disk.
+    in_memory: bool,
+    /// Where the logs publish their segment rolls, if anywhere.
+    events: RwLock<Option<EventBus>>,
+}
+
+impl LogDirs {
+    /// Opens every partition found in `paths`. Dirs that can't be read are
+    /// marked offline rather than failing the whole open.
+    pub fn open(
+        paths: impl IntoIterator<Item = impl Into<PathBuf>>,
+        config: LogConfig,
+        placement: Placement,
+    ) -> Self {
+        let dirs: Vec<_> = paths
+            .into_iter()
+            .map(|path| LogDir {
+                path: path.into(),
+                online: AtomicBool::new(true),
+            })
+            .collect();
+
+        let mut partitions = HashMap::new();
+        for (i, dir) in dirs.iter().enumerate() {
+            match load_dir(&dir.path, &config) {
+                Ok(logs) => partitions.extend(logs.into_iter().map(|(partition, log)| {
+                    let log = Arc::new(RwLock::new(log));
+                    (partition, Partition { dir: i, log })
+                })),
+                Err(_) => dir.online.store(false, Ordering::SeqCst),
+            }
+        }
+
+        LogDirs {
+            dirs,
+            config,
+            placement,
+            partitions: RwLock::new(partitions),
+            in_memory: false,
+            events: RwLock::new(None),
+        }
+    }
+
+    /// Keeps every partition in memory, under a single dir named
+    /// [`IN_MEMORY_DIR`], for tests. Nothing survives the `LogDirs` being
+    /// dropped.
+    pub fn in_memory(config: LogConfig) -> Self {
+        LogDirs {
+            dirs: vec![LogDir {
+                path: PathBuf::from(IN_MEMORY_DIR),
+                online: AtomicBool::new(true),
+            }],
+            config,
+            placement: Placement::FewestPartitions,
+            partitions: RwLock::new(HashMap::new()),
+            in_memory: true,
+            events: RwLock::new(None),
+        }
+    }
+
+    /// Has every log, including those created from now on, publish its
+    /// segment rolls on `bus`.
+    pub fn publish_events(&self, bus: EventBus) {
+        // Held throughout so no partition is created in between
+        let partitions = self.partitions.read().unwrap();
+        for (partition, found) in partitions.iter() {
+            let mut log = found.log.write().unwrap();
+            log.publish_events(partition.clone(), bus.clone());
+        }
+        *self.events.write().unwrap() = Some(bus);
+    }
+
+    /// The log of `partition`, if it exists and its dir is online.
+    pub fn get(&self, partition: &TopicPartition) -> Result<Arc<RwLock<Log>>, LogDirError> {
+        let partitions = self.partitions.read().unwrap();
+        let found = partitions
+            .get(partition)
+            .ok_or_else(|| LogDirError::UnknownPartition(partition.clone()))?;
+
+        let dir = &self.dirs[found.dir];
+        if !dir.online.load(Ordering::SeqCst) {
+            return Err(LogDirError::Offline {
+                partition: partition.clone(),
+                dir: dir.path.clone(),
+            });
+        }
+        Ok(found.log.clone())
+    }
+
+    /// The config partitions are opened and created with.
+    pub fn config(&self) -> &LogConfig {
+        &self.config
+    }
+
+    /// Creates the log of a new partition on the dir the [`Placement`]
+    /// picks. Dirs that fail to create it are taken offline and the next
+    /// best is tried.
+    pub fn create(&self, partition: &TopicPartition) -> Result<Arc<RwLock<Log>>, LogDirError> {
+        self.create_with_config(partition, self.config.clone())
+    }
+
+    /// Like [`LogDirs::create`], with `config` rather than the one shared
+    /// by every partition.
+    pub fn create_with_config(
+        &self,
+        partition: &TopicPartition,
+        config: LogConfig,
+    ) -> Result<Arc<RwLock<Log>>, LogDirError> {
+        let mut partitions = self.partitions.write().unwrap();
+        if partitions.contains_key(partition) {
+            return Err(LogDirError::PartitionExists(partition.clone()));
+        }
+
+        for i in self.candidates(&partitions) {
+            let path = self.dirs[i].path.join(partition_dir_name(partition));
+            let log = if self.in_memory {
+                Log::with_backend(Arc::new(MemBackend::new()), config.clone())
+            } else {
+                Log::open(&path, config.clone())
+            };
+            match log {
+                Ok(mut log) => {
+                    if let Some(bus) = &*self.events.read().unwrap() {
+                        log.publish_events(partition.clone(), bus.clone());
+                    }
+                    let log = Arc::new(RwLock::new(log));
+                    partitions.insert(
+                        partition.clone(),
+                        Partition {
+                            dir: i,
+                            log: log.clone(),
+                        },
+                    );
+                    return Ok(log);
+                }
+                Err(LogError::Io(_)) => self.dirs[i].online.store(false, Ordering::SeqCst),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Err(LogDirError::NoOnlineDirs)
+    }
+
+    /// Deletes `partition` and its files.
+    pub fn remove(&self, partition: &TopicPartition) -> Result<(), LogDirError> {
+        let mut partitions = self.partitions.write().unwrap();
+        let removed = partitions
+            .remove(partition)
+            .ok_or_else(|| LogDirError::UnknownPartition(partition.clone()))?;
+        if self.in_memory {
+            return Ok(());
+        }
+
+        let path = self.dirs[removed.dir]
+            .path
+            .join(partition_dir_name(partition));
+        std::fs::remove_dir_all(path).map_err(LogError::from)?;
+        Ok(())
+    }
+
+    /// Takes the dir of `partition` offline if `err` came from its disk.
+    /// Meant to be called with any error an operation on the log returns.
+    pub fn handle_error(&self, partition: &TopicPartition, err: &LogError) {
+        if !matches!(err, LogError::Io(_)) {
+            return;
+        }
+        if let Some(found) = self.partitions.read().unwrap().get(partition) {
+            self.dirs[found.dir].online.store(false, Ordering::SeqCst);
+        }
+    }
+
+    /// Takes the dir at `path` offline, along with its partitions.
+    pub fn mark_offline(&self, path: &Path) {
+        for dir in self.dirs.iter().filter(|dir| dir.path == path) {
+            dir.online.store(false, Ordering::SeqCst);
+        }
+    }
+
+    pub fn is_online(&self, path: &Path) -> bool {
+        self.dirs
+            .iter()
+            .any(|dir| dir.path == path && dir.online.load(Ordering::SeqCst))
+    }
+
+    /// Every dir, online or not, in the order they were opened with.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.dirs.iter().map(|dir| dir.path.clone()).collect()
+    }
+
+    /// The partitions stored in the dir at `path` with their logs, in
+    /// partition order. Empty when the dir is offline.
+    pub fn partitions_in(&self, path: &Path) -> Vec<(TopicPartition, Arc<RwLock<Log>>)> {
+        let partitions = self.partitions.read().unwrap();
+        let mut found: Vec<_> = partitions
+            .iter()
+            .filter(|(_, found)| {
+                let dir = &self.dirs[found.dir];
+                dir.path == path && dir.online.load(Ordering::SeqCst)
+            })
+            .map(|(partition, found)| (partition.clone(), found.log.clone()))
+            .collect();
+        found.sort_by(|(a, _), (b, _)| a.cmp(b));
+        found
+    }
+
+    /// Partitions whose dir is offline.
+    pub fn offline_partitions(&self) -> Vec<TopicPartition> {
+        let partitions = self.partitions.read().unwrap();
+        let mut offline: Vec<_> = partitions
+            .iter()
+            .filter(|(_, found)| !self.dirs[found.dir].online.load(Ordering::SeqCst))
+            .map(|(partition, _)| partition.clone())
+            .collect();
+        offline.sort();
+        offline
+    }
+
+    /// Logs of every online partition, for background tasks like
+    /// [`RetentionTask`](super::RetentionTask).
+    pub fn logs(&self) -> Vec<Arc<RwLock<Log>>> {
+        let partitions = self.partitions.read().unwrap();
+        partitions
+            .values()
+            .filter(|found| self.dirs[found.dir].online.load(Ordering::SeqCst))
+            .map(|found| found.log.clone())
+            .collect()
+    }
+
+    /// Every online partition with its log, in partition order.
+    pub fn partitions(&self) -> Vec<(TopicPartition, Arc<RwLock<Log>>)> {
+        let partitions = self.partitions.read().unwrap();
+        let mut online: Vec<_> = partitions
+            .iter()
+            .filter(|(_, found)| self.dirs[found.dir].online.load(Ordering::SeqCst))
+            .map(|(partition, found)| (partition.clone(), found.log.clone()))
+            .collect();
+        online.sort_by(|(a, _), (b, _)| a.cmp(b));
+        online
+    }
+
+    /// Flushes every online partition, as on shutdown. A failing partition
+    /// takes its dir offline without stopping the others from being flushed,
+    /// and the first error is returned.
+    pub fn flush(&self) -> Result<(), LogDirError> {
+        let mut result = Ok(());
+        for (partition, log) in self.partitions() {
+            if let Err(err) = log.write().unwrap().flush() {
+                self.handle_error(&partition, &err);
+                if result.is_ok() {
+                    result = Err(err.into());
+                }
+            }
+        }
+        result
+    }
+
+    /// Online dirs, best first by the placement.
+    fn candidates(&self, partitions: &HashMap<TopicPartition, Partition>) -> Vec<usize> {
+        let online = (0..self.dirs.len()).filter(|&i| self.dirs[i].online.load(Ordering::SeqCst));
+
+        match self.placement {
+            Placement::MostFreeSpace => {
+                let mut scored: Vec<_> = online
+                    .map(|i| (free_bytes(&self.dirs[i].path).unwrap_or(0), i))
+                    .collect();
+                // Stable, so ties go to the dir listed first
+                scored.sort_by_key(|&(free, _)| std::cmp::Reverse(free));
+                scored.into_iter().map(|(_, i)| i).collect()
+            }
+            Placement::FewestPartitions => {
+                let mut scored: Vec<_> = online
+                    .map(|i| {
+                        (
+                            partitions.values().filter(|found| found.dir == i).count(),
+                            i,
+                        )
+                    })
+                    .collect();
+                scored.sort_by_key(|&(count, _)| count);
+                scored.into_iter().map(|(_, i)| i).collect()
+            }
+        }
+    }
+}
+
+fn load_dir(path: &Path, config: &LogConfig) -> Result<Vec<(TopicPartition, Log)>, LogError> {
+    std::fs::create_dir_all(path)?;
+
+    let mut logs = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let Some(partition) = entry
+            .file_name()
+            .to_str()
+            .and_then(parse_partition_dir_name)
+        else {
+            continue;
+        };
+        logs.push((partition, Log::open(entry.path(), config.clone())?));
+    }
+    Ok(logs)
+}
+
+fn partition_dir_name(partition: &TopicPartition) -> String {
+    partition
+        .to_string()
+        .replace(NAMESPACE_SEPARATOR, &DIR_NAMESPACE_SEPARATOR.to_string())
+}
+
+fn parse_partition_dir_name(name: &str) -> Option<TopicPartition> {
+    let (topic, partition) = name.rsplit_once('-')?;
+    let topic = topic.replacen(DIR_NAMESPACE_SEPARATOR, &NAMESPACE_SEPARATOR.to_string(), 1);
+    validate_topic_name(&topic).ok()?;
+    Some(TopicPartition::new(topic, partition.parse().ok()?))
+}
+
+/// Bytes available to unprivileged users on the filesystem holding `path`.
+fn free_bytes(path: &Path) -> std::io::Result<u64> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // Safety: `path` is a valid C string and `stat` is written by the call
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bytes::Bytes;
+
+    use crate::record::{Record, RecordBatch};
+
+    fn batch() -> RecordBatch {
+        RecordBatch::new(vec![Record::new(None, Some(Bytes::from_static(b"a")))])
+    }
+
+    fn fewest_partitions(paths: &[PathBuf]) -> LogDirs {
+        LogDirs::open(
+            paths.to_vec(),
+            LogConfig::default(),
+            Placement::FewestPartitions,
+        )
+    }
+
+    #[test]
+    fn test_place_by_partition_count() {
+        let root = tempfile::tempdir().unwrap();
+        let paths = vec![root.path().join("a"), root.path().join("b")];
+        let dirs = fewest_partitions(&paths);
+
+        for partition in 0..4 {
+            dirs.create(&TopicPartition::new("events", partition))
+                .unwrap();
+        }
+        assert!(paths[0].join("events-0").is_dir());
+        assert!(paths[1].join("events-1").is_dir());
+        assert!(paths[0].join("events-2").is_dir());
+        assert!(paths[1].join("events-3").is_dir());
+
+        assert!(matches!(
+            dirs.create(&TopicPartition::new("events", 0)),
+            Err(LogDirError::PartitionExists(_))
+        ));
+    }
+
+    #[test]
+    fn test_place_by_free_space() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = LogDirs::open(
+            [root.path().join("a"), root.path().join("b")],
+            LogConfig::default(),
+            Placement::MostFreeSpace,
+        );
+        assert!(free_bytes(root.path()).unwrap() > 0);
+
+        dirs.create(&TopicPartition::new("events", 0)).unwrap();
+        assert_eq!(dirs.logs().len(), 1);
+    }
+
+    #[test]
+    fn test_reopen() {
+        let root = tempfile::tempdir().unwrap();
+        let paths = vec![root.path().join("a"), root.path().join("b")];
+        let dirs = fewest_partitions(&paths);
+
+        let partition = TopicPartition::new("tenant/events", 1);
+        dirs.create(&partition)
+            .unwrap()
+            .write()
+            .unwrap()
+            .append(batch())
+            .unwrap();
+        dirs.create(&TopicPartition::new("events", 0)).unwrap();
+        assert!(paths[0].join("tenant+events-1").is_dir());
+        drop(dirs);
+
+        let dirs = fewest_partitions(&paths);
+        assert_eq!(dirs.logs().len(), 2);
+        assert_eq!(
+            dirs.get(&partition).unwrap().read().unwrap().next_offset(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_flush() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = fewest_partitions(&[root.path().join("a")]);
+
+        let log = dirs.create(&TopicPartition::new("events", 0)).unwrap();
+        log.write().unwrap().append(batch()).unwrap();
+        assert_eq!(log.read().unwrap().flushed_offset(), 0);
+
+        dirs.flush().unwrap();
+        assert_eq!(log.read().unwrap().flushed_offset(), 1);
+    }
+
+    #[test]
+    fn test_offline_dir() {
+        let root = tempfile::tempdir().unwrap();
+        let paths = vec![root.path().join("a"), root.path().join("b")];
+        let dirs = fewest_partitions(&paths);
+
+        let on_a = TopicPartition::new("events", 0);
+        let on_b = TopicPartition::new("events", 1);
+        dirs.create(&on_a).unwrap();
+        dirs.create(&on_b).unwrap();
+
+        let err = LogError::Io(std::io::Error::other("read-only filesystem"));
+        dirs.handle_error(&on_a, &err);
+        assert!(!dirs.is_online(&paths[0]));
+        assert!(matches!(
+            dirs.get(&on_a),
+            Err(LogDirError::Offline { dir, .. }) if dir == paths[0]
+        ));
+        assert_eq!(dirs.offline_partitions(), vec![on_a]);
+        let online: Vec<_> = dirs
+            .partitions()
+            .into_iter()
+            .map(|(partition, _)| partition)
+            .collect();
+        assert_eq!(online, vec![on_b.clone()]);
+        assert!(dirs.get(&on_b).is_ok());
+        assert_eq!(dirs.logs().len(), 1);
+        assert_eq!(dirs.paths(), paths);
+        assert!(dirs.partitions_in(&paths[0]).is_empty());
+        assert_eq!(dirs.partitions_in(&paths[1]).len(), 1);
+
+        // New partitions avoid the offline dir
+        dirs.create(&TopicPartition::new("events", 2)).unwrap();
+        assert!(paths[1].join("events-2").is_dir());
+
+        dirs.mark_offline(&paths[1]);
+        assert!(matches!(
+            dirs.create(&TopicPartition::new("events", 3)),
+            Err(LogDirError::NoOnlineDirs)
+        ));
+    }
+
+    #[test]
+    fn test_unreadable_dir() {
+        let root = tempfile::tempdir().unwrap();
+        // A file where a dir should be
+        let broken = root.path().join("broken");
+        std::fs::write(&broken, b"").unwrap();
+
+        let dirs = fewest_partitions(&[broken.clone(), root.path().join("ok")]);
+        assert!(!dirs.is_online(&broken));
+        dirs.create(&TopicPartition::new("events", 0)).unwrap();
+    }
+
+    #[test]
+    fn test_remove() {
+        let root = tempfile::tempdir().unwrap();
+        let paths = vec![root.path().join("a")];
+        let dirs = fewest_partitions(&paths);
+
+        let partition = TopicPartition::new("events", 0);
+        dirs.create(&partition).unwrap();
+        dirs.remove(&partition).unwrap();
+        assert!(!paths[0].join("events-0").exists());
+        assert!(matches!(
+            dirs.get(&partition),
+            Err(LogDirError::UnknownPartition(_))
+        ));
+    }
+
+    #[test]
+    fn test_in_memory() {
+        let dirs = LogDirs::in_memory(LogConfig::default());
+        let partition = TopicPartition::new("events", 0);
+        let log = dirs.create(&partition).unwrap();
+        log.write().unwrap().append(batch()).unwrap();
+        assert_eq!(
+            dirs.get(&partition).unwrap().read().unwrap().next_offset(),
+            1
+        );
+        assert_eq!(dirs.paths(), vec![PathBuf::from(IN_MEMORY_DIR)]);
+        assert!(!Path::new(IN_MEMORY_DIR).exists());
+
+        dirs.remove(&partition).unwrap();
+        assert!(dirs.partitions().is_empty());
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, RwLock};
+
+use super::Backend;
+
+/// Keeps files in memory. Clones share the same files, so a log can be
+/// dropped and opened again on a clone to simulate a restart.
+#[derive(Debug, Clone, Default)]
+pub struct MemBackend {
+    files: Arc<RwLock<HashMap<String, Vec<u8>>>>,
+}
+
+impl MemBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn not_found(name: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("no file named {}", name))
+}
+
+impl Backend for MemBackend {
+    fn open(&self, name: &str) -> io::Result<u64> {
+        let mut files = self.files.write().unwrap();
+        Ok(files.entry(name.to_string()).or_default().len() as u64)
+    }
+
+    fn append(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        let mut files = self.files.write().unwrap();
+        files
+            .entry(name.to_string())
+            .or_default()
+            .extend_from_slice(data);
+        Ok(())
+    }
+
+    fn read(&self, name: &str, position: u64, buf: &mut [u8]) -> io::Result<()> {
+        let files = self.files.read().unwrap();
+        let file = files.get(name).ok_or_else(|| not_found(name))?;
+
+        let start = position as usize;
+        let src = file
+            .get(start..start + buf.len())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "read past end of file"))?;
+        buf.copy_from_slice(src);
+        Ok(())
+    }
+
+    fn truncate(&self, name: &str, len: u64) -> io::Result<()> {
+        let mut files = self.files.write().unwrap();
+        let file = files.get_mut(name).ok_or_else(|| not_found(name))?;
+        file.truncate(len as usize);
+        Ok(())
+    }
+
+    fn flush(&self, _name: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        Ok(self.files.read().unwrap().keys().cloned().collect())
+    }
+
+    fn remove(&self, name: &str) -> io::Result<()> {
+        self.files.write().unwrap().remove(name);
+        Ok(())
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let mut files = self.files.write().unwrap();
+        let file = files.remove(from).ok_or_else(|| not_found(from))?;
+        files.insert(to.to_string(), file);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_files() {
+        let backend = MemBackend::new();
+        assert_eq!(backend.open("a").unwrap(), 0);
+        backend.append("a", b"hello").unwrap();
+        backend.append("a", b" world").unwrap();
+        assert_eq!(backend.open("a").unwrap(), 11);
+
+        let mut buf = [0u8; 5];
+        backend.read("a", 6, &mut buf).unwrap();
+        assert_eq!(&buf, b"world");
+        assert_eq!(
+            backend.read("a", 7, &mut buf).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        backend.truncate("a", 5).unwrap();
+        backend.rename("a", "b").unwrap();
+        assert_eq!(backend.list().unwrap(), vec!["b".to_string()]);
+        assert_eq!(backend.open("b").unwrap(), 5);
+
+        // Clones see the same files
+        backend.clone().remove("b").unwrap();
+        assert!(backend.list().unwrap().is_empty());
+        assert_eq!(
+            backend.read("b", 0, &mut buf).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+}
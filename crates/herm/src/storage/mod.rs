@@ -0,0 +1,26 @@
+mod backend;
+mod config;
+mod dump;
+mod file_slice;
+mod fs_backend;
+mod index;
+mod leader_epoch;
+mod log;
+mod log_dirs;
+mod mem_backend;
+mod retention;
+mod segment;
+#[cfg(feature = "tiered")]
+pub mod tiered;
+mod time_index;
+#[cfg(feature = "io-uring")]
+mod uring;
+pub use backend::Backend;
+pub use config::{CleanupPolicy, FlushPolicy, LogConfig, TopicConfigError};
+pub use dump::{dump_file, Dump, DumpError, DumpedEntry, IndexDump, SegmentDump};
+pub use file_slice::FileSlice;
+pub use fs_backend::FsBackend;
+pub use log::{CleanupEstimate, CompactionStats, Log, LogError};
+pub use log_dirs::{LogDirError, LogDirs, Placement, IN_MEMORY_DIR};
+pub use mem_backend::MemBackend;
+pub use retention::RetentionTask;
@@ -0,0 +1,153 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use super::Log;
+
+/// Background thread applying [`Log::cleanup`], retention or compaction
+/// depending on the log's policy, to a set of logs every `interval`. Logs
+/// with an interval [`FlushPolicy`](super::FlushPolicy) are flushed here too
+/// when due. The set is re-read on each pass, so logs can come and go.
+/// Logs that fail are logged, counted and retried on the next pass. Stops
+/// when dropped.
+#[derive(Debug)]
+pub struct RetentionTask {
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+    errors: Arc<AtomicU64>,
+}
+
+impl RetentionTask {
+    pub fn spawn(
+        interval: Duration,
+        logs: impl Fn() -> Vec<Arc<RwLock<Log>>> + Send + 'static,
+    ) -> Self {
+        let (stop, stopped) = channel();
+        let errors = Arc::new(AtomicU64::new(0));
+        let failed = errors.clone();
+
+        let handle = std::thread::spawn(move || loop {
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => return,
+            }
+
+            for log in logs() {
+                // A failing log shouldn't hold back the others, it is retried
+                // on the next pass
+                let mut log = log.write().unwrap();
+                let flushed = log.maybe_flush();
+                let cleaned = log.cleanup();
+                for err in [flushed.err(), cleaned.err()].into_iter().flatten() {
+                    tracing::warn!(%err, "log flush or cleanup failed");
+                    failed.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        });
+
+        RetentionTask {
+            stop: Some(stop),
+            handle: Some(handle),
+            errors,
+        }
+    }
+
+    /// Number of times a log failed to be flushed or cleaned up.
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for RetentionTask {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::path::Path;
+
+    use crate::record::{Record, RecordBatch};
+    use crate::storage::LogConfig;
+
+    /// A log of three segments, all but the last past retention.
+    fn expired_log(dir: &Path) -> Log {
+        let config = LogConfig {
+            segment_bytes: 1,
+            retention_age: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+        let mut log = Log::open(dir, config).unwrap();
+        for _ in 0..3 {
+            log.append(RecordBatch::new(vec![
+                Record::new(None, None).with_timestamp(0)
+            ]))
+            .unwrap();
+        }
+        log
+    }
+
+    #[test]
+    fn test_background_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = Arc::new(RwLock::new(expired_log(dir.path())));
+
+        let shared = log.clone();
+        let task = RetentionTask::spawn(Duration::from_millis(10), move || vec![shared.clone()]);
+
+        for _ in 0..100 {
+            if log.read().unwrap().segment_count() == 1 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        drop(task);
+
+        assert_eq!(log.read().unwrap().start_offset(), 2);
+    }
+
+    #[test]
+    fn test_failing_log() {
+        let dirs = [tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap()];
+        let failing = expired_log(dirs[0].path());
+        // Segment files can't be removed when one is a directory
+        let mut names: Vec<_> = fs::read_dir(dirs[0].path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "index")
+            })
+            .collect();
+        names.sort();
+        fs::remove_file(&names[0]).unwrap();
+        fs::create_dir(&names[0]).unwrap();
+
+        let logs = [
+            Arc::new(RwLock::new(failing)),
+            Arc::new(RwLock::new(expired_log(dirs[1].path()))),
+        ];
+        let shared = logs.clone();
+        let task = RetentionTask::spawn(Duration::from_millis(10), move || shared.to_vec());
+
+        for _ in 0..100 {
+            if task.errors() > 0 && logs[1].read().unwrap().segment_count() == 1 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(task.errors() > 0);
+        drop(task);
+
+        assert_eq!(logs[1].read().unwrap().start_offset(), 2);
+    }
+}
@@ -0,0 +1,743 @@
+use std::sync::{Arc, OnceLock};
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use super::index::OffsetIndex;
+use super::time_index::TimeIndex;
+use super::{Backend, FileSlice, LogError};
+use crate::record::{crc32c, Record, RecordBatch};
+
+pub(super) const LOG_SUFFIX: &str = "log";
+pub(super) const INDEX_SUFFIX: &str = "index";
+pub(super) const TIME_INDEX_SUFFIX: &str = "timeindex";
+
+/// Every batch in a segment file is framed by its length and a crc32c of its
+/// encoded bytes, both u32. The frame is checked before the batch is decoded,
+/// so a corrupt file is caught no matter what it did to the batch.
+pub(super) const ENTRY_HEADER_SIZE: usize = 4 + 4;
+
+/// Bytes `batch` takes up in a segment file.
+pub(super) fn entry_size(batch: &RecordBatch) -> usize {
+    ENTRY_HEADER_SIZE + batch.size()
+}
+
+pub(super) fn encode_entry(batch: &RecordBatch, buf: &mut impl BufMut) {
+    let encoded = batch.to_bytes();
+    buf.put_u32(encoded.len() as u32);
+    buf.put_u32(crc32c(&encoded));
+    buf.put_slice(&encoded);
+}
+
+/// Appended to the file names of a segment while it is being rewritten.
+const CLEANED_TAG: &str = ".cleaned";
+
+/// Segment files are named after the first offset they hold, zero padded so
+/// they sort by offset.
+pub(super) fn segment_name(base_offset: u64, suffix: &str) -> String {
+    format!("{:020}.{}", base_offset, suffix)
+}
+
+fn tagged_name(base_offset: u64, suffix: &str, tag: &str) -> String {
+    segment_name(base_offset, suffix) + tag
+}
+
+/// One file of the log, holding consecutive batches starting at
+/// `base_offset`, along with its offset and time indexes.
+#[derive(Debug)]
+pub(super) struct Segment {
+    backend: Arc<dyn Backend>,
+    base_offset: u64,
+    name: String,
+    size: u64,
+    index: OffsetIndex,
+    time_index: TimeIndex,
+    next_offset: u64,
+    first_timestamp: Option<u64>,
+    /// Whether any batch is flagged as holding expiring records, worked out
+    /// on first use.
+    expiring: OnceLock<bool>,
+}
+
+impl Segment {
+    pub fn open(backend: Arc<dyn Backend>, base_offset: u64) -> Result<Self, LogError> {
+        Self::load(backend, base_offset, "", false).map(|(segment, _)| segment)
+    }
+
+    /// Opens the segment after checking the length and crc of every batch,
+    /// truncating the file at the first torn or corrupt one and rebuilding
+    /// the indexes. Used for the active segment, which a crash may have left
+    /// half written. Returns the segment with the number of bytes dropped.
+    pub fn recover(backend: Arc<dyn Backend>, base_offset: u64) -> Result<(Self, u64), LogError> {
+        Self::load(backend, base_offset, "", true)
+    }
+
+    fn load(
+        backend: Arc<dyn Backend>,
+        base_offset: u64,
+        tag: &str,
+        recover: bool,
+    ) -> Result<(Self, u64), LogError> {
+        let name = tagged_name(base_offset, LOG_SUFFIX, tag);
+        let size = backend.open(&name)?;
+        let index =
+            OffsetIndex::open(backend.clone(), tagged_name(base_offset, INDEX_SUFFIX, tag))?;
+        let time_index = TimeIndex::open(
+            backend.clone(),
+            tagged_name(base_offset, TIME_INDEX_SUFFIX, tag),
+        )?;
+
+        let mut segment = Segment {
+            backend,
+            base_offset,
+            name,
+            size,
+            index,
+            time_index,
+            next_offset: base_offset,
+            first_timestamp: None,
+            expiring: OnceLock::new(),
+        };
+
+        let mut truncated = 0;
+        if recover {
+            truncated = segment.truncate_invalid_tail()?;
+            segment.index.clear()?;
+            segment.time_index.clear()?;
+        }
+
+        if segment.size > 0 && (segment.index.is_empty() || segment.time_index.is_empty()) {
+            segment.rebuild_indexes()?;
+        }
+
+        if let Some((_, position)) = segment.index.last() {
+            let (batch, _) = segment
+                .read_batch_at(position as u64)?
+                .ok_or(LogError::CorruptIndex)?;
+            segment.next_offset = batch.next_offset();
+        }
+
+        if let Some((batch, _)) = segment.read_batch_at(0)? {
+            segment.first_timestamp = Some(batch.first_timestamp());
+        }
+
+        Ok((segment, truncated))
+    }
+
+    pub fn base_offset(&self) -> u64 {
+        self.base_offset
+    }
+
+    pub fn next_offset(&self) -> u64 {
+        self.next_offset
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Timestamp of the first record in the segment, if it has any.
+    pub fn first_timestamp(&self) -> Option<u64> {
+        self.first_timestamp
+    }
+
+    /// Largest record timestamp in the segment, if it has any records.
+    pub fn max_timestamp(&self) -> Option<u64> {
+        self.time_index.max_timestamp()
+    }
+
+    /// Writes `batch`, which must already carry its final offsets.
+    pub fn append(&mut self, batch: &RecordBatch) -> Result<(), LogError> {
+        let mut buf = BytesMut::with_capacity(entry_size(batch));
+        encode_entry(batch, &mut buf);
+        self.backend.append(&self.name, &buf)?;
+
+        self.index.append(
+            (batch.base_offset - self.base_offset) as u32,
+            self.size as u32,
+        )?;
+        self.time_index
+            .maybe_append(batch.max_timestamp(), self.size as u32)?;
+        self.size += buf.len() as u64;
+        self.next_offset = batch.next_offset();
+        self.first_timestamp.get_or_insert(batch.first_timestamp());
+        if batch.is_expiring() {
+            self.expiring.take();
+        }
+        Ok(())
+    }
+
+    /// Whether any batch in the segment holds records with an expiry. Only
+    /// the batch attributes are read, and only the first time.
+    pub fn has_expiring(&self) -> Result<bool, LogError> {
+        if let Some(&expiring) = self.expiring.get() {
+            return Ok(expiring);
+        }
+
+        let mut expiring = false;
+        let mut position = 0;
+        let mut prefix = [0u8; ENTRY_HEADER_SIZE + RecordBatch::LOG_OVERHEAD + 4 + 2];
+        while !expiring && position + prefix.len() as u64 <= self.size {
+            self.read_exact_at(&mut prefix, position)?;
+            let len = u32::from_be_bytes(prefix[0..4].try_into().unwrap());
+            let attributes = RecordBatch::peek_attributes(&prefix[ENTRY_HEADER_SIZE..]);
+            expiring = attributes.is_some_and(|attributes| attributes & RecordBatch::EXPIRING != 0);
+            position += ENTRY_HEADER_SIZE as u64 + len as u64;
+        }
+        Ok(*self.expiring.get_or_init(|| expiring))
+    }
+
+    /// Reads batches holding `offset` and after, up to `max_bytes`. At least
+    /// one batch is returned if there is any, so consumers can make progress
+    /// past batches bigger than `max_bytes`.
+    pub fn read(&self, offset: u64, max_bytes: usize) -> Result<Vec<RecordBatch>, LogError> {
+        let Some(mut position) = self.index.lookup((offset - self.base_offset) as u32) else {
+            return Ok(vec![]);
+        };
+
+        let mut batches = Vec::new();
+        let mut read_bytes = 0;
+        while let Some((batch, size)) = self.read_batch_at(position as u64)? {
+            position += size as u32;
+
+            if batch.next_offset() <= offset {
+                continue;
+            }
+            if !batches.is_empty() && read_bytes + size > max_bytes {
+                break;
+            }
+
+            read_bytes += size;
+            batches.push(batch);
+        }
+        Ok(batches)
+    }
+
+    /// Like [`read`](Segment::read), but points at the batches in the segment
+    /// file rather than reading them, or `None` if the backend has no file to
+    /// point into. Stops at the first batch ending past `max_offset`. Only the
+    /// framing of each entry is read, its crc is left to the batch crc the
+    /// client checks.
+    pub fn read_slices(
+        &self,
+        offset: u64,
+        max_bytes: usize,
+        max_offset: u64,
+    ) -> Result<Option<Vec<FileSlice>>, LogError> {
+        let Some(file) = self.backend.file_handle(&self.name)? else {
+            return Ok(None);
+        };
+        let Some(position) = self.index.lookup((offset - self.base_offset) as u32) else {
+            return Ok(Some(vec![]));
+        };
+
+        let mut position = position as u64;
+        let mut slices = Vec::new();
+        let mut read_bytes = 0;
+        let mut prefix = [0u8; ENTRY_HEADER_SIZE + RecordBatch::PREFIX_SIZE];
+        while position < self.size {
+            let corrupt = LogError::CorruptSegment {
+                base_offset: self.base_offset,
+                position,
+            };
+            if self.size - position < prefix.len() as u64 {
+                return Err(corrupt);
+            }
+            self.read_exact_at(&mut prefix, position)?;
+            let len = u32::from_be_bytes(prefix[0..4].try_into().unwrap()) as usize;
+            let size = ENTRY_HEADER_SIZE + len;
+            if len < RecordBatch::PREFIX_SIZE || position + size as u64 > self.size {
+                return Err(corrupt);
+            }
+            let next_offset = RecordBatch::peek_next_offset(&prefix[ENTRY_HEADER_SIZE..]).unwrap();
+            let batch_position = position + ENTRY_HEADER_SIZE as u64;
+            position += size as u64;
+
+            if next_offset <= offset {
+                continue;
+            }
+            if next_offset > max_offset || (!slices.is_empty() && read_bytes + size > max_bytes) {
+                break;
+            }
+
+            read_bytes += size;
+            slices.push(FileSlice::new(file.clone(), batch_position, len as u64));
+        }
+        Ok(Some(slices))
+    }
+
+    /// Earliest offset of a record with a timestamp at or after `timestamp`.
+    pub fn offset_for_timestamp(&self, timestamp: u64) -> Result<Option<u64>, LogError> {
+        let Some(mut position) = self.time_index.lookup(timestamp) else {
+            return Ok(None);
+        };
+
+        while let Some((batch, size)) = self.read_batch_at(position as u64)? {
+            position += size as u32;
+
+            // Timestamps aren't ordered inside a batch, so take the earliest
+            // offset of any record that qualifies.
+            if let Some(record) = batch
+                .records
+                .iter()
+                .find(|record| record.timestamp >= timestamp)
+            {
+                return Ok(Some(record.offset));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Marks the segment as sealed, letting the backend serve its reads
+    /// some faster way.
+    pub fn seal(&self) -> Result<(), LogError> {
+        self.backend.seal(&self.name)?;
+        Ok(())
+    }
+
+    /// Syncs the segment and its indexes to disk.
+    pub fn flush(&self) -> Result<(), LogError> {
+        self.backend.flush(&self.name)?;
+        self.index.flush()?;
+        self.time_index.flush()?;
+        Ok(())
+    }
+
+    /// Calls `f` with every batch in the segment, in order.
+    pub fn for_each_batch(&self, mut f: impl FnMut(RecordBatch)) -> Result<(), LogError> {
+        let mut position = 0;
+        while let Some((batch, size)) = self.read_batch_at(position)? {
+            position += size as u64;
+            f(batch);
+        }
+        Ok(())
+    }
+
+    /// Rewrites the segment with only the records `keep` accepts. Offsets are
+    /// left as they are, and batches left with no records are dropped. The
+    /// segment is left as it was if the rewritten copy can't be written.
+    pub fn rewrite(&mut self, keep: impl FnMut(&Record) -> bool) -> Result<(), LogError> {
+        let backend = self.backend.clone();
+        let base_offset = self.base_offset;
+
+        // Leftovers from an interrupted rewrite
+        remove_files(&*backend, base_offset, CLEANED_TAG)?;
+
+        if let Err(err) = self.write_cleaned(keep) {
+            let _ = remove_files(&*backend, base_offset, CLEANED_TAG);
+            return Err(err);
+        }
+
+        // Indexes go first, so a crash part way leaves a log file without
+        // indexes, which are rebuilt on open.
+        for suffix in [INDEX_SUFFIX, TIME_INDEX_SUFFIX] {
+            backend.remove(&segment_name(base_offset, suffix))?;
+        }
+        for suffix in [LOG_SUFFIX, INDEX_SUFFIX, TIME_INDEX_SUFFIX] {
+            backend.rename(
+                &tagged_name(base_offset, suffix, CLEANED_TAG),
+                &segment_name(base_offset, suffix),
+            )?;
+        }
+
+        *self = Segment::open(backend, base_offset)?;
+        Ok(())
+    }
+
+    /// Writes the records `keep` accepts to a copy of the segment tagged
+    /// [`CLEANED_TAG`].
+    fn write_cleaned(&self, mut keep: impl FnMut(&Record) -> bool) -> Result<(), LogError> {
+        let (mut cleaned, _) =
+            Segment::load(self.backend.clone(), self.base_offset, CLEANED_TAG, false)?;
+        let mut result = Ok(());
+        self.for_each_batch(|mut batch| {
+            batch.records.retain(&mut keep);
+            if result.is_ok() && !batch.records.is_empty() {
+                result = cleaned.append(&batch);
+            }
+        })?;
+        result
+    }
+
+    /// Records [`rewrite`](Segment::rewrite) would drop given `keep`, and the
+    /// size of the segment after, without rewriting it.
+    pub fn rewritten(
+        &self,
+        mut keep: impl FnMut(&Record) -> bool,
+    ) -> Result<(usize, u64), LogError> {
+        let (mut removed, mut size) = (0, 0);
+        self.for_each_batch(|mut batch| {
+            let records = batch.records.len();
+            batch.records.retain(&mut keep);
+            removed += records - batch.records.len();
+            if !batch.records.is_empty() {
+                size += entry_size(&batch) as u64;
+            }
+        })?;
+        Ok((removed, size))
+    }
+
+    /// Removes the segment and its indexes.
+    pub fn delete(self) -> Result<(), LogError> {
+        remove_files(&*self.backend, self.base_offset, "")?;
+        Ok(())
+    }
+
+    /// Decodes the batch at `position`, returning it with its entry size.
+    fn read_batch_at(&self, position: u64) -> Result<Option<(RecordBatch, usize)>, LogError> {
+        if position >= self.size {
+            return Ok(None);
+        }
+
+        match self.valid_entry_at(position)? {
+            Some(entry) => Ok(Some(entry)),
+            None => Err(LogError::CorruptSegment {
+                base_offset: self.base_offset,
+                position,
+            }),
+        }
+    }
+
+    /// Cuts the file at the first entry that is incomplete or fails a crc,
+    /// returning the number of bytes removed.
+    fn truncate_invalid_tail(&mut self) -> Result<u64, LogError> {
+        let mut position = 0;
+        while let Some((_, size)) = self.valid_entry_at(position)? {
+            position += size as u64;
+        }
+
+        let truncated = self.size - position;
+        if truncated > 0 {
+            self.backend.truncate(&self.name, position)?;
+            self.size = position;
+        }
+        Ok(truncated)
+    }
+
+    /// The batch at `position` with its entry size, if the entry is complete
+    /// and intact.
+    fn valid_entry_at(&self, position: u64) -> Result<Option<(RecordBatch, usize)>, LogError> {
+        let remaining = self.size - position;
+        if remaining < ENTRY_HEADER_SIZE as u64 {
+            return Ok(None);
+        }
+
+        let mut header = [0u8; ENTRY_HEADER_SIZE];
+        self.read_exact_at(&mut header, position)?;
+        let len = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+        let crc = u32::from_be_bytes(header[4..8].try_into().unwrap());
+        if (ENTRY_HEADER_SIZE + len) as u64 > remaining {
+            return Ok(None);
+        }
+
+        let mut buf = vec![0u8; len];
+        self.read_exact_at(&mut buf, position + ENTRY_HEADER_SIZE as u64)?;
+        if crc32c(&buf) != crc {
+            return Ok(None);
+        }
+
+        Ok(RecordBatch::from_bytes(Bytes::from(buf))
+            .ok()
+            .map(|batch| (batch, ENTRY_HEADER_SIZE + len)))
+    }
+
+    fn read_exact_at(&self, buf: &mut [u8], position: u64) -> std::io::Result<()> {
+        self.backend.read(&self.name, position, buf)
+    }
+
+    /// Refills whichever index is empty from the segment file.
+    fn rebuild_indexes(&mut self) -> Result<(), LogError> {
+        let rebuild_offsets = self.index.is_empty();
+        let rebuild_times = self.time_index.is_empty();
+
+        let mut position = 0;
+        while let Some((batch, size)) = self.read_batch_at(position)? {
+            if rebuild_offsets {
+                self.index.append(
+                    (batch.base_offset - self.base_offset) as u32,
+                    position as u32,
+                )?;
+            }
+            if rebuild_times {
+                self.time_index
+                    .maybe_append(batch.max_timestamp(), position as u32)?;
+            }
+            position += size as u64;
+        }
+        Ok(())
+    }
+}
+
+fn remove_files(backend: &dyn Backend, base_offset: u64, tag: &str) -> std::io::Result<()> {
+    for suffix in [LOG_SUFFIX, INDEX_SUFFIX, TIME_INDEX_SUFFIX] {
+        backend.remove(&tagged_name(base_offset, suffix, tag))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::record::Record;
+    use crate::storage::MemBackend;
+
+    fn timed_batch(base_offset: u64, timestamps: &[u64]) -> RecordBatch {
+        let mut batch = RecordBatch::new(
+            timestamps
+                .iter()
+                .map(|timestamp| Record::new(None, None).with_timestamp(*timestamp))
+                .collect(),
+        );
+        batch.set_base_offset(base_offset);
+        batch
+    }
+
+    fn batch(base_offset: u64, count: usize) -> RecordBatch {
+        let mut batch = RecordBatch::new(
+            (0..count)
+                .map(|i| {
+                    Record::new(None, Some(Bytes::from(format!("value-{}", i))))
+                        .with_timestamp(1000)
+                })
+                .collect(),
+        );
+        batch.set_base_offset(base_offset);
+        batch
+    }
+
+    #[test]
+    fn test_append_and_read() {
+        let backend = Arc::new(MemBackend::new());
+        let mut segment = Segment::open(backend.clone(), 10).unwrap();
+        assert_eq!(segment.next_offset(), 10);
+
+        segment.append(&batch(10, 3)).unwrap();
+        segment.append(&batch(13, 2)).unwrap();
+        assert_eq!(segment.next_offset(), 15);
+        assert_eq!(
+            segment.first_timestamp(),
+            Some(batch(10, 3).first_timestamp())
+        );
+
+        let batches = segment.read(10, usize::MAX).unwrap();
+        assert_eq!(batches, vec![batch(10, 3), batch(13, 2)]);
+
+        // Offsets inside a batch return the whole batch
+        let batches = segment.read(11, usize::MAX).unwrap();
+        assert_eq!(batches[0].base_offset, 10);
+
+        let batches = segment.read(13, usize::MAX).unwrap();
+        assert_eq!(batches, vec![batch(13, 2)]);
+
+        assert!(segment.read(15, usize::MAX).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_max_bytes() {
+        let backend = Arc::new(MemBackend::new());
+        let mut segment = Segment::open(backend.clone(), 0).unwrap();
+        segment.append(&batch(0, 1)).unwrap();
+        segment.append(&batch(1, 1)).unwrap();
+
+        // Always returns at least one batch
+        assert_eq!(segment.read(0, 1).unwrap().len(), 1);
+
+        let size = entry_size(&batch(0, 1));
+        assert_eq!(segment.read(0, 2 * size).unwrap().len(), 2);
+        assert_eq!(segment.read(0, 2 * size - 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_reopen() {
+        let backend = Arc::new(MemBackend::new());
+        let mut segment = Segment::open(backend.clone(), 0).unwrap();
+        segment.append(&batch(0, 3)).unwrap();
+        segment.append(&batch(3, 3)).unwrap();
+        drop(segment);
+
+        let segment = Segment::open(backend.clone(), 0).unwrap();
+        assert_eq!(segment.next_offset(), 6);
+        assert!(segment.first_timestamp().is_some());
+        assert_eq!(segment.read(4, usize::MAX).unwrap(), vec![batch(3, 3)]);
+    }
+
+    #[test]
+    fn test_rebuild_missing_index() {
+        let backend = Arc::new(MemBackend::new());
+        let mut segment = Segment::open(backend.clone(), 0).unwrap();
+        segment.append(&batch(0, 3)).unwrap();
+        segment.append(&batch(3, 3)).unwrap();
+        drop(segment);
+
+        backend.remove(&segment_name(0, INDEX_SUFFIX)).unwrap();
+
+        let segment = Segment::open(backend.clone(), 0).unwrap();
+        assert_eq!(segment.next_offset(), 6);
+        assert_eq!(segment.read(4, usize::MAX).unwrap(), vec![batch(3, 3)]);
+    }
+
+    #[test]
+    fn test_offset_for_timestamp() {
+        let backend = Arc::new(MemBackend::new());
+        let mut segment = Segment::open(backend.clone(), 0).unwrap();
+        segment.append(&timed_batch(0, &[100, 200])).unwrap();
+        segment.append(&timed_batch(2, &[400, 300])).unwrap();
+        segment.append(&timed_batch(4, &[350])).unwrap();
+        segment.append(&timed_batch(5, &[500])).unwrap();
+        assert_eq!(segment.max_timestamp(), Some(500));
+
+        assert_eq!(segment.offset_for_timestamp(0).unwrap(), Some(0));
+        assert_eq!(segment.offset_for_timestamp(150).unwrap(), Some(1));
+        assert_eq!(segment.offset_for_timestamp(250).unwrap(), Some(2));
+        assert_eq!(segment.offset_for_timestamp(320).unwrap(), Some(2));
+        assert_eq!(segment.offset_for_timestamp(450).unwrap(), Some(5));
+        assert_eq!(segment.offset_for_timestamp(501).unwrap(), None);
+    }
+
+    #[test]
+    fn test_rebuild_missing_time_index() {
+        let backend = Arc::new(MemBackend::new());
+        let mut segment = Segment::open(backend.clone(), 0).unwrap();
+        segment.append(&timed_batch(0, &[100, 200])).unwrap();
+        segment.append(&timed_batch(2, &[300])).unwrap();
+        drop(segment);
+
+        backend.remove(&segment_name(0, TIME_INDEX_SUFFIX)).unwrap();
+
+        let segment = Segment::open(backend.clone(), 0).unwrap();
+        assert_eq!(segment.max_timestamp(), Some(300));
+        assert_eq!(segment.offset_for_timestamp(250).unwrap(), Some(2));
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let backend = Arc::new(MemBackend::new());
+        let mut segment = Segment::open(backend.clone(), 0).unwrap();
+        segment.append(&batch(0, 3)).unwrap();
+        segment.append(&batch(3, 2)).unwrap();
+        segment.append(&batch(5, 2)).unwrap();
+        let size = segment.size();
+
+        // Keep offsets 1, 2 and 6
+        segment
+            .rewrite(|record| [1, 2, 6].contains(&record.offset))
+            .unwrap();
+        assert!(segment.size() < size);
+
+        let batches = segment.read(0, usize::MAX).unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].base_offset, 0);
+        assert_eq!(
+            batches
+                .iter()
+                .flat_map(|batch| batch.records.iter().map(|record| record.offset))
+                .collect::<Vec<_>>(),
+            vec![1, 2, 6]
+        );
+
+        // Reads in the gap land on the next kept record
+        assert_eq!(segment.read(3, usize::MAX).unwrap()[0].records[0].offset, 6);
+        assert_eq!(segment.next_offset(), 7);
+
+        drop(segment);
+        let segment = Segment::open(backend.clone(), 0).unwrap();
+        assert_eq!(segment.read(0, usize::MAX).unwrap(), batches);
+    }
+
+    fn entry(batch: &RecordBatch) -> Vec<u8> {
+        let mut buf = Vec::new();
+        encode_entry(batch, &mut buf);
+        buf
+    }
+
+    fn append_raw(backend: &MemBackend, bytes: &[u8]) {
+        backend.append(&segment_name(0, LOG_SUFFIX), bytes).unwrap();
+    }
+
+    #[test]
+    fn test_recover_torn_write() {
+        let backend = Arc::new(MemBackend::new());
+        let mut segment = Segment::open(backend.clone(), 0).unwrap();
+        segment.append(&batch(0, 3)).unwrap();
+        segment.append(&batch(3, 2)).unwrap();
+        let size = segment.size();
+        drop(segment);
+
+        // Half of the next batch made it to disk before the crash
+        let torn = entry(&batch(5, 2));
+        append_raw(&backend, &torn[..torn.len() / 2]);
+
+        let (mut segment, truncated) = Segment::recover(backend.clone(), 0).unwrap();
+        assert_eq!(truncated, (torn.len() / 2) as u64);
+        assert_eq!(segment.size(), size);
+        assert_eq!(segment.next_offset(), 5);
+        assert_eq!(backend.open(&segment_name(0, LOG_SUFFIX)).unwrap(), size);
+
+        // Appends carry on from the last good batch
+        segment.append(&batch(5, 2)).unwrap();
+        assert_eq!(segment.read(5, usize::MAX).unwrap(), vec![batch(5, 2)]);
+    }
+
+    #[test]
+    fn test_recover_corrupt_tail() {
+        let backend = Arc::new(MemBackend::new());
+        let mut segment = Segment::open(backend.clone(), 0).unwrap();
+        segment.append(&batch(0, 3)).unwrap();
+        let size = segment.size();
+        drop(segment);
+
+        let mut corrupt = entry(&batch(3, 2));
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xFF;
+        append_raw(&backend, &corrupt);
+        append_raw(&backend, &entry(&batch(5, 1)));
+
+        // Everything from the corrupt batch on is dropped
+        let (segment, truncated) = Segment::recover(backend.clone(), 0).unwrap();
+        assert_eq!(truncated, (corrupt.len() + entry_size(&batch(5, 1))) as u64);
+        assert_eq!(segment.size(), size);
+        assert_eq!(segment.next_offset(), 3);
+    }
+
+    #[test]
+    fn test_recover_stale_index() {
+        let backend = Arc::new(MemBackend::new());
+        let mut segment = Segment::open(backend.clone(), 0).unwrap();
+        segment.append(&batch(0, 3)).unwrap();
+        drop(segment);
+
+        // The batch made it, its index entries didn't
+        append_raw(&backend, &entry(&batch(3, 2)));
+
+        let (segment, truncated) = Segment::recover(backend.clone(), 0).unwrap();
+        assert_eq!(truncated, 0);
+        assert_eq!(segment.next_offset(), 5);
+        assert_eq!(segment.read(3, usize::MAX).unwrap(), vec![batch(3, 2)]);
+    }
+
+    #[test]
+    fn test_corrupt_entry() {
+        let backend = Arc::new(MemBackend::new());
+        let mut segment = Segment::open(backend.clone(), 0).unwrap();
+        segment.append(&batch(0, 3)).unwrap();
+        segment.append(&batch(3, 2)).unwrap();
+        segment.append(&batch(5, 1)).unwrap();
+        drop(segment);
+
+        // Flip a byte inside the middle batch
+        let name = segment_name(0, LOG_SUFFIX);
+        let mut raw = vec![0u8; backend.open(&name).unwrap() as usize];
+        backend.read(&name, 0, &mut raw).unwrap();
+        let position = entry_size(&batch(0, 3));
+        raw[position + ENTRY_HEADER_SIZE + 20] ^= 0xFF;
+        backend.remove(&name).unwrap();
+        append_raw(&backend, &raw);
+
+        let segment = Segment::open(backend.clone(), 0).unwrap();
+        assert_eq!(segment.read(5, usize::MAX).unwrap(), vec![batch(5, 1)]);
+        assert!(matches!(
+            segment.read(3, usize::MAX),
+            Err(LogError::CorruptSegment { base_offset: 0, position: p }) if p == position as u64
+        ));
+    }
+}
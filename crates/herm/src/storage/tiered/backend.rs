@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex, RwLock};
+
+use super::ObjectStore;
+use crate::storage::Backend;
+
+/// Remote reads are made in blocks of this many bytes, so scanning a segment
+/// front to back takes one request per block rather than two per batch.
+const READ_BLOCK_SIZE: u64 = 1024 * 1024;
+
+/// Keeps files in a local backend until they are sealed, then moves them to
+/// an object store. Local disk only holds the active segment and the indexes,
+/// older batches are read back from the store.
+#[derive(Debug)]
+pub struct TieredBackend {
+    local: Arc<dyn Backend>,
+    remote: Arc<dyn ObjectStore>,
+    /// Sizes of the files held remotely.
+    offloaded: RwLock<HashMap<String, u64>>,
+    /// Last block read from the store.
+    block: Mutex<Option<Block>>,
+}
+
+#[derive(Debug)]
+struct Block {
+    name: String,
+    start: u64,
+    bytes: Arc<Vec<u8>>,
+}
+
+impl TieredBackend {
+    pub fn new(local: Arc<dyn Backend>, remote: Arc<dyn ObjectStore>) -> io::Result<Self> {
+        let offloaded = remote.list()?.into_iter().collect();
+        Ok(TieredBackend {
+            local,
+            remote,
+            offloaded: RwLock::new(offloaded),
+            block: Mutex::new(None),
+        })
+    }
+
+    /// Whether `name` has been moved to the object store.
+    pub fn is_offloaded(&self, name: &str) -> bool {
+        self.offloaded.read().unwrap().contains_key(name)
+    }
+
+    fn offloaded_size(&self, name: &str) -> Option<u64> {
+        self.offloaded.read().unwrap().get(name).copied()
+    }
+
+    fn read_block(&self, name: &str, start: u64, size: u64) -> io::Result<Arc<Vec<u8>>> {
+        let mut block = self.block.lock().unwrap();
+        if let Some(cached) = &*block {
+            if cached.name == name && cached.start == start {
+                return Ok(cached.bytes.clone());
+            }
+        }
+
+        let len = READ_BLOCK_SIZE.min(size - start) as usize;
+        let bytes = Arc::new(self.remote.get_range(name, start, len)?);
+        *block = Some(Block {
+            name: name.to_string(),
+            start,
+            bytes: bytes.clone(),
+        });
+        Ok(bytes)
+    }
+
+    fn forget(&self, name: &str) {
+        self.offloaded.write().unwrap().remove(name);
+        let mut block = self.block.lock().unwrap();
+        if matches!(&*block, Some(cached) if cached.name == name) {
+            *block = None;
+        }
+    }
+}
+
+fn offloaded_error(name: &str) -> io::Error {
+    io::Error::other(format!("{} is offloaded and can't be changed", name))
+}
+
+impl Backend for TieredBackend {
+    fn open(&self, name: &str) -> io::Result<u64> {
+        match self.offloaded_size(name) {
+            Some(size) => Ok(size),
+            None => self.local.open(name),
+        }
+    }
+
+    fn append(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        if self.is_offloaded(name) {
+            return Err(offloaded_error(name));
+        }
+        self.local.append(name, data)
+    }
+
+    fn read(&self, name: &str, position: u64, buf: &mut [u8]) -> io::Result<()> {
+        let Some(size) = self.offloaded_size(name) else {
+            return self.local.read(name, position, buf);
+        };
+        if position + buf.len() as u64 > size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "read past end of offloaded file",
+            ));
+        }
+
+        let mut filled = 0;
+        while filled < buf.len() {
+            let at = position + filled as u64;
+            let start = at - at % READ_BLOCK_SIZE;
+            let block = self.read_block(name, start, size)?;
+
+            let from = (at - start) as usize;
+            let len = (block.len() - from).min(buf.len() - filled);
+            buf[filled..filled + len].copy_from_slice(&block[from..from + len]);
+            filled += len;
+        }
+        Ok(())
+    }
+
+    fn truncate(&self, name: &str, len: u64) -> io::Result<()> {
+        if self.is_offloaded(name) {
+            return Err(offloaded_error(name));
+        }
+        self.local.truncate(name, len)
+    }
+
+    fn flush(&self, name: &str) -> io::Result<()> {
+        if self.is_offloaded(name) {
+            return Ok(());
+        }
+        self.local.flush(name)
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        let mut names = self.local.list()?;
+        for name in self.offloaded.read().unwrap().keys() {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        Ok(names)
+    }
+
+    fn remove(&self, name: &str) -> io::Result<()> {
+        if self.is_offloaded(name) {
+            self.remote.delete(name)?;
+            self.forget(name);
+        }
+        self.local.remove(name)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        if self.is_offloaded(from) {
+            return Err(offloaded_error(from));
+        }
+        // Compaction renames a rewritten segment over an offloaded one
+        if self.is_offloaded(to) {
+            self.remote.delete(to)?;
+            self.forget(to);
+        }
+        self.local.rename(from, to)
+    }
+
+    /// Uploads `name` and drops the local copy.
+    fn seal(&self, name: &str) -> io::Result<()> {
+        if self.is_offloaded(name) {
+            return Ok(());
+        }
+
+        let size = self.local.open(name)?;
+        let mut data = vec![0u8; size as usize];
+        self.local.read(name, 0, &mut data)?;
+        self.remote.put(name, data)?;
+
+        // Offloaded before the local copy goes, so a crash in between leaves
+        // the file in both places rather than neither
+        self.offloaded
+            .write()
+            .unwrap()
+            .insert(name.to_string(), size);
+        self.local.remove(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bytes::Bytes;
+
+    use crate::record::{Record, RecordBatch};
+    use crate::storage::tiered::MemStore;
+    use crate::storage::{Log, LogConfig, MemBackend};
+
+    fn batch(value: &'static str) -> RecordBatch {
+        RecordBatch::new(vec![Record::new(
+            None,
+            Some(Bytes::from_static(value.as_bytes())),
+        )])
+    }
+
+    fn open(local: &MemBackend, remote: &MemStore) -> Log {
+        let backend =
+            TieredBackend::new(Arc::new(local.clone()), Arc::new(remote.clone())).unwrap();
+        let config = LogConfig {
+            segment_bytes: 1,
+            ..Default::default()
+        };
+        Log::with_backend(Arc::new(backend), config).unwrap()
+    }
+
+    fn log_files(names: impl IntoIterator<Item = String>) -> Vec<String> {
+        let mut names: Vec<_> = names
+            .into_iter()
+            .filter(|name| name.ends_with(".log"))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_offload_sealed_segments() {
+        let (local, remote) = (MemBackend::new(), MemStore::new());
+        let mut log = open(&local, &remote);
+        for value in ["a", "b", "c"] {
+            log.append(batch(value)).unwrap();
+        }
+        assert_eq!(log.segment_count(), 3);
+
+        // Only the active segment stays local
+        assert_eq!(
+            log_files(local.list().unwrap()),
+            vec!["00000000000000000002.log"]
+        );
+        assert_eq!(
+            log_files(remote.list().unwrap().into_iter().map(|(key, _)| key)),
+            vec!["00000000000000000000.log", "00000000000000000001.log"]
+        );
+
+        let read = log.read(1, usize::MAX).unwrap();
+        assert_eq!(read[0].records[0].value, Some(Bytes::from_static(b"b")));
+
+        drop(log);
+        let log = open(&local, &remote);
+        assert_eq!(log.next_offset(), 3);
+        let read = log.read(0, usize::MAX).unwrap();
+        assert_eq!(read[0].records[0].value, Some(Bytes::from_static(b"a")));
+    }
+
+    #[test]
+    fn test_retention_deletes_remote() {
+        let (local, remote) = (MemBackend::new(), MemStore::new());
+        let mut log = open(&local, &remote);
+        for value in ["a", "b", "c"] {
+            log.append(batch(value)).unwrap();
+        }
+
+        let (backend, mut config) = (log.backend().clone(), log.config().clone());
+        drop(log);
+
+        config.retention_bytes = Some(0);
+        let mut log = Log::with_backend(backend, config).unwrap();
+        assert_eq!(log.enforce_retention().unwrap(), 2);
+        assert!(log_files(remote.list().unwrap().into_iter().map(|(key, _)| key)).is_empty());
+    }
+
+    #[test]
+    fn test_block_reads() {
+        let (local, remote) = (MemBackend::new(), MemStore::new());
+        let data: Vec<u8> = (0..3 * READ_BLOCK_SIZE).map(|i| i as u8).collect();
+        local.append("file", &data).unwrap();
+
+        let backend = TieredBackend::new(Arc::new(local), Arc::new(remote)).unwrap();
+        backend.seal("file").unwrap();
+        assert!(backend.is_offloaded("file"));
+
+        // Spans a block boundary
+        let mut buf = vec![0u8; 100];
+        let position = READ_BLOCK_SIZE - 50;
+        backend.read("file", position, &mut buf).unwrap();
+        assert_eq!(buf, data[position as usize..position as usize + 100]);
+
+        assert!(backend.append("file", b"more").is_err());
+        assert_eq!(
+            backend
+                .read("file", 3 * READ_BLOCK_SIZE - 10, &mut buf)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
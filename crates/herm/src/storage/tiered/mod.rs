@@ -0,0 +1,6 @@
+mod backend;
+mod s3;
+mod store;
+pub use backend::TieredBackend;
+pub use s3::{S3Config, S3Store};
+pub use store::{MemStore, ObjectStore};
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, RwLock};
+
+/// Bucket of objects, like S3. Objects are written whole and read back by
+/// range. A store holds the files of one log, stores shared between logs are
+/// expected to scope keys with a prefix.
+pub trait ObjectStore: Send + Sync + std::fmt::Debug {
+    /// Writes `data` as `key`, replacing any object already there.
+    fn put(&self, key: &str, data: Vec<u8>) -> io::Result<()>;
+
+    /// Reads `len` bytes of `key` starting at `position`.
+    fn get_range(&self, key: &str, position: u64, len: usize) -> io::Result<Vec<u8>>;
+
+    /// Every key held, with the size of its object.
+    fn list(&self) -> io::Result<Vec<(String, u64)>>;
+
+    /// Removes `key`. Removing a key that doesn't exist isn't an error.
+    fn delete(&self, key: &str) -> io::Result<()>;
+}
+
+/// Keeps objects in memory, for tests. Clones share the same objects.
+#[derive(Debug, Clone, Default)]
+pub struct MemStore {
+    objects: Arc<RwLock<HashMap<String, Vec<u8>>>>,
+}
+
+impl MemStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ObjectStore for MemStore {
+    fn put(&self, key: &str, data: Vec<u8>) -> io::Result<()> {
+        self.objects.write().unwrap().insert(key.to_string(), data);
+        Ok(())
+    }
+
+    fn get_range(&self, key: &str, position: u64, len: usize) -> io::Result<Vec<u8>> {
+        let objects = self.objects.read().unwrap();
+        let object = objects.get(key).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("no object named {}", key))
+        })?;
+
+        let start = position as usize;
+        object
+            .get(start..start + len)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "read past end of object"))
+    }
+
+    fn list(&self) -> io::Result<Vec<(String, u64)>> {
+        let objects = self.objects.read().unwrap();
+        Ok(objects
+            .iter()
+            .map(|(key, object)| (key.clone(), object.len() as u64))
+            .collect())
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        self.objects.write().unwrap().remove(key);
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use super::Backend;
+
+/// Maps timestamps to the file position of the first batch that raised the
+/// segment's max timestamp to them. Entries are only written when the max
+/// timestamp grows, so they are sorted both ways and can be binary searched.
+#[derive(Debug)]
+pub(super) struct TimeIndex {
+    backend: Arc<dyn Backend>,
+    name: String,
+    entries: Vec<(u64, u32)>,
+}
+
+impl TimeIndex {
+    pub(super) const ENTRY_SIZE: usize = 8 + 4;
+
+    pub fn open(backend: Arc<dyn Backend>, name: String) -> std::io::Result<Self> {
+        let mut raw = vec![0u8; backend.open(&name)? as usize];
+        backend.read(&name, 0, &mut raw)?;
+        let entries = Self::decode(&raw);
+
+        Ok(TimeIndex {
+            backend,
+            name,
+            entries,
+        })
+    }
+
+    /// Entries of a time index file, ignoring a torn one at the end.
+    pub(super) fn decode(raw: &[u8]) -> Vec<(u64, u32)> {
+        raw.chunks_exact(Self::ENTRY_SIZE)
+            .map(|entry| {
+                (
+                    u64::from_be_bytes(entry[0..8].try_into().unwrap()),
+                    u32::from_be_bytes(entry[8..12].try_into().unwrap()),
+                )
+            })
+            .collect()
+    }
+
+    /// Records a batch with `max_timestamp` at `position`. Batches that don't
+    /// raise the max timestamp are skipped.
+    pub fn maybe_append(&mut self, max_timestamp: u64, position: u32) -> std::io::Result<()> {
+        if matches!(self.max_timestamp(), Some(max) if max_timestamp <= max) {
+            return Ok(());
+        }
+
+        let mut entry = [0u8; Self::ENTRY_SIZE];
+        entry[0..8].copy_from_slice(&max_timestamp.to_be_bytes());
+        entry[8..12].copy_from_slice(&position.to_be_bytes());
+        self.backend.append(&self.name, &entry)?;
+
+        self.entries.push((max_timestamp, position));
+        Ok(())
+    }
+
+    /// Position of the first batch that may hold a record at or after
+    /// `timestamp`, or `None` if every record in the segment is older.
+    pub fn lookup(&self, timestamp: u64) -> Option<u32> {
+        let idx = self.entries.partition_point(|(max, _)| *max < timestamp);
+        self.entries.get(idx).map(|(_, position)| *position)
+    }
+
+    pub fn max_timestamp(&self) -> Option<u64> {
+        self.entries.last().map(|(max, _)| *max)
+    }
+
+    pub fn flush(&self) -> std::io::Result<()> {
+        self.backend.flush(&self.name)
+    }
+
+    /// Drops every entry, on disk too.
+    pub fn clear(&mut self) -> std::io::Result<()> {
+        self.backend.truncate(&self.name, 0)?;
+        self.entries.clear();
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::storage::MemBackend;
+
+    #[test]
+    fn test_lookup() {
+        let mut index = TimeIndex::open(Arc::new(MemBackend::new()), "0.timeindex".into()).unwrap();
+        assert_eq!(index.lookup(0), None);
+
+        index.maybe_append(100, 0).unwrap();
+        index.maybe_append(300, 50).unwrap();
+        // Doesn't raise the max, so not indexed
+        index.maybe_append(200, 80).unwrap();
+        index.maybe_append(400, 120).unwrap();
+
+        assert_eq!(index.lookup(0), Some(0));
+        assert_eq!(index.lookup(100), Some(0));
+        assert_eq!(index.lookup(101), Some(50));
+        assert_eq!(index.lookup(300), Some(50));
+        assert_eq!(index.lookup(301), Some(120));
+        assert_eq!(index.lookup(401), None);
+        assert_eq!(index.max_timestamp(), Some(400));
+    }
+
+    #[test]
+    fn test_reopen() {
+        let backend = Arc::new(MemBackend::new());
+
+        let mut index = TimeIndex::open(backend.clone(), "0.timeindex".into()).unwrap();
+        index.maybe_append(100, 0).unwrap();
+        index.maybe_append(200, 42).unwrap();
+        drop(index);
+
+        let index = TimeIndex::open(backend.clone(), "0.timeindex".into()).unwrap();
+        assert_eq!(index.lookup(150), Some(42));
+        assert_eq!(index.max_timestamp(), Some(200));
+    }
+}
@@ -0,0 +1,161 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io;
+use std::os::fd::AsRawFd;
+
+use io_uring::{opcode, squeue, types, IoUring};
+
+/// Entries of each thread's ring. Only one operation is in flight at a time.
+const RING_ENTRIES: u32 = 8;
+
+/// Offset telling the kernel to use, and advance, the file position, which
+/// for files opened to append is always the end.
+const CURRENT_POSITION: u64 = u64::MAX;
+
+thread_local! {
+    /// Every thread doing I/O gets a ring of its own, so threads never wait
+    /// on each other to submit.
+    static RING: RefCell<Option<IoUring>> = const { RefCell::new(None) };
+}
+
+/// Writes all of `data` at the end of `file`, which must be opened to append.
+pub(super) fn append(file: &File, data: &[u8]) -> io::Result<()> {
+    let mut written = 0;
+    while written < data.len() {
+        let rest = &data[written..];
+        let entry = opcode::Write::new(
+            types::Fd(file.as_raw_fd()),
+            rest.as_ptr(),
+            rest.len().min(u32::MAX as usize) as u32,
+        )
+        .offset(CURRENT_POSITION)
+        .build();
+        // Safety: `rest` outlives the operation, `submit` waits for it
+        match unsafe { submit(entry) }? {
+            0 => return Err(io::ErrorKind::WriteZero.into()),
+            n => written += n as usize,
+        }
+    }
+    Ok(())
+}
+
+/// Fills `buf` from `file` starting at `position`, like
+/// [`FileExt::read_exact_at`](std::os::unix::fs::FileExt::read_exact_at).
+pub(super) fn read_exact_at(file: &File, buf: &mut [u8], position: u64) -> io::Result<()> {
+    let mut read = 0;
+    while read < buf.len() {
+        let rest = &mut buf[read..];
+        let entry = opcode::Read::new(
+            types::Fd(file.as_raw_fd()),
+            rest.as_mut_ptr(),
+            rest.len().min(u32::MAX as usize) as u32,
+        )
+        .offset(position + read as u64)
+        .build();
+        // Safety: `rest` outlives the operation, `submit` waits for it
+        match unsafe { submit(entry) }? {
+            0 => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "failed to fill whole buffer",
+                ))
+            }
+            n => read += n as usize,
+        }
+    }
+    Ok(())
+}
+
+/// Makes the data written to `file` durable, like [`File::sync_data`].
+pub(super) fn sync_data(file: &File) -> io::Result<()> {
+    let entry = opcode::Fsync::new(types::Fd(file.as_raw_fd()))
+        .flags(types::FsyncFlags::DATASYNC)
+        .build();
+    // Safety: no buffers involved
+    unsafe { submit(entry) }.map(|_| ())
+}
+
+/// Runs `entry` on this thread's ring and waits for it to complete, returning
+/// its result.
+///
+/// # Safety
+///
+/// Any buffer `entry` points to must stay valid until this returns.
+unsafe fn submit(entry: squeue::Entry) -> io::Result<u32> {
+    RING.with(|ring| {
+        let mut ring = ring.borrow_mut();
+        if ring.is_none() {
+            *ring = Some(IoUring::new(RING_ENTRIES)?);
+        }
+        let uring = ring.as_mut().unwrap();
+
+        // The ring is only ever used here, and empty between calls
+        uring
+            .submission()
+            .push(&entry)
+            .map_err(|_| io::Error::other("io_uring submission queue full"))?;
+        loop {
+            match uring.submit_and_wait(1) {
+                Ok(_) => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    // Whether the entry went in is unknown, and it must not
+                    // run later against a buffer that is gone by then
+                    *ring = None;
+                    return Err(err);
+                }
+            }
+        }
+
+        let result = uring
+            .completion()
+            .next()
+            .expect("completion after waiting for it")
+            .result();
+        if result < 0 {
+            Err(io::Error::from_raw_os_error(-result))
+        } else {
+            Ok(result as u32)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::OpenOptions;
+
+    #[test]
+    fn test_io() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(dir.path().join("a"))
+            .unwrap();
+        append(&file, b"hello").unwrap();
+        append(&file, b" world").unwrap();
+        sync_data(&file).unwrap();
+
+        let mut buf = [0u8; 5];
+        read_exact_at(&file, &mut buf, 6).unwrap();
+        assert_eq!(&buf, b"world");
+        assert_eq!(
+            read_exact_at(&file, &mut buf, 7).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        // Threads get rings of their own
+        let file = std::sync::Arc::new(file);
+        let reader = file.clone();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 11];
+            read_exact_at(&reader, &mut buf, 0).unwrap();
+            assert_eq!(&buf, b"hello world");
+        })
+        .join()
+        .unwrap();
+    }
+}
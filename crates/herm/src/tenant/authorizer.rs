@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use super::Namespaces;
+use crate::auth::{Authorizer, Operation, Resource};
+use crate::broker::Principal;
+
+/// Scopes another [`Authorizer`] to namespaces: topics in a namespace are
+/// only open to the principals it lists, and only as far as `inner` allows.
+/// Everything else is left to `inner`.
+#[derive(Debug, Clone)]
+pub struct NamespaceAuthorizer {
+    namespaces: Arc<Namespaces>,
+    inner: Arc<dyn Authorizer>,
+}
+
+impl NamespaceAuthorizer {
+    pub fn new(namespaces: Arc<Namespaces>, inner: impl Authorizer) -> Self {
+        NamespaceAuthorizer {
+            namespaces,
+            inner: Arc::new(inner),
+        }
+    }
+}
+
+impl Authorizer for NamespaceAuthorizer {
+    fn authorize(&self, principal: &Principal, operation: Operation, resource: Resource) -> bool {
+        if let Resource::Topic(topic) = resource {
+            // Anonymous clients belong to no namespace
+            let name = match principal {
+                Principal::User(name) => name.as_str(),
+                Principal::Anonymous => "",
+            };
+            if self.namespaces.authorize(name, topic).is_err() {
+                return false;
+            }
+        }
+        self.inner.authorize(principal, operation, resource)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    use crate::auth::{AclAuthorizer, AllowAll};
+    use crate::tenant::NamespaceConfig;
+
+    fn namespaces() -> Arc<Namespaces> {
+        let mut namespaces = Namespaces::new();
+        let config = NamespaceConfig {
+            principals: HashSet::from(["alice".to_string(), "bob".to_string()]),
+            ..Default::default()
+        };
+        namespaces.register("team-a", config).unwrap();
+        Arc::new(namespaces)
+    }
+
+    #[test]
+    fn test_scopes_topics_to_namespaces() {
+        let authorizer = NamespaceAuthorizer::new(namespaces(), AllowAll);
+        let alice = Principal::User("alice".to_string());
+        let carol = Principal::User("carol".to_string());
+
+        assert!(authorizer.authorize(&alice, Operation::Write, Resource::Topic("team-a/orders")));
+        assert!(!authorizer.authorize(&carol, Operation::Write, Resource::Topic("team-a/orders")));
+        assert!(!authorizer.authorize(
+            &Principal::Anonymous,
+            Operation::Read,
+            Resource::Topic("team-a/orders")
+        ));
+        // Unknown namespaces are closed to everyone
+        assert!(!authorizer.authorize(&alice, Operation::Read, Resource::Topic("team-b/orders")));
+
+        // Outside namespaces it is up to the inner authorizer
+        assert!(authorizer.authorize(&carol, Operation::Write, Resource::Topic("orders")));
+        assert!(authorizer.authorize(&carol, Operation::Alter, Resource::Cluster));
+    }
+
+    #[test]
+    fn test_inner_still_applies() {
+        let acls = AclAuthorizer::parse("allow User:alice read topic:*").unwrap();
+        let authorizer = NamespaceAuthorizer::new(namespaces(), acls);
+        let alice = Principal::User("alice".to_string());
+        let bob = Principal::User("bob".to_string());
+
+        assert!(authorizer.authorize(&alice, Operation::Read, Resource::Topic("team-a/orders")));
+        assert!(!authorizer.authorize(&alice, Operation::Write, Resource::Topic("team-a/orders")));
+        assert!(!authorizer.authorize(&bob, Operation::Read, Resource::Topic("team-a/orders")));
+    }
+}
@@ -0,0 +1,4 @@
+mod authorizer;
+mod namespace;
+pub use authorizer::NamespaceAuthorizer;
+pub use namespace::{NamespaceConfig, NamespaceError, NamespaceQuota, Namespaces};
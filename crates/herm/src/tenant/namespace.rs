@@ -0,0 +1,311 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use thiserror::Error;
+
+use crate::protocol::split_namespace;
+use crate::storage::{LogConfig, TopicConfigError};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum NamespaceError {
+    #[error("Unknown namespace {0}")]
+    UnknownNamespace(String),
+    #[error("Namespace {0} already exists")]
+    AlreadyExists(String),
+    #[error("Namespace {namespace} is over its {limit} quota")]
+    QuotaExceeded {
+        namespace: String,
+        limit: &'static str,
+    },
+    #[error("Principal {principal} is not allowed in namespace {namespace}")]
+    AccessDenied {
+        principal: String,
+        namespace: String,
+    },
+    #[error(transparent)]
+    TopicConfig(#[from] TopicConfigError),
+}
+
+/// Limits applied to a namespace as a whole. `None` means unlimited.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NamespaceQuota {
+    pub max_topics: Option<usize>,
+    pub max_partitions: Option<u32>,
+    pub produce_bytes_per_sec: Option<u64>,
+    pub fetch_bytes_per_sec: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NamespaceConfig {
+    pub quota: NamespaceQuota,
+    /// Topic configs applied to topics created in the namespace, unless
+    /// overridden at creation.
+    pub topic_defaults: HashMap<String, String>,
+    /// Principals allowed to touch topics in the namespace.
+    pub principals: HashSet<String>,
+}
+
+#[derive(Debug, Default)]
+struct NamespaceState {
+    config: NamespaceConfig,
+    /// Partitions of each topic in the namespace.
+    topics: Mutex<HashMap<String, u32>>,
+}
+
+/// Registry of tenant namespaces. Topics without a `tenant/` prefix live
+/// outside of any namespace and are not restricted here.
+///
+/// Namespaces are registered up front. The broker then scopes access to
+/// their topics with a [`NamespaceAuthorizer`](super::NamespaceAuthorizer),
+/// counts their topics as they are created and deleted, and throttles
+/// their byte rates along with the per-client quotas.
+#[derive(Debug, Default)]
+pub struct Namespaces {
+    namespaces: HashMap<String, NamespaceState>,
+}
+
+impl Namespaces {
+    pub fn new() -> Self {
+        Namespaces::default()
+    }
+
+    pub fn register(&mut self, name: &str, config: NamespaceConfig) -> Result<(), NamespaceError> {
+        if self.namespaces.contains_key(name) {
+            return Err(NamespaceError::AlreadyExists(name.to_string()));
+        }
+
+        self.namespaces.insert(
+            name.to_string(),
+            NamespaceState {
+                config,
+                topics: Mutex::new(HashMap::new()),
+            },
+        );
+        Ok(())
+    }
+
+    pub fn config(&self, name: &str) -> Option<&NamespaceConfig> {
+        self.namespaces.get(name).map(|state| &state.config)
+    }
+
+    /// Config for the namespace owning `topic`, if it has one.
+    pub fn config_for_topic(
+        &self,
+        topic: &str,
+    ) -> Result<Option<&NamespaceConfig>, NamespaceError> {
+        match split_namespace(topic).0 {
+            Some(namespace) => Ok(Some(&self.state(namespace)?.config)),
+            None => Ok(None),
+        }
+    }
+
+    /// Namespace owning `topic` and its quota, if it is a known one.
+    pub fn quota_for_topic<'a>(&'a self, topic: &'a str) -> Option<(&'a str, &'a NamespaceQuota)> {
+        let namespace = split_namespace(topic).0?;
+        let state = self.namespaces.get(namespace)?;
+        Some((namespace, &state.config.quota))
+    }
+
+    /// Merges namespace defaults under the configs given at topic creation.
+    pub fn topic_configs(
+        &self,
+        topic: &str,
+        overrides: &HashMap<String, String>,
+    ) -> Result<HashMap<String, String>, NamespaceError> {
+        let mut configs = self
+            .config_for_topic(topic)?
+            .map(|config| config.topic_defaults.clone())
+            .unwrap_or_default();
+        configs.extend(overrides.iter().map(|(k, v)| (k.clone(), v.clone())));
+        Ok(configs)
+    }
+
+    /// `base` with the [`Namespaces::topic_configs`] of `topic` applied.
+    pub fn log_config(
+        &self,
+        topic: &str,
+        base: &LogConfig,
+        overrides: &HashMap<String, String>,
+    ) -> Result<LogConfig, NamespaceError> {
+        Ok(base.with_topic_configs(&self.topic_configs(topic, overrides)?)?)
+    }
+
+    /// Checks `principal` may access `topic`, scoping ACLs to the namespace.
+    pub fn authorize(&self, principal: &str, topic: &str) -> Result<(), NamespaceError> {
+        let Some(namespace) = split_namespace(topic).0 else {
+            return Ok(());
+        };
+
+        if self.state(namespace)?.config.principals.contains(principal) {
+            Ok(())
+        } else {
+            Err(NamespaceError::AccessDenied {
+                principal: principal.to_string(),
+                namespace: namespace.to_string(),
+            })
+        }
+    }
+
+    /// Accounts for a new topic against its namespace quota.
+    pub fn add_topic(&self, topic: &str, partitions: u32) -> Result<(), NamespaceError> {
+        let Some(namespace) = split_namespace(topic).0 else {
+            return Ok(());
+        };
+
+        let state = self.state(namespace)?;
+        let quota = &state.config.quota;
+        let mut topics = state.topics.lock().unwrap();
+
+        if matches!(quota.max_topics, Some(max) if topics.len() >= max) {
+            return Err(NamespaceError::QuotaExceeded {
+                namespace: namespace.to_string(),
+                limit: "topic count",
+            });
+        }
+
+        if let Some(max) = quota.max_partitions {
+            // Going past u32 is over any quota as well
+            let used = topics
+                .values()
+                .try_fold(partitions, |used, &topic| used.checked_add(topic));
+            if !matches!(used, Some(used) if used <= max) {
+                return Err(NamespaceError::QuotaExceeded {
+                    namespace: namespace.to_string(),
+                    limit: "partition count",
+                });
+            }
+        }
+
+        topics.insert(topic.to_string(), partitions);
+        Ok(())
+    }
+
+    pub fn remove_topic(&self, topic: &str) {
+        if let Some(namespace) = split_namespace(topic).0 {
+            if let Some(state) = self.namespaces.get(namespace) {
+                state.topics.lock().unwrap().remove(topic);
+            }
+        }
+    }
+
+    fn state(&self, namespace: &str) -> Result<&NamespaceState, NamespaceError> {
+        self.namespaces
+            .get(namespace)
+            .ok_or_else(|| NamespaceError::UnknownNamespace(namespace.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    fn namespaces() -> Namespaces {
+        let mut namespaces = Namespaces::new();
+        namespaces
+            .register(
+                "team-a",
+                NamespaceConfig {
+                    quota: NamespaceQuota {
+                        max_topics: Some(2),
+                        max_partitions: Some(4),
+                        ..Default::default()
+                    },
+                    topic_defaults: HashMap::from([
+                        ("retention.ms".to_string(), "1000".to_string()),
+                        ("segment.bytes".to_string(), "1024".to_string()),
+                    ]),
+                    principals: HashSet::from(["alice".to_string()]),
+                },
+            )
+            .unwrap();
+        namespaces
+    }
+
+    #[test]
+    fn test_register_twice() {
+        let mut namespaces = namespaces();
+        assert_eq!(
+            namespaces.register("team-a", NamespaceConfig::default()),
+            Err(NamespaceError::AlreadyExists("team-a".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_topic_configs() {
+        let namespaces = namespaces();
+        let overrides = HashMap::from([("retention.ms".to_string(), "5".to_string())]);
+
+        let configs = namespaces
+            .topic_configs("team-a/orders", &overrides)
+            .unwrap();
+        assert_eq!(configs["retention.ms"], "5");
+        assert_eq!(configs["segment.bytes"], "1024");
+
+        let configs = namespaces.topic_configs("orders", &overrides).unwrap();
+        assert_eq!(configs, overrides);
+
+        let config = namespaces
+            .log_config("team-a/orders", &LogConfig::default(), &overrides)
+            .unwrap();
+        assert_eq!(config.retention_age, Some(Duration::from_millis(5)));
+        assert_eq!(config.segment_bytes, 1024);
+
+        assert_eq!(
+            namespaces.topic_configs("team-b/orders", &overrides),
+            Err(NamespaceError::UnknownNamespace("team-b".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_authorize() {
+        let namespaces = namespaces();
+        assert!(namespaces.authorize("alice", "team-a/orders").is_ok());
+        assert!(namespaces.authorize("bob", "orders").is_ok());
+        assert_eq!(
+            namespaces.authorize("bob", "team-a/orders"),
+            Err(NamespaceError::AccessDenied {
+                principal: "bob".to_string(),
+                namespace: "team-a".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_quotas() {
+        let namespaces = namespaces();
+        namespaces.add_topic("team-a/orders", 3).unwrap();
+
+        assert!(matches!(
+            namespaces.add_topic("team-a/payments", 2),
+            Err(NamespaceError::QuotaExceeded {
+                limit: "partition count",
+                ..
+            })
+        ));
+
+        assert!(matches!(
+            namespaces.add_topic("team-a/payments", u32::MAX),
+            Err(NamespaceError::QuotaExceeded {
+                limit: "partition count",
+                ..
+            })
+        ));
+
+        namespaces.add_topic("team-a/payments", 1).unwrap();
+        assert!(matches!(
+            namespaces.add_topic("team-a/refunds", 0),
+            Err(NamespaceError::QuotaExceeded {
+                limit: "topic count",
+                ..
+            })
+        ));
+
+        // Freeing up a topic makes room again
+        namespaces.remove_topic("team-a/orders");
+        namespaces.add_topic("team-a/refunds", 2).unwrap();
+
+        // Topics outside namespaces are unrestricted
+        namespaces.add_topic("orders", 100).unwrap();
+    }
+}
@@ -0,0 +1,893 @@
+use proptest::prelude::*;
+
+use crate::protocol::{ApiKey, ErrorCode, RequestHeader, TopicPartition};
+use crate::record::{Header, Record, RecordBatch};
+use crate::request::{
+    Acks, BrokerHeartbeat, CreateTopic, DeleteTopic, DescribeCluster, DescribeGroups,
+    DescribeLogDirs, EstimateCleanup, Fetch, Heartbeat, JoinGroup, LeaderAndIsr, LeaveGroup,
+    ListGroups, ListOffsets, Metadata, OffsetCommit, Produce, ReassignPartition, Request,
+    SyncGroup,
+};
+use crate::response::{
+    AdminResponse, BrokerDescription, BrokerMetadata, DescribeClusterResponse,
+    DescribeGroupsResponse, DescribeLogDirsResponse, EstimateCleanupResponse, FetchResponse,
+    GroupDescription, GroupMember, GroupState, JoinGroupResponse, ListGroupsResponse,
+    ListOffsetsResponse, LogDirDescription, MemberDescription, MetadataResponse,
+    PartitionCleanupEstimate, PartitionLogDescription, PartitionMetadata, PartitionOffset,
+    ProduceResponse, Response, SyncGroupResponse, TopicMetadata,
+};
+use crate::storage::CleanupPolicy;
+
+/// Valid topic names, with or without a `tenant/` namespace.
+pub fn any_topic_name() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[a-zA-Z0-9._-]{1,64}",
+        "[a-zA-Z0-9._-]{1,32}/[a-zA-Z0-9._-]{1,32}",
+    ]
+}
+
+pub fn any_client_id() -> impl Strategy<Value = String> {
+    ".{0,32}"
+}
+
+pub fn any_group_id() -> impl Strategy<Value = String> {
+    "[a-zA-Z0-9._-]{1,32}"
+}
+
+pub fn any_member_id() -> impl Strategy<Value = String> {
+    "[a-z0-9-]{1,32}"
+}
+
+fn any_assignment() -> impl Strategy<Value = Vec<TopicPartition>> {
+    prop::collection::vec((any_topic_name(), any::<u32>()), 0..4).prop_map(|assignment| {
+        assignment
+            .into_iter()
+            .map(|(topic, partition)| TopicPartition::new(topic, partition))
+            .collect()
+    })
+}
+
+impl Arbitrary for ApiKey {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop::sample::select(ApiKey::ALL.to_vec()).boxed()
+    }
+}
+
+impl Arbitrary for RequestHeader {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any::<ApiKey>(), any::<u32>(), any_client_id())
+            .prop_map(|(api_key, correlation_id, client_id)| {
+                RequestHeader::new(api_key, correlation_id, client_id).unwrap()
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for TopicPartition {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any_topic_name(), any::<u32>())
+            .prop_map(|(topic, partition)| TopicPartition::new(topic, partition))
+            .boxed()
+    }
+}
+
+impl Arbitrary for Header {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (".{0,16}", prop::collection::vec(any::<u8>(), 0..64))
+            .prop_map(|(key, value)| Header::new(key, value))
+            .boxed()
+    }
+}
+
+impl Arbitrary for Record {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        let bytes = || prop::option::of(prop::collection::vec(any::<u8>(), 0..64));
+        (
+            any::<u64>(),
+            bytes(),
+            bytes(),
+            prop::collection::vec(any::<Header>(), 0..4),
+        )
+            .prop_map(|(timestamp, key, value, headers)| Record {
+                offset: 0,
+                timestamp,
+                key: key.map(Into::into),
+                value: value.map(Into::into),
+                headers,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for RecordBatch {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            0..u64::MAX / 2,
+            prop::collection::vec(any::<Record>(), 0..8),
+        )
+            .prop_map(|(base_offset, records)| {
+                let mut batch = RecordBatch::new(records);
+                batch.set_base_offset(base_offset);
+                batch
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Fetch {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            any_topic_name(),
+            any::<u32>(),
+            any::<u64>(),
+            any::<u32>(),
+            any::<u32>(),
+            any::<u32>(),
+            any::<Option<u32>>(),
+            any::<Option<u32>>(),
+        )
+            .prop_map(
+                |(topic, partition, offset, size, min_bytes, max_wait_ms, replica_id, epoch)| {
+                    let fetch = Fetch::new(topic, partition, offset, size)
+                        .unwrap()
+                        .wait_for(min_bytes, max_wait_ms);
+                    let fetch = match replica_id {
+                        Some(replica_id) => fetch.from_replica(replica_id),
+                        None => fetch,
+                    };
+                    match epoch {
+                        Some(epoch) => fetch.with_leader_epoch(epoch),
+                        None => fetch,
+                    }
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for ListOffsets {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any_topic_name(), any::<u32>(), any::<u64>())
+            .prop_map(|(topic, partition, timestamp)| {
+                ListOffsets::new(topic, partition, timestamp).unwrap()
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Produce {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            any_topic_name(),
+            any::<u32>(),
+            prop_oneof![Just(Acks::None), Just(Acks::Leader), Just(Acks::All)],
+            any::<u32>(),
+            any::<Option<u32>>(),
+            any::<RecordBatch>(),
+        )
+            .prop_map(|(topic, partition, acks, timeout_ms, epoch, batch)| {
+                let produce = Produce::new(topic, partition, batch)
+                    .unwrap()
+                    .with_acks(acks)
+                    .with_timeout(timeout_ms);
+                match epoch {
+                    Some(epoch) => produce.with_leader_epoch(epoch),
+                    None => produce,
+                }
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for LeaderAndIsr {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            any_topic_name(),
+            any::<u32>(),
+            any::<u32>(),
+            prop::option::of("[a-z0-9.-]{1,32}:[0-9]{1,5}"),
+        )
+            .prop_map(|(topic, partition, epoch, leader)| match leader {
+                Some(leader) => LeaderAndIsr::follow(topic, partition, epoch, leader).unwrap(),
+                None => LeaderAndIsr::lead(topic, partition, epoch).unwrap(),
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for ReassignPartition {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            any_topic_name(),
+            any::<u32>(),
+            any::<u32>(),
+            "[a-z0-9.-]{1,32}:[0-9]{1,5}",
+            any::<u32>(),
+        )
+            .prop_map(|(topic, partition, target_id, target, timeout_ms)| {
+                ReassignPartition::new(topic, partition, target_id, target)
+                    .unwrap()
+                    .with_timeout(timeout_ms)
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Metadata {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop::collection::vec(any_topic_name(), 0..4)
+            .prop_map(|topics| Metadata::new(topics).unwrap())
+            .boxed()
+    }
+}
+
+impl Arbitrary for BrokerHeartbeat {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            any::<u32>(),
+            prop::option::of("[a-z0-9-]{1,16}"),
+            prop::collection::vec((any_topic_name(), any::<u32>(), any::<u32>()), 0..4),
+        )
+            .prop_map(|(broker_id, rack, leaders)| {
+                let leaders = leaders
+                    .into_iter()
+                    .map(|(topic, partition, epoch)| (TopicPartition::new(topic, partition), epoch))
+                    .collect();
+                BrokerHeartbeat::new(broker_id, leaders)
+                    .unwrap()
+                    .with_rack(rack)
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for DescribeLogDirs {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop::collection::vec(any_topic_name(), 0..4)
+            .prop_map(|topics| DescribeLogDirs::new(topics).unwrap())
+            .boxed()
+    }
+}
+
+impl Arbitrary for OffsetCommit {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            any_group_id(),
+            prop::collection::vec((any_topic_name(), any::<u32>(), any::<u64>()), 0..4),
+        )
+            .prop_map(|(group, offsets)| {
+                let offsets = offsets
+                    .into_iter()
+                    .map(|(topic, partition, offset)| {
+                        (TopicPartition::new(topic, partition), offset)
+                    })
+                    .collect();
+                OffsetCommit::new(group, offsets).unwrap()
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for DescribeGroups {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop::collection::vec(any_group_id(), 0..4)
+            .prop_map(|groups| DescribeGroups::new(groups).unwrap())
+            .boxed()
+    }
+}
+
+impl Arbitrary for JoinGroup {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            any_group_id(),
+            prop_oneof![Just(String::new()), any_member_id()],
+            any::<u32>(),
+            any::<u32>(),
+            prop::collection::vec(any_topic_name(), 0..4),
+            prop::collection::vec("[a-z-]{1,32}", 0..3),
+            any_assignment(),
+        )
+            .prop_map(
+                |(group, member_id, session, rebalance, topics, assignors, owned)| {
+                    JoinGroup::new(
+                        group, member_id, session, rebalance, topics, assignors, owned,
+                    )
+                    .unwrap()
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for SyncGroup {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            any_group_id(),
+            any::<u32>(),
+            any_member_id(),
+            prop::collection::vec((any_member_id(), any_assignment()), 0..3),
+        )
+            .prop_map(|(group, generation, member_id, assignments)| {
+                SyncGroup::new(group, generation, member_id, assignments).unwrap()
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Heartbeat {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any_group_id(), any::<u32>(), any_member_id())
+            .prop_map(|(group, generation, member_id)| {
+                Heartbeat::new(group, generation, member_id).unwrap()
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for LeaveGroup {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any_group_id(), any_member_id())
+            .prop_map(|(group, member_id)| LeaveGroup::new(group, member_id).unwrap())
+            .boxed()
+    }
+}
+
+impl Arbitrary for CreateTopic {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any_topic_name(), 1..=u32::MAX)
+            .prop_map(|(topic, partitions)| CreateTopic::new(topic, partitions).unwrap())
+            .boxed()
+    }
+}
+
+impl Arbitrary for DeleteTopic {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        any_topic_name()
+            .prop_map(|topic| DeleteTopic::new(topic).unwrap())
+            .boxed()
+    }
+}
+
+impl Arbitrary for EstimateCleanup {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            any_topic_name(),
+            prop::option::of(prop::sample::select(vec![
+                CleanupPolicy::Delete,
+                CleanupPolicy::Compact,
+            ])),
+            prop::option::of(-1i64..i64::MAX),
+            prop::option::of(-1i64..i64::MAX),
+        )
+            .prop_map(|(topic, cleanup_policy, retention_ms, retention_bytes)| {
+                let mut request = EstimateCleanup::new(topic).unwrap();
+                if let Some(cleanup_policy) = cleanup_policy {
+                    request = request.with_cleanup_policy(cleanup_policy);
+                }
+                if let Some(retention_ms) = retention_ms {
+                    request = request.with_retention_ms(retention_ms);
+                }
+                if let Some(retention_bytes) = retention_bytes {
+                    request = request.with_retention_bytes(retention_bytes);
+                }
+                request
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Request {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop_oneof![
+            any::<Produce>().prop_map(Request::Produce),
+            any::<Fetch>().prop_map(Request::Fetch),
+            any::<ListOffsets>().prop_map(Request::ListOffsets),
+            any::<LeaderAndIsr>().prop_map(Request::LeaderAndIsr),
+            any::<ReassignPartition>().prop_map(Request::ReassignPartition),
+            any::<Metadata>().prop_map(Request::Metadata),
+            any::<BrokerHeartbeat>().prop_map(Request::BrokerHeartbeat),
+            Just(Request::DescribeCluster(DescribeCluster::new())),
+            any::<DescribeLogDirs>().prop_map(Request::DescribeLogDirs),
+            any::<OffsetCommit>().prop_map(Request::OffsetCommit),
+            Just(Request::ListGroups(ListGroups::new())),
+            any::<DescribeGroups>().prop_map(Request::DescribeGroups),
+            any::<JoinGroup>().prop_map(Request::JoinGroup),
+            any::<SyncGroup>().prop_map(Request::SyncGroup),
+            any::<Heartbeat>().prop_map(Request::Heartbeat),
+            any::<LeaveGroup>().prop_map(Request::LeaveGroup),
+            any::<CreateTopic>().prop_map(Request::CreateTopic),
+            any::<DeleteTopic>().prop_map(Request::DeleteTopic),
+            any::<EstimateCleanup>().prop_map(Request::EstimateCleanup),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for ErrorCode {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        any::<i16>().prop_map(ErrorCode::from_code).boxed()
+    }
+}
+
+impl Arbitrary for ProduceResponse {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any::<ErrorCode>(), any::<u32>(), any::<u64>())
+            .prop_map(|(error, throttle_time_ms, base_offset)| ProduceResponse {
+                error,
+                throttle_time_ms,
+                base_offset,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for FetchResponse {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            any::<ErrorCode>(),
+            any::<u32>(),
+            any::<u64>(),
+            any::<u32>(),
+            prop::collection::vec(any::<RecordBatch>(), 0..4),
+        )
+            .prop_map(
+                |(error, throttle_time_ms, high_watermark, leader_epoch, batches)| FetchResponse {
+                    error,
+                    throttle_time_ms,
+                    high_watermark,
+                    leader_epoch,
+                    batches,
+                    slices: vec![],
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for ListOffsetsResponse {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any::<ErrorCode>(), any::<u32>(), any::<u64>())
+            .prop_map(|(error, throttle_time_ms, offset)| ListOffsetsResponse {
+                error,
+                throttle_time_ms,
+                offset,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for AdminResponse {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any::<ErrorCode>(), any::<u32>())
+            .prop_map(|(error, throttle_time_ms)| AdminResponse {
+                error,
+                throttle_time_ms,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for MetadataResponse {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        let broker = (any::<u32>(), "[a-z0-9.-]{1,32}:[0-9]{1,5}")
+            .prop_map(|(id, address)| BrokerMetadata { id, address });
+        let partition = (any::<u32>(), any::<u32>(), any::<u32>()).prop_map(
+            |(partition, leader_id, leader_epoch)| PartitionMetadata {
+                partition,
+                leader_id,
+                leader_epoch,
+            },
+        );
+        let topic = (
+            any::<ErrorCode>(),
+            any_topic_name(),
+            prop::collection::vec(partition, 0..4),
+        )
+            .prop_map(|(error, topic, partitions)| TopicMetadata {
+                error,
+                topic,
+                partitions,
+            });
+        (
+            any::<ErrorCode>(),
+            any::<u32>(),
+            prop::collection::vec(broker, 0..4),
+            prop::collection::vec(topic, 0..4),
+        )
+            .prop_map(
+                |(error, throttle_time_ms, brokers, topics)| MetadataResponse {
+                    error,
+                    throttle_time_ms,
+                    brokers,
+                    topics,
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for DescribeClusterResponse {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        let broker = (
+            any::<u32>(),
+            "[a-z0-9.-]{1,32}:[0-9]{1,5}",
+            "[0-9]{1,2}\\.[0-9]{1,2}\\.[0-9]{1,2}",
+            prop::option::of("[a-z0-9-]{1,16}"),
+        )
+            .prop_map(|(id, address, version, rack)| BrokerDescription {
+                id,
+                address,
+                version,
+                rack,
+            });
+        (
+            any::<ErrorCode>(),
+            any::<u32>(),
+            prop::collection::vec(broker, 0..4),
+        )
+            .prop_map(
+                |(error, throttle_time_ms, brokers)| DescribeClusterResponse {
+                    error,
+                    throttle_time_ms,
+                    brokers,
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for DescribeLogDirsResponse {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        let partition = (
+            any_topic_name(),
+            any::<u32>(),
+            any::<u64>(),
+            any::<u32>(),
+            any::<u64>(),
+            any::<u64>(),
+        )
+            .prop_map(
+                |(topic, partition, size, segments, start_offset, next_offset)| {
+                    PartitionLogDescription {
+                        topic,
+                        partition,
+                        size,
+                        segments,
+                        start_offset,
+                        next_offset,
+                    }
+                },
+            );
+        let dir = (
+            any::<ErrorCode>(),
+            "(/[a-z0-9]{1,8}){1,4}",
+            prop::collection::vec(partition, 0..4),
+        )
+            .prop_map(|(error, path, partitions)| LogDirDescription {
+                error,
+                path,
+                partitions,
+            });
+        (
+            any::<ErrorCode>(),
+            any::<u32>(),
+            prop::collection::vec(dir, 0..4),
+        )
+            .prop_map(|(error, throttle_time_ms, dirs)| DescribeLogDirsResponse {
+                error,
+                throttle_time_ms,
+                dirs,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for EstimateCleanupResponse {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        let partition = (
+            any::<u32>(),
+            any::<u64>(),
+            any::<u32>(),
+            any::<u64>(),
+            any::<u64>(),
+        )
+            .prop_map(
+                |(partition, size, segments, records_removed, reclaimable_bytes)| {
+                    PartitionCleanupEstimate {
+                        partition,
+                        size,
+                        segments,
+                        records_removed,
+                        reclaimable_bytes,
+                    }
+                },
+            );
+        (
+            any::<ErrorCode>(),
+            any::<u32>(),
+            prop::collection::vec(partition, 0..4),
+        )
+            .prop_map(
+                |(error, throttle_time_ms, partitions)| EstimateCleanupResponse {
+                    error,
+                    throttle_time_ms,
+                    partitions,
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for GroupState {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop::sample::select(vec![
+            GroupState::Empty,
+            GroupState::Stable,
+            GroupState::PreparingRebalance,
+            GroupState::CompletingRebalance,
+        ])
+        .boxed()
+    }
+}
+
+impl Arbitrary for ListGroupsResponse {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            any::<ErrorCode>(),
+            any::<u32>(),
+            prop::collection::vec((any_group_id(), any::<GroupState>()), 0..4),
+        )
+            .prop_map(|(error, throttle_time_ms, groups)| ListGroupsResponse {
+                error,
+                throttle_time_ms,
+                groups,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for DescribeGroupsResponse {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        let member = (
+            any_member_id(),
+            any_client_id(),
+            "[0-9.]{7,15}",
+            any_assignment(),
+        )
+            .prop_map(
+                |(member_id, client_id, host, assignment)| MemberDescription {
+                    member_id,
+                    client_id,
+                    host,
+                    assignment,
+                },
+            );
+        let offset = (any_topic_name(), any::<u32>(), any::<u64>(), any::<u64>()).prop_map(
+            |(topic, partition, committed, end_offset)| PartitionOffset {
+                topic,
+                partition,
+                committed,
+                end_offset,
+            },
+        );
+        let group = (
+            any::<ErrorCode>(),
+            any_group_id(),
+            any::<GroupState>(),
+            prop::collection::vec(member, 0..3),
+            prop::collection::vec(offset, 0..4),
+        )
+            .prop_map(|(error, group, state, members, offsets)| GroupDescription {
+                error,
+                group,
+                state,
+                members,
+                offsets,
+            });
+        (
+            any::<ErrorCode>(),
+            any::<u32>(),
+            prop::collection::vec(group, 0..3),
+        )
+            .prop_map(|(error, throttle_time_ms, groups)| DescribeGroupsResponse {
+                error,
+                throttle_time_ms,
+                groups,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for JoinGroupResponse {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        let member = (
+            any_member_id(),
+            prop::collection::vec(any_topic_name(), 0..4),
+            any_assignment(),
+        )
+            .prop_map(|(member_id, topics, owned)| GroupMember {
+                member_id,
+                topics,
+                owned,
+            });
+        (
+            any::<ErrorCode>(),
+            any::<u32>(),
+            any::<u32>(),
+            "[a-z-]{0,32}",
+            any_member_id(),
+            any_member_id(),
+            prop::collection::vec(member, 0..3),
+        )
+            .prop_map(
+                |(error, throttle_time_ms, generation, assignor, leader_id, member_id, members)| {
+                    JoinGroupResponse {
+                        error,
+                        throttle_time_ms,
+                        generation,
+                        assignor,
+                        leader_id,
+                        member_id,
+                        members,
+                    }
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for SyncGroupResponse {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any::<ErrorCode>(), any::<u32>(), any_assignment())
+            .prop_map(|(error, throttle_time_ms, assignment)| SyncGroupResponse {
+                error,
+                throttle_time_ms,
+                assignment,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Response {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop_oneof![
+            any::<ProduceResponse>().prop_map(Response::Produce),
+            any::<FetchResponse>().prop_map(Response::Fetch),
+            any::<ListOffsetsResponse>().prop_map(Response::ListOffsets),
+            any::<AdminResponse>().prop_map(Response::LeaderAndIsr),
+            any::<AdminResponse>().prop_map(Response::ReassignPartition),
+            any::<MetadataResponse>().prop_map(Response::Metadata),
+            any::<AdminResponse>().prop_map(Response::BrokerHeartbeat),
+            any::<DescribeClusterResponse>().prop_map(Response::DescribeCluster),
+            any::<DescribeLogDirsResponse>().prop_map(Response::DescribeLogDirs),
+            any::<AdminResponse>().prop_map(Response::OffsetCommit),
+            any::<ListGroupsResponse>().prop_map(Response::ListGroups),
+            any::<DescribeGroupsResponse>().prop_map(Response::DescribeGroups),
+            any::<JoinGroupResponse>().prop_map(Response::JoinGroup),
+            any::<SyncGroupResponse>().prop_map(Response::SyncGroup),
+            any::<AdminResponse>().prop_map(Response::Heartbeat),
+            any::<AdminResponse>().prop_map(Response::LeaveGroup),
+            any::<AdminResponse>().prop_map(Response::CreateTopic),
+            any::<AdminResponse>().prop_map(Response::DeleteTopic),
+            any::<EstimateCleanupResponse>().prop_map(Response::EstimateCleanup),
+        ]
+        .boxed()
+    }
+}
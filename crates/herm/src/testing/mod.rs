@@ -0,0 +1,4 @@
+mod arbitrary;
+mod round_trip;
+pub use arbitrary::{any_client_id, any_topic_name};
+pub use round_trip::assert_round_trip;
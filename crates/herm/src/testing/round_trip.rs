@@ -0,0 +1,124 @@
+use std::fmt::Debug;
+
+use bytes::Bytes;
+
+/// Encodes `value`, decodes the result and checks both match. Meant to be
+/// called from proptest bodies with types' `to_bytes` and decoder.
+pub fn assert_round_trip<T, E>(
+    value: &T,
+    encode: impl Fn(&T) -> Bytes,
+    decode: impl Fn(Bytes) -> Result<T, E>,
+) where
+    T: Debug + PartialEq,
+    E: Debug,
+{
+    let bytes = encode(value);
+    let decoded = decode(bytes.clone())
+        .unwrap_or_else(|err| panic!("Failed to decode {:?} from {:?}: {:?}", value, bytes, err));
+    assert_eq!(&decoded, value, "Round trip changed value");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proptest::prelude::*;
+
+    use crate::protocol::{inspect, RequestHeader};
+    use crate::record::RecordBatch;
+    use crate::request::{
+        BrokerHeartbeat, CreateTopic, DeleteTopic, DescribeCluster, DescribeGroups,
+        DescribeLogDirs, EstimateCleanup, Fetch, Heartbeat, JoinGroup, LeaderAndIsr, LeaveGroup,
+        ListGroups, ListOffsets, Metadata, OffsetCommit, Produce, ReassignPartition, Request,
+        SyncGroup,
+    };
+    use crate::response::{
+        AdminResponse, DescribeClusterResponse, DescribeGroupsResponse, DescribeLogDirsResponse,
+        EstimateCleanupResponse, FetchResponse, JoinGroupResponse, ListGroupsResponse,
+        ListOffsetsResponse, MetadataResponse, ProduceResponse, Response, SyncGroupResponse,
+    };
+
+    proptest! {
+        #[test]
+        fn fetch_round_trip(fetch in any::<Fetch>()) {
+            assert_round_trip(&fetch, Fetch::to_bytes, Fetch::from_bytes);
+        }
+
+        #[test]
+        fn produce_round_trip(produce in any::<Produce>()) {
+            assert_round_trip(&produce, Produce::to_bytes, Produce::from_bytes);
+        }
+
+        #[test]
+        fn request_round_trip(request in any::<Request>()) {
+            assert_round_trip(&request, Request::to_bytes, |bytes| {
+                Request::decode(request.api_key(), bytes)
+            });
+        }
+
+        #[test]
+        fn response_round_trip(response in any::<Response>()) {
+            assert_round_trip(&response, Response::to_bytes, |bytes| {
+                Response::decode(response.api_key(), bytes)
+            });
+        }
+
+        #[test]
+        fn header_round_trip(header in any::<RequestHeader>()) {
+            assert_round_trip(&header, RequestHeader::to_bytes, |mut bytes| {
+                RequestHeader::decode(&mut bytes)
+            });
+        }
+
+        #[test]
+        fn record_batch_round_trip(batch in any::<RecordBatch>()) {
+            assert_round_trip(&batch, RecordBatch::to_bytes, RecordBatch::from_bytes);
+        }
+
+        #[test]
+        fn decoders_reject_garbage_without_panicking(bytes in prop::collection::vec(any::<u8>(), 0..128)) {
+            let _ = Fetch::from_bytes(Bytes::from(bytes.clone()));
+            let _ = Produce::from_bytes(Bytes::from(bytes.clone()));
+            let _ = ListOffsets::from_bytes(Bytes::from(bytes.clone()));
+            let _ = LeaderAndIsr::from_bytes(Bytes::from(bytes.clone()));
+            let _ = ReassignPartition::from_bytes(Bytes::from(bytes.clone()));
+            let _ = Metadata::from_bytes(Bytes::from(bytes.clone()));
+            let _ = BrokerHeartbeat::from_bytes(Bytes::from(bytes.clone()));
+            let _ = DescribeCluster::from_bytes(Bytes::from(bytes.clone()));
+            let _ = DescribeLogDirs::from_bytes(Bytes::from(bytes.clone()));
+            let _ = OffsetCommit::from_bytes(Bytes::from(bytes.clone()));
+            let _ = ListGroups::from_bytes(Bytes::from(bytes.clone()));
+            let _ = DescribeGroups::from_bytes(Bytes::from(bytes.clone()));
+            let _ = JoinGroup::from_bytes(Bytes::from(bytes.clone()));
+            let _ = SyncGroup::from_bytes(Bytes::from(bytes.clone()));
+            let _ = Heartbeat::from_bytes(Bytes::from(bytes.clone()));
+            let _ = LeaveGroup::from_bytes(Bytes::from(bytes.clone()));
+            let _ = CreateTopic::from_bytes(Bytes::from(bytes.clone()));
+            let _ = DeleteTopic::from_bytes(Bytes::from(bytes.clone()));
+            let _ = EstimateCleanup::from_bytes(Bytes::from(bytes.clone()));
+            let _ = AdminResponse::from_bytes(Bytes::from(bytes.clone()));
+            let _ = MetadataResponse::from_bytes(Bytes::from(bytes.clone()));
+            let _ = DescribeClusterResponse::from_bytes(Bytes::from(bytes.clone()));
+            let _ = DescribeLogDirsResponse::from_bytes(Bytes::from(bytes.clone()));
+            let _ = EstimateCleanupResponse::from_bytes(Bytes::from(bytes.clone()));
+            let _ = ListGroupsResponse::from_bytes(Bytes::from(bytes.clone()));
+            let _ = DescribeGroupsResponse::from_bytes(Bytes::from(bytes.clone()));
+            let _ = JoinGroupResponse::from_bytes(Bytes::from(bytes.clone()));
+            let _ = SyncGroupResponse::from_bytes(Bytes::from(bytes.clone()));
+            let _ = ProduceResponse::from_bytes(Bytes::from(bytes.clone()));
+            let _ = FetchResponse::from_bytes(Bytes::from(bytes.clone()));
+            let _ = ListOffsetsResponse::from_bytes(Bytes::from(bytes.clone()));
+            let _ = RequestHeader::decode(&mut Bytes::from(bytes.clone()));
+            let _ = RecordBatch::from_bytes(Bytes::from(bytes.clone()));
+            let _ = inspect(&bytes);
+        }
+
+        #[test]
+        fn inspect_accepts_valid_requests(mut header in any::<RequestHeader>(), request in any::<Request>()) {
+            header.api_key = request.api_key();
+            let mut bytes = header.to_bytes().to_vec();
+            bytes.extend_from_slice(&request.to_bytes());
+            prop_assert!(inspect(&bytes).is_ok());
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::pem::{self, PemObject};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use tokio_rustls::rustls::server::{VerifierBuilderError, WebPkiClientVerifier};
+use tokio_rustls::rustls::{self, ClientConfig, RootCertStore, ServerConfig};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+#[derive(Error, Debug)]
+pub enum TlsError {
+    #[error("Cannot read {path:?}: {source}")]
+    Pem { path: PathBuf, source: pem::Error },
+    #[error("No certificates in {0:?}")]
+    NoCertificates(PathBuf),
+    #[error(transparent)]
+    Rustls(#[from] rustls::Error),
+    #[error(transparent)]
+    Verifier(#[from] VerifierBuilderError),
+}
+
+/// Reads every certificate in the PEM file at `path`.
+pub fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let pem_error = |source| TlsError::Pem {
+        path: path.to_path_buf(),
+        source,
+    };
+    let certs = CertificateDer::pem_file_iter(path)
+        .map_err(pem_error)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(pem_error)?;
+
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificates(path.to_path_buf()));
+    }
+    Ok(certs)
+}
+
+/// Reads the first private key in the PEM file at `path`.
+pub fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>, TlsError> {
+    PrivateKeyDer::from_pem_file(path).map_err(|source| TlsError::Pem {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Accepts TLS connections for a broker, presenting the certificate chain in
+/// `cert_path` with the key in `key_path`.
+pub fn acceptor(cert_path: &Path, key_path: &Path) -> Result<TlsAcceptor, TlsError> {
+    let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(load_certs(cert_path)?, load_key(key_path)?)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Like [`acceptor`], but also requires clients to present a certificate
+/// signed by one of the CAs in `client_ca_path`. See [`principal_name`] for
+/// who the client is taken to be.
+pub fn client_auth_acceptor(
+    cert_path: &Path,
+    key_path: &Path,
+    client_ca_path: &Path,
+) -> Result<TlsAcceptor, TlsError> {
+    let provider = Arc::new(ring::default_provider());
+    let verifier = WebPkiClientVerifier::builder_with_provider(
+        Arc::new(roots(client_ca_path)?),
+        provider.clone(),
+    )
+    .build()?;
+
+    let config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(load_certs(cert_path)?, load_key(key_path)?)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Makes TLS connections to brokers whose certificates are signed by one of
+/// the CAs in `ca_path`.
+pub fn connector(ca_path: &Path) -> Result<TlsConnector, TlsError> {
+    let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots(ca_path)?)
+        .with_no_client_auth();
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+/// Like [`connector`], presenting the certificate in `cert_path` to brokers
+/// that require one.
+pub fn client_auth_connector(
+    ca_path: &Path,
+    cert_path: &Path,
+    key_path: &Path,
+) -> Result<TlsConnector, TlsError> {
+    let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots(ca_path)?)
+        .with_client_auth_cert(load_certs(cert_path)?, load_key(key_path)?)?;
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+/// Name of the client a certificate was issued to: the common name of its
+/// subject, or failing that its first DNS or email subject alternative name.
+pub fn principal_name(cert: &CertificateDer) -> Option<String> {
+    let (_, cert) = X509Certificate::from_der(cert.as_ref()).ok()?;
+
+    let common_name = cert
+        .subject()
+        .iter_common_name()
+        .find_map(|name| name.as_str().ok());
+    if let Some(name) = common_name {
+        return Some(name.to_string());
+    }
+
+    let names = cert.subject_alternative_name().ok()??;
+    names
+        .value
+        .general_names
+        .iter()
+        .find_map(|name| match name {
+            GeneralName::DNSName(name) | GeneralName::RFC822Name(name) => Some(name.to_string()),
+            _ => None,
+        })
+}
+
+fn roots(ca_path: &Path) -> Result<RootCertStore, TlsError> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(ca_path)? {
+        roots.add(cert)?;
+    }
+    Ok(roots)
+}
+
+/// Connects to the broker at `addr`, checking that its certificate is valid
+/// for `server_name`.
+pub async fn connect(
+    addr: impl ToSocketAddrs,
+    server_name: &str,
+    connector: &TlsConnector,
+) -> io::Result<TlsStream<TcpStream>> {
+    let server_name = ServerName::try_from(server_name.to_string())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+    let stream = TcpStream::connect(addr).await?;
+    stream.set_nodelay(true)?;
+    connector.connect(server_name, stream).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rcgen::{CertificateParams, DistinguishedName, DnType, KeyPair, SanType};
+
+    fn certificate(subject: Option<&str>, sans: Vec<SanType>) -> CertificateDer<'static> {
+        let mut params = CertificateParams::default();
+        params.distinguished_name = DistinguishedName::new();
+        if let Some(subject) = subject {
+            params.distinguished_name.push(DnType::CommonName, subject);
+        }
+        params.subject_alt_names = sans;
+        let key = KeyPair::generate().unwrap();
+        params.self_signed(&key).unwrap().der().clone()
+    }
+
+    #[test]
+    fn test_principal_name() {
+        let dns = SanType::DnsName("client.example.com".try_into().unwrap());
+        assert_eq!(
+            principal_name(&certificate(Some("alice"), vec![dns.clone()])).as_deref(),
+            Some("alice")
+        );
+        assert_eq!(
+            principal_name(&certificate(None, vec![dns])).as_deref(),
+            Some("client.example.com")
+        );
+        assert_eq!(principal_name(&certificate(None, vec![])), None);
+        assert_eq!(principal_name(&CertificateDer::from(vec![1, 2, 3])), None);
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let generated = rcgen::generate_simple_self_signed(["localhost".to_string()]).unwrap();
+        let cert_path = dir.path().join("cert.pem");
+        let key_path = dir.path().join("key.pem");
+        std::fs::write(&cert_path, generated.cert.pem()).unwrap();
+        std::fs::write(&key_path, generated.key_pair.serialize_pem()).unwrap();
+
+        assert_eq!(load_certs(&cert_path).unwrap().len(), 1);
+        assert!(acceptor(&cert_path, &key_path).is_ok());
+        assert!(connector(&cert_path).is_ok());
+
+        assert!(matches!(
+            load_certs(&key_path),
+            Err(TlsError::NoCertificates(_))
+        ));
+        assert!(matches!(load_key(&cert_path), Err(TlsError::Pem { .. })));
+        assert!(matches!(
+            acceptor(&dir.path().join("missing.pem"), &key_path),
+            Err(TlsError::Pem { .. })
+        ));
+    }
+}
@@ -0,0 +1,6 @@
+mod config;
+pub use config::{
+    acceptor, client_auth_acceptor, client_auth_connector, connect, connector, load_certs,
+    load_key, principal_name, TlsError,
+};
+pub use tokio_rustls::{client, server, TlsAcceptor, TlsConnector};
@@ -0,0 +1,82 @@
+use std::collections::HashSet;
+
+use bytes::Bytes;
+
+use super::Transform;
+use crate::record::{Header, Record};
+
+/// Replaces the values of the named headers, e.g. to keep PII off disk.
+#[derive(Debug)]
+pub struct RedactHeaders {
+    keys: HashSet<String>,
+}
+
+impl RedactHeaders {
+    pub const REDACTED: &'static str = "[redacted]";
+
+    pub fn new(keys: impl IntoIterator<Item = String>) -> Self {
+        RedactHeaders {
+            keys: keys.into_iter().collect(),
+        }
+    }
+}
+
+impl Transform for RedactHeaders {
+    fn apply(&self, mut record: Record) -> Option<Record> {
+        for header in &mut record.headers {
+            if self.keys.contains(&header.key) {
+                header.value = Bytes::from_static(Self::REDACTED.as_bytes());
+            }
+        }
+        Some(record)
+    }
+}
+
+/// Adds a fixed header to every record.
+#[derive(Debug)]
+pub struct InsertHeader {
+    header: Header,
+}
+
+impl InsertHeader {
+    pub fn new(key: impl Into<String>, value: impl Into<Bytes>) -> Self {
+        InsertHeader {
+            header: Header::new(key, value),
+        }
+    }
+}
+
+impl Transform for InsertHeader {
+    fn apply(&self, record: Record) -> Option<Record> {
+        Some(record.with_header(self.header.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_headers() {
+        let transform = RedactHeaders::new(["email".to_string()]);
+        let record = Record::new(None, None)
+            .with_header(Header::new("email", "a@b.c"))
+            .with_header(Header::new("id", "1"));
+
+        let record = transform.apply(record).unwrap();
+        assert_eq!(
+            record.headers,
+            vec![
+                Header::new("email", RedactHeaders::REDACTED),
+                Header::new("id", "1")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_insert_header() {
+        let transform = InsertHeader::new("source", "billing");
+        let record = transform.apply(Record::new(None, None)).unwrap();
+        assert_eq!(record.headers, vec![Header::new("source", "billing")]);
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+
+use super::{InsertHeader, RedactHeaders};
+use crate::record::{Record, RecordBatch};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum TransformError {
+    #[error("Unknown transform type {0}")]
+    UnknownType(String),
+    #[error("Transform {0} has no type")]
+    MissingType(String),
+    #[error("Transform {transform} is missing config {config}")]
+    MissingConfig { transform: String, config: String },
+}
+
+/// Rewrites records passing through the broker, e.g. to redact fields or
+/// enrich headers. Returning `None` drops the record.
+pub trait Transform: Send + Sync {
+    fn apply(&self, record: Record) -> Option<Record>;
+}
+
+type Factory = Box<
+    dyn Fn(&str, &HashMap<String, String>) -> Result<Arc<dyn Transform>, TransformError>
+        + Send
+        + Sync,
+>;
+
+/// Transforms applied to one topic, on produce before records are appended
+/// and on fetch before they are returned.
+#[derive(Clone, Default)]
+pub struct TransformChain {
+    produce: Vec<Arc<dyn Transform>>,
+    fetch: Vec<Arc<dyn Transform>>,
+}
+
+impl TransformChain {
+    pub fn new(produce: Vec<Arc<dyn Transform>>, fetch: Vec<Arc<dyn Transform>>) -> Self {
+        TransformChain { produce, fetch }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.produce.is_empty() && self.fetch.is_empty()
+    }
+
+    /// Whether fetched records are transformed, so can't be sent on from
+    /// the log as they are.
+    pub fn transforms_fetches(&self) -> bool {
+        !self.fetch.is_empty()
+    }
+
+    pub fn apply_produce(&self, batch: RecordBatch) -> RecordBatch {
+        if self.produce.is_empty() {
+            return batch;
+        }
+        // Offsets are assigned after this, so drop gaps left by removed records
+        RecordBatch {
+            attributes: batch.attributes,
+            ..RecordBatch::new(apply_all(&self.produce, batch.records))
+        }
+    }
+
+    pub fn apply_fetch(&self, mut batch: RecordBatch) -> RecordBatch {
+        if !self.fetch.is_empty() {
+            batch.records = apply_all(&self.fetch, batch.records);
+        }
+        batch
+    }
+}
+
+impl std::fmt::Debug for TransformChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransformChain")
+            .field("produce", &self.produce.len())
+            .field("fetch", &self.fetch.len())
+            .finish()
+    }
+}
+
+fn apply_all(transforms: &[Arc<dyn Transform>], records: Vec<Record>) -> Vec<Record> {
+    records
+        .into_iter()
+        .filter_map(|record| {
+            transforms
+                .iter()
+                .try_fold(record, |record, transform| transform.apply(record))
+        })
+        .collect()
+}
+
+/// Builds [`TransformChain`]s from topic configs, connect style:
+///
+/// ```text
+/// produce.transforms = redact,source
+/// fetch.transforms = ...
+/// transforms.redact.type = redact-headers
+/// transforms.redact.headers = ssn,email
+/// transforms.source.type = insert-header
+/// transforms.source.key = source
+/// transforms.source.value = billing
+/// ```
+pub struct TransformRegistry {
+    factories: HashMap<String, Factory>,
+}
+
+impl TransformRegistry {
+    /// Registry knowing the built in transform types.
+    pub fn new() -> Self {
+        let mut registry = TransformRegistry {
+            factories: HashMap::new(),
+        };
+        registry.register("redact-headers", |name, configs| {
+            Ok(Arc::new(RedactHeaders::new(
+                required(name, configs, "headers")?
+                    .split(',')
+                    .map(|header| header.trim().to_string()),
+            )))
+        });
+        registry.register("insert-header", |name, configs| {
+            Ok(Arc::new(InsertHeader::new(
+                required(name, configs, "key")?,
+                required(name, configs, "value")?.to_string(),
+            )))
+        });
+        registry
+    }
+
+    /// Adds a transform type. `factory` gets the transform name and its
+    /// configs, with the `transforms.<name>.` prefix stripped.
+    pub fn register(
+        &mut self,
+        transform_type: &str,
+        factory: impl Fn(&str, &HashMap<String, String>) -> Result<Arc<dyn Transform>, TransformError>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.factories
+            .insert(transform_type.to_string(), Box::new(factory));
+    }
+
+    pub fn build(
+        &self,
+        configs: &HashMap<String, String>,
+    ) -> Result<TransformChain, TransformError> {
+        Ok(TransformChain {
+            produce: self.build_stage(configs, "produce.transforms")?,
+            fetch: self.build_stage(configs, "fetch.transforms")?,
+        })
+    }
+
+    fn build_stage(
+        &self,
+        configs: &HashMap<String, String>,
+        stage: &str,
+    ) -> Result<Vec<Arc<dyn Transform>>, TransformError> {
+        let Some(names) = configs.get(stage) else {
+            return Ok(vec![]);
+        };
+
+        names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let prefix = format!("transforms.{}.", name);
+                let transform_configs: HashMap<String, String> = configs
+                    .iter()
+                    .filter_map(|(key, value)| {
+                        key.strip_prefix(&prefix)
+                            .map(|key| (key.to_string(), value.clone()))
+                    })
+                    .collect();
+
+                let transform_type = transform_configs
+                    .get("type")
+                    .ok_or_else(|| TransformError::MissingType(name.to_string()))?;
+                let factory = self
+                    .factories
+                    .get(transform_type)
+                    .ok_or_else(|| TransformError::UnknownType(transform_type.clone()))?;
+                factory(name, &transform_configs)
+            })
+            .collect()
+    }
+}
+
+impl Default for TransformRegistry {
+    fn default() -> Self {
+        TransformRegistry::new()
+    }
+}
+
+fn required<'a>(
+    name: &str,
+    configs: &'a HashMap<String, String>,
+    config: &str,
+) -> Result<&'a str, TransformError> {
+    configs
+        .get(config)
+        .map(String::as_str)
+        .ok_or_else(|| TransformError::MissingConfig {
+            transform: name.to_string(),
+            config: config.to_string(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bytes::Bytes;
+
+    use crate::broker::{LogHandler, Server};
+    use crate::client::Client;
+    use crate::protocol::TopicPartition;
+    use crate::record::Header;
+    use crate::request::{Fetch, Produce};
+    use crate::storage::{LogConfig, LogDirs};
+
+    struct DropEmpty;
+
+    impl Transform for DropEmpty {
+        fn apply(&self, record: Record) -> Option<Record> {
+            record.value.as_ref().filter(|value| !value.is_empty())?;
+            Some(record)
+        }
+    }
+
+    fn configs(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn record(value: &'static str) -> Record {
+        Record::new(None, Some(Bytes::from_static(value.as_bytes())))
+            .with_header(Header::new("ssn", "123-45-6789"))
+    }
+
+    #[test]
+    fn test_build_from_configs() {
+        let chain = TransformRegistry::new()
+            .build(&configs(&[
+                ("produce.transforms", "redact, source"),
+                ("transforms.redact.type", "redact-headers"),
+                ("transforms.redact.headers", "ssn"),
+                ("transforms.source.type", "insert-header"),
+                ("transforms.source.key", "source"),
+                ("transforms.source.value", "billing"),
+            ]))
+            .unwrap();
+
+        let batch = chain.apply_produce(RecordBatch::new(vec![record("a")]));
+        assert_eq!(
+            batch.records[0].headers,
+            vec![
+                Header::new("ssn", RedactHeaders::REDACTED),
+                Header::new("source", "billing"),
+            ]
+        );
+
+        // Fetch path is left alone
+        let batch = chain.apply_fetch(RecordBatch::new(vec![record("a")]));
+        assert_eq!(
+            batch.records[0],
+            record("a").with_timestamp(batch.records[0].timestamp)
+        );
+    }
+
+    #[test]
+    fn test_no_transforms() {
+        let chain = TransformRegistry::new().build(&HashMap::new()).unwrap();
+        assert!(chain.is_empty());
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let registry = TransformRegistry::new();
+
+        assert_eq!(
+            registry
+                .build(&configs(&[("fetch.transforms", "x")]))
+                .unwrap_err(),
+            TransformError::MissingType("x".to_string())
+        );
+        assert_eq!(
+            registry
+                .build(&configs(&[
+                    ("fetch.transforms", "x"),
+                    ("transforms.x.type", "wasm")
+                ]))
+                .unwrap_err(),
+            TransformError::UnknownType("wasm".to_string())
+        );
+        assert_eq!(
+            registry
+                .build(&configs(&[
+                    ("fetch.transforms", "x"),
+                    ("transforms.x.type", "insert-header"),
+                    ("transforms.x.key", "k"),
+                ]))
+                .unwrap_err(),
+            TransformError::MissingConfig {
+                transform: "x".to_string(),
+                config: "value".to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_broker_transforms() {
+        let chain = TransformRegistry::new()
+            .build(&configs(&[
+                ("produce.transforms", "redact"),
+                ("fetch.transforms", "source"),
+                ("transforms.redact.type", "redact-headers"),
+                ("transforms.redact.headers", "ssn"),
+                ("transforms.source.type", "insert-header"),
+                ("transforms.source.key", "source"),
+                ("transforms.source.value", "billing"),
+            ]))
+            .unwrap();
+        let handler = LogHandler::new(Arc::new(LogDirs::in_memory(LogConfig::default())))
+            .with_transforms(HashMap::from([("orders".to_string(), chain)]));
+        let partition = TopicPartition::new("orders", 0);
+        handler.logs().create(&partition).unwrap();
+        let server = Server::bind("127.0.0.1:0", handler.clone()).await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        handler.replicas().set_advertised_listener(addr.clone());
+        tokio::spawn(server.run());
+        let client = Client::new(vec![addr]);
+
+        let produce = Produce::new("orders".into(), 0, RecordBatch::new(vec![record("a")]));
+        client.produce(produce.unwrap()).await.unwrap();
+
+        // Redacted before it is appended
+        let log = handler.logs().get(&partition).unwrap();
+        let stored = log.read().unwrap().read(0, usize::MAX).unwrap();
+        let redacted = Header::new("ssn", RedactHeaders::REDACTED);
+        assert_eq!(stored[0].records[0].headers, vec![redacted.clone()]);
+
+        // And enriched on the way out
+        let fetch = Fetch::new("orders".into(), 0, 0, u32::MAX).unwrap();
+        let fetched = client.fetch(fetch).await.unwrap();
+        assert_eq!(
+            fetched.batches[0].records[0].headers,
+            vec![redacted, Header::new("source", "billing")]
+        );
+    }
+
+    #[test]
+    fn test_custom_transform_drops_records() {
+        let mut registry = TransformRegistry::new();
+        registry.register("drop-empty", |_, _| Ok(Arc::new(DropEmpty)));
+
+        let chain = registry
+            .build(&configs(&[
+                ("produce.transforms", "d"),
+                ("fetch.transforms", "d"),
+                ("transforms.d.type", "drop-empty"),
+            ]))
+            .unwrap();
+
+        // Produce renumbers what's left, keeping the batch attributes
+        let batch = RecordBatch {
+            attributes: RecordBatch::EXPIRING,
+            ..RecordBatch::new(vec![record(""), record("b")])
+        };
+        let batch = chain.apply_produce(batch);
+        assert_eq!(batch.records.len(), 1);
+        assert_eq!(batch.records[0].offset, 0);
+        assert!(batch.is_expiring());
+        assert!(chain.transforms_fetches());
+
+        // Fetch keeps the assigned offsets
+        let mut batch = RecordBatch::new(vec![record(""), record("b")]);
+        batch.set_base_offset(10);
+        let batch = chain.apply_fetch(batch);
+        assert_eq!(batch.records.len(), 1);
+        assert_eq!(batch.records[0].offset, 11);
+    }
+}
@@ -0,0 +1,4 @@
+mod builtin;
+mod chain;
+pub use builtin::{InsertHeader, RedactHeaders};
+pub use chain::{Transform, TransformChain, TransformError, TransformRegistry};